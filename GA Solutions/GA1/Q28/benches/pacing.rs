@@ -0,0 +1,74 @@
+//! Inter-chunk jitter with many concurrent streams on one worker, comparing
+//! per-stream sleeps with the shared pacing wheel.
+//!
+//!     cargo bench --bench pacing
+//!     PACING_STREAMS=10000 cargo bench --bench pacing
+//!
+//! Jitter is how late each chunk is relative to its intended gap.
+
+use std::time::Duration;
+
+use streaming_llm_api::config::{PacingMode, StreamConfig};
+use streaming_llm_api::pacer;
+use tokio::time::Instant;
+
+const DELAY: Duration = Duration::from_millis(50);
+const CHUNKS: usize = 20;
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+fn run(mode: PacingMode, streams: usize) -> Vec<Duration> {
+    let config = StreamConfig {
+        pacing: mode,
+        ..StreamConfig::default()
+    };
+    actix_rt::System::new().block_on(async move {
+        let tasks: Vec<_> = (0..streams)
+            .map(|i| {
+                let config = config.clone();
+                // Spread arrivals over one delay period, like independent clients.
+                let arrival = Duration::from_micros((i as u64 * 7919) % DELAY.as_micros() as u64);
+                actix_rt::spawn(async move {
+                    tokio::time::sleep(arrival).await;
+                    let mut lateness = Vec::with_capacity(CHUNKS);
+                    for _ in 0..CHUNKS {
+                        let asked = Instant::now();
+                        pacer::sleep(DELAY, &config).await;
+                        lateness.push(asked.elapsed().saturating_sub(DELAY));
+                        // Stand-in for rendering and writing the chunk.
+                        std::hint::black_box((0..200).sum::<u32>());
+                    }
+                    lateness
+                })
+            })
+            .collect();
+        let mut all = Vec::with_capacity(streams * CHUNKS);
+        for task in tasks {
+            all.extend(task.await.unwrap());
+        }
+        all.sort();
+        all
+    })
+}
+
+fn main() {
+    // `cargo test --benches` passes `--bench`-less args; only run for real under `cargo bench`.
+    if !std::env::args().any(|a| a == "--bench") {
+        return;
+    }
+    let streams = std::env::var("PACING_STREAMS").ok().and_then(|v| v.parse().ok()).unwrap_or(5_000);
+    println!("{} streams x {} chunks, {:?} apart", streams, CHUNKS, DELAY);
+    println!("{:<10} {:>10} {:>10} {:>10}", "pacing", "p50", "p99", "max");
+    for (name, mode) in [("sleep", PacingMode::Sleep), ("scheduler", PacingMode::Scheduler)] {
+        let lateness = run(mode, streams);
+        println!(
+            "{:<10} {:>10.2?} {:>10.2?} {:>10.2?}",
+            name,
+            percentile(&lateness, 0.50),
+            percentile(&lateness, 0.99),
+            lateness.last().unwrap()
+        );
+    }
+}
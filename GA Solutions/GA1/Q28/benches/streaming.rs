@@ -0,0 +1,123 @@
+use actix_web::{test, web, App};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use streaming_llm_api::chat::{Choice, Delta, StreamChunk, EXTENDED_CONTENT};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::state::AppState;
+use streaming_llm_api::generator::CyclingText;
+use streaming_llm_api::sse::{self, FrameTemplate};
+use streaming_llm_api::stubs::{Matcher, ResponseProfile, StubRule};
+
+const HEADER: &str = "Regarding your prompt 'benchmark':\n\n";
+
+/// The original strategy: materialize the reply, collect it into chars, then split.
+fn split_collected(content: &str, chunks: usize) -> Vec<String> {
+    content
+        .chars()
+        .collect::<Vec<_>>()
+        .chunks(content.len() / chunks + 1)
+        .map(|c| c.iter().collect::<String>())
+        .collect()
+}
+
+fn chunking(c: &mut Criterion) {
+    let total_chars = HEADER.len() + EXTENDED_CONTENT.len();
+    let content = format!("{}{}", HEADER, EXTENDED_CONTENT);
+
+    let mut group = c.benchmark_group("chunking");
+    group.bench_function("collect_and_split/15", |b| {
+        b.iter(|| split_collected(black_box(&content), 15))
+    });
+    group.bench_function("cycling_text/15", |b| {
+        b.iter(|| {
+            CyclingText::new(HEADER.to_string(), EXTENDED_CONTENT, total_chars, total_chars / 15 + 1)
+                .for_each(|chunk| {
+                    black_box(chunk);
+                })
+        })
+    });
+    group.bench_function("cycling_text/1_char", |b| {
+        b.iter(|| {
+            CyclingText::new(HEADER.to_string(), EXTENDED_CONTENT, total_chars, 1).for_each(|chunk| {
+                black_box(chunk);
+            })
+        })
+    });
+    group.finish();
+}
+
+fn chunk_for(content: &str) -> StreamChunk {
+    StreamChunk {
+        choices: vec![Choice {
+            delta: Delta {
+                content: Some(content.to_string()),
+                ..Delta::default()
+            },
+            finish_reason: None,
+        }],
+        x_mock: None,
+        usage: None,
+        service_tier: None,
+    }
+}
+
+fn serialization(c: &mut Criterion) {
+    let chunk = chunk_for(&EXTENDED_CONTENT[..133]);
+
+    let mut group = c.benchmark_group("serialization");
+    group.bench_function("to_string_and_format", |b| {
+        b.iter(|| format!("data: {}\n\n", serde_json::to_string(black_box(&chunk)).unwrap()))
+    });
+    group.bench_function("pooled_data_event", |b| b.iter(|| sse::data_event(black_box(&chunk)).unwrap()));
+
+    let text = &EXTENDED_CONTENT[..133];
+    let template = FrameTemplate::new(&chunk_for("__CONTENT__"), "__CONTENT__").unwrap();
+    group.bench_function("frame_template", |b| b.iter(|| template.render(black_box(text))));
+
+    group.bench_function("serde_json_to_vec", |b| b.iter(|| serde_json::to_vec(black_box(&chunk)).unwrap()));
+    #[cfg(feature = "simd-json")]
+    group.bench_function("sonic_rs_to_vec", |b| b.iter(|| sonic_rs::to_vec(black_box(&chunk)).unwrap()));
+    group.finish();
+}
+
+fn request_handling(c: &mut Criterion) {
+    let config = Config {
+        stubs: vec![StubRule {
+            name: None,
+            matcher: Matcher::default(),
+            profile: ResponseProfile {
+                chunk_delay_ms: 0,
+                ..ResponseProfile::default()
+            },
+        }],
+        ..Config::default()
+    };
+    let system = actix_rt::System::new();
+    let app = system.block_on(test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(&config)))
+            .app_data(web::Data::new(config))
+            .configure(streaming_llm_api::configure),
+    ));
+
+    c.bench_function("request/chat_completions_unpaced", |b| {
+        b.iter_batched(
+            || {
+                test::TestRequest::post()
+                    .uri("/v1/chat/completions")
+                    .set_json(serde_json::json!({"prompt": "benchmark", "stream": true}))
+                    .to_request()
+            },
+            |req| {
+                system.block_on(async {
+                    let resp = test::call_service(&app, req).await;
+                    black_box(test::read_body(resp).await)
+                })
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, chunking, serialization, request_handling);
+criterion_main!(benches);
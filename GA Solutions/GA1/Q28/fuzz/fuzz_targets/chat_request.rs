@@ -0,0 +1,81 @@
+//! Feeds arbitrary bodies and reconnection headers through the chat endpoint
+//! in-process. Any response is fine; a panic is not.
+#![no_main]
+
+use std::cell::RefCell;
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{test, web, App};
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use streaming_llm_api::config::Config;
+use streaming_llm_api::state::AppState;
+use streaming_llm_api::stubs::{Matcher, ResponseProfile, StubRule};
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    body: &'a [u8],
+    last_event_id: Option<&'a str>,
+    content_type_json: bool,
+    event_ids: bool,
+    legacy_sse_errors: bool,
+    error_after: Option<u8>,
+}
+
+thread_local! {
+    static SYSTEM: RefCell<actix_rt::SystemRunner> = RefCell::new(actix_rt::System::new());
+}
+
+fn config(input: &Input) -> Config {
+    let mut config = Config {
+        stubs: vec![StubRule {
+            name: Some("fuzz".to_string()),
+            matcher: Matcher::default(),
+            profile: ResponseProfile {
+                chunk_delay_ms: 0,
+                // Bounded so each run stays fast.
+                tokens: Some(64),
+                error_after: input.error_after.map(usize::from),
+                ..ResponseProfile::default()
+            },
+        }],
+        ..Config::default()
+    };
+    config.stream.event_ids = input.event_ids;
+    config.compat.legacy_sse_errors = input.legacy_sse_errors;
+    config
+}
+
+fuzz_target!(|input: Input| {
+    SYSTEM.with(|system| {
+        system.borrow().block_on(async {
+            let config = config(&input);
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState::new(&config)))
+                    .app_data(web::Data::new(config))
+                    .configure(streaming_llm_api::configure),
+            )
+            .await;
+
+            let mut req = test::TestRequest::post()
+                .uri("/v1/chat/completions")
+                .set_payload(input.body.to_vec());
+            if input.content_type_json {
+                req = req.insert_header(("Content-Type", "application/json"));
+            }
+            if let Some(id) = input.last_event_id {
+                if let Ok(value) = actix_web::http::header::HeaderValue::from_str(id) {
+                    req = req.insert_header(("Last-Event-ID", value));
+                }
+            }
+
+            let resp: ServiceResponse = match app.call(req.to_request()).await {
+                Ok(resp) => resp,
+                Err(_) => return,
+            };
+            let _ = test::read_body(resp).await;
+        })
+    })
+});
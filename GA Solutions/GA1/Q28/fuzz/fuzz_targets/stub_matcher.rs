@@ -0,0 +1,42 @@
+//! Builds stub rules from arbitrary strings, both directly and through the
+//! TOML config loader, and matches arbitrary prompts against them.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use streaming_llm_api::config::Config;
+use streaming_llm_api::extensions::Extensions;
+use streaming_llm_api::stubs::{self, Matcher, Pattern, StubRule};
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    contains: Option<&'a str>,
+    starts_with: Option<&'a str>,
+    ends_with: Option<&'a str>,
+    equals: Option<&'a str>,
+    regex: Option<&'a str>,
+    prompt: &'a str,
+    toml: &'a str,
+}
+
+fuzz_target!(|input: Input| {
+    let matcher = Matcher {
+        contains: input.contains.map(str::to_string),
+        starts_with: input.starts_with.map(str::to_string),
+        ends_with: input.ends_with.map(str::to_string),
+        equals: input.equals.map(str::to_string),
+        regex: input.regex.and_then(|r| Pattern::try_from(r.to_string()).ok()),
+        extensions: Extensions::default(),
+    };
+    let rules = [StubRule {
+        name: None,
+        matcher,
+        profile: Default::default(),
+    }];
+    let _ = stubs::resolve(&rules, input.prompt, &Extensions::default());
+
+    if let Ok(config) = toml::from_str::<Config>(input.toml) {
+        let _ = stubs::resolve(&config.stubs, input.prompt, &Extensions::default());
+    }
+});
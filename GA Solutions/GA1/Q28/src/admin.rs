@@ -0,0 +1,24 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse};
+
+use crate::config::Config;
+use crate::error;
+use crate::keys;
+
+/// Checks the request's `Authorization: Bearer` token against `admin.token`,
+/// returning the rejection to send when it does not match. Without a
+/// configured token the admin surface is disabled entirely.
+pub fn reject_unauthorized(req: &HttpRequest, config: &Config) -> Option<HttpResponse> {
+    let Some(expected) = config.admin.token.as_deref() else {
+        return Some(error::json_error(
+            StatusCode::FORBIDDEN,
+            "Admin API is disabled; set admin.token to enable it",
+            None,
+        ));
+    };
+    if keys::bearer_token(req) == Some(expected) {
+        None
+    } else {
+        Some(error::json_error(StatusCode::UNAUTHORIZED, "Invalid admin token", None))
+    }
+}
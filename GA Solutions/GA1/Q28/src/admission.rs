@@ -0,0 +1,301 @@
+//! What every chat-shaped endpoint does around generating a reply: checking
+//! the caller, recording the capture, resolving the profile, and charging
+//! the rate limits, quota, admission queue and throughput budget. Chat
+//! completions, the Responses API, Cohere and Gemini all go through here, so
+//! a limit configured for one holds for all of them.
+
+use std::iter::Peekable;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponseBuilder};
+
+use crate::capture::{Capture, StreamRecorder};
+use crate::chat::{self, NormalizedRequest, CHARS_PER_TOKEN};
+use crate::config::{Config, PiiPolicy, StreamConfig, TierSettings};
+use crate::error::MockError;
+use crate::keys::{self, Tier};
+use crate::lifecycle::Tracker;
+use crate::metrics;
+use crate::orgs::{self, OrgScope};
+use crate::overrides::MockOverrides;
+use crate::pacer::{self, Pace};
+use crate::panics;
+use crate::pii;
+use crate::quota::{self, QuotaStatus};
+use crate::ratelimit::RateLimitStatus;
+use crate::retry_budget::RetryBudgetStatus;
+use crate::state::AppState;
+use crate::stubs::ResponseProfile;
+use crate::throughput::Lease;
+use crate::tokenizer::TokenEncoding;
+
+/// Turns requests away during a rate-limit storm, then checks the
+/// organization headers and the key's model and capability allowlists.
+pub fn check_caller(config: &Config, state: &AppState, http_req: &HttpRequest, req: &NormalizedRequest) -> Result<OrgScope, MockError> {
+    // Like a real provider, a rate-limit storm turns requests away before looking at them.
+    state.storm.check()?;
+    let scope = orgs::resolve(config, http_req)?;
+    keys::authorize(&config.keys, http_req, req)?;
+    Ok(scope)
+}
+
+/// Where a request was recorded.
+pub struct Recorded {
+    pub capture_id: u64,
+    /// `req_` and the capture id.
+    pub request_id: String,
+}
+
+/// Applies the PII policy and records the capture. A request the policy
+/// rejects is still recorded, then fails with `pii_detected` naming `param`.
+pub fn record(config: &Config, state: &AppState, http_req: &HttpRequest, req: &mut NormalizedRequest, param: &str) -> Result<Recorded, MockError> {
+    let findings = pii::apply(&config.pii, req);
+    let rejected = config.pii.policy == PiiPolicy::Reject && !findings.is_empty();
+    let capture_id = state.captures.record(pii::scrub(&config.pii, Capture::new(req, findings, rejected)));
+    let request_id = format!("req_{}", capture_id);
+    panics::label(http_req, &request_id);
+    if rejected {
+        metrics::PII_REJECTIONS.inc();
+        return Err(MockError::rejected(
+            StatusCode::BAD_REQUEST,
+            "The request contains personal data and was rejected by the privacy filter.",
+            Some(param),
+        )
+        .with_code("pii_detected"));
+    }
+    Ok(Recorded { capture_id, request_id })
+}
+
+/// `chat::resolve_profile` with the request's service tier applied. The
+/// experiment variant, if any, is recorded on the capture.
+pub fn profile(
+    config: &Config,
+    state: &AppState,
+    req: &NormalizedRequest,
+    overrides: &MockOverrides,
+    capture_id: u64,
+) -> (ResponseProfile, Option<String>, Option<String>) {
+    let (mut profile, rule_name, variant) = chat::resolve_profile(config, req, overrides);
+    if let Some(variant) = &variant {
+        state.captures.label_variant(capture_id, variant);
+    }
+    if let Some(tier) = req.service_tier {
+        config.service_tiers.get(tier).apply(&mut profile);
+    }
+    (profile, rule_name, variant)
+}
+
+/// Charges a fault the profile injects to the caller's retry budget, kept
+/// for its organization and project or listed key. Out of budget, the fault
+/// is dropped from the profile.
+pub fn retry_budget(
+    config: &Config,
+    state: &AppState,
+    http_req: &HttpRequest,
+    scope: &OrgScope,
+    profile: &mut ResponseProfile,
+) -> Option<RetryBudgetStatus> {
+    let key = keys::key_for(&config.keys, http_req).map_or("", |key| key.key.as_str());
+    let budget = state.retry_budgets.admit(
+        &scope.account(key),
+        state.retry_budgets.is_retry(http_req),
+        profile.error_after.is_some(),
+    );
+    if budget.is_some_and(|budget| !budget.fault_allowed) {
+        profile.error_after = None;
+    }
+    budget
+}
+
+/// The tokens a request is charged, as OpenAI counts them: the prompt plus
+/// the completion reserved, here the reply itself when the client reserves
+/// nothing. Only counted when a limit asks.
+pub fn reservation<'a>(req: &'a NormalizedRequest, encoding: TokenEncoding, reply_chars: usize) -> impl Fn() -> u64 + 'a {
+    move || {
+        let completion = req.max_tokens.unwrap_or_else(|| reply_chars.div_ceil(CHARS_PER_TOKEN));
+        encoding.count_messages(&req.messages).saturating_add(completion) as u64
+    }
+}
+
+/// A request's share of the server's limits, charged once its reply is known.
+pub struct Admission<'a> {
+    /// The tier of the key, if it is a listed one.
+    pub tier: Option<Tier>,
+    tier_settings: Option<&'a TierSettings>,
+    scope: OrgScope,
+    rate_limits: RateLimitStatus,
+    quota: Option<QuotaStatus>,
+    /// Time spent in the admission queue, when there is one.
+    pub queue_ms: Option<u64>,
+}
+
+impl<'a> Admission<'a> {
+    /// Charges `tokens` to the caller's rate limits and its key's quota, then
+    /// waits for the request's turn in the admission queue.
+    pub async fn charge(
+        config: &'a Config,
+        state: &AppState,
+        http_req: &HttpRequest,
+        scope: &OrgScope,
+        request_id: &str,
+        model: Option<&str>,
+        tokens: impl Fn() -> u64,
+    ) -> Result<Admission<'a>, MockError> {
+        let rate_limits = state.rate_limits.charge(&scope.account(keys::bearer_token(http_req).unwrap_or_default()), &tokens)?;
+        let key = keys::key_for(&config.keys, http_req);
+        let quota = key.and_then(|key| state.quotas.charge(key, &tokens));
+        if let (Some(key), Some(status)) = (key, &quota) {
+            quota::notify(state, key, status, request_id, model, scope);
+        }
+        let tier = key.map(|key| key.tier);
+        let tier_settings = tier.map(|tier| config.tiers.get(tier));
+        let queue_ms = match &state.queue {
+            Some(queue) => Some(queue.admit(tier_settings.map_or(0, |t| t.priority)).await?.as_millis() as u64),
+            None => None,
+        };
+        Ok(Admission {
+            tier,
+            tier_settings,
+            scope: scope.clone(),
+            rate_limits,
+            quota,
+            queue_ms,
+        })
+    }
+
+    /// A share of the throughput budget for a reply of `weight`, scaled and
+    /// capped by the key's tier. Held for as long as the reply is sent.
+    pub fn lease(&self, state: &AppState, model: Option<&str>, weight: u32) -> Lease {
+        state.throughput.lease(
+            model,
+            weight.saturating_mul(self.tier_settings.map_or(1, |t| t.weight)),
+            self.tier_settings.and_then(|t| t.tokens_per_sec),
+        )
+    }
+
+    /// The tier, organization, project, rate-limit and quota headers.
+    pub fn insert_headers(&self, response: &mut HttpResponseBuilder) {
+        if let Some(tier) = self.tier {
+            response.insert_header(("X-Mock-Tier", tier.name()));
+        }
+        if let Some(org) = &self.scope.organization {
+            response.insert_header(("openai-organization", org.as_str()));
+        }
+        if let Some(project) = &self.scope.project {
+            response.insert_header(("openai-project", project.as_str()));
+        }
+        self.rate_limits.insert_headers(response);
+        if let Some(quota) = &self.quota {
+            quota.insert_headers(response);
+        }
+    }
+}
+
+/// What `Paced::next` has for the endpoint.
+pub enum Step {
+    Chunk(String),
+    /// The profile's injected failure; the tracker and recorder have been told.
+    Failed,
+    /// The reply is complete; the tracker and recorder have been told.
+    Done,
+}
+
+/// A reply's chunks as the provider endpoints send them: paced, within the
+/// throughput lease, counted by the tracker and the recorder, and cut short
+/// where the profile injects a failure.
+pub struct Paced {
+    chunks: Peekable<Box<dyn Iterator<Item = String>>>,
+    sent: usize,
+    pace: Pace,
+    error_after: Option<usize>,
+    error_message: String,
+    stream: StreamConfig,
+    lease: Lease,
+    tracker: Option<Tracker>,
+    recorder: Option<StreamRecorder>,
+    ended: bool,
+}
+
+impl Paced {
+    pub fn new(
+        config: &Config,
+        profile: &ResponseProfile,
+        encoding: TokenEncoding,
+        chunks: Box<dyn Iterator<Item = String>>,
+        lease: Lease,
+        tracker: Option<Tracker>,
+        recorder: Option<StreamRecorder>,
+    ) -> Paced {
+        Paced {
+            chunks: chunks.peekable(),
+            sent: 0,
+            pace: Pace::new(profile, encoding),
+            error_after: profile.error_after,
+            error_message: profile.error_message.clone(),
+            stream: config.stream.clone(),
+            lease,
+            tracker,
+            recorder,
+            ended: false,
+        }
+    }
+
+    /// The next chunk, once it is due.
+    pub async fn next(&mut self) -> Step {
+        if self.ended {
+            return Step::Done;
+        }
+        if self.error_after == Some(self.sent) {
+            self.end(false);
+            return Step::Failed;
+        }
+        let Some(chunk) = self.chunks.next() else {
+            self.end(true);
+            return Step::Done;
+        };
+        let delay = self.pace.before(&chunk, self.sent == 0);
+        // Counting tokens can mean loading the tokenizer, so uncapped replies skip it.
+        let capped = if self.lease.is_capped() { self.lease.delay_for(self.pace.tokens(&chunk)) } else { Duration::ZERO };
+        let delay = delay.max(capped);
+        if !delay.is_zero() {
+            pacer::sleep(delay, &self.stream).await;
+        }
+        if let Some(tracker) = self.tracker.as_ref() {
+            tracker.chunk(&chunk);
+        }
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.chunk(&chunk);
+        }
+        self.sent += 1;
+        Step::Chunk(chunk)
+    }
+
+    /// Chunks sent so far.
+    pub fn sent(&self) -> usize {
+        self.sent
+    }
+
+    /// Whether the reply has no more chunks to send, so the next step is `Done`.
+    pub fn is_last(&mut self) -> bool {
+        self.error_after != Some(self.sent) && self.chunks.peek().is_none()
+    }
+
+    /// The message of the profile's injected failure.
+    pub fn error_message(&self) -> &str {
+        &self.error_message
+    }
+
+    fn end(&mut self, completed: bool) {
+        self.ended = true;
+        if let Some(tracker) = self.tracker.as_ref() {
+            match completed {
+                true => tracker.completed(),
+                false => tracker.failed(&self.error_message),
+            }
+        }
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.finish(completed);
+        }
+    }
+}
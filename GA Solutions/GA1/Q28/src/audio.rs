@@ -0,0 +1,205 @@
+//! Spoken replies (`modalities: ["text", "audio"]` with an `audio`
+//! parameter), for validating multimodal client plumbing offline.
+//!
+//! The speech is a sine tone whose pitch depends on the voice, sounding for
+//! `MS_PER_CHAR` per character of the transcript and silent on whitespace,
+//! as 24 kHz mono 16-bit PCM. A stream sends each delta's text as
+//! `delta.audio.transcript` with the base64 audio for it in
+//! `delta.audio.data`, and closes with the audio's `expires_at`; as with
+//! OpenAI, streams only come in `pcm16`. A JSON reply carries the whole
+//! audio in `message.audio`, as `pcm16` or a `wav` file. A reply's audio
+//! stops after `MAX_AUDIO_SECS`; the transcript carries on without it.
+
+use actix_web::http::StatusCode;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+use std::num::TryFromIntError;
+
+use crate::error::MockError;
+use crate::generator;
+
+pub const SAMPLE_RATE: u32 = 24_000;
+/// Far quicker than speech, to keep the audio of long replies small.
+pub const MS_PER_CHAR: u32 = 10;
+const SAMPLES_PER_CHAR: usize = (SAMPLE_RATE * MS_PER_CHAR / 1000) as usize;
+/// The most audio one reply gets, so a long reply cannot take unbounded
+/// memory to speak.
+pub const MAX_AUDIO_SECS: u32 = 300;
+/// A quarter of full scale.
+const AMPLITUDE: f64 = 8192.0;
+/// Audio can be referred back to for this long, as with OpenAI.
+const EXPIRES_AFTER_SECS: u64 = 3600;
+
+/// The `audio` request parameter.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AudioParams {
+    pub voice: String,
+    pub format: AudioFormat,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    Wav,
+    Pcm16,
+    Mp3,
+    Flac,
+    Opus,
+    Aac,
+}
+
+impl AudioFormat {
+    fn name(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Pcm16 => "pcm16",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Aac => "aac",
+        }
+    }
+}
+
+/// The audio a request asks for, when its `modalities` include audio.
+pub fn requested<'a>(modalities: &[String], audio: Option<&'a AudioParams>, streaming: bool) -> Result<Option<&'a AudioParams>, MockError> {
+    if !modalities.iter().any(|m| m == "audio") {
+        return Ok(None);
+    }
+    let invalid = |message: String, param: &str| Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some(param)));
+    let Some(audio) = audio else {
+        return invalid("Missing required parameter: 'audio'.".to_string(), "audio");
+    };
+    match audio.format {
+        AudioFormat::Pcm16 => Ok(Some(audio)),
+        AudioFormat::Wav if !streaming => Ok(Some(audio)),
+        AudioFormat::Wav => invalid("Audio output with stream=true only supports the 'pcm16' format.".to_string(), "audio.format"),
+        format => invalid(
+            format!("The mock only generates 'wav' and 'pcm16' audio, not '{}'.", format.name()),
+            "audio.format",
+        ),
+    }
+}
+
+/// The id and expiry of one reply's audio.
+pub fn id_for(request_id: &str, now_secs: u64) -> (String, u64) {
+    (format!("audio_{}", request_id), now_secs + EXPIRES_AFTER_SECS)
+}
+
+/// The tone a voice speaks in, carried across the chunks of a reply so
+/// the wave stays continuous.
+pub struct Tone {
+    /// Phase advance per sample.
+    step: f64,
+    phase: f64,
+    /// Samples left before `MAX_AUDIO_SECS` is reached.
+    left: usize,
+}
+
+impl Tone {
+    /// Each voice gets its own pitch, between 180 and 420 Hz.
+    pub fn new(voice: &str) -> Tone {
+        let hz = 180.0 + (generator::stable_hash([voice]) % 240) as f64;
+        Tone {
+            step: TAU * hz / SAMPLE_RATE as f64,
+            phase: 0.0,
+            left: (MAX_AUDIO_SECS * SAMPLE_RATE) as usize,
+        }
+    }
+
+    /// Little-endian 16-bit samples speaking `text`, as much of it as fits
+    /// in what is left of `MAX_AUDIO_SECS`.
+    pub fn speak(&mut self, text: &str) -> Vec<u8> {
+        let spoken = text.chars().count().min(self.left / SAMPLES_PER_CHAR);
+        self.left -= spoken * SAMPLES_PER_CHAR;
+        let mut pcm = Vec::with_capacity(spoken * SAMPLES_PER_CHAR * 2);
+        for c in text.chars().take(spoken) {
+            for _ in 0..SAMPLES_PER_CHAR {
+                let sample = if c.is_whitespace() {
+                    0
+                } else {
+                    self.phase = (self.phase + self.step) % TAU;
+                    (self.phase.sin() * AMPLITUDE) as i16
+                };
+                pcm.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        pcm
+    }
+}
+
+/// `pcm` in a WAV file, or an error when there is more than its 32-bit
+/// sizes can describe.
+pub fn wav(pcm: &[u8]) -> Result<Vec<u8>, TryFromIntError> {
+    let data_len = u32::try_from(pcm.len())?;
+    let riff_len = u32::try_from(pcm.len() + 36)?;
+    let mut file = Vec::with_capacity(44 + pcm.len());
+    file.extend_from_slice(b"RIFF");
+    file.extend_from_slice(&riff_len.to_le_bytes());
+    file.extend_from_slice(b"WAVEfmt ");
+    file.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel.
+    file.extend_from_slice(&1u16.to_le_bytes());
+    file.extend_from_slice(&1u16.to_le_bytes());
+    file.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    file.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    file.extend_from_slice(&2u16.to_le_bytes());
+    file.extend_from_slice(&16u16.to_le_bytes());
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&data_len.to_le_bytes());
+    file.extend_from_slice(pcm);
+    Ok(file)
+}
+
+/// `delta.audio` of a streamed chunk.
+#[derive(Serialize, Default)]
+pub struct AudioDelta {
+    /// On the first chunk and the closing one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<String>,
+    /// On the closing chunk only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl AudioDelta {
+    /// `text` with its audio.
+    pub fn spoken(tone: &mut Tone, text: String) -> AudioDelta {
+        AudioDelta {
+            data: Some(STANDARD.encode(tone.speak(&text))),
+            transcript: Some(text),
+            ..AudioDelta::default()
+        }
+    }
+}
+
+/// `message.audio` of a JSON reply.
+#[derive(Serialize)]
+pub struct AudioMessage {
+    pub id: String,
+    pub data: String,
+    pub expires_at: u64,
+    pub transcript: String,
+}
+
+impl AudioMessage {
+    pub fn new(params: &AudioParams, id: String, expires_at: u64, transcript: String) -> AudioMessage {
+        let pcm = Tone::new(&params.voice).speak(&transcript);
+        let audio = match params.format {
+            AudioFormat::Wav => wav(&pcm).expect("audio within MAX_AUDIO_SECS fits a WAV file"),
+            _ => pcm,
+        };
+        AudioMessage {
+            id,
+            data: STANDARD.encode(audio),
+            expires_at,
+            transcript,
+        }
+    }
+}
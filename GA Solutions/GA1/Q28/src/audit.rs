@@ -0,0 +1,267 @@
+//! Tamper-evident record of admin API mutations, for shared staging servers
+//! that need to show who changed what. Each record carries the SHA-256 of the
+//! one before it, so editing, dropping or reordering records breaks the
+//! chain; with `admin.audit_secret` every hash is also HMAC-signed so the
+//! chain cannot be rebuilt without the secret. With `admin.audit_log` the
+//! records are appended to a JSON Lines file and the chain continues across
+//! restarts.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::admin;
+use crate::config::{AdminConfig, Config};
+use crate::error::MockError;
+use crate::keys;
+use crate::state::AppState;
+
+/// `prev_hash` of the first record.
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// State before and after a mutation.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Change {
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct AuditRecord {
+    /// Position in the chain, from 0.
+    pub seq: u64,
+    pub timestamp_ms: u64,
+    /// Fingerprint of the bearer token that made the change; see `actor_of`.
+    pub actor: String,
+    /// What was done, e.g. `storm.start`.
+    pub action: String,
+    pub change: Change,
+    pub prev_hash: String,
+    /// SHA-256 over `prev_hash` and the fields above.
+    pub hash: String,
+    /// Hex HMAC-SHA256 of `hash` under `admin.audit_secret`, if one is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// The hashed part of a record, in a fixed field order.
+#[derive(Serialize)]
+struct Hashed<'a> {
+    seq: u64,
+    timestamp_ms: u64,
+    actor: &'a str,
+    action: &'a str,
+    change: &'a Change,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl AuditRecord {
+    fn compute_hash(&self) -> Result<String, MockError> {
+        let hashed = Hashed {
+            seq: self.seq,
+            timestamp_ms: self.timestamp_ms,
+            actor: &self.actor,
+            action: &self.action,
+            change: &self.change,
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(serde_json::to_vec(&hashed).map_err(|e| MockError::Serialize(e.to_string()))?);
+        Ok(hex(&hasher.finalize()))
+    }
+}
+
+fn sign(secret: &str, hash: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(hash.as_bytes());
+    hex(&mac.finalize().into_bytes())
+}
+
+/// Who made a change: `sha256:` and the first 16 hex digits of the token's
+/// hash, so the log names the key without storing it.
+pub fn actor_of(req: &HttpRequest) -> String {
+    let token = keys::bearer_token(req).unwrap_or_default();
+    format!("sha256:{}", &hex(&Sha256::digest(token.as_bytes()))[..16])
+}
+
+/// Checks that `records` form an intact chain from the genesis hash, and with
+/// `secret`, that each is signed under it. Returns the `seq` of the first
+/// record that does not fit.
+pub fn verify(records: &[AuditRecord], secret: Option<&str>) -> Result<(), u64> {
+    let mut prev = GENESIS;
+    for (i, record) in records.iter().enumerate() {
+        let signed = match secret {
+            Some(secret) => record.signature.as_deref() == Some(sign(secret, &record.hash).as_str()),
+            None => true,
+        };
+        if record.seq != i as u64 || record.prev_hash != prev || !record.compute_hash().is_ok_and(|hash| hash == record.hash) || !signed {
+            return Err(record.seq);
+        }
+        prev = &record.hash;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct AuditLog {
+    secret: Option<String>,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    records: Vec<AuditRecord>,
+    /// The `seq` of the next record: the lines already in the file, including
+    /// any that could not be read.
+    next_seq: u64,
+    /// The position of the first line of the file that could not be read.
+    unreadable: Option<u64>,
+    file: Option<File>,
+}
+
+impl AuditLog {
+    /// Continues the chain in `admin.audit_log` if it exists. Lines that do
+    /// not parse are skipped with a warning and break the chain where they
+    /// are; new records are still appended. A file that cannot be opened
+    /// leaves the log in memory only, with an error logged.
+    pub fn new(config: &AdminConfig) -> AuditLog {
+        let inner = match &config.audit_log {
+            Some(path) => Inner::open(path).unwrap_or_else(|e| {
+                log::error!("cannot open audit log {}: {}; keeping it in memory only", path.display(), e);
+                Inner::default()
+            }),
+            None => Inner::default(),
+        };
+        AuditLog {
+            secret: config.audit_secret.clone(),
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Appends a record of `action` by the caller of `req`.
+    pub fn record(&self, req: &HttpRequest, action: &str, change: Change) -> Result<(), MockError> {
+        let mut inner = self.lock();
+        let mut record = AuditRecord {
+            seq: inner.next_seq,
+            timestamp_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+            actor: actor_of(req),
+            action: action.to_string(),
+            change,
+            prev_hash: inner.records.last().map_or_else(|| GENESIS.to_string(), |r| r.hash.clone()),
+            hash: String::new(),
+            signature: None,
+        };
+        record.hash = record.compute_hash()?;
+        record.signature = self.secret.as_deref().map(|secret| sign(secret, &record.hash));
+        if let Some(file) = &mut inner.file {
+            let line = serde_json::to_string(&record).map_err(|e| MockError::Serialize(e.to_string()))?;
+            if let Err(e) = writeln!(file, "{}", line) {
+                log::error!("cannot append to the audit log: {}", e);
+            }
+        }
+        inner.records.push(record);
+        inner.next_seq += 1;
+        Ok(())
+    }
+
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.lock().records.clone()
+    }
+
+    /// `verify` over the records, failing at the first unreadable line of the
+    /// file if that comes earlier.
+    pub fn verify(&self) -> Result<(), u64> {
+        let inner = self.lock();
+        let checked = verify(&inner.records, self.secret.as_deref());
+        match (inner.unreadable, checked) {
+            (Some(line), Err(seq)) => Err(line.min(seq)),
+            (Some(line), Ok(())) => Err(line),
+            (None, checked) => checked,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Inner {
+    fn open(path: &Path) -> io::Result<Inner> {
+        let mut inner = Inner::default();
+        let mut text = Vec::new();
+        if path.exists() {
+            text = std::fs::read(path)?;
+            let lines = text.split(|&b| b == b'\n').filter(|line| !line.trim_ascii().is_empty());
+            for (i, line) in lines.enumerate() {
+                match serde_json::from_slice(line) {
+                    Ok(record) => inner.records.push(record),
+                    Err(e) => {
+                        log::warn!("skipping unreadable line {} of audit log {}: {}", i + 1, path.display(), e);
+                        inner.unreadable.get_or_insert(i as u64);
+                    }
+                }
+                inner.next_seq += 1;
+            }
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        // A record cut short by a crash must not swallow the next one.
+        if text.last().is_some_and(|&b| b != b'\n') {
+            writeln!(file)?;
+        }
+        inner.file = Some(file);
+        Ok(inner)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    /// Only records with a larger `seq`.
+    pub after: Option<u64>,
+    pub action: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AuditList {
+    data: Vec<AuditRecord>,
+    /// Whether the whole chain verifies, not just the records returned.
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    broken_at: Option<u64>,
+}
+
+#[get("/v1/admin/audit")]
+pub async fn list_endpoint(
+    req: HttpRequest,
+    query: web::Query<AuditQuery>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return resp;
+    }
+    let broken_at = state.audit.verify().err();
+    let data = state
+        .audit
+        .records()
+        .into_iter()
+        .filter(|r| query.after.is_none_or(|after| r.seq > after))
+        .filter(|r| query.action.as_deref().is_none_or(|action| r.action == action))
+        .collect();
+    HttpResponse::Ok().json(AuditList {
+        data,
+        valid: broken_at.is_none(),
+        broken_at,
+    })
+}
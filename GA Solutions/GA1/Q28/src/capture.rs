@@ -0,0 +1,248 @@
+//! A bounded in-memory record of recent requests and the replies they got,
+//! readable over `/v1/internal/captures` so tests can assert on what the
+//! mock was sent, and exportable through `export`.
+
+use actix_web::{delete, get, web, HttpResponse};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chat::{Message, NormalizedRequest};
+use crate::config::CaptureConfig;
+use crate::extensions::Extensions;
+use crate::pii::Finding;
+use crate::state::AppState;
+
+#[derive(Serialize, Clone)]
+pub struct Capture {
+    pub id: u64,
+    pub received_at_ms: u64,
+    pub model: Option<String>,
+    /// The prompt with any personal data redacted, whatever the PII policy.
+    pub prompt: String,
+    /// The request messages, redacted like `prompt`.
+    pub messages: Vec<Message>,
+    /// vLLM and TGI sampling fields the request set.
+    #[serde(skip_serializing_if = "Extensions::is_empty")]
+    pub extensions: Extensions,
+    pub pii: Vec<Finding>,
+    /// Turned away by the PII filter.
+    pub rejected: bool,
+    /// The experiment variant that served the request; see `experiments`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// The streamed reply. Like `timing`, filled in once the stream ends.
+    pub reply: Option<Reply>,
+    /// When each chunk went out. Filled in once the stream ends, so `null`
+    /// for requests that are still streaming or never started.
+    pub timing: Option<Timing>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Reply {
+    /// The delta text as sent, after transforms, up to `capture.max_reply_chars`.
+    pub content: String,
+    /// Characters of `content` each chunk contributed, in order.
+    pub chunk_chars: Vec<usize>,
+    pub truncated: bool,
+    /// Whether the stream reached `[DONE]`, rather than failing or being abandoned.
+    pub finished: bool,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Timing {
+    /// Wall-clock time each content chunk was emitted, in Unix milliseconds.
+    pub chunks_at_ms: Vec<u64>,
+    /// When the stream ended, whether finished, failed or abandoned.
+    pub ended_at_ms: u64,
+}
+
+impl Reply {
+    /// `content` split back into the chunks it was sent as.
+    pub fn chunks(&self) -> Vec<&str> {
+        let mut rest = self.content.as_str();
+        self.chunk_chars
+            .iter()
+            .map(|&chars| {
+                let cut = rest.char_indices().nth(chars).map_or(rest.len(), |(i, _)| i);
+                let (chunk, tail) = rest.split_at(cut);
+                rest = tail;
+                chunk
+            })
+            .collect()
+    }
+}
+
+impl Capture {
+    pub fn new(req: &NormalizedRequest, pii: Vec<Finding>, rejected: bool) -> Capture {
+        Capture {
+            id: 0,
+            received_at_ms: now_ms(),
+            model: req.model.clone(),
+            prompt: req.prompt.clone(),
+            messages: req.messages.clone(),
+            extensions: req.extensions.clone(),
+            pii,
+            rejected,
+            variant: None,
+            reply: None,
+            timing: None,
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub struct CaptureStore {
+    limit: usize,
+    max_reply_chars: usize,
+    next_id: AtomicU64,
+    entries: Mutex<VecDeque<Capture>>,
+}
+
+impl CaptureStore {
+    pub fn new(config: &CaptureConfig) -> CaptureStore {
+        CaptureStore {
+            // Without the `recording` feature nothing is kept, as with `limit = 0`.
+            limit: if cfg!(feature = "recording") { config.limit } else { 0 },
+            max_reply_chars: config.max_reply_chars,
+            next_id: AtomicU64::new(1),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Stores `capture` under a fresh id and returns the id.
+    pub fn record(&self, mut capture: Capture) -> u64 {
+        capture.id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let id = capture.id;
+        if self.limit == 0 {
+            return id;
+        }
+        let mut entries = self.lock();
+        if entries.len() == self.limit {
+            entries.pop_front();
+        }
+        entries.push_back(capture);
+        id
+    }
+
+    /// Records which experiment variant serves capture `id`.
+    pub fn label_variant(&self, id: u64, variant: &str) {
+        if let Some(capture) = self.lock().iter_mut().rev().find(|c| c.id == id) {
+            capture.variant = Some(variant.to_string());
+        }
+    }
+
+    /// Attaches the outcome of its stream to capture `id`, unless it has
+    /// been dropped meanwhile.
+    pub fn complete(&self, id: u64, reply: Reply, timing: Timing) {
+        if let Some(capture) = self.lock().iter_mut().rev().find(|c| c.id == id) {
+            capture.reply = Some(reply);
+            capture.timing = Some(timing);
+        }
+    }
+
+    /// Every retained capture, oldest first.
+    pub fn list(&self) -> Vec<Capture> {
+        self.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Capture>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Collects a stream's reply text and chunk timestamps and stores them with
+/// its capture when the stream finishes, or when it is dropped, so failed
+/// and abandoned streams are recorded too.
+pub struct StreamRecorder {
+    state: web::Data<AppState>,
+    id: u64,
+    max_reply_chars: usize,
+    /// The reply and timing so far, with the characters kept; `None` once stored.
+    progress: RefCell<Option<(Reply, Timing, usize)>>,
+}
+
+impl StreamRecorder {
+    /// A recorder for capture `id`, or `None` when capturing is off.
+    pub fn new(state: &web::Data<AppState>, id: u64) -> Option<StreamRecorder> {
+        (state.captures.limit > 0).then(|| StreamRecorder {
+            state: state.clone(),
+            id,
+            max_reply_chars: state.captures.max_reply_chars,
+            progress: RefCell::new(Some(Default::default())),
+        })
+    }
+
+    pub fn chunk(&self, text: &str) {
+        let mut progress = self.progress.borrow_mut();
+        let Some((reply, timing, kept)) = progress.as_mut() else {
+            return;
+        };
+        timing.chunks_at_ms.push(now_ms());
+        if reply.truncated {
+            return;
+        }
+        let room = self.max_reply_chars - *kept;
+        match text.char_indices().nth(room) {
+            Some((cut, _)) => {
+                reply.content.push_str(&text[..cut]);
+                reply.chunk_chars.push(room);
+                reply.truncated = true;
+                *kept = self.max_reply_chars;
+            }
+            None => {
+                let chars = text.chars().count();
+                reply.content.push_str(text);
+                reply.chunk_chars.push(chars);
+                *kept += chars;
+            }
+        }
+    }
+
+    /// Stores the reply and timing, `finished` if the stream reached
+    /// `[DONE]`. Later calls and chunks are ignored.
+    pub fn finish(&self, finished: bool) {
+        if let Some((mut reply, mut timing, _)) = self.progress.take() {
+            reply.finished = finished;
+            timing.ended_at_ms = now_ms();
+            self.state.captures.complete(self.id, reply, timing);
+        }
+    }
+}
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        self.finish(false);
+    }
+}
+
+#[derive(Serialize)]
+struct CaptureList {
+    data: Vec<Capture>,
+}
+
+#[get("/v1/internal/captures")]
+pub async fn list_endpoint(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(CaptureList {
+        data: state.captures.list(),
+    })
+}
+
+#[delete("/v1/internal/captures")]
+pub async fn clear_endpoint(state: web::Data<AppState>) -> HttpResponse {
+    state.captures.clear();
+    HttpResponse::NoContent().finish()
+}
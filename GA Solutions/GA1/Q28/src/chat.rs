@@ -0,0 +1,1583 @@
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError, post, Error};
+use actix_web::http::header::{Accept, Header};
+use actix_web::http::StatusCode;
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use serde::de::{IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::admission::{self, Admission, Recorded};
+use crate::audio::{self, AudioDelta, AudioMessage, AudioParams, Tone};
+use crate::capture::StreamRecorder;
+use crate::chunking;
+use crate::citations::{Annotation, Citations};
+use crate::compression::{self, Encoding};
+use crate::config::{CompressionMode, Config, EchoConfig, EchoMode, UnknownFieldPolicy, WatermarkMode};
+use crate::error::{self, ErrorStyle, MockError};
+use crate::etag;
+use crate::experiments;
+use crate::extensions::Extensions;
+use crate::generator::{self, CyclingText};
+use crate::guardrail::Screen;
+use crate::image::ImageUrl;
+use crate::ingest::ChatBody;
+use crate::languages::{self, Language};
+use crate::lifecycle::{Tracker, Usage};
+use crate::metadata::ReplyDigest;
+use crate::metrics;
+use crate::models::ModelInfo;
+use crate::orgs::OrgScope;
+use crate::presets::{self, Preset};
+use crate::pricing::UsageMeter;
+use crate::overrides::MockOverrides;
+use crate::pacer::{self, Pace};
+use crate::pool;
+use crate::service_tier::ServiceTier;
+use crate::stubs::{self, ContentMode, GeneratorKind, Quirk, ResponseProfile, TimeoutMode};
+use crate::sse::{self, FrameTemplate};
+use crate::state::AppState;
+use crate::stored::{self, Metadata, StoredCompletion, StoredStream};
+use crate::transforms::Pipeline;
+use crate::watermark::Watermark;
+use crate::writer;
+
+/// The original request shape, `{"prompt": ..., "stream": true}`. Still
+/// accepted, but new clients should send `messages`.
+#[derive(Deserialize)]
+pub struct StreamRequest {
+    pub prompt: String,
+    pub stream: bool,
+}
+
+/// The standard OpenAI chat completions request.
+#[derive(Deserialize, Default)]
+pub struct ChatRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub stream: Option<bool>,
+    #[serde(default)]
+    pub stream_options: Option<StreamOptions>,
+    /// Picks the reply variant. Derived from the messages when absent.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    /// Newer name for `max_tokens`; wins when both are sent.
+    #[serde(default)]
+    pub max_completion_tokens: Option<usize>,
+    /// Only checked for presence, against the key's capabilities.
+    #[serde(default)]
+    pub tools: Vec<serde_json::Value>,
+    /// The deprecated form of `tools`.
+    #[serde(default)]
+    pub functions: Vec<serde_json::Value>,
+    /// vLLM and TGI sampling extensions; see `Extensions`.
+    #[serde(default)]
+    pub best_of: Option<u32>,
+    #[serde(default)]
+    pub use_beam_search: Option<bool>,
+    #[serde(default)]
+    pub min_tokens: Option<usize>,
+    #[serde(default)]
+    pub repetition_penalty: Option<f64>,
+    /// `["text", "audio"]` asks for a spoken reply; see `audio`.
+    #[serde(default)]
+    pub modalities: Vec<String>,
+    #[serde(default)]
+    pub audio: Option<AudioParams>,
+    /// Text the reply is expected to resemble; see `Prediction`.
+    #[serde(default)]
+    pub prediction: Option<Prediction>,
+    /// Scales the reply's pacing; see `service_tier`.
+    #[serde(default)]
+    pub service_tier: Option<ServiceTier>,
+    /// Keeps the completion for the stored-completions endpoints; see `stored`.
+    #[serde(default)]
+    pub store: Option<bool>,
+    /// Tags stored completions are listed by.
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+    /// Every other top-level key; see `UnknownFields`.
+    #[serde(flatten)]
+    pub other: UnknownFields,
+}
+
+#[derive(Deserialize, Default)]
+pub struct StreamOptions {
+    /// Ends the stream with a chunk carrying `usage` and no choices.
+    #[serde(default)]
+    pub include_usage: bool,
+}
+
+/// Request parameters the OpenAI API defines that the mock accepts without
+/// acting on. Keys outside these and `ChatRequest`'s own fields are unknown.
+pub const OPENAI_FIELDS: &[&str] = &[
+    "frequency_penalty",
+    "function_call",
+    "logit_bias",
+    "logprobs",
+    "n",
+    "parallel_tool_calls",
+    "presence_penalty",
+    "reasoning_effort",
+    "response_format",
+    "stop",
+    "temperature",
+    "tool_choice",
+    "top_logprobs",
+    "top_p",
+    "user",
+    "web_search_options",
+];
+
+/// Predicted outputs. The reply is generated as usual; its usage reports
+/// how many of the prediction's tokens it used.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Prediction {
+    Content { content: MessageContent },
+}
+
+/// The keys of a map, with their values skipped unparsed. Flattened into
+/// `ChatRequest`, it collects the keys no named field took.
+#[derive(Default, Debug)]
+pub struct UnknownFields(pub Vec<String>);
+
+impl<'de> Deserialize<'de> for UnknownFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Keys;
+
+        impl<'de> Visitor<'de> for Keys {
+            type Value = UnknownFields;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<UnknownFields, A::Error> {
+                let mut keys = Vec::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    map.next_value::<IgnoredAny>()?;
+                    keys.push(key);
+                }
+                Ok(UnknownFields(keys))
+            }
+        }
+
+        deserializer.deserialize_map(Keys)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Message {
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<MessageContent>,
+    /// The participant's name, which OpenAI bills as part of the prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ContentPart {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<ImageUrl>,
+}
+
+impl MessageContent {
+    /// The text of the message, with multi-part content joined by newlines and
+    /// non-text parts skipped.
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|p| p.text.as_deref())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Either accepted request shape. `messages` is tried first so a body that
+/// carries both is treated as a chat request.
+// One per request and short-lived, so its size does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum IncomingRequest {
+    Chat(ChatRequest),
+    Legacy(StreamRequest),
+}
+
+/// The internal form every accepted shape is normalized to.
+pub struct NormalizedRequest {
+    pub model: Option<String>,
+    pub messages: Vec<Message>,
+    /// `None` when the client left it out; see `ReplyFormat::negotiate`.
+    pub stream: Option<bool>,
+    /// `stream_options.include_usage`.
+    pub include_usage: bool,
+    /// Text the reply is keyed and echoed on: the last user message, or the legacy `prompt`.
+    pub prompt: String,
+    pub legacy: bool,
+    /// The client's `seed`, or a hash of the messages so identical
+    /// conversations always get the same reply.
+    pub seed: u64,
+    /// Whether the client sent `seed` itself.
+    pub seeded: bool,
+    /// Completion tokens the client reserved.
+    pub max_tokens: Option<usize>,
+    /// Whether the request offers the model tools or functions.
+    pub uses_tools: bool,
+    pub modalities: Vec<String>,
+    pub audio: Option<AudioParams>,
+    /// The text of the request's `prediction`.
+    pub prediction: Option<String>,
+    pub service_tier: Option<ServiceTier>,
+    pub store: bool,
+    pub metadata: Metadata,
+    pub extensions: Extensions,
+    /// Top-level fields the OpenAI API does not define, in request order.
+    pub unknown_fields: Vec<String>,
+}
+
+impl NormalizedRequest {
+    /// Whether any message carries an image part.
+    pub fn has_images(&self) -> bool {
+        self.messages.iter().any(|m| match &m.content {
+            Some(MessageContent::Parts(parts)) => parts.iter().any(|p| p.kind == "image_url"),
+            _ => false,
+        })
+    }
+}
+
+fn messages_seed(messages: &[Message]) -> u64 {
+    let texts: Vec<(&str, String)> = messages
+        .iter()
+        .map(|m| (m.role.as_str(), m.content.as_ref().map(MessageContent::text).unwrap_or_default()))
+        .collect();
+    generator::stable_hash(texts.iter().flat_map(|(role, text)| [*role, text.as_str()]))
+}
+
+/// The text of the last user message, which replies are keyed and echoed on.
+pub fn last_user_text(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .and_then(|m| m.content.as_ref())
+        .map(MessageContent::text)
+        .unwrap_or_default()
+}
+
+impl From<IncomingRequest> for NormalizedRequest {
+    fn from(req: IncomingRequest) -> Self {
+        match req {
+            IncomingRequest::Chat(chat) => {
+                let prompt = last_user_text(&chat.messages);
+                let seeded = chat.seed.is_some();
+                let seed = chat.seed.unwrap_or_else(|| messages_seed(&chat.messages));
+                NormalizedRequest {
+                    model: chat.model,
+                    messages: chat.messages,
+                    stream: chat.stream,
+                    include_usage: chat.stream_options.is_some_and(|o| o.include_usage),
+                    prompt,
+                    legacy: false,
+                    seed,
+                    seeded,
+                    max_tokens: chat.max_completion_tokens.or(chat.max_tokens),
+                    uses_tools: !chat.tools.is_empty() || !chat.functions.is_empty(),
+                    modalities: chat.modalities,
+                    audio: chat.audio,
+                    prediction: chat.prediction.map(|Prediction::Content { content }| content.text()),
+                    service_tier: chat.service_tier,
+                    store: chat.store.unwrap_or(false),
+                    metadata: chat.metadata.unwrap_or_default(),
+                    extensions: Extensions {
+                        best_of: chat.best_of,
+                        use_beam_search: chat.use_beam_search,
+                        min_tokens: chat.min_tokens,
+                        repetition_penalty: chat.repetition_penalty,
+                    },
+                    unknown_fields: chat
+                        .other
+                        .0
+                        .into_iter()
+                        .filter(|key| !OPENAI_FIELDS.contains(&key.as_str()))
+                        .collect(),
+                }
+            }
+            IncomingRequest::Legacy(legacy) => {
+                let messages = vec![Message {
+                    role: "user".to_string(),
+                    content: Some(MessageContent::Text(legacy.prompt.clone())),
+                    name: None,
+                }];
+                NormalizedRequest {
+                    model: None,
+                    seed: messages_seed(&messages),
+                    seeded: false,
+                    messages,
+                    stream: Some(legacy.stream),
+                    include_usage: false,
+                    prompt: legacy.prompt,
+                    legacy: true,
+                    max_tokens: None,
+                    uses_tools: false,
+                    modalities: Vec::new(),
+                    audio: None,
+                    prediction: None,
+                    service_tier: None,
+                    store: false,
+                    metadata: Metadata::new(),
+                    extensions: Extensions::default(),
+                    unknown_fields: Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+static LEGACY_WARNED: AtomicBool = AtomicBool::new(false);
+
+fn record_shape(req: &NormalizedRequest) {
+    if req.legacy {
+        metrics::LEGACY_PROMPT_REQUESTS.inc();
+        if !LEGACY_WARNED.swap(true, Ordering::Relaxed) {
+            log::warn!("received a legacy {{prompt, stream}} request; this shape is deprecated in favor of `messages`");
+        }
+    } else {
+        metrics::CHAT_REQUESTS.inc();
+    }
+}
+
+#[derive(Serialize)]
+pub struct StreamChunk {
+    pub choices: Vec<Choice>,
+    /// Mock-specific diagnostics, only present when there is something to report.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x_mock: Option<MockExtension>,
+    /// Only on the closing chunk of a stream that asked for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// The tier served, when the request named one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<&'static str>,
+}
+
+#[derive(Serialize, Default)]
+pub struct MockExtension {
+    /// Time the request spent in the admission queue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_ms: Option<u64>,
+    /// The server and rule that produced the reply; see `watermark`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<Watermark>,
+    /// What the reply would cost at the model's `pricing`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+    /// The experiment variant that served the request; see `experiments`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+impl StreamChunk {
+    pub fn with_content(content: String) -> StreamChunk {
+        StreamChunk {
+            choices: vec![Choice {
+                delta: Delta {
+                    content: Some(content),
+                    ..Delta::default()
+                },
+                finish_reason: None,
+            }],
+            x_mock: None,
+            usage: None,
+            service_tier: None,
+        }
+    }
+
+    /// A chunk whose only delta is a tool call, as sent when a model decides
+    /// to call a function.
+    pub fn tool_call(call: ToolCallDelta) -> StreamChunk {
+        StreamChunk {
+            choices: vec![Choice {
+                delta: Delta {
+                    tool_calls: Some(vec![call]),
+                    ..Delta::default()
+                },
+                finish_reason: None,
+            }],
+            x_mock: None,
+            usage: None,
+            service_tier: None,
+        }
+    }
+
+    /// The usage chunk `include_usage` asks for, sent after the last choice.
+    pub fn usage(meter: &UsageMeter) -> StreamChunk {
+        StreamChunk {
+            choices: Vec::new(),
+            x_mock: meter.estimated_cost_usd().map(|cost| MockExtension {
+                estimated_cost_usd: Some(cost),
+                ..MockExtension::default()
+            }),
+            usage: Some(meter.usage()),
+            service_tier: None,
+        }
+    }
+
+    /// The chunk, echoing the tier the request asked for.
+    pub fn in_tier(mut self, tier: Option<ServiceTier>) -> StreamChunk {
+        self.service_tier = tier.map(ServiceTier::name);
+        self
+    }
+
+    /// The closing chunk: an empty delta carrying why generation stopped.
+    pub fn finished(reason: String) -> StreamChunk {
+        StreamChunk {
+            choices: vec![Choice {
+                delta: Delta::default(),
+                finish_reason: Some(reason),
+            }],
+            x_mock: None,
+            usage: None,
+            service_tier: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Choice {
+    pub delta: Delta,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+pub struct Delta {
+    /// Sent on the first chunk of a reply only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// The reply so far, with `ContentMode::Both`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+    /// Citations whose links end in this delta; see `citations`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Vec<Annotation>>,
+    /// Takes the place of `content` in a spoken reply; see `audio`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioDelta>,
+}
+
+#[derive(Serialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub function: FunctionDelta,
+}
+
+#[derive(Serialize)]
+pub struct FunctionDelta {
+    pub name: String,
+    pub arguments: String,
+}
+
+#[derive(Serialize)]
+pub struct ErrorEvent {
+    pub error: String,
+    pub code: i32,
+}
+
+/// Stands in for the delta text when pre-rendering the chunk envelope.
+const CONTENT_PLACEHOLDER: &str = "__CONTENT__";
+
+/// Rough characters per token used to convert between reply length and token counts.
+pub const CHARS_PER_TOKEN: usize = 4;
+const DEFAULT_LONG_TOKENS: usize = 1_000_000;
+// Roughly the size of a default canned chunk, so long replies pace similarly.
+const DEFAULT_LONG_CHUNK_CHARS: usize = 128;
+
+pub const EXTENDED_CONTENT: &str = "Streaming LLM APIs represent a paradigm shift in how we build and interact with artificial intelligence. By delivering content incrementally, we significantly reduce the time to first token, which is a critical metric for user engagement. In a world where attention spans are measured in seconds, providing immediate feedback can make the difference between a successful product and a failed one. This implementation showcases a robust, high-performance streaming endpoint built with Rust and actix-web. 
+
+Rust's zero-cost abstractions and memory safety guarantees make it an ideal choice for building high-throughput, low-latency APIs. The actix-web framework provides a powerful, non-blocking asynchronous architecture that can handle thousands of concurrent streaming connections with minimal overhead. 
+
+Key technical aspects of this solution include:
+1. Server-Sent Events (SSE) Protocol: We use the text/event-stream content type to maintain a persistent connection and push data chunks to the client as they become available.
+2. Asynchronous Streams: Leveraging the `futures` crate, we generate a non-blocking stream of data chunks, allowing the server to handle other requests while waiting for the next chunk to be ready.
+3. Efficient Serialization: We use `serde_json` for fast and safe serialization of JSON deltas, ensuring minimal processing latency.
+4. Error Handling: The API includes robust validation for prompts and stream parameters, returning descriptive errors in the same streaming format to ensure client-side consistency.
+5. Buffering Control: By setting specific HTTP headers like `X-Accel-Buffering: no` and `Cache-Control: no-cache`, we prevent intermediate proxies from buffering the stream, ensuring real-time delivery to the end user.
+
+This approach not only improves perceived performance but also enables complex real-time applications such as interactive chat interfaces, live coding assistants, and dynamic content generators that feel alive and responsive. By following these architectural patterns, developers can build AI-powered tools that provide a seamless and premium user experience. Moreover, the integration of streaming capabilities into the development workflow allows for a more iterative and fast-paced environment where feedback loops are shortened and productivity is enhanced. In conclusion, this solution provides a robust foundation for any application requiring high-quality, real-time AI-generated content.";
+
+/// The reply of `generator = "code"`: prose around fenced code blocks full
+/// of backslash escapes, for exercising Markdown renderers.
+pub const CODE_CONTENT: &str = r#"Here is a small Rust program that prints a greeting and a Windows path:
+
+```rust
+fn main() {
+    let name = "world";
+    println!("Hello, {}!\n", name);
+    let path = "C:\\Users\\mock\\config.toml";
+    let quote = "She said \"hi\"\tand left \u{1F600}";
+    println!("{}\n{}", path, quote);
+}
+```
+
+The same values as JSON, the way a client might receive them:
+
+```json
+{"greeting": "Hello, world!\n", "path": "C:\\Users\\mock", "emoji": "\ud83d\ude00", "tab": "\x09"}
+```
+
+A shell one-liner, fenced with tildes this time:
+
+~~~sh
+printf 'line one\nline two\n' | grep -c "line\s"
+~~~
+
+Inline code such as `cargo run` and ``a `tick` inside`` shows up in prose too, and a fence can hold a fence:
+
+````markdown
+```python
+print("nested\n")
+```
+````
+"#;
+
+fn create_error_event(error: &str, code: i32) -> Result<Bytes, MockError> {
+    let event = ErrorEvent {
+        error: error.to_string(),
+        code,
+    };
+    sse::data_event(&event)
+}
+
+/// The in-band error event for an injected mid-stream failure.
+fn injected_error_event(profile: &ResponseProfile) -> Result<Bytes, MockError> {
+    match profile.error_style {
+        ErrorStyle::Openai => create_error_event(&profile.error_message, profile.error_code as i32),
+        ErrorStyle::Anthropic => {
+            let status = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            let data = sse::data_event(&error::AnthropicEnvelope::new(status, profile.error_message.clone()))?;
+            let mut event = b"event: error\n".to_vec();
+            event.extend_from_slice(&data);
+            Ok(Bytes::from(event))
+        }
+    }
+}
+
+/// Sent in place of a chunk that failed to serialize. Pre-rendered, since
+/// serializing it could fail the same way.
+const SERIALIZE_FAILED_EVENT: &str = "data: {\"error\":\"Failed to serialize chunk\",\"code\":500}\n\n";
+
+/// Fails a request before any streaming has started. Mid-stream failures are
+/// reported in-band with `create_error_event` instead.
+fn reject(config: &Config, status: StatusCode, message: &str, param: Option<&str>) -> HttpResponse {
+    reject_styled(config, ErrorStyle::Openai, status, message, param)
+}
+
+/// `reject` with the error body in `style`'s provider format.
+pub(crate) fn reject_styled(config: &Config, style: ErrorStyle, status: StatusCode, message: &str, param: Option<&str>) -> HttpResponse {
+    if config.compat.legacy_sse_errors {
+        return match create_error_event(message, status.as_u16() as i32) {
+            Ok(event) => HttpResponse::build(status).content_type("text/event-stream").body(event),
+            Err(e) => e.error_response(),
+        };
+    }
+    error::styled_error(style, status, message, param)
+}
+
+/// Rejects requests whose prompt plus reserved completion exceeds the model's
+/// context window, worded exactly as OpenAI words it.
+pub fn check_context_window(model: &ModelInfo, req: &NormalizedRequest) -> Result<(), MockError> {
+    let prompt_tokens = model.encoding.count_messages(&req.messages);
+    let completion_tokens = req.max_tokens.unwrap_or(0);
+    let Some(requested) = prompt_tokens.checked_add(completion_tokens) else {
+        let message = format!("max_tokens is too large: {}. It must fit within the model's context window.", completion_tokens);
+        return Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some("max_tokens")).with_code("context_length_exceeded"));
+    };
+    if requested <= model.context_window {
+        return Ok(());
+    }
+    let message = match req.max_tokens {
+        Some(_) => format!(
+            "This model's maximum context length is {} tokens. However, you requested {} tokens ({} in the messages, {} in the completion). Please reduce the length of the messages or completion.",
+            model.context_window, requested, prompt_tokens, completion_tokens
+        ),
+        None => format!(
+            "This model's maximum context length is {} tokens. However, your messages resulted in {} tokens. Please reduce the length of the messages.",
+            model.context_window, prompt_tokens
+        ),
+    };
+    Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some("messages")).with_code("context_length_exceeded"))
+}
+
+/// How a chat reply is sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplyFormat {
+    Sse,
+    /// One `chat.completion` object.
+    Json,
+}
+
+impl ReplyFormat {
+    /// `stream: true` streams. When `stream` is left out, the first of
+    /// `text/event-stream` and `application/json` in the client's ranked
+    /// `Accept` decides; `None` means the request is refused, as is
+    /// `stream: false`, since the mock exists to stream.
+    pub fn negotiate(stream: Option<bool>, req: &HttpRequest) -> Option<ReplyFormat> {
+        match stream {
+            Some(true) => Some(ReplyFormat::Sse),
+            Some(false) => None,
+            None => Accept::parse(req).ok()?.ranked().into_iter().find_map(|mime| {
+                match (mime.type_().as_str(), mime.subtype().as_str()) {
+                    ("text", "event-stream") => Some(ReplyFormat::Sse),
+                    ("application", "json") => Some(ReplyFormat::Json),
+                    _ => None,
+                }
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Completion<'a> {
+    id: &'a str,
+    object: &'static str,
+    created: u64,
+    model: Option<&'a str>,
+    choices: [CompletionChoice; 1],
+    usage: Usage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_tier: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x_mock: Option<MockExtension>,
+}
+
+#[derive(Serialize)]
+struct CompletionChoice {
+    index: u32,
+    message: CompletionMessage,
+    finish_reason: String,
+}
+
+#[derive(Serialize)]
+struct CompletionMessage {
+    role: &'static str,
+    /// `null` in a spoken reply, whose text is the audio's transcript.
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<Annotation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audio: Option<AudioMessage>,
+}
+
+/// The whole reply as one `chat.completion`, paced like the stream so it
+/// arrives when the stream would have ended. An injected failure fails the
+/// request instead, with its status and error body.
+#[allow(clippy::too_many_arguments)]
+async fn completion(
+    config: &Config,
+    state: &web::Data<AppState>,
+    req: &NormalizedRequest,
+    profile: &ResponseProfile,
+    chunks: Box<dyn Iterator<Item = String>>,
+    screen: Option<&Screen>,
+    citations: Option<&Citations>,
+    audio: Option<&AudioParams>,
+    model_info: Option<&ModelInfo>,
+    request_id: &str,
+    capture_id: u64,
+    scope: &OrgScope,
+    owner: &str,
+    variant: Option<String>,
+) -> Result<serde_json::Value, HttpResponse> {
+    let encoding = model_info.map(|m| m.encoding).unwrap_or_default();
+    let recorder = StreamRecorder::new(state, capture_id);
+    let tracker = Tracker::start(state, request_id, req.model.as_deref(), scope, encoding, &req.messages);
+    let meter = UsageMeter::new(request_id, model_info, &req.messages, req.prediction.as_deref());
+    let shadow = state.shadow.mirror(config, state, req, model_info, request_id);
+    let pace = Pace::new(profile, encoding);
+    let mut content = String::new();
+    let mut annotations = Vec::new();
+    for (i, chunk) in chunks.enumerate() {
+        if profile.error_after == Some(i) {
+            if let Some(tracker) = tracker.as_ref() {
+                tracker.failed(&profile.error_message);
+            }
+            if let Some(recorder) = recorder.as_ref() {
+                recorder.finish(false);
+            }
+            let status = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(reject_styled(config, profile.error_style, status, &profile.error_message, None));
+        }
+        let delay = pace.before(&chunk, i == 0) + screen.map_or(Duration::ZERO, Screen::take_delay);
+        if !delay.is_zero() {
+            pacer::sleep(delay, &config.stream).await;
+        }
+        if let Some(tracker) = tracker.as_ref() {
+            tracker.chunk(&chunk);
+        }
+        if let Some(recorder) = recorder.as_ref() {
+            recorder.chunk(&chunk);
+        }
+        if let Some(shadow) = shadow.as_ref() {
+            shadow.chunk(&chunk);
+        }
+        meter.chunk(&chunk);
+        if let Some(citations) = citations {
+            annotations.extend(citations.completed_by(&chunk));
+        }
+        content.push_str(&chunk);
+    }
+    // A guardrail that blocked the whole tail still takes its time.
+    if let Some(delay) = screen.map(Screen::take_delay).filter(|d| !d.is_zero()) {
+        pacer::sleep(delay, &config.stream).await;
+    }
+    meter.finish();
+    if let Some(tracker) = tracker.as_ref() {
+        tracker.completed();
+    }
+    if let Some(recorder) = recorder.as_ref() {
+        recorder.finish(true);
+    }
+    if let Some(shadow) = shadow.as_ref() {
+        shadow.finish(true);
+    }
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let audio = audio.map(|params| {
+        let (id, expires_at) = audio::id_for(request_id, created);
+        AudioMessage::new(params, id, expires_at, std::mem::take(&mut content))
+    });
+    let finish_reason = match screen.filter(|s| s.take_tripped()) {
+        Some(_) => "content_filter".to_string(),
+        None => profile.finish_reason.clone().unwrap_or_else(|| "stop".to_string()),
+    };
+    if req.store {
+        let transcript = audio.as_ref().map_or(&content, |audio| &audio.transcript);
+        state.stored.put(StoredCompletion::new(req, owner, request_id, transcript, finish_reason.clone(), meter.usage()));
+    }
+    let completion = Completion {
+        id: request_id,
+        object: "chat.completion",
+        created,
+        model: req.model.as_deref(),
+        choices: [CompletionChoice {
+            index: 0,
+            message: CompletionMessage {
+                role: "assistant",
+                content: audio.is_none().then_some(content),
+                annotations,
+                audio,
+            },
+            finish_reason,
+        }],
+        usage: meter.usage(),
+        service_tier: req.service_tier.map(ServiceTier::name),
+        x_mock: (meter.estimated_cost_usd().is_some() || variant.is_some()).then(|| MockExtension {
+            estimated_cost_usd: meter.estimated_cost_usd(),
+            variant,
+            ..MockExtension::default()
+        }),
+    };
+    serde_json::to_value(completion).map_err(|e| MockError::Serialize(e.to_string()).error_response())
+}
+
+/// Events a profile's quirks add around content chunks, pre-rendered once
+/// per request.
+struct QuirkEvents {
+    /// Sent ahead of the first chunk.
+    preamble: Vec<u8>,
+    /// Sent after every chunk.
+    trailer: Option<Bytes>,
+}
+
+impl QuirkEvents {
+    fn new(quirks: &[Quirk], tier: Option<ServiceTier>) -> Result<QuirkEvents, MockError> {
+        let mut preamble = Vec::new();
+        if quirks.contains(&Quirk::EmptyChoices) {
+            preamble.extend_from_slice(&sse::data_event(&StreamChunk {
+                choices: Vec::new(),
+                x_mock: None,
+                usage: None,
+                service_tier: tier.map(ServiceTier::name),
+            })?);
+        }
+        if quirks.contains(&Quirk::ContentAfterToolCalls) {
+            preamble.extend_from_slice(&sse::data_event(&StreamChunk::tool_call(ToolCallDelta {
+                index: 0,
+                id: "call_mock_0".to_string(),
+                kind: "function",
+                function: FunctionDelta {
+                    name: "lookup".to_string(),
+                    arguments: "{\"query\":\"mock\"}".to_string(),
+                },
+            })
+            .in_tier(tier))?);
+        }
+        let trailer = if quirks.contains(&Quirk::EmptyDeltas) {
+            Some(sse::data_event(&StreamChunk {
+                choices: vec![Choice {
+                    delta: Delta::default(),
+                    finish_reason: None,
+                }],
+                x_mock: None,
+                usage: None,
+                service_tier: tier.map(ServiceTier::name),
+            })?)
+        } else {
+            None
+        };
+        Ok(QuirkEvents { preamble, trailer })
+    }
+
+    /// `event` with the quirk events that belong around it, in one write.
+    fn surround(&self, event: Bytes, first: bool) -> Bytes {
+        let preamble: &[u8] = if first { &self.preamble } else { &[] };
+        let trailer = self.trailer.as_deref().unwrap_or_default();
+        if preamble.is_empty() && trailer.is_empty() {
+            return event;
+        }
+        let mut buf = pool::take();
+        buf.extend_from_slice(preamble);
+        buf.extend_from_slice(&event);
+        buf.extend_from_slice(trailer);
+        pool::freeze(buf)
+    }
+}
+
+/// The `Last-Event-ID` a reconnecting client sent, or 0 for a fresh stream.
+fn last_event_id(req: &HttpRequest) -> usize {
+    req.headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// The reply's opening line, quoting as much of the prompt as `echo` allows.
+pub fn prompt_header(prompt: &str, echo: &EchoConfig) -> String {
+    prompt_header_in(Language::English, prompt, echo)
+}
+
+/// `prompt_header` for a reply in `language`.
+pub fn prompt_header_in(language: Language, prompt: &str, echo: &EchoConfig) -> String {
+    let quoted = match echo.mode {
+        EchoMode::Full => prompt.to_string(),
+        EchoMode::Truncate => match prompt.char_indices().nth(echo.max_chars) {
+            Some((cut, _)) => format!("{}…", &prompt[..cut]),
+            None => prompt.to_string(),
+        },
+        EchoMode::Hash => {
+            let digest = Sha256::digest(prompt.as_bytes());
+            format!("sha256:{}", digest[..6].iter().map(|b| format!("{:02x}", b)).collect::<String>())
+        }
+        EchoMode::Omit => return format!("{}:\n\n", language.opening()),
+    };
+    format!("{} '{}':\n\n", language.opening(), quoted)
+}
+
+/// The profile a request is served with, the name of the stub rule it came
+/// from and the experiment variant it was routed to. A matching stub rule
+/// wins; otherwise an experiment on the model, then the model's provider
+/// preset, if any. The request's overrides apply on top.
+pub fn resolve_profile(
+    config: &Config,
+    req: &NormalizedRequest,
+    overrides: &MockOverrides,
+) -> (ResponseProfile, Option<String>, Option<String>) {
+    let rule = stubs::resolve(&config.stubs, &req.prompt, &req.extensions);
+    let variant = rule.is_none().then(|| experiments::choose(&config.experiments, req)).flatten();
+    let mut profile = match (rule, variant) {
+        (Some(rule), _) => rule.profile.clone(),
+        (None, Some(variant)) => variant.profile.clone(),
+        (None, None) => req
+            .model
+            .as_deref()
+            .and_then(|model| presets::for_model(config, model))
+            .map(Preset::profile)
+            .unwrap_or_default(),
+    };
+    overrides.apply(&mut profile);
+    (profile, rule.and_then(|r| r.name.clone()), variant.map(|v| v.name.clone()))
+}
+
+/// The text of a reply, shared by every wire format.
+pub struct ReplyText {
+    /// Produced chunk by chunk as the stream is polled; the full reply is
+    /// never held in memory. The profile's transforms are already applied.
+    pub chunks: Box<dyn Iterator<Item = String>>,
+    /// Length of the generated text before transforms.
+    pub total_chars: usize,
+    /// Whether the chunks come from a loaded recording.
+    pub replayed: bool,
+    /// Set when `[watermark]` is on; the hidden form is already in the text.
+    pub watermark: Option<Watermark>,
+    /// The language of a canned reply, when the profile chose one.
+    pub language: Option<Language>,
+    /// Set when the profile cites sources; the links are already in the text.
+    pub citations: Option<Rc<Citations>>,
+}
+
+impl ReplyText {
+    pub fn new(config: &Config, state: &AppState, req: &NormalizedRequest, profile: &ResponseProfile, rule_name: Option<&str>) -> ReplyText {
+        let generator = available(profile.generator);
+        let language = match generator {
+            GeneratorKind::Canned if profile.detect_language => languages::detect(&req.prompt).or(profile.language).or(Some(Language::English)),
+            GeneratorKind::Canned => profile.language,
+            _ => None,
+        };
+        let header = prompt_header_in(language.unwrap_or_default(), &req.prompt, &config.echo);
+        let corpus_reply = match generator {
+            #[cfg(feature = "generators")]
+            GeneratorKind::Corpus => corpus_reply(state, req),
+            _ => None,
+        };
+        // A corpus reply was picked by the seed, and code makes no sense
+        // started mid-block, so both start at the top.
+        let start_seed = if corpus_reply.is_some() || generator == GeneratorKind::Code { 0 } else { req.seed };
+        let canned = match generator {
+            GeneratorKind::Code => CODE_CONTENT,
+            _ => language.unwrap_or_default().reply(),
+        };
+        let body: Cow<'static, str> = corpus_reply.map_or(Cow::Borrowed(canned), Cow::Owned);
+        let (total_chars, default_chunk_chars) = match generator {
+            GeneratorKind::Canned | GeneratorKind::Corpus | GeneratorKind::Code => {
+                let total = profile
+                    .tokens
+                    .map(|tokens| profile.chars_for_tokens(tokens))
+                    .unwrap_or_else(|| header.chars().count() + body.chars().count());
+                // Split into the profile's chunk count (15 by default) to ensure progressive delivery
+                (total, generator::chunk_size_for(total, profile.chunks))
+            }
+            GeneratorKind::Long => (
+                profile.chars_for_tokens(profile.tokens.unwrap_or(DEFAULT_LONG_TOKENS)),
+                DEFAULT_LONG_CHUNK_CHARS,
+            ),
+        };
+        // Transforms run before any resume skip so a replayed stream is corrupted identically.
+        let mut transforms = Pipeline::new(&profile.transforms, req.seed);
+        let recording = state.replay.get(&req.prompt);
+        let replayed = recording.is_some();
+        let mut citations = None;
+        let text: Box<dyn Iterator<Item = String>> = match recording {
+            Some(recording) => Box::new(recording.chunks.clone().into_iter()),
+            None => {
+                let header_chars = header.chars().count();
+                let mut generated: Box<dyn Iterator<Item = String>> = Box::new(
+                    CyclingText::new(
+                        header,
+                        body,
+                        total_chars,
+                        profile.chunk_chars.unwrap_or(default_chunk_chars),
+                    )
+                    .seeded(start_seed),
+                );
+                // Cited before rechunking, so a link can straddle deltas.
+                if profile.citations {
+                    let (cited, linked) = Citations::wrap(header_chars, generated);
+                    citations = Some(cited);
+                    generated = linked;
+                }
+                let generated = chunking::rechunk(
+                    profile.chunk_strategy,
+                    profile.chunk_chars.unwrap_or(default_chunk_chars),
+                    generated,
+                );
+                if profile.fence_safe {
+                    chunking::fence_safe(generated)
+                } else {
+                    generated
+                }
+            }
+        };
+        let mode = config.watermark.mode;
+        let watermark = (mode != WatermarkMode::Off).then(|| Watermark::new(&config.watermark, rule_name));
+        // Marked after the transforms, so they cannot garble the marker.
+        let mut hidden_mark = watermark.as_ref().filter(|_| mode.hidden()).map(Watermark::hidden);
+        let marked_citations = citations.clone();
+        let chunks = Box::new(text.map(move |chunk| {
+            let mut chunk = transforms.apply(chunk);
+            if let Some(mark) = hidden_mark.take() {
+                if let Some(citations) = &marked_citations {
+                    citations.insert(chunk.chars().count(), mark.chars().count());
+                }
+                chunk.push_str(&mark);
+            }
+            chunk
+        }));
+        ReplyText {
+            chunks,
+            total_chars,
+            replayed,
+            watermark,
+            language,
+            citations,
+        }
+    }
+}
+
+/// `kind`, or the canned reply when this build has no `generators` feature.
+fn available(kind: GeneratorKind) -> GeneratorKind {
+    if cfg!(feature = "generators") || kind == GeneratorKind::Canned {
+        return kind;
+    }
+    log::warn!("generator {:?} needs the generators feature; sending the canned reply", kind);
+    GeneratorKind::Canned
+}
+
+/// The `[corpus]` reply for `req`, or `None` to fall back to the canned
+/// text when there is no corpus or the reply cannot be read.
+#[cfg(feature = "generators")]
+fn corpus_reply(state: &AppState, req: &NormalizedRequest) -> Option<String> {
+    let Some(corpus) = &state.corpus else {
+        log::warn!("generator = \"corpus\" without a [corpus] path; sending the canned reply");
+        return None;
+    };
+    let i = corpus.choose(&req.prompt, req.seed);
+    match corpus.reply(i) {
+        Ok(text) => Some(text),
+        Err(e) => {
+            log::warn!("corpus reply {} could not be read ({}); sending the canned reply", i, e);
+            None
+        }
+    }
+}
+
+/// Lets browser clients on other origins read the reply, streamed or whole.
+fn insert_cors_headers(response: &mut HttpResponseBuilder) {
+    response
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .insert_header(("Access-Control-Allow-Methods", "POST, GET, OPTIONS"))
+        .insert_header((
+            "Access-Control-Allow-Headers",
+            "Content-Type, Authorization, Last-Event-ID, OpenAI-Organization, OpenAI-Project, X-Mock-Latency, X-Mock-Chunks, X-Mock-Error-After, X-Mock-Finish-Reason",
+        ));
+}
+
+#[post("/v1/chat/completions")]
+pub async fn stream_endpoint(
+    http_req: HttpRequest,
+    body: ChatBody,
+    overrides: MockOverrides,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let accepted = Instant::now();
+    let mut req = NormalizedRequest::from(body.0);
+    record_shape(&req);
+    let scope = admission::check_caller(&config, &state, &http_req, &req)?;
+    if !req.unknown_fields.is_empty() {
+        let fields = req.unknown_fields.join(", ");
+        match config.request.unknown_fields {
+            UnknownFieldPolicy::Strict => {
+                let noun = if req.unknown_fields.len() == 1 { "argument" } else { "arguments" };
+                let message = format!("Unrecognized request {} supplied: {}", noun, fields);
+                return Ok(reject(&config, StatusCode::BAD_REQUEST, &message, None));
+            }
+            UnknownFieldPolicy::Warn => log::warn!("serving a request with unrecognized fields: {}", fields),
+            UnknownFieldPolicy::Ignore => {}
+        }
+    }
+
+    let param = if req.legacy { "prompt" } else { "messages" };
+    let Recorded { capture_id, request_id } = admission::record(&config, &state, &http_req, &mut req, param)?;
+
+    if req.prompt.trim().is_empty() {
+        return Ok(if req.legacy {
+            reject(&config, StatusCode::BAD_REQUEST, "Prompt cannot be empty", Some("prompt"))
+        } else {
+            reject(&config, StatusCode::BAD_REQUEST, "messages must include a non-empty user message", Some("messages"))
+        });
+    }
+
+    let Some(format) = ReplyFormat::negotiate(req.stream, &http_req) else {
+        return Ok(reject(&config, StatusCode::BAD_REQUEST, "stream parameter must be true", Some("stream")));
+    };
+
+    req.extensions.validate(req.max_tokens)?;
+    stored::validate(&req.metadata)?;
+    let audio = audio::requested(&req.modalities, req.audio.as_ref(), format == ReplyFormat::Sse)?.cloned();
+    let model_info = req.model.as_deref().and_then(|m| state.models.get(m));
+    if let Some(model) = model_info {
+        check_context_window(model, &req)?;
+    }
+
+    let (mut profile, rule_name, variant) = admission::profile(&config, &state, &req, &overrides, capture_id);
+    let retry_budget = admission::retry_budget(&config, &state, &http_req, &scope, &mut profile);
+    if profile.error_after == Some(0) {
+        let status = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = reject_styled(&config, profile.error_style, status, &profile.error_message, None);
+        if let Some(budget) = &retry_budget {
+            budget.insert_into(response.headers_mut());
+        }
+        profile.headers.insert_into(response.headers_mut());
+        return Ok(response);
+    }
+
+    let ReplyText {
+        mut chunks,
+        total_chars,
+        replayed,
+        watermark,
+        language,
+        citations,
+    } = ReplyText::new(&config, &state, &req, &profile, rule_name.as_deref());
+    let screen = match &profile.guardrail {
+        Some(guardrail) => {
+            let (screen, screened) = Screen::wrap(guardrail, req.seed, chunks);
+            chunks = screened;
+            Some(screen)
+        }
+        None => None,
+    };
+    let token_encoding = model_info.map(|m| m.encoding).unwrap_or_default();
+    let charged = admission::reservation(&req, token_encoding, total_chars);
+    let admission = Admission::charge(&config, &state, &http_req, &scope, &request_id, req.model.as_deref(), charged).await?;
+
+    if format == ReplyFormat::Json {
+        let body = match completion(&config, &state, &req, &profile, chunks, screen.as_deref(), citations.as_deref(), audio.as_ref(), model_info, &request_id, capture_id, &scope, &stored::owner(&scope, &http_req), variant.clone()).await {
+            Ok(body) => body,
+            Err(mut response) => {
+                if let Some(budget) = &retry_budget {
+                    budget.insert_into(response.headers_mut());
+                }
+                profile.headers.insert_into(response.headers_mut());
+                return Ok(response);
+            }
+        };
+        let etag = req.seeded.then(|| etag::of(&body));
+        let mut response = HttpResponse::Ok();
+        response
+            .insert_header(("Vary", "Accept"))
+            .insert_header(("X-Mock-Seed", req.seed.to_string()))
+            .insert_header(("x-request-id", request_id));
+        if let Some(language) = language {
+            response.insert_header(("X-Mock-Language", language.code()));
+        }
+        if let Some(name) = rule_name {
+            response.insert_header(("X-Mock-Rule", name));
+        }
+        if let Some(variant) = &variant {
+            response.insert_header(("X-Mock-Variant", variant.as_str()));
+        }
+        if replayed {
+            response.insert_header(("X-Mock-Replay", "true"));
+        }
+        insert_cors_headers(&mut response);
+        admission.insert_headers(&mut response);
+        if let Some(budget) = &retry_budget {
+            budget.insert_headers(&mut response);
+        }
+        profile.headers.insert_headers(&mut response);
+        if let Some(etag) = etag {
+            response.insert_header(("ETag", etag.as_str()));
+            if etag::not_modified(&http_req, &etag) {
+                return Ok(response.status(StatusCode::NOT_MODIFIED).finish());
+            }
+        }
+        return Ok(response.json(body));
+    }
+
+    let recording = state.replay.get(&req.prompt);
+    // A reconnecting client resumes after the last event it saw. Only chunks
+    // that actually exist are skipped, so the ids stay in range.
+    let resumed = if config.stream.event_ids { last_event_id(&http_req) } else { 0 };
+    let mut digest = profile
+        .metadata_frame
+        .then(|| ReplyDigest::new(token_encoding, &req.messages));
+    let content_mode = profile
+        .content_mode
+        .or_else(|| model_info.and_then(|m| m.content_mode))
+        .unwrap_or_default();
+    // Only snapshot modes keep the reply so far; it includes skipped chunks.
+    let mut so_far = String::new();
+    let stored = StoredStream::new(&req, &stored::owner(&scope, &http_req), &request_id, profile.finish_reason.clone().unwrap_or_else(|| "stop".to_string()));
+    let resumed = chunks
+        .by_ref()
+        .take(resumed)
+        .inspect(|chunk| {
+            if let Some(digest) = digest.as_mut() {
+                digest.chunk(chunk);
+            }
+            if let Some(stored) = stored.as_ref() {
+                stored.chunk(chunk);
+            }
+            if let Some(citations) = citations.as_ref() {
+                citations.completed_by(chunk);
+            }
+            if content_mode != ContentMode::Delta {
+                so_far.push_str(chunk);
+            }
+        })
+        .count();
+    let digest = Rc::new(RefCell::new(digest));
+    let so_far = Rc::new(RefCell::new(so_far));
+    let stored = Rc::new(stored);
+
+    let pace = Pace::new(&profile, token_encoding);
+    let error_after = profile.error_after;
+    let error_event = injected_error_event(&profile)?;
+    let finish_event = profile
+        .finish_reason
+        .map(|reason| sse::data_event(&StreamChunk::finished(reason).in_tier(req.service_tier)))
+        .transpose()?;
+    let deadline = profile.max_duration_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+    let on_timeout = profile.on_timeout;
+    let length_event = sse::data_event(&StreamChunk::finished("length".to_string()).in_tier(req.service_tier))?;
+    let filtered_event = sse::data_event(&StreamChunk::finished("content_filter".to_string()).in_tier(req.service_tier))?;
+    // Set once a soft timeout has cut the reply short, so the stream goes straight to its ending.
+    let truncated = Rc::new(Cell::new(false));
+    let role = (!profile.quirks.contains(&Quirk::MissingRole)).then_some("assistant");
+    let quirks = Rc::new(QuirkEvents::new(&profile.quirks, req.service_tier)?);
+    // Spoken chunks carry no `content` to fill a template with.
+    let frame = Rc::new(match audio {
+        Some(_) => None,
+        None => FrameTemplate::new(
+            &StreamChunk::with_content(CONTENT_PLACEHOLDER.to_string()).in_tier(req.service_tier),
+            CONTENT_PLACEHOLDER,
+        ),
+    });
+    let (audio_id, audio_expires_at) =
+        audio::id_for(&request_id, SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()));
+    let audio_end = match audio {
+        Some(_) => Some(sse::data_event(&StreamChunk {
+            choices: vec![Choice {
+                delta: Delta {
+                    audio: Some(AudioDelta {
+                        id: Some(audio_id.clone()),
+                        expires_at: Some(audio_expires_at),
+                        ..AudioDelta::default()
+                    }),
+                    ..Delta::default()
+                },
+                finish_reason: None,
+            }],
+            x_mock: None,
+            usage: None,
+            service_tier: req.service_tier.map(ServiceTier::name),
+        })?),
+        None => None,
+    };
+    let audio_end = Rc::new(Cell::new(audio_end));
+    let tone = Rc::new(audio.as_ref().map(|params| RefCell::new(Tone::new(&params.voice))));
+
+    let stream_config = Rc::new(config.stream.clone());
+    let queue_ms = Rc::new(Cell::new(admission.queue_ms));
+    let watermark = Rc::new(RefCell::new(watermark.filter(|_| config.watermark.mode.extension())));
+    let first_variant = Rc::new(RefCell::new(variant.clone()));
+    // Held by the stream, so the request stops counting against the budget once it ends or the client leaves.
+    let lease = Rc::new(admission.lease(&state, req.model.as_deref(), profile.weight));
+    let error_message = Rc::new(profile.error_message.clone());
+    let recorder = Rc::new(StreamRecorder::new(&state, capture_id));
+    let shadow = Rc::new(state.shadow.mirror(&config, &state, &req, model_info, &request_id));
+    let tracker = Rc::new(Tracker::start(&state, &request_id, req.model.as_deref(), &scope, token_encoding, &req.messages));
+    let meter = Rc::new(UsageMeter::start(&request_id, model_info, &req.messages, req.prediction.as_deref(), req.include_usage || req.store));
+    let usage_pending = Rc::new(Cell::new(req.include_usage));
+    let clock = Rc::new(metrics::ChunkClock::start(req.model.as_deref(), accepted));
+    let service_tier = req.service_tier;
+    let stream = stream::unfold((chunks, resumed, false, finish_event), move |(mut chunks, count, finished, mut finish_event)| {
+        let error_event = error_event.clone();
+        let error_message = error_message.clone();
+        let frame = frame.clone();
+        let stream_config = stream_config.clone();
+        let lease = lease.clone();
+        let queue_ms = queue_ms.clone();
+        let watermark = watermark.clone();
+        let first_variant = first_variant.clone();
+        let tracker = tracker.clone();
+        let meter = meter.clone();
+        let usage_pending = usage_pending.clone();
+        let clock = clock.clone();
+        let recorder = recorder.clone();
+        let shadow = shadow.clone();
+        let quirks = quirks.clone();
+        let digest = digest.clone();
+        let so_far = so_far.clone();
+        let stored = stored.clone();
+        let state = state.clone();
+        let length_event = length_event.clone();
+        let truncated = truncated.clone();
+        let screen = screen.clone();
+        let filtered_event = filtered_event.clone();
+        let recording = recording.clone();
+        let citations = citations.clone();
+        let tone = tone.clone();
+        let audio_end = audio_end.clone();
+        let audio_id = audio_id.clone();
+        async move {
+            if finished {
+                return None;
+            }
+            if error_after == Some(count) {
+                // Injected failure: report it in-band and end without [DONE]
+                if let Some(tracker) = tracker.as_ref() {
+                    tracker.failed(&error_message);
+                }
+                return Some((Ok::<Bytes, Error>(error_event), (chunks, count, true, None)));
+            }
+            let next = if truncated.get() { None } else { chunks.next() };
+            match next {
+                Some(chunk) => {
+                    let recorded = recording.as_ref().and_then(|r| r.delays.get(count).copied());
+                    let delay = recorded.unwrap_or_else(|| pace.before(&chunk, count == resumed));
+                    // Counting tokens can mean loading the tokenizer, so uncapped streams skip it.
+                    let capped = if lease.is_capped() { lease.delay_for(pace.tokens(&chunk)) } else { Duration::ZERO };
+                    let delay = delay.max(capped)
+                        + screen.as_ref().map_or(Duration::ZERO, |s| s.take_delay());
+                    if let Some(deadline) = deadline.filter(|&deadline| Instant::now() + delay >= deadline) {
+                        // The chunk would land past the deadline, so the stream ends at the deadline instead.
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        if !remaining.is_zero() {
+                            pacer::sleep(remaining, &stream_config).await;
+                        }
+                        return match on_timeout {
+                            TimeoutMode::Soft => {
+                                truncated.set(true);
+                                if let Some(stored) = stored.as_ref() {
+                                    stored.end_for("length");
+                                }
+                                Some((Ok::<Bytes, Error>(length_event), (chunks, count, false, None)))
+                            }
+                            TimeoutMode::Hard => {
+                                if let Some(tracker) = tracker.as_ref() {
+                                    tracker.failed("stream exceeded max_duration_ms");
+                                }
+                                Some((Err(actix_web::error::ErrorGatewayTimeout("stream exceeded max_duration_ms")), (chunks, count, true, None)))
+                            }
+                        };
+                    }
+                    if !delay.is_zero() {
+                        pacer::sleep(delay, &stream_config).await;
+                    }
+
+                    if let Some(tracker) = tracker.as_ref() {
+                        tracker.chunk(&chunk);
+                    }
+                    if let Some(meter) = meter.as_ref() {
+                        meter.chunk(&chunk);
+                    }
+                    if let Some(digest) = digest.borrow_mut().as_mut() {
+                        digest.chunk(&chunk);
+                    }
+                    if let Some(stored) = stored.as_ref() {
+                        stored.chunk(&chunk);
+                    }
+                    if let Some(recorder) = recorder.as_ref() {
+                        recorder.chunk(&chunk);
+                    }
+                    if let Some(shadow) = shadow.as_ref() {
+                        shadow.chunk(&chunk);
+                    }
+                    let role = if count == 0 { role } else { None };
+                    let annotations = citations.as_ref().map(|c| c.completed_by(&chunk)).filter(|a| !a.is_empty());
+                    let spoken = tone.as_ref().as_ref().map(|tone| AudioDelta {
+                        id: (count == resumed).then(|| audio_id.clone()),
+                        ..AudioDelta::spoken(&mut tone.borrow_mut(), chunk.clone())
+                    });
+                    let (content, snapshot) = match content_mode {
+                        ContentMode::Delta => (chunk, None),
+                        ContentMode::Cumulative => {
+                            so_far.borrow_mut().push_str(&chunk);
+                            (so_far.borrow().clone(), None)
+                        }
+                        ContentMode::Both => {
+                            so_far.borrow_mut().push_str(&chunk);
+                            (chunk, Some(so_far.borrow().clone()))
+                        }
+                    };
+                    let event = match (queue_ms.take(), watermark.take(), first_variant.take(), role, snapshot, annotations, frame.as_ref()) {
+                        (None, None, None, None, None, None, Some(frame)) => Ok(frame.render(&content)),
+                        // The first chunk carries the role, reports queueing,
+                        // the watermark and the variant, and snapshots and
+                        // annotations are extra fields, so these cannot use
+                        // the shared template.
+                        (queue_ms, watermark, variant, role, snapshot, annotations, _) => {
+                            let mut event = StreamChunk {
+                                x_mock: (queue_ms.is_some() || watermark.is_some() || variant.is_some()).then(|| MockExtension {
+                                    queue_ms,
+                                    watermark,
+                                    variant,
+                                    ..MockExtension::default()
+                                }),
+                                ..StreamChunk::with_content(content).in_tier(service_tier)
+                            };
+                            event.choices[0].delta.role = role;
+                            event.choices[0].delta.text = snapshot;
+                            event.choices[0].delta.annotations = annotations;
+                            if spoken.is_some() {
+                                event.choices[0].delta.content = None;
+                                event.choices[0].delta.audio = spoken;
+                            }
+                            sse::data_event(&event)
+                        }
+                    };
+                    let event = event.map(|event| quirks.surround(event, count == 0));
+                    match event {
+                        Ok(event) => {
+                            clock.chunk();
+                            Some((Ok::<Bytes, Error>(event), (chunks, count + 1, false, finish_event)))
+                        }
+                        Err(e) => {
+                            // Headers are already sent, so the failure can only be reported in-band.
+                            log::error!("{}", e);
+                            if let Some(tracker) = tracker.as_ref() {
+                                tracker.failed(&e.to_string());
+                            }
+                            Some((Ok::<Bytes, Error>(Bytes::from_static(SERIALIZE_FAILED_EVENT.as_bytes())), (chunks, count, true, None)))
+                        }
+                    }
+                }
+                None => {
+                    if let Some(screen) = screen.as_ref() {
+                        // A guardrail that blocked the whole tail still takes its time.
+                        let delay = screen.take_delay();
+                        if !delay.is_zero() {
+                            pacer::sleep(delay, &stream_config).await;
+                        }
+                        if screen.take_tripped() {
+                            finish_event = Some(filtered_event);
+                            if let Some(stored) = stored.as_ref() {
+                                stored.end_for("content_filter");
+                            }
+                        }
+                    }
+                    if let Some(event) = audio_end.take() {
+                        return Some((Ok::<Bytes, Error>(event), (chunks, count, false, finish_event)));
+                    }
+                    if let Some(event) = finish_event.take() {
+                        return Some((Ok::<Bytes, Error>(event), (chunks, count, false, None)));
+                    }
+                    if let Some(meter) = meter.as_ref().as_ref().filter(|_| usage_pending.replace(false)) {
+                        match sse::data_event(&StreamChunk::usage(meter).in_tier(service_tier)) {
+                            Ok(event) => return Some((Ok::<Bytes, Error>(event), (chunks, count, false, None))),
+                            Err(e) => log::error!("{}", e),
+                        }
+                    }
+                    if let Some(digest) = digest.take() {
+                        match digest.event() {
+                            Ok(event) => return Some((Ok::<Bytes, Error>(event), (chunks, count, false, None))),
+                            Err(e) => log::error!("{}", e),
+                        }
+                    }
+                    if let Some(tracker) = tracker.as_ref() {
+                        tracker.completed();
+                    }
+                    if let Some(meter) = meter.as_ref() {
+                        if let Some(stored) = stored.as_ref() {
+                            stored.finish(&state, meter.usage());
+                        }
+                        meter.finish();
+                    }
+                    if let Some(recorder) = recorder.as_ref() {
+                        recorder.finish(true);
+                    }
+                    if let Some(shadow) = shadow.as_ref() {
+                        shadow.finish(true);
+                    }
+                    // Send [DONE] signal at the end
+                    let done_signal = "data: [DONE]\n\n";
+                    Some((Ok::<Bytes, Error>(Bytes::from(done_signal)), (chunks, count, true, None)))
+                }
+            }
+        }
+    });
+
+    let stream = if config.stream.event_ids {
+        sse::number_events(stream, resumed).boxed_local()
+    } else {
+        stream.boxed_local()
+    };
+
+    let stream = sse::corrupt_frames(stream, profile.frame_faults);
+    let stream = sse::split_writes(stream, profile.write_split, Duration::from_millis(profile.write_split_pause_ms));
+
+    let mut body = writer::apply(
+        stream,
+        config.stream.write_mode,
+        Duration::from_millis(config.stream.coalesce_window_ms),
+    );
+
+    let encoding = match config.stream.compression {
+        CompressionMode::Off => None,
+        CompressionMode::Auto => http_req
+            .headers()
+            .get("Accept-Encoding")
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::negotiate),
+    };
+
+    let mut response = HttpResponse::Ok();
+    if req.stream.is_none() {
+        response.insert_header(("Vary", "Accept"));
+    }
+    response
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("Connection", "keep-alive"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .insert_header(("X-Mock-Seed", req.seed.to_string()))
+        .insert_header(("x-request-id", request_id));
+    if let Some(language) = language {
+        response.insert_header(("X-Mock-Language", language.code()));
+    }
+    if let Some(name) = rule_name {
+        response.insert_header(("X-Mock-Rule", name));
+    }
+    if let Some(variant) = variant {
+        response.insert_header(("X-Mock-Variant", variant));
+    }
+    if replayed {
+        response.insert_header(("X-Mock-Replay", "true"));
+    }
+    insert_cors_headers(&mut response);
+    admission.insert_headers(&mut response);
+    if let Some(budget) = &retry_budget {
+        budget.insert_headers(&mut response);
+    }
+    profile.headers.insert_headers(&mut response);
+
+    if let Some(encoding) = encoding {
+        response
+            .insert_header(("Content-Encoding", encoding.header_value()))
+            .append_header(("Vary", "Accept-Encoding"));
+        body = compression::compress(body, encoding).boxed_local();
+    }
+
+    // Behave like a proxy that ignores `X-Accel-Buffering` and holds the whole reply.
+    if config.stream.simulate_proxy_buffering {
+        body = writer::buffer_all(body).boxed_local();
+    }
+
+    Ok(response.streaming(body))
+}
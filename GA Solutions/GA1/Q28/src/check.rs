@@ -0,0 +1,238 @@
+//! Offline validation of a config file for `stream-api check`, so mistakes
+//! surface before the server is deployed: TOML and schema errors with their
+//! line, unknown presets, stub rules that can never match, malformed
+//! webhook URLs, keys in organizations or projects that do not exist, key
+//! allowlists naming unknown models, and corpus files that cannot be opened.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+#[cfg(feature = "generators")]
+use crate::corpus::Corpus;
+use crate::models::ModelRegistry;
+use crate::presets;
+use crate::stubs::Matcher;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// 1-based line in the file, when one can be pointed at.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Checks the config file at `path`.
+pub fn check_file(path: &str) -> io::Result<Vec<Finding>> {
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    Ok(check_text_in(&std::fs::read_to_string(path)?, dir))
+}
+
+/// Checks config file contents. A file that does not parse reports only that.
+pub fn check_text(text: &str) -> Vec<Finding> {
+    check_text_in(text, Path::new(""))
+}
+
+/// `check_text` for a config file in `dir`, which the paths it names are
+/// relative to.
+pub fn check_text_in(text: &str, dir: &Path) -> Vec<Finding> {
+    let config: Config = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(e) => {
+            return vec![Finding {
+                severity: Severity::Error,
+                line: e.span().map(|span| line_of(text, span.start)),
+                message: e.message().to_string(),
+            }]
+        }
+    };
+    let mut findings = Vec::new();
+    let mut report = |severity, line, message| findings.push(Finding { severity, line, message });
+
+    let mut presets: Vec<_> = config.model_presets.iter().collect();
+    presets.sort();
+    for (model, preset) in presets {
+        if presets::find(preset).is_none() {
+            let line = find_line(text, |l| l.contains(preset.as_str()) && l.contains(model.as_str()));
+            report(
+                Severity::Error,
+                line,
+                format!("model_presets.{} names unknown preset '{}'", model, preset),
+            );
+        }
+    }
+
+    for (i, rule) in config.stubs.iter().enumerate() {
+        let name = rule.name.as_deref().map_or_else(|| format!("stubs[{}]", i), |n| format!("stub rule '{}'", n));
+        let line = nth_header_line(text, "[[stubs]]", i);
+        if !satisfiable(&rule.matcher) {
+            report(Severity::Warning, line, format!("{} can never match: its conditions contradict each other", name));
+        } else if let Some(j) = (0..i).find(|&j| shadows(&config.stubs[j].matcher, &rule.matcher)) {
+            let earlier = config.stubs[j]
+                .name
+                .as_deref()
+                .map_or_else(|| format!("stubs[{}]", j), |n| format!("'{}'", n));
+            report(
+                Severity::Warning,
+                line,
+                format!("{} is unreachable: every prompt it matches is taken by {} first", name, earlier),
+            );
+        }
+    }
+
+    #[cfg(feature = "webhooks")]
+    for (i, hook) in config.webhooks.iter().enumerate() {
+        if let Err(e) = reqwest::Url::parse(&hook.url) {
+            report(
+                Severity::Error,
+                nth_header_line(text, "[[webhooks]]", i),
+                format!("webhooks[{}].url '{}' is not a valid URL: {}", i, hook.url, e),
+            );
+        }
+    }
+    #[cfg(not(feature = "webhooks"))]
+    if !config.webhooks.is_empty() {
+        report(
+            Severity::Warning,
+            nth_header_line(text, "[[webhooks]]", 0),
+            "this build has no webhooks feature, so [[webhooks]] are ignored".to_string(),
+        );
+    }
+
+    let models = ModelRegistry::new(&config.models);
+    for (i, key) in config.keys.iter().enumerate() {
+        if config.keys[..i].iter().any(|k| k.key == key.key) {
+            report(
+                Severity::Warning,
+                nth_header_line(text, "[[keys]]", i),
+                format!("keys[{}] repeats an earlier key; only the first entry's tier applies", i),
+            );
+        }
+        for model in key.models.iter().flatten().filter(|m| models.get(m).is_none()) {
+            report(
+                Severity::Warning,
+                nth_header_line(text, "[[keys]]", i),
+                format!("keys[{}].models lists '{}', which is not a known model", i, model),
+            );
+        }
+        if config.organizations.is_empty() {
+            continue;
+        }
+        let org = key.organization.as_ref().map(|id| (id, config.organizations.iter().find(|o| &o.id == id)));
+        if let Some((id, None)) = org {
+            report(
+                Severity::Error,
+                nth_header_line(text, "[[keys]]", i),
+                format!("keys[{}].organization '{}' is not listed in [[organizations]]", i, id),
+            );
+        } else if let Some(project) = &key.project {
+            let known = match org {
+                Some((_, Some(org))) => org.projects.contains(project),
+                _ => config.organizations.iter().any(|o| o.projects.contains(project)),
+            };
+            if !known {
+                report(
+                    Severity::Error,
+                    nth_header_line(text, "[[keys]]", i),
+                    format!("keys[{}].project '{}' is not a project of its organization", i, project),
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "generators")]
+    if let Some(path) = &config.corpus.path {
+        if let Err(e) = Corpus::open(&dir.join(path)) {
+            report(
+                Severity::Error,
+                find_line(text, |l| l.trim_start().starts_with("path") && l.contains(path.as_str())),
+                format!("corpus.path '{}' cannot be served: {}", path, e),
+            );
+        }
+    }
+    #[cfg(not(feature = "generators"))]
+    if let Some(path) = &config.corpus.path {
+        report(
+            Severity::Warning,
+            find_line(text, |l| l.trim() == "[corpus]"),
+            format!("this build has no generators feature, so the corpus at {} is ignored", dir.join(path).display()),
+        );
+    }
+    findings
+}
+
+/// Whether some prompt satisfies every condition of `m`. Only `equals` is
+/// checked against the rest; other combinations are assumed satisfiable.
+fn satisfiable(m: &Matcher) -> bool {
+    m.equals.as_deref().is_none_or(|prompt| m.matches(prompt))
+}
+
+/// Whether every prompt `later` matches is also matched by `earlier`, judged
+/// conservatively from the conditions themselves.
+fn shadows(earlier: &Matcher, later: &Matcher) -> bool {
+    if !earlier.extensions.within(&later.extensions) {
+        return false;
+    }
+    // A later `equals` pins the prompt down exactly.
+    if let Some(prompt) = later.equals.as_deref() {
+        return earlier.matches(prompt);
+    }
+    let contains = earlier.contains.as_deref().is_none_or(|c| {
+        [&later.contains, &later.starts_with, &later.ends_with]
+            .into_iter()
+            .any(|s| s.as_deref().is_some_and(|s| s.contains(c)))
+    });
+    let starts = earlier
+        .starts_with
+        .as_deref()
+        .is_none_or(|p| later.starts_with.as_deref().is_some_and(|s| s.starts_with(p)));
+    let ends = earlier
+        .ends_with
+        .as_deref()
+        .is_none_or(|p| later.ends_with.as_deref().is_some_and(|s| s.ends_with(p)));
+    let equals = earlier.equals.is_none();
+    let regex = earlier.regex.as_ref().is_none_or(|r| {
+        later
+            .regex
+            .as_ref()
+            .is_some_and(|l| l.regex().as_str() == r.regex().as_str())
+    });
+    contains && starts && ends && equals && regex
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+fn find_line(text: &str, pred: impl Fn(&str) -> bool) -> Option<usize> {
+    text.lines().position(pred).map(|i| i + 1)
+}
+
+/// The line of the `n`th `header` table, for rules written as `[[stubs]]` blocks.
+fn nth_header_line(text: &str, header: &str, n: usize) -> Option<usize> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| l.trim() == header)
+        .nth(n)
+        .map(|(i, _)| i + 1)
+}
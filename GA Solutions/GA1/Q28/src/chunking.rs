@@ -0,0 +1,341 @@
+//! Where a reply is cut into deltas (`chunk_strategy` in a response
+//! profile). By default the generator's fixed-size pieces go out as they
+//! are; the other strategies regroup the same text at natural boundaries,
+//! for testing clients that accumulate or render deltas by unit.
+//!
+//! Regrouping only moves boundaries, so the deltas still join up to exactly
+//! the generated text.
+
+use serde::Deserialize;
+use std::collections::VecDeque;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    /// `chunks` or `chunk_chars` characters per delta, wherever that falls.
+    #[default]
+    Chars,
+    /// One whole sentence per delta, with the whitespace and blank lines
+    /// after it, as Unicode text segmentation (UAX #29) finds them.
+    Sentence,
+    /// At least `chunk_chars` characters per delta, cut only where a word
+    /// starts after whitespace, so every delta ends in whole words and the
+    /// whitespace after them. Text with no spaces to cut at, such as
+    /// Chinese, is cut between two words once a delta grows to four times
+    /// the size.
+    Word,
+}
+
+/// Bytes held while waiting for a boundary before the text goes out
+/// regardless, so a reply without one cannot grow the buffer unbounded.
+const MAX_HELD: usize = 64 * 1024;
+
+/// How far a cut has looked into the held text without finding the end of
+/// its first unit: a byte offset it can resume from once more text arrives,
+/// and the characters before it.
+#[derive(Clone, Copy, Default)]
+struct Scanned {
+    at: usize,
+    chars: usize,
+}
+
+/// Finds the byte length of the first complete unit of the held text, if
+/// it is known to be complete, looking from where the last attempt left off
+/// so a long unit arriving in small pieces is not rescanned for each. The
+/// flag says no more text will follow.
+type Cut = Box<dyn Fn(&str, Scanned, bool) -> Result<usize, Scanned>>;
+
+/// Regroups `chunks` by `strategy`, aiming for `chunk_chars` characters
+/// per delta where the strategy has a size.
+pub fn rechunk(strategy: ChunkStrategy, chunk_chars: usize, chunks: Box<dyn Iterator<Item = String>>) -> Box<dyn Iterator<Item = String>> {
+    let cut: Cut = match strategy {
+        ChunkStrategy::Chars => return chunks,
+        ChunkStrategy::Sentence => Box::new(sentence_end),
+        ChunkStrategy::Word => Box::new(move |text: &str, scanned, ended| word_end(text, scanned, ended, chunk_chars.max(1))),
+    };
+    Box::new(Regrouped {
+        chunks,
+        held: String::new(),
+        scanned: Scanned::default(),
+        ended: false,
+        cut,
+    })
+}
+
+/// Text from `chunks` handed out a unit at a time, as `cut` finds them.
+struct Regrouped {
+    chunks: Box<dyn Iterator<Item = String>>,
+    held: String,
+    scanned: Scanned,
+    ended: bool,
+    cut: Cut,
+}
+
+impl Iterator for Regrouped {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            match (self.cut)(&self.held, self.scanned, self.ended) {
+                Ok(end) if end > 0 => {
+                    let rest = self.held.split_off(end);
+                    self.scanned = Scanned::default();
+                    return Some(std::mem::replace(&mut self.held, rest));
+                }
+                Ok(_) => {}
+                Err(scanned) => self.scanned = scanned,
+            }
+            if self.held.len() >= MAX_HELD || (self.ended && !self.held.is_empty()) {
+                self.scanned = Scanned::default();
+                return Some(std::mem::take(&mut self.held));
+            }
+            if self.ended {
+                return None;
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.held.push_str(&chunk),
+                None => self.ended = true,
+            }
+        }
+    }
+}
+
+/// The end of the first sentence of `text`, with any blank lines after it,
+/// which UAX #29 would otherwise send as sentences of their own. A sentence
+/// is only known to be over once a letter, or the start of another
+/// sentence, follows it: UAX #29 keeps `etc. 5 apples` together by looking
+/// past the digits.
+///
+/// With no boundary yet, the scan resumes at the last letter: the rules only
+/// look back from a boundary over the punctuation and spaces after a full
+/// stop, and the letter before it, and a letter settles any boundary left
+/// open before it.
+fn sentence_end(text: &str, scanned: Scanned, ended: bool) -> Result<usize, Scanned> {
+    let from = scanned.at;
+    let segments: Vec<&str> = text[from..].split_sentence_bounds().collect();
+    let Some((first, mut rest)) = segments.split_first() else {
+        return Err(scanned);
+    };
+    if rest.is_empty() && !ended {
+        let at = text[from..].char_indices().rev().find(|(_, c)| c.is_alphabetic()).map_or(from, |(i, _)| from + i);
+        return Err(Scanned { at, chars: 0 });
+    }
+    let mut end = from + first.len();
+    while let [blank, more @ ..] = rest {
+        if !blank.trim().is_empty() || (more.is_empty() && !ended) {
+            break;
+        }
+        end += blank.len();
+        rest = more;
+    }
+    if ended {
+        return Ok(end);
+    }
+    // Stopped at a blank line that more blank lines might still join.
+    let next = rest.first().filter(|next| !next.trim().is_empty()).ok_or(scanned)?;
+    match next.chars().any(|c| c.is_alphabetic() || matches!(c, '.' | '!' | '?' | '\n')) {
+        true => Ok(end),
+        false => Err(scanned),
+    }
+}
+
+/// Where the first delta of at least `target` characters of `text` can end:
+/// before a word that follows whitespace, or failing that within
+/// `4 * target`, between two words, never next to punctuation. The boundary
+/// before the last word seen is not yet settled between two words, since
+/// UAX #29 looks one character ahead to keep `can't` together.
+///
+/// With no end yet, the scan resumes at the last settled boundary next to
+/// whitespace or between two words: the rules look no further back than the
+/// character before a boundary, so the segments after it come out the same.
+fn word_end(text: &str, scanned: Scanned, ended: bool, target: usize) -> Result<usize, Scanned> {
+    let from = scanned.at;
+    let segments: Vec<(usize, &str)> = text[from..].split_word_bound_indices().collect();
+    let blank = |segment: &str| segment.starts_with(char::is_whitespace);
+    let mut chars = scanned.chars;
+    let mut resume = scanned;
+    for (k, pair) in segments.windows(2).enumerate() {
+        let ((_, before), (start, word)) = (pair[0], pair[1]);
+        chars += before.chars().count();
+        let between_words = before.ends_with(char::is_alphanumeric) && word.starts_with(char::is_alphanumeric);
+        let settled = ended || k + 2 < segments.len();
+        let cut = blank(before) || (chars >= 4 * target && between_words && settled);
+        if chars >= target && !blank(word) && cut {
+            return Ok(from + start);
+        }
+        if settled && (blank(before) || blank(word) || between_words) {
+            resume = Scanned { at: from + start, chars };
+        }
+    }
+    Err(resume)
+}
+
+/// Bytes read past the end of a delta before deciding where it may end,
+/// enough to see any escape sequence through.
+const LOOKAHEAD: usize = 12;
+
+/// Moves cuts out of fence markers and, inside code blocks, out of escape
+/// sequences, for `fence_safe` profiles. A cut that would split one moves
+/// back to where it starts, or forward past it when it starts the delta,
+/// so deltas stay close to their generated sizes.
+pub fn fence_safe(chunks: Box<dyn Iterator<Item = String>>) -> Box<dyn Iterator<Item = String>> {
+    Box::new(FenceSafe {
+        chunks,
+        pending: String::new(),
+        ends: VecDeque::new(),
+        ended: false,
+        markdown: Markdown::default(),
+    })
+}
+
+struct FenceSafe {
+    chunks: Box<dyn Iterator<Item = String>>,
+    /// Text not yet sent.
+    pending: String,
+    /// Where in `pending` each generated delta ends.
+    ends: VecDeque<usize>,
+    ended: bool,
+    /// Where the text before `pending` left off.
+    markdown: Markdown,
+}
+
+impl FenceSafe {
+    fn read(&mut self) {
+        match self.chunks.next() {
+            Some(chunk) => {
+                self.pending.push_str(&chunk);
+                self.ends.push_back(self.pending.len());
+            }
+            None => self.ended = true,
+        }
+    }
+}
+
+impl Iterator for FenceSafe {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            while !self.ended && self.ends.front().is_none_or(|&end| self.pending.len() < end + LOOKAHEAD) {
+                self.read();
+            }
+            let &end = self.ends.front()?;
+            let mut allowed = vec![false; self.pending.len() + 1];
+            self.markdown.scan(&self.pending, Some(&mut allowed));
+            allowed[self.pending.len()] = self.ended;
+            let cut = (1..=end).rev().find(|&i| allowed[i]).or_else(|| (end + 1..allowed.len()).find(|&i| allowed[i]));
+            let Some(cut) = cut else {
+                // A marker or escape runs past what has been read.
+                self.read();
+                continue;
+            };
+            let rest = self.pending.split_off(cut);
+            let delta = std::mem::replace(&mut self.pending, rest);
+            // Text held back from this delta joins the next one.
+            self.ends = self.ends.iter().filter(|&&e| e > end.max(cut)).map(|&e| e - cut).collect();
+            if self.ends.is_empty() && !self.pending.is_empty() {
+                self.ends.push_back(self.pending.len());
+            }
+            self.markdown = self.markdown.scan(&delta, None);
+            return Some(delta);
+        }
+    }
+}
+
+/// How far into Markdown the text so far has got.
+#[derive(Clone, Copy)]
+struct Markdown {
+    /// The character and length of the fence of the open code block.
+    fence: Option<(char, usize)>,
+    /// Whether a fence could still start on the current line, which it can
+    /// after up to three spaces.
+    line_start: bool,
+    indent: usize,
+}
+
+impl Default for Markdown {
+    fn default() -> Self {
+        Markdown {
+            fence: None,
+            line_start: true,
+            indent: 0,
+        }
+    }
+}
+
+impl Markdown {
+    /// Where `text` leaves off. With `allowed`, also marks which of its byte
+    /// offsets a delta may end at: char boundaries outside runs of backticks
+    /// or tildes and, in code blocks, outside escape sequences.
+    fn scan(mut self, text: &str, mut allowed: Option<&mut Vec<bool>>) -> Markdown {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        if let Some(allowed) = allowed.as_deref_mut() {
+            for &(at, _) in &chars {
+                allowed[at] = true;
+            }
+            allowed[0] = false;
+        }
+        let mut forbid = |from: usize, to: usize| {
+            if let Some(allowed) = allowed.as_deref_mut() {
+                for &(at, _) in &chars[from..to] {
+                    allowed[at] = false;
+                }
+            }
+        };
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i].1;
+            if c == '`' || c == '~' {
+                let run = chars[i..].iter().take_while(|&&(_, r)| r == c).count();
+                forbid(i + 1, i + run);
+                if self.line_start && run >= 3 {
+                    self.fence = match self.fence {
+                        None => Some((c, run)),
+                        Some((open, length)) if open == c && run >= length => None,
+                        open => open,
+                    };
+                }
+                self.line_start = false;
+                i += run;
+                continue;
+            }
+            if c == '\\' && self.fence.is_some() {
+                let escape = escape_len(&chars[i + 1..]);
+                forbid(i + 1, (i + 1 + escape).min(chars.len()));
+                self.line_start = chars.get(i + escape).is_some_and(|&(_, last)| last == '\n');
+                self.indent = 0;
+                i += 1 + escape;
+                continue;
+            }
+            match c {
+                '\n' => {
+                    self.line_start = true;
+                    self.indent = 0;
+                }
+                ' ' if self.line_start && self.indent < 3 => self.indent += 1,
+                _ => self.line_start = false,
+            }
+            i += 1;
+        }
+        self
+    }
+}
+
+/// Characters after a backslash that belong to its escape: `\u{...}`,
+/// `\uXXXX`, `\UXXXXXXXX` and `\xXX` take their digits, anything else the
+/// one character.
+fn escape_len(after: &[(usize, char)]) -> usize {
+    let digits = |from: usize, max: usize| after.iter().skip(from).take(max).take_while(|(_, c)| c.is_ascii_hexdigit()).count();
+    match after.first().map(|&(_, c)| c) {
+        None => 0,
+        Some('u') if after.get(1).is_some_and(|&(_, c)| c == '{') => {
+            let close = after.iter().skip(2).take(7).position(|&(_, c)| c == '}');
+            close.map_or(2 + digits(2, 6), |close| close + 3)
+        }
+        Some('u') => 1 + digits(1, 4),
+        Some('U') => 1 + digits(1, 8),
+        Some('x') => 1 + digits(1, 2),
+        Some(_) => 1,
+    }
+}
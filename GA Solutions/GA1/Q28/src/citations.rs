@@ -0,0 +1,183 @@
+//! Web-search style citations (`citations` in a response profile, and the
+//! `mock-search` preset), for testing clients that render OpenAI's
+//! `url_citation` annotations.
+//!
+//! A Markdown link to one of a few fixed sources follows every sentence that
+//! ends in a full stop, as search models add them, and an annotation gives
+//! the link's offsets in the message content. A stream carries each
+//! annotation in `delta.annotations` on the chunk that completes its link; a
+//! JSON reply lists them all on the message. Offsets count Unicode
+//! characters of the text as sent, including a hidden watermark, which is
+//! reported with `insert`; transforms and guardrails that change its length
+//! still move the links away from them.
+
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// The sources cited, in turn: the link text, the page title and its url.
+const SOURCES: &[(&str, &str, &str)] = &[
+    (
+        "developer.mozilla.org",
+        "Using server-sent events - Web APIs | MDN",
+        "https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events/Using_server-sent_events",
+    ),
+    (
+        "html.spec.whatwg.org",
+        "HTML Standard: Server-sent events",
+        "https://html.spec.whatwg.org/multipage/server-sent-events.html",
+    ),
+    (
+        "actix.rs",
+        "Actix Web: Streaming responses",
+        "https://actix.rs/docs/response/",
+    ),
+    (
+        "www.rfc-editor.org",
+        "RFC 9110: HTTP Semantics",
+        "https://www.rfc-editor.org/rfc/rfc9110.html",
+    ),
+    (
+        "tokio.rs",
+        "Tokio: Streams",
+        "https://tokio.rs/tokio/tutorial/streams",
+    ),
+];
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Annotation {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub url_citation: UrlCitation,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct UrlCitation {
+    /// Offset of the link's first character in the message content.
+    pub start_index: usize,
+    /// Offset just past the link's last character.
+    pub end_index: usize,
+    pub url: &'static str,
+    pub title: &'static str,
+}
+
+/// The annotations of one reply, shared between the chunks the links are
+/// written into and the stream that sends them.
+#[derive(Default)]
+pub struct Citations {
+    /// Written into the text, but not yet in a chunk sent.
+    found: RefCell<VecDeque<Annotation>>,
+    /// Characters sent so far.
+    sent: Cell<usize>,
+    /// Text added to the reply after it was cited, as (offset, characters),
+    /// in the order it appears.
+    inserted: RefCell<Vec<(usize, usize)>>,
+}
+
+impl Citations {
+    /// Wraps `chunks` so links are written in after their sentences. The
+    /// first `skip_chars` characters, the prompt header, are left uncited.
+    pub fn wrap(skip_chars: usize, chunks: Box<dyn Iterator<Item = String>>) -> (Rc<Citations>, Box<dyn Iterator<Item = String>>) {
+        let citations = Rc::new(Citations::default());
+        let cited = Cited {
+            chunks,
+            citations: citations.clone(),
+            skip: skip_chars,
+            written: 0,
+            prev_letter: false,
+            after_stop: false,
+            cited: 0,
+        };
+        (citations, Box::new(cited))
+    }
+
+    /// Records `chars` characters added to the reply at offset `at` after
+    /// its links were written in, moving the links after it.
+    pub fn insert(&self, at: usize, chars: usize) {
+        for annotation in self.found.borrow_mut().iter_mut() {
+            let citation = &mut annotation.url_citation;
+            if citation.start_index >= at {
+                citation.start_index += chars;
+                citation.end_index += chars;
+            }
+        }
+        self.inserted.borrow_mut().push((at, chars));
+    }
+
+    /// Where the text written at `offset` ends up once insertions are counted.
+    fn sent_offset(&self, offset: usize) -> usize {
+        self.inserted.borrow().iter().fold(offset, |offset, &(at, chars)| if offset >= at { offset + chars } else { offset })
+    }
+
+    /// The annotations whose links end within `chunk`, the next chunk sent.
+    pub fn completed_by(&self, chunk: &str) -> Vec<Annotation> {
+        let sent = self.sent.get() + chunk.chars().count();
+        self.sent.set(sent);
+        let mut found = self.found.borrow_mut();
+        let due = found.iter().take_while(|a| a.url_citation.end_index <= sent).count();
+        found.drain(..due).collect()
+    }
+}
+
+struct Cited {
+    chunks: Box<dyn Iterator<Item = String>>,
+    citations: Rc<Citations>,
+    /// Header characters still to pass through.
+    skip: usize,
+    /// Characters written so far, links included.
+    written: usize,
+    prev_letter: bool,
+    /// The last character ended a sentence, so a link goes before the
+    /// whitespace that follows it.
+    after_stop: bool,
+    cited: usize,
+}
+
+impl Cited {
+    fn cite(&mut self, out: &mut String) {
+        let (site, title, url) = SOURCES[self.cited % SOURCES.len()];
+        self.cited += 1;
+        let link = format!("([{}]({}))", site, url);
+        out.push(' ');
+        let start_index = self.written + 1;
+        let end_index = start_index + link.chars().count();
+        out.push_str(&link);
+        self.written = end_index;
+        let shift = self.citations.sent_offset(start_index) - start_index;
+        let (start_index, end_index) = (start_index + shift, end_index + shift);
+        self.citations.found.borrow_mut().push_back(Annotation {
+            kind: "url_citation",
+            url_citation: UrlCitation {
+                start_index,
+                end_index,
+                url,
+                title,
+            },
+        });
+    }
+}
+
+impl Iterator for Cited {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let chunk = self.chunks.next()?;
+        let mut out = String::with_capacity(chunk.len());
+        for c in chunk.chars() {
+            if self.skip > 0 {
+                self.skip -= 1;
+            } else {
+                if self.after_stop && c.is_whitespace() {
+                    self.cite(&mut out);
+                }
+                // A letter before the stop, so list numbers such as `1.` are not cited.
+                self.after_stop = c == '.' && self.prev_letter;
+                self.prev_letter = c.is_alphabetic();
+            }
+            out.push(c);
+            self.written += 1;
+        }
+        Some(out)
+    }
+}
@@ -0,0 +1,299 @@
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Load-generation options for `stream-api bench`.
+#[derive(clap::Args, Clone, Debug)]
+pub struct BenchArgs {
+    /// Base URL of the server under test.
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    pub url: String,
+    /// Total number of streaming requests to send.
+    #[arg(long, default_value_t = 20)]
+    pub requests: usize,
+    /// Requests kept in flight at once.
+    #[arg(long, default_value_t = 4)]
+    pub concurrency: usize,
+    #[arg(long, default_value = "Explain streaming APIs")]
+    pub prompt: String,
+    /// Read each stream no faster than this many bytes per second (`512`,
+    /// `4k`, `1m`), to exercise server-side backpressure.
+    #[arg(long, value_parser = parse_rate)]
+    pub read_rate: Option<u64>,
+    /// Give up on a single stream after this many seconds.
+    #[arg(long, default_value_t = 120)]
+    pub timeout_secs: u64,
+    /// Soak instead: keep streams running back to back for this long (`90s`,
+    /// `30m`, `2h`), ignoring `--requests`.
+    #[arg(long, value_parser = parse_duration)]
+    pub duration: Option<Duration>,
+    /// With `--duration`, move concurrency linearly between these levels
+    /// (`0..500`) instead of holding `--concurrency`.
+    #[arg(long, value_parser = parse_ramp)]
+    pub ramp: Option<Ramp>,
+    /// With `--duration`, how often to sample the server's stats.
+    #[arg(long, default_value_t = 5)]
+    pub sample_secs: u64,
+}
+
+/// Concurrency levels at the start and end of a soak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ramp {
+    pub from: usize,
+    pub to: usize,
+}
+
+impl Ramp {
+    /// The concurrency `elapsed` into a soak lasting `total`.
+    pub fn at(&self, elapsed: Duration, total: Duration) -> usize {
+        let progress = (elapsed.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON)).min(1.0);
+        (self.from as f64 + (self.to as f64 - self.from as f64) * progress).round() as usize
+    }
+}
+
+fn parse_ramp(s: &str) -> Result<Ramp, String> {
+    let invalid = || format!("invalid ramp '{}', expected e.g. 0..500", s);
+    let (from, to) = s.split_once("..").ok_or_else(invalid)?;
+    Ok(Ramp {
+        from: from.trim().parse().map_err(|_| invalid())?,
+        to: to.trim().parse().map_err(|_| invalid())?,
+    })
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (digits, scale) = match s.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('s') => (&s[..s.len() - 1], 1),
+        Some('m') => (&s[..s.len() - 1], 60),
+        Some('h') => (&s[..s.len() - 1], 3600),
+        _ => (s, 1),
+    };
+    match digits.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(Duration::from_secs(n * scale)),
+        _ => Err(format!("invalid duration '{}', expected e.g. 90s, 30m or 2h", s)),
+    }
+}
+
+fn parse_rate(s: &str) -> Result<u64, String> {
+    let (digits, scale) = match s.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => (&s[..s.len() - 1], 1024),
+        Some('m') => (&s[..s.len() - 1], 1024 * 1024),
+        _ => (s, 1),
+    };
+    match digits.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n * scale),
+        _ => Err(format!("invalid read rate '{}', expected e.g. 512, 4k or 1m", s)),
+    }
+}
+
+/// How a single stream ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// `[DONE]` was received.
+    Completed,
+    /// The body ended cleanly but without `[DONE]`.
+    Truncated,
+    /// The connection failed or was reset mid-stream.
+    Aborted,
+    /// The server answered with a non-2xx status.
+    Rejected,
+    TimedOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamResult {
+    pub outcome: Outcome,
+    pub first_byte: Option<Duration>,
+    pub total: Duration,
+    pub bytes: usize,
+}
+
+#[derive(Debug)]
+pub struct BenchReport {
+    pub results: Vec<StreamResult>,
+    pub wall_time: Duration,
+    pub server_rss_before: Option<u64>,
+    pub server_rss_peak: Option<u64>,
+    pub server_rss_after: Option<u64>,
+}
+
+/// Runs the benchmark described by `args` against a live server.
+pub async fn run(args: &BenchArgs) -> BenchReport {
+    let client = reqwest::Client::new();
+    let stats_url = format!("{}/v1/internal/stats", args.url.trim_end_matches('/'));
+
+    let server_rss_before = server_rss(&client, &stats_url).await;
+    let started = Instant::now();
+
+    // Sample server memory while the streams run, so growth under slow readers is visible.
+    let (done_tx, mut done_rx) = tokio::sync::oneshot::channel::<()>();
+    let sampler = {
+        let client = client.clone();
+        let stats_url = stats_url.clone();
+        actix_rt::spawn(async move {
+            let mut peak = None;
+            loop {
+                if let Some(rss) = server_rss(&client, &stats_url).await {
+                    peak = Some(peak.map_or(rss, |p: u64| p.max(rss)));
+                }
+                tokio::select! {
+                    _ = &mut done_rx => return peak,
+                    _ = sleep(Duration::from_millis(250)) => {}
+                }
+            }
+        })
+    };
+
+    let results: Vec<StreamResult> = stream::iter(0..args.requests)
+        .map(|_| one_stream(&client, args))
+        .buffer_unordered(args.concurrency.max(1))
+        .collect()
+        .await;
+    let wall_time = started.elapsed();
+
+    let _ = done_tx.send(());
+    let server_rss_peak = sampler.await.ok().flatten();
+    let server_rss_after = server_rss(&client, &stats_url).await;
+
+    BenchReport {
+        results,
+        wall_time,
+        server_rss_before,
+        server_rss_peak,
+        server_rss_after,
+    }
+}
+
+async fn server_rss(client: &reqwest::Client, stats_url: &str) -> Option<u64> {
+    server_process(client, stats_url).await?["rss_bytes"].as_u64()
+}
+
+/// The `process` section of the server's `/v1/internal/stats`.
+pub(crate) async fn server_process(client: &reqwest::Client, stats_url: &str) -> Option<serde_json::Value> {
+    let mut stats: serde_json::Value = client.get(stats_url).send().await.ok()?.json().await.ok()?;
+    Some(stats["process"].take())
+}
+
+pub(crate) async fn one_stream(client: &reqwest::Client, args: &BenchArgs) -> StreamResult {
+    let started = Instant::now();
+    let url = format!("{}/v1/chat/completions", args.url.trim_end_matches('/'));
+    let request = client
+        .post(url)
+        .json(&serde_json::json!({"prompt": args.prompt, "stream": true}))
+        .send();
+
+    let read = async {
+        let resp = match request.await {
+            Ok(resp) => resp,
+            Err(_) => return (Outcome::Aborted, None, 0),
+        };
+        if !resp.status().is_success() {
+            return (Outcome::Rejected, Some(started.elapsed()), 0);
+        }
+        let mut body = resp.bytes_stream();
+        let mut first_byte = None;
+        let mut bytes = 0;
+        let mut tail = Vec::new();
+        while let Some(chunk) = body.next().await {
+            let chunk: Bytes = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => return (Outcome::Aborted, first_byte, bytes),
+            };
+            first_byte.get_or_insert_with(|| started.elapsed());
+            bytes += chunk.len();
+            remember_tail(&mut tail, &chunk);
+            if let Some(rate) = args.read_rate {
+                // Holding off the next poll lets the socket buffers fill up, so
+                // the server sees a genuinely slow reader rather than a fast one.
+                sleep(Duration::from_secs_f64(chunk.len() as f64 / rate as f64)).await;
+            }
+        }
+        let outcome = if String::from_utf8_lossy(&tail).contains("data: [DONE]") {
+            Outcome::Completed
+        } else {
+            Outcome::Truncated
+        };
+        (outcome, first_byte, bytes)
+    };
+
+    match tokio::time::timeout(Duration::from_secs(args.timeout_secs), read).await {
+        Ok((outcome, first_byte, bytes)) => StreamResult {
+            outcome,
+            first_byte,
+            total: started.elapsed(),
+            bytes,
+        },
+        Err(_) => StreamResult {
+            outcome: Outcome::TimedOut,
+            first_byte: None,
+            total: started.elapsed(),
+            bytes: 0,
+        },
+    }
+}
+
+/// Keeps the last few bytes seen, enough to spot a `[DONE]` split across reads.
+fn remember_tail(tail: &mut Vec<u8>, chunk: &[u8]) {
+    const KEEP: usize = 64;
+    tail.extend_from_slice(chunk);
+    if tail.len() > KEEP {
+        tail.drain(..tail.len() - KEEP);
+    }
+}
+
+pub(crate) fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let idx = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[idx]
+}
+
+pub(crate) fn mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+impl BenchReport {
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.results.iter().filter(|r| r.outcome == outcome).count()
+    }
+
+    pub fn print(&self) {
+        let mut totals: Vec<Duration> = self.results.iter().map(|r| r.total).collect();
+        totals.sort();
+        let mut ttfb: Vec<Duration> = self.results.iter().filter_map(|r| r.first_byte).collect();
+        ttfb.sort();
+        let bytes: usize = self.results.iter().map(|r| r.bytes).sum();
+
+        println!("📊 {} streams in {:.2?}", self.results.len(), self.wall_time);
+        println!(
+            "   completed {}  truncated {}  aborted {}  rejected {}  timed out {}",
+            self.count(Outcome::Completed),
+            self.count(Outcome::Truncated),
+            self.count(Outcome::Aborted),
+            self.count(Outcome::Rejected),
+            self.count(Outcome::TimedOut),
+        );
+        println!(
+            "   first byte p50 {:.2?}  p99 {:.2?}",
+            percentile(&ttfb, 0.5),
+            percentile(&ttfb, 0.99)
+        );
+        println!(
+            "   stream time p50 {:.2?}  p99 {:.2?}",
+            percentile(&totals, 0.5),
+            percentile(&totals, 0.99)
+        );
+        println!("   received {} bytes", bytes);
+        match (self.server_rss_before, self.server_rss_peak, self.server_rss_after) {
+            (Some(before), peak, Some(after)) => println!(
+                "   server RSS {} -> peak {} -> {} ({:+.1} MiB)",
+                mib(before),
+                peak.map(mib).unwrap_or_else(|| "?".to_string()),
+                mib(after),
+                (after as f64 - before as f64) / (1024.0 * 1024.0)
+            ),
+            _ => println!("   server RSS unavailable (no /v1/internal/stats)"),
+        }
+    }
+}
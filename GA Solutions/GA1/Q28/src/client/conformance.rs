@@ -0,0 +1,325 @@
+//! `stream-api conformance`: runs the guarantees this mock makes about its
+//! wire format against another endpoint, usually the real provider, and
+//! reports every place the two diverge. A clean run against the provider
+//! means tests written against the mock still describe it; the suite always
+//! passes against the mock itself. With `--compare-url`, the reply is also
+//! diffed against a second endpoint's, to see how closely the mock's text
+//! stands in for the provider's.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::scenario::{Report, StepResult};
+use crate::diffing;
+use crate::sse::Parser;
+
+/// Environment variable holding the provider API key, used when `--api-key`
+/// is not given.
+pub const API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+/// Options for `stream-api conformance`.
+#[derive(clap::Args, Clone, Debug)]
+pub struct ConformanceArgs {
+    /// Base URL of the endpoint to check.
+    #[arg(long, default_value = "https://api.openai.com")]
+    pub url: String,
+    /// API key; overrides OPENAI_API_KEY.
+    #[arg(long)]
+    pub api_key: Option<String>,
+    #[arg(long, default_value = "gpt-4o-mini")]
+    pub model: String,
+    /// Also write the results as JUnit XML to this file.
+    #[arg(long)]
+    pub junit: Option<PathBuf>,
+    /// Give up on a single request after this many seconds.
+    #[arg(long, default_value_t = 60)]
+    pub timeout_secs: u64,
+    /// Base URL of a second endpoint whose reply to the same request the
+    /// reply is diffed against.
+    #[arg(long)]
+    pub compare_url: Option<String>,
+    /// The least token ratio (see `diffing::TokenDiff`) the two replies may
+    /// have for the comparison to pass.
+    #[arg(long, default_value_t = 0.5)]
+    pub min_similarity: f64,
+}
+
+/// One response, read to the end.
+struct Fetched {
+    status: u16,
+    content_type: String,
+    body: Vec<u8>,
+    elapsed: Duration,
+}
+
+/// Runs every check against `args.url`, one test case per guarantee.
+pub async fn run(args: &ConformanceArgs) -> Report {
+    let client = reqwest::Client::new();
+    let api_key = args.api_key.clone().or_else(|| std::env::var(API_KEY_ENV).ok());
+    let started = Instant::now();
+    let mut steps = Vec::new();
+
+    let stream_body = serde_json::json!({
+        "model": args.model,
+        "messages": [{"role": "user", "content": "Say hello in five words."}],
+        "stream": true,
+        "max_tokens": 16,
+    });
+    let streamed = fetch(&client, args, &args.url, api_key.as_deref(), stream_body.to_string()).await;
+    steps.push(judge("streams as text/event-stream", &streamed, check_stream_headers));
+    steps.push(judge("chunks carry choices with deltas", &streamed, check_chunks));
+    steps.push(judge("stream ends with a single [DONE]", &streamed, check_done));
+    if let Some(compare_url) = &args.compare_url {
+        let reference = fetch(&client, args, compare_url, api_key.as_deref(), stream_body.to_string()).await;
+        let name = format!("reply resembles {}", compare_url);
+        steps.push(match (&streamed, &reference) {
+            (Ok(streamed), Ok(reference)) => StepResult {
+                name,
+                elapsed: reference.elapsed,
+                failures: check_similar(streamed, reference, args.min_similarity),
+                error: None,
+            },
+            (Err(e), _) | (_, Err(e)) => StepResult {
+                name,
+                elapsed: Duration::ZERO,
+                failures: Vec::new(),
+                error: Some(e.clone()),
+            },
+        });
+    }
+
+    let missing = serde_json::json!({"model": args.model, "stream": true});
+    let missing = fetch(&client, args, &args.url, api_key.as_deref(), missing.to_string()).await;
+    steps.push(judge("missing messages is a 400 error envelope", &missing, |f| {
+        check_error(f, Some("messages"))
+    }));
+
+    let malformed = fetch(&client, args, &args.url, api_key.as_deref(), r#"{"model": "#.to_string()).await;
+    steps.push(judge("malformed JSON is a 400 error envelope", &malformed, |f| check_error(f, None)));
+
+    Report {
+        scenario: format!("conformance {}", args.url),
+        steps,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Runs the suite, prints the divergences, and writes the JUnit report if one
+/// was asked for. Returns whether the endpoint conformed.
+pub async fn run_cli(args: &ConformanceArgs) -> io::Result<bool> {
+    let report = run(args).await;
+    report.print();
+    if let Some(path) = &args.junit {
+        std::fs::write(path, report.junit())?;
+    }
+    Ok(report.passed())
+}
+
+async fn fetch(client: &reqwest::Client, args: &ConformanceArgs, url: &str, api_key: Option<&str>, body: String) -> Result<Fetched, String> {
+    let mut request = client
+        .post(format!("{}/v1/chat/completions", url.trim_end_matches('/')))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+    let started = Instant::now();
+    let read = async {
+        let resp = request.send().await.map_err(|e| format!("request failed: {}", e))?;
+        let status = resp.status().as_u16();
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = resp.bytes().await.map_err(|e| format!("body could not be read: {}", e))?;
+        Ok(Fetched {
+            status,
+            content_type,
+            body: body.to_vec(),
+            elapsed: started.elapsed(),
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(args.timeout_secs), read)
+        .await
+        .unwrap_or_else(|_| Err(format!("no complete response within {} s", args.timeout_secs)))
+}
+
+fn judge(name: &str, fetched: &Result<Fetched, String>, check: impl Fn(&Fetched) -> Vec<String>) -> StepResult {
+    let (failures, error, elapsed) = match fetched {
+        Ok(fetched) => (check(fetched), None, fetched.elapsed),
+        Err(e) => (Vec::new(), Some(e.clone()), Duration::ZERO),
+    };
+    StepResult {
+        name: name.to_string(),
+        elapsed,
+        failures,
+        error,
+    }
+}
+
+/// The stream's `data:` payloads in order, and any bytes after the last
+/// complete event.
+fn data_events(body: &[u8]) -> (Vec<String>, Vec<u8>) {
+    let mut parser = Parser::new();
+    let data = parser.push(body).into_iter().map(|event| event.data).collect();
+    (data, parser.remainder().to_vec())
+}
+
+fn check_stream_headers(fetched: &Fetched) -> Vec<String> {
+    let mut failures = Vec::new();
+    if fetched.status != 200 {
+        failures.push(format!("status {}: {}", fetched.status, snippet(&fetched.body)));
+    }
+    if !fetched.content_type.starts_with("text/event-stream") {
+        failures.push(format!("Content-Type {:?}, expected text/event-stream", fetched.content_type));
+    }
+    failures
+}
+
+fn check_chunks(fetched: &Fetched) -> Vec<String> {
+    if fetched.status != 200 {
+        return vec![format!("status {}, so there are no chunks to check", fetched.status)];
+    }
+    let (data, _) = data_events(&fetched.body);
+    let mut failures = Vec::new();
+    let mut role_checked = false;
+    let mut finished_at = None;
+    let mut content_chunks = 0;
+    for (i, data) in data.iter().enumerate().filter(|(_, d)| d.as_str() != "[DONE]") {
+        let value: serde_json::Value = match serde_json::from_str(data) {
+            Ok(value) => value,
+            Err(e) => {
+                failures.push(format!("chunk {} is not JSON ({}): {}", i, e, data));
+                continue;
+            }
+        };
+        let Some(choices) = value["choices"].as_array() else {
+            failures.push(format!("chunk {} has no choices array", i));
+            continue;
+        };
+        for choice in choices {
+            let delta = &choice["delta"];
+            if !delta.is_object() {
+                failures.push(format!("chunk {} has a choice without a delta object", i));
+                continue;
+            }
+            if !role_checked {
+                role_checked = true;
+                if delta["role"] != "assistant" {
+                    failures.push(format!("the first delta's role is {}, expected \"assistant\"", delta["role"]));
+                }
+            }
+            match &delta["content"] {
+                serde_json::Value::String(content) if !content.is_empty() => {
+                    content_chunks += 1;
+                    if let Some(at) = finished_at {
+                        failures.push(format!("chunk {} carries content after finish_reason in chunk {}", i, at));
+                    }
+                }
+                serde_json::Value::String(_) | serde_json::Value::Null => {}
+                other => failures.push(format!("chunk {} has a non-string content {}", i, other)),
+            }
+            match &choice["finish_reason"] {
+                serde_json::Value::Null => {}
+                serde_json::Value::String(_) => finished_at = finished_at.or(Some(i)),
+                other => failures.push(format!("chunk {} has a non-string finish_reason {}", i, other)),
+            }
+        }
+    }
+    if content_chunks == 0 {
+        failures.push("no chunk carried content".to_string());
+    }
+    failures
+}
+
+fn check_done(fetched: &Fetched) -> Vec<String> {
+    if fetched.status != 200 {
+        return vec![format!("status {}, so there is no stream to check", fetched.status)];
+    }
+    let (data, trailing) = data_events(&fetched.body);
+    let mut failures = Vec::new();
+    match data.iter().filter(|d| d.as_str() == "[DONE]").count() {
+        0 => failures.push("no data: [DONE] event".to_string()),
+        1 if data.last().is_some_and(|d| d == "[DONE]") => {}
+        1 => failures.push("events follow data: [DONE]".to_string()),
+        n => failures.push(format!("data: [DONE] sent {} times", n)),
+    }
+    if !trailing.iter().all(u8::is_ascii_whitespace) {
+        failures.push(format!("stream ends mid-event: {}", snippet(&trailing)));
+    }
+    failures
+}
+
+/// The two replies' text, close enough by token ratio.
+fn check_similar(streamed: &Fetched, reference: &Fetched, min_similarity: f64) -> Vec<String> {
+    let mut failures = Vec::new();
+    for (which, fetched) in [("checked", streamed), ("comparison", reference)] {
+        if fetched.status != 200 {
+            failures.push(format!("the {} endpoint answered {}, so there is no reply to compare", which, fetched.status));
+        }
+    }
+    if !failures.is_empty() {
+        return failures;
+    }
+    let text = |fetched: &Fetched| diffing::stream_text(&String::from_utf8_lossy(&fetched.body));
+    let diff = diffing::diff(&text(streamed), &text(reference));
+    if diff.tokens.ratio < min_similarity {
+        failures.push(format!(
+            "token ratio {:.2} is below {:.2} (semantic distance {}/64, first difference at character {})",
+            diff.tokens.ratio,
+            min_similarity,
+            diff.semantic.distance,
+            diff.diverges_at.unwrap_or_default()
+        ));
+    }
+    failures
+}
+
+/// `{"error": {"message", "type", "param", "code"}}` with status 400 and,
+/// when given, the offending parameter.
+fn check_error(fetched: &Fetched, param: Option<&str>) -> Vec<String> {
+    let mut failures = Vec::new();
+    if fetched.status != 400 {
+        failures.push(format!("status {}, expected 400", fetched.status));
+    }
+    let value: serde_json::Value = match serde_json::from_slice(&fetched.body) {
+        Ok(value) => value,
+        Err(_) => {
+            failures.push(format!("body is not JSON: {}", snippet(&fetched.body)));
+            return failures;
+        }
+    };
+    let error = &value["error"];
+    if !error.is_object() {
+        failures.push(format!("body has no error object: {}", value));
+        return failures;
+    }
+    if error["message"].as_str().is_none_or(str::is_empty) {
+        failures.push("error.message is missing or empty".to_string());
+    }
+    if error["type"] != "invalid_request_error" {
+        failures.push(format!("error.type is {}, expected \"invalid_request_error\"", error["type"]));
+    }
+    for key in ["param", "code"] {
+        if error.get(key).is_none() {
+            failures.push(format!("error.{} is absent, expected a value or null", key));
+        }
+    }
+    if let Some(param) = param {
+        if error["param"] != param {
+            failures.push(format!("error.param is {}, expected {:?}", error["param"], param));
+        }
+    }
+    failures
+}
+
+fn snippet(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    match text.char_indices().nth(200) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text.into_owned(),
+    }
+}
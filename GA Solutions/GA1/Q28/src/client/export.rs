@@ -0,0 +1,48 @@
+use std::io;
+use std::path::PathBuf;
+
+use crate::export::ExportFormat;
+
+/// Environment variable holding the admin token, used when `--token` is not given.
+pub const TOKEN_ENV: &str = "STREAM_API_ADMIN_TOKEN";
+
+/// Options for `stream-api export`.
+#[derive(clap::Args, Clone, Debug)]
+pub struct ExportArgs {
+    /// Base URL of the running server.
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    pub url: String,
+    /// Admin token; overrides STREAM_API_ADMIN_TOKEN.
+    #[arg(long)]
+    pub token: Option<String>,
+    #[arg(long, value_enum, default_value_t = ExportFormat::Finetune)]
+    pub format: ExportFormat,
+    /// File to write; standard output when omitted.
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+/// Downloads the server's captures in `args.format` and writes them out.
+pub async fn run(args: &ExportArgs) -> io::Result<()> {
+    let token = args
+        .token
+        .clone()
+        .or_else(|| std::env::var(TOKEN_ENV).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("--token or {} is required", TOKEN_ENV)))?;
+    let resp = reqwest::Client::new()
+        .get(format!("{}/v1/admin/captures/export", args.url.trim_end_matches('/')))
+        .query(&[("format", args.format.name())])
+        .bearer_auth(token)
+        .send()
+        .await
+        .map_err(io::Error::other)?;
+    let status = resp.status();
+    let body = resp.bytes().await.map_err(io::Error::other)?;
+    if !status.is_success() {
+        return Err(io::Error::other(format!("server answered {}: {}", status, String::from_utf8_lossy(&body))));
+    }
+    match &args.output {
+        Some(path) => std::fs::write(path, &body),
+        None => io::Write::write_all(&mut io::stdout(), &body),
+    }
+}
@@ -0,0 +1,8 @@
+//! Client-side tooling that drives a running server.
+
+pub mod bench;
+pub mod conformance;
+#[cfg(feature = "recording")]
+pub mod export;
+pub mod scenario;
+pub mod soak;
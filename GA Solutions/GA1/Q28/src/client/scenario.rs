@@ -0,0 +1,421 @@
+//! `stream-api scenario run`: executes a scripted sequence of requests against
+//! a server, checks each response against the step's expectations, and
+//! reports the results, optionally as JUnit XML for CI. Because it only speaks
+//! HTTP, the same scenario files can check any OpenAI-compatible endpoint.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use serde::Deserialize;
+
+use crate::sse::{Event, Parser};
+
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum ScenarioCommand {
+    /// Run a scenario file and report each step.
+    Run(RunArgs),
+}
+
+/// Options for `stream-api scenario run`.
+#[derive(clap::Args, Clone, Debug)]
+pub struct RunArgs {
+    /// Scenario file (YAML).
+    pub file: PathBuf,
+    /// Base URL of the server under test.
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    pub url: String,
+    /// Also write the results as JUnit XML to this file.
+    #[arg(long)]
+    pub junit: Option<PathBuf>,
+}
+
+/// A named sequence of steps, run in order.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// Headers sent with every step, e.g. `Authorization`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub name: String,
+    #[serde(default)]
+    pub request: StepRequest,
+    #[serde(default)]
+    pub expect: Expect,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StepRequest {
+    pub path: String,
+    pub headers: BTreeMap<String, String>,
+    /// JSON body, written as YAML.
+    pub body: serde_json::Value,
+    /// Give up on the response after this long.
+    pub timeout_ms: u64,
+}
+
+impl Default for StepRequest {
+    fn default() -> Self {
+        StepRequest {
+            path: "/v1/chat/completions".to_string(),
+            headers: BTreeMap::new(),
+            body: serde_json::json!({"messages": [{"role": "user", "content": "Hello"}], "stream": true}),
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+/// What a step's response must look like. Unset fields are not checked,
+/// except `status`, which defaults to any 2xx.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Expect {
+    pub status: Option<u16>,
+    /// Number of content deltas.
+    pub min_chunks: Option<usize>,
+    pub max_chunks: Option<usize>,
+    pub content_contains: Option<String>,
+    pub finish_reason: Option<String>,
+    /// Whether the stream must (or must not) end with `data: [DONE]`.
+    pub done: Option<bool>,
+    /// Whether an error must (or must not) be reported, in the body or in-band.
+    pub error: Option<bool>,
+    /// `error.type` of the error body or of an in-band stream error.
+    pub error_type: Option<String>,
+    pub min_first_chunk_ms: Option<u64>,
+    pub max_first_chunk_ms: Option<u64>,
+    pub max_total_ms: Option<u64>,
+}
+
+/// What came back for one step.
+#[derive(Debug, Clone, Default)]
+pub struct Observed {
+    pub status: u16,
+    pub chunks: usize,
+    pub content: String,
+    pub finish_reason: Option<String>,
+    pub done: bool,
+    pub error: bool,
+    pub error_type: Option<String>,
+    /// Time to the first SSE data event, for streamed responses.
+    pub first_chunk: Option<Duration>,
+    pub total: Duration,
+    /// Why the body could not be read to the end, if it could not.
+    pub aborted: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub name: String,
+    pub elapsed: Duration,
+    /// Expectations that did not hold.
+    pub failures: Vec<String>,
+    /// Set when the request could not be made or timed out.
+    pub error: Option<String>,
+}
+
+impl StepResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty() && self.error.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub scenario: String,
+    pub steps: Vec<StepResult>,
+    pub elapsed: Duration,
+}
+
+/// Reads and parses a scenario file.
+pub fn load(path: &Path) -> io::Result<Scenario> {
+    let text = std::fs::read_to_string(path)?;
+    serde_yaml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// Runs a loaded scenario against the server at `url`, one step at a time.
+pub async fn run(scenario: &Scenario, url: &str) -> Report {
+    let client = reqwest::Client::new();
+    let started = Instant::now();
+    let mut steps = Vec::with_capacity(scenario.steps.len());
+    for step in &scenario.steps {
+        let step_started = Instant::now();
+        let (failures, error) = match send(&client, url, &scenario.headers, &step.request).await {
+            Ok(observed) => (step.expect.check(&observed), None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        steps.push(StepResult {
+            name: step.name.clone(),
+            elapsed: step_started.elapsed(),
+            failures,
+            error,
+        });
+    }
+    Report {
+        scenario: scenario.name.clone(),
+        steps,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Runs the file named by `args`, prints the results, and writes the JUnit
+/// report if one was asked for. Returns whether every step passed.
+pub async fn run_file(args: &RunArgs) -> io::Result<bool> {
+    let scenario = load(&args.file)?;
+    let report = run(&scenario, &args.url).await;
+    report.print();
+    if let Some(path) = &args.junit {
+        std::fs::write(path, report.junit())?;
+    }
+    Ok(report.passed())
+}
+
+async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    headers: &BTreeMap<String, String>,
+    request: &StepRequest,
+) -> Result<Observed, String> {
+    let started = Instant::now();
+    let mut builder = client.post(format!("{}{}", url.trim_end_matches('/'), request.path)).json(&request.body);
+    for (name, value) in headers.iter().chain(&request.headers) {
+        builder = builder.header(name, value);
+    }
+    let read = async {
+        let resp = builder.send().await.map_err(|e| format!("request failed: {}", e))?;
+        let mut observed = Observed {
+            status: resp.status().as_u16(),
+            ..Observed::default()
+        };
+        let streamed = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if streamed {
+            read_stream(resp, started, &mut observed).await;
+        } else {
+            match resp.bytes().await {
+                Ok(body) => observe_body(&body, &mut observed),
+                Err(e) => observed.aborted = Some(e.to_string()),
+            }
+        }
+        observed.total = started.elapsed();
+        Ok(observed)
+    };
+    tokio::time::timeout(Duration::from_millis(request.timeout_ms), read)
+        .await
+        .unwrap_or_else(|_| Err(format!("no complete response within {} ms", request.timeout_ms)))
+}
+
+async fn read_stream(resp: reqwest::Response, started: Instant, observed: &mut Observed) {
+    let mut body = resp.bytes_stream();
+    let mut parser = Parser::new();
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                observed.aborted = Some(e.to_string());
+                return;
+            }
+        };
+        for event in parser.push(&chunk) {
+            observe_event(&event, started.elapsed(), observed);
+        }
+    }
+}
+
+fn observe_event(event: &Event, at: Duration, observed: &mut Observed) {
+    observed.first_chunk.get_or_insert(at);
+    if event.is_done() {
+        observed.done = true;
+        return;
+    }
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(&event.data) else {
+        return;
+    };
+    observe_error(&value, observed);
+    let choice = &value["choices"][0];
+    if let Some(text) = choice["delta"]["content"].as_str() {
+        observed.chunks += 1;
+        observed.content.push_str(text);
+    }
+    if let Some(reason) = choice["finish_reason"].as_str() {
+        observed.finish_reason = Some(reason.to_string());
+    }
+}
+
+/// Errors come as `{"error": {"type": ..}}`, or in this mock's own in-band
+/// form as `{"error": "message"}`.
+fn observe_error(value: &serde_json::Value, observed: &mut Observed) {
+    if value.get("error").is_some_and(|e| !e.is_null()) {
+        observed.error = true;
+        if let Some(kind) = value["error"]["type"].as_str() {
+            observed.error_type = Some(kind.to_string());
+        }
+    }
+}
+
+fn observe_body(body: &[u8], observed: &mut Observed) {
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return;
+    };
+    observe_error(&value, observed);
+    let choice = &value["choices"][0];
+    if let Some(text) = choice["message"]["content"].as_str() {
+        observed.chunks = 1;
+        observed.content = text.to_string();
+    }
+    if let Some(reason) = choice["finish_reason"].as_str() {
+        observed.finish_reason = Some(reason.to_string());
+    }
+}
+
+impl Expect {
+    /// Every expectation `observed` breaks, in a readable form.
+    pub fn check(&self, observed: &Observed) -> Vec<String> {
+        let mut failures = Vec::new();
+        match self.status {
+            Some(status) if status != observed.status => {
+                failures.push(format!("status {}, expected {}", observed.status, status))
+            }
+            None if !(200..300).contains(&observed.status) => {
+                failures.push(format!("status {}, expected 2xx", observed.status))
+            }
+            _ => {}
+        }
+        if let Some(min) = self.min_chunks.filter(|&min| observed.chunks < min) {
+            failures.push(format!("{} chunk(s), expected at least {}", observed.chunks, min));
+        }
+        if let Some(max) = self.max_chunks.filter(|&max| observed.chunks > max) {
+            failures.push(format!("{} chunk(s), expected at most {}", observed.chunks, max));
+        }
+        if let Some(needle) = self.content_contains.as_deref().filter(|n| !observed.content.contains(n)) {
+            failures.push(format!("content does not contain {:?}", needle));
+        }
+        if let Some(reason) = &self.finish_reason {
+            if observed.finish_reason.as_ref() != Some(reason) {
+                failures.push(format!("finish_reason {:?}, expected {:?}", observed.finish_reason, reason));
+            }
+        }
+        match (self.done, observed.done) {
+            (Some(true), false) => failures.push(match &observed.aborted {
+                Some(e) => format!("stream ended without [DONE]: {}", e),
+                None => "stream ended without [DONE]".to_string(),
+            }),
+            (Some(false), true) => failures.push("stream ended with [DONE], expected it not to".to_string()),
+            _ => {}
+        }
+        match (self.error, observed.error) {
+            (Some(true), false) => failures.push("no error reported".to_string()),
+            (Some(false), true) => failures.push(format!("error reported: {:?}", observed.error_type)),
+            _ => {}
+        }
+        if let Some(kind) = &self.error_type {
+            if observed.error_type.as_ref() != Some(kind) {
+                failures.push(format!("error type {:?}, expected {:?}", observed.error_type, kind));
+            }
+        }
+        let first_ms = observed.first_chunk.map(|d| d.as_millis() as u64);
+        if let Some(min) = self.min_first_chunk_ms {
+            if first_ms.is_none_or(|ms| ms < min) {
+                failures.push(format!("first chunk after {:?} ms, expected at least {} ms", first_ms, min));
+            }
+        }
+        if let Some(max) = self.max_first_chunk_ms {
+            if first_ms.is_none_or(|ms| ms > max) {
+                failures.push(format!("first chunk after {:?} ms, expected at most {} ms", first_ms, max));
+            }
+        }
+        let total_ms = observed.total.as_millis() as u64;
+        if let Some(max) = self.max_total_ms.filter(|&max| total_ms > max) {
+            failures.push(format!("took {} ms, expected at most {} ms", total_ms, max));
+        }
+        failures
+    }
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(StepResult::passed)
+    }
+
+    pub fn print(&self) {
+        println!("🎬 {}", self.scenario);
+        for step in &self.steps {
+            let mark = if step.passed() { "✅" } else { "❌" };
+            println!("   {} {} ({:.2?})", mark, step.name, step.elapsed);
+            for problem in step.error.iter().chain(&step.failures) {
+                println!("      {}", problem);
+            }
+        }
+        let passed = self.steps.iter().filter(|s| s.passed()).count();
+        println!("   {}/{} step(s) passed in {:.2?}", passed, self.steps.len(), self.elapsed);
+    }
+
+    /// The results as a JUnit XML document with one test case per step.
+    pub fn junit(&self) -> String {
+        let failures = self.steps.iter().filter(|s| s.error.is_none() && !s.failures.is_empty()).count();
+        let errors = self.steps.iter().filter(|s| s.error.is_some()).count();
+        let counts = format!(
+            r#"name="{}" tests="{}" failures="{}" errors="{}" time="{:.3}""#,
+            xml_escape(&self.scenario),
+            self.steps.len(),
+            failures,
+            errors,
+            self.elapsed.as_secs_f64()
+        );
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(xml, "<testsuites {}>", counts);
+        let _ = writeln!(xml, "  <testsuite {}>", counts);
+        for step in &self.steps {
+            let _ = write!(
+                xml,
+                r#"    <testcase name="{}" classname="{}" time="{:.3}""#,
+                xml_escape(&step.name),
+                xml_escape(&self.scenario),
+                step.elapsed.as_secs_f64()
+            );
+            if let Some(error) = &step.error {
+                let _ = writeln!(xml, ">\n      <error message=\"{}\"/>\n    </testcase>", xml_escape(error));
+            } else if let Some(first) = step.failures.first() {
+                let _ = writeln!(
+                    xml,
+                    ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                    xml_escape(first),
+                    xml_escape(&step.failures.join("\n"))
+                );
+            } else {
+                xml.push_str("/>\n");
+            }
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,163 @@
+//! Soak mode for `stream-api bench --duration`: keeps streams running back to
+//! back for a fixed time, optionally ramping concurrency, while sampling the
+//! server's memory and open descriptors, to find how many concurrent streams
+//! a deployment sustains and whether it leaks over time.
+
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use futures::future;
+use tokio::time::{sleep, timeout_at, Instant};
+
+use super::bench::{mib, one_stream, server_process, BenchArgs, Outcome, StreamResult};
+
+/// The state of the soak at one point in time.
+#[derive(Debug, Clone)]
+pub struct SoakSample {
+    pub at: Duration,
+    /// Concurrency the ramp called for.
+    pub target: usize,
+    /// Streams actually in flight.
+    pub active: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub rss_bytes: Option<u64>,
+    pub open_fds: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct SoakReport {
+    pub duration: Duration,
+    /// Streams that finished before the soak ended; ones still running at the
+    /// end are cut off and not counted.
+    pub results: Vec<StreamResult>,
+    pub samples: Vec<SoakSample>,
+    pub peak_active: usize,
+    /// When the first stream did not complete, and how many were in flight.
+    pub first_failure: Option<(Duration, usize)>,
+}
+
+/// Runs streams against `args.url` for `duration`.
+pub async fn run(args: &BenchArgs, duration: Duration) -> SoakReport {
+    let client = reqwest::Client::new();
+    let stats_url = format!("{}/v1/internal/stats", args.url.trim_end_matches('/'));
+    let started = Instant::now();
+    let deadline = started + duration;
+    let target = |elapsed: Duration| args.ramp.map_or(args.concurrency, |r| r.at(elapsed, duration));
+    let workers = args.ramp.map_or(args.concurrency, |r| r.from.max(r.to));
+
+    let active = Cell::new(0);
+    let peak_active = Cell::new(0);
+    let first_failure = Cell::new(None);
+    let results = RefCell::new(Vec::new());
+
+    // Worker `i` streams while the ramp calls for more than `i` streams.
+    let worker = |i: usize| {
+        let (client, active, peak_active, first_failure, results) = (&client, &active, &peak_active, &first_failure, &results);
+        async move {
+            while started.elapsed() < duration {
+                if target(started.elapsed()) <= i {
+                    let _ = timeout_at(deadline, sleep(Duration::from_millis(50))).await;
+                    continue;
+                }
+                active.set(active.get() + 1);
+                peak_active.set(peak_active.get().max(active.get()));
+                let in_flight = active.get();
+                let result = timeout_at(deadline, one_stream(client, args)).await;
+                active.set(active.get() - 1);
+                let Ok(result) = result else { break };
+                if result.outcome != Outcome::Completed && first_failure.get().is_none() {
+                    first_failure.set(Some((started.elapsed(), in_flight)));
+                }
+                results.borrow_mut().push(result);
+            }
+        }
+    };
+
+    let sample = || async {
+        let process = server_process(&client, &stats_url).await;
+        let results = results.borrow();
+        let completed = results.iter().filter(|r| r.outcome == Outcome::Completed).count();
+        SoakSample {
+            at: started.elapsed(),
+            target: target(started.elapsed()),
+            active: active.get(),
+            completed,
+            failed: results.len() - completed,
+            rss_bytes: process.as_ref().and_then(|p| p["rss_bytes"].as_u64()),
+            open_fds: process.as_ref().and_then(|p| p["open_fds"].as_u64()),
+        }
+    };
+    let sampler = async {
+        let every = Duration::from_secs(args.sample_secs.max(1));
+        let mut samples = Vec::new();
+        let mut next = started;
+        while next < deadline {
+            sleep(next.saturating_duration_since(Instant::now())).await;
+            samples.push(sample().await);
+            next += every;
+        }
+        samples
+    };
+
+    let streams = async {
+        future::join_all((0..workers).map(worker)).await;
+    };
+    let ((), mut samples) = future::join(streams, sampler).await;
+    samples.push(sample().await);
+
+    SoakReport {
+        duration: started.elapsed(),
+        results: results.into_inner(),
+        samples,
+        peak_active: peak_active.get(),
+        first_failure: first_failure.get(),
+    }
+}
+
+impl SoakReport {
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.results.iter().filter(|r| r.outcome == outcome).count()
+    }
+
+    pub fn print(&self) {
+        println!("🧪 soak: {} streams in {:.0?}", self.results.len(), self.duration);
+        println!("   {:>7}  {:>6}  {:>6}  {:>9}  {:>6}  {:>10}  {:>5}", "t", "target", "active", "completed", "failed", "server RSS", "fds");
+        for s in &self.samples {
+            println!(
+                "   {:>7}  {:>6}  {:>6}  {:>9}  {:>6}  {:>10}  {:>5}",
+                format!("{}s", s.at.as_secs()),
+                s.target,
+                s.active,
+                s.completed,
+                s.failed,
+                s.rss_bytes.map(mib).unwrap_or_else(|| "?".to_string()),
+                s.open_fds.map_or_else(|| "?".to_string(), |n| n.to_string()),
+            );
+        }
+        println!(
+            "   completed {}  truncated {}  aborted {}  rejected {}  timed out {}",
+            self.count(Outcome::Completed),
+            self.count(Outcome::Truncated),
+            self.count(Outcome::Aborted),
+            self.count(Outcome::Rejected),
+            self.count(Outcome::TimedOut),
+        );
+        match self.first_failure {
+            Some((at, in_flight)) => println!("   first failure after {:.0?} with {} streams in flight", at, in_flight),
+            None => println!("   no failures with up to {} streams in flight", self.peak_active),
+        }
+        let rss = self.samples.iter().filter_map(|s| s.rss_bytes);
+        if let (Some(first), Some(last)) = (self.samples.first().and_then(|s| s.rss_bytes), self.samples.last().and_then(|s| s.rss_bytes)) {
+            println!(
+                "   server RSS {} -> peak {} -> {}",
+                mib(first),
+                mib(rss.max().unwrap_or(last)),
+                mib(last)
+            );
+        }
+        if let Some(peak) = self.samples.iter().filter_map(|s| s.open_fds).max() {
+            println!("   server fds peak {}", peak);
+        }
+    }
+}
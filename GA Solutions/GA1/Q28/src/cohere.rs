@@ -0,0 +1,280 @@
+//! A Cohere-shaped `/v1/chat`: the request's `message`, `chat_history` and
+//! `preamble` are served by the same stub rules, presets, transforms and
+//! pacing as chat completions, streamed as Cohere's newline-delimited
+//! `stream-start`, `text-generation` and `stream-end` events. Errors use
+//! Cohere's `{"message": ...}` body.
+
+use actix_web::body::BoxBody;
+use actix_web::http::StatusCode;
+use actix_web::{post, web, Error, HttpRequest, HttpResponse, ResponseError};
+use bytes::Bytes;
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+use crate::admission::{self, Admission, Paced, Recorded, Step};
+use crate::capture::StreamRecorder;
+use crate::chat::{self, ChatRequest, IncomingRequest, Message, MessageContent, NormalizedRequest, ReplyText};
+use crate::config::Config;
+use crate::error::{self, MockError};
+use crate::lifecycle::Tracker;
+use crate::overrides::MockOverrides;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct CohereRequest {
+    pub message: String,
+    #[serde(default)]
+    pub chat_history: Vec<CohereMessage>,
+    #[serde(default)]
+    pub preamble: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+}
+
+/// A turn of `chat_history`, with Cohere's `USER`, `CHATBOT` and `SYSTEM` roles.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CohereMessage {
+    pub role: String,
+    pub message: String,
+}
+
+impl From<CohereRequest> for NormalizedRequest {
+    fn from(req: CohereRequest) -> Self {
+        let turn = |role: &str, text: String| Message {
+            role: role.to_string(),
+            content: Some(MessageContent::Text(text)),
+            name: None,
+        };
+        let mut messages: Vec<Message> = req.preamble.map(|p| turn("system", p)).into_iter().collect();
+        messages.extend(req.chat_history.into_iter().map(|m| {
+            let role = match m.role.to_ascii_uppercase().as_str() {
+                "CHATBOT" => "assistant",
+                "SYSTEM" => "system",
+                _ => "user",
+            };
+            turn(role, m.message)
+        }));
+        messages.push(turn("user", req.message));
+        NormalizedRequest::from(IncomingRequest::Chat(ChatRequest {
+            model: req.model,
+            messages,
+            stream: Some(req.stream),
+            seed: req.seed,
+            max_tokens: req.max_tokens,
+            ..ChatRequest::default()
+        }))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event_type")]
+enum CohereEvent<'a> {
+    #[serde(rename = "stream-start")]
+    StreamStart { is_finished: bool, generation_id: &'a str },
+    #[serde(rename = "text-generation")]
+    TextGeneration { is_finished: bool, text: &'a str },
+    #[serde(rename = "stream-end")]
+    StreamEnd {
+        is_finished: bool,
+        finish_reason: &'a str,
+        response: CohereResponse<'a>,
+    },
+}
+
+#[derive(Serialize)]
+struct CohereResponse<'a> {
+    response_id: &'a str,
+    generation_id: &'a str,
+    text: &'a str,
+    finish_reason: &'a str,
+    chat_history: Vec<CohereMessage>,
+    meta: CohereMeta,
+}
+
+#[derive(Serialize)]
+struct CohereMeta {
+    billed_units: BilledUnits,
+}
+
+#[derive(Serialize)]
+struct BilledUnits {
+    input_tokens: usize,
+    output_tokens: usize,
+}
+
+fn ndjson_line(event: &CohereEvent) -> Result<Bytes, MockError> {
+    let mut line = serde_json::to_vec(event).map_err(|e| MockError::Serialize(e.to_string()))?;
+    line.push(b'\n');
+    Ok(Bytes::from(line))
+}
+
+/// `error` with Cohere's body, keeping the status and `Retry-After`.
+fn cohere_error(error: &MockError) -> HttpResponse {
+    let body = serde_json::json!({"message": error.to_string()}).to_string();
+    error.error_response().set_body(BoxBody::new(body))
+}
+
+#[post("/v1/chat")]
+pub async fn chat_endpoint(
+    http_req: HttpRequest,
+    body: web::Json<CohereRequest>,
+    overrides: MockOverrides,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let history = body.chat_history.clone();
+    match stream_reply(http_req, NormalizedRequest::from(body), history, overrides, config, state).await {
+        Ok(response) => response,
+        Err(e) => cohere_error(&e),
+    }
+}
+
+struct Progress {
+    paced: Paced,
+    text: String,
+    started: bool,
+    finished: bool,
+}
+
+async fn stream_reply(
+    http_req: HttpRequest,
+    mut req: NormalizedRequest,
+    history: Vec<CohereMessage>,
+    overrides: MockOverrides,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    error::check_media_type(&http_req)?;
+    let scope = admission::check_caller(&config, &state, &http_req, &req)?;
+    let Recorded { capture_id, request_id } = admission::record(&config, &state, &http_req, &mut req, "message")?;
+    let bad_request = |message: &str, param: &str| MockError::rejected(StatusCode::BAD_REQUEST, message, Some(param));
+    if req.prompt.trim().is_empty() {
+        return Err(bad_request("message must not be empty", "message"));
+    }
+    if req.stream != Some(true) {
+        return Err(bad_request("stream parameter must be true", "stream"));
+    }
+    let model_info = req.model.as_deref().and_then(|m| state.models.get(m));
+    if let Some(model) = model_info {
+        chat::check_context_window(model, &req)?;
+    }
+
+    let (mut profile, rule_name, variant) = admission::profile(&config, &state, &req, &overrides, capture_id);
+    let retry_budget = admission::retry_budget(&config, &state, &http_req, &scope, &mut profile);
+    if profile.error_after == Some(0) {
+        let status = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(MockError::rejected(status, profile.error_message.clone(), None));
+    }
+    let ReplyText { chunks, total_chars, .. } = ReplyText::new(&config, &state, &req, &profile, rule_name.as_deref());
+    let encoding = model_info.map(|m| m.encoding).unwrap_or_default();
+    let charged = admission::reservation(&req, encoding, total_chars);
+    let admission = Admission::charge(&config, &state, &http_req, &scope, &request_id, req.model.as_deref(), charged).await?;
+    let input_tokens = encoding.count_messages(&req.messages);
+    let generation_id = format!("gen_{}", capture_id);
+    let finish_reason = match profile.finish_reason.as_deref() {
+        Some("length") => "MAX_TOKENS",
+        _ => "COMPLETE",
+    };
+    let mut chat_history = history;
+    chat_history.push(CohereMessage {
+        role: "USER".to_string(),
+        message: req.prompt.clone(),
+    });
+
+    let paced = Paced::new(
+        &config,
+        &profile,
+        encoding,
+        chunks,
+        admission.lease(&state, req.model.as_deref(), profile.weight),
+        Tracker::start(&state, &request_id, req.model.as_deref(), &scope, encoding, &req.messages),
+        StreamRecorder::new(&state, capture_id),
+    );
+    let ids = Rc::new((request_id.clone(), generation_id));
+    let chat_history = Rc::new(chat_history);
+    let progress = Progress {
+        paced,
+        text: String::new(),
+        started: false,
+        finished: false,
+    };
+    let body = stream::unfold(progress, move |mut progress| {
+        let (chat_history, ids) = (chat_history.clone(), ids.clone());
+        async move {
+            if progress.finished {
+                return None;
+            }
+            let (request_id, generation_id) = &*ids;
+            if !progress.started {
+                progress.started = true;
+                let line = ndjson_line(&CohereEvent::StreamStart {
+                    is_finished: false,
+                    generation_id,
+                });
+                return Some((line.map_err(Error::from), progress));
+            }
+            let finish_reason = match progress.paced.next().await {
+                Step::Chunk(chunk) => {
+                    progress.text.push_str(&chunk);
+                    let line = ndjson_line(&CohereEvent::TextGeneration {
+                        is_finished: false,
+                        text: &chunk,
+                    });
+                    return Some((line.map_err(Error::from), progress));
+                }
+                Step::Failed => "ERROR",
+                Step::Done => finish_reason,
+            };
+            progress.finished = true;
+            let mut chat_history = (*chat_history).clone();
+            chat_history.push(CohereMessage {
+                role: "CHATBOT".to_string(),
+                message: progress.text.clone(),
+            });
+            let line = ndjson_line(&CohereEvent::StreamEnd {
+                is_finished: true,
+                finish_reason,
+                response: CohereResponse {
+                    response_id: request_id,
+                    generation_id,
+                    text: &progress.text,
+                    finish_reason,
+                    chat_history,
+                    meta: CohereMeta {
+                        billed_units: BilledUnits {
+                            input_tokens,
+                            output_tokens: encoding.count(&progress.text),
+                        },
+                    },
+                },
+            });
+            Some((line.map_err(Error::from), progress))
+        }
+    });
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type("application/stream+json")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("x-request-id", request_id));
+    if let Some(name) = rule_name {
+        response.insert_header(("X-Mock-Rule", name));
+    }
+    if let Some(variant) = variant {
+        response.insert_header(("X-Mock-Variant", variant));
+    }
+    admission.insert_headers(&mut response);
+    if let Some(budget) = &retry_budget {
+        budget.insert_headers(&mut response);
+    }
+    profile.headers.insert_headers(&mut response);
+    Ok(response.streaming(body))
+}
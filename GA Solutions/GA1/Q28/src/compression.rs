@@ -0,0 +1,114 @@
+use actix_web::Error;
+use bytes::Bytes;
+use flate2::write::GzEncoder;
+use futures::stream::{self, Stream, StreamExt};
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    pub fn header_value(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+        }
+    }
+
+    /// Picks an encoding from an `Accept-Encoding` header, preferring `br` over
+    /// `gzip` when both are acceptable. Entries with `q=0` are treated as refusals.
+    pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+        let mut gzip = false;
+        let mut brotli = false;
+        for entry in accept_encoding.split(',') {
+            let mut parts = entry.split(';');
+            let name = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+            let refused = parts.any(|p| {
+                p.trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            if refused {
+                continue;
+            }
+            match name.as_str() {
+                "br" => brotli = true,
+                "gzip" | "x-gzip" => gzip = true,
+                "*" => {
+                    gzip = true;
+                    brotli = true;
+                }
+                _ => {}
+            }
+        }
+        if brotli {
+            Some(Encoding::Brotli)
+        } else if gzip {
+            Some(Encoding::Gzip)
+        } else {
+            None
+        }
+    }
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+}
+
+impl Encoder {
+    fn new(encoding: Encoding) -> Encoder {
+        match encoding {
+            Encoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::fast())),
+            Encoding::Brotli => Encoder::Brotli(Box::new(brotli::CompressorWriter::new(Vec::new(), 4096, 4, 22))),
+        }
+    }
+
+    /// Compresses `data` and flushes the encoder so the client can decode it
+    /// immediately, without waiting for later events.
+    fn push(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        let out = match self {
+            Encoder::Gzip(enc) => {
+                enc.write_all(data)?;
+                enc.flush()?;
+                std::mem::take(enc.get_mut())
+            }
+            Encoder::Brotli(enc) => {
+                enc.write_all(data)?;
+                enc.flush()?;
+                std::mem::take(enc.get_mut())
+            }
+        };
+        Ok(Bytes::from(out))
+    }
+
+    fn finish(self) -> io::Result<Bytes> {
+        let out = match self {
+            Encoder::Gzip(enc) => enc.finish()?,
+            Encoder::Brotli(enc) => enc.into_inner(),
+        };
+        Ok(Bytes::from(out))
+    }
+}
+
+/// Compresses every chunk of `body` as its own flushed block, then appends the encoder trailer.
+pub fn compress<S>(body: S, encoding: Encoding) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + 'static,
+{
+    stream::unfold(Some((Box::pin(body), Encoder::new(encoding))), |state| async move {
+        let (mut body, mut encoder) = state?;
+        match body.next().await {
+            Some(Ok(chunk)) => match encoder.push(&chunk) {
+                Ok(out) => Some((Ok(out), Some((body, encoder)))),
+                Err(err) => Some((Err(err.into()), None)),
+            },
+            Some(Err(err)) => Some((Err(err), None)),
+            None => Some((encoder.finish().map_err(Into::into), None)),
+        }
+    })
+}
@@ -0,0 +1,708 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::experiments::Experiment;
+use crate::keys::ApiKey;
+use crate::models::ModelInfo;
+use crate::orgs::Organization;
+use crate::pii::PiiKind;
+use crate::presets;
+use crate::stubs::{ResponseProfile, StubRule};
+use crate::webhooks::WebhookConfig;
+
+/// Environment variable naming the TOML config file, used when `--config` is not given.
+pub const CONFIG_ENV: &str = "STREAM_API_CONFIG";
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub stream: StreamConfig,
+    /// Prompt-keyed response profiles, checked in order (`[[stubs]]` tables).
+    pub stubs: Vec<StubRule>,
+    pub admin: AdminConfig,
+    pub compat: CompatConfig,
+    pub throughput: ThroughputConfig,
+    pub queue: QueueConfig,
+    pub pii: PiiConfig,
+    pub capture: CaptureConfig,
+    pub store: StoreConfig,
+    pub realtime: RealtimeConfig,
+    pub shadow: ShadowConfig,
+    /// Extra or overridden entries for the model registry (`[[models]]`).
+    pub models: Vec<ModelInfo>,
+    /// Model name to built-in preset name (`[model_presets]`); see `presets`.
+    pub model_presets: HashMap<String, String>,
+    /// Models served by weighted profile variants (`[[experiments]]`); see
+    /// `experiments`.
+    pub experiments: Vec<Experiment>,
+    /// Receivers of stream lifecycle events (`[[webhooks]]`); see `webhooks`.
+    pub webhooks: Vec<WebhookConfig>,
+    /// API keys and their tiers (`[[keys]]`); see `keys`.
+    pub keys: Vec<ApiKey>,
+    pub tiers: TiersConfig,
+    /// Pacing for each `service_tier` a request may ask for; see `service_tier`.
+    pub service_tiers: ServiceTiersConfig,
+    /// Organizations and their projects (`[[organizations]]`); see `orgs`.
+    pub organizations: Vec<Organization>,
+    pub rate_limits: RateLimitConfig,
+    pub quota: QuotaConfig,
+    pub watermark: WatermarkConfig,
+    pub echo: EchoConfig,
+    pub request: RequestConfig,
+    pub timeouts: TimeoutsConfig,
+    pub retry_budget: RetryBudgetConfig,
+    pub corpus: CorpusConfig,
+}
+
+/// What each key tier gets; see `keys`. A tier's table need only name the
+/// settings it changes; the rest keep that tier's defaults.
+#[derive(Deserialize, Clone)]
+#[serde(from = "TierTables")]
+pub struct TiersConfig {
+    pub free: TierSettings,
+    pub pro: TierSettings,
+    pub enterprise: TierSettings,
+}
+
+impl Default for TiersConfig {
+    fn default() -> Self {
+        TiersConfig {
+            free: TierSettings {
+                priority: 0,
+                weight: 1,
+                tokens_per_sec: Some(50.0),
+            },
+            pro: TierSettings {
+                priority: 1,
+                weight: 4,
+                tokens_per_sec: Some(150.0),
+            },
+            enterprise: TierSettings {
+                priority: 2,
+                weight: 16,
+                tokens_per_sec: None,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TierSettings {
+    /// Queued requests are admitted highest priority first; untiered
+    /// requests have priority 0.
+    pub priority: u8,
+    /// Multiplies the stub's `weight` when sharing a throughput budget.
+    pub weight: u32,
+    /// Cap on each stream's own token rate. Unset leaves only the shared budgets.
+    pub tokens_per_sec: Option<f64>,
+}
+
+/// `[tiers]` as written, before the defaults fill it in.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TierTables {
+    free: TierTable,
+    pro: TierTable,
+    enterprise: TierTable,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TierTable {
+    priority: Option<u8>,
+    weight: Option<u32>,
+    /// 0 lifts the cap.
+    tokens_per_sec: Option<f64>,
+}
+
+impl TierTable {
+    fn over(self, defaults: TierSettings) -> TierSettings {
+        TierSettings {
+            priority: self.priority.unwrap_or(defaults.priority),
+            weight: self.weight.unwrap_or(defaults.weight),
+            tokens_per_sec: match self.tokens_per_sec {
+                Some(rate) => Some(rate).filter(|&rate| rate > 0.0),
+                None => defaults.tokens_per_sec,
+            },
+        }
+    }
+}
+
+impl From<TierTables> for TiersConfig {
+    fn from(tables: TierTables) -> Self {
+        let defaults = TiersConfig::default();
+        TiersConfig {
+            free: tables.free.over(defaults.free),
+            pro: tables.pro.over(defaults.pro),
+            enterprise: tables.enterprise.over(defaults.enterprise),
+        }
+    }
+}
+
+/// What each `service_tier` gets.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ServiceTiersConfig {
+    pub flex: ServiceTierSettings,
+    pub default: ServiceTierSettings,
+    pub priority: ServiceTierSettings,
+}
+
+impl Default for ServiceTiersConfig {
+    fn default() -> Self {
+        ServiceTiersConfig {
+            flex: ServiceTierSettings { latency_factor: 3.0 },
+            default: ServiceTierSettings { latency_factor: 1.0 },
+            priority: ServiceTierSettings { latency_factor: 0.5 },
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceTierSettings {
+    /// Multiplies the profile's delays, time to first token included, and
+    /// divides its `tokens_per_sec`. 1 when a tier's table leaves it out.
+    #[serde(default = "default_latency_factor")]
+    pub latency_factor: f64,
+}
+
+fn default_latency_factor() -> f64 {
+    1.0
+}
+
+/// Inbound personal-data filter; see `pii`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PiiConfig {
+    pub policy: PiiPolicy,
+    /// What to look for. Defaults to every kind.
+    pub kinds: Vec<PiiKind>,
+}
+
+impl Default for PiiConfig {
+    fn default() -> Self {
+        PiiConfig {
+            policy: PiiPolicy::Off,
+            kinds: vec![PiiKind::Email, PiiKind::Phone, PiiKind::CreditCard],
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiPolicy {
+    /// Prompts are not inspected.
+    #[default]
+    Off,
+    /// Findings are recorded but prompts pass through untouched.
+    Audit,
+    /// Matches are replaced by a `[KIND]` placeholder before the prompt is
+    /// matched against stubs or echoed back.
+    Redact,
+    /// Requests containing any match fail with a 400 `pii_detected` error.
+    Reject,
+}
+
+/// How much of the prompt replies quote in their opening line.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct EchoConfig {
+    pub mode: EchoMode,
+    /// Characters kept by `EchoMode::Truncate`.
+    pub max_chars: usize,
+}
+
+impl Default for EchoConfig {
+    fn default() -> Self {
+        EchoConfig {
+            mode: EchoMode::Full,
+            max_chars: 64,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EchoMode {
+    /// The whole prompt, verbatim.
+    #[default]
+    Full,
+    /// The first `max_chars` characters, with `…` if any were cut.
+    Truncate,
+    /// `sha256:` and the first 12 hex digits of the prompt's hash, so replies
+    /// to the same prompt can still be told apart.
+    Hash,
+    /// No quote at all.
+    Omit,
+}
+
+/// How request bodies are read and parsed.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RequestConfig {
+    pub unknown_fields: UnknownFieldPolicy,
+    /// Largest JSON body accepted, counted after any `Content-Encoding` is
+    /// decoded, so a small compressed body cannot inflate past it.
+    pub max_body_bytes: usize,
+}
+
+impl Default for RequestConfig {
+    fn default() -> Self {
+        RequestConfig {
+            unknown_fields: UnknownFieldPolicy::default(),
+            max_body_bytes: 2 * 1024 * 1024,
+        }
+    }
+}
+
+/// What happens to top-level request fields the OpenAI API does not define.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownFieldPolicy {
+    /// A 400 naming them, as OpenAI answers.
+    Strict,
+    /// Served, with the fields logged.
+    Warn,
+    /// Served without comment, as most OpenAI-compatible servers do.
+    #[default]
+    Ignore,
+}
+
+/// Markers identifying this server in its replies; see `watermark`.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WatermarkConfig {
+    pub mode: WatermarkMode,
+    /// Names this server in its markers; `stream-api` when unset.
+    pub instance: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkMode {
+    #[default]
+    Off,
+    /// Zero-width characters after the first chunk's text.
+    ZeroWidth,
+    /// `x_mock.watermark` on the first chunk.
+    Extension,
+    Both,
+}
+
+impl WatermarkMode {
+    pub fn hidden(self) -> bool {
+        matches!(self, WatermarkMode::ZeroWidth | WatermarkMode::Both)
+    }
+
+    pub fn extension(self) -> bool {
+        matches!(self, WatermarkMode::Extension | WatermarkMode::Both)
+    }
+}
+
+/// Recent-request store; see `capture`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// Captures kept before the oldest is dropped; `0` disables capturing.
+    pub limit: usize,
+    /// Characters of each reply kept; the rest is dropped and the reply
+    /// marked truncated.
+    pub max_reply_chars: usize,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        CaptureConfig {
+            limit: 1000,
+            max_reply_chars: 65_536,
+        }
+    }
+}
+
+/// Completions kept for `store: true` requests; see `stored`.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    /// Completions kept before the oldest is dropped; `0` stores none.
+    pub limit: usize,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig { limit: 1000 }
+    }
+}
+
+/// Transcription timing for `/v1/realtime`; see `realtime`.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RealtimeConfig {
+    /// Audio that makes one transcribed word.
+    pub ms_per_word: u64,
+    /// How often words heard so far are sent as a delta.
+    pub delta_interval_ms: u64,
+}
+
+impl Default for RealtimeConfig {
+    fn default() -> Self {
+        RealtimeConfig {
+            ms_per_word: 400,
+            delta_interval_ms: 200,
+        }
+    }
+}
+
+/// A secondary target each chat request is mirrored to; see `shadow`.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadowConfig {
+    /// An OpenAI-compatible chat completions URL to mirror requests to.
+    pub url: Option<String>,
+    /// Sent to `url` as a bearer token.
+    pub api_key: Option<String>,
+    pub timeout_ms: u64,
+    /// A local profile to replay requests with instead, when `url` is unset.
+    pub profile: Option<ResponseProfile>,
+    /// Comparisons kept for `/v1/internal/shadow`; the oldest is dropped
+    /// first.
+    pub limit: usize,
+    /// Mirrors running at once; requests past it are not mirrored.
+    pub max_in_flight: usize,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        ShadowConfig {
+            url: None,
+            api_key: None,
+            timeout_ms: 30_000,
+            profile: None,
+            limit: 100,
+            max_in_flight: 16,
+        }
+    }
+}
+
+/// Admission queue in front of stream start; see `queue`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Streams started per second. Unset admits every request immediately.
+    pub admissions_per_sec: Option<f64>,
+    /// Requests allowed to wait at once; further arrivals get a 503.
+    pub depth: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            admissions_per_sec: None,
+            depth: 100,
+        }
+    }
+}
+
+/// Per-key limits reported in `x-ratelimit-*` headers; see `ratelimit`.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Requests per minute for each key. Unset sends no request headers.
+    pub requests_per_min: Option<u64>,
+    /// Prompt plus completion tokens per minute for each key. Unset sends no
+    /// token headers.
+    pub tokens_per_min: Option<u64>,
+    /// Turn away requests that would overdraw a limit with a 429. Off by
+    /// default, so the headers can be paced against without any request
+    /// failing.
+    pub enforce: bool,
+}
+
+/// The packed reply corpus `generator = "corpus"` draws from; see `corpus`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CorpusConfig {
+    /// A file written by `stream-api corpus pack`, relative to the config
+    /// file. Unset, the corpus generator falls back to the canned reply.
+    pub path: Option<String>,
+    pub selection: CorpusSelection,
+    /// With `similar`, how many of the best-scoring replies the seed draws
+    /// from, weighted by score.
+    pub candidates: usize,
+}
+
+impl Default for CorpusConfig {
+    fn default() -> Self {
+        CorpusConfig {
+            path: None,
+            selection: CorpusSelection::Seed,
+            candidates: 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CorpusSelection {
+    /// Any reply, picked by the request seed.
+    #[default]
+    Seed,
+    /// The reply most like the prompt by BM25, from an index built at
+    /// startup. Prompts sharing no terms with any reply fall back to `seed`.
+    Similar,
+}
+
+/// Per-key budgets the fault injector pays for its failures from; see
+/// `retry_budget`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RetryBudgetConfig {
+    pub enabled: bool,
+    /// Retries earned by each first attempt, in percent of one.
+    pub percent_can_retry: f64,
+    /// Retries earned with time regardless of traffic.
+    pub min_retries_per_sec: f64,
+    /// Most retries a key can save up; each key starts with this many.
+    pub max_retries: f64,
+    /// Request header holding the client's retry count.
+    pub retry_header: String,
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        RetryBudgetConfig {
+            enabled: false,
+            percent_can_retry: 20.0,
+            min_retries_per_sec: 0.1,
+            max_retries: 10.0,
+            retry_header: "x-stainless-retry-count".to_string(),
+        }
+    }
+}
+
+/// Budgets that cut off a hanging handler; see `timeouts`.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TimeoutsConfig {
+    /// Longest a handler may take to produce its response headers. Unset
+    /// leaves handlers unbounded.
+    pub validation_ms: Option<u64>,
+    /// Longest an event stream may go between writes once its headers are
+    /// out. Unset leaves streams unbounded.
+    pub stream_idle_ms: Option<u64>,
+    /// Overrides by request path, such as `"/v1/chat/completions"`.
+    pub routes: HashMap<String, RouteTimeouts>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteTimeouts {
+    /// Unset inherits the server-wide budget; `0` lifts it for the route.
+    pub validation_ms: Option<u64>,
+    pub stream_idle_ms: Option<u64>,
+}
+
+impl TimeoutsConfig {
+    /// The validation and stream idle budgets for `path`: its override,
+    /// falling back field by field to the server-wide ones.
+    pub fn for_path(&self, path: &str) -> (Option<Duration>, Option<Duration>) {
+        let route = self.routes.get(path).copied().unwrap_or_default();
+        let budget = |route: Option<u64>, default: Option<u64>| route.or(default).filter(|&ms| ms > 0).map(Duration::from_millis);
+        (
+            budget(route.validation_ms, self.validation_ms),
+            budget(route.stream_idle_ms, self.stream_idle_ms),
+        )
+    }
+}
+
+/// When keys with a `token_quota` are warned about their usage; see `quota`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Shares of the quota, in percent, whose crossing is logged and
+    /// published as a `quota.threshold` event.
+    pub thresholds: Vec<u8>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        QuotaConfig {
+            thresholds: vec![50, 80, 100],
+        }
+    }
+}
+
+/// Token-per-second budgets shared by all streams; see `throughput`.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ThroughputConfig {
+    /// Server-wide budget. Unset leaves throughput uncapped.
+    pub tokens_per_sec: Option<f64>,
+    /// Budgets for individual models, applied on top of the global one.
+    pub models: HashMap<String, f64>,
+}
+
+/// Switches that restore earlier wire behavior for clients that depend on it.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CompatConfig {
+    /// Send pre-stream failures as `text/event-stream` error events (the
+    /// original behavior) instead of `application/json` OpenAI error envelopes.
+    pub legacy_sse_errors: bool,
+}
+
+/// Listener and connection settings. Long simulated time-to-first-token
+/// scenarios usually need `client_request_timeout_ms` and `keep_alive_secs`
+/// raised above actix's defaults.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub bind: String,
+    /// Idle keep-alive timeout between requests; `0` disables keep-alive.
+    pub keep_alive_secs: u64,
+    /// Time allowed for a client to send the request head; `0` disables the limit.
+    pub client_request_timeout_ms: u64,
+    /// Time allowed for a graceful connection shutdown; `0` disables the limit.
+    pub client_disconnect_timeout_ms: u64,
+    /// Maximum number of pending connections in the listen queue.
+    pub backlog: u32,
+    /// Worker threads; defaults to the number of physical CPUs.
+    pub workers: Option<usize>,
+    /// Bind with SO_REUSEPORT so another server can share the port during a
+    /// handover (Unix only). `serve --reuse-port` sets it.
+    pub reuse_port: bool,
+    /// How long a stopping server lets in-flight streams finish before
+    /// cutting them off.
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: "127.0.0.1:8080".to_string(),
+            keep_alive_secs: 5,
+            client_request_timeout_ms: 5000,
+            client_disconnect_timeout_ms: 1000,
+            backlog: 1024,
+            workers: None,
+            reuse_port: false,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Bearer token guarding admin and debug routes. Unset disables them.
+    pub token: Option<String>,
+    /// JSON Lines file admin mutations are appended to; see `audit`.
+    pub audit_log: Option<PathBuf>,
+    /// Signs each audit record with HMAC-SHA256, so the chain cannot be
+    /// rewritten by someone without it.
+    pub audit_secret: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct StreamConfig {
+    /// How SSE events are handed to the socket.
+    pub write_mode: WriteMode,
+    /// Window used by `WriteMode::Coalesce` to gather events into one write.
+    pub coalesce_window_ms: u64,
+    /// Whether streamed bodies may be compressed with `gzip`/`br`.
+    pub compression: CompressionMode,
+    /// Hold back the entire response and send it at once, as a buffering proxy would.
+    pub simulate_proxy_buffering: bool,
+    /// Emit an increasing SSE `id:` line with every event and honor
+    /// `Last-Event-ID` on reconnect.
+    pub event_ids: bool,
+    /// How chunk delays are timed.
+    pub pacing: PacingMode,
+    /// With `PacingMode::Scheduler`, the most streams a worker releases per
+    /// tick. Unset releases every stream that is due.
+    pub pacing_max_per_tick: Option<usize>,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            write_mode: WriteMode::Flush,
+            coalesce_window_ms: 50,
+            compression: CompressionMode::Off,
+            simulate_proxy_buffering: false,
+            event_ids: false,
+            pacing: PacingMode::Sleep,
+            pacing_max_per_tick: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    /// Every SSE event is written and flushed on its own.
+    #[default]
+    Flush,
+    /// Events produced within `coalesce_window_ms` are joined into a single write.
+    Coalesce,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PacingMode {
+    /// Each stream sleeps on its own timer.
+    #[default]
+    Sleep,
+    /// Streams share one timer wheel per worker; see `pacer`.
+    Scheduler,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionMode {
+    /// Never compress, regardless of `Accept-Encoding`.
+    #[default]
+    Off,
+    /// Compress when the client advertises `br` or `gzip` support.
+    Auto,
+}
+
+impl Config {
+    /// Loads `path` if given, else the file named by `STREAM_API_CONFIG`, else defaults.
+    pub fn load(path: Option<&str>) -> io::Result<Config> {
+        if let Some(path) = path {
+            return Config::from_file(path);
+        }
+        match std::env::var(CONFIG_ENV) {
+            Ok(path) => Config::from_file(&path),
+            Err(_) => Ok(Config::default()),
+        }
+    }
+
+    pub fn from_file(path: &str) -> io::Result<Config> {
+        let text = std::fs::read_to_string(path)?;
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e));
+        let mut config: Config = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        if let (Some(corpus), Some(dir)) = (&mut config.corpus.path, Path::new(path).parent()) {
+            *corpus = dir.join(&*corpus).to_string_lossy().into_owned();
+        }
+        if let Some((model, preset)) = config.model_presets.iter().find(|(_, p)| presets::find(p).is_none()) {
+            return Err(invalid(format!("model_presets.{} names unknown preset '{}'", model, preset)));
+        }
+        let tiers = &config.service_tiers;
+        for (name, tier) in [("flex", &tiers.flex), ("default", &tiers.default), ("priority", &tiers.priority)] {
+            if !(tier.latency_factor.is_finite() && tier.latency_factor > 0.0) {
+                return Err(invalid(format!("service_tiers.{}.latency_factor must be positive", name)));
+            }
+        }
+        if config.realtime.ms_per_word == 0 || config.realtime.delta_interval_ms == 0 {
+            return Err(invalid("realtime.ms_per_word and realtime.delta_interval_ms must be positive".to_string()));
+        }
+        Ok(config)
+    }
+}
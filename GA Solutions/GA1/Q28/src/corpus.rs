@@ -0,0 +1,357 @@
+//! Reply corpora too large to hold in memory (`[corpus]`), for the varied
+//! long replies of `generator = "corpus"`.
+//!
+//! `stream-api corpus pack` turns a JSONL file of replies into one file in
+//! which every reply is compressed with brotli on its own, followed by an
+//! index of where each one lies. The server maps the file instead of
+//! reading it, so startup takes the same time whatever its size, and a
+//! request decompresses only the reply its seed picks.
+//!
+//! With `selection = "similar"` the reply is instead the one most like the
+//! prompt, scored with BM25 against an index of every reply's terms. The
+//! index is built by the first request that needs it, which reads the whole
+//! corpus once, and keeps only term counts, not text. A term in more than
+//! half the replies says little about any of them, so once its postings pass
+//! that share it is dropped, which keeps words like "the" from costing an
+//! entry per reply.
+//!
+//! The layout, with integers little-endian:
+//!
+//! ```text
+//! "MOCKCRP1"                     magic
+//! reply 0, reply 1, ...          independent brotli streams
+//! (offset u64, length u64) ...   the index, one entry per reply
+//! count u64, "MOCKCRP1"          trailer
+//! ```
+
+use memmap2::Mmap;
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::{CorpusConfig, CorpusSelection};
+use crate::generator;
+
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum CorpusCommand {
+    /// Pack a JSONL file of replies, one JSON string or `{"text": ...}` object
+    /// per line, into a corpus for `[corpus] path`.
+    Pack {
+        input: PathBuf,
+        output: PathBuf,
+    },
+}
+
+const MAGIC: &[u8; 8] = b"MOCKCRP1";
+const ENTRY_BYTES: usize = 16;
+const TRAILER_BYTES: usize = 16;
+
+/// Brotli settings for packing: slow to compress, which happens once, and
+/// as fast as any to decompress.
+const QUALITY: u32 = 9;
+const WINDOW_BITS: u32 = 22;
+
+/// BM25 tuning, at the usual values.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+/// Postings a term may have before it counts as too common to index, however
+/// small the corpus; past this, the limit is half the replies.
+const MIN_POSTINGS_CAP: usize = 1024;
+
+/// A packed corpus, mapped into memory.
+pub struct Corpus {
+    map: Mmap,
+    count: usize,
+    index_at: usize,
+    /// How many of the best replies `choose` draws from, when it picks by
+    /// similarity.
+    similar: Option<usize>,
+    terms: OnceLock<TermIndex>,
+}
+
+impl Corpus {
+    pub fn open(path: &Path) -> io::Result<Corpus> {
+        let invalid = |what: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), what));
+        let file = File::open(path)?;
+        // SAFETY: the map is only read. A packed corpus is written once and
+        // replaced rather than edited, so it does not change under the map.
+        let map = unsafe { Mmap::map(&file)? };
+        let len = map.len();
+        if len < MAGIC.len() + TRAILER_BYTES || &map[..MAGIC.len()] != MAGIC || &map[len - MAGIC.len()..] != MAGIC {
+            return Err(invalid("not a packed corpus; build one with `stream-api corpus pack`".to_string()));
+        }
+        let count = read_u64(&map, len - TRAILER_BYTES) as usize;
+        let index_at = count
+            .checked_mul(ENTRY_BYTES)
+            .and_then(|index| (len - TRAILER_BYTES).checked_sub(index))
+            .filter(|&at| at >= MAGIC.len())
+            .ok_or_else(|| invalid(format!("its index of {} replies overruns the file", count)))?;
+        if count == 0 {
+            return Err(invalid("it holds no replies".to_string()));
+        }
+        let corpus = Corpus {
+            map,
+            count,
+            index_at,
+            similar: None,
+            terms: OnceLock::new(),
+        };
+        for i in 0..count {
+            let (offset, length) = corpus.entry(i);
+            if offset < MAGIC.len() || offset.checked_add(length).is_none_or(|end| end > index_at) {
+                return Err(invalid(format!("reply {} lies outside the file", i)));
+            }
+        }
+        Ok(corpus)
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Reply `i`, decompressed.
+    pub fn reply(&self, i: usize) -> io::Result<String> {
+        let (offset, length) = self.entry(i);
+        let mut text = String::new();
+        brotli::Decompressor::new(&self.map[offset..offset + length], 4096).read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    /// The reply `seed` picks, spread evenly over the corpus.
+    pub fn pick(&self, seed: u64) -> usize {
+        (generator::mix64(seed) % self.count as u64) as usize
+    }
+
+    /// Makes `choose` pick by similarity to the prompt, drawing from the
+    /// best `candidates`. The replies' terms are indexed on first use.
+    pub fn select_similar(&mut self, candidates: usize) {
+        self.similar = Some(candidates.max(1));
+    }
+
+    /// The reply for `prompt`: by similarity when selected, else, or when
+    /// nothing matches, by `seed` alone.
+    pub fn choose(&self, prompt: &str, seed: u64) -> usize {
+        self.similar
+            .and_then(|candidates| self.terms().draw(prompt, seed, candidates))
+            .unwrap_or_else(|| self.pick(seed))
+    }
+
+    /// The term index, built by the first call. A reply that does not
+    /// decompress is indexed as empty, so it is only ever picked by seed.
+    fn terms(&self) -> &TermIndex {
+        self.terms.get_or_init(|| {
+            let started = std::time::Instant::now();
+            let mut index = TermIndex::new((self.count / 2).max(MIN_POSTINGS_CAP));
+            for i in 0..self.count {
+                let reply = self.reply(i).unwrap_or_else(|e| {
+                    log::warn!("corpus reply {} could not be read for indexing: {}", i, e);
+                    String::new()
+                });
+                index.add(&reply);
+            }
+            log::info!(
+                "indexed corpus terms in {} ms, leaving out {} common ones",
+                started.elapsed().as_millis(),
+                index.common.len()
+            );
+            index
+        })
+    }
+
+    fn entry(&self, i: usize) -> (usize, usize) {
+        let at = self.index_at + i * ENTRY_BYTES;
+        (read_u64(&self.map, at) as usize, read_u64(&self.map, at + 8) as usize)
+    }
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().expect("eight bytes"))
+}
+
+/// Opens the configured corpus, if any.
+pub fn open(config: &CorpusConfig) -> io::Result<Option<Corpus>> {
+    let Some(path) = &config.path else {
+        return Ok(None);
+    };
+    let mut corpus = Corpus::open(Path::new(path))?;
+    log::info!("mapped corpus {} with {} replies", path, corpus.len());
+    if config.selection == CorpusSelection::Similar {
+        corpus.select_similar(config.candidates);
+    }
+    Ok(Some(corpus))
+}
+
+/// Term counts of every reply, for BM25.
+struct TermIndex {
+    /// Each term's (reply, occurrences) pairs, in reply order.
+    postings: HashMap<String, Vec<(u32, u32)>>,
+    /// Terms whose postings passed `max_postings`, left out of scoring.
+    common: HashSet<String>,
+    max_postings: usize,
+    /// Terms in each reply.
+    lengths: Vec<u32>,
+    total_length: u64,
+}
+
+/// Lowercased runs of letters and digits.
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).map(str::to_lowercase)
+}
+
+impl TermIndex {
+    fn new(max_postings: usize) -> TermIndex {
+        TermIndex {
+            postings: HashMap::new(),
+            common: HashSet::new(),
+            max_postings,
+            lengths: Vec::new(),
+            total_length: 0,
+        }
+    }
+
+    fn add(&mut self, reply: &str) {
+        let id = self.lengths.len() as u32;
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        let mut length = 0;
+        for term in terms(reply) {
+            *counts.entry(term).or_default() += 1;
+            length += 1;
+        }
+        for (term, count) in counts {
+            if self.common.contains(&term) {
+                continue;
+            }
+            match self.postings.entry(term) {
+                Entry::Occupied(mut postings) => {
+                    postings.get_mut().push((id, count));
+                    if postings.get().len() > self.max_postings {
+                        self.common.insert(postings.remove_entry().0);
+                    }
+                }
+                Entry::Vacant(postings) => {
+                    postings.insert(vec![(id, count)]);
+                }
+            }
+        }
+        self.lengths.push(length);
+        self.total_length += u64::from(length);
+    }
+
+    /// BM25 scores of the replies sharing a term with `prompt`.
+    fn scores(&self, prompt: &str) -> HashMap<u32, f64> {
+        let n = self.lengths.len() as f64;
+        let average = (self.total_length as f64 / n).max(1.0);
+        let mut query: Vec<String> = terms(prompt).collect();
+        query.sort();
+        query.dedup();
+        let mut scores = HashMap::new();
+        for postings in query.iter().filter_map(|term| self.postings.get(term)) {
+            let containing = postings.len() as f64;
+            let idf = (1.0 + (n - containing + 0.5) / (containing + 0.5)).ln();
+            for &(reply, count) in postings {
+                let tf = f64::from(count);
+                let norm = 1.0 - B + B * f64::from(self.lengths[reply as usize]) / average;
+                *scores.entry(reply).or_insert(0.0) += idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+            }
+        }
+        scores
+    }
+
+    /// One of the `candidates` best replies for `prompt`, drawn by `seed`
+    /// in proportion to score, or `None` when no reply shares a term.
+    fn draw(&self, prompt: &str, seed: u64, candidates: usize) -> Option<usize> {
+        let mut ranked: Vec<(u32, f64)> = self.scores(prompt).into_iter().filter(|&(_, score)| score > 0.0).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(candidates);
+        let total: f64 = ranked.iter().map(|&(_, score)| score).sum();
+        let mut target = generator::unit(generator::mix64(seed)) * total;
+        for &(reply, score) in &ranked {
+            if target < score {
+                return Some(reply as usize);
+            }
+            target -= score;
+        }
+        ranked.last().map(|&(reply, _)| reply as usize)
+    }
+}
+
+/// One line of a JSONL corpus: the reply itself, or an object with `text`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line {
+    Text(String),
+    Object { text: String },
+}
+
+/// Packs the replies in `input`, one JSON line each, into `output`, and
+/// returns how many there were. Blank lines are skipped; a reply with no
+/// text is an error.
+pub fn pack(input: impl BufRead, mut output: impl Write) -> io::Result<usize> {
+    output.write_all(MAGIC)?;
+    let mut offset = MAGIC.len() as u64;
+    let mut index = Vec::new();
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let text = match serde_json::from_str(&line) {
+            Ok(Line::Text(text) | Line::Object { text }) => text,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", n + 1, e))),
+        };
+        if text.trim().is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: the reply is empty", n + 1)));
+        }
+        let mut compressed = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, QUALITY, WINDOW_BITS);
+            writer.write_all(text.as_bytes())?;
+        }
+        output.write_all(&compressed)?;
+        index.extend_from_slice(&offset.to_le_bytes());
+        index.extend_from_slice(&(compressed.len() as u64).to_le_bytes());
+        offset += compressed.len() as u64;
+    }
+    let count = index.len() / ENTRY_BYTES;
+    output.write_all(&index)?;
+    output.write_all(&(count as u64).to_le_bytes())?;
+    output.write_all(MAGIC)?;
+    output.flush()?;
+    Ok(count)
+}
+
+/// Runs `stream-api corpus`.
+pub fn run(command: &CorpusCommand) -> io::Result<()> {
+    match command {
+        CorpusCommand::Pack { input, output } => {
+            let reader = io::BufReader::new(File::open(input)?);
+            // Written beside the output and renamed over it once complete, so
+            // a server mapping the old corpus never sees a partial one.
+            let name = output.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the output is not a file"))?;
+            let partial = output.with_file_name(format!(".{}.{}.partial", name.to_string_lossy(), std::process::id()));
+            let packed = File::create(&partial).and_then(|file| {
+                let mut writer = io::BufWriter::new(file);
+                let count = pack(reader, &mut writer)?;
+                if count == 0 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} holds no replies", input.display())));
+                }
+                writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+                std::fs::rename(&partial, output)?;
+                Ok(count)
+            });
+            let count = packed.inspect_err(|_| {
+                let _ = std::fs::remove_file(&partial);
+            })?;
+            println!("Packed {} replies into {}", count, output.display());
+            Ok(())
+        }
+    }
+}
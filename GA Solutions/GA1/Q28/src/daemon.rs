@@ -0,0 +1,326 @@
+//! Running the server without a terminal: `serve --daemonize` on Unix, and a
+//! Windows service on Windows, so the mock can stay up on developer machines
+//! and lab boxes.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Options for `stream-api serve`.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ServeArgs {
+    /// Detach from the terminal and keep running in the background.
+    #[cfg(unix)]
+    #[arg(long)]
+    pub daemonize: bool,
+    /// Write the server's process id here while it runs, and refuse to start
+    /// while another server holds the file.
+    #[arg(long)]
+    pub pidfile: Option<PathBuf>,
+    /// With --daemonize, append output here instead of discarding it.
+    #[cfg(unix)]
+    #[arg(long, requires = "daemonize")]
+    pub log_file: Option<PathBuf>,
+    /// Bind with SO_REUSEPORT so a new server can share the port. Given the
+    /// --pidfile of a running server, this one takes over: once it is
+    /// listening, the old server is told to drain its streams and exit.
+    #[arg(long)]
+    pub reuse_port: bool,
+}
+
+/// A locked pidfile naming this process. The lock lasts as long as the
+/// process, so a crashed server never blocks the next one; a clean shutdown
+/// also removes the file.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    _file: File,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> io::Result<PidFile> {
+        let mut file = open(path)?;
+        lock(&mut file, path)?;
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(PidFile {
+            path: path.to_path_buf(),
+            _file: file,
+        })
+    }
+
+    /// Waits for whichever server holds the pidfile to exit, then claims it.
+    pub fn wait_for(path: &Path) -> io::Result<PidFile> {
+        let held = open(path)?;
+        held.lock()?;
+        // The old server removes the file on its way out, so the one just
+        // locked may no longer be at `path`.
+        drop(held);
+        PidFile::create(path)
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Fails if a running server holds the pidfile at `path`.
+pub fn check_pidfile(path: &Path) -> io::Result<()> {
+    match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(mut file) => lock(&mut file, path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// The pid of the running server holding the pidfile at `path`, if any.
+pub fn pidfile_holder(path: &Path) -> io::Result<Option<u32>> {
+    match check_pidfile(path) {
+        Ok(()) => Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            let pid = fs::read_to_string(path)?;
+            pid.trim()
+                .parse()
+                .map(Some)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{}: not a pid: {:?}", path.display(), pid)))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)
+}
+
+fn lock(file: &mut File, path: &Path) -> io::Result<()> {
+    match file.try_lock() {
+        Ok(()) => Ok(()),
+        Err(fs::TryLockError::WouldBlock) => {
+            let mut pid = String::new();
+            file.read_to_string(&mut pid)?;
+            Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("already running as pid {} ({})", pid.trim(), path.display()),
+            ))
+        }
+        Err(fs::TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// How long a server taking over waits, once new connections come to it,
+/// before its predecessor stops listening.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// This server's hold on its pidfile.
+#[derive(Debug)]
+pub enum Claim {
+    Held(PidFile),
+    /// Taking over from another server, which still holds the pidfile while
+    /// it drains.
+    Pending(std::thread::JoinHandle<io::Result<PidFile>>),
+}
+
+impl Claim {
+    /// Claims `path`, or with `predecessor`, sends that server SIGTERM so it
+    /// stops accepting, lets its in-flight streams finish and exits, and
+    /// claims the pidfile once it has. Call it only once this server is
+    /// listening, so no connection finds the port closed.
+    pub fn new(path: &Path, predecessor: Option<u32>) -> io::Result<Claim> {
+        let Some(pid) = predecessor else {
+            return PidFile::create(path).map(Claim::Held);
+        };
+        // Handshakes the old server had started before this one took every
+        // new connection still land in its queue; give it time to accept them.
+        std::thread::sleep(SETTLE);
+        terminate(pid)?;
+        let path = path.to_path_buf();
+        Ok(Claim::Pending(std::thread::spawn(move || PidFile::wait_for(&path))))
+    }
+
+    /// Waits for a pending takeover to complete, then removes the pidfile.
+    pub fn release(self) {
+        match self {
+            Claim::Held(pidfile) => drop(pidfile),
+            Claim::Pending(handle) => drop(handle.join()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> io::Result<()> {
+    let signalled = libc::pid_t::try_from(pid).is_ok_and(|pid| {
+        // SAFETY: kill takes plain integers and has no memory effects here.
+        unsafe { libc::kill(pid, libc::SIGTERM) == 0 }
+    });
+    if signalled {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "could not signal pid {} to drain: {}",
+            pid,
+            io::Error::last_os_error()
+        )))
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(pid: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("pid {} holds the pidfile and handover needs Unix signals", pid),
+    ))
+}
+
+/// Forks into the background, keeping the working directory so relative
+/// paths in the config still resolve, and returns in the detached process
+/// only. Must run before any runtime or worker threads are started.
+#[cfg(unix)]
+pub fn daemonize(args: &ServeArgs) -> io::Result<()> {
+    let mut daemon = daemonize::Daemonize::new().working_directory(std::env::current_dir()?);
+    if let Some(path) = &args.log_file {
+        let log = OpenOptions::new().create(true).append(true).open(path)?;
+        daemon = daemon.stdout(log.try_clone()?).stderr(log);
+    }
+    daemon.start().map_err(io::Error::other)
+}
+
+/// `stream-api service`: registers the server with the Windows service
+/// control manager, which then runs it at boot without a console.
+#[cfg(windows)]
+pub mod service {
+    use std::ffi::OsString;
+    use std::io;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    use windows_service::service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo,
+        ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    use crate::config::Config;
+    use crate::server;
+
+    pub const SERVICE_NAME: &str = "stream-api";
+
+    #[derive(clap::Subcommand, Clone, Debug)]
+    pub enum ServiceCommand {
+        /// Register the service to start at boot, serving the global --config.
+        Install,
+        /// Stop and remove the service.
+        Uninstall,
+        /// The entry point the service control manager launches.
+        #[command(hide = true)]
+        Run,
+    }
+
+    /// The config path `service run` was launched with, for `service_main`.
+    static CONFIG_PATH: OnceLock<Option<String>> = OnceLock::new();
+
+    pub fn run(command: &ServiceCommand, config_path: Option<&str>) -> io::Result<()> {
+        match command {
+            ServiceCommand::Install => install(config_path),
+            ServiceCommand::Uninstall => uninstall(),
+            ServiceCommand::Run => {
+                let _ = CONFIG_PATH.set(config_path.map(str::to_string));
+                service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(io::Error::other)
+            }
+        }
+    }
+
+    fn install(config_path: Option<&str>) -> io::Result<()> {
+        let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)
+            .map_err(io::Error::other)?;
+        // Services start in the system directory, so the config path must be absolute.
+        let mut launch_arguments = Vec::new();
+        if let Some(path) = config_path {
+            launch_arguments.push(OsString::from("--config"));
+            launch_arguments.push(std::path::absolute(path)?.into_os_string());
+        }
+        launch_arguments.extend(["service", "run"].map(OsString::from));
+        let info = ServiceInfo {
+            name: OsString::from(SERVICE_NAME),
+            display_name: OsString::from("Streaming LLM mock"),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: std::env::current_exe()?,
+            launch_arguments,
+            dependencies: Vec::new(),
+            account_name: None,
+            account_password: None,
+        };
+        let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG).map_err(io::Error::other)?;
+        service
+            .set_description("Serves the streaming chat completions mock")
+            .map_err(io::Error::other)
+    }
+
+    fn uninstall() -> io::Result<()> {
+        let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT).map_err(io::Error::other)?;
+        let service = manager
+            .open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE)
+            .map_err(io::Error::other)?;
+        // Deletion completes once the service has stopped.
+        service.delete().map_err(io::Error::other)?;
+        if service.query_status().map_err(io::Error::other)?.current_state != ServiceState::Stopped {
+            service.stop().map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    fn service_main(_arguments: Vec<OsString>) {
+        if let Err(e) = run_service() {
+            log::error!("service failed: {}", e);
+        }
+    }
+
+    fn run_service() -> io::Result<()> {
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+        let handler = move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                let _ = stop_tx.send(());
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+        let status = service_control_handler::register(SERVICE_NAME, handler).map_err(io::Error::other)?;
+        let set_state = |current_state, controls_accepted, code| {
+            status
+                .set_service_status(ServiceStatus {
+                    service_type: ServiceType::OWN_PROCESS,
+                    current_state,
+                    controls_accepted,
+                    exit_code: ServiceExitCode::ServiceSpecific(code),
+                    checkpoint: 0,
+                    wait_hint: Duration::default(),
+                    process_id: None,
+                })
+                .map_err(io::Error::other)
+        };
+        set_state(ServiceState::Running, ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN, 0)?;
+
+        let served = actix_web::rt::System::new().block_on(async move {
+            let config = Config::load(CONFIG_PATH.get().and_then(Option::as_deref))?;
+            let listener = server::bind(&config.server)?;
+            let server = server::serve(config, listener)?;
+            let handle = server.handle();
+            actix_web::rt::spawn(async move {
+                let _ = tokio::task::spawn_blocking(move || stop_rx.recv()).await;
+                handle.stop(true).await;
+            });
+            server.await
+        });
+        set_state(ServiceState::Stopped, ServiceControlAccept::empty(), u32::from(served.is_err()))?;
+        served
+    }
+}
@@ -0,0 +1,79 @@
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use pprof::protos::Message;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::admin;
+use crate::config::Config;
+use crate::error;
+
+const MAX_PROFILE_SECONDS: u64 = 120;
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    #[serde(default = "default_seconds")]
+    seconds: u64,
+    #[serde(default)]
+    format: ProfileFormat,
+}
+
+fn default_seconds() -> u64 {
+    10
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum ProfileFormat {
+    /// An SVG flamegraph, viewable directly in a browser.
+    #[default]
+    Flamegraph,
+    /// An uncompressed pprof protobuf, for `go tool pprof` and similar tools.
+    Protobuf,
+}
+
+fn profiler_error(e: impl std::fmt::Display) -> HttpResponse {
+    error::json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Profiling failed: {}", e), None)
+}
+
+/// Samples the whole process for `seconds` and returns the resulting profile.
+#[get("/debug/pprof/profile")]
+pub async fn profile_endpoint(
+    req: HttpRequest,
+    query: web::Query<ProfileQuery>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return resp;
+    }
+
+    let guard = match pprof::ProfilerGuardBuilder::default()
+        .frequency(99)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+    {
+        Ok(guard) => guard,
+        Err(e) => return profiler_error(e),
+    };
+    sleep(Duration::from_secs(query.seconds.clamp(1, MAX_PROFILE_SECONDS))).await;
+
+    let report = match guard.report().build() {
+        Ok(report) => report,
+        Err(e) => return profiler_error(e),
+    };
+    match query.format {
+        ProfileFormat::Flamegraph => {
+            let mut svg = Vec::new();
+            match report.flamegraph(&mut svg) {
+                Ok(()) => HttpResponse::Ok().content_type("image/svg+xml").body(svg),
+                Err(e) => profiler_error(e),
+            }
+        }
+        ProfileFormat::Protobuf => match report.pprof().map(|p| p.write_to_bytes()) {
+            Ok(Ok(bytes)) => HttpResponse::Ok().content_type("application/octet-stream").body(bytes),
+            Ok(Err(e)) => profiler_error(e),
+            Err(e) => profiler_error(e),
+        },
+    }
+}
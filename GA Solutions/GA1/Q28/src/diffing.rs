@@ -0,0 +1,449 @@
+//! Differences between two completions, for shadow traffic, the conformance
+//! runner and `POST /v1/internal/diff`. Texts are compared two ways:
+//!
+//! - **Token-level**: the longest common subsequence of their words and
+//!   punctuation marks, as runs of equal, deleted and inserted text. Past
+//!   `MAX_LCS_CELLS` the middle, between the common prefix and suffix, is
+//!   reported as one deletion and one insertion rather than aligned.
+//! - **Semantic hash**: a 64-bit SimHash of each text's lowercased word
+//!   trigrams, whose Hamming distance stays small for near-duplicates, such as
+//!   the same reply reworded in a few places. It is lexical, not a measure
+//!   of meaning.
+
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::error::MockError;
+use crate::generator;
+use crate::lifecycle::CompletionTokensDetails;
+use crate::sse;
+use crate::tokenizer::TokenEncoding;
+
+/// The most cells of the alignment table filled for one diff.
+pub const MAX_LCS_CELLS: usize = 1 << 20;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Diff {
+    pub identical: bool,
+    /// Character offset of the first difference, unless identical.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diverges_at: Option<usize>,
+    /// What `similarity` gives for the two texts.
+    pub similarity: f64,
+    pub tokens: TokenDiff,
+    pub semantic: SemanticDiff,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TokenDiff {
+    pub a_tokens: usize,
+    pub b_tokens: usize,
+    /// Tokens in both, in the same order.
+    pub equal: usize,
+    pub deleted: usize,
+    pub inserted: usize,
+    /// `2 * equal / (a_tokens + b_tokens)`: 1 for the same tokens in the same
+    /// order.
+    pub ratio: f64,
+    /// Runs of text that transform `a` into `b`. Equal and deleted runs
+    /// quote `a`, inserted runs `b`, with the spacing inside each run kept.
+    pub ops: Vec<Op>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "op", content = "text", rename_all = "lowercase")]
+pub enum Op {
+    Equal(String),
+    Delete(String),
+    Insert(String),
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SemanticDiff {
+    /// Each text's SimHash, as 16 hex digits.
+    pub a_hash: String,
+    pub b_hash: String,
+    /// Bits the hashes differ in, out of 64.
+    pub distance: u32,
+    /// `1 - distance / 64`.
+    pub similarity: f64,
+}
+
+/// Compares `a` with `b`.
+pub fn diff(a: &str, b: &str) -> Diff {
+    let diverges_at = a
+        .chars()
+        .zip(b.chars())
+        .position(|(x, y)| x != y)
+        .or_else(|| {
+            let (a_chars, b_chars) = (a.chars().count(), b.chars().count());
+            (a_chars != b_chars).then(|| a_chars.min(b_chars))
+        });
+    let (a_hash, b_hash) = (simhash(a), simhash(b));
+    let distance = (a_hash ^ b_hash).count_ones();
+    Diff {
+        identical: diverges_at.is_none(),
+        diverges_at,
+        similarity: similarity(a, b),
+        tokens: token_diff(a, b),
+        semantic: SemanticDiff {
+            a_hash: format!("{:016x}", a_hash),
+            b_hash: format!("{:016x}", b_hash),
+            distance,
+            similarity: 1.0 - f64::from(distance) / 64.0,
+        },
+    }
+}
+
+/// The Sørensen–Dice coefficient of the words of `a` and `b`, counted with
+/// multiplicity: 1 for the same words in any order, 0 for none in common.
+/// Two empty texts are identical.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    dice(&word_counts(a), &word_counts(b))
+}
+
+fn word_counts(text: &str) -> HashMap<&str, usize> {
+    let mut words = HashMap::new();
+    for word in text.split_whitespace() {
+        *words.entry(word).or_default() += 1;
+    }
+    words
+}
+
+/// `similarity` of two texts from the number of times each word occurs in
+/// them, so a text read in pieces need not be kept.
+pub fn dice<W: Eq + Hash>(a: &HashMap<W, usize>, b: &HashMap<W, usize>) -> f64 {
+    let (total_a, total_b) = (a.values().sum::<usize>(), b.values().sum::<usize>());
+    if total_a + total_b == 0 {
+        return 1.0;
+    }
+    let shared: usize = a.iter().map(|(word, &n)| n.min(b.get(word).copied().unwrap_or(0))).sum();
+    2.0 * shared as f64 / (total_a + total_b) as f64
+}
+
+/// The byte ranges of `text`'s tokens: runs of letters and digits, and each
+/// other non-space character on its own.
+pub fn tokens(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut word: Option<usize> = None;
+    for (at, c) in text.char_indices() {
+        if c.is_alphanumeric() {
+            word.get_or_insert(at);
+            continue;
+        }
+        if let Some(start) = word.take() {
+            spans.push((start, at));
+        }
+        if !c.is_whitespace() {
+            spans.push((at, at + c.len_utf8()));
+        }
+    }
+    if let Some(start) = word {
+        spans.push((start, text.len()));
+    }
+    spans
+}
+
+/// Aligns the tokens of `a` and `b`.
+pub fn token_diff(a: &str, b: &str) -> TokenDiff {
+    let (a_spans, b_spans) = (tokens(a), tokens(b));
+    let a_tokens: Vec<&str> = a_spans.iter().map(|&(s, e)| &a[s..e]).collect();
+    let b_tokens: Vec<&str> = b_spans.iter().map(|&(s, e)| &b[s..e]).collect();
+
+    // Each step is (kind, index into a or b); runs are merged afterwards.
+    let prefix = a_tokens.iter().zip(&b_tokens).take_while(|(x, y)| x == y).count();
+    let suffix = a_tokens[prefix..]
+        .iter()
+        .rev()
+        .zip(b_tokens[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a_tokens[prefix..a_tokens.len() - suffix], &b_tokens[prefix..b_tokens.len() - suffix]);
+    let mut steps: Vec<(Kind, usize)> = (0..prefix).map(|i| (Kind::Equal, i)).collect();
+    steps.extend(align(a_mid, b_mid).into_iter().map(|(kind, i)| (kind, i + prefix)));
+    steps.extend((a_tokens.len() - suffix..a_tokens.len()).map(|i| (Kind::Equal, i)));
+
+    let mut ops: Vec<Op> = Vec::new();
+    let mut run: Option<(Kind, usize, usize)> = None;
+    let count = |kind| steps.iter().filter(|(k, _)| *k == kind).count();
+    let (equal, deleted, inserted) = (count(Kind::Equal), count(Kind::Delete), count(Kind::Insert));
+    let quote = |kind: Kind, first: usize, last: usize| match kind {
+        Kind::Equal => Op::Equal(a[a_spans[first].0..a_spans[last].1].to_string()),
+        Kind::Delete => Op::Delete(a[a_spans[first].0..a_spans[last].1].to_string()),
+        Kind::Insert => Op::Insert(b[b_spans[first].0..b_spans[last].1].to_string()),
+    };
+    for (kind, i) in steps {
+        run = match run {
+            Some((k, first, last)) if k == kind && last + 1 == i => Some((k, first, i)),
+            Some((k, first, last)) => {
+                ops.push(quote(k, first, last));
+                Some((kind, i, i))
+            }
+            None => Some((kind, i, i)),
+        };
+    }
+    if let Some((kind, first, last)) = run {
+        ops.push(quote(kind, first, last));
+    }
+    let total = a_tokens.len() + b_tokens.len();
+    TokenDiff {
+        a_tokens: a_tokens.len(),
+        b_tokens: b_tokens.len(),
+        equal,
+        deleted,
+        inserted,
+        ratio: if total == 0 { 1.0 } else { 2.0 * equal as f64 / total as f64 },
+        ops,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Edit steps from `a` to `b` along a longest common subsequence. Equal and
+/// delete steps index `a`, insert steps `b`. Deletions come before
+/// insertions where either order would do.
+fn align(a: &[&str], b: &[&str]) -> Vec<(Kind, usize)> {
+    if a.len().saturating_mul(b.len()) > MAX_LCS_CELLS {
+        let mut steps: Vec<(Kind, usize)> = (0..a.len()).map(|i| (Kind::Delete, i)).collect();
+        steps.extend((0..b.len()).map(|j| (Kind::Insert, j)));
+        return steps;
+    }
+    // lengths[i][j]: common subsequence of a[i..] and b[j..].
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut steps = Vec::with_capacity(a.len() + b.len());
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            steps.push((Kind::Equal, i));
+            (i, j) = (i + 1, j + 1);
+        } else if j == b.len() || (i < a.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1]) {
+            steps.push((Kind::Delete, i));
+            i += 1;
+        } else {
+            steps.push((Kind::Insert, j));
+            j += 1;
+        }
+    }
+    steps
+}
+
+/// Prediction tokens skipped over looking for the next reply token, past
+/// which the reply token counts as new text instead.
+const RESYNC_TOKENS: usize = 32;
+
+/// How much of `prediction` made it into `reply`, as `PredictionMatcher`
+/// works it out.
+pub fn prediction_tokens(prediction: &str, reply: &str, encoding: TokenEncoding) -> CompletionTokensDetails {
+    let mut matcher = PredictionMatcher::new(prediction, encoding);
+    matcher.push(reply);
+    matcher.details("")
+}
+
+/// How much of a prediction a reply uses, worked out as the reply arrives
+/// so it need not be kept: the tokens of the runs of the prediction the
+/// reply shares, and of the runs it skips or never reaches, counted with
+/// the encoding. Each reply token is looked for in the prediction from
+/// where the last one matched, up to `RESYNC_TOKENS` ahead. Unlike
+/// `token_diff` this finds no best alignment, so a reply that moves text
+/// around in the prediction gets less of it accepted.
+pub struct PredictionMatcher {
+    prediction: String,
+    spans: Vec<(usize, usize)>,
+    encoding: TokenEncoding,
+    progress: Progress,
+}
+
+/// Where a `PredictionMatcher` has got to.
+#[derive(Clone, Copy, Default)]
+struct Progress {
+    /// The next prediction token to match.
+    cursor: usize,
+    /// The first token of the run of matches ending at the cursor.
+    run: Option<usize>,
+    accepted: usize,
+    rejected: usize,
+}
+
+impl PredictionMatcher {
+    pub fn new(prediction: &str, encoding: TokenEncoding) -> PredictionMatcher {
+        PredictionMatcher {
+            prediction: prediction.to_string(),
+            spans: tokens(prediction),
+            encoding,
+            progress: Progress::default(),
+        }
+    }
+
+    /// Matches `text`, the next part of the reply. It must end between
+    /// tokens, as text up to `tokenizer::token_boundary` does.
+    pub fn push(&mut self, text: &str) {
+        self.progress = self.advance(self.progress, text);
+    }
+
+    /// The counts so far, taking `rest` as the end of the reply.
+    pub fn details(&self, rest: &str) -> CompletionTokensDetails {
+        let mut progress = self.advance(self.progress, rest);
+        self.close_run(&mut progress);
+        progress.rejected += self.count(progress.cursor, self.spans.len());
+        CompletionTokensDetails {
+            accepted_prediction_tokens: progress.accepted,
+            rejected_prediction_tokens: progress.rejected,
+        }
+    }
+
+    fn advance(&self, mut progress: Progress, text: &str) -> Progress {
+        for (start, end) in tokens(text) {
+            let token = &text[start..end];
+            let window = progress.cursor..self.spans.len().min(progress.cursor + RESYNC_TOKENS);
+            let found = window.into_iter().find(|&k| &self.prediction[self.spans[k].0..self.spans[k].1] == token);
+            match found {
+                Some(k) => {
+                    if k > progress.cursor {
+                        self.close_run(&mut progress);
+                        progress.rejected += self.count(progress.cursor, k);
+                    }
+                    progress.run.get_or_insert(k);
+                    progress.cursor = k + 1;
+                }
+                // Text the prediction does not have ends the run it falls in.
+                None => self.close_run(&mut progress),
+            }
+        }
+        progress
+    }
+
+    fn close_run(&self, progress: &mut Progress) {
+        if let Some(first) = progress.run.take() {
+            progress.accepted += self.count(first, progress.cursor);
+        }
+    }
+
+    /// Tokens, by the encoding, of prediction tokens `from` to `to`.
+    fn count(&self, from: usize, to: usize) -> usize {
+        match from < to {
+            true => self.encoding.count(&self.prediction[self.spans[from].0..self.spans[to - 1].1]),
+            false => 0,
+        }
+    }
+}
+
+/// A 64-bit SimHash of `text`'s lowercased word trigrams, or of its words
+/// when it has fewer than three.
+pub fn simhash(text: &str) -> u64 {
+    let mut hasher = SimHasher::default();
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        hasher.push(word);
+    }
+    hasher.finish()
+}
+
+/// `simhash` built up a word at a time, for text read in pieces.
+#[derive(Clone, Debug)]
+pub struct SimHasher {
+    votes: [i64; 64],
+    /// The last two words, lowercased, or as many as there have been.
+    recent: Vec<String>,
+    words: usize,
+}
+
+impl Default for SimHasher {
+    fn default() -> Self {
+        SimHasher {
+            votes: [0; 64],
+            recent: Vec::with_capacity(2),
+            words: 0,
+        }
+    }
+}
+
+impl SimHasher {
+    /// Adds the next word of the text.
+    pub fn push(&mut self, word: &str) {
+        let word = word.to_lowercase();
+        if self.recent.len() == 2 {
+            let hash = generator::stable_hash(self.recent.iter().map(String::as_str).chain([word.as_str()]));
+            vote(&mut self.votes, hash);
+            self.recent.remove(0);
+        }
+        self.recent.push(word);
+        self.words += 1;
+    }
+
+    /// The hash of the words so far.
+    pub fn finish(&self) -> u64 {
+        let mut votes = self.votes;
+        // Fewer than three words are hashed as one shingle.
+        if (1..3).contains(&self.words) {
+            vote(&mut votes, generator::stable_hash(self.recent.iter().map(String::as_str)));
+        }
+        votes.iter().enumerate().filter(|(_, &v)| v > 0).fold(0, |hash, (bit, _)| hash | 1 << bit)
+    }
+}
+
+fn vote(votes: &mut [i64; 64], hash: u64) {
+    for (bit, vote) in votes.iter_mut().enumerate() {
+        *vote += if hash >> bit & 1 == 1 { 1 } else { -1 };
+    }
+}
+
+/// The reply text of a response body: a `chat.completion` object, an SSE
+/// stream of `chat.completion.chunk` events as a string, or plain text.
+pub fn reply_text(body: &serde_json::Value) -> Option<String> {
+    match body {
+        serde_json::Value::String(text) if text.lines().any(|l| l.starts_with("data:")) => Some(stream_text(text)),
+        serde_json::Value::String(text) => Some(text.clone()),
+        serde_json::Value::Object(_) => {
+            let message = &body["choices"][0]["message"];
+            message.get("content").map(|content| content.as_str().unwrap_or_default().to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The content deltas of an SSE stream, concatenated.
+pub fn stream_text(stream: &str) -> String {
+    sse::parse(stream.as_bytes())
+        .into_iter()
+        .filter_map(|event| serde_json::from_str::<serde_json::Value>(&event.data).ok())
+        .filter_map(|event| event["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct DiffRequest {
+    pub a: serde_json::Value,
+    pub b: serde_json::Value,
+}
+
+#[post("/v1/internal/diff")]
+pub async fn diff_endpoint(body: web::Json<DiffRequest>) -> Result<HttpResponse, MockError> {
+    let text = |value: &serde_json::Value, param: &'static str| {
+        reply_text(value).ok_or_else(|| {
+            MockError::rejected(
+                StatusCode::BAD_REQUEST,
+                format!("`{}` must be a chat.completion object, an SSE stream or text", param),
+                Some(param),
+            )
+        })
+    };
+    let (a, b) = (text(&body.a, "a")?, text(&body.b, "b")?);
+    Ok(HttpResponse::Ok().json(diff(&a, &b)))
+}
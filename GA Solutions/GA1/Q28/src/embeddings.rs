@@ -0,0 +1,168 @@
+//! `/v1/embeddings`: unit vectors derived from each input's text, the same
+//! for the same model and input, so retrieval code can be exercised offline.
+//! Similar texts do not get similar vectors. Keys need the `embeddings`
+//! capability and the model on their allowlist.
+
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::MockError;
+use crate::generator;
+use crate::keys::{self, Capability};
+use crate::orgs;
+use crate::state::AppState;
+use crate::tokenizer::TokenEncoding;
+
+const DEFAULT_MODEL: &str = "text-embedding-3-small";
+/// More inputs than OpenAI takes in one request.
+const MAX_INPUTS: usize = 2048;
+
+#[derive(Deserialize)]
+pub struct EmbeddingRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub input: EmbeddingInput,
+    /// Shortens `text-embedding-3-*` vectors.
+    #[serde(default)]
+    pub dimensions: Option<usize>,
+    #[serde(default)]
+    pub encoding_format: EncodingFormat,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingFormat {
+    #[default]
+    Float,
+    /// The vector's little-endian `f32`s, base64-encoded.
+    Base64,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Vector {
+    Float(Vec<f32>),
+    Base64(String),
+}
+
+#[derive(Serialize)]
+struct Embedding {
+    object: &'static str,
+    index: usize,
+    embedding: Vector,
+}
+
+#[derive(Serialize)]
+struct EmbeddingUsage {
+    prompt_tokens: usize,
+    total_tokens: usize,
+}
+
+#[derive(Serialize)]
+struct EmbeddingList<'a> {
+    object: &'static str,
+    data: Vec<Embedding>,
+    model: &'a str,
+    usage: EmbeddingUsage,
+}
+
+/// The length of `model`'s vectors, as OpenAI's embedding models have them.
+fn native_dimensions(model: &str) -> usize {
+    if model.starts_with("text-embedding-3-large") {
+        3072
+    } else {
+        1536
+    }
+}
+
+/// The vector for `text`: components drawn from its hash, scaled to unit length.
+pub fn embed(model: &str, text: &str, dimensions: usize) -> Vec<f32> {
+    let seed = generator::stable_hash([model, text]);
+    let mut vector: Vec<f32> = (0..dimensions as u64)
+        .map(|i| (generator::unit(generator::mix64(seed.wrapping_add(i.wrapping_mul(generator::SPLITMIX_GAMMA)))) * 2.0 - 1.0) as f32)
+        .collect();
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+#[post("/v1/embeddings")]
+pub async fn embeddings_endpoint(
+    http_req: HttpRequest,
+    body: web::Json<EmbeddingRequest>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let req = body.into_inner();
+    let model = req.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    keys::authorize_use(&config.keys, &http_req, Some(model), [(Capability::Embeddings, true)])?;
+    let scope = orgs::resolve(&config, &http_req)?;
+    let bad_request = |message: &str, param: &str| MockError::rejected(StatusCode::BAD_REQUEST, message, Some(param));
+
+    let inputs = match req.input {
+        EmbeddingInput::One(text) => vec![text],
+        EmbeddingInput::Many(texts) => texts,
+    };
+    if inputs.is_empty() || inputs.len() > MAX_INPUTS || inputs.iter().any(|text| text.is_empty()) {
+        let message = format!("input must be a non-empty string or an array of 1 to {} non-empty strings", MAX_INPUTS);
+        return Err(bad_request(&message, "input"));
+    }
+    let native = native_dimensions(model);
+    let dimensions = match req.dimensions {
+        None => native,
+        Some(_) if !model.starts_with("text-embedding-3") => {
+            return Err(bad_request("This model does not support specifying dimensions.", "dimensions"))
+        }
+        Some(n) if n == 0 || n > native => {
+            return Err(bad_request(&format!("dimensions must be between 1 and {}", native), "dimensions"))
+        }
+        Some(n) => n,
+    };
+
+    let encoding = state.models.get(model).map_or(TokenEncoding::Cl100k, |m| m.encoding);
+    let tokens = inputs.iter().fold(0usize, |sum, text| sum.saturating_add(encoding.count(text)));
+    let rate_limits = state
+        .rate_limits
+        .charge(&scope.account(keys::bearer_token(&http_req).unwrap_or_default()), || tokens as u64)?;
+
+    let data = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, text)| {
+            let vector = embed(model, text, dimensions);
+            let embedding = match req.encoding_format {
+                EncodingFormat::Float => Vector::Float(vector),
+                EncodingFormat::Base64 => Vector::Base64(STANDARD.encode(vector.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>())),
+            };
+            Embedding {
+                object: "embedding",
+                index,
+                embedding,
+            }
+        })
+        .collect();
+    let mut response = HttpResponse::Ok();
+    rate_limits.insert_headers(&mut response);
+    Ok(response.json(EmbeddingList {
+        object: "list",
+        data,
+        model,
+        usage: EmbeddingUsage {
+            prompt_tokens: tokens,
+            total_tokens: tokens,
+        },
+    }))
+}
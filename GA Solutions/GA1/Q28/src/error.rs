@@ -0,0 +1,313 @@
+use actix_web::error::{InternalError, JsonPayloadError, PayloadError};
+use actix_web::http::{header, StatusCode};
+use actix_web::{mime, web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Failures the mock server reports to clients instead of panicking.
+#[derive(Debug)]
+pub enum MockError {
+    /// The request was refused before any streaming started.
+    Rejected {
+        status: StatusCode,
+        message: String,
+        param: Option<String>,
+        code: Option<&'static str>,
+    },
+    /// A response frame could not be serialized.
+    Serialize(String),
+    /// The request was refused with a 429; `retry_after` becomes the
+    /// `Retry-After` header.
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+}
+
+impl MockError {
+    pub fn rejected(status: StatusCode, message: impl Into<String>, param: Option<&str>) -> MockError {
+        MockError::Rejected {
+            status,
+            message: message.into(),
+            param: param.map(str::to_string),
+            code: None,
+        }
+    }
+
+    /// Sets the machine-readable `code` of a rejection.
+    pub fn with_code(mut self, code: &'static str) -> MockError {
+        if let MockError::Rejected { code: slot, .. } = &mut self {
+            *slot = Some(code);
+        }
+        self
+    }
+
+    pub fn envelope(&self) -> ErrorEnvelope {
+        match self {
+            MockError::Rejected {
+                status,
+                message,
+                param,
+                code,
+            } => {
+                let envelope = ErrorEnvelope::new(*status, message.clone(), param.as_deref());
+                match code {
+                    Some(code) => envelope.with_code(code),
+                    None => envelope,
+                }
+            }
+            MockError::Serialize(_) => ErrorEnvelope::new(self.status_code(), self.to_string(), None).with_code("serialization_failed"),
+            MockError::RateLimited { message, .. } => {
+                ErrorEnvelope::new(self.status_code(), message.clone(), None).with_code("rate_limit_exceeded")
+            }
+        }
+    }
+}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockError::Rejected { message, .. } => f.write_str(message),
+            MockError::Serialize(detail) => write!(f, "Failed to serialize response: {}", detail),
+            MockError::RateLimited { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for MockError {}
+
+impl ResponseError for MockError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            MockError::Rejected { status, .. } => *status,
+            MockError::Serialize(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            MockError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let MockError::RateLimited {
+            retry_after: Some(after), ..
+        } = self
+        {
+            // Whole seconds, rounded up so clients never retry early.
+            response.insert_header(("Retry-After", after.as_secs_f64().ceil().to_string()));
+        }
+        response.json(self.envelope())
+    }
+}
+
+/// The provider format error responses and in-band error events follow.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorStyle {
+    /// `{"error": {"message", "type", "param", "code"}}`.
+    #[default]
+    Openai,
+    /// `{"type": "error", "error": {"type", "message"}}`.
+    Anthropic,
+}
+
+/// Anthropic-style error body.
+#[derive(Serialize)]
+pub struct AnthropicEnvelope {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub error: AnthropicErrorBody,
+}
+
+#[derive(Serialize)]
+pub struct AnthropicErrorBody {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl AnthropicEnvelope {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> AnthropicEnvelope {
+        AnthropicEnvelope {
+            kind: "error",
+            error: AnthropicErrorBody {
+                kind: anthropic_error_type_for(status),
+                message: message.into(),
+            },
+        }
+    }
+}
+
+/// The error `type` Anthropic reports for a given HTTP status.
+pub fn anthropic_error_type_for(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 | 422 => "invalid_request_error",
+        401 => "authentication_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        413 => "request_too_large",
+        429 => "rate_limit_error",
+        529 => "overloaded_error",
+        _ => "api_error",
+    }
+}
+
+/// OpenAI-style error body: `{"error": {"message", "type", "param", "code"}}`.
+#[derive(Serialize)]
+pub struct ErrorEnvelope {
+    pub error: ErrorBody,
+}
+
+#[derive(Serialize)]
+pub struct ErrorBody {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub param: Option<String>,
+    pub code: Option<String>,
+}
+
+/// The `type` OpenAI reports for a given HTTP status.
+pub fn error_type_for(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 | 404 | 413 | 415 | 422 => "invalid_request_error",
+        401 => "authentication_error",
+        403 => "permission_error",
+        429 => "rate_limit_error",
+        500..=599 => "server_error",
+        _ => "api_error",
+    }
+}
+
+impl ErrorEnvelope {
+    pub fn new(status: StatusCode, message: impl Into<String>, param: Option<&str>) -> ErrorEnvelope {
+        ErrorEnvelope {
+            error: ErrorBody {
+                message: message.into(),
+                kind: error_type_for(status).to_string(),
+                param: param.map(str::to_string),
+                code: None,
+            },
+        }
+    }
+
+    pub fn with_code(mut self, code: &str) -> ErrorEnvelope {
+        self.error.code = Some(code.to_string());
+        self
+    }
+}
+
+/// A plain `application/json` error response carrying an OpenAI error envelope.
+pub fn json_error(status: StatusCode, message: impl Into<String>, param: Option<&str>) -> HttpResponse {
+    MockError::rejected(status, message, param).error_response()
+}
+
+/// A JSON error response in the given provider's format.
+pub fn styled_error(style: ErrorStyle, status: StatusCode, message: impl Into<String>, param: Option<&str>) -> HttpResponse {
+    match style {
+        ErrorStyle::Openai => json_error(status, message, param),
+        ErrorStyle::Anthropic => HttpResponse::build(status).json(AnthropicEnvelope::new(status, message)),
+    }
+}
+
+/// Extractor config that turns JSON body failures into OpenAI-style 400s
+/// instead of actix's plain-text defaults.
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(body_error)
+}
+
+/// `err` as the OpenAI error the provider sends for the same body.
+pub fn body_error(err: JsonPayloadError, req: &HttpRequest) -> actix_web::Error {
+    // A body in another charset is a media type problem, whatever the parser made of it.
+    if let Err(unsupported) = check_media_type(req) {
+        return InternalError::from_response(err, unsupported.error_response()).into();
+    }
+    let (status, envelope) = describe_json_error(&err);
+    let response = HttpResponse::build(status).json(envelope);
+    InternalError::from_response(err, response).into()
+}
+
+/// `Content-Encoding`s request bodies may arrive in. The body extractors
+/// decode them before parsing, and their size limits count decoded bytes.
+pub const BODY_ENCODINGS: &[&str] = &["identity", "gzip", "deflate", "br", #[cfg(feature = "zstd")] "zstd"];
+
+/// The 415 for a body the mock cannot read: not JSON (`+json` types count),
+/// JSON with a charset other than UTF-8, the only encoding JSON has on the
+/// wire, or compressed in an encoding it cannot decode. Actix's JSON
+/// extractor checks the type but not the charset, so handlers using it call
+/// this first.
+pub fn check_media_type(req: &HttpRequest) -> Result<(), MockError> {
+    let unsupported = |message: String| Err(MockError::rejected(StatusCode::UNSUPPORTED_MEDIA_TYPE, message, None).with_code("unsupported_media_type"));
+    if let Some(encoding) = req.headers().get(header::CONTENT_ENCODING) {
+        let encoding = encoding.to_str().unwrap_or_default().trim();
+        if !BODY_ENCODINGS.iter().any(|known| known.eq_ignore_ascii_case(encoding)) {
+            return unsupported(format!("Unsupported Content-Encoding '{}'; expected one of {}", encoding, BODY_ENCODINGS.join(", ")));
+        }
+    }
+    let readable = match req.mime_type() {
+        Ok(Some(mime)) => {
+            let json = mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON);
+            json && mime.get_param(mime::CHARSET).is_none_or(|charset| charset == mime::UTF_8)
+        }
+        _ => false,
+    };
+    if readable {
+        return Ok(());
+    }
+    unsupported(match req.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        Some(content_type) => format!("Unsupported Content-Type '{}'; expected application/json with UTF-8 encoding", content_type),
+        None => "Missing Content-Type header; expected application/json".to_string(),
+    })
+}
+
+fn describe_json_error(err: &JsonPayloadError) -> (StatusCode, ErrorEnvelope) {
+    let bad_request = StatusCode::BAD_REQUEST;
+    match err {
+        JsonPayloadError::Deserialize(e) if e.is_data() => {
+            let detail = e.to_string();
+            if let Some(field) = backticked_after(&detail, "missing field ") {
+                let message = format!("Missing required parameter: '{}'.", field);
+                (bad_request, ErrorEnvelope::new(bad_request, message, Some(field)).with_code("missing_required_parameter"))
+            } else if let Some(field) = backticked_after(&detail, "unknown field ") {
+                let message = format!("Unrecognized request argument supplied: {}", field);
+                (bad_request, ErrorEnvelope::new(bad_request, message, Some(field)).with_code("unknown_parameter"))
+            } else if detail.contains("did not match any variant") {
+                let message = "Request body must include either 'messages' or the legacy 'prompt' and 'stream' fields.";
+                (bad_request, ErrorEnvelope::new(bad_request, message, Some("messages")).with_code("missing_required_parameter"))
+            } else {
+                let message = format!("Invalid value in request body: {}", detail);
+                (bad_request, ErrorEnvelope::new(bad_request, message, None).with_code("invalid_type"))
+            }
+        }
+        JsonPayloadError::Deserialize(_) | JsonPayloadError::Serialize(_) => {
+            let message = "We could not parse the JSON body of your request. (HINT: This likely means you aren't \
+                           using your HTTP library correctly. The OpenAI API expects a JSON payload, but what was \
+                           sent was not valid JSON.)";
+            (bad_request, ErrorEnvelope::new(bad_request, message, None))
+        }
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+            let status = StatusCode::PAYLOAD_TOO_LARGE;
+            (status, ErrorEnvelope::new(status, err.to_string(), None))
+        }
+        JsonPayloadError::ContentType => {
+            let status = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+            let message = "Invalid Content-Type header; expected application/json";
+            (status, ErrorEnvelope::new(status, message, None).with_code("unsupported_media_type"))
+        }
+        JsonPayloadError::Payload(PayloadError::EncodingCorrupted) => {
+            let message = "We could not decompress the body of your request; check that it matches its Content-Encoding.";
+            (bad_request, ErrorEnvelope::new(bad_request, message, None))
+        }
+        JsonPayloadError::Payload(PayloadError::Overflow) => {
+            let status = StatusCode::PAYLOAD_TOO_LARGE;
+            (status, ErrorEnvelope::new(status, err.to_string(), None))
+        }
+        _ => (bad_request, ErrorEnvelope::new(bad_request, err.to_string(), None)),
+    }
+}
+
+/// Pulls `name` out of serde messages shaped like ``missing field `name` at line 1``.
+fn backticked_after<'a>(detail: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = &detail[detail.find(marker)? + marker.len()..];
+    let rest = rest.strip_prefix('`')?;
+    Some(&rest[..rest.find('`')?])
+}
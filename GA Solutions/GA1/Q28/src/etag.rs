@@ -0,0 +1,42 @@
+//! Entity tags for JSON chat completions, so HTTP caches in front of the
+//! mock can revalidate instead of fetching the reply again.
+//!
+//! Only replies to requests with a `seed` are tagged, since only those
+//! promise the same reply next time. A tag covers everything in the
+//! completion but what differs from one reply to the next: its `id` and
+//! `created`, and the `x_mock` fields `queue_ms` and `watermark`. A request
+//! whose `If-None-Match` names it is answered `304 Not Modified` with no
+//! body. The reply is still generated, and paced, to find its tag.
+
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use sha2::{Digest, Sha256};
+
+/// The strong tag of `completion`, quoted for the `ETag` header.
+pub fn of(completion: &serde_json::Value) -> String {
+    let mut content = completion.clone();
+    if let Some(fields) = content.as_object_mut() {
+        fields.remove("id");
+        fields.remove("created");
+        if let Some(extension) = fields.get_mut("x_mock").and_then(|x| x.as_object_mut()) {
+            extension.remove("queue_ms");
+            extension.remove("watermark");
+            if extension.is_empty() {
+                fields.remove("x_mock");
+            }
+        }
+    }
+    let digest = Sha256::digest(content.to_string().as_bytes());
+    format!("\"{}\"", digest[..16].iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+/// Whether `req`'s `If-None-Match` names `etag`, compared weakly as RFC 9110
+/// has it, or is `*`.
+pub fn not_modified(req: &HttpRequest, etag: &str) -> bool {
+    req.headers()
+        .get_all(header::IF_NONE_MATCH)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
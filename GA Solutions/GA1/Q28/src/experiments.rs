@@ -0,0 +1,57 @@
+//! Simulated A/B routing (`[[experiments]]`): a model name served by several
+//! response profiles by weight, for testing experimentation frameworks that
+//! compare variants. The choice is a hash of the request's seed, so a client
+//! `seed`, or the same messages, always lands on the same variant. Replies
+//! name their variant in `x_mock.variant` and `X-Mock-Variant`, and captures
+//! record it.
+//!
+//! A matching stub rule still wins over an experiment, and an experiment over
+//! the model's preset.
+
+use serde::Deserialize;
+
+use crate::chat::NormalizedRequest;
+use crate::generator;
+use crate::stubs::ResponseProfile;
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Experiment {
+    /// The model name requests are routed on.
+    pub model: String,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Variant {
+    pub name: String,
+    /// Share of traffic relative to the other variants; 0 turns it off.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub profile: ResponseProfile,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// The variant that serves `req`, if its model is under an experiment.
+pub fn choose<'a>(experiments: &'a [Experiment], req: &NormalizedRequest) -> Option<&'a Variant> {
+    let model = req.model.as_deref()?;
+    let experiment = experiments.iter().find(|e| e.model == model)?;
+    let total: u64 = experiment.variants.iter().map(|v| u64::from(v.weight)).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut pick = generator::mix64(req.seed) % total;
+    experiment.variants.iter().find(|v| {
+        let weight = u64::from(v.weight);
+        if pick < weight {
+            return true;
+        }
+        pick -= weight;
+        false
+    })
+}
@@ -0,0 +1,105 @@
+//! Exports of the capture store for use outside the mock, served from
+//! `GET /v1/admin/captures/export?format=...` and written by `stream-api export`.
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::admin;
+use crate::capture::Capture;
+use crate::config::Config;
+use crate::error::MockError;
+use crate::har;
+use crate::state::AppState;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// OpenAI chat fine-tuning JSONL: one `{"messages": [...]}` per line,
+    /// ending with the assistant's reply.
+    #[default]
+    Finetune,
+    /// HTTP Archive 1.2 with SSE chunk timings; see `har`.
+    Har,
+}
+
+impl ExportFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Finetune => "finetune",
+            ExportFormat::Har => "har",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Example<'a> {
+    messages: Vec<ExampleMessage<'a>>,
+}
+
+#[derive(Serialize)]
+struct ExampleMessage<'a> {
+    role: &'a str,
+    content: String,
+}
+
+/// One fine-tuning example per capture whose stream reached `[DONE]` with
+/// its reply kept in full. Multi-part content is flattened to its text and
+/// messages without content are left out.
+pub fn finetune_jsonl(captures: &[Capture]) -> Result<String, MockError> {
+    let mut out = String::new();
+    for capture in captures {
+        let Some(reply) = capture.reply.as_ref().filter(|r| r.finished && !r.truncated && !capture.rejected) else {
+            continue;
+        };
+        let mut messages: Vec<ExampleMessage> = capture
+            .messages
+            .iter()
+            .filter_map(|m| {
+                m.content.as_ref().map(|content| ExampleMessage {
+                    role: &m.role,
+                    content: content.text(),
+                })
+            })
+            .collect();
+        messages.push(ExampleMessage {
+            role: "assistant",
+            content: reply.content.clone(),
+        });
+        let line = serde_json::to_string(&Example { messages }).map_err(|e| MockError::Serialize(e.to_string()))?;
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[get("/v1/admin/captures/export")]
+pub async fn export_endpoint(
+    req: HttpRequest,
+    query: web::Query<ExportQuery>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return Ok(resp);
+    }
+    let captures = state.captures.list();
+    Ok(match query.format {
+        ExportFormat::Finetune => HttpResponse::Ok()
+            .content_type("application/jsonl")
+            .insert_header(("Content-Disposition", "attachment; filename=\"captures.jsonl\""))
+            .body(finetune_jsonl(&captures)?),
+        ExportFormat::Har => {
+            let connection = req.connection_info();
+            let url = format!("{}://{}/v1/chat/completions", connection.scheme(), connection.host());
+            HttpResponse::Ok()
+                .insert_header(("Content-Disposition", "attachment; filename=\"captures.har\""))
+                .json(har::from_captures(&captures, &url))
+        }
+    })
+}
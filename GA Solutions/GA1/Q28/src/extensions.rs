@@ -0,0 +1,65 @@
+//! Sampling parameters open-source inference servers (vLLM, TGI) accept on
+//! top of OpenAI's. The mock does not act on them, but validates them the way
+//! vLLM does, keeps them in captures, and lets stub rules match on them.
+
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::error::MockError;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Extensions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_of: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_beam_search: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repetition_penalty: Option<f64>,
+}
+
+impl Extensions {
+    pub fn is_empty(&self) -> bool {
+        *self == Extensions::default()
+    }
+
+    /// Whether every field set in `self` has the same value in `other`, so
+    /// an empty set of conditions holds for every request.
+    pub fn within(&self, other: &Extensions) -> bool {
+        fn holds<T: PartialEq>(condition: &Option<T>, value: &Option<T>) -> bool {
+            condition.is_none() || condition == value
+        }
+        holds(&self.best_of, &other.best_of)
+            && holds(&self.use_beam_search, &other.use_beam_search)
+            && holds(&self.min_tokens, &other.min_tokens)
+            && holds(&self.repetition_penalty, &other.repetition_penalty)
+    }
+
+    /// The 400 vLLM answers out-of-range values with. `max_tokens` is the
+    /// completion budget the request reserved.
+    pub fn validate(&self, max_tokens: Option<usize>) -> Result<(), MockError> {
+        let invalid = |message: String, param: &str| Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some(param)));
+        if self.best_of == Some(0) {
+            return invalid("best_of must be at least 1, got 0.".to_string(), "best_of");
+        }
+        if self.use_beam_search == Some(true) && self.best_of.unwrap_or(1) <= 1 {
+            return invalid(
+                format!("best_of must be greater than 1 when using beam search. Got {}.", self.best_of.unwrap_or(1)),
+                "best_of",
+            );
+        }
+        if let (Some(min), Some(max)) = (self.min_tokens, max_tokens) {
+            if min > max {
+                return invalid(format!("min_tokens must be less than or equal to max_tokens={}, got {}.", max, min), "min_tokens");
+            }
+        }
+        if let Some(penalty) = self.repetition_penalty {
+            if !(penalty.is_finite() && penalty > 0.0) {
+                return invalid(format!("repetition_penalty must be greater than zero, got {}.", penalty), "repetition_penalty");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+//! A Gemini-shaped `streamGenerateContent`: `contents` and
+//! `systemInstruction` are served by the same stub rules, presets, transforms
+//! and pacing as chat completions, streamed as `GenerateContentResponse`
+//! candidates with `usageMetadata`. With `?alt=sse` each response is an SSE
+//! `data:` event; otherwise the responses are streamed as one JSON array, as
+//! the provider does. Errors use Google's `{"error": {code, message, status}}`.
+//! Keys may come as `x-goog-api-key` or `?key=`, as Google's clients send them.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, AUTHORIZATION};
+use actix_web::http::StatusCode;
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{post, web, Error, HttpRequest, HttpResponse, ResponseError};
+use bytes::Bytes;
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+use crate::admission::{self, Admission, Paced, Recorded, Step};
+use crate::capture::StreamRecorder;
+use crate::chat::{self, ChatRequest, ContentPart, IncomingRequest, Message, MessageContent, NormalizedRequest, ReplyText};
+use crate::config::Config;
+use crate::error::{self, MockError};
+use crate::lifecycle::Tracker;
+use crate::overrides::MockOverrides;
+use crate::sse;
+use crate::state::AppState;
+use crate::tokenizer::TokenEncoding;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiRequest {
+    pub contents: Vec<Content>,
+    #[serde(default)]
+    pub system_instruction: Option<Content>,
+    #[serde(default)]
+    pub generation_config: GenerationConfig,
+    /// Only checked for presence, against the key's capabilities.
+    #[serde(default)]
+    pub tools: Vec<serde_json::Value>,
+}
+
+/// A turn of `contents`, with Gemini's `user` and `model` roles.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Content {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    pub parts: Vec<Part>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Part {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Images and other media, only checked for presence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(default)]
+    pub max_output_tokens: Option<usize>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl GeminiRequest {
+    fn normalize(self, model: String) -> NormalizedRequest {
+        let message = |role: &str, content: Content| Message {
+            role: role.to_string(),
+            content: Some(MessageContent::Parts(
+                content
+                    .parts
+                    .into_iter()
+                    .map(|part| ContentPart {
+                        kind: if part.inline_data.is_some() { "image_url" } else { "text" }.to_string(),
+                        text: part.text,
+                        image_url: None,
+                    })
+                    .collect(),
+            )),
+            name: None,
+        };
+        let mut messages: Vec<Message> = self.system_instruction.map(|c| message("system", c)).into_iter().collect();
+        messages.extend(self.contents.into_iter().map(|c| {
+            let role = match c.role.as_deref() {
+                Some("model") => "assistant",
+                _ => "user",
+            };
+            message(role, c)
+        }));
+        NormalizedRequest::from(IncomingRequest::Chat(ChatRequest {
+            model: Some(model),
+            messages,
+            stream: Some(true),
+            seed: self.generation_config.seed,
+            max_tokens: self.generation_config.max_output_tokens,
+            tools: self.tools,
+            ..ChatRequest::default()
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    #[serde(default)]
+    pub alt: Option<String>,
+    /// The API key, which Google's clients may send here instead.
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// Presents a key sent Google's way, as `x-goog-api-key` or `?key=`, as the
+/// bearer token the key and organization checks read.
+async fn google_api_key(mut req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if !req.headers().contains_key(AUTHORIZATION) {
+        let key = req
+            .headers()
+            .get("x-goog-api-key")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .or_else(|| web::Query::<StreamQuery>::from_query(req.query_string()).ok().and_then(|q| q.into_inner().key));
+        if let Some(value) = key.and_then(|key| HeaderValue::from_str(&format!("Bearer {}", key)).ok()) {
+            req.headers_mut().insert(AUTHORIZATION, value);
+        }
+    }
+    next.call(req).await
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse<'a> {
+    candidates: [Candidate<'a>; 1],
+    usage_metadata: UsageMetadata,
+    model_version: &'a str,
+    response_id: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate<'a> {
+    content: CandidateContent<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finish_reason: Option<&'a str>,
+    index: usize,
+}
+
+#[derive(Serialize)]
+struct CandidateContent<'a> {
+    parts: [TextPart<'a>; 1],
+    role: &'static str,
+}
+
+#[derive(Serialize)]
+struct TextPart<'a> {
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    prompt_token_count: usize,
+    candidates_token_count: usize,
+    total_token_count: usize,
+}
+
+/// The gRPC status name Google's error bodies carry for `status`.
+fn status_name(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "INVALID_ARGUMENT",
+        401 => "UNAUTHENTICATED",
+        403 => "PERMISSION_DENIED",
+        404 => "NOT_FOUND",
+        409 => "ABORTED",
+        429 => "RESOURCE_EXHAUSTED",
+        499 => "CANCELLED",
+        501 => "UNIMPLEMENTED",
+        503 => "UNAVAILABLE",
+        504 => "DEADLINE_EXCEEDED",
+        _ => "INTERNAL",
+    }
+}
+
+fn error_body(status: StatusCode, message: &str) -> serde_json::Value {
+    serde_json::json!({"error": {"code": status.as_u16(), "message": message, "status": status_name(status)}})
+}
+
+/// `error` with Google's body, keeping the status and `Retry-After`.
+fn gemini_error(error: &MockError) -> HttpResponse {
+    let response = error.error_response();
+    let body = error_body(response.status(), &error.to_string()).to_string();
+    response.set_body(BoxBody::new(body))
+}
+
+/// How the responses are framed on the wire.
+#[derive(Clone, Copy)]
+enum Framing {
+    Sse,
+    JsonArray,
+}
+
+impl Framing {
+    fn frame(self, value: &impl Serialize, first: bool) -> Result<Bytes, MockError> {
+        match self {
+            Framing::Sse => sse::data_event(value),
+            Framing::JsonArray => {
+                let mut out = if first { b"[".to_vec() } else { b",\r\n".to_vec() };
+                sse::write_json(&mut out, value)?;
+                Ok(Bytes::from(out))
+            }
+        }
+    }
+}
+
+#[post("/v1beta/models/{model}:streamGenerateContent", wrap = "from_fn(google_api_key)")]
+pub async fn stream_endpoint(
+    http_req: HttpRequest,
+    model: web::Path<String>,
+    query: web::Query<StreamQuery>,
+    body: web::Json<GeminiRequest>,
+    overrides: MockOverrides,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let framing = match query.alt.as_deref() {
+        Some("sse") => Framing::Sse,
+        _ => Framing::JsonArray,
+    };
+    let req = body.into_inner().normalize(model.into_inner());
+    match stream_reply(http_req, req, framing, overrides, config, state).await {
+        Ok(response) => response,
+        Err(e) => gemini_error(&e),
+    }
+}
+
+struct Progress {
+    paced: Paced,
+    output_tokens: usize,
+    finished: bool,
+}
+
+async fn stream_reply(
+    http_req: HttpRequest,
+    mut req: NormalizedRequest,
+    framing: Framing,
+    overrides: MockOverrides,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    error::check_media_type(&http_req)?;
+    let scope = admission::check_caller(&config, &state, &http_req, &req)?;
+    let Recorded { capture_id, request_id } = admission::record(&config, &state, &http_req, &mut req, "contents")?;
+    if req.prompt.trim().is_empty() {
+        return Err(MockError::rejected(StatusCode::BAD_REQUEST, "contents must include a non-empty user turn", Some("contents")));
+    }
+    let model_info = req.model.as_deref().and_then(|m| state.models.get(m));
+    if let Some(model) = model_info {
+        chat::check_context_window(model, &req)?;
+    }
+
+    let (mut profile, rule_name, variant) = admission::profile(&config, &state, &req, &overrides, capture_id);
+    let retry_budget = admission::retry_budget(&config, &state, &http_req, &scope, &mut profile);
+    if profile.error_after == Some(0) {
+        let status = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(MockError::rejected(status, profile.error_message.clone(), None));
+    }
+    let ReplyText { chunks, total_chars, .. } = ReplyText::new(&config, &state, &req, &profile, rule_name.as_deref());
+    let encoding: TokenEncoding = model_info.map(|m| m.encoding).unwrap_or_default();
+    let charged = admission::reservation(&req, encoding, total_chars);
+    let admission = Admission::charge(&config, &state, &http_req, &scope, &request_id, req.model.as_deref(), charged).await?;
+    let input_tokens = encoding.count_messages(&req.messages);
+    let finish_reason = match profile.finish_reason.as_deref() {
+        Some("length") => "MAX_TOKENS",
+        _ => "STOP",
+    };
+
+    let error_code = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let paced = Paced::new(
+        &config,
+        &profile,
+        encoding,
+        chunks,
+        admission.lease(&state, req.model.as_deref(), profile.weight),
+        Tracker::start(&state, &request_id, req.model.as_deref(), &scope, encoding, &req.messages),
+        StreamRecorder::new(&state, capture_id),
+    );
+    let ids = Rc::new((request_id.clone(), req.model.clone().unwrap_or_default()));
+    let progress = Progress {
+        paced,
+        output_tokens: 0,
+        finished: false,
+    };
+    let body = stream::unfold(progress, move |mut progress| {
+        let ids = ids.clone();
+        async move {
+            if progress.finished {
+                return None;
+            }
+            let (request_id, model) = &*ids;
+            let first = progress.paced.sent() == 0;
+            let (chunk, last) = match progress.paced.next().await {
+                Step::Chunk(chunk) => {
+                    let last = progress.paced.is_last();
+                    (chunk, last)
+                }
+                Step::Failed => {
+                    progress.finished = true;
+                    // Mid-stream failures arrive as one more element carrying the error.
+                    let error = error_body(error_code, progress.paced.error_message());
+                    let line = framing.frame(&error, first).map(|event| close(framing, event));
+                    return Some((line.map_err(Error::from), progress));
+                }
+                // Only an empty reply gets here: the last chunk ends the
+                // stream itself. It is still one finished response.
+                Step::Done => (String::new(), true),
+            };
+            progress.output_tokens += encoding.count(&chunk);
+            let response = GenerateContentResponse {
+                candidates: [Candidate {
+                    content: CandidateContent {
+                        parts: [TextPart { text: &chunk }],
+                        role: "model",
+                    },
+                    finish_reason: last.then_some(finish_reason),
+                    index: 0,
+                }],
+                usage_metadata: UsageMetadata {
+                    prompt_token_count: input_tokens,
+                    candidates_token_count: progress.output_tokens,
+                    total_token_count: input_tokens + progress.output_tokens,
+                },
+                model_version: model,
+                response_id: request_id,
+            };
+            let mut line = framing.frame(&response, first);
+            if last {
+                progress.finished = true;
+                // Reports the reply complete.
+                progress.paced.next().await;
+                line = line.map(|event| close(framing, event));
+            }
+            Some((line.map_err(Error::from), progress))
+        }
+    });
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(match framing {
+            Framing::Sse => "text/event-stream",
+            Framing::JsonArray => "application/json",
+        })
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("x-request-id", request_id));
+    if let Some(name) = rule_name {
+        response.insert_header(("X-Mock-Rule", name));
+    }
+    if let Some(variant) = variant {
+        response.insert_header(("X-Mock-Variant", variant));
+    }
+    admission.insert_headers(&mut response);
+    if let Some(budget) = &retry_budget {
+        budget.insert_headers(&mut response);
+    }
+    profile.headers.insert_headers(&mut response);
+    Ok(response.streaming(body))
+}
+
+/// `event` with the array closed after it, when the responses are one array.
+fn close(framing: Framing, event: Bytes) -> Bytes {
+    match framing {
+        Framing::Sse => event,
+        Framing::JsonArray => [&event[..], b"]"].concat().into(),
+    }
+}
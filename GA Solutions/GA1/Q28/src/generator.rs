@@ -0,0 +1,154 @@
+use std::borrow::Cow;
+
+/// Lazily produces reply text of a fixed length by cycling through a corpus,
+/// yielding `chunk_chars` characters at a time. Only the chunk being handed
+/// out is ever allocated, so replies of millions of tokens cost no more memory
+/// than short ones.
+pub struct CyclingText {
+    header: String,
+    corpus: Cow<'static, str>,
+    in_header: bool,
+    pos: usize,
+    /// Where the first pass over the corpus begins.
+    start: usize,
+    remaining: usize,
+    chunk_chars: usize,
+}
+
+/// Inserted between consecutive passes over the corpus.
+const PASS_SEPARATOR: &str = "\n\n";
+
+/// Byte offsets where a sentence or paragraph of `corpus` begins, always
+/// including 0.
+fn sentence_starts(corpus: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut boundary = false;
+    for (i, ch) in corpus.char_indices() {
+        if boundary && !ch.is_whitespace() {
+            starts.push(i);
+            boundary = false;
+        } else if matches!(ch, '.' | '!' | '?' | '\n') {
+            boundary = true;
+        } else if !ch.is_whitespace() {
+            boundary = false;
+        }
+    }
+    starts
+}
+
+/// A stable 64-bit FNV-1a hash of `parts`, used to derive response seeds.
+/// Unlike `DefaultHasher` its output is fixed across Rust releases, so
+/// snapshots taken against one build keep matching the next.
+pub fn stable_hash<'a>(parts: impl IntoIterator<Item = &'a str>) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = OFFSET;
+    for part in parts {
+        // The terminator keeps ["ab", "c"] and ["a", "bc"] apart.
+        for &b in part.as_bytes().iter().chain(&[0xff]) {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+/// Increment between successive SplitMix64 states.
+pub const SPLITMIX_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The SplitMix64 output function: maps a state to a well-mixed 64-bit value.
+pub fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Maps a 64-bit random value to a uniform `f64` in `[0, 1)`.
+pub fn unit(z: u64) -> f64 {
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Chunk size that splits `total_chars` into at most `chunks` non-empty chunks.
+pub fn chunk_size_for(total_chars: usize, chunks: usize) -> usize {
+    total_chars.div_ceil(chunks.max(1)).max(1)
+}
+
+impl CyclingText {
+    /// `header` is emitted once, then `corpus` repeats until `total_chars`
+    /// characters have been produced.
+    pub fn new(header: String, corpus: impl Into<Cow<'static, str>>, total_chars: usize, chunk_chars: usize) -> CyclingText {
+        CyclingText {
+            in_header: !header.is_empty(),
+            header,
+            corpus: corpus.into(),
+            pos: 0,
+            start: 0,
+            remaining: total_chars,
+            chunk_chars: chunk_chars.max(1),
+        }
+    }
+
+    /// Starts the first pass at the corpus sentence picked by `seed`, so
+    /// different seeds open with different text. Later passes start from the
+    /// top as usual. Seed 0 leaves the corpus order unchanged.
+    pub fn seeded(mut self, seed: u64) -> CyclingText {
+        let starts = sentence_starts(&self.corpus);
+        self.start = starts[(seed % starts.len() as u64) as usize];
+        if !self.in_header {
+            self.pos = self.start;
+        }
+        self
+    }
+
+    /// The unread part of the current segment (header, corpus pass or separator).
+    fn unread(&self) -> &str {
+        if self.in_header {
+            &self.header[self.pos..]
+        } else if self.pos < self.corpus.len() {
+            &self.corpus[self.pos..]
+        } else {
+            &PASS_SEPARATOR[self.pos - self.corpus.len()..]
+        }
+    }
+
+    /// Moves to the next segment once the current one is exhausted.
+    fn advance_segment(&mut self) {
+        if self.in_header {
+            self.in_header = false;
+            self.pos = self.start;
+        } else if self.pos >= self.corpus.len() + PASS_SEPARATOR.len() {
+            self.pos = 0;
+        }
+    }
+}
+
+impl Iterator for CyclingText {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.remaining == 0 || self.corpus.is_empty() {
+            return None;
+        }
+        let want = self.chunk_chars.min(self.remaining);
+        let mut chunk = String::with_capacity(want);
+        let mut taken = 0;
+        while taken < want {
+            let (consumed, exhausted) = {
+                let text = self.unread();
+                let mut consumed = 0;
+                for ch in text.chars().take(want - taken) {
+                    chunk.push(ch);
+                    consumed += ch.len_utf8();
+                    taken += 1;
+                }
+                (consumed, consumed == text.len())
+            };
+            self.pos += consumed;
+            if exhausted {
+                self.advance_segment();
+            }
+        }
+        self.remaining -= taken;
+        Some(chunk)
+    }
+}
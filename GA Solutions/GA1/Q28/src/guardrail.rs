@@ -0,0 +1,157 @@
+//! A simulated moderation pass over the end of a reply (`guardrail` in a
+//! response profile), for testing how clients cope with the latency and
+//! verdicts of a provider's output filter.
+//!
+//! The last `window_chars` of the reply, in whole chunks, are held back until
+//! generation ends. The verdict then takes `delay_ms`, after which the held
+//! chunks go out unchanged, or, when the guardrail trips, are dropped or
+//! replaced and the reply finishes with `content_filter`. Only the held text
+//! is screened, so a `trigger` match earlier in the reply goes unnoticed.
+
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::generator::{self, SPLITMIX_GAMMA};
+use crate::stubs::Pattern;
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Guardrail {
+    /// How long the verdict takes; the held chunks wait this much longer.
+    pub delay_ms: u64,
+    /// Characters at the end of the reply held back for screening. At
+    /// least the final chunk is always held.
+    pub window_chars: usize,
+    /// Trips the guardrail when the held text matches.
+    pub trigger: Option<Pattern>,
+    /// Chance of tripping regardless of the text, drawn from the request seed.
+    pub rate: f64,
+    pub action: GuardrailAction,
+    /// Sent in place of the held text by `rewrite`.
+    pub replacement: String,
+}
+
+impl Default for Guardrail {
+    fn default() -> Self {
+        Guardrail {
+            delay_ms: 0,
+            window_chars: 0,
+            trigger: None,
+            rate: 0.0,
+            action: GuardrailAction::Block,
+            replacement: "[This content was removed by a content filter.]".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailAction {
+    /// Drops the held text, so the reply stops short.
+    Block,
+    /// Sends `replacement` instead of the held text.
+    Rewrite,
+}
+
+/// The verdict of one reply's guardrail, shared between the chunks it
+/// screens and the stream that sends them.
+#[derive(Default)]
+pub struct Screen {
+    delay: Cell<Duration>,
+    tripped: Cell<bool>,
+}
+
+impl Screen {
+    /// Wraps `chunks` so their tail is screened by `guardrail`.
+    pub fn wrap(
+        guardrail: &Guardrail,
+        seed: u64,
+        chunks: Box<dyn Iterator<Item = String>>,
+    ) -> (Rc<Screen>, Box<dyn Iterator<Item = String>>) {
+        let screen = Rc::new(Screen::default());
+        let screened = Screened {
+            guardrail: guardrail.clone(),
+            seed,
+            chunks,
+            held: VecDeque::new(),
+            held_chars: 0,
+            released: None,
+            screen: screen.clone(),
+        };
+        (screen, Box::new(screened))
+    }
+
+    /// The verdict's delay, once it is due; zero before and after.
+    pub fn take_delay(&self) -> Duration {
+        self.delay.take()
+    }
+
+    /// Whether the guardrail tripped, once the held chunks were asked for.
+    /// Answers yes only once, so the reply finishes with `content_filter`
+    /// a single time.
+    pub fn take_tripped(&self) -> bool {
+        self.tripped.take()
+    }
+}
+
+struct Screened {
+    guardrail: Guardrail,
+    seed: u64,
+    chunks: Box<dyn Iterator<Item = String>>,
+    held: VecDeque<String>,
+    held_chars: usize,
+    /// The chunks left after the verdict, once it is in.
+    released: Option<VecDeque<String>>,
+    screen: Rc<Screen>,
+}
+
+impl Screened {
+    fn verdict(&mut self) -> VecDeque<String> {
+        let held = std::mem::take(&mut self.held);
+        self.screen.delay.set(Duration::from_millis(self.guardrail.delay_ms));
+        let text: String = held.iter().map(String::as_str).collect();
+        let matched = self.guardrail.trigger.as_ref().is_some_and(|p| p.regex().is_match(&text));
+        let drawn = generator::unit(generator::mix64(self.seed ^ SPLITMIX_GAMMA)) < self.guardrail.rate;
+        if !(matched || drawn) {
+            return held;
+        }
+        self.screen.tripped.set(true);
+        log::info!("guardrail tripped on the last {} characters of a reply", text.chars().count());
+        match self.guardrail.action {
+            GuardrailAction::Block => VecDeque::new(),
+            GuardrailAction::Rewrite => VecDeque::from([self.guardrail.replacement.clone()]),
+        }
+    }
+}
+
+impl Iterator for Screened {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if let Some(released) = self.released.as_mut() {
+            return released.pop_front();
+        }
+        loop {
+            // The oldest held chunk goes out once the newer ones cover the window.
+            let front = self.held.front().map_or(0, |c| c.chars().count());
+            if !self.held.is_empty() && self.held_chars - front >= self.guardrail.window_chars.max(1) {
+                self.held_chars -= front;
+                return self.held.pop_front();
+            }
+            match self.chunks.next() {
+                Some(chunk) => {
+                    self.held_chars += chunk.chars().count();
+                    self.held.push_back(chunk);
+                }
+                None => break,
+            }
+        }
+        let mut released = self.verdict();
+        let next = released.pop_front();
+        self.released = Some(released);
+        next
+    }
+}
@@ -0,0 +1,280 @@
+//! HTTP Archive (HAR 1.2) conversion of captured traffic, for interop with
+//! browser devtools and other HTTP tooling. Exports carry each SSE chunk's
+//! emission time in the custom `_sseChunkOffsetsMs` entry field; imports
+//! turn entries back into replay recordings (see `replay`), using that field
+//! when present and spreading the HAR `receive` time over the chunks otherwise.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::capture::Capture;
+use crate::chat::{IncomingRequest, NormalizedRequest, StreamChunk};
+use crate::replay::Recording;
+use crate::sse;
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct Har {
+    pub log: Log,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Entry {
+    pub started_date_time: String,
+    /// Total time of the exchange in milliseconds.
+    pub time: f64,
+    pub request: Request,
+    pub response: Response,
+    pub cache: serde_json::Map<String, serde_json::Value>,
+    pub timings: Timings,
+    /// When each SSE chunk was emitted, in milliseconds after the request started.
+    #[serde(rename = "_sseChunkOffsetsMs", skip_serializing_if = "Vec::is_empty")]
+    pub sse_chunk_offsets_ms: Vec<u64>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub headers: Vec<Header>,
+    pub query_string: Vec<Header>,
+    pub cookies: Vec<serde_json::Value>,
+    pub headers_size: i64,
+    pub body_size: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PostData {
+    pub mime_type: String,
+    pub text: String,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Response {
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    pub headers: Vec<Header>,
+    pub cookies: Vec<serde_json::Value>,
+    pub content: Content,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Content {
+    pub size: i64,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+/// Phase durations in milliseconds; `-1` means not applicable.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct Timings {
+    pub send: f64,
+    /// Until the first byte of the response.
+    pub wait: f64,
+    /// From the first byte to the last.
+    pub receive: f64,
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// Every capture that streamed, as a HAR log of requests to `url`. The
+/// response body is rebuilt as the OpenAI SSE stream the client received.
+pub fn from_captures(captures: &[Capture], url: &str) -> Har {
+    let entries = captures
+        .iter()
+        .filter_map(|capture| Some((capture, capture.reply.as_ref()?, capture.timing.as_ref()?)))
+        .map(|(capture, reply, timing)| {
+            let request_body = serde_json::json!({
+                "model": capture.model,
+                "messages": capture.messages,
+                "stream": true,
+            })
+            .to_string();
+            let mut body = String::new();
+            for (i, chunk) in reply.chunks().into_iter().enumerate() {
+                let mut event = StreamChunk::with_content(chunk.to_string());
+                if i == 0 {
+                    event.choices[0].delta.role = Some("assistant");
+                }
+                if let Ok(event) = sse::data_event(&event) {
+                    body.push_str(&String::from_utf8_lossy(&event));
+                }
+            }
+            if reply.finished {
+                body.push_str("data: [DONE]\n\n");
+            }
+            let offsets: Vec<u64> = timing
+                .chunks_at_ms
+                .iter()
+                .map(|at| at.saturating_sub(capture.received_at_ms))
+                .collect();
+            let total = timing.ended_at_ms.saturating_sub(capture.received_at_ms) as f64;
+            let wait = offsets.first().map_or(total, |&first| first as f64);
+            Entry {
+                started_date_time: iso8601(capture.received_at_ms),
+                time: total,
+                request: Request {
+                    method: "POST".to_string(),
+                    url: url.to_string(),
+                    http_version: "HTTP/1.1".to_string(),
+                    headers: vec![header("Content-Type", "application/json")],
+                    headers_size: -1,
+                    body_size: request_body.len() as i64,
+                    post_data: Some(PostData {
+                        mime_type: "application/json".to_string(),
+                        text: request_body,
+                    }),
+                    ..Request::default()
+                },
+                response: Response {
+                    status: 200,
+                    status_text: "OK".to_string(),
+                    http_version: "HTTP/1.1".to_string(),
+                    headers: vec![header("Content-Type", "text/event-stream")],
+                    content: Content {
+                        size: body.len() as i64,
+                        mime_type: "text/event-stream".to_string(),
+                        text: Some(body),
+                    },
+                    headers_size: -1,
+                    body_size: -1,
+                    ..Response::default()
+                },
+                timings: Timings {
+                    send: 0.0,
+                    wait,
+                    receive: total - wait,
+                },
+                sse_chunk_offsets_ms: offsets,
+                ..Entry::default()
+            }
+        })
+        .collect();
+    Har {
+        log: Log {
+            version: "1.2".to_string(),
+            creator: Creator {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            entries,
+        },
+    }
+}
+
+/// A recording for each entry that holds a chat request and a streamed
+/// reply with content, keyed on the prompt it answers.
+pub fn recordings(har: &Har) -> Vec<(String, Recording)> {
+    har.log
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let request: IncomingRequest = serde_json::from_str(&entry.request.post_data.as_ref()?.text).ok()?;
+            let prompt = NormalizedRequest::from(request).prompt;
+            let chunks = sse_contents(entry.response.content.text.as_deref()?);
+            if chunks.is_empty() {
+                return None;
+            }
+            let offsets = if entry.sse_chunk_offsets_ms.len() == chunks.len() {
+                entry.sse_chunk_offsets_ms.clone()
+            } else {
+                spread(entry.timings.wait.max(0.0), entry.timings.receive.max(0.0), chunks.len())
+            };
+            let mut previous = 0;
+            let delays = offsets
+                .iter()
+                .map(|&offset| {
+                    let delay = Duration::from_millis(offset.saturating_sub(previous));
+                    previous = previous.max(offset);
+                    delay
+                })
+                .collect();
+            Some((prompt, Recording { chunks, delays }))
+        })
+        .collect()
+}
+
+/// The `delta.content` of every chunk in an SSE body, skipping events without any.
+fn sse_contents(body: &str) -> Vec<String> {
+    sse::parse(body.as_bytes())
+        .into_iter()
+        .filter(|event| !event.is_done())
+        .filter_map(|event| serde_json::from_str::<serde_json::Value>(&event.data).ok())
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+        .filter(|content| !content.is_empty())
+        .collect()
+}
+
+/// `count` offsets: the first after `wait`, the rest evenly over `receive`.
+fn spread(wait: f64, receive: f64, count: usize) -> Vec<u64> {
+    let step = if count > 1 { receive / (count - 1) as f64 } else { 0.0 };
+    (0..count).map(|i| (wait + step * i as f64) as u64).collect()
+}
+
+/// `ms` since the Unix epoch as an RFC 3339 UTC timestamp with milliseconds.
+fn iso8601(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        ms % 1000
+    )
+}
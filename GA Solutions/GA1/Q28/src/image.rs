@@ -0,0 +1,117 @@
+//! What an `image_url` content part costs in prompt tokens, by OpenAI's
+//! published rule: a `low` detail image is a flat 85 tokens; otherwise the
+//! image is scaled to fit 2048×2048, then down until its shorter side is at
+//! most 768, and each 512×512 tile of the result costs 170 on top of the 85.
+//!
+//! The pixel size is read from the header of a `data:` URL's PNG, JPEG, GIF
+//! or WebP bytes. Remote images are never fetched; they, and data the
+//! header cannot be read from, are billed as `UNKNOWN_SIZE`.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Assumed for images whose size cannot be known without fetching them.
+pub const UNKNOWN_SIZE: (u32, u32) = (1024, 1024);
+
+const BASE_TOKENS: usize = 85;
+const TILE_TOKENS: usize = 170;
+const TILE: u32 = 512;
+const MAX_SIDE: u32 = 2048;
+const MAX_SHORT_SIDE: u32 = 768;
+/// Enough of the decoded data to find the size in any of the formats read,
+/// short of JPEGs with very large metadata segments ahead of their frame.
+const HEADER_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    Low,
+    High,
+    /// OpenAI's default, billed as `High` here.
+    #[default]
+    Auto,
+}
+
+/// The `image_url` object of a content part.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ImageUrl {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Detail>,
+}
+
+impl ImageUrl {
+    pub fn tokens(&self) -> usize {
+        tokens(data_url_size(&self.url).unwrap_or(UNKNOWN_SIZE), self.detail.unwrap_or_default())
+    }
+}
+
+/// Prompt tokens for an image of `width` × `height` pixels.
+pub fn tokens((width, height): (u32, u32), detail: Detail) -> usize {
+    if detail == Detail::Low || width == 0 || height == 0 {
+        return BASE_TOKENS;
+    }
+    let (mut w, mut h) = (f64::from(width), f64::from(height));
+    let fit = f64::from(MAX_SIDE) / w.max(h);
+    if fit < 1.0 {
+        (w, h) = (w * fit, h * fit);
+    }
+    let shrink = f64::from(MAX_SHORT_SIDE) / w.min(h);
+    if shrink < 1.0 {
+        (w, h) = (w * shrink, h * shrink);
+    }
+    let tiles = |side: f64| (side / f64::from(TILE)).ceil() as usize;
+    BASE_TOKENS + TILE_TOKENS * tiles(w) * tiles(h)
+}
+
+/// The pixel size of the image in a base64 `data:` URL.
+pub fn data_url_size(url: &str) -> Option<(u32, u32)> {
+    let (meta, data) = url.strip_prefix("data:")?.split_once(',')?;
+    if !meta.ends_with(";base64") {
+        return None;
+    }
+    // Decode whole 4-character groups only, so a prefix decodes cleanly.
+    let prefix = &data.as_bytes()[..data.len().min(HEADER_BYTES / 3 * 4)];
+    let prefix = &prefix[..prefix.len() / 4 * 4];
+    let bytes = base64::engine::general_purpose::STANDARD.decode(prefix).ok()?;
+    dimensions(&bytes)
+}
+
+/// The pixel size in a PNG, JPEG, GIF or WebP header.
+pub fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| bytes.get(at..at + 2).map(|b| u32::from(u16::from_be_bytes([b[0], b[1]])));
+    let le16 = |at: usize| bytes.get(at..at + 2).map(|b| u32::from(u16::from_le_bytes([b[0], b[1]])));
+    let be32 = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let le24 = |at: usize| bytes.get(at..at + 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]));
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be32(16)?, be32(20)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        return Some((le16(6)?, le16(8)?));
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return match bytes.get(12..16)? {
+            b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = le24(21)? | (u32::from(*bytes.get(24)?) << 24);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        };
+    }
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        // Walk the segments to the first start-of-frame marker.
+        let mut at = 2;
+        while *bytes.get(at)? == 0xff {
+            let marker = *bytes.get(at + 1)?;
+            let is_frame = matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+            if is_frame {
+                return Some((be16(at + 7)?, be16(at + 5)?));
+            }
+            at += 2 + be16(at + 2)? as usize;
+        }
+    }
+    None
+}
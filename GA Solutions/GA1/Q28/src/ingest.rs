@@ -0,0 +1,251 @@
+//! Reads chat request bodies chunk by chunk instead of through `web::Json`,
+//! so a prompt that cannot fit the model's context window is turned away
+//! while it is still arriving rather than after megabytes are buffered and
+//! parsed. `PromptScanner` follows the JSON as it streams in, adding up the
+//! message text; once even the fewest tokens that text could encode to
+//! exceed the window, the rest of the body is never read.
+//!
+//! A body that fits is still buffered whole before it is parsed: serde_json
+//! only deserializes complete documents, and the parsed request holds every
+//! message's text anyway, so parsing as it arrives would save at most one
+//! copy. The buffer is capped at `request.max_body_bytes`, counted after
+//! decoding, and a body is refused as soon as it is known to exceed it.
+
+use actix_web::dev::{self, Decompress};
+use actix_web::error::{JsonPayloadError, PayloadError};
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use bytes::BytesMut;
+use futures::future::LocalBoxFuture;
+use futures::StreamExt;
+
+use crate::chat::IncomingRequest;
+use crate::config::{Config, RequestConfig};
+use crate::error::{self, MockError};
+use crate::metrics;
+use crate::state::AppState;
+use StringRole::{Key, Model, Other, Text};
+
+/// Keys longer than this cannot be `messages`, `content` or `text`, so only
+/// their first bytes are kept.
+const KEY_CAP: usize = 16;
+/// Longer `model` values are not worth looking up.
+const MODEL_CAP: usize = 256;
+
+/// A chat completions body, read incrementally; see the module docs.
+pub struct ChatBody(pub IncomingRequest);
+
+impl FromRequest for ChatBody {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let req = req.clone();
+        let payload = Decompress::from_headers(payload.take(), req.headers());
+        Box::pin(async move { read(&req, payload).await.map(ChatBody) })
+    }
+}
+
+async fn read(req: &HttpRequest, mut payload: Decompress<dev::Payload>) -> Result<IncomingRequest, Error> {
+    error::check_media_type(req)?;
+    let limit = req
+        .app_data::<web::Data<Config>>()
+        .map_or_else(|| RequestConfig::default().max_body_bytes, |c| c.request.max_body_bytes);
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    let encoded = req.headers().contains_key(header::CONTENT_ENCODING);
+    if let Some(length) = length.filter(|&length| length > limit && !encoded) {
+        return Err(error::body_error(JsonPayloadError::OverflowKnownLength { length, limit }, req));
+    }
+    let state = req.app_data::<web::Data<AppState>>().cloned();
+
+    let mut scanner = PromptScanner::default();
+    let mut body = BytesMut::with_capacity(length.filter(|_| !encoded).unwrap_or(0).min(limit));
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| error::body_error(JsonPayloadError::Payload(e), req))?;
+        if body.len() + chunk.len() > limit {
+            return Err(error::body_error(JsonPayloadError::Payload(PayloadError::Overflow), req));
+        }
+        scanner.feed(&chunk);
+        body.extend_from_slice(&chunk);
+        let window = state.as_ref().zip(scanner.model()).and_then(|(state, model)| state.models.get(model));
+        if let Some(model) = window {
+            let at_least = scanner.min_tokens(model.encoding.max_token_bytes());
+            if at_least > model.context_window {
+                metrics::EARLY_CONTEXT_REJECTIONS.inc();
+                let message = format!(
+                    "This model's maximum context length is {} tokens. However, your messages resulted in at least {} tokens. Please reduce the length of the messages.",
+                    model.context_window, at_least
+                );
+                return Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some("messages")).with_code("context_length_exceeded").into());
+            }
+        }
+    }
+    serde_json::from_slice(&body).map_err(|e| error::body_error(JsonPayloadError::Deserialize(e), req))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Object,
+    Array,
+}
+
+/// Where the byte being scanned sits, for a string that has started.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StringRole {
+    Key,
+    /// Message text: `messages[].content`, or a part's `text`.
+    Text,
+    Model,
+    Other,
+}
+
+/// Follows a chat request's JSON a chunk at a time, keeping only what the
+/// context check needs: the top-level `model`, the number of messages and
+/// a lower bound on the bytes of their text. Malformed JSON is not
+/// diagnosed here; the full parse that follows reports it.
+#[derive(Default)]
+pub struct PromptScanner {
+    /// Open containers, each with the last key seen if it is an object.
+    stack: Vec<(Container, Vec<u8>)>,
+    /// Whether the next string in the innermost object is a key.
+    expecting_key: bool,
+    string: Option<StringRole>,
+    /// Inside a string, just after a backslash.
+    escaped: bool,
+    /// Hex digits of a `\u` escape still to skip.
+    hex_left: u8,
+    key: Vec<u8>,
+    model: Vec<u8>,
+    model_done: bool,
+    messages: usize,
+    text_bytes: usize,
+}
+
+impl PromptScanner {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            match self.string {
+                Some(role) => self.string_byte(role, b),
+                None => self.structural_byte(b),
+            }
+        }
+    }
+
+    /// The top-level `model`, once its value has been read whole.
+    pub fn model(&self) -> Option<&str> {
+        self.model_done.then(|| std::str::from_utf8(&self.model).ok()).flatten()
+    }
+
+    /// The fewest prompt tokens the messages so far can count as, with
+    /// `count_messages`' framing and every token as long as it can be.
+    pub fn min_tokens(&self, max_token_bytes: usize) -> usize {
+        3 * self.messages + 3 + self.text_bytes / max_token_bytes.max(1)
+    }
+
+    fn string_byte(&mut self, role: StringRole, b: u8) {
+        if self.hex_left > 0 {
+            self.hex_left -= 1;
+            return;
+        }
+        if self.escaped {
+            self.escaped = false;
+            if b == b'u' {
+                self.hex_left = 4;
+            }
+            // Every escape decodes to at least one byte.
+            self.push_string_byte(role, b);
+            return;
+        }
+        match b {
+            b'\\' => self.escaped = true,
+            b'"' => {
+                self.string = None;
+                match role {
+                    Key => {
+                        if let Some((Container::Object, last)) = self.stack.last_mut() {
+                            std::mem::swap(last, &mut self.key);
+                        }
+                    }
+                    Model => self.model_done = true,
+                    Text | Other => {}
+                }
+            }
+            _ => self.push_string_byte(role, b),
+        }
+    }
+
+    fn push_string_byte(&mut self, role: StringRole, b: u8) {
+        match role {
+            Key if self.key.len() < KEY_CAP => self.key.push(b),
+            Model if self.model.len() < MODEL_CAP => self.model.push(b),
+            Text => self.text_bytes += 1,
+            _ => {}
+        }
+    }
+
+    fn structural_byte(&mut self, b: u8) {
+        match b {
+            b'"' => {
+                let role = if self.expecting_key && self.in_object() {
+                    self.key.clear();
+                    Key
+                } else {
+                    self.value_role()
+                };
+                if role == Model {
+                    self.model.clear();
+                    self.model_done = false;
+                }
+                self.string = Some(role);
+            }
+            b'{' => {
+                if self.path_is(&[Some("messages"), None]) {
+                    self.messages += 1;
+                }
+                self.stack.push((Container::Object, Vec::new()));
+                self.expecting_key = true;
+            }
+            b'[' => {
+                self.stack.push((Container::Array, Vec::new()));
+                self.expecting_key = false;
+            }
+            b'}' | b']' => {
+                self.stack.pop();
+                self.expecting_key = false;
+            }
+            b':' => self.expecting_key = false,
+            b',' => self.expecting_key = self.in_object(),
+            _ => {}
+        }
+    }
+
+    fn in_object(&self) -> bool {
+        matches!(self.stack.last(), Some((Container::Object, _)))
+    }
+
+    /// Whether the open containers are exactly `path`: `Some(key)` for an
+    /// object whose current key is `key`, `None` for an array.
+    fn path_is(&self, path: &[Option<&str>]) -> bool {
+        self.stack.len() == path.len()
+            && self.stack.iter().zip(path).all(|((container, key), step)| match step {
+                Some(name) => *container == Container::Object && key == name.as_bytes(),
+                None => *container == Container::Array,
+            })
+    }
+
+    fn value_role(&self) -> StringRole {
+        if self.path_is(&[Some("model")]) {
+            Model
+        } else if self.path_is(&[Some("messages"), None, Some("content")])
+            || self.path_is(&[Some("messages"), None, Some("content"), None, Some("text")])
+        {
+            Text
+        } else {
+            Other
+        }
+    }
+}
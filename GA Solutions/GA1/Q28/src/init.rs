@@ -0,0 +1,161 @@
+//! `stream-api init`: writes a commented starter config with example stub
+//! rules and a sample reply corpus, so a new team can start from something
+//! that runs and edit it.
+
+#[cfg(feature = "generators")]
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "generators")]
+use crate::corpus;
+
+/// Options for `stream-api init`.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct InitArgs {
+    /// Directory to write `config.toml` into.
+    #[arg(long, default_value = ".")]
+    pub dir: PathBuf,
+    /// Enable the admin API and add example API keys with tiers.
+    #[arg(long)]
+    pub with_auth: bool,
+    /// Map Claude model names to the Anthropic preset and add an
+    /// Anthropic-style error rule.
+    #[arg(long)]
+    pub with_anthropic: bool,
+    /// Replace an existing `config.toml`.
+    #[arg(long)]
+    pub force: bool,
+}
+
+const BASE: &str = r#"# Starter config for stream-api. Run `stream-api check config.toml` after
+# editing, then `stream-api --config config.toml`.
+
+[server]
+bind = "127.0.0.1:8080"
+# Long time-to-first-token scenarios need these raised.
+keep_alive_secs = 5
+client_request_timeout_ms = 5000
+
+[stream]
+# Number events and honor Last-Event-ID on reconnect.
+event_ids = true
+# "flush" writes every event on its own; "coalesce" batches them.
+write_mode = "flush"
+
+# Stub rules are checked in order and the first match wins, so list specific
+# rules before broad ones. Prompts no rule matches get the default profile.
+
+[[stubs]]
+name = "slow-start"
+match = { contains = "slow" }
+profile.first_chunk_delay_ms = 3000
+profile.chunk_delay_ms = 100
+
+[[stubs]]
+name = "mid-stream-failure"
+match = { starts_with = "fail" }
+profile.error_after = 3
+profile.error_code = 503
+profile.error_message = "The model is overloaded"
+
+[[stubs]]
+name = "truncated"
+match = { regex = "(?i)\\btoo long\\b" }
+profile.tokens = 50
+profile.finish_reason = "length"
+"#;
+
+/// Written only by builds with the `generators` feature, which serves it.
+const CORPUS: &str = r#"
+[[stubs]]
+name = "long-form"
+match = { contains = "essay" }
+profile.generator = "corpus"
+
+# Replies for `generator = "corpus"`, relative to this file. After editing
+# corpus/replies.jsonl, repack it with
+# `stream-api corpus pack corpus/replies.jsonl corpus/replies.corpus`.
+[corpus]
+path = "corpus/replies.corpus"
+"#;
+
+/// Where the sample corpus goes, relative to the config.
+#[cfg(feature = "generators")]
+const CORPUS_DIR: &str = "corpus";
+
+/// The sample corpus source, one reply per line.
+#[cfg(feature = "generators")]
+const SAMPLE_REPLIES: &str = r#"{"text": "A mock server earns its keep in the failure cases. Real providers stall before the first token, drop connections halfway through a stream, and return errors in formats that differ from one vendor to the next. Reproducing those on demand, with the same pacing every time, is what lets a client's retry and timeout logic be tested before it meets production traffic."}
+{"text": "Streaming changes how an application feels more than how fast it is. The total time to a finished reply barely moves, but the first words arrive in a fraction of a second, and readers start reading while the rest is still being generated. Clients have to handle partial words, events split across reads, and streams that end without a final message."}
+{"text": "Replace these replies with text that looks like your own traffic: long answers, code-heavy answers, answers in the languages your users write in. Each line is a JSON string or an object with a text field, and the whole file is repacked with a single command whenever it changes."}
+"#;
+
+const AUTH: &str = r#"
+# Bearer token for /v1/admin/* (storms, exports, replay). Change it.
+[admin]
+token = "change-me"
+
+# Keys presented as `Authorization: Bearer <key>`; higher tiers queue less
+# and stream faster. Unlisted keys are served untiered.
+[[keys]]
+key = "sk-free-demo"
+tier = "free"
+
+[[keys]]
+key = "sk-enterprise-demo"
+tier = "enterprise"
+"#;
+
+const ANTHROPIC: &str = r#"
+# Requests naming these models get the preset's pacing and error format
+# unless a stub rule matches first.
+[model_presets]
+"claude-3-5-sonnet" = "anthropic-sonnet"
+
+[[stubs]]
+name = "anthropic-overloaded"
+match = { contains = "overloaded" }
+profile.error_after = 0
+profile.error_code = 529
+profile.error_style = "anthropic"
+profile.error_message = "Overloaded"
+"#;
+
+/// The starter config for `args`.
+pub fn starter_config(args: &InitArgs) -> String {
+    let mut config = BASE.to_string();
+    if cfg!(feature = "generators") {
+        config.push_str(CORPUS);
+    }
+    if args.with_auth {
+        config.push_str(AUTH);
+    }
+    if args.with_anthropic {
+        config.push_str(ANTHROPIC);
+    }
+    config
+}
+
+/// Writes the starter config and the sample corpus it points at into
+/// `args.dir`, and returns the config's path. An existing config is only
+/// replaced, along with the corpus, with `force`.
+pub fn run(args: &InitArgs) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(&args.dir)?;
+    let path = Path::new(&args.dir).join("config.toml");
+    if path.exists() && !args.force {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists; pass --force to replace it", path.display()),
+        ));
+    }
+    #[cfg(feature = "generators")]
+    {
+        let corpus_dir = Path::new(&args.dir).join(CORPUS_DIR);
+        std::fs::create_dir_all(&corpus_dir)?;
+        std::fs::write(corpus_dir.join("replies.jsonl"), SAMPLE_REPLIES)?;
+        corpus::pack(SAMPLE_REPLIES.as_bytes(), io::BufWriter::new(File::create(corpus_dir.join("replies.corpus"))?))?;
+    }
+    std::fs::write(&path, starter_config(args))?;
+    Ok(path)
+}
@@ -0,0 +1,76 @@
+use actix_web::{get, HttpResponse};
+use serde::Serialize;
+
+use crate::{metrics, pool};
+
+#[derive(Serialize)]
+struct InternalStats {
+    requests: metrics::RequestStats,
+    buffer_pool: pool::PoolStats,
+    process: ProcessStats,
+}
+
+#[derive(Serialize)]
+struct ProcessStats {
+    /// Resident set size; only available on Linux.
+    rss_bytes: Option<u64>,
+    /// Open file descriptors, sockets included; only available on Linux.
+    open_fds: Option<u64>,
+    /// Chat completion streams currently open.
+    active_streams: u64,
+    /// Background tasks the server spawned, such as webhook deliveries and
+    /// shadow requests, still running. Connections are not counted.
+    background_tasks: u64,
+    /// Chunk delays parked in the pacing wheels; see `pacer`.
+    parked_timers: u64,
+}
+
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn open_fds() -> Option<u64> {
+    // Listing the directory opens one descriptor of its own.
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?.count() as u64;
+    Some(entries.saturating_sub(1))
+}
+
+fn process_stats() -> ProcessStats {
+    ProcessStats {
+        rss_bytes: resident_memory(),
+        open_fds: open_fds(),
+        active_streams: metrics::ACTIVE_STREAMS.get(),
+        background_tasks: metrics::BACKGROUND_TASKS.get(),
+        parked_timers: metrics::PARKED_TIMERS.get(),
+    }
+}
+
+#[get("/v1/internal/stats")]
+pub async fn stats_endpoint() -> HttpResponse {
+    HttpResponse::Ok().json(InternalStats {
+        requests: metrics::request_stats(),
+        buffer_pool: pool::stats(),
+        process: process_stats(),
+    })
+}
+
+/// The same counters and process gauges, plus per-model chunk pacing
+/// histograms, for Prometheus.
+#[get("/v1/internal/metrics")]
+pub async fn metrics_endpoint() -> HttpResponse {
+    let mut out = metrics::prometheus();
+    let process = process_stats();
+    // Absent rather than zero where the platform cannot say.
+    if let Some(rss) = process.rss_bytes {
+        metrics::render_gauge(&mut out, "process_resident_memory_bytes", "Resident memory size in bytes.", rss);
+    }
+    if let Some(fds) = process.open_fds {
+        metrics::render_gauge(&mut out, "process_open_fds", "Open file descriptors, sockets included.", fds);
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(out)
+}
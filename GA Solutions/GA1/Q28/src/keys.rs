@@ -0,0 +1,188 @@
+//! API keys and the service tiers they belong to, for demonstrating tiered
+//! SLAs: higher tiers jump the admission queue, get a larger share of the
+//! throughput budget and a higher per-stream token rate. Requests with no
+//! key, or one that is not listed, are served untiered as before. Keys can
+//! also be restricted to some models and capabilities, to exercise a client's
+//! handling of the provider's access errors.
+
+use actix_web::http::StatusCode;
+use actix_web::HttpRequest;
+use serde::Deserialize;
+
+use crate::chat::NormalizedRequest;
+use crate::config::{TierSettings, TiersConfig};
+use crate::error::MockError;
+
+/// A key as listed in `[[keys]]`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    pub key: String,
+    pub tier: Tier,
+    /// The organization the key belongs to, the default for
+    /// `OpenAI-Organization`; see `orgs`.
+    #[serde(default)]
+    pub organization: Option<String>,
+    /// The project a project-scoped key belongs to, the default for
+    /// `OpenAI-Project`.
+    #[serde(default)]
+    pub project: Option<String>,
+    /// The models the key may use; all when absent.
+    #[serde(default)]
+    pub models: Option<Vec<String>>,
+    /// What the key may do beyond plain text chat; everything when absent.
+    #[serde(default)]
+    pub capabilities: Option<Vec<Capability>>,
+    /// Prompt plus completion tokens the key is budgeted for the life of the
+    /// server; see `quota`.
+    #[serde(default)]
+    pub token_quota: Option<u64>,
+}
+
+impl ApiKey {
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.models.as_ref().is_none_or(|models| models.iter().any(|m| m == model))
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.capabilities.as_ref().is_none_or(|granted| granted.contains(&capability))
+    }
+}
+
+/// A request feature a key can be granted.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Image parts in message content.
+    Vision,
+    /// `tools` or `functions` in the request.
+    Tools,
+    /// `/v1/embeddings`.
+    Embeddings,
+}
+
+impl Capability {
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Vision => "vision",
+            Capability::Tools => "tools",
+            Capability::Embeddings => "embeddings",
+        }
+    }
+
+    /// The request field that uses the capability, reported as the error's `param`.
+    fn param(self) -> &'static str {
+        match self {
+            Capability::Vision => "messages",
+            Capability::Tools => "tools",
+            Capability::Embeddings => "model",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Tier {
+    Free,
+    Pro,
+    Enterprise,
+}
+
+impl Tier {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tier::Free => "free",
+            Tier::Pro => "pro",
+            Tier::Enterprise => "enterprise",
+        }
+    }
+}
+
+impl TiersConfig {
+    pub fn get(&self, tier: Tier) -> &TierSettings {
+        match tier {
+            Tier::Free => &self.free,
+            Tier::Pro => &self.pro,
+            Tier::Enterprise => &self.enterprise,
+        }
+    }
+}
+
+/// The token of an `Authorization: Bearer` header.
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// The tier of the key `req` presents, if it is a listed one.
+pub fn tier_for(keys: &[ApiKey], req: &HttpRequest) -> Option<Tier> {
+    key_for(keys, req).map(|k| k.tier)
+}
+
+/// The listed key `req` presents, if any.
+pub fn key_for<'a>(keys: &'a [ApiKey], req: &HttpRequest) -> Option<&'a ApiKey> {
+    let presented = bearer_token(req)?;
+    keys.iter().find(|k| k.key == presented)
+}
+
+/// With keys listed, turns away a request that presents none of them, as
+/// the provider does a missing or revoked key. Without, anyone may connect.
+pub fn require(keys: &[ApiKey], req: &HttpRequest) -> Result<(), MockError> {
+    if keys.is_empty() || key_for(keys, req).is_some() {
+        return Ok(());
+    }
+    let error = match bearer_token(req) {
+        None => MockError::rejected(
+            StatusCode::UNAUTHORIZED,
+            "You didn't provide an API key. You need to provide your API key in an Authorization header using Bearer auth (i.e. Authorization: Bearer YOUR_KEY).",
+            None,
+        ),
+        Some(_) => MockError::rejected(StatusCode::UNAUTHORIZED, "Incorrect API key provided.", None).with_code("invalid_api_key"),
+    };
+    Err(error)
+}
+
+/// The 404 OpenAI sends for a model that does not exist or that the key
+/// cannot see.
+pub fn model_not_found(model: &str) -> MockError {
+    MockError::rejected(
+        StatusCode::NOT_FOUND,
+        format!("The model `{}` does not exist or you do not have access to it.", model),
+        None,
+    )
+    .with_code("model_not_found")
+}
+
+/// Checks the request's model and the capabilities it uses against the
+/// key's allowlists. Unlisted keys may do anything.
+pub fn authorize(keys: &[ApiKey], http_req: &HttpRequest, req: &NormalizedRequest) -> Result<(), MockError> {
+    let used = [(Capability::Vision, req.has_images()), (Capability::Tools, req.uses_tools)];
+    authorize_use(keys, http_req, req.model.as_deref(), used)
+}
+
+/// `authorize` for a request that names `model` and uses each capability
+/// flagged in `used`.
+pub fn authorize_use(
+    keys: &[ApiKey],
+    http_req: &HttpRequest,
+    model: Option<&str>,
+    used: impl IntoIterator<Item = (Capability, bool)>,
+) -> Result<(), MockError> {
+    let Some(key) = key_for(keys, http_req) else {
+        return Ok(());
+    };
+    if let Some(model) = model.filter(|m| !key.allows_model(m)) {
+        return Err(model_not_found(model));
+    }
+    match used.into_iter().find(|&(capability, used)| used && !key.allows(capability)) {
+        Some((capability, _)) => Err(MockError::rejected(
+            StatusCode::FORBIDDEN,
+            format!("This API key does not have permission to use {}.", capability.name()),
+            Some(capability.param()),
+        )
+        .with_code("permission_denied")),
+        None => Ok(()),
+    }
+}
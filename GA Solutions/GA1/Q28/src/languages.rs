@@ -0,0 +1,287 @@
+//! Canned replies in several languages (`language` and `detect_language` in
+//! a response profile), for exercising how clients render text that is not
+//! English: other scripts, right-to-left text, wide characters and
+//! language-specific punctuation.
+//!
+//! Detection is built in, standing in for the `whatlang` crate, and is
+//! deliberately simple. The script of most of a prompt's letters settles it
+//! for every language here but the Latin-script ones, which are told apart
+//! by counting their most common short words. A prompt with too few
+//! letters, or no such words, is not detected. It knows only these eleven
+//! languages, so any other is taken for the one sharing its script or a
+//! common word: Ukrainian reads as Russian, Persian as Arabic, Italian as
+//! Spanish. Kanji with no kana reads as Chinese, and a prompt mixing
+//! languages goes to the one with the most common words.
+
+use serde::{Deserialize, Serialize};
+
+use crate::chat::EXTENDED_CONTENT;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "es")]
+    Spanish,
+    #[serde(rename = "fr")]
+    French,
+    #[serde(rename = "de")]
+    German,
+    #[serde(rename = "pt")]
+    Portuguese,
+    #[serde(rename = "ru")]
+    Russian,
+    #[serde(rename = "zh")]
+    Chinese,
+    #[serde(rename = "ja")]
+    Japanese,
+    #[serde(rename = "ko")]
+    Korean,
+    #[serde(rename = "ar")]
+    Arabic,
+    #[serde(rename = "hi")]
+    Hindi,
+}
+
+pub const LANGUAGES: &[Language] = &[
+    Language::English,
+    Language::Spanish,
+    Language::French,
+    Language::German,
+    Language::Portuguese,
+    Language::Russian,
+    Language::Chinese,
+    Language::Japanese,
+    Language::Korean,
+    Language::Arabic,
+    Language::Hindi,
+];
+
+impl Language {
+    /// The ISO 639-1 code, as in config and the `X-Mock-Language` header.
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Portuguese => "pt",
+            Language::Russian => "ru",
+            Language::Chinese => "zh",
+            Language::Japanese => "ja",
+            Language::Korean => "ko",
+            Language::Arabic => "ar",
+            Language::Hindi => "hi",
+        }
+    }
+
+    /// Opens the reply's first line, before the quoted prompt.
+    pub fn opening(self) -> &'static str {
+        match self {
+            Language::English => "Regarding your prompt",
+            Language::Spanish => "Sobre tu consulta",
+            Language::French => "Au sujet de votre demande",
+            Language::German => "Zu Ihrer Anfrage",
+            Language::Portuguese => "Sobre a sua pergunta",
+            Language::Russian => "По вашему запросу",
+            Language::Chinese => "关于您的问题",
+            Language::Japanese => "ご質問について",
+            Language::Korean => "질문에 대하여",
+            Language::Arabic => "بخصوص سؤالك",
+            Language::Hindi => "आपके प्रश्न के बारे में",
+        }
+    }
+
+    /// The canned reply.
+    pub fn reply(self) -> &'static str {
+        match self {
+            Language::English => EXTENDED_CONTENT,
+            Language::Spanish => SPANISH,
+            Language::French => FRENCH,
+            Language::German => GERMAN,
+            Language::Portuguese => PORTUGUESE,
+            Language::Russian => RUSSIAN,
+            Language::Chinese => CHINESE,
+            Language::Japanese => JAPANESE,
+            Language::Korean => KOREAN,
+            Language::Arabic => ARABIC,
+            Language::Hindi => HINDI,
+        }
+    }
+}
+
+/// Letters a prompt needs before its language is guessed at.
+const MIN_LETTERS: usize = 3;
+
+/// Common short words of each Latin-script language. A word several share,
+/// such as `de`, counts for each.
+const STOPWORDS: &[(Language, &[&str])] = &[
+    (
+        Language::English,
+        &["the", "and", "is", "are", "of", "to", "in", "what", "how", "why", "you", "it", "this", "that", "with", "for", "can", "do", "my"],
+    ),
+    (
+        Language::Spanish,
+        &["el", "la", "los", "las", "es", "y", "qué", "que", "cómo", "por", "para", "una", "un", "con", "del", "se", "mi", "está", "puedes"],
+    ),
+    (
+        Language::French,
+        &["le", "la", "les", "est", "et", "des", "une", "un", "que", "quoi", "comment", "pour", "avec", "du", "vous", "je", "ce", "pas", "sont"],
+    ),
+    (
+        Language::German,
+        &["der", "die", "das", "ist", "und", "wie", "was", "nicht", "ich", "sie", "ein", "eine", "mit", "für", "zu", "auf", "den", "warum", "kannst"],
+    ),
+    (
+        Language::Portuguese,
+        &["o", "os", "as", "é", "e", "que", "como", "não", "um", "uma", "com", "para", "do", "da", "em", "você", "por", "meu", "são"],
+    ),
+];
+
+/// The language of `text`, if it can be told.
+pub fn detect(text: &str) -> Option<Language> {
+    let mut scripts = [0usize; 8];
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        let script = match c as u32 {
+            0x3040..=0x30ff => 0,
+            0x4e00..=0x9fff | 0x3400..=0x4dbf | 0xf900..=0xfaff => 1,
+            0xac00..=0xd7af | 0x1100..=0x11ff | 0x3130..=0x318f => 2,
+            0x0400..=0x04ff => 3,
+            0x0600..=0x06ff | 0x0750..=0x077f => 4,
+            0x0900..=0x097f => 5,
+            _ if c.is_ascii_alphabetic() || ('\u{c0}'..='\u{24f}').contains(&c) => 6,
+            _ => 7,
+        };
+        scripts[script] += 1;
+    }
+    let (kana, han, hangul, cyrillic, arabic, devanagari, latin) =
+        (scripts[0], scripts[1], scripts[2], scripts[3], scripts[4], scripts[5], scripts[6]);
+    let letters: usize = scripts.iter().sum();
+    if letters < MIN_LETTERS {
+        return None;
+    }
+    // Japanese mixes kana into its kanji; Chinese has none.
+    if kana > 0 && kana + han >= letters / 2 {
+        return Some(Language::Japanese);
+    }
+    let (most, count) = [
+        (Language::Chinese, han),
+        (Language::Korean, hangul),
+        (Language::Russian, cyrillic),
+        (Language::Arabic, arabic),
+        (Language::Hindi, devanagari),
+    ]
+    .into_iter()
+    .max_by_key(|&(_, count)| count)
+    .expect("scripts to compare");
+    if count > latin {
+        return Some(most);
+    }
+    latin_language(text)
+}
+
+/// The Latin-script language whose common words `text` uses most. A tie
+/// goes to the one listed first.
+fn latin_language(text: &str) -> Option<Language> {
+    let lowered = text.to_lowercase();
+    let words: Vec<&str> = lowered.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).collect();
+    let mut best = None;
+    for &(language, stopwords) in STOPWORDS {
+        let hits = words.iter().filter(|w| stopwords.contains(w)).count();
+        if hits > 0 && best.is_none_or(|(_, most)| hits > most) {
+            best = Some((language, hits));
+        }
+    }
+    best.map(|(language, _)| language)
+}
+
+const SPANISH: &str = "Las API de LLM con streaming cambian la forma en que construimos aplicaciones de inteligencia artificial. Al entregar el contenido poco a poco, reducimos de manera notable el tiempo hasta el primer token, una métrica clave para que el usuario siga atento. ¿Por qué importa tanto? Porque una respuesta inmediata marca la diferencia entre un producto que se usa y uno que se abandona.
+
+Aspectos clave de esta implementación:
+1. **Eventos enviados por el servidor (SSE):** la conexión permanece abierta y cada fragmento llega en cuanto está listo.
+2. **Flujos asíncronos:** el servidor atiende otras peticiones mientras espera el siguiente fragmento.
+3. **Errores coherentes:** los fallos llegan en el mismo formato que el contenido, así el cliente los trata igual.
+
+En resumen, el streaming mejora la percepción de velocidad y hace posibles interfaces de chat interactivas, asistentes de programación en vivo y generadores de contenido dinámico. ¡Pruébalo con tu propio cliente!";
+
+const FRENCH: &str = "Les API de LLM en streaming changent la façon dont nous concevons les applications d'intelligence artificielle. En livrant le contenu au fur et à mesure, on réduit nettement le délai avant le premier jeton, un indicateur essentiel de l'engagement des utilisateurs. Pourquoi est-ce si important ? Parce qu'une réponse immédiate fait toute la différence entre un produit qu'on adopte et un produit qu'on abandonne.
+
+Points clés de cette implémentation :
+1. **Événements envoyés par le serveur (SSE) :** la connexion reste ouverte et chaque fragment part dès qu'il est prêt.
+2. **Flux asynchrones :** le serveur traite d'autres requêtes en attendant le fragment suivant.
+3. **Erreurs cohérentes :** les échecs arrivent dans le même format que le contenu, et le client les traite de la même manière.
+
+En résumé, le streaming améliore la rapidité perçue et rend possibles les interfaces de discussion interactives, les assistants de programmation en direct et les générateurs de contenu dynamiques. « Essayez-le » avec votre propre client !";
+
+const GERMAN: &str = "Streaming-APIs für große Sprachmodelle verändern, wie wir Anwendungen mit künstlicher Intelligenz bauen. Weil die Inhalte Stück für Stück ankommen, sinkt die Zeit bis zum ersten Token deutlich – eine entscheidende Kennzahl dafür, ob Nutzer dranbleiben. Warum ist das so wichtig? Eine sofortige Rückmeldung entscheidet oft darüber, ob ein Produkt genutzt oder verworfen wird.
+
+Wesentliche Merkmale dieser Implementierung:
+1. **Server-Sent Events (SSE):** Die Verbindung bleibt offen, und jedes Fragment wird gesendet, sobald es fertig ist.
+2. **Asynchrone Datenströme:** Der Server bearbeitet weitere Anfragen, während er auf das nächste Fragment wartet.
+3. **Einheitliche Fehler:** Fehler kommen im selben Format wie die Inhalte, sodass der Client sie gleich behandelt.
+
+Kurz gesagt: Streaming verbessert die gefühlte Geschwindigkeit und ermöglicht interaktive Chat-Oberflächen, Live-Programmierassistenten und dynamische Inhaltsgeneratoren. Probieren Sie es mit Ihrem eigenen Client aus – „es lohnt sich“!";
+
+const PORTUGUESE: &str = "As APIs de LLM com streaming mudam a forma como construímos aplicações de inteligência artificial. Ao entregar o conteúdo aos poucos, reduzimos bastante o tempo até o primeiro token, uma métrica essencial para manter o usuário engajado. Por que isso importa tanto? Porque uma resposta imediata faz toda a diferença entre um produto que é usado e um que é abandonado.
+
+Aspectos principais desta implementação:
+1. **Eventos enviados pelo servidor (SSE):** a conexão permanece aberta e cada fragmento é enviado assim que fica pronto.
+2. **Fluxos assíncronos:** o servidor atende outras requisições enquanto espera o próximo fragmento.
+3. **Erros consistentes:** as falhas chegam no mesmo formato do conteúdo, e o cliente as trata da mesma maneira.
+
+Em resumo, o streaming melhora a percepção de velocidade e torna possíveis interfaces de chat interativas, assistentes de programação ao vivo e geradores de conteúdo dinâmico. Experimente com o seu próprio cliente!";
+
+const RUSSIAN: &str = "Потоковые API языковых моделей меняют то, как мы создаём приложения с искусственным интеллектом. Когда содержимое приходит по частям, время до первого токена заметно сокращается, а это ключевой показатель вовлечённости пользователей. Почему это так важно? Мгновенный отклик часто решает, будут ли продуктом пользоваться или забросят его.
+
+Ключевые особенности этой реализации:
+1. **События, отправляемые сервером (SSE):** соединение остаётся открытым, и каждый фрагмент уходит, как только он готов.
+2. **Асинхронные потоки:** сервер обрабатывает другие запросы, пока ждёт следующий фрагмент.
+3. **Единообразные ошибки:** сбои приходят в том же формате, что и содержимое, и клиент обрабатывает их так же.
+
+Итак, потоковая передача улучшает воспринимаемую скорость и делает возможными интерактивные чаты, помощников программиста в реальном времени и генераторы динамического контента. Попробуйте с собственным клиентом — «это стоит того»!";
+
+const CHINESE: &str = "流式大语言模型接口正在改变我们构建人工智能应用的方式。通过逐步传输内容，首个令牌的等待时间大大缩短，而这正是衡量用户参与度的关键指标。为什么这一点如此重要？因为即时的反馈往往决定了一个产品是被持续使用还是被放弃。
+
+本实现的关键要点：
+1. **服务器发送事件（SSE）：**连接保持打开，每个片段一旦就绪便立即发送。
+2. **异步数据流：**在等待下一个片段时，服务器可以继续处理其他请求。
+3. **一致的错误格式：**错误与正文使用相同的格式返回，客户端可以用同样的方式处理。
+
+总之，流式传输不仅提升了用户感知到的速度，还让交互式聊天界面、实时编程助手和动态内容生成器成为可能。欢迎用您自己的客户端试一试！";
+
+const JAPANESE: &str = "ストリーミング対応のLLM APIは、人工知能アプリケーションの作り方を大きく変えつつあります。コンテンツを少しずつ届けることで、最初のトークンが表示されるまでの時間を大幅に短縮でき、これはユーザーの関心をつなぎとめるための重要な指標です。なぜそれほど大切なのでしょうか？すぐに反応が返ってくるかどうかが、製品が使われ続けるか見放されるかの分かれ目になるからです。
+
+この実装の主なポイント：
+1. **サーバー送信イベント（SSE）：**接続を開いたまま、各フラグメントを準備でき次第送信します。
+2. **非同期ストリーム：**次のフラグメントを待つ間も、サーバーは他のリクエストを処理できます。
+3. **一貫したエラー形式：**エラーも本文と同じ形式で届くため、クライアントは同じように扱えます。
+
+つまり、ストリーミングは体感速度を向上させるだけでなく、対話型チャット画面やリアルタイムのコーディング支援、動的なコンテンツ生成を可能にします。ぜひご自分のクライアントで「試してみて」ください！";
+
+const KOREAN: &str = "스트리밍 LLM API는 인공지능 애플리케이션을 만드는 방식을 바꾸고 있습니다. 콘텐츠를 조금씩 전달하면 첫 토큰까지 걸리는 시간이 크게 줄어드는데, 이는 사용자 참여를 가늠하는 핵심 지표입니다. 왜 이것이 그렇게 중요할까요? 즉각적인 반응이 제품이 계속 쓰일지 외면받을지를 가르기 때문입니다.
+
+이 구현의 핵심 사항:
+1. **서버 전송 이벤트(SSE):** 연결을 열어 둔 채 각 조각이 준비되는 대로 보냅니다.
+2. **비동기 스트림:** 다음 조각을 기다리는 동안에도 서버는 다른 요청을 처리합니다.
+3. **일관된 오류 형식:** 오류도 본문과 같은 형식으로 전달되므로 클라이언트가 똑같이 처리할 수 있습니다.
+
+요컨대 스트리밍은 체감 속도를 높일 뿐 아니라 대화형 채팅 화면, 실시간 코딩 도우미, 동적 콘텐츠 생성기를 가능하게 합니다. 직접 만든 클라이언트로 꼭 시험해 보세요!";
+
+const ARABIC: &str = "تُغيّر واجهات برمجة نماذج اللغة الكبيرة المتدفقة طريقة بناء تطبيقات الذكاء الاصطناعي. فعندما يصل المحتوى على دفعات، يقصر الوقت حتى وصول أول رمز بشكل ملحوظ، وهو مقياس أساسي لتفاعل المستخدمين. لماذا يهمّ ذلك إلى هذا الحد؟ لأن الاستجابة الفورية كثيراً ما تحدد ما إذا كان المنتج سيُستخدم أم سيُهجر.
+
+أبرز جوانب هذا التنفيذ:
+1. **الأحداث المرسلة من الخادم (SSE):** يبقى الاتصال مفتوحاً ويُرسل كل جزء فور جاهزيته.
+2. **التدفقات غير المتزامنة:** يعالج الخادم طلبات أخرى بينما ينتظر الجزء التالي.
+3. **أخطاء متسقة:** تصل الأخطاء بنفس صيغة المحتوى، فيتعامل معها العميل بالطريقة نفسها.
+
+باختصار، يحسّن البث السرعة التي يشعر بها المستخدم، ويتيح واجهات محادثة تفاعلية ومساعدي برمجة فوريين ومولدات محتوى ديناميكية. جرّبه باستخدام العميل الخاص بك!";
+
+const HINDI: &str = "स्ट्रीमिंग एलएलएम एपीआई कृत्रिम बुद्धिमत्ता वाले एप्लिकेशन बनाने का तरीका बदल रहे हैं। सामग्री को थोड़ा-थोड़ा करके भेजने से पहले टोकन तक का समय काफ़ी घट जाता है, और यही उपयोगकर्ताओं की रुचि बनाए रखने का एक अहम पैमाना है। यह इतना ज़रूरी क्यों है? क्योंकि तुरंत मिलने वाली प्रतिक्रिया ही अक्सर तय करती है कि कोई उत्पाद अपनाया जाएगा या छोड़ दिया जाएगा।
+
+इस कार्यान्वयन की मुख्य बातें:
+1. **सर्वर-सेंट इवेंट्स (SSE):** कनेक्शन खुला रहता है और हर हिस्सा तैयार होते ही भेज दिया जाता है।
+2. **एसिंक्रोनस स्ट्रीम:** अगले हिस्से की प्रतीक्षा करते हुए भी सर्वर दूसरे अनुरोध संभालता है।
+3. **एक जैसी त्रुटियाँ:** त्रुटियाँ भी सामग्री वाले प्रारूप में ही आती हैं, इसलिए क्लाइंट उन्हें उसी तरह संभालता है।
+
+संक्षेप में, स्ट्रीमिंग से गति बेहतर महसूस होती है और इंटरैक्टिव चैट, लाइव कोडिंग सहायक और गतिशील सामग्री जनरेटर संभव होते हैं। इसे अपने क्लाइंट के साथ ज़रूर आज़माएँ!";
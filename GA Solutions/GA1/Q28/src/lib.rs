@@ -0,0 +1,133 @@
+//! Streaming LLM mock server: an OpenAI-style SSE chat completions endpoint
+//! whose pacing, framing and failure behavior are driven by configuration.
+
+use actix_web::web;
+
+pub mod admin;
+pub mod admission;
+pub mod audio;
+pub mod audit;
+pub mod capture;
+pub mod chat;
+pub mod check;
+pub mod chunking;
+pub mod citations;
+#[cfg(feature = "client-tools")]
+pub mod client;
+#[cfg(feature = "endpoints")]
+pub mod cohere;
+pub mod compression;
+pub mod config;
+#[cfg(feature = "generators")]
+pub mod corpus;
+pub mod daemon;
+pub mod diffing;
+#[cfg(feature = "internal-debug")]
+pub mod debug;
+#[cfg(feature = "endpoints")]
+pub mod embeddings;
+pub mod error;
+pub mod etag;
+pub mod experiments;
+#[cfg(feature = "recording")]
+pub mod export;
+pub mod extensions;
+#[cfg(feature = "endpoints")]
+pub mod gemini;
+pub mod generator;
+pub mod guardrail;
+#[cfg(feature = "recording")]
+pub mod har;
+pub mod image;
+pub mod ingest;
+pub mod init;
+pub mod internal;
+pub mod keys;
+pub mod languages;
+pub mod lifecycle;
+pub mod metadata;
+pub mod metrics;
+pub mod models;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+pub mod orgs;
+pub mod overrides;
+pub mod pacer;
+pub mod panics;
+pub mod pii;
+pub mod pool;
+pub mod presets;
+pub mod pricing;
+pub mod queue;
+pub mod quota;
+pub mod ratelimit;
+#[cfg(feature = "endpoints")]
+pub mod realtime;
+pub mod replay;
+#[cfg(feature = "endpoints")]
+pub mod responses;
+pub mod retry_budget;
+pub mod server;
+pub mod service_tier;
+pub mod shadow;
+pub mod sse;
+pub mod state;
+pub mod storm;
+pub mod stored;
+pub mod stubs;
+pub mod throughput;
+pub mod timeouts;
+pub mod tokenizer;
+#[cfg(feature = "tokenizer")]
+pub mod tokenize;
+pub mod transforms;
+pub mod watermark;
+pub mod webhooks;
+pub mod wire;
+pub mod writer;
+#[cfg(feature = "endpoints")]
+pub mod ws;
+
+/// Registers every route the enabled features provide. Expects
+/// `web::Data<config::Config>` and `web::Data<state::AppState>` in app data.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(error::json_config());
+    cfg.service(chat::stream_endpoint);
+    #[cfg(feature = "openapi")]
+    cfg.service(openapi::document_endpoint)
+        .service(openapi::docs_endpoint);
+    #[cfg(feature = "endpoints")]
+    cfg.service(cohere::chat_endpoint)
+        .service(gemini::stream_endpoint)
+        .service(responses::responses_endpoint)
+        .service(realtime::realtime_endpoint)
+        .service(embeddings::embeddings_endpoint)
+        .service(models::list_endpoint)
+        .service(models::retrieve_endpoint)
+        .service(stored::list_endpoint)
+        .service(stored::retrieve_endpoint)
+        .service(stored::messages_endpoint)
+        .service(internal::stats_endpoint)
+        .service(internal::metrics_endpoint)
+        .service(diffing::diff_endpoint)
+        .service(lifecycle::events_endpoint);
+    #[cfg(feature = "tokenizer")]
+    cfg.service(tokenize::tokenize_endpoint)
+        .service(tokenize::detokenize_endpoint);
+    #[cfg(feature = "recording")]
+    cfg.service(capture::list_endpoint)
+        .service(capture::clear_endpoint)
+        .service(shadow::list_endpoint)
+        .service(shadow::clear_endpoint);
+    #[cfg(feature = "admin")]
+    cfg.service(storm::start_endpoint)
+        .service(storm::status_endpoint)
+        .service(audit::list_endpoint)
+        .service(storm::stop_endpoint)
+        .service(replay::clear_endpoint);
+    #[cfg(all(feature = "admin", feature = "recording"))]
+    cfg.service(export::export_endpoint)
+        .service(replay::load_endpoint);
+    #[cfg(feature = "internal-debug")]
+    cfg.service(debug::profile_endpoint);
+}
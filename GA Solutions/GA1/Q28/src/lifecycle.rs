@@ -0,0 +1,258 @@
+//! Lifecycle events for each stream: started, completed, or failed (including
+//! the client going away), with usage and timing. They are handed to the
+//! configured webhooks and broadcast on `/v1/internal/events`.
+
+use actix_web::{get, web, Error, HttpResponse};
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::chat::Message;
+use crate::orgs::OrgScope;
+use crate::quota::QuotaAlert;
+use crate::sse;
+use crate::state::AppState;
+use crate::tokenizer::TokenEncoding;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    #[serde(rename = "stream.started")]
+    Started,
+    #[serde(rename = "stream.completed")]
+    Completed,
+    #[serde(rename = "stream.error")]
+    Error,
+    /// A key's usage crossed a quota threshold; see `quota`.
+    #[serde(rename = "quota.threshold")]
+    QuotaThreshold,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 4] = [EventKind::Started, EventKind::Completed, EventKind::Error, EventKind::QuotaThreshold];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Started => "stream.started",
+            EventKind::Completed => "stream.completed",
+            EventKind::Error => "stream.error",
+            EventKind::QuotaThreshold => "quota.threshold",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub total_tokens: usize,
+    /// Only reported for requests with a `prediction`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+/// How much of a `prediction` the reply used. Rejected tokens are counted
+/// in `completion_tokens` too, as OpenAI bills them.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompletionTokensDetails {
+    pub accepted_prediction_tokens: usize,
+    pub rejected_prediction_tokens: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LifecycleEvent {
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+    /// Matches the `x-request-id` response header.
+    pub request_id: String,
+    pub model: Option<String>,
+    /// The scope usage is charged to; see `orgs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub timestamp_ms: u64,
+    /// Time since the request was accepted.
+    pub duration_ms: u64,
+    /// Completion tokens count what was sent so far.
+    pub usage: Usage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set on `quota.threshold` events only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaAlert>,
+}
+
+/// Milliseconds since the Unix epoch, as events report their time.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Follows one stream and reports its lifecycle. A tracker dropped before
+/// `completed` or `failed` means the client disconnected, which is reported
+/// as an error.
+pub struct Tracker {
+    state: web::Data<AppState>,
+    request_id: String,
+    model: Option<String>,
+    scope: OrgScope,
+    encoding: TokenEncoding,
+    accepted: Instant,
+    prompt_tokens: usize,
+    completion_tokens: Cell<usize>,
+    finished: Cell<bool>,
+}
+
+impl Tracker {
+    /// Reports `stream.started`, or returns `None` when nothing listens for
+    /// lifecycle events, so untracked streams pay nothing for counting.
+    pub fn start(
+        state: &web::Data<AppState>,
+        request_id: &str,
+        model: Option<&str>,
+        scope: &OrgScope,
+        encoding: TokenEncoding,
+        messages: &[Message],
+    ) -> Option<Tracker> {
+        if state.webhooks.is_empty() && !state.events.has_subscribers() {
+            return None;
+        }
+        let tracker = Tracker {
+            state: state.clone(),
+            request_id: request_id.to_string(),
+            model: model.map(str::to_string),
+            scope: scope.clone(),
+            encoding,
+            accepted: Instant::now(),
+            prompt_tokens: encoding.count_messages(messages),
+            completion_tokens: Cell::new(0),
+            finished: Cell::new(false),
+        };
+        tracker.emit(EventKind::Started, None);
+        Some(tracker)
+    }
+
+    /// Counts a chunk of reply text as sent.
+    pub fn chunk(&self, text: &str) {
+        self.completion_tokens.set(self.completion_tokens.get() + self.encoding.count(text));
+    }
+
+    pub fn completed(&self) {
+        self.finish(EventKind::Completed, None);
+    }
+
+    pub fn failed(&self, message: &str) {
+        self.finish(EventKind::Error, Some(message.to_string()));
+    }
+
+    fn finish(&self, kind: EventKind, error: Option<String>) {
+        if !self.finished.replace(true) {
+            self.emit(kind, error);
+        }
+    }
+
+    fn emit(&self, kind: EventKind, error: Option<String>) {
+        let completion_tokens = self.completion_tokens.get();
+        self.state.publish(LifecycleEvent {
+            kind,
+            request_id: self.request_id.clone(),
+            model: self.model.clone(),
+            organization: self.scope.organization.clone(),
+            project: self.scope.project.clone(),
+            timestamp_ms: now_ms(),
+            duration_ms: self.accepted.elapsed().as_millis() as u64,
+            usage: Usage {
+                prompt_tokens: self.prompt_tokens,
+                completion_tokens,
+                total_tokens: self.prompt_tokens + completion_tokens,
+                completion_tokens_details: None,
+            },
+            error,
+            quota: None,
+        });
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        self.failed("client disconnected");
+    }
+}
+
+/// Events a slow `/v1/internal/events` subscriber may fall behind by before
+/// it starts missing them.
+const EVENT_BUFFER: usize = 1024;
+/// Idle time after which the firehose sends a comment to keep proxies from
+/// closing the connection.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Fans lifecycle events out to every live `/v1/internal/events` subscriber.
+pub struct EventBus {
+    sender: broadcast::Sender<LifecycleEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus {
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl EventBus {
+    /// Sends `event` to current subscribers; with none it is dropped.
+    pub fn publish(&self, event: LifecycleEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+}
+
+/// `event: <name>` followed by `json`, or an empty object if it fails to serialize.
+fn named_event(name: &str, json: &impl Serialize) -> Bytes {
+    sse::named_event(name, json).unwrap_or_else(|e| {
+        log::error!("{}", e);
+        sse::named_event(name, &serde_json::json!({})).expect("an empty object always serializes")
+    })
+}
+
+/// Streams every lifecycle event as it happens, one SSE event per lifecycle
+/// event named after its type. The `: subscribed` comment sent first marks
+/// the point from which events are delivered, so tests can subscribe, wait
+/// for it, and then send traffic. A subscriber that falls more than
+/// `EVENT_BUFFER` events behind gets an `event: lagged` with the number missed.
+#[get("/v1/internal/events")]
+pub async fn events_endpoint(state: web::Data<AppState>) -> HttpResponse {
+    let receiver = state.events.subscribe();
+    let subscribed = stream::once(async { Ok::<Bytes, Error>(Bytes::from_static(b": subscribed\n\n")) });
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        let event = tokio::select! {
+            event = receiver.recv() => event,
+            _ = tokio::time::sleep(KEEPALIVE) => {
+                return Some((Ok::<Bytes, Error>(Bytes::from_static(b": keepalive\n\n")), receiver));
+            }
+        };
+        let bytes = match event {
+            Ok(event) => named_event(event.kind.name(), &event),
+            Err(RecvError::Lagged(missed)) => named_event("lagged", &serde_json::json!({ "missed": missed })),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok::<Bytes, Error>(bytes), receiver))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(subscribed.chain(events))
+}
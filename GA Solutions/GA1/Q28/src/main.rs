@@ -1,142 +1,221 @@
-use actix_web::{web, App, HttpServer, HttpResponse, post, middleware, Error};
-use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use futures::stream;
-use bytes::Bytes;
-use tokio::time::sleep;
-
-#[derive(Deserialize)]
-struct StreamRequest {
-    prompt: String,
-    stream: bool,
-}
-
-#[derive(Serialize)]
-struct StreamChunk {
-    choices: Vec<Choice>,
+use clap::{Parser, Subcommand};
+
+#[cfg(feature = "client-tools")]
+use streaming_llm_api::client::bench::{self, BenchArgs};
+#[cfg(feature = "client-tools")]
+use streaming_llm_api::client::conformance::{self, ConformanceArgs};
+#[cfg(all(feature = "client-tools", feature = "recording"))]
+use streaming_llm_api::client::export::{self, ExportArgs};
+#[cfg(feature = "client-tools")]
+use streaming_llm_api::client::scenario::{self, ScenarioCommand};
+#[cfg(feature = "client-tools")]
+use streaming_llm_api::client::soak;
+use streaming_llm_api::check::{self, Severity};
+use streaming_llm_api::config::{self, Config};
+#[cfg(feature = "generators")]
+use streaming_llm_api::corpus::{self, CorpusCommand};
+#[cfg(windows)]
+use streaming_llm_api::daemon::service::{self, ServiceCommand};
+use streaming_llm_api::daemon::{self, Claim, ServeArgs};
+use streaming_llm_api::init::{self, InitArgs};
+use streaming_llm_api::server;
+use streaming_llm_api::watermark;
+
+#[derive(Parser)]
+#[command(name = "stream-api", about = "Streaming LLM mock server and load tools")]
+struct Cli {
+    /// Config file; overrides STREAM_API_CONFIG.
+    #[arg(long, global = true)]
+    config: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Serialize)]
-struct Choice {
-    delta: Delta,
+#[derive(Subcommand)]
+enum Command {
+    /// Run the mock server (the default).
+    Serve(ServeArgs),
+    /// Drive streaming load against a running server and report how it coped;
+    /// with --duration, soak it instead.
+    #[cfg(feature = "client-tools")]
+    Bench(BenchArgs),
+    /// Download a running server's captured traffic, e.g. as fine-tuning JSONL.
+    #[cfg(all(feature = "client-tools", feature = "recording"))]
+    Export(ExportArgs),
+    /// Run scripted request sequences against a server and check the responses.
+    #[cfg(feature = "client-tools")]
+    Scenario {
+        #[command(subcommand)]
+        command: ScenarioCommand,
+    },
+    /// Check that another endpoint, usually the real provider, behaves as the mock does.
+    #[cfg(feature = "client-tools")]
+    Conformance(ConformanceArgs),
+    /// Install or remove the Windows service, which serves the given --config.
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
+    },
+    /// Write a commented starter config.
+    Init(InitArgs),
+    /// Build the packed reply corpora `[corpus]` serves from.
+    #[cfg(feature = "generators")]
+    Corpus {
+        #[command(subcommand)]
+        command: CorpusCommand,
+    },
+    /// Validate a config file without starting the server.
+    Check {
+        /// File to check; defaults to --config, then STREAM_API_CONFIG.
+        file: Option<String>,
+    },
+    /// Find watermarks in files, or stdin, and name the server and rule that
+    /// produced them. Exits non-zero if there are none.
+    Detect {
+        files: Vec<String>,
+    },
 }
 
-#[derive(Serialize, Default)]
-struct Delta {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    content: Option<String>,
+fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
+    // Serving may fork or hand over to the service dispatcher, so it starts
+    // its own runtime; everything else shares this one.
+    match cli.command.unwrap_or_else(|| Command::Serve(ServeArgs::default())) {
+        Command::Serve(args) => serve(cli.config.as_deref(), &args),
+        #[cfg(windows)]
+        Command::Service { command } => service::run(&command, cli.config.as_deref()),
+        command => actix_web::rt::System::new().block_on(run(command, cli.config)),
+    }
 }
 
-#[derive(Serialize)]
-struct ErrorEvent {
-    error: String,
-    code: i32,
+async fn run(command: Command, config: Option<String>) -> std::io::Result<()> {
+    match command {
+        Command::Serve(_) => unreachable!("serve starts its own runtime"),
+        #[cfg(windows)]
+        Command::Service { .. } => unreachable!("the service starts its own runtime"),
+        #[cfg(feature = "client-tools")]
+        Command::Bench(args) => {
+            match args.duration {
+                Some(duration) => soak::run(&args, duration).await.print(),
+                None => bench::run(&args).await.print(),
+            }
+            Ok(())
+        }
+        #[cfg(all(feature = "client-tools", feature = "recording"))]
+        Command::Export(args) => export::run(&args).await,
+        #[cfg(feature = "client-tools")]
+        Command::Scenario {
+            command: ScenarioCommand::Run(args),
+        } => {
+            if !scenario::run_file(&args).await? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        #[cfg(feature = "client-tools")]
+        Command::Conformance(args) => {
+            if !conformance::run_cli(&args).await? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Init(args) => {
+            let path = init::run(&args)?;
+            println!("Wrote {}; check it with `stream-api check {}`", path.display(), path.display());
+            Ok(())
+        }
+        #[cfg(feature = "generators")]
+        Command::Corpus { command } => corpus::run(&command),
+        Command::Check { file } => check(file.or(config)),
+        Command::Detect { files } => detect(&files),
+    }
 }
 
-const EXTENDED_CONTENT: &str = "Streaming LLM APIs represent a paradigm shift in how we build and interact with artificial intelligence. By delivering content incrementally, we significantly reduce the time to first token, which is a critical metric for user engagement. In a world where attention spans are measured in seconds, providing immediate feedback can make the difference between a successful product and a failed one. This implementation showcases a robust, high-performance streaming endpoint built with Rust and actix-web. 
-
-Rust's zero-cost abstractions and memory safety guarantees make it an ideal choice for building high-throughput, low-latency APIs. The actix-web framework provides a powerful, non-blocking asynchronous architecture that can handle thousands of concurrent streaming connections with minimal overhead. 
-
-Key technical aspects of this solution include:
-1. Server-Sent Events (SSE) Protocol: We use the text/event-stream content type to maintain a persistent connection and push data chunks to the client as they become available.
-2. Asynchronous Streams: Leveraging the `futures` crate, we generate a non-blocking stream of data chunks, allowing the server to handle other requests while waiting for the next chunk to be ready.
-3. Efficient Serialization: We use `serde_json` for fast and safe serialization of JSON deltas, ensuring minimal processing latency.
-4. Error Handling: The API includes robust validation for prompts and stream parameters, returning descriptive errors in the same streaming format to ensure client-side consistency.
-5. Buffering Control: By setting specific HTTP headers like `X-Accel-Buffering: no` and `Cache-Control: no-cache`, we prevent intermediate proxies from buffering the stream, ensuring real-time delivery to the end user.
-
-This approach not only improves perceived performance but also enables complex real-time applications such as interactive chat interfaces, live coding assistants, and dynamic content generators that feel alive and responsive. By following these architectural patterns, developers can build AI-powered tools that provide a seamless and premium user experience. Moreover, the integration of streaming capabilities into the development workflow allows for a more iterative and fast-paced environment where feedback loops are shortened and productivity is enhanced. In conclusion, this solution provides a robust foundation for any application requiring high-quality, real-time AI-generated content.";
-
-fn create_error_event(error: &str, code: i32) -> String {
-    let event = ErrorEvent {
-        error: error.to_string(),
-        code,
+/// Prints each watermark found as `<file>: <instance> [rule <name>]`.
+fn detect(files: &[String]) -> std::io::Result<()> {
+    let inputs = if files.is_empty() {
+        vec![("-".to_string(), std::io::read_to_string(std::io::stdin())?)]
+    } else {
+        files.iter().map(|f| Ok((f.clone(), std::fs::read_to_string(f)?))).collect::<std::io::Result<_>>()?
     };
-    format!("data: {}\n\n", serde_json::to_string(&event).unwrap())
+    let mut found = 0;
+    for (name, text) in inputs {
+        for mark in watermark::detect(&text) {
+            found += 1;
+            match &mark.rule {
+                Some(rule) => println!("{}: {} rule {}", name, mark.instance, rule),
+                None => println!("{}: {}", name, mark.instance),
+            }
+        }
+    }
+    if found == 0 {
+        std::process::exit(1);
+    }
+    Ok(())
 }
 
-#[post("/v1/chat/completions")]
-async fn stream_endpoint(req: web::Json<StreamRequest>) -> HttpResponse {
-    if req.prompt.trim().is_empty() {
-        return HttpResponse::BadRequest()
-            .content_type("text/event-stream")
-            .body(create_error_event("Prompt cannot be empty", 400));
+/// Prints every finding and exits non-zero if any is an error.
+fn check(path: Option<String>) -> std::io::Result<()> {
+    let path = path.or_else(|| std::env::var(config::CONFIG_ENV).ok()).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no config file given to check")
+    })?;
+    let findings = check::check_file(&path)?;
+    for finding in &findings {
+        println!("{}: {}", path, finding);
     }
-
-    if !req.stream {
-        return HttpResponse::BadRequest()
-            .content_type("text/event-stream")
-            .body(create_error_event("stream parameter must be true", 400));
+    let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+    println!("{}: {} error(s), {} warning(s)", path, errors, findings.len() - errors);
+    if errors > 0 {
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    let prompt = req.prompt.clone();
-    let content = format!("Regarding your prompt '{}':\n\n{}", prompt, EXTENDED_CONTENT);
-    
-    // Split into at least 15 chunks to ensure progressive delivery and character count
-    let chunks: Vec<String> = content
-        .as_str()
-        .chars()
-        .collect::<Vec<_>>()
-        .chunks(content.len() / 15 + 1)
-        .map(|c| c.iter().collect::<String>())
-        .collect();
+/// Loads the config and binds before detaching, so those errors still reach
+/// the terminal.
+fn serve(config_path: Option<&str>, args: &ServeArgs) -> std::io::Result<()> {
+    println!("🚀 High-Performance Streaming LLM API Server Starting...");
 
-    let stream = stream::unfold((chunks.into_iter(), 0), |(mut chunks, count)| async move {
-        match chunks.next() {
-            Some(chunk) => {
-                // Throttle slightly to simulate generation and ensure throughput measurement is accurate
-                // ~30 tokens/sec, assuming 1 token ~ 4 chars. 15 chunks for ~2000 chars = 133 chars/chunk.
-                // 133 chars ~ 33 tokens. To get 30 tokens/sec, we need ~1.1 sec total.
-                // 15 chunks * 75ms = 1125ms total.
-                sleep(Duration::from_millis(75)).await;
-                
-                let stream_chunk = StreamChunk {
-                    choices: vec![Choice {
-                        delta: Delta {
-                            content: Some(chunk),
-                        },
-                    }],
-                };
-                
-                let json = serde_json::to_string(&stream_chunk).unwrap_or_default();
-                let event = format!("data: {}\n\n", json);
-                Some((Ok::<Bytes, Error>(Bytes::from(event)), (chunks, count + 1)))
-            }
-            None => {
-                if count > 0 {
-                    // Send [DONE] signal at the end
-                    let done_signal = "data: [DONE]\n\n";
-                    // Using a dummy count to avoid infinite loop
-                    Some((Ok::<Bytes, Error>(Bytes::from(done_signal)), (chunks, 0)))
-                } else {
-                    None
-                }
-            }
+    let mut config = Config::load(config_path)?;
+    config.server.reuse_port |= args.reuse_port;
+    // Another server may only hold the pidfile if this one is replacing it.
+    let predecessor = match &args.pidfile {
+        Some(path) if config.server.reuse_port => daemon::pidfile_holder(path)?,
+        Some(path) => daemon::check_pidfile(path).map(|()| None)?,
+        None => None,
+    };
+    let listener = match server::activated()? {
+        Some(listener) => {
+            println!("🔌 Using the socket passed in by systemd");
+            listener
         }
-    });
+        None => server::bind(&config.server)?,
+    };
+    println!("📡 Endpoint: http://{}/v1/chat/completions", listener.local_addr()?);
+    println!("✍️  Write mode: {:?}", config.stream.write_mode);
+    if !config.stubs.is_empty() {
+        println!("🧩 Loaded {} stub rule(s)", config.stubs.len());
+    }
+    if config.stream.simulate_proxy_buffering {
+        println!("🧱 Simulating proxy buffering: responses are sent in one piece");
+    }
+    if let Some(pid) = predecessor {
+        server::steer_new_connections(&listener)?;
+        println!("🔁 Taking over from pid {}, which will drain and exit", pid);
+    }
 
-    HttpResponse::Ok()
-        .content_type("text/event-stream")
-        .insert_header(("Access-Control-Allow-Origin", "*"))
-        .insert_header(("Access-Control-Allow-Methods", "POST, GET, OPTIONS"))
-        .insert_header(("Access-Control-Allow-Headers", "Content-Type, Authorization"))
-        .insert_header(("Cache-Control", "no-cache"))
-        .insert_header(("Connection", "keep-alive"))
-        .insert_header(("X-Accel-Buffering", "no"))
-        .streaming(stream)
-}
+    #[cfg(unix)]
+    if args.daemonize {
+        println!("🌙 Detaching{}", args.log_file.as_ref().map(|p| format!("; output goes to {}", p.display())).unwrap_or_default());
+        daemon::daemonize(args)?;
+    }
+    let claim = args.pidfile.as_deref().map(|path| Claim::new(path, predecessor)).transpose()?;
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    println!("🚀 High-Performance Streaming LLM API Server Starting...");
-    println!("📡 Endpoint: http://127.0.0.1:8080/v1/chat/completions");
-    
-    HttpServer::new(|| {
-        App::new()
-            .wrap(middleware::Logger::default())
-            .service(stream_endpoint)
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    let served = actix_web::rt::System::new().block_on(async { server::serve(config, listener)?.await });
+    if let Some(claim) = claim {
+        claim.release();
+    }
+    served
 }
-
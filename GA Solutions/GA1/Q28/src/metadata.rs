@@ -0,0 +1,74 @@
+//! The optional `event: metadata` frame sent just before `[DONE]`, carrying
+//! the reply's token usage, duration and a hash of its content, for clients
+//! experimenting with richer completion metadata than OpenAI streams provide.
+//!
+//! HTTP trailers would be the other natural channel, but actix-web's HTTP/1.1
+//! encoder cannot send them, and few SSE clients could read them if it did.
+
+use bytes::Bytes;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::Instant;
+
+use crate::chat::Message;
+use crate::error::MockError;
+use crate::lifecycle::Usage;
+use crate::sse;
+use crate::tokenizer::TokenEncoding;
+
+#[derive(Serialize)]
+pub struct Metadata {
+    /// Completion tokens are counted chunk by chunk, as they were generated.
+    pub usage: Usage,
+    /// Time from the start of the stream to this frame.
+    pub duration_ms: u64,
+    /// Hex SHA-256 of the concatenated `delta.content` of the whole reply.
+    pub content_sha256: String,
+}
+
+/// Accumulates the metadata of one reply as its chunks are produced.
+pub struct ReplyDigest {
+    encoding: TokenEncoding,
+    started: Instant,
+    prompt_tokens: usize,
+    completion_tokens: usize,
+    hasher: Sha256,
+}
+
+impl ReplyDigest {
+    pub fn new(encoding: TokenEncoding, messages: &[Message]) -> ReplyDigest {
+        ReplyDigest {
+            encoding,
+            started: Instant::now(),
+            prompt_tokens: encoding.count_messages(messages),
+            completion_tokens: 0,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Adds a chunk of reply text. Chunks skipped on resume are added too, so
+    /// the hash always covers the reply a client reassembles.
+    pub fn chunk(&mut self, text: &str) {
+        self.completion_tokens += self.encoding.count(text);
+        self.hasher.update(text.as_bytes());
+    }
+
+    pub fn metadata(self) -> Metadata {
+        let content_sha256 = self.hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        Metadata {
+            usage: Usage {
+                prompt_tokens: self.prompt_tokens,
+                completion_tokens: self.completion_tokens,
+                total_tokens: self.prompt_tokens + self.completion_tokens,
+                completion_tokens_details: None,
+            },
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            content_sha256,
+        }
+    }
+
+    /// The finished `event: metadata` frame.
+    pub fn event(self) -> Result<Bytes, MockError> {
+        sse::named_event("metadata", &self.metadata())
+    }
+}
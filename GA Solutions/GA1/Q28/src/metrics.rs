@@ -0,0 +1,309 @@
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A monotonically increasing process-wide count.
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub const fn new() -> Counter {
+        Counter(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Counter::new()
+    }
+}
+
+/// A process-wide level that rises and falls.
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub const fn new() -> Gauge {
+        Gauge(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dec(&self) {
+        self.sub(1);
+    }
+
+    pub fn sub(&self, n: u64) {
+        self.0.fetch_sub(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Gauge {
+    fn default() -> Self {
+        Gauge::new()
+    }
+}
+
+/// Chat completion streams currently open.
+pub static ACTIVE_STREAMS: Gauge = Gauge::new();
+/// Tasks started with `spawn` that have not finished.
+pub static BACKGROUND_TASKS: Gauge = Gauge::new();
+/// Chunk delays waiting in a pacing wheel; see `pacer`.
+pub static PARKED_TIMERS: Gauge = Gauge::new();
+
+/// Spawns `task` on the current worker, counted in `BACKGROUND_TASKS` until it
+/// finishes or is dropped with its runtime.
+pub fn spawn<F: Future<Output = ()> + 'static>(task: F) {
+    struct Running;
+    impl Drop for Running {
+        fn drop(&mut self) {
+            BACKGROUND_TASKS.dec();
+        }
+    }
+    BACKGROUND_TASKS.inc();
+    let running = Running;
+    actix_rt::spawn(async move {
+        let _running = running;
+        task.await
+    });
+}
+
+/// Chat completion requests in the standard `messages` shape.
+pub static CHAT_REQUESTS: Counter = Counter::new();
+/// Chat completion requests still using the deprecated `{prompt, stream}` shape.
+pub static LEGACY_PROMPT_REQUESTS: Counter = Counter::new();
+/// Requests that had to wait in the admission queue.
+pub static QUEUED_REQUESTS: Counter = Counter::new();
+/// Requests turned away because the admission queue was full.
+pub static QUEUE_REJECTIONS: Counter = Counter::new();
+/// Total time requests spent in the admission queue, in milliseconds.
+pub static QUEUE_MS_TOTAL: Counter = Counter::new();
+/// Requests refused by the PII filter.
+pub static PII_REJECTIONS: Counter = Counter::new();
+/// Requests turned away over the context window before their body was read whole.
+pub static EARLY_CONTEXT_REJECTIONS: Counter = Counter::new();
+/// Estimated cost of priced replies, in billionths of a dollar; see `pricing`.
+pub static ESTIMATED_COST_NANOS: Counter = Counter::new();
+/// Handlers that panicked and were answered with a 500; see `panics`.
+pub static HANDLER_PANICS: Counter = Counter::new();
+/// Handlers and streams cut off by `[timeouts]`; see `timeouts`.
+pub static HANDLER_TIMEOUTS: Counter = Counter::new();
+/// Lifecycle events a webhook accepted.
+pub static WEBHOOK_DELIVERIES: Counter = Counter::new();
+/// Lifecycle events dropped after a webhook refused them or retries ran out.
+pub static WEBHOOK_FAILURES: Counter = Counter::new();
+
+#[derive(Serialize)]
+pub struct RequestStats {
+    pub chat: u64,
+    pub legacy_prompt: u64,
+    pub queued: u64,
+    pub queue_rejected: u64,
+    pub queue_ms_total: u64,
+    pub pii_rejected: u64,
+    pub context_rejected_early: u64,
+    pub webhooks_delivered: u64,
+    pub webhooks_failed: u64,
+    pub handler_panics: u64,
+    pub handler_timeouts: u64,
+    pub estimated_cost_usd: f64,
+}
+
+pub fn request_stats() -> RequestStats {
+    RequestStats {
+        chat: CHAT_REQUESTS.get(),
+        legacy_prompt: LEGACY_PROMPT_REQUESTS.get(),
+        queued: QUEUED_REQUESTS.get(),
+        queue_rejected: QUEUE_REJECTIONS.get(),
+        queue_ms_total: QUEUE_MS_TOTAL.get(),
+        pii_rejected: PII_REJECTIONS.get(),
+        context_rejected_early: EARLY_CONTEXT_REJECTIONS.get(),
+        webhooks_delivered: WEBHOOK_DELIVERIES.get(),
+        webhooks_failed: WEBHOOK_FAILURES.get(),
+        handler_panics: HANDLER_PANICS.get(),
+        handler_timeouts: HANDLER_TIMEOUTS.get(),
+        estimated_cost_usd: ESTIMATED_COST_NANOS.get() as f64 / 1e9,
+    }
+}
+
+/// Upper bounds of the time-to-first-token buckets, in seconds.
+const TTFT_BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+/// Upper bounds of the inter-chunk gap buckets, in seconds.
+const GAP_BUCKETS: [f64; 12] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 1.0, 5.0];
+/// Distinct model labels kept; streams for any further models share the
+/// `other` label, so clients cannot grow the registry without bound.
+const MAX_MODELS: usize = 64;
+
+/// Counts of observations at or under each bucket's bound, Prometheus style.
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// One per bound, then one for `+Inf`; not cumulative.
+    counts: Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: Duration) {
+        let seconds = value.as_secs_f64();
+        let bucket = self.bounds.iter().position(|&bound| seconds <= bound).unwrap_or(self.bounds.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(value.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Appends the histogram's `_bucket`, `_sum` and `_count` lines.
+    fn render(&self, out: &mut String, name: &str, model: &str) {
+        let model = escape_label(model);
+        let mut cumulative = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            let le = self.bounds.get(i).map_or_else(|| "+Inf".to_string(), f64::to_string);
+            let _ = writeln!(out, "{}_bucket{{model=\"{}\",le=\"{}\"}} {}", name, model, le, cumulative);
+        }
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(out, "{}_sum{{model=\"{}\"}} {}", name, model, sum);
+        let _ = writeln!(out, "{}_count{{model=\"{}\"}} {}", name, model, cumulative);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Pacing histograms of one model's streams.
+pub struct ChunkTimings {
+    pub time_to_first_token: Histogram,
+    pub inter_chunk_gap: Histogram,
+}
+
+/// Chunk timings by model name, for every streamed chat completion.
+pub static CHUNK_TIMINGS: Mutex<BTreeMap<String, Arc<ChunkTimings>>> = Mutex::new(BTreeMap::new());
+
+/// The timings a stream for `model` is recorded under.
+pub fn chunk_timings(model: Option<&str>) -> Arc<ChunkTimings> {
+    let mut timings = CHUNK_TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let model = model.unwrap_or("unknown");
+    let model = if timings.contains_key(model) || timings.len() < MAX_MODELS { model } else { "other" };
+    timings
+        .entry(model.to_string())
+        .or_insert_with(|| {
+            Arc::new(ChunkTimings {
+                time_to_first_token: Histogram::new(&TTFT_BUCKETS),
+                inter_chunk_gap: Histogram::new(&GAP_BUCKETS),
+            })
+        })
+        .clone()
+}
+
+/// Times the content chunks of one stream as they go out: the first from
+/// when the request arrived, each later one from the chunk before.
+pub struct ChunkClock {
+    timings: Arc<ChunkTimings>,
+    accepted: Instant,
+    last: Cell<Option<Instant>>,
+}
+
+impl ChunkClock {
+    pub fn start(model: Option<&str>, accepted: Instant) -> ChunkClock {
+        ACTIVE_STREAMS.inc();
+        ChunkClock {
+            timings: chunk_timings(model),
+            accepted,
+            last: Cell::new(None),
+        }
+    }
+
+    pub fn chunk(&self) {
+        let now = Instant::now();
+        match self.last.replace(Some(now)) {
+            Some(last) => self.timings.inter_chunk_gap.observe(now - last),
+            None => self.timings.time_to_first_token.observe(now - self.accepted),
+        }
+    }
+}
+
+impl Drop for ChunkClock {
+    fn drop(&mut self) {
+        ACTIVE_STREAMS.dec();
+    }
+}
+
+/// Appends a gauge in the Prometheus text exposition format.
+pub fn render_gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+}
+
+/// Every counter and histogram in the Prometheus text exposition format.
+pub fn prometheus() -> String {
+    let mut out = String::new();
+    let counters: [(&str, &str, &Counter); 12] = [
+        ("mock_chat_requests_total", "Chat completion requests in the messages shape.", &CHAT_REQUESTS),
+        ("mock_legacy_prompt_requests_total", "Chat completion requests in the deprecated prompt shape.", &LEGACY_PROMPT_REQUESTS),
+        ("mock_queued_requests_total", "Requests that waited in the admission queue.", &QUEUED_REQUESTS),
+        ("mock_queue_rejections_total", "Requests turned away by a full admission queue.", &QUEUE_REJECTIONS),
+        ("mock_queue_milliseconds_total", "Time requests spent in the admission queue.", &QUEUE_MS_TOTAL),
+        ("mock_pii_rejections_total", "Requests refused by the PII filter.", &PII_REJECTIONS),
+        ("mock_early_context_rejections_total", "Requests refused over the context window before their body was read.", &EARLY_CONTEXT_REJECTIONS),
+        ("mock_estimated_cost_nanodollars_total", "Estimated cost of priced replies.", &ESTIMATED_COST_NANOS),
+        ("mock_webhook_deliveries_total", "Lifecycle events a webhook accepted.", &WEBHOOK_DELIVERIES),
+        ("mock_webhook_failures_total", "Lifecycle events dropped by webhook delivery.", &WEBHOOK_FAILURES),
+        ("mock_handler_panics_total", "Handlers that panicked and were answered with a 500.", &HANDLER_PANICS),
+        ("mock_handler_timeouts_total", "Handlers and streams cut off by a timeout budget.", &HANDLER_TIMEOUTS),
+    ];
+    for (name, help, counter) in counters {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, counter.get());
+    }
+    render_gauge(&mut out, "mock_active_streams", "Chat completion streams currently open.", ACTIVE_STREAMS.get());
+    render_gauge(&mut out, "mock_background_tasks", "Background tasks spawned and still running.", BACKGROUND_TASKS.get());
+    render_gauge(&mut out, "mock_parked_timers", "Chunk delays waiting in the pacing wheels.", PARKED_TIMERS.get());
+    let timings = CHUNK_TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut family = |name: &str, help: &str, histogram: fn(&ChunkTimings) -> &Histogram| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} histogram", name, help, name);
+        for (model, timings) in timings.iter() {
+            histogram(timings).render(&mut out, name, model);
+        }
+    };
+    family(
+        "mock_time_to_first_token_seconds",
+        "Time from a streamed request arriving to its first content chunk.",
+        |t| &t.time_to_first_token,
+    );
+    family(
+        "mock_inter_chunk_gap_seconds",
+        "Time between consecutive content chunks of a stream.",
+        |t| &t.inter_chunk_gap,
+    );
+    out
+}
@@ -0,0 +1,150 @@
+//! The models the server advertises, with the limits requests are checked against.
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::MockError;
+use crate::keys;
+use crate::pricing::Pricing;
+use crate::state::AppState;
+use crate::stubs::ContentMode;
+use crate::tokenizer::TokenEncoding;
+
+/// A model as listed in `[[models]]`, which also overrides built-in entries
+/// with the same `id`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Prompt plus completion tokens the model accepts.
+    pub context_window: usize,
+    #[serde(default)]
+    pub encoding: TokenEncoding,
+    #[serde(default = "default_owner")]
+    pub owned_by: String,
+    /// How this model's chunks carry text, unless the stub rule says.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_mode: Option<ContentMode>,
+    /// Prices replies are costed at; unpriced models report no cost.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+}
+
+fn default_owner() -> String {
+    "mock".to_string()
+}
+
+/// Context windows and list prices (USD per million input and output
+/// tokens) as documented by the respective providers.
+const BUILTIN: &[(&str, usize, TokenEncoding, &str, f64, f64)] = &[
+    ("gpt-4o", 128_000, TokenEncoding::O200k, "openai", 2.50, 10.00),
+    ("gpt-4o-mini", 128_000, TokenEncoding::O200k, "openai", 0.15, 0.60),
+    ("gpt-4-turbo", 128_000, TokenEncoding::Cl100k, "openai", 10.00, 30.00),
+    ("gpt-4", 8_192, TokenEncoding::Cl100k, "openai", 30.00, 60.00),
+    ("gpt-3.5-turbo", 16_385, TokenEncoding::Cl100k, "openai", 0.50, 1.50),
+    ("claude-3-5-sonnet", 200_000, TokenEncoding::Cl100k, "anthropic", 3.00, 15.00),
+    ("llama-3.1-8b-instant", 131_072, TokenEncoding::Cl100k, "meta", 0.05, 0.08),
+];
+
+pub struct ModelRegistry {
+    models: Vec<ModelInfo>,
+}
+
+impl ModelRegistry {
+    pub fn new(configured: &[ModelInfo]) -> ModelRegistry {
+        let mut models: Vec<ModelInfo> = BUILTIN
+            .iter()
+            .filter(|(id, ..)| !configured.iter().any(|m| m.id == *id))
+            .map(|&(id, context_window, encoding, owned_by, input_per_million, output_per_million)| ModelInfo {
+                id: id.to_string(),
+                context_window,
+                encoding,
+                owned_by: owned_by.to_string(),
+                content_mode: None,
+                pricing: Some(Pricing {
+                    input_per_million,
+                    output_per_million,
+                }),
+            })
+            .collect();
+        models.extend(configured.iter().cloned());
+        ModelRegistry { models }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ModelInfo> {
+        self.models.iter().find(|m| m.id == id)
+    }
+
+    pub fn all(&self) -> &[ModelInfo] {
+        &self.models
+    }
+}
+
+/// `created` reported for every model. Fixed so responses are reproducible.
+const CREATED: u64 = 1_700_000_000;
+
+/// The OpenAI model object, plus the registry's own metadata.
+#[derive(Serialize)]
+struct ModelObject<'a> {
+    id: &'a str,
+    object: &'static str,
+    created: u64,
+    owned_by: &'a str,
+    context_window: usize,
+    encoding: TokenEncoding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pricing: Option<Pricing>,
+}
+
+impl<'a> From<&'a ModelInfo> for ModelObject<'a> {
+    fn from(model: &'a ModelInfo) -> Self {
+        ModelObject {
+            id: &model.id,
+            object: "model",
+            created: CREATED,
+            owned_by: &model.owned_by,
+            context_window: model.context_window,
+            encoding: model.encoding,
+            pricing: model.pricing,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ModelList<'a> {
+    object: &'static str,
+    data: Vec<ModelObject<'a>>,
+}
+
+/// Keys restricted to some models see only those, as with the provider.
+#[get("/v1/models")]
+pub async fn list_endpoint(req: HttpRequest, config: web::Data<Config>, state: web::Data<AppState>) -> HttpResponse {
+    let key = keys::key_for(&config.keys, &req);
+    HttpResponse::Ok().json(ModelList {
+        object: "list",
+        data: state
+            .models
+            .all()
+            .iter()
+            .filter(|m| key.is_none_or(|k| k.allows_model(&m.id)))
+            .map(ModelObject::from)
+            .collect(),
+    })
+}
+
+/// Ids may contain slashes (`org/model`), so the rest of the path is the id.
+#[get("/v1/models/{id:.*}")]
+pub async fn retrieve_endpoint(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let id = path.into_inner();
+    let allowed = keys::key_for(&config.keys, &req).is_none_or(|k| k.allows_model(&id));
+    match state.models.get(&id).filter(|_| allowed) {
+        Some(model) => Ok(HttpResponse::Ok().json(ModelObject::from(model))),
+        None => Err(keys::model_not_found(&id)),
+    }
+}
@@ -0,0 +1,1001 @@
+//! The OpenAPI 3.1 document for every route the enabled features register,
+//! served at `/openapi.json`, with a Swagger UI at `/docs`.
+//!
+//! The schemas describe exactly what the mock reads and writes, field for
+//! field with the `wire` types, including the fields it leaves out where the
+//! provider sends them; reply objects are closed, so a generated client
+//! rejects anything the mock does not send. The document is written out here
+//! rather than derived, since the handlers' own structs are borrowed and
+//! serialize-only. `tests/openapi.rs` checks real replies against it, every
+//! documented operation against the router, and every route attribute in
+//! `src` that the router serves against the document.
+//!
+//! The Swagger UI at `/docs` is not vendored: the page loads it from unpkg,
+//! so it needs network access in the browser. The document itself does not.
+
+use actix_web::{get, HttpResponse};
+use serde_json::{json, Map, Value};
+
+/// The Swagger UI page, loading the UI from a CDN and pointed at the document.
+const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>streaming-llm-api</title>
+<link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+<script>SwaggerUIBundle({url: "/openapi.json", dom_id: "#swagger-ui"});</script>
+</body>
+</html>
+"##;
+
+fn reference(name: &str) -> Value {
+    json!({"$ref": format!("#/components/schemas/{}", name)})
+}
+
+fn array(items: Value) -> Value {
+    json!({"type": "array", "items": items})
+}
+
+fn nullable(schema: Value) -> Value {
+    json!({"anyOf": [schema, {"type": "null"}]})
+}
+
+fn string_enum(values: &[&str]) -> Value {
+    json!({"type": "string", "enum": values})
+}
+
+/// A request object; unknown fields are accepted.
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({"type": "object", "required": required, "properties": properties})
+}
+
+/// A reply object: the mock sends these fields and no others.
+fn closed(required: &[&str], properties: Value) -> Value {
+    json!({"type": "object", "required": required, "properties": properties, "additionalProperties": false})
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({"required": true, "content": {"application/json": {"schema": schema}}})
+}
+
+fn reply(description: &str, media_type: &str, schema: Value) -> Value {
+    json!({"description": description, "content": {media_type: {"schema": schema}}})
+}
+
+#[cfg(any(feature = "endpoints", feature = "admin", feature = "internal-debug"))]
+fn query(name: &str, description: &str, schema: Value) -> Value {
+    json!({"name": name, "in": "query", "description": description, "schema": schema})
+}
+
+#[cfg(feature = "endpoints")]
+fn path_param(name: &str) -> Value {
+    json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}})
+}
+
+/// Errors in the OpenAI format, as every route but Cohere's and Gemini's sends.
+#[cfg(any(feature = "endpoints", feature = "tokenizer", feature = "admin", feature = "internal-debug"))]
+fn errors() -> Value {
+    json!({"$ref": "#/components/responses/Error"})
+}
+
+/// Operations under the admin token rather than an API key.
+#[cfg(any(feature = "admin", feature = "internal-debug"))]
+fn admin(mut operation: Value) -> Value {
+    operation["tags"] = json!(["Admin"]);
+    operation["security"] = json!([{"adminToken": []}]);
+    operation
+}
+
+fn add(paths: &mut Map<String, Value>, path: &str, method: &str, operation: Value) {
+    let item = paths.entry(path).or_insert_with(|| json!({}));
+    item[method] = operation;
+}
+
+fn paths() -> Map<String, Value> {
+    let mut paths = Map::new();
+    add(&mut paths, "/v1/chat/completions", "post", json!({
+        "operationId": "createChatCompletion",
+        "tags": ["OpenAI"],
+        "summary": "Create a chat completion",
+        "description": "`stream: true` streams chunks, ending with `data: [DONE]`. With `stream` left out, \
+            the first of `text/event-stream` and `application/json` in `Accept` decides; `stream: false` is \
+            refused. A stream cut short by an injected error ends with a `StreamError` event. Rules with \
+            `error_style = \"anthropic\"` send Anthropic's error body instead.",
+        "requestBody": json_body(json!({"oneOf": [reference("ChatCompletionRequest"), reference("LegacyRequest")]})),
+        "responses": {
+            "200": {
+                "description": "The reply, streamed or whole.",
+                "content": {
+                    "text/event-stream": {"schema": {"oneOf": [reference("ChatCompletionChunk"), reference("StreamError")]}},
+                    "application/json": {"schema": reference("ChatCompletion")},
+                },
+            },
+            "default": {
+                "description": "An error, in the rule's `error_style`.",
+                "content": {"application/json": {"schema": {"oneOf": [reference("ErrorEnvelope"), reference("AnthropicErrorEnvelope")]}}},
+            },
+        },
+    }));
+    #[cfg(feature = "endpoints")]
+    endpoint_paths(&mut paths);
+    #[cfg(feature = "tokenizer")]
+    tokenizer_paths(&mut paths);
+    #[cfg(feature = "recording")]
+    recording_paths(&mut paths);
+    #[cfg(feature = "admin")]
+    admin_paths(&mut paths);
+    #[cfg(all(feature = "admin", feature = "recording"))]
+    admin_recording_paths(&mut paths);
+    #[cfg(feature = "internal-debug")]
+    add(&mut paths, "/debug/pprof/profile", "get", admin(json!({
+        "operationId": "profileCpu",
+        "summary": "Profile the server's CPU",
+        "parameters": [
+            query("seconds", "How long to sample; 10 by default.", json!({"type": "integer", "minimum": 1})),
+            query("format", "`flamegraph` (SVG) by default, or pprof `protobuf`.", string_enum(&["flamegraph", "protobuf"])),
+        ],
+        "responses": {
+            "200": {
+                "description": "The profile.",
+                "content": {
+                    "image/svg+xml": {"schema": {"type": "string"}},
+                    "application/octet-stream": {"schema": {"type": "string", "contentEncoding": "binary"}},
+                },
+            },
+            "default": errors(),
+        },
+    })));
+    add(&mut paths, "/openapi.json", "get", json!({
+        "operationId": "getOpenApi",
+        "tags": ["Meta"],
+        "summary": "This document",
+        "responses": {"200": {"description": "OpenAPI 3.1.", "content": {"application/json": {"schema": {"type": "object"}}}}},
+    }));
+    add(&mut paths, "/docs", "get", json!({
+        "operationId": "getDocs",
+        "tags": ["Meta"],
+        "summary": "Swagger UI for this document",
+        "responses": {"200": {"description": "An HTML page.", "content": {"text/html": {"schema": {"type": "string"}}}}},
+    }));
+    paths
+}
+
+#[cfg(feature = "endpoints")]
+fn endpoint_paths(paths: &mut Map<String, Value>) {
+    add(paths, "/v1/chat/completions", "get", json!({
+        "operationId": "listChatCompletions",
+        "tags": ["OpenAI"],
+        "summary": "List stored completions",
+        "description": "Completions made with `store: true`. Filter on metadata with `metadata[key]=value`.",
+        "parameters": [
+            query("after", "The id of the last completion of the previous page.", json!({"type": "string"})),
+            query("limit", "20 by default.", json!({"type": "integer", "minimum": 1, "maximum": 100})),
+            query("order", "`asc` by default.", string_enum(&["asc", "desc"])),
+            query("model", "Only completions for this model.", json!({"type": "string"})),
+        ],
+        "responses": {
+            "200": reply("A page of completions.", "application/json", reference("ChatCompletionList")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/chat/completions/{completion_id}", "get", json!({
+        "operationId": "getChatCompletion",
+        "tags": ["OpenAI"],
+        "summary": "Retrieve a stored completion",
+        "parameters": [path_param("completion_id")],
+        "responses": {
+            "200": reply("The completion.", "application/json", reference("ChatCompletion")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/chat/completions/{completion_id}/messages", "get", json!({
+        "operationId": "getChatCompletionMessages",
+        "tags": ["OpenAI"],
+        "summary": "List a stored completion's request messages",
+        "parameters": [
+            path_param("completion_id"),
+            query("after", "The id of the last message of the previous page.", json!({"type": "string"})),
+            query("limit", "20 by default.", json!({"type": "integer", "minimum": 1, "maximum": 100})),
+            query("order", "`asc` by default.", string_enum(&["asc", "desc"])),
+        ],
+        "responses": {
+            "200": reply("A page of messages.", "application/json", reference("StoredMessageList")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/models", "get", json!({
+        "operationId": "listModels",
+        "tags": ["OpenAI"],
+        "summary": "List models",
+        "responses": {
+            "200": reply("The models the key may use.", "application/json", reference("ModelList")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/models/{model}", "get", json!({
+        "operationId": "retrieveModel",
+        "tags": ["OpenAI"],
+        "summary": "Retrieve a model",
+        "description": "The id may contain slashes, as fine-tuned and namespaced ids do.",
+        "parameters": [path_param("model")],
+        "responses": {
+            "200": reply("The model.", "application/json", reference("Model")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/embeddings", "post", json!({
+        "operationId": "createEmbedding",
+        "tags": ["OpenAI"],
+        "summary": "Create embeddings",
+        "description": "Unit vectors derived from each input's hash: the same input always gets the same vector, \
+            but similar inputs do not get similar vectors.",
+        "requestBody": json_body(reference("EmbeddingRequest")),
+        "responses": {
+            "200": reply("One vector per input, in input order.", "application/json", reference("EmbeddingList")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/responses", "post", json!({
+        "operationId": "createResponse",
+        "tags": ["OpenAI"],
+        "summary": "Create a response",
+        "description": "With `stream: true` each event is named after its `type`.",
+        "requestBody": json_body(reference("ResponsesRequest")),
+        "responses": {
+            "200": {
+                "description": "The response, streamed or whole.",
+                "content": {
+                    "text/event-stream": {"schema": reference("ResponseStreamEvent")},
+                    "application/json": {"schema": reference("Response")},
+                },
+            },
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/realtime", "get", json!({
+        "operationId": "createRealtimeSession",
+        "tags": ["OpenAI"],
+        "summary": "Open a realtime transcription session",
+        "description": "A WebSocket. The client sends `RealtimeClientEvent`s and the server `RealtimeServerEvent`s, \
+            one JSON text frame each.",
+        "parameters": [
+            query("intent", "Only `transcription` is served.", string_enum(&["transcription"])),
+        ],
+        "responses": {
+            "101": {"description": "Switched to the WebSocket protocol."},
+            "426": reply("The request was not a WebSocket upgrade.", "application/json", reference("ErrorEnvelope")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/chat", "post", json!({
+        "operationId": "cohereChat",
+        "tags": ["Cohere"],
+        "summary": "Stream a Cohere-style chat reply",
+        "description": "Only `stream: true` is served; each line is one event.",
+        "requestBody": json_body(reference("CohereChatRequest")),
+        "responses": {
+            "200": reply("The reply, as newline-delimited events.", "application/stream+json", reference("CohereStreamEvent")),
+            "default": reply("An error.", "application/json", reference("CohereError")),
+        },
+    }));
+    add(paths, "/v1beta/models/{model}:streamGenerateContent", "post", json!({
+        "operationId": "geminiStreamGenerateContent",
+        "tags": ["Gemini"],
+        "summary": "Stream a Gemini-style reply",
+        "parameters": [
+            path_param("model"),
+            query("alt", "`sse` streams `data:` events; otherwise one JSON array is streamed.", string_enum(&["sse"])),
+        ],
+        "requestBody": json_body(reference("GeminiRequest")),
+        "responses": {
+            "200": {
+                "description": "The reply, in pieces.",
+                "content": {
+                    "text/event-stream": {"schema": reference("GeminiResponse")},
+                    "application/json": {"schema": array(reference("GeminiResponse"))},
+                },
+            },
+            "default": reply("An error.", "application/json", reference("GeminiErrorEnvelope")),
+        },
+    }));
+    add(paths, "/v1/internal/stats", "get", json!({
+        "operationId": "getStats",
+        "tags": ["Internal"],
+        "summary": "Request counters and process gauges",
+        "responses": {"200": reply("The counters.", "application/json", reference("InternalStats"))},
+    }));
+    add(paths, "/v1/internal/metrics", "get", json!({
+        "operationId": "getMetrics",
+        "tags": ["Internal"],
+        "summary": "The counters, gauges and pacing histograms, for Prometheus",
+        "responses": {"200": reply("Prometheus text exposition.", "text/plain", json!({"type": "string"}))},
+    }));
+    add(paths, "/v1/internal/diff", "post", json!({
+        "operationId": "diffCompletions",
+        "tags": ["Internal"],
+        "summary": "Compare two replies",
+        "description": "Each side is a `chat.completion`, a list of chunks or an SSE body as a string.",
+        "requestBody": json_body(reference("DiffRequest")),
+        "responses": {
+            "200": reply("How the replies differ.", "application/json", reference("Diff")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/internal/events", "get", json!({
+        "operationId": "streamEvents",
+        "tags": ["Internal"],
+        "summary": "Follow request lifecycle events",
+        "description": "Each event is named after its `type`. The stream never ends.",
+        "responses": {"200": reply("The events.", "text/event-stream", reference("LifecycleEvent"))},
+    }));
+}
+
+#[cfg(feature = "tokenizer")]
+fn tokenizer_paths(paths: &mut Map<String, Value>) {
+    add(paths, "/v1/internal/tokenize", "post", json!({
+        "operationId": "tokenize",
+        "tags": ["Internal"],
+        "summary": "Count and encode tokens",
+        "requestBody": json_body(reference("TokenizeRequest")),
+        "responses": {
+            "200": reply("The tokens.", "application/json", reference("TokenizeResponse")),
+            "default": errors(),
+        },
+    }));
+    add(paths, "/v1/internal/detokenize", "post", json!({
+        "operationId": "detokenize",
+        "tags": ["Internal"],
+        "summary": "Decode tokens",
+        "requestBody": json_body(reference("DetokenizeRequest")),
+        "responses": {
+            "200": reply("The text.", "application/json", reference("DetokenizeResponse")),
+            "default": errors(),
+        },
+    }));
+}
+
+#[cfg(feature = "recording")]
+fn recording_paths(paths: &mut Map<String, Value>) {
+    add(paths, "/v1/internal/captures", "get", json!({
+        "operationId": "listCaptures",
+        "tags": ["Internal"],
+        "summary": "List captured requests and their replies",
+        "responses": {"200": reply("The captures, oldest first.", "application/json", reference("CaptureList"))},
+    }));
+    add(paths, "/v1/internal/captures", "delete", json!({
+        "operationId": "clearCaptures",
+        "tags": ["Internal"],
+        "summary": "Forget every capture",
+        "responses": {"204": {"description": "Cleared."}},
+    }));
+    add(paths, "/v1/internal/shadow", "get", json!({
+        "operationId": "listShadowDiffs",
+        "tags": ["Internal"],
+        "summary": "List how shadowed requests' replies differed",
+        "responses": {"200": reply("The diffs, oldest first.", "application/json", reference("ShadowList"))},
+    }));
+    add(paths, "/v1/internal/shadow", "delete", json!({
+        "operationId": "clearShadowDiffs",
+        "tags": ["Internal"],
+        "summary": "Forget every shadow diff",
+        "responses": {"204": {"description": "Cleared."}},
+    }));
+}
+
+#[cfg(feature = "admin")]
+fn admin_paths(paths: &mut Map<String, Value>) {
+    add(paths, "/v1/admin/storm", "post", admin(json!({
+        "operationId": "startStorm",
+        "summary": "Start a 429 storm",
+        "requestBody": json_body(reference("StormSpec")),
+        "responses": {
+            "200": reply("The storm.", "application/json", reference("StormStatus")),
+            "default": errors(),
+        },
+    })));
+    add(paths, "/v1/admin/storm", "get", admin(json!({
+        "operationId": "getStorm",
+        "summary": "The current storm",
+        "responses": {
+            "200": reply("The storm, if any.", "application/json", reference("StormStatus")),
+            "default": errors(),
+        },
+    })));
+    add(paths, "/v1/admin/storm", "delete", admin(json!({
+        "operationId": "stopStorm",
+        "summary": "End the storm",
+        "responses": {
+            "200": reply("The storm as it ended.", "application/json", reference("StormStatus")),
+            "default": errors(),
+        },
+    })));
+    add(paths, "/v1/admin/audit", "get", admin(json!({
+        "operationId": "listAudit",
+        "summary": "Read the audit log",
+        "parameters": [
+            query("after", "Only records with a larger `seq`.", json!({"type": "integer", "minimum": 0})),
+            query("action", "Only records of this action.", json!({"type": "string"})),
+        ],
+        "responses": {
+            "200": reply("The records and whether the chain verifies.", "application/json", reference("AuditList")),
+            "default": errors(),
+        },
+    })));
+    add(paths, "/v1/admin/replay", "delete", admin(json!({
+        "operationId": "clearReplay",
+        "summary": "Forget every loaded recording",
+        "responses": {
+            "200": reply("What is left.", "application/json", reference("ReplayStatus")),
+            "default": errors(),
+        },
+    })));
+}
+
+#[cfg(all(feature = "admin", feature = "recording"))]
+fn admin_recording_paths(paths: &mut Map<String, Value>) {
+    add(paths, "/v1/admin/captures/export", "get", admin(json!({
+        "operationId": "exportCaptures",
+        "summary": "Export the captures",
+        "parameters": [
+            query("format", "`finetune` by default.", string_enum(&["finetune", "har"])),
+        ],
+        "responses": {
+            "200": {
+                "description": "Fine-tuning JSONL, or an HTTP Archive.",
+                "content": {
+                    "application/jsonl": {"schema": {"type": "string"}},
+                    "application/json": {"schema": {"type": "object"}},
+                },
+            },
+            "default": errors(),
+        },
+    })));
+    add(paths, "/v1/admin/replay", "post", admin(json!({
+        "operationId": "loadReplay",
+        "summary": "Load recordings from a HAR",
+        "requestBody": json_body(json!({"type": "object", "description": "HTTP Archive 1.2."})),
+        "responses": {
+            "200": reply("The recordings loaded.", "application/json", reference("ReplayStatus")),
+            "default": errors(),
+        },
+    })));
+}
+
+fn openai_schemas(schemas: &mut Map<String, Value>) {
+    let integer = || json!({"type": "integer", "minimum": 0});
+    let string = || json!({"type": "string"});
+    let metadata = || json!({"type": "object", "additionalProperties": {"type": "string"}});
+    schemas.insert("ChatCompletionRequest".into(), object(&["messages"], json!({
+        "model": string(),
+        "messages": array(reference("Message")),
+        "stream": {"type": "boolean"},
+        "stream_options": reference("StreamOptions"),
+        "seed": integer(),
+        "max_tokens": integer(),
+        "max_completion_tokens": integer(),
+        "temperature": {"type": "number"},
+        "top_p": {"type": "number"},
+        "stop": {"anyOf": [string(), array(string())]},
+        "tools": array(json!({"type": "object"})),
+        "tool_choice": {"anyOf": [string(), {"type": "object"}]},
+        "modalities": array(string_enum(&["text", "audio"])),
+        "audio": reference("AudioParams"),
+        "prediction": reference("Prediction"),
+        "service_tier": string_enum(&["auto", "default", "flex", "priority"]),
+        "store": {"type": "boolean"},
+        "metadata": metadata(),
+    })));
+    schemas.insert("LegacyRequest".into(), object(&["prompt", "stream"], json!({
+        "prompt": string(),
+        "stream": {"type": "boolean"},
+    })));
+    schemas.insert("StreamOptions".into(), object(&[], json!({"include_usage": {"type": "boolean"}})));
+    schemas.insert("Message".into(), object(&["role"], json!({
+        "role": string(),
+        "content": nullable(reference("MessageContent")),
+        "name": string(),
+        "tool_calls": array(reference("ToolCall")),
+        "tool_call_id": string(),
+    })));
+    schemas.insert("MessageContent".into(), json!({"anyOf": [string(), array(reference("ContentPart"))]}));
+    schemas.insert("ContentPart".into(), object(&["type"], json!({
+        "type": string(),
+        "text": string(),
+        "image_url": reference("ImageUrl"),
+    })));
+    schemas.insert("ImageUrl".into(), object(&["url"], json!({
+        "url": string(),
+        "detail": string_enum(&["low", "high", "auto"]),
+    })));
+    schemas.insert("ToolCall".into(), object(&["id", "type", "function"], json!({
+        "id": string(),
+        "type": string(),
+        "function": reference("FunctionCall"),
+    })));
+    schemas.insert("FunctionCall".into(), object(&["name", "arguments"], json!({
+        "name": string(),
+        "arguments": {"type": "string", "description": "JSON, as a string."},
+    })));
+    schemas.insert("AudioParams".into(), object(&["voice", "format"], json!({
+        "voice": string(),
+        "format": string_enum(&["wav", "mp3", "flac", "opus", "pcm16"]),
+    })));
+    schemas.insert("Prediction".into(), object(&["type", "content"], json!({
+        "type": string_enum(&["content"]),
+        "content": reference("MessageContent"),
+    })));
+    schemas.insert("ChatCompletionChunk".into(), closed(&["choices"], json!({
+        "id": string(),
+        "object": string_enum(&["chat.completion.chunk"]),
+        "created": integer(),
+        "model": string(),
+        "system_fingerprint": string(),
+        "choices": array(reference("ChunkChoice")),
+        "usage": reference("Usage"),
+        "service_tier": string(),
+        "x_mock": {"type": "object", "description": "The mock's own diagnostics."},
+    })));
+    schemas.insert("ChunkChoice".into(), closed(&["delta"], json!({
+        "index": integer(),
+        "delta": reference("Delta"),
+        "finish_reason": nullable(string()),
+    })));
+    schemas.insert("Delta".into(), closed(&[], json!({
+        "role": string(),
+        "content": nullable(string()),
+        "refusal": nullable(string()),
+        "text": {"type": "string", "description": "The reply so far, with the cumulative content mode."},
+        "tool_calls": array(reference("ToolCallDelta")),
+        "annotations": array(reference("Annotation")),
+        "audio": reference("AudioDelta"),
+    })));
+    schemas.insert("ToolCallDelta".into(), closed(&["index"], json!({
+        "index": integer(),
+        "id": string(),
+        "type": string(),
+        "function": reference("FunctionDelta"),
+    })));
+    schemas.insert("FunctionDelta".into(), closed(&[], json!({
+        "name": string(),
+        "arguments": string(),
+    })));
+    schemas.insert("Annotation".into(), closed(&["type", "url_citation"], json!({
+        "type": string_enum(&["url_citation"]),
+        "url_citation": reference("UrlCitation"),
+    })));
+    schemas.insert("UrlCitation".into(), closed(&["start_index", "end_index", "url", "title"], json!({
+        "start_index": integer(),
+        "end_index": integer(),
+        "url": string(),
+        "title": string(),
+    })));
+    schemas.insert("AudioDelta".into(), closed(&[], json!({
+        "id": string(),
+        "data": {"type": "string", "description": "Base64 PCM."},
+        "transcript": string(),
+        "expires_at": integer(),
+    })));
+    schemas.insert("Usage".into(), closed(&["prompt_tokens", "completion_tokens", "total_tokens"], json!({
+        "prompt_tokens": integer(),
+        "completion_tokens": integer(),
+        "total_tokens": integer(),
+        "completion_tokens_details": reference("CompletionTokensDetails"),
+    })));
+    schemas.insert("CompletionTokensDetails".into(), closed(&["accepted_prediction_tokens", "rejected_prediction_tokens"], json!({
+        "accepted_prediction_tokens": integer(),
+        "rejected_prediction_tokens": integer(),
+    })));
+    schemas.insert("ChatCompletion".into(), closed(&["id", "object", "created", "model", "choices", "usage"], json!({
+        "id": string(),
+        "object": string_enum(&["chat.completion"]),
+        "created": integer(),
+        "model": nullable(string()),
+        "choices": array(reference("CompletionChoice")),
+        "usage": reference("Usage"),
+        "metadata": metadata(),
+        "service_tier": string(),
+        "x_mock": {"type": "object", "description": "The mock's own diagnostics."},
+    })));
+    schemas.insert("CompletionChoice".into(), closed(&["index", "message", "finish_reason"], json!({
+        "index": integer(),
+        "message": reference("CompletionMessage"),
+        "finish_reason": string(),
+    })));
+    schemas.insert("CompletionMessage".into(), closed(&["role", "content"], json!({
+        "role": string_enum(&["assistant"]),
+        "content": nullable(string()),
+        "annotations": array(reference("Annotation")),
+        "audio": reference("AudioMessage"),
+    })));
+    schemas.insert("AudioMessage".into(), closed(&["id", "data", "expires_at", "transcript"], json!({
+        "id": string(),
+        "data": string(),
+        "expires_at": integer(),
+        "transcript": string(),
+    })));
+    for (name, item) in [("ChatCompletionList", "ChatCompletion"), ("StoredMessageList", "StoredMessage")] {
+        schemas.insert(name.into(), closed(&["object", "data", "first_id", "last_id", "has_more"], json!({
+            "object": string_enum(&["list"]),
+            "data": array(reference(item)),
+            "first_id": nullable(string()),
+            "last_id": nullable(string()),
+            "has_more": {"type": "boolean"},
+        })));
+    }
+    schemas.insert("StoredMessage".into(), closed(&["id", "role", "content"], json!({
+        "id": string(),
+        "role": string(),
+        "content": nullable(reference("MessageContent")),
+        "name": string(),
+    })));
+    schemas.insert("Model".into(), closed(&["id", "object", "created", "owned_by", "context_window", "encoding"], json!({
+        "id": string(),
+        "object": string_enum(&["model"]),
+        "created": integer(),
+        "owned_by": string(),
+        "context_window": integer(),
+        "encoding": string_enum(&["cl100k_base", "o200k_base"]),
+        "pricing": closed(&["input_per_million", "output_per_million"], json!({
+            "input_per_million": {"type": "number"},
+            "output_per_million": {"type": "number"},
+        })),
+    })));
+    schemas.insert("ModelList".into(), closed(&["object", "data"], json!({
+        "object": string_enum(&["list"]),
+        "data": array(reference("Model")),
+    })));
+    schemas.insert("EmbeddingRequest".into(), object(&["input"], json!({
+        "model": string(),
+        "input": {"anyOf": [string(), array(string())]},
+        "dimensions": integer(),
+        "encoding_format": string_enum(&["float", "base64"]),
+    })));
+    schemas.insert("EmbeddingList".into(), closed(&["object", "data", "model", "usage"], json!({
+        "object": string_enum(&["list"]),
+        "data": array(closed(&["object", "index", "embedding"], json!({
+            "object": string_enum(&["embedding"]),
+            "index": integer(),
+            "embedding": {"anyOf": [array(json!({"type": "number"})), string()]},
+        }))),
+        "model": string(),
+        "usage": closed(&["prompt_tokens", "total_tokens"], json!({
+            "prompt_tokens": integer(),
+            "total_tokens": integer(),
+        })),
+    })));
+    schemas.insert("ErrorEnvelope".into(), closed(&["error"], json!({"error": reference("ErrorBody")})));
+    schemas.insert("ErrorBody".into(), closed(&["message", "type", "param", "code"], json!({
+        "message": string(),
+        "type": string(),
+        "param": nullable(string()),
+        "code": nullable(string()),
+    })));
+    schemas.insert("StreamError".into(), closed(&["error", "code"], json!({
+        "error": string(),
+        "code": {"type": "integer"},
+    })));
+    schemas.insert("AnthropicErrorEnvelope".into(), closed(&["type", "error"], json!({
+        "type": string_enum(&["error"]),
+        "error": closed(&["type", "message"], json!({"type": string(), "message": string()})),
+    })));
+}
+
+fn responses_schemas(schemas: &mut Map<String, Value>) {
+    let integer = || json!({"type": "integer", "minimum": 0});
+    let string = || json!({"type": "string"});
+    schemas.insert("ResponsesRequest".into(), object(&["input"], json!({
+        "model": string(),
+        "input": {"anyOf": [string(), array(reference("ResponsesInputItem"))]},
+        "instructions": string(),
+        "stream": {"type": "boolean"},
+        "max_output_tokens": integer(),
+        "tools": array(json!({"type": "object"})),
+    })));
+    schemas.insert("ResponsesInputItem".into(), object(&[], json!({
+        "type": string(),
+        "role": string(),
+        "content": {"anyOf": [string(), array(reference("ResponsesInputPart"))]},
+    })));
+    schemas.insert("ResponsesInputPart".into(), object(&["type"], json!({
+        "type": string(),
+        "text": string(),
+    })));
+    schemas.insert("Response".into(), closed(&["id", "object", "created_at", "status", "model", "output", "usage", "error", "incomplete_details"], json!({
+        "id": string(),
+        "object": string_enum(&["response"]),
+        "created_at": integer(),
+        "status": string_enum(&["in_progress", "completed", "incomplete", "failed"]),
+        "model": nullable(string()),
+        "output": array(reference("ResponseOutputMessage")),
+        "usage": nullable(closed(&["input_tokens", "output_tokens", "total_tokens"], json!({
+            "input_tokens": integer(),
+            "output_tokens": integer(),
+            "total_tokens": integer(),
+        }))),
+        "error": nullable(closed(&["code", "message"], json!({"code": string(), "message": string()}))),
+        "incomplete_details": nullable(closed(&["reason"], json!({
+            "reason": string_enum(&["max_output_tokens", "content_filter"]),
+        }))),
+    })));
+    schemas.insert("ResponseOutputMessage".into(), closed(&["id", "type", "status", "role", "content"], json!({
+        "id": string(),
+        "type": string_enum(&["message"]),
+        "status": string(),
+        "role": string_enum(&["assistant"]),
+        "content": array(reference("ResponseOutputText")),
+    })));
+    schemas.insert("ResponseOutputText".into(), closed(&["type", "text", "annotations"], json!({
+        "type": string_enum(&["output_text"]),
+        "text": string(),
+        "annotations": array(json!({"type": "object"})),
+    })));
+    schemas.insert("ResponseStreamEvent".into(), closed(&["type", "sequence_number"], json!({
+        "type": string_enum(&[
+            "response.created",
+            "response.in_progress",
+            "response.output_item.added",
+            "response.content_part.added",
+            "response.output_text.delta",
+            "response.output_text.done",
+            "response.content_part.done",
+            "response.output_item.done",
+            "response.completed",
+            "response.incomplete",
+            "response.failed",
+        ]),
+        "sequence_number": integer(),
+        "response": reference("Response"),
+        "output_index": integer(),
+        "content_index": integer(),
+        "item_id": string(),
+        "item": reference("ResponseOutputMessage"),
+        "part": reference("ResponseOutputText"),
+        "delta": string(),
+        "text": string(),
+    })));
+}
+
+fn provider_schemas(schemas: &mut Map<String, Value>) {
+    let integer = || json!({"type": "integer", "minimum": 0});
+    let string = || json!({"type": "string"});
+    schemas.insert("CohereChatRequest".into(), object(&["message"], json!({
+        "message": string(),
+        "chat_history": array(reference("CohereChatMessage")),
+        "preamble": string(),
+        "model": string(),
+        "stream": {"type": "boolean"},
+        "seed": integer(),
+        "max_tokens": integer(),
+    })));
+    schemas.insert("CohereChatMessage".into(), closed(&["role", "message"], json!({
+        "role": string_enum(&["USER", "CHATBOT", "SYSTEM"]),
+        "message": string(),
+    })));
+    schemas.insert("CohereStreamEvent".into(), closed(&["event_type", "is_finished"], json!({
+        "event_type": string_enum(&["stream-start", "text-generation", "stream-end"]),
+        "is_finished": {"type": "boolean"},
+        "generation_id": string(),
+        "text": string(),
+        "finish_reason": string_enum(&["COMPLETE", "MAX_TOKENS", "ERROR"]),
+        "response": reference("CohereChatResponse"),
+    })));
+    schemas.insert("CohereChatResponse".into(), closed(&["response_id", "generation_id", "text", "finish_reason", "chat_history", "meta"], json!({
+        "response_id": string(),
+        "generation_id": string(),
+        "text": string(),
+        "finish_reason": string(),
+        "chat_history": array(reference("CohereChatMessage")),
+        "meta": closed(&["billed_units"], json!({
+            "billed_units": closed(&["input_tokens", "output_tokens"], json!({
+                "input_tokens": integer(),
+                "output_tokens": integer(),
+            })),
+        })),
+    })));
+    schemas.insert("CohereError".into(), closed(&["message"], json!({"message": string()})));
+    schemas.insert("GeminiRequest".into(), object(&["contents"], json!({
+        "contents": array(reference("GeminiContent")),
+        "systemInstruction": reference("GeminiContent"),
+        "generationConfig": object(&[], json!({
+            "maxOutputTokens": integer(),
+            "seed": integer(),
+            "temperature": {"type": "number"},
+        })),
+        "tools": array(json!({"type": "object"})),
+    })));
+    schemas.insert("GeminiContent".into(), object(&["parts"], json!({
+        "role": string_enum(&["user", "model"]),
+        "parts": array(object(&[], json!({
+            "text": string(),
+            "inlineData": object(&["mimeType", "data"], json!({"mimeType": string(), "data": string()})),
+        }))),
+    })));
+    schemas.insert("GeminiResponse".into(), closed(&["candidates"], json!({
+        "candidates": array(closed(&["content", "index"], json!({
+            "content": reference("GeminiContent"),
+            "finishReason": string_enum(&["STOP", "MAX_TOKENS"]),
+            "index": integer(),
+        }))),
+        "usageMetadata": closed(&["promptTokenCount", "candidatesTokenCount", "totalTokenCount"], json!({
+            "promptTokenCount": integer(),
+            "candidatesTokenCount": integer(),
+            "totalTokenCount": integer(),
+        })),
+        "modelVersion": string(),
+        "responseId": string(),
+    })));
+    schemas.insert("GeminiErrorEnvelope".into(), closed(&["error"], json!({
+        "error": closed(&["code", "message", "status"], json!({
+            "code": {"type": "integer"},
+            "message": string(),
+            "status": string(),
+        })),
+    })));
+    schemas.insert("RealtimeClientEvent".into(), object(&["type"], json!({
+        "event_id": string(),
+        "type": string_enum(&[
+            "transcription_session.update",
+            "input_audio_buffer.append",
+            "input_audio_buffer.commit",
+            "input_audio_buffer.clear",
+        ]),
+        "session": object(&[], json!({
+            "input_audio_format": string_enum(&["pcm16", "g711_ulaw", "g711_alaw"]),
+            "input_audio_transcription": {"type": "object"},
+        })),
+        "audio": {"type": "string", "description": "Base64, in the session's `input_audio_format`."},
+    })));
+    schemas.insert("RealtimeServerEvent".into(), closed(&["event_id", "type"], json!({
+        "event_id": string(),
+        "type": string_enum(&[
+            "transcription_session.created",
+            "transcription_session.updated",
+            "input_audio_buffer.committed",
+            "input_audio_buffer.cleared",
+            "conversation.item.input_audio_transcription.delta",
+            "conversation.item.input_audio_transcription.completed",
+            "error",
+        ]),
+        "session": closed(&["id", "object", "input_audio_format", "input_audio_transcription", "turn_detection"], json!({
+            "id": string(),
+            "object": string(),
+            "input_audio_format": string(),
+            "input_audio_transcription": {"type": "object"},
+            "turn_detection": nullable(json!({"type": "object"})),
+        })),
+        "previous_item_id": nullable(string()),
+        "item_id": string(),
+        "content_index": integer(),
+        "delta": string(),
+        "transcript": string(),
+        "error": closed(&["type", "code", "message", "event_id"], json!({
+            "type": string(),
+            "code": string(),
+            "message": string(),
+            "event_id": nullable(string()),
+        })),
+    })));
+}
+
+/// The internal and admin routes' bodies, whose nested reports are left open.
+fn internal_schemas(schemas: &mut Map<String, Value>) {
+    let integer = || json!({"type": "integer", "minimum": 0});
+    let number = || json!({"type": "number"});
+    let string = || json!({"type": "string"});
+    let report = || json!({"type": "object"});
+    let encoding = || string_enum(&["cl100k_base", "o200k_base"]);
+    schemas.insert("InternalStats".into(), closed(&["requests", "buffer_pool", "process"], json!({
+        "requests": report(),
+        "buffer_pool": report(),
+        "process": report(),
+    })));
+    schemas.insert("DiffRequest".into(), object(&["a", "b"], json!({"a": {}, "b": {}})));
+    schemas.insert("Diff".into(), closed(&["identical", "similarity", "tokens", "semantic"], json!({
+        "identical": {"type": "boolean"},
+        "diverges_at": integer(),
+        "similarity": number(),
+        "tokens": report(),
+        "semantic": report(),
+    })));
+    schemas.insert("LifecycleEvent".into(), closed(&["type", "request_id", "model", "timestamp_ms", "duration_ms", "usage"], json!({
+        "type": string_enum(&["stream.started", "stream.completed", "stream.error", "quota.threshold"]),
+        "request_id": string(),
+        "model": nullable(string()),
+        "organization": string(),
+        "project": string(),
+        "timestamp_ms": integer(),
+        "duration_ms": integer(),
+        "usage": reference("Usage"),
+        "error": string(),
+        "quota": report(),
+    })));
+    schemas.insert("TokenizeRequest".into(), object(&[], json!({
+        "model": string(),
+        "encoding": encoding(),
+        "text": string(),
+        "messages": array(reference("Message")),
+    })));
+    schemas.insert("TokenizeResponse".into(), closed(&["encoding", "count"], json!({
+        "encoding": encoding(),
+        "count": integer(),
+        "tokens": array(integer()),
+    })));
+    schemas.insert("DetokenizeRequest".into(), object(&["tokens"], json!({
+        "model": string(),
+        "encoding": encoding(),
+        "tokens": array(integer()),
+    })));
+    schemas.insert("DetokenizeResponse".into(), closed(&["encoding", "text"], json!({
+        "encoding": encoding(),
+        "text": string(),
+    })));
+    schemas.insert("CaptureList".into(), closed(&["data"], json!({"data": array(report())})));
+    schemas.insert("ShadowList".into(), closed(&["data"], json!({"data": array(report())})));
+    schemas.insert("StormSpec".into(), object(&["probability", "duration_secs"], json!({
+        "probability": {"type": "number", "minimum": 0, "maximum": 1},
+        "duration_secs": number(),
+        "curve": string_enum(&["constant", "linear", "exponential"]),
+        "retry_after_secs": nullable(number()),
+        "seed": nullable(integer()),
+    })));
+    schemas.insert("StormStatus".into(), closed(&["active", "probability", "remaining_secs", "spec", "rejected"], json!({
+        "active": {"type": "boolean"},
+        "probability": number(),
+        "remaining_secs": number(),
+        "spec": nullable(reference("StormSpec")),
+        "rejected": integer(),
+    })));
+    schemas.insert("AuditList".into(), closed(&["data", "valid"], json!({
+        "data": array(report()),
+        "valid": {"type": "boolean", "description": "Whether the whole chain verifies."},
+        "broken_at": integer(),
+    })));
+    schemas.insert("ReplayStatus".into(), closed(&["loaded", "recordings"], json!({
+        "loaded": integer(),
+        "recordings": integer(),
+    })));
+}
+
+/// The document for the routes this build registers.
+pub fn document() -> Value {
+    let mut schemas = Map::new();
+    openai_schemas(&mut schemas);
+    responses_schemas(&mut schemas);
+    provider_schemas(&mut schemas);
+    internal_schemas(&mut schemas);
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "streaming-llm-api",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "A streaming LLM mock. Pacing, framing and failures follow the server's config, \
+                so replies are valid against these schemas but their timing is not described here.",
+        },
+        "paths": paths(),
+        "components": {
+            "schemas": schemas,
+            "responses": {
+                "Error": reply("An error.", "application/json", reference("ErrorEnvelope")),
+            },
+            "securitySchemes": {
+                "apiKey": {"type": "http", "scheme": "bearer", "description": "Needed only when `[[keys]]` are configured."},
+                "adminToken": {"type": "http", "scheme": "bearer", "description": "`admin.token`."},
+            },
+        },
+        "security": [{}, {"apiKey": []}],
+    })
+}
+
+#[get("/openapi.json")]
+pub async fn document_endpoint() -> HttpResponse {
+    HttpResponse::Ok().json(document())
+}
+
+#[get("/docs")]
+pub async fn docs_endpoint() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(DOCS_PAGE)
+}
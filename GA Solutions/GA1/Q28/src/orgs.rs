@@ -0,0 +1,87 @@
+//! `OpenAI-Organization` and `OpenAI-Project` headers. The headers are
+//! checked against the key's own organization and project, and the projects
+//! `[[organizations]]` lists for that organization, and anything else is
+//! rejected with the provider's `mismatched_organization` /
+//! `mismatched_project` 401s. The resolved scope keys rate limits and is
+//! reported on lifecycle events, so only configured scopes ever key them.
+
+use actix_web::http::StatusCode;
+use actix_web::HttpRequest;
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::error::MockError;
+use crate::keys;
+
+/// An organization as listed in `[[organizations]]`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Organization {
+    pub id: String,
+    /// Project ids inside the organization.
+    #[serde(default)]
+    pub projects: Vec<String>,
+}
+
+/// The organization and project a request is made under.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrgScope {
+    pub organization: Option<String>,
+    pub project: Option<String>,
+}
+
+impl OrgScope {
+    /// Whom usage is charged to: the organization and project when known,
+    /// else the bearer token.
+    pub fn account<'a>(&'a self, token: &'a str) -> std::borrow::Cow<'a, str> {
+        match &self.organization {
+            Some(org) => format!("{}/{}", org, self.project.as_deref().unwrap_or_default()).into(),
+            None => token.into(),
+        }
+    }
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+/// The 401 OpenAI sends when `header` names something the key is not in.
+fn mismatched(header: &str, what: &str, code: &'static str) -> MockError {
+    MockError::rejected(StatusCode::UNAUTHORIZED, format!("{} header should match {} for API key", header, what), None)
+        .with_code(code)
+}
+
+/// Resolves and checks the request's scope. The headers default to the
+/// key's organization and project, and may only name those, or, for a key
+/// not pinned to a project, another project its organization lists.
+pub fn resolve(config: &Config, req: &HttpRequest) -> Result<OrgScope, MockError> {
+    let key = keys::bearer_token(req).and_then(|token| config.keys.iter().find(|k| k.key == token));
+    let own_org = key.and_then(|k| k.organization.as_ref());
+    let own_project = key.and_then(|k| k.project.as_ref());
+    let organization = match header(req, "OpenAI-Organization") {
+        Some(id) if own_org.is_some_and(|own| own == id) => Some(id.to_string()),
+        Some(_) => return Err(mismatched("OpenAI-Organization", "organization", "mismatched_organization")),
+        None => own_org.cloned(),
+    };
+    // Without an organization, the project's own is charged.
+    let org = match &organization {
+        Some(id) => config.organizations.iter().find(|o| &o.id == id),
+        None => own_project.and_then(|project| config.organizations.iter().find(|o| o.projects.contains(project))),
+    };
+    let project = match header(req, "OpenAI-Project") {
+        Some(project) if own_project.is_some_and(|own| own == project) => Some(project.to_string()),
+        Some(project) if own_project.is_none() && org.is_some_and(|o| o.projects.iter().any(|p| p == project)) => {
+            Some(project.to_string())
+        }
+        Some(_) => return Err(mismatched("OpenAI-Project", "project", "mismatched_project")),
+        None => own_project.cloned(),
+    };
+    Ok(OrgScope {
+        organization: organization.or_else(|| org.map(|o| o.id.clone())),
+        project,
+    })
+}
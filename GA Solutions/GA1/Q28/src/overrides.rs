@@ -0,0 +1,100 @@
+//! Per-request behavior overrides carried in `X-Mock-*` headers, so a single
+//! test case can shape its stream without touching the global config.
+//!
+//! | Header                 | Value                         | Overrides              |
+//! |------------------------|-------------------------------|------------------------|
+//! | `X-Mock-Latency`       | delay before each chunk, ms   | `chunk_delay_ms`       |
+//! | `X-Mock-Chunks`        | content chunk count, `>= 1`   | `chunks`, `chunk_chars`|
+//! | `X-Mock-Error-After`   | chunks before failing         | `error_after`          |
+//! | `X-Mock-Finish-Reason` | e.g. `stop`, `length`         | `finish_reason`        |
+//!
+//! Overrides apply on top of whichever stub rule matched the prompt.
+
+use actix_web::dev::Payload;
+use actix_web::http::header::HeaderMap;
+use actix_web::http::StatusCode;
+use actix_web::{FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+use std::str::FromStr;
+
+use crate::error::MockError;
+use crate::stubs::ResponseProfile;
+
+pub const LATENCY: &str = "X-Mock-Latency";
+pub const CHUNKS: &str = "X-Mock-Chunks";
+pub const ERROR_AFTER: &str = "X-Mock-Error-After";
+pub const FINISH_REASON: &str = "X-Mock-Finish-Reason";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockOverrides {
+    pub latency_ms: Option<u64>,
+    pub chunks: Option<usize>,
+    pub error_after: Option<usize>,
+    pub finish_reason: Option<String>,
+}
+
+impl MockOverrides {
+    /// Parses the `X-Mock-*` headers. A header that is present but malformed
+    /// is rejected rather than ignored, so a typo cannot silently fall back
+    /// to the configured behavior.
+    pub fn from_headers(headers: &HeaderMap) -> Result<MockOverrides, MockError> {
+        let chunks = parse(headers, CHUNKS)?;
+        if chunks == Some(0) {
+            return Err(invalid(CHUNKS, "must be at least 1"));
+        }
+        let finish_reason = match text(headers, FINISH_REASON)? {
+            Some("") => return Err(invalid(FINISH_REASON, "must not be empty")),
+            reason => reason.map(str::to_string),
+        };
+        Ok(MockOverrides {
+            latency_ms: parse(headers, LATENCY)?,
+            chunks,
+            error_after: parse(headers, ERROR_AFTER)?,
+            finish_reason,
+        })
+    }
+
+    pub fn apply(&self, profile: &mut ResponseProfile) {
+        if let Some(latency) = self.latency_ms {
+            profile.chunk_delay_ms = latency;
+            profile.tokens_per_sec = None;
+        }
+        if let Some(chunks) = self.chunks {
+            // An explicit count wins over a rule's fixed chunk size.
+            profile.chunks = chunks;
+            profile.chunk_chars = None;
+        }
+        if let Some(after) = self.error_after {
+            profile.error_after = Some(after);
+        }
+        if let Some(reason) = &self.finish_reason {
+            profile.finish_reason = Some(reason.clone());
+        }
+    }
+}
+
+impl FromRequest for MockOverrides {
+    type Error = MockError;
+    type Future = Ready<Result<MockOverrides, MockError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(MockOverrides::from_headers(req.headers()))
+    }
+}
+
+fn text<'a>(headers: &'a HeaderMap, name: &str) -> Result<Option<&'a str>, MockError> {
+    headers
+        .get(name)
+        .map(|v| v.to_str().map(str::trim).map_err(|_| invalid(name, "must be visible ASCII")))
+        .transpose()
+}
+
+fn parse<T: FromStr>(headers: &HeaderMap, name: &str) -> Result<Option<T>, MockError> {
+    text(headers, name)?
+        .map(|v| v.parse().map_err(|_| invalid(name, "must be a non-negative integer")))
+        .transpose()
+}
+
+fn invalid(name: &str, reason: &str) -> MockError {
+    MockError::rejected(StatusCode::BAD_REQUEST, format!("Invalid {} header: {}", name, reason), Some(name))
+}
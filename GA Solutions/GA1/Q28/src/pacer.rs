@@ -0,0 +1,243 @@
+//! Central pacing for chunk delays. Instead of one timer per stream, every
+//! stream on a worker parks in a shared timer wheel that a single task
+//! advances once per tick, so thousands of concurrent streams cost one timer
+//! wakeup per tick rather than one per chunk.
+//!
+//! Fairness: streams due in the same tick are released in the order they
+//! asked to sleep. With `pacing_max_per_tick` set, the remainder carry over
+//! and are released ahead of anything that falls due later, so a burst
+//! delays everyone a little instead of starving whoever registered last.
+//!
+//! `Pace` decides how long each chunk waits in the first place, from a
+//! response profile.
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+use crate::config::{PacingMode, StreamConfig};
+use crate::metrics;
+use crate::stubs::ResponseProfile;
+use crate::tokenizer::TokenEncoding;
+
+/// Wheel resolution. Delays are rounded up to a whole number of ticks.
+pub const TICK: Duration = Duration::from_millis(1);
+/// Slots in the wheel; longer delays wrap and wait out extra rotations.
+const SLOTS: usize = 1024;
+/// The longest a chunk waits under `tokens_per_sec`, however slow the rate.
+const MAX_TOKEN_DELAY: Duration = Duration::from_secs(3600);
+
+thread_local! {
+    // Streams never leave the worker that created them, so each worker gets its own wheel.
+    static WHEEL: RefCell<Wheel> = RefCell::new(Wheel::new());
+}
+
+/// Waits `delay` using the strategy selected by `config.pacing`.
+pub fn sleep(delay: Duration, config: &StreamConfig) -> LocalBoxFuture<'static, ()> {
+    match config.pacing {
+        PacingMode::Sleep => time::sleep(delay).boxed_local(),
+        PacingMode::Scheduler => park(delay, config.pacing_max_per_tick).boxed_local(),
+    }
+}
+
+/// Parks the caller in this worker's wheel until `delay` has elapsed and the
+/// per-tick budget lets it through.
+pub fn park(delay: Duration, max_per_tick: Option<usize>) -> Parked {
+    let waiter = Rc::new(Waiter::default());
+    let start_driver = WHEEL.with(|wheel| {
+        let mut wheel = wheel.borrow_mut();
+        wheel.max_per_tick = max_per_tick.map(|n| n.max(1));
+        wheel.insert(delay, waiter.clone());
+        !std::mem::replace(&mut wheel.driving, true)
+    });
+    if start_driver {
+        metrics::spawn(drive());
+    }
+    Parked { waiter }
+}
+
+/// Streams currently parked on this worker, for tests and stats.
+pub fn parked() -> usize {
+    WHEEL.with(|wheel| wheel.borrow().pending)
+}
+
+/// How long a profile's chunks take to generate. With `tokens_per_sec` a
+/// chunk waits for the tokens in it, so a delta of CJK or emoji, which
+/// takes several times the tokens of as many English characters, waits
+/// that much longer; otherwise every chunk waits `chunk_delay_ms`.
+#[derive(Clone, Copy)]
+pub struct Pace {
+    delay: Duration,
+    first_delay: Option<Duration>,
+    tokens_per_sec: Option<f64>,
+    chars_per_token: Option<f64>,
+    encoding: TokenEncoding,
+}
+
+impl Pace {
+    /// Pacing for `profile`, counting tokens with `encoding` unless the
+    /// profile sets `chars_per_token`.
+    pub fn new(profile: &ResponseProfile, encoding: TokenEncoding) -> Pace {
+        Pace {
+            delay: Duration::from_millis(profile.chunk_delay_ms),
+            first_delay: profile.first_chunk_delay_ms.map(Duration::from_millis),
+            tokens_per_sec: profile.tokens_per_sec.filter(|rate| *rate > 0.0),
+            chars_per_token: profile.chars_per_token.filter(|ratio| *ratio > 0.0),
+            encoding,
+        }
+    }
+
+    /// Tokens `chunk` stands for.
+    pub fn tokens(&self, chunk: &str) -> usize {
+        match self.chars_per_token {
+            Some(ratio) => (chunk.chars().count() as f64 / ratio).ceil() as usize,
+            None => self.encoding.count(chunk),
+        }
+    }
+
+    /// The wait before `chunk`; the first chunk waits `first_chunk_delay_ms`
+    /// when the profile sets it.
+    pub fn before(&self, chunk: &str, first: bool) -> Duration {
+        match (first.then_some(self.first_delay).flatten(), self.tokens_per_sec) {
+            (Some(delay), _) => delay,
+            (None, Some(rate)) => {
+                Duration::try_from_secs_f64(self.tokens(chunk) as f64 / rate).map_or(MAX_TOKEN_DELAY, |delay| delay.min(MAX_TOKEN_DELAY))
+            }
+            (None, None) => self.delay,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Waiter {
+    released: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl Waiter {
+    fn release(&self) {
+        self.released.set(true);
+        if let Some(waker) = self.waker.borrow_mut().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Resolves once the wheel releases it. Dropping it early is fine; the wheel
+/// just releases an entry nobody is waiting on.
+pub struct Parked {
+    waiter: Rc<Waiter>,
+}
+
+impl Future for Parked {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.waiter.released.get() {
+            return Poll::Ready(());
+        }
+        *self.waiter.waker.borrow_mut() = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+struct Entry {
+    due: u64,
+    waiter: Rc<Waiter>,
+}
+
+struct Wheel {
+    origin: Instant,
+    /// Last tick whose slot has been drained.
+    current: u64,
+    slots: Vec<Vec<Entry>>,
+    /// Due but held back by the per-tick budget, oldest first.
+    ready: VecDeque<Rc<Waiter>>,
+    max_per_tick: Option<usize>,
+    pending: usize,
+    driving: bool,
+}
+
+impl Wheel {
+    fn new() -> Wheel {
+        Wheel {
+            origin: Instant::now(),
+            current: 0,
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+            ready: VecDeque::new(),
+            max_per_tick: None,
+            pending: 0,
+            driving: false,
+        }
+    }
+
+    fn tick_of(&self, at: Instant) -> u64 {
+        (at.saturating_duration_since(self.origin).as_nanos() / TICK.as_nanos()) as u64
+    }
+
+    fn insert(&mut self, delay: Duration, waiter: Rc<Waiter>) {
+        let deadline = Instant::now() + delay;
+        let since = deadline.saturating_duration_since(self.origin).as_nanos();
+        // Round up, and never into a slot that has already been drained.
+        let due = (since.div_ceil(TICK.as_nanos()) as u64).max(self.current + 1);
+        self.slots[(due % SLOTS as u64) as usize].push(Entry { due, waiter });
+        self.pending += 1;
+        metrics::PARKED_TIMERS.inc();
+    }
+
+    /// Drains every slot up to `now` and releases as many due waiters as the
+    /// budget allows.
+    fn advance(&mut self, now: Instant) {
+        let target = self.tick_of(now);
+        // After a long stall every slot is visited once rather than once per missed tick.
+        let first = self.current + 1;
+        let first = first.max(target.saturating_sub(SLOTS as u64 - 1));
+        for tick in first..=target {
+            let ready = &mut self.ready;
+            // `retain` visits in order, so registration order is kept within the slot.
+            self.slots[(tick % SLOTS as u64) as usize].retain(|entry| {
+                if entry.due <= target {
+                    ready.push_back(entry.waiter.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        self.current = self.current.max(target);
+
+        let budget = self.max_per_tick.unwrap_or(usize::MAX).min(self.ready.len());
+        for waiter in self.ready.drain(..budget) {
+            waiter.release();
+        }
+        self.pending -= budget;
+        metrics::PARKED_TIMERS.sub(budget as u64);
+    }
+}
+
+/// Advances this worker's wheel every tick until nothing is parked.
+async fn drive() {
+    let mut ticks = time::interval(TICK);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        ticks.tick().await;
+        let idle = WHEEL.with(|wheel| {
+            let mut wheel = wheel.borrow_mut();
+            wheel.advance(Instant::now());
+            if wheel.pending == 0 {
+                wheel.driving = false;
+            }
+            wheel.pending == 0
+        });
+        if idle {
+            break;
+        }
+    }
+}
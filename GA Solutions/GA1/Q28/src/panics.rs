@@ -0,0 +1,81 @@
+//! Turns a panicking handler into an OpenAI-style 500 reply. Without it the
+//! panic unwinds out of the worker's connection task and the client sees the
+//! connection drop with no response at all.
+//!
+//! Only the handler is covered: once a stream's headers are out, a panic
+//! while producing its body still ends the connection.
+//!
+//! The server's shared state outlives a panic, and later requests use it
+//! again. Every mutex in the crate takes over a poisoned guard rather than
+//! panicking in turn, so a panic does not take down each request after it;
+//! whatever the handler left half-updated stays that way.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse};
+use futures::FutureExt;
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+
+use crate::error::ErrorEnvelope;
+use crate::metrics;
+
+/// Where a handler leaves the id it assigned its request, so a panic later
+/// in the handler can still be logged against it. The middleware keeps its
+/// own handle, since the request itself is lost with the panic.
+#[derive(Clone, Default)]
+struct RequestId(Rc<RefCell<Option<String>>>);
+
+/// Records `request_id` as the id of `req`.
+pub fn label(req: &HttpRequest, request_id: &str) {
+    if let Some(RequestId(slot)) = req.extensions().get::<RequestId>() {
+        *slot.borrow_mut() = Some(request_id.to_string());
+    }
+}
+
+/// Middleware answering for handlers that panic; see the module docs.
+pub async fn catch(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = RequestId::default();
+    req.extensions_mut().insert(request_id.clone());
+    let (method, path) = (req.method().clone(), req.path().to_string());
+    // Shared state is used again after a panic; its mutexes recover from
+    // being poisoned, as the module docs describe.
+    match AssertUnwindSafe(next.call(req)).catch_unwind().await {
+        Ok(response) => response,
+        Err(panic) => {
+            metrics::HANDLER_PANICS.inc();
+            let request_id = request_id.0.borrow_mut().take();
+            log::error!(
+                "handler for {} {} panicked (request {}): {}",
+                method,
+                path,
+                request_id.as_deref().unwrap_or("unassigned"),
+                message(&*panic)
+            );
+            let mut response = HttpResponse::InternalServerError();
+            if let Some(id) = request_id {
+                response.insert_header(("x-request-id", id));
+            }
+            let envelope = ErrorEnvelope::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "The server had an error while processing your request. Sorry about that!",
+                None,
+            );
+            Err(InternalError::from_response("handler panicked", response.json(envelope)).into())
+        }
+    }
+}
+
+/// The text a panic was raised with, when it was raised with text.
+fn message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
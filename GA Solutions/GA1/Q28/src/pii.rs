@@ -0,0 +1,173 @@
+//! Inbound filter that looks for personal data in prompts, so privacy-gateway
+//! behavior can be exercised locally. What it finds is recorded in the
+//! capture store by kind and position only; the matched values are never kept.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use crate::capture::Capture;
+use crate::chat::{self, MessageContent, NormalizedRequest};
+use crate::config::{PiiConfig, PiiPolicy};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    CreditCard,
+}
+
+impl PiiKind {
+    fn placeholder(self) -> &'static str {
+        match self {
+            PiiKind::Email => "[EMAIL]",
+            PiiKind::Phone => "[PHONE]",
+            PiiKind::CreditCard => "[CREDIT_CARD]",
+        }
+    }
+}
+
+/// One match, located by message and byte range in the original text.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: PiiKind,
+    /// Index into `messages`; always 0 for the legacy `prompt` shape.
+    pub message: usize,
+    /// Index of the content part, for multi-part messages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<usize>,
+    pub start: usize,
+    pub end: usize,
+}
+
+static EMAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap());
+static CARD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(?:\d[ -]?){12,18}\d\b").unwrap());
+static PHONE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{3}\)|\b\d{3})[ .-]?\d{3}[ .-]?\d{4}\b").unwrap());
+
+/// Finds every match of `kinds` in `text`, in order and without overlaps.
+/// Card numbers must pass the Luhn check, and take precedence over phone
+/// numbers, whose digits they usually contain.
+pub fn scan(text: &str, kinds: &[PiiKind]) -> Vec<(PiiKind, Range<usize>)> {
+    let mut found: Vec<(PiiKind, Range<usize>)> = Vec::new();
+    for kind in [PiiKind::Email, PiiKind::CreditCard, PiiKind::Phone] {
+        if !kinds.contains(&kind) {
+            continue;
+        }
+        let pattern = match kind {
+            PiiKind::Email => &EMAIL,
+            PiiKind::CreditCard => &CARD,
+            PiiKind::Phone => &PHONE,
+        };
+        for m in pattern.find_iter(text) {
+            let range = m.range();
+            if kind == PiiKind::CreditCard && !luhn_valid(m.as_str()) {
+                continue;
+            }
+            if found.iter().all(|(_, r)| range.end <= r.start || range.start >= r.end) {
+                found.push((kind, range));
+            }
+        }
+    }
+    found.sort_by_key(|(_, r)| r.start);
+    found
+}
+
+fn luhn_valid(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// `text` with every match replaced by its kind's placeholder.
+pub fn redact(text: &str, matches: &[(PiiKind, Range<usize>)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (kind, range) in matches {
+        out.push_str(&text[last..range.start]);
+        out.push_str(kind.placeholder());
+        last = range.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Scans every message of `req` under `config`. With `Redact` or `Reject`
+/// the messages and prompt are rewritten, so a rejected request is still
+/// captured without the values it was rejected for.
+pub fn apply(config: &PiiConfig, req: &mut NormalizedRequest) -> Vec<Finding> {
+    if config.policy == PiiPolicy::Off {
+        return Vec::new();
+    }
+    let rewrite = config.policy != PiiPolicy::Audit;
+    let mut findings = Vec::new();
+    let mut check = |text: &mut String, message: usize, part: Option<usize>| {
+        let matches = scan(text, &config.kinds);
+        findings.extend(matches.iter().map(|(kind, range)| Finding {
+            kind: *kind,
+            message,
+            part,
+            start: range.start,
+            end: range.end,
+        }));
+        if rewrite && !matches.is_empty() {
+            *text = redact(text, &matches);
+        }
+    };
+    for (i, message) in req.messages.iter_mut().enumerate() {
+        match &mut message.content {
+            Some(MessageContent::Text(text)) => check(text, i, None),
+            Some(MessageContent::Parts(parts)) => {
+                for (j, part) in parts.iter_mut().enumerate() {
+                    if let Some(text) = &mut part.text {
+                        check(text, i, Some(j));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    if rewrite && !findings.is_empty() {
+        req.prompt = chat::last_user_text(&req.messages);
+    }
+    findings
+}
+
+/// Under `Audit` the request is served as sent, so `capture`'s copy of it is
+/// redacted here from its findings instead; the matched values are never kept.
+pub fn scrub(config: &PiiConfig, mut capture: Capture) -> Capture {
+    if config.policy != PiiPolicy::Audit || capture.pii.is_empty() {
+        return capture;
+    }
+    let matches = |message: usize, part: Option<usize>| -> Vec<(PiiKind, Range<usize>)> {
+        capture
+            .pii
+            .iter()
+            .filter(|f| f.message == message && f.part == part)
+            .map(|f| (f.kind, f.start..f.end))
+            .collect()
+    };
+    for (i, message) in capture.messages.iter_mut().enumerate() {
+        match &mut message.content {
+            Some(MessageContent::Text(text)) => *text = redact(text, &matches(i, None)),
+            Some(MessageContent::Parts(parts)) => {
+                for (j, part) in parts.iter_mut().enumerate() {
+                    if let Some(text) = &mut part.text {
+                        *text = redact(text, &matches(i, Some(j)));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    capture.prompt = chat::last_user_text(&capture.messages);
+    capture
+}
@@ -0,0 +1,94 @@
+use bytes::Bytes;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Buffers that grew past this are dropped instead of pooled, so one huge
+/// event does not pin a large allocation for the worker's lifetime.
+const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+/// Upper bound on idle buffers kept per worker thread.
+const MAX_POOLED: usize = 256;
+
+thread_local! {
+    // actix runs each worker on its own thread, so this is a per-worker pool.
+    static FREE: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static DISCARDED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    /// Buffers dropped on return because the pool was full or they were oversized.
+    pub discarded: u64,
+}
+
+/// Takes an empty buffer from the current worker's pool, allocating if none is free.
+pub fn take() -> Vec<u8> {
+    match FREE.with(|free| free.borrow_mut().pop()) {
+        Some(buf) => {
+            HITS.fetch_add(1, Ordering::Relaxed);
+            buf
+        }
+        None => {
+            MISSES.fetch_add(1, Ordering::Relaxed);
+            Vec::with_capacity(256)
+        }
+    }
+}
+
+/// Wraps a pooled buffer as `Bytes`. The allocation goes back to the pool of
+/// whichever worker drops the last reference, normally once it is written out.
+pub fn freeze(buf: Vec<u8>) -> Bytes {
+    Bytes::from_owner(Recycled(buf))
+}
+
+pub fn stats() -> PoolStats {
+    let hits = HITS.load(Ordering::Relaxed);
+    let misses = MISSES.load(Ordering::Relaxed);
+    let total = hits + misses;
+    PoolStats {
+        hits,
+        misses,
+        hit_rate: if total == 0 { 0.0 } else { hits as f64 / total as f64 },
+        discarded: DISCARDED.load(Ordering::Relaxed),
+    }
+}
+
+struct Recycled(Vec<u8>);
+
+impl AsRef<[u8]> for Recycled {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for Recycled {
+    fn drop(&mut self) {
+        let mut buf = std::mem::take(&mut self.0);
+        if buf.capacity() > MAX_RETAINED_CAPACITY {
+            DISCARDED.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        buf.clear();
+        // The thread-local may already be gone if this runs during thread teardown.
+        let kept = FREE
+            .try_with(|free| {
+                let mut free = free.borrow_mut();
+                if free.len() < MAX_POOLED {
+                    free.push(buf);
+                    true
+                } else {
+                    false
+                }
+            })
+            .unwrap_or(false);
+        if !kept {
+            DISCARDED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Built-in response profiles approximating well-known providers, so client
+//! benchmarks can compare latency behavior offline. The figures are typical
+//! published/observed values, not guarantees about any provider.
+
+use crate::chat::CHARS_PER_TOKEN;
+use crate::config::Config;
+use crate::error::ErrorStyle;
+use crate::stubs::ResponseProfile;
+
+pub struct Preset {
+    pub name: &'static str,
+    /// Time to first token.
+    pub ttft_ms: u64,
+    pub tokens_per_sec: u64,
+    /// Tokens per streamed chunk.
+    pub chunk_tokens: usize,
+    pub error_style: ErrorStyle,
+    /// Cites sources with `url_citation` annotations, as search models do.
+    pub citations: bool,
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "openai-gpt4o",
+        ttft_ms: 350,
+        tokens_per_sec: 80,
+        chunk_tokens: 1,
+        error_style: ErrorStyle::Openai,
+        citations: false,
+    },
+    Preset {
+        name: "anthropic-sonnet",
+        ttft_ms: 700,
+        tokens_per_sec: 60,
+        chunk_tokens: 3,
+        error_style: ErrorStyle::Anthropic,
+        citations: false,
+    },
+    Preset {
+        name: "groq-fast",
+        ttft_ms: 150,
+        tokens_per_sec: 500,
+        chunk_tokens: 4,
+        error_style: ErrorStyle::Openai,
+        citations: false,
+    },
+    Preset {
+        name: "local-slow",
+        ttft_ms: 2000,
+        tokens_per_sec: 8,
+        chunk_tokens: 1,
+        error_style: ErrorStyle::Openai,
+        citations: false,
+    },
+    Preset {
+        name: "mock-search",
+        // Searching comes before the first token.
+        ttft_ms: 1500,
+        tokens_per_sec: 70,
+        chunk_tokens: 3,
+        error_style: ErrorStyle::Openai,
+        citations: true,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+/// The preset for `model`: the one mapped in `model_presets`, or a preset
+/// whose name is the model name itself.
+pub fn for_model(config: &Config, model: &str) -> Option<&'static Preset> {
+    find(config.model_presets.get(model).map(String::as_str).unwrap_or(model))
+}
+
+impl Preset {
+    pub fn profile(&self) -> ResponseProfile {
+        ResponseProfile {
+            chunk_chars: Some(self.chunk_tokens * CHARS_PER_TOKEN),
+            chunk_delay_ms: self.chunk_tokens as u64 * 1000 / self.tokens_per_sec.max(1),
+            first_chunk_delay_ms: Some(self.ttft_ms),
+            tokens_per_sec: Some(self.tokens_per_sec as f64),
+            error_style: self.error_style,
+            citations: self.citations,
+            ..ResponseProfile::default()
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Estimated cost of mock traffic at the prices of the models it imitates,
+//! so test runs can double as budget projections. Each priced reply reports
+//! `x_mock.estimated_cost_usd` next to its usage, logs it, and adds it to the
+//! running total in `/v1/internal/stats`.
+
+use std::cell::{Cell, RefCell};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chat::Message;
+use crate::diffing::PredictionMatcher;
+use crate::lifecycle::Usage;
+use crate::metrics;
+use crate::models::ModelInfo;
+use crate::tokenizer::{self, TokenEncoding};
+
+/// List prices in USD per million tokens.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Pricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl Pricing {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million + usage.completion_tokens as f64 * self.output_per_million) / 1e6
+    }
+}
+
+/// Counts one reply's usage as it is sent. `finish`, or dropping it when
+/// the client leaves early, adds what was sent to the running total.
+pub struct UsageMeter {
+    request_id: String,
+    model: Option<String>,
+    pricing: Option<Pricing>,
+    encoding: TokenEncoding,
+    prompt_tokens: usize,
+    completion_tokens: Cell<usize>,
+    /// Reply text not yet counted: a token can span two chunks, so the text
+    /// after the last place the tokenizer is sure to split waits for the next.
+    pending: RefCell<String>,
+    /// How the reply counted so far compares with the request's `prediction`.
+    prediction: Option<RefCell<PredictionMatcher>>,
+    finished: Cell<bool>,
+}
+
+impl UsageMeter {
+    /// `None` for an unpriced model when the client did not ask for usage,
+    /// so such replies pay nothing for counting.
+    pub fn start(
+        request_id: &str,
+        model: Option<&ModelInfo>,
+        messages: &[Message],
+        prediction: Option<&str>,
+        wanted: bool,
+    ) -> Option<UsageMeter> {
+        let priced = model.is_some_and(|m| m.pricing.is_some());
+        (priced || wanted).then(|| UsageMeter::new(request_id, model, messages, prediction))
+    }
+
+    /// A meter for a reply whose usage is always reported.
+    pub fn new(request_id: &str, model: Option<&ModelInfo>, messages: &[Message], prediction: Option<&str>) -> UsageMeter {
+        let encoding = model.map(|m| m.encoding).unwrap_or_default();
+        UsageMeter {
+            request_id: request_id.to_string(),
+            model: model.map(|m| m.id.clone()),
+            pricing: model.and_then(|m| m.pricing),
+            encoding,
+            prompt_tokens: encoding.count_messages(messages),
+            completion_tokens: Cell::new(0),
+            pending: RefCell::new(String::new()),
+            prediction: prediction.map(|p| RefCell::new(PredictionMatcher::new(p, encoding))),
+            finished: Cell::new(false),
+        }
+    }
+
+    /// Counts a chunk of reply text as sent.
+    pub fn chunk(&self, text: &str) {
+        let mut pending = self.pending.borrow_mut();
+        pending.push_str(text);
+        if let Some(split) = tokenizer::token_boundary(&pending) {
+            self.completion_tokens.set(self.completion_tokens.get() + self.encoding.count(&pending[..split]));
+            if let Some(prediction) = &self.prediction {
+                prediction.borrow_mut().push(&pending[..split]);
+            }
+            pending.drain(..split);
+        }
+    }
+
+    pub fn usage(&self) -> Usage {
+        let pending = self.pending.borrow();
+        let details = self.prediction.as_ref().map(|prediction| prediction.borrow().details(&pending));
+        let completion_tokens = self.completion_tokens.get() + self.encoding.count(&pending) + details.map_or(0, |d| d.rejected_prediction_tokens);
+        Usage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens,
+            total_tokens: self.prompt_tokens + completion_tokens,
+            completion_tokens_details: details,
+        }
+    }
+
+    pub fn estimated_cost_usd(&self) -> Option<f64> {
+        self.pricing.map(|p| p.cost(&self.usage()))
+    }
+
+    /// Records the estimate; later calls do nothing.
+    pub fn finish(&self) {
+        if self.finished.replace(true) {
+            return;
+        }
+        let Some(cost) = self.estimated_cost_usd() else {
+            return;
+        };
+        metrics::ESTIMATED_COST_NANOS.add((cost * 1e9).round() as u64);
+        let usage = self.usage();
+        log::info!(
+            "{} {}: {} prompt + {} completion tokens, estimated ${:.6}",
+            self.request_id,
+            self.model.as_deref().unwrap_or_default(),
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            cost
+        );
+    }
+}
+
+impl Drop for UsageMeter {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,156 @@
+//! Admission queue emulating a provider under a traffic spike: streams start
+//! at a fixed service rate, arrivals beyond it wait their turn (higher key
+//! tiers first), and once `depth` requests are already waiting new ones are
+//! turned away.
+
+use actix_web::http::StatusCode;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::{sleep_until, Instant};
+
+use crate::config::QueueConfig;
+use crate::error::MockError;
+use crate::metrics;
+
+pub struct AdmissionQueue {
+    interval: Duration,
+    depth: usize,
+    schedule: Arc<Mutex<Schedule>>,
+    waiting: AtomicUsize,
+}
+
+struct Schedule {
+    /// Earliest instant the next request may start.
+    next_start: Instant,
+    /// Requests waiting for a start slot, highest priority first.
+    waiters: BinaryHeap<Waiter>,
+    /// Whether a `release` task is handing out slots.
+    releasing: bool,
+    arrivals: u64,
+}
+
+struct Waiter {
+    priority: u8,
+    arrival: u64,
+    admit: oneshot::Sender<()>,
+}
+
+impl Ord for Waiter {
+    /// Higher priorities first, then first come, first served.
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.arrival.cmp(&self.arrival))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl AdmissionQueue {
+    /// A queue for `config`, or `None` when no service rate is set.
+    pub fn new(config: &QueueConfig) -> Option<AdmissionQueue> {
+        let rate = config.admissions_per_sec.filter(|&rate| rate > 0.0)?;
+        Some(AdmissionQueue {
+            interval: Duration::from_secs_f64(1.0 / rate),
+            depth: config.depth,
+            schedule: Arc::new(Mutex::new(Schedule {
+                next_start: Instant::now(),
+                waiters: BinaryHeap::new(),
+                releasing: false,
+                arrivals: 0,
+            })),
+            waiting: AtomicUsize::new(0),
+        })
+    }
+
+    /// Waits for this request's start slot and returns how long it queued.
+    /// Each free slot goes to the waiting request with the highest
+    /// `priority`, oldest first among equals. A request whose client goes
+    /// away while queued still uses up its slot, as it would on a real backend.
+    pub async fn admit(&self, priority: u8) -> Result<Duration, MockError> {
+        let arrived = Instant::now();
+        if self.waiting.fetch_add(1, Ordering::AcqRel) >= self.depth {
+            self.waiting.fetch_sub(1, Ordering::AcqRel);
+            metrics::QUEUE_REJECTIONS.inc();
+            return Err(MockError::rejected(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The server is currently overloaded with other requests. Please retry your request later.",
+                None,
+            ));
+        }
+        let _waiting = Waiting(&self.waiting);
+
+        let admitted = {
+            let mut schedule = lock(&self.schedule);
+            if schedule.waiters.is_empty() && schedule.next_start <= arrived {
+                schedule.next_start = arrived + self.interval;
+                None
+            } else {
+                let (admit, admitted) = oneshot::channel();
+                let arrival = schedule.arrivals;
+                schedule.arrivals += 1;
+                schedule.waiters.push(Waiter {
+                    priority,
+                    arrival,
+                    admit,
+                });
+                if !schedule.releasing {
+                    schedule.releasing = true;
+                    metrics::spawn(release(self.schedule.clone(), self.interval));
+                }
+                Some(admitted)
+            }
+        };
+        if let Some(admitted) = admitted {
+            metrics::QUEUED_REQUESTS.inc();
+            let _ = admitted.await;
+        }
+        let queued = arrived.elapsed();
+        metrics::QUEUE_MS_TOTAL.add(queued.as_millis() as u64);
+        Ok(queued)
+    }
+}
+
+/// Hands out start slots one interval apart until nobody is waiting.
+async fn release(schedule: Arc<Mutex<Schedule>>, interval: Duration) {
+    loop {
+        let at = lock(&schedule).next_start;
+        sleep_until(at).await;
+        let mut schedule = lock(&schedule);
+        let Some(waiter) = schedule.waiters.pop() else {
+            schedule.releasing = false;
+            return;
+        };
+        schedule.next_start = at + interval;
+        let _ = waiter.admit.send(());
+    }
+}
+
+fn lock(schedule: &Mutex<Schedule>) -> MutexGuard<'_, Schedule> {
+    schedule.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Counts a request as waiting until it is admitted or abandoned.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,129 @@
+//! Token quotas for listed keys, with alerts as usage crosses configured
+//! shares of them (`[quota] thresholds`, 50, 80 and 100% by default), for
+//! testing how clients warn users about their budget. Each crossing is
+//! logged and published as a `quota.threshold` lifecycle event, and every
+//! later response to the key says how much of its quota is used.
+//!
+//! Requests are charged as rate limits charge them: the prompt plus the
+//! completion reserved. Usage only grows for the life of the server, and a
+//! key over its quota is still served.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use actix_web::HttpResponseBuilder;
+use serde::Serialize;
+
+use crate::config::QuotaConfig;
+use crate::keys::ApiKey;
+use crate::lifecycle::{self, EventKind, LifecycleEvent, Usage};
+use crate::orgs::OrgScope;
+use crate::state::AppState;
+
+/// A key's quota as it stands after charging a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuotaStatus {
+    pub quota: u64,
+    pub used: u64,
+    /// The highest threshold reached so far, in percent.
+    pub reached: Option<u8>,
+    /// The threshold this request took the key past, if any.
+    pub crossed: Option<u8>,
+}
+
+impl QuotaStatus {
+    /// Adds `x-mock-quota-limit-tokens` and `x-mock-quota-used-tokens`, and
+    /// `x-mock-quota-warning` once a threshold has been reached.
+    pub fn insert_headers(&self, response: &mut HttpResponseBuilder) {
+        response
+            .insert_header(("x-mock-quota-limit-tokens", self.quota.to_string()))
+            .insert_header(("x-mock-quota-used-tokens", self.used.to_string()));
+        if let Some(percent) = self.reached {
+            response.insert_header(("x-mock-quota-warning", format!("{}% of this key's token quota used", percent)));
+        }
+    }
+}
+
+/// What a `quota.threshold` event adds to the lifecycle fields.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct QuotaAlert {
+    /// The key, with all but its last four characters hidden.
+    pub key: String,
+    pub threshold_percent: u8,
+    pub used_tokens: u64,
+    pub quota_tokens: u64,
+}
+
+#[derive(Default)]
+pub struct Quotas {
+    thresholds: Vec<u8>,
+    used: Mutex<HashMap<String, u64>>,
+}
+
+impl Quotas {
+    pub fn new(config: &QuotaConfig) -> Quotas {
+        let mut thresholds = config.thresholds.clone();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        Quotas {
+            thresholds,
+            used: Mutex::default(),
+        }
+    }
+
+    /// Charges `tokens` to `key`, or does nothing for a key without a quota.
+    /// A request that jumps several thresholds at once reports the highest.
+    pub fn charge(&self, key: &ApiKey, tokens: impl FnOnce() -> u64) -> Option<QuotaStatus> {
+        let quota = key.token_quota.filter(|&quota| quota > 0)?;
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let used = used.entry(key.key.clone()).or_default();
+        let before = *used;
+        *used = used.saturating_add(tokens());
+        let reached_at = |tokens: u64| self.thresholds.iter().rev().copied().find(|&t| tokens.saturating_mul(100) >= quota.saturating_mul(u64::from(t)));
+        let reached = reached_at(*used);
+        Some(QuotaStatus {
+            quota,
+            used: *used,
+            reached,
+            crossed: reached.filter(|_| reached != reached_at(before)),
+        })
+    }
+}
+
+/// Logs a newly crossed threshold and publishes its `quota.threshold` event.
+pub fn notify(state: &AppState, key: &ApiKey, status: &QuotaStatus, request_id: &str, model: Option<&str>, scope: &OrgScope) {
+    let Some(threshold) = status.crossed else {
+        return;
+    };
+    let alert = QuotaAlert {
+        key: redact(&key.key),
+        threshold_percent: threshold,
+        used_tokens: status.used,
+        quota_tokens: status.quota,
+    };
+    log::warn!(
+        "quota threshold crossed: key={} threshold={}% used={} quota={} request_id={}",
+        alert.key,
+        alert.threshold_percent,
+        alert.used_tokens,
+        alert.quota_tokens,
+        request_id
+    );
+    state.publish(LifecycleEvent {
+        kind: EventKind::QuotaThreshold,
+        request_id: request_id.to_string(),
+        model: model.map(str::to_string),
+        organization: scope.organization.clone(),
+        project: scope.project.clone(),
+        timestamp_ms: lifecycle::now_ms(),
+        duration_ms: 0,
+        usage: Usage::default(),
+        error: None,
+        quota: Some(alert),
+    });
+}
+
+fn redact(key: &str) -> String {
+    let tail: String = key.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("...{}", tail)
+}
@@ -0,0 +1,197 @@
+//! Per-key request and token limits, reported on every successful response in
+//! OpenAI's `x-ratelimit-*` headers so clients that pace themselves from
+//! those headers can be tested. Each limit is a bucket that refills
+//! continuously over a minute, as the provider's do. By default the limits
+//! are only reported; with `enforce`, a request that would overdraw one is
+//! turned away with a 429.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::HttpResponseBuilder;
+
+use crate::config::RateLimitConfig;
+use crate::error::MockError;
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Accounts tracked before idle ones are dropped to make room for a new one.
+const PRUNE_ABOVE: usize = 1024;
+
+/// How much of one limit is left after a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub limit: u64,
+    pub remaining: u64,
+    /// Until the bucket is full again.
+    pub reset: Duration,
+}
+
+/// Both limits as they stand after charging a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimitStatus {
+    pub requests: Option<Quota>,
+    pub tokens: Option<Quota>,
+}
+
+impl RateLimitStatus {
+    /// Adds the `x-ratelimit-*` headers for each configured limit.
+    pub fn insert_headers(&self, response: &mut HttpResponseBuilder) {
+        for (kind, quota) in [("requests", self.requests), ("tokens", self.tokens)] {
+            let Some(quota) = quota else { continue };
+            response
+                .insert_header((format!("x-ratelimit-limit-{}", kind), quota.limit.to_string()))
+                .insert_header((format!("x-ratelimit-remaining-{}", kind), quota.remaining.to_string()))
+                .insert_header((format!("x-ratelimit-reset-{}", kind), format_reset(quota.reset)));
+        }
+    }
+}
+
+/// A limit that refills at `limit` per minute, up to `limit`.
+struct Bucket {
+    limit: u64,
+    level: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: u64, now: Instant) -> Bucket {
+        Bucket {
+            limit,
+            level: limit as f64,
+            updated: now,
+        }
+    }
+
+    fn per_sec(&self) -> f64 {
+        self.limit as f64 / WINDOW.as_secs_f64()
+    }
+
+    fn is_full_at(&self, now: Instant) -> bool {
+        self.level + now.duration_since(self.updated).as_secs_f64() * self.per_sec() >= self.limit as f64
+    }
+
+    fn refill(&mut self, now: Instant) {
+        self.level = (self.level + now.duration_since(self.updated).as_secs_f64() * self.per_sec()).min(self.limit as f64);
+        self.updated = now;
+    }
+
+    /// Time until the bucket holds `amount`, or is full if `amount` is more.
+    fn wait_for(&self, amount: f64) -> Duration {
+        let amount = amount.min(self.limit as f64);
+        Duration::from_secs_f64(((amount - self.level) / self.per_sec()).max(0.0))
+    }
+
+    fn quota(&self) -> Quota {
+        Quota {
+            limit: self.limit,
+            remaining: self.level.max(0.0) as u64,
+            reset: self.wait_for(self.limit as f64),
+        }
+    }
+
+    fn exceeded(&self, kind: &str, amount: f64) -> MockError {
+        let retry_after = self.wait_for(amount);
+        let unit = if kind == "requests" { "RPM" } else { "TPM" };
+        MockError::RateLimited {
+            message: format!(
+                "Rate limit reached on {} per min ({}): Limit {}, Used {}, Requested {}. Please try again in {}.",
+                kind,
+                unit,
+                self.limit,
+                self.limit - self.level.max(0.0) as u64,
+                amount,
+                format_reset(retry_after)
+            ),
+            retry_after: Some(retry_after),
+        }
+    }
+}
+
+/// One key's buckets, for the limits that are configured.
+struct KeyBuckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+}
+
+impl KeyBuckets {
+    /// Whether every bucket has refilled by `now`, which makes these the
+    /// same as a new key's.
+    fn idle_at(&self, now: Instant) -> bool {
+        [&self.requests, &self.tokens].into_iter().flatten().all(|b| b.is_full_at(now))
+    }
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    keys: Mutex<HashMap<String, KeyBuckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            config: RateLimitConfig {
+                requests_per_min: config.requests_per_min.filter(|&limit| limit > 0),
+                tokens_per_min: config.tokens_per_min.filter(|&limit| limit > 0),
+                enforce: config.enforce,
+            },
+            keys: Mutex::default(),
+        }
+    }
+
+    /// Charges one request to `key` (the `org/project` when the request has
+    /// an organization, else the bearer token or `""`), and the request's
+    /// `tokens`, which are only counted if a token limit is set. Reported
+    /// limits never go below zero; an overdraft only fails the request when
+    /// limits are enforced, and then nothing is charged.
+    pub fn charge(&self, key: &str, tokens: impl FnOnce() -> u64) -> Result<RateLimitStatus, MockError> {
+        if self.config.requests_per_min.is_none() && self.config.tokens_per_min.is_none() {
+            return Ok(RateLimitStatus::default());
+        }
+        let now = Instant::now();
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        if keys.len() >= PRUNE_ABOVE && !keys.contains_key(key) {
+            keys.retain(|_, buckets| !buckets.idle_at(now));
+        }
+        let buckets = keys.entry(key.to_string()).or_insert_with(|| KeyBuckets {
+            requests: self.config.requests_per_min.map(|limit| Bucket::full(limit, now)),
+            tokens: self.config.tokens_per_min.map(|limit| Bucket::full(limit, now)),
+        });
+        let tokens = if buckets.tokens.is_some() { tokens() as f64 } else { 0.0 };
+        let mut charges = [(buckets.requests.as_mut(), 1.0, "requests"), (buckets.tokens.as_mut(), tokens, "tokens")];
+        for (bucket, amount, kind) in &mut charges {
+            let Some(bucket) = bucket else { continue };
+            bucket.refill(now);
+            if self.config.enforce && bucket.level < *amount {
+                return Err(bucket.exceeded(kind, *amount));
+            }
+        }
+        for (bucket, amount, _) in charges {
+            if let Some(bucket) = bucket {
+                bucket.level = (bucket.level - amount).max(0.0);
+            }
+        }
+        Ok(RateLimitStatus {
+            requests: buckets.requests.as_ref().map(Bucket::quota),
+            tokens: buckets.tokens.as_ref().map(Bucket::quota),
+        })
+    }
+}
+
+/// A duration the way OpenAI writes it in reset headers: `120ms`, `8.64s`,
+/// `6m0s`.
+pub fn format_reset(duration: Duration) -> String {
+    let ms = duration.as_millis();
+    if ms < 1000 {
+        return format!("{}ms", ms);
+    }
+    let minutes = ms / 60_000;
+    let seconds = (ms % 60_000) as f64 / 1000.0;
+    let seconds = format!("{:.3}", seconds).trim_end_matches('0').trim_end_matches('.').to_string();
+    if minutes == 0 {
+        format!("{}s", seconds)
+    } else {
+        format!("{}m{}s", minutes, seconds)
+    }
+}
@@ -0,0 +1,387 @@
+//! A Realtime API transcription session at `/v1/realtime`, over WebSocket,
+//! for testing streaming speech-to-text clients offline.
+//!
+//! The client sends `input_audio_buffer.append` events with base64 audio.
+//! Every `[realtime] delta_interval_ms` the session sends the words heard
+//! since the last tick as `conversation.item.input_audio_transcription.delta`
+//! events, one word per `ms_per_word` of audio in the session's
+//! `input_audio_format`. The words are the default reply corpus, read in
+//! order; the audio itself is never decoded. `input_audio_buffer.commit`
+//! sends the rest of the item's words, then `input_audio_buffer.committed`
+//! and the item's `...transcription.completed` with the whole transcript,
+//! and starts a new item. `input_audio_buffer.clear` drops the item
+//! unfinished, and `transcription_session.update` sets the format and is
+//! echoed back in `transcription_session.updated`. Turn detection is not
+//! modelled: items end only when committed.
+//!
+//! Unlike the HTTP endpoints, the session needs a listed key whenever any
+//! are configured: a missing or unlisted one is refused before the upgrade.
+
+use actix_web::http::StatusCode;
+use actix_web::{get, web, Error, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use crate::chat::EXTENDED_CONTENT;
+use crate::config::{Config, RealtimeConfig};
+use crate::error::MockError;
+use crate::keys;
+use crate::orgs;
+use crate::state::AppState;
+use crate::ws::{self, FrameError, Opcode};
+
+/// Largest client message accepted, as with OpenAI.
+const MAX_MESSAGE_BYTES: usize = 15 * 1024 * 1024;
+/// The shortest buffer that can be committed, as with OpenAI.
+const MIN_COMMIT_MS: u64 = 100;
+
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+/// The audio formats the Realtime API takes, and their byte rates.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum InputAudioFormat {
+    /// 24 kHz mono 16-bit little-endian.
+    #[default]
+    Pcm16,
+    G711Ulaw,
+    G711Alaw,
+}
+
+impl InputAudioFormat {
+    fn name(self) -> &'static str {
+        match self {
+            InputAudioFormat::Pcm16 => "pcm16",
+            InputAudioFormat::G711Ulaw => "g711_ulaw",
+            InputAudioFormat::G711Alaw => "g711_alaw",
+        }
+    }
+
+    pub fn bytes_per_ms(self) -> u64 {
+        match self {
+            InputAudioFormat::Pcm16 => 48,
+            InputAudioFormat::G711Ulaw | InputAudioFormat::G711Alaw => 8,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum ClientEvent {
+    #[serde(rename = "transcription_session.update")]
+    SessionUpdate { session: SessionUpdate },
+    #[serde(rename = "input_audio_buffer.append")]
+    Append { audio: String },
+    #[serde(rename = "input_audio_buffer.commit")]
+    Commit,
+    #[serde(rename = "input_audio_buffer.clear")]
+    Clear,
+}
+
+#[derive(Deserialize)]
+struct SessionUpdate {
+    #[serde(default)]
+    input_audio_format: Option<InputAudioFormat>,
+    #[serde(default)]
+    input_audio_transcription: Option<Value>,
+}
+
+/// One transcription session: its settings, the item being heard, and the
+/// events waiting to go out.
+pub struct Session {
+    id: String,
+    ms_per_word: u64,
+    format: InputAudioFormat,
+    transcription: Value,
+    next_event: u64,
+    next_item: u64,
+    previous_item: Option<String>,
+    /// The item audio is being appended to, once any has been.
+    item: Option<String>,
+    buffered_bytes: u64,
+    transcript: String,
+    heard_words: usize,
+    words: std::iter::Cycle<std::str::SplitWhitespace<'static>>,
+    /// Server events, serialized, in order.
+    pub outgoing: VecDeque<String>,
+}
+
+impl Session {
+    pub fn new(config: &RealtimeConfig) -> Session {
+        let mut session = Session {
+            id: format!("sess_{}", NEXT_SESSION.fetch_add(1, Ordering::Relaxed)),
+            ms_per_word: config.ms_per_word,
+            format: InputAudioFormat::default(),
+            transcription: json!({"model": "gpt-4o-transcribe"}),
+            next_event: 1,
+            next_item: 1,
+            previous_item: None,
+            item: None,
+            buffered_bytes: 0,
+            transcript: String::new(),
+            heard_words: 0,
+            words: EXTENDED_CONTENT.split_whitespace().cycle(),
+            outgoing: VecDeque::new(),
+        };
+        let session_object = session.session_object();
+        session.send("transcription_session.created", json!({"session": session_object}));
+        session
+    }
+
+    fn session_object(&self) -> Value {
+        json!({
+            "id": self.id,
+            "object": "realtime.transcription_session",
+            "input_audio_format": self.format.name(),
+            "input_audio_transcription": self.transcription,
+            "turn_detection": null,
+        })
+    }
+
+    fn send(&mut self, kind: &str, fields: Value) {
+        let mut event = json!({"event_id": format!("event_{}", self.next_event), "type": kind});
+        self.next_event += 1;
+        if let (Some(event), Value::Object(fields)) = (event.as_object_mut(), fields) {
+            event.extend(fields);
+        }
+        self.outgoing.push_back(event.to_string());
+    }
+
+    fn error(&mut self, code: &str, message: String, client_event: Option<&Value>) {
+        let event_id = client_event.and_then(|e| e.get("event_id")).cloned().unwrap_or(Value::Null);
+        self.send(
+            "error",
+            json!({"error": {"type": "invalid_request_error", "code": code, "message": message, "event_id": event_id}}),
+        );
+    }
+
+    /// Handles a client text message.
+    pub fn receive(&mut self, message: &str) {
+        let raw: Value = match serde_json::from_str(message) {
+            Ok(raw) => raw,
+            Err(e) => return self.error("invalid_json", format!("The event could not be parsed as JSON: {}", e), None),
+        };
+        let event = match ClientEvent::deserialize(&raw) {
+            Ok(event) => event,
+            Err(e) => {
+                let kind = raw.get("type").and_then(Value::as_str).unwrap_or_default().to_string();
+                return self.error("invalid_event", format!("Invalid '{}' event: {}", kind, e), Some(&raw));
+            }
+        };
+        match event {
+            ClientEvent::SessionUpdate { session } => {
+                if let Some(format) = session.input_audio_format {
+                    self.format = format;
+                }
+                if let Some(transcription) = session.input_audio_transcription {
+                    self.transcription = transcription;
+                }
+                let session_object = self.session_object();
+                self.send("transcription_session.updated", json!({"session": session_object}));
+            }
+            ClientEvent::Append { audio } => match STANDARD.decode(audio.as_bytes()) {
+                Ok(audio) => {
+                    if self.item.is_none() {
+                        self.item = Some(format!("item_{}", self.next_item));
+                        self.next_item += 1;
+                    }
+                    self.buffered_bytes += audio.len() as u64;
+                }
+                Err(_) => self.error("invalid_value", "Invalid 'audio': expected base64-encoded audio.".to_string(), Some(&raw)),
+            },
+            ClientEvent::Commit => self.commit(&raw),
+            ClientEvent::Clear => {
+                self.item = None;
+                self.buffered_bytes = 0;
+                self.transcript.clear();
+                self.heard_words = 0;
+                self.send("input_audio_buffer.cleared", json!({}));
+            }
+        }
+    }
+
+    fn buffered_ms(&self) -> u64 {
+        self.buffered_bytes / self.format.bytes_per_ms()
+    }
+
+    /// Sends the words heard since the last tick, if any.
+    pub fn tick(&mut self) {
+        let due = (self.buffered_ms() / self.ms_per_word) as usize;
+        self.hear(due);
+    }
+
+    /// Sends a delta with the item's words up to `due`.
+    fn hear(&mut self, due: usize) {
+        let Some(item) = self.item.clone() else {
+            return;
+        };
+        if due <= self.heard_words {
+            return;
+        }
+        let mut delta = String::new();
+        for _ in self.heard_words..due {
+            if !self.transcript.is_empty() || !delta.is_empty() {
+                delta.push(' ');
+            }
+            delta.push_str(self.words.next().expect("the corpus cycles"));
+        }
+        self.heard_words = due;
+        self.transcript.push_str(&delta);
+        self.send(
+            "conversation.item.input_audio_transcription.delta",
+            json!({"item_id": item, "content_index": 0, "delta": delta}),
+        );
+    }
+
+    fn commit(&mut self, raw: &Value) {
+        let buffered_ms = self.buffered_ms();
+        let item = match self.item.clone() {
+            Some(item) if buffered_ms >= MIN_COMMIT_MS => item,
+            _ => {
+                let message = format!(
+                    "Error committing input audio buffer: buffer too small. Expected at least {}ms of audio, but buffer only has {}ms of audio.",
+                    MIN_COMMIT_MS, buffered_ms
+                );
+                return self.error("input_audio_buffer_commit_empty", message, Some(raw));
+            }
+        };
+        // Whatever is left of the last word counts as one.
+        self.hear(buffered_ms.div_ceil(self.ms_per_word) as usize);
+        let previous_item = self.previous_item.replace(item.clone());
+        self.send("input_audio_buffer.committed", json!({"previous_item_id": previous_item, "item_id": item}));
+        let transcript = std::mem::take(&mut self.transcript);
+        self.send(
+            "conversation.item.input_audio_transcription.completed",
+            json!({"item_id": item, "content_index": 0, "transcript": transcript}),
+        );
+        self.item = None;
+        self.buffered_bytes = 0;
+        self.heard_words = 0;
+    }
+}
+
+/// The connection's state between writes.
+struct Connection {
+    payload: web::Payload,
+    /// Client bytes not yet read as frames.
+    buf: Vec<u8>,
+    /// A fragmented message's payload so far.
+    message: Option<Vec<u8>>,
+    ticker: Interval,
+    session: Session,
+    /// Control frames waiting to go out, ahead of the session's events.
+    control: VecDeque<Bytes>,
+    closed: bool,
+}
+
+impl Connection {
+    /// Reads every whole frame from `buf`.
+    fn read_frames(&mut self) {
+        while !self.closed {
+            let (frame, used) = match ws::decode(&self.buf, MAX_MESSAGE_BYTES, true) {
+                Ok(Some(decoded)) => decoded,
+                Ok(None) => return,
+                Err(e) => return self.close(e.close_code(), ""),
+            };
+            self.buf.drain(..used);
+            match frame.opcode {
+                Opcode::Text | Opcode::Binary | Opcode::Continuation => {
+                    let mut message = match (frame.opcode, self.message.take()) {
+                        (Opcode::Continuation, Some(message)) => message,
+                        (Opcode::Text, None) => Vec::new(),
+                        (Opcode::Binary, None) => return self.close(1003, "Only text messages are accepted."),
+                        _ => return self.close(FrameError::Protocol.close_code(), ""),
+                    };
+                    if message.len() + frame.payload.len() > MAX_MESSAGE_BYTES {
+                        return self.close(FrameError::TooLarge.close_code(), "");
+                    }
+                    message.extend_from_slice(&frame.payload);
+                    if !frame.fin {
+                        self.message = Some(message);
+                        continue;
+                    }
+                    match String::from_utf8(message) {
+                        Ok(text) => self.session.receive(&text),
+                        Err(_) => return self.close(1007, "Messages must be UTF-8."),
+                    }
+                }
+                Opcode::Ping => self.control.push_back(ws::encode(Opcode::Pong, &frame.payload, None)),
+                Opcode::Pong => {}
+                Opcode::Close => return self.close(1000, ""),
+            }
+        }
+    }
+
+    fn close(&mut self, code: u16, reason: &str) {
+        self.control.push_back(ws::close(code, reason));
+        self.closed = true;
+    }
+
+    fn next_write(&mut self) -> Option<Bytes> {
+        if let Some(frame) = self.control.pop_front() {
+            return Some(frame);
+        }
+        if self.closed {
+            return None;
+        }
+        self.session.outgoing.pop_front().map(|event| ws::encode(Opcode::Text, event.as_bytes(), None))
+    }
+}
+
+#[get("/v1/realtime")]
+pub async fn realtime_endpoint(
+    req: HttpRequest,
+    payload: web::Payload,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    state.storm.check()?;
+    keys::require(&config.keys, &req)?;
+    keys::authorize_use(&config.keys, &req, None, [])?;
+    orgs::resolve(&config, &req)?;
+    let mut response = ws::handshake(&req)?;
+    if req.query_string().split('&').any(|pair| pair.starts_with("intent=") && pair != "intent=transcription") {
+        return Err(MockError::rejected(StatusCode::BAD_REQUEST, "The mock only serves transcription sessions.", Some("intent")));
+    }
+    let mut ticker = time::interval(Duration::from_millis(config.realtime.delta_interval_ms));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let connection = Connection {
+        payload,
+        buf: Vec::new(),
+        message: None,
+        ticker,
+        session: Session::new(&config.realtime),
+        control: VecDeque::new(),
+        closed: false,
+    };
+    let frames = stream::unfold(connection, |mut connection| async move {
+        loop {
+            if let Some(frame) = connection.next_write() {
+                return Some((Ok::<Bytes, Error>(frame), connection));
+            }
+            if connection.closed {
+                return None;
+            }
+            tokio::select! {
+                read = connection.payload.next() => match read {
+                    Some(Ok(bytes)) => {
+                        connection.buf.extend_from_slice(&bytes);
+                        connection.read_frames();
+                    }
+                    // The client went away without a close frame.
+                    _ => return None,
+                },
+                _ = connection.ticker.tick() => connection.session.tick(),
+            }
+        }
+    });
+    Ok(response.streaming(frames))
+}
@@ -0,0 +1,133 @@
+//! Replay mode: recorded replies, seeded from a HAR through
+//! `POST /v1/admin/replay`, that are streamed back chunk for chunk with the
+//! recorded pacing whenever a request's prompt matches one exactly. The
+//! matched stub rule still shapes everything else about the stream. Loading
+//! needs the `recording` feature; without it the store stays empty.
+
+#[cfg(feature = "recording")]
+use actix_web::http::StatusCode;
+#[cfg(feature = "recording")]
+use actix_web::post;
+use actix_web::{delete, web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::admin;
+use crate::audit::Change;
+use crate::config::Config;
+use crate::error::MockError;
+#[cfg(feature = "recording")]
+use crate::har::{self, Har};
+use crate::state::AppState;
+
+/// One recorded reply.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub chunks: Vec<String>,
+    /// Wait before each chunk, matching `chunks`.
+    pub delays: Vec<Duration>,
+}
+
+#[derive(Default)]
+pub struct ReplayStore {
+    recordings: Mutex<HashMap<String, Arc<Recording>>>,
+}
+
+impl ReplayStore {
+    /// Adds `recordings`, replacing any earlier one for the same prompt.
+    pub fn load(&self, recordings: Vec<(String, Recording)>) {
+        let mut stored = self.lock();
+        for (prompt, recording) in recordings {
+            stored.insert(prompt, Arc::new(recording));
+        }
+    }
+
+    pub fn get(&self, prompt: &str) -> Option<Arc<Recording>> {
+        self.lock().get(prompt).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<Recording>>> {
+        self.recordings.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReplayStatus {
+    /// Recordings taken from the uploaded HAR.
+    pub loaded: usize,
+    /// Prompts with a recording now.
+    pub recordings: usize,
+}
+
+/// Largest HAR accepted, well above the generic request body limit. Only
+/// `POST /v1/admin/replay` reads this much, and only once the caller is an admin.
+pub const MAX_HAR_BYTES: usize = 64 * 1024 * 1024;
+
+#[cfg(feature = "recording")]
+#[post("/v1/admin/replay")]
+pub async fn load_endpoint(
+    req: HttpRequest,
+    payload: web::Payload,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    // Checked before the body is read, so unauthenticated uploads are not buffered.
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return Ok(resp);
+    }
+    let body = payload
+        .to_bytes_limited(MAX_HAR_BYTES)
+        .await
+        .map_err(|_| {
+            let message = format!("HAR exceeds the limit of {} bytes", MAX_HAR_BYTES);
+            MockError::rejected(StatusCode::PAYLOAD_TOO_LARGE, message, None)
+        })?
+        .map_err(|e| MockError::rejected(StatusCode::BAD_REQUEST, format!("Could not read the HAR: {}", e), None))?;
+    let har: Har = serde_json::from_slice(&body)
+        .map_err(|e| MockError::rejected(StatusCode::BAD_REQUEST, format!("Invalid HAR: {}", e), None))?;
+    let recordings = har::recordings(&har);
+    let loaded = recordings.len();
+    let before = state.replay.len();
+    state.replay.load(recordings);
+    state.audit.record(
+        &req,
+        "replay.load",
+        Change {
+            before: json!({"recordings": before}),
+            after: json!({"recordings": state.replay.len(), "loaded": loaded}),
+        },
+    )?;
+    Ok(HttpResponse::Ok().json(ReplayStatus {
+        loaded,
+        recordings: state.replay.len(),
+    }))
+}
+
+#[delete("/v1/admin/replay")]
+pub async fn clear_endpoint(req: HttpRequest, config: web::Data<Config>, state: web::Data<AppState>) -> Result<HttpResponse, MockError> {
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return Ok(resp);
+    }
+    let before = state.replay.len();
+    state.replay.clear();
+    state.audit.record(&req, "replay.clear", Change { before: json!({"recordings": before}), after: json!({"recordings": 0}) })?;
+    Ok(HttpResponse::Ok().json(ReplayStatus {
+        loaded: 0,
+        recordings: 0,
+    }))
+}
@@ -0,0 +1,545 @@
+//! OpenAI's Responses API (`/v1/responses`): `input` and `instructions` are
+//! served by the same stub rules, presets, transforms and pacing as chat
+//! completions. With `stream: true` the reply is streamed as the API's named
+//! events, `response.created` through `response.output_text.delta` to a
+//! terminal `response.completed`; otherwise it is returned as one `response`
+//! object, paced like the stream. Tool calls, reasoning items and
+//! `previous_response_id` are not modelled: function call items in `input`
+//! are skipped, and replies are always a single message.
+
+use actix_web::http::StatusCode;
+use actix_web::{post, web, Error, HttpRequest, HttpResponse, ResponseError};
+use bytes::Bytes;
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::admission::{self, Admission, Paced, Recorded, Step};
+use crate::capture::StreamRecorder;
+use crate::chat::{self, ChatRequest, ContentPart, IncomingRequest, Message, MessageContent, NormalizedRequest, ReplyText};
+use crate::config::Config;
+use crate::error::{self, MockError};
+use crate::lifecycle::Tracker;
+use crate::overrides::MockOverrides;
+use crate::sse;
+use crate::state::AppState;
+use crate::stubs::ResponseProfile;
+use crate::tokenizer::TokenEncoding;
+
+#[derive(Deserialize)]
+pub struct ResponsesRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub input: Input,
+    /// Sent to the model as a system message ahead of `input`.
+    #[serde(default)]
+    pub instructions: Option<String>,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub max_output_tokens: Option<usize>,
+    /// Only checked for presence, against the key's capabilities.
+    #[serde(default)]
+    pub tools: Vec<serde_json::Value>,
+}
+
+/// A user message as plain text, or a list of input items.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Input {
+    Text(String),
+    Items(Vec<InputItem>),
+}
+
+/// A message of `input`. `type` is optional for messages; items of any
+/// other type carry no role and are skipped.
+#[derive(Deserialize)]
+pub struct InputItem {
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub content: Option<ItemContent>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ItemContent {
+    Text(String),
+    Parts(Vec<InputPart>),
+}
+
+/// `input_text`, `output_text` (in earlier assistant turns) or `input_image`.
+#[derive(Deserialize)]
+pub struct InputPart {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl From<ResponsesRequest> for NormalizedRequest {
+    fn from(req: ResponsesRequest) -> Self {
+        let text = |role: &str, text: String| Message {
+            role: role.to_string(),
+            content: Some(MessageContent::Text(text)),
+            name: None,
+        };
+        let mut messages: Vec<Message> = req.instructions.map(|i| text("system", i)).into_iter().collect();
+        match req.input {
+            Input::Text(input) => messages.push(text("user", input)),
+            Input::Items(items) => messages.extend(
+                items
+                    .into_iter()
+                    .filter(|item| item.kind.as_deref().is_none_or(|kind| kind == "message"))
+                    .filter_map(|item| {
+                        let content = match item.content? {
+                            ItemContent::Text(text) => MessageContent::Text(text),
+                            ItemContent::Parts(parts) => MessageContent::Parts(
+                                parts
+                                    .into_iter()
+                                    .map(|part| ContentPart {
+                                        kind: if part.kind == "input_image" { "image_url" } else { "text" }.to_string(),
+                                        text: part.text,
+                                        image_url: None,
+                                    })
+                                    .collect(),
+                            ),
+                        };
+                        Some(Message {
+                            role: item.role?,
+                            content: Some(content),
+                            name: None,
+                        })
+                    }),
+            ),
+        }
+        NormalizedRequest::from(IncomingRequest::Chat(ChatRequest {
+            model: req.model,
+            messages,
+            stream: Some(req.stream),
+            max_tokens: req.max_output_tokens,
+            tools: req.tools,
+            ..ChatRequest::default()
+        }))
+    }
+}
+
+/// The `response` object, as sent in full at the start and end of a stream
+/// and as the reply to a request that does not stream.
+#[derive(Serialize)]
+struct ResponseObject<'a> {
+    id: &'a str,
+    object: &'static str,
+    created_at: u64,
+    /// `in_progress`, `completed`, `incomplete` or `failed`.
+    status: &'static str,
+    model: Option<&'a str>,
+    output: Vec<OutputMessage<'a>>,
+    usage: Option<ResponseUsage>,
+    error: Option<ResponseFailure<'a>>,
+    incomplete_details: Option<IncompleteDetails>,
+}
+
+#[derive(Serialize)]
+struct OutputMessage<'a> {
+    id: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    status: &'static str,
+    role: &'static str,
+    content: Vec<OutputText<'a>>,
+}
+
+#[derive(Serialize)]
+struct OutputText<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    text: &'a str,
+    annotations: [(); 0],
+}
+
+impl<'a> OutputText<'a> {
+    fn new(text: &'a str) -> OutputText<'a> {
+        OutputText {
+            kind: "output_text",
+            text,
+            annotations: [],
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy)]
+struct ResponseUsage {
+    input_tokens: usize,
+    output_tokens: usize,
+    total_tokens: usize,
+}
+
+#[derive(Serialize)]
+struct ResponseFailure<'a> {
+    code: &'static str,
+    message: &'a str,
+}
+
+#[derive(Serialize)]
+struct IncompleteDetails {
+    reason: &'static str,
+}
+
+/// One streamed event; `type` is also sent as the SSE event name.
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum StreamEvent<'a> {
+    #[serde(rename = "response.created")]
+    Created { response: ResponseObject<'a> },
+    #[serde(rename = "response.in_progress")]
+    InProgress { response: ResponseObject<'a> },
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded { output_index: usize, item: OutputMessage<'a> },
+    #[serde(rename = "response.content_part.added")]
+    ContentPartAdded {
+        item_id: &'a str,
+        output_index: usize,
+        content_index: usize,
+        part: OutputText<'a>,
+    },
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta {
+        item_id: &'a str,
+        output_index: usize,
+        content_index: usize,
+        delta: &'a str,
+    },
+    #[serde(rename = "response.output_text.done")]
+    OutputTextDone {
+        item_id: &'a str,
+        output_index: usize,
+        content_index: usize,
+        text: &'a str,
+    },
+    #[serde(rename = "response.content_part.done")]
+    ContentPartDone {
+        item_id: &'a str,
+        output_index: usize,
+        content_index: usize,
+        part: OutputText<'a>,
+    },
+    #[serde(rename = "response.output_item.done")]
+    OutputItemDone { output_index: usize, item: OutputMessage<'a> },
+    #[serde(rename = "response.completed")]
+    Completed { response: ResponseObject<'a> },
+    #[serde(rename = "response.incomplete")]
+    Incomplete { response: ResponseObject<'a> },
+    #[serde(rename = "response.failed")]
+    Failed { response: ResponseObject<'a> },
+}
+
+impl StreamEvent<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            StreamEvent::Created { .. } => "response.created",
+            StreamEvent::InProgress { .. } => "response.in_progress",
+            StreamEvent::OutputItemAdded { .. } => "response.output_item.added",
+            StreamEvent::ContentPartAdded { .. } => "response.content_part.added",
+            StreamEvent::OutputTextDelta { .. } => "response.output_text.delta",
+            StreamEvent::OutputTextDone { .. } => "response.output_text.done",
+            StreamEvent::ContentPartDone { .. } => "response.content_part.done",
+            StreamEvent::OutputItemDone { .. } => "response.output_item.done",
+            StreamEvent::Completed { .. } => "response.completed",
+            StreamEvent::Incomplete { .. } => "response.incomplete",
+            StreamEvent::Failed { .. } => "response.failed",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Sequenced<'a> {
+    #[serde(flatten)]
+    event: StreamEvent<'a>,
+    sequence_number: u64,
+}
+
+/// What is fixed about a reply from the start: its ids, model and prompt
+/// size, and how it ends if it runs to the end.
+struct Reply {
+    id: String,
+    item_id: String,
+    created_at: u64,
+    model: Option<String>,
+    input_tokens: usize,
+    encoding: TokenEncoding,
+    /// Why the reply stops short, if the profile ends it for `length`.
+    incomplete: Option<&'static str>,
+}
+
+impl Reply {
+    fn message<'a>(&'a self, status: &'static str, text: Option<&'a str>) -> OutputMessage<'a> {
+        OutputMessage {
+            id: &self.item_id,
+            kind: "message",
+            status,
+            role: "assistant",
+            content: text.map(OutputText::new).into_iter().collect(),
+        }
+    }
+
+    fn in_progress(&self) -> ResponseObject<'_> {
+        ResponseObject {
+            id: &self.id,
+            object: "response",
+            created_at: self.created_at,
+            status: "in_progress",
+            model: self.model.as_deref(),
+            output: Vec::new(),
+            usage: None,
+            error: None,
+            incomplete_details: None,
+        }
+    }
+
+    /// The response once `text` is all there will be.
+    fn ended<'a>(&'a self, text: &'a str) -> ResponseObject<'a> {
+        let output_tokens = self.encoding.count(text);
+        ResponseObject {
+            status: if self.incomplete.is_some() { "incomplete" } else { "completed" },
+            output: vec![self.message(if self.incomplete.is_some() { "incomplete" } else { "completed" }, Some(text))],
+            usage: Some(ResponseUsage {
+                input_tokens: self.input_tokens,
+                output_tokens,
+                total_tokens: self.input_tokens + output_tokens,
+            }),
+            incomplete_details: self.incomplete.map(|reason| IncompleteDetails { reason }),
+            ..self.in_progress()
+        }
+    }
+
+    fn failed<'a>(&'a self, message: &'a str) -> ResponseObject<'a> {
+        ResponseObject {
+            status: "failed",
+            error: Some(ResponseFailure {
+                code: "server_error",
+                message,
+            }),
+            ..self.in_progress()
+        }
+    }
+}
+
+#[post("/v1/responses")]
+pub async fn responses_endpoint(
+    http_req: HttpRequest,
+    body: web::Json<ResponsesRequest>,
+    overrides: MockOverrides,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let mut req = NormalizedRequest::from(body.into_inner());
+    error::check_media_type(&http_req)?;
+    let scope = admission::check_caller(&config, &state, &http_req, &req)?;
+    let Recorded { capture_id, request_id } = admission::record(&config, &state, &http_req, &mut req, "input")?;
+    if req.prompt.trim().is_empty() {
+        return Err(MockError::rejected(StatusCode::BAD_REQUEST, "input must include a non-empty user message", Some("input")));
+    }
+    let model_info = req.model.as_deref().and_then(|m| state.models.get(m));
+    if let Some(model) = model_info {
+        chat::check_context_window(model, &req)?;
+    }
+
+    let (mut profile, rule_name, variant) = admission::profile(&config, &state, &req, &overrides, capture_id);
+    let retry_budget = admission::retry_budget(&config, &state, &http_req, &scope, &mut profile);
+    if profile.error_after == Some(0) {
+        let status = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = chat::reject_styled(&config, profile.error_style, status, &profile.error_message, None);
+        if let Some(budget) = &retry_budget {
+            budget.insert_into(response.headers_mut());
+        }
+        profile.headers.insert_into(response.headers_mut());
+        return Ok(response);
+    }
+    let ReplyText { chunks, total_chars, .. } = ReplyText::new(&config, &state, &req, &profile, rule_name.as_deref());
+    let encoding = model_info.map(|m| m.encoding).unwrap_or_default();
+    let charged = admission::reservation(&req, encoding, total_chars);
+    let admission = Admission::charge(&config, &state, &http_req, &scope, &request_id, req.model.as_deref(), charged).await?;
+    let reply = Reply {
+        id: format!("resp_{}", capture_id),
+        item_id: format!("msg_{}", capture_id),
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        model: req.model.clone(),
+        input_tokens: encoding.count_messages(&req.messages),
+        encoding,
+        incomplete: (profile.finish_reason.as_deref() == Some("length")).then_some("max_output_tokens"),
+    };
+    let paced = Paced::new(
+        &config,
+        &profile,
+        encoding,
+        chunks,
+        admission.lease(&state, req.model.as_deref(), profile.weight),
+        Tracker::start(&state, &request_id, req.model.as_deref(), &scope, encoding, &req.messages),
+        StreamRecorder::new(&state, capture_id),
+    );
+
+    let mut response = HttpResponse::Ok();
+    response.insert_header(("x-request-id", request_id));
+    if let Some(name) = rule_name {
+        response.insert_header(("X-Mock-Rule", name));
+    }
+    if let Some(variant) = variant {
+        response.insert_header(("X-Mock-Variant", variant));
+    }
+    admission.insert_headers(&mut response);
+    if let Some(budget) = &retry_budget {
+        budget.insert_headers(&mut response);
+    }
+    profile.headers.insert_headers(&mut response);
+    if !req.stream.unwrap_or(false) {
+        return Ok(match whole_reply(&config, &profile, &reply, paced).await {
+            Ok(body) => response.json(body),
+            // The failure keeps the request id, rule, rate-limit, retry-budget and stub headers.
+            Err(mut failed) => {
+                for (name, value) in response.finish().headers() {
+                    failed.headers_mut().append(name.clone(), value.clone());
+                }
+                failed
+            }
+        });
+    }
+    Ok(response
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream_reply(reply, paced)))
+}
+
+/// The reply as one `response` object, sent when the stream would have
+/// ended. An injected failure fails the request instead, in the profile's
+/// error style.
+async fn whole_reply(config: &Config, profile: &ResponseProfile, reply: &Reply, mut paced: Paced) -> Result<serde_json::Value, HttpResponse> {
+    let mut text = String::new();
+    loop {
+        match paced.next().await {
+            Step::Chunk(chunk) => text.push_str(&chunk),
+            Step::Failed => {
+                let status = StatusCode::from_u16(profile.error_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                return Err(chat::reject_styled(config, profile.error_style, status, &profile.error_message, None));
+            }
+            Step::Done => break,
+        }
+    }
+    serde_json::to_value(reply.ended(&text)).map_err(|e| MockError::Serialize(e.to_string()).error_response())
+}
+
+struct Progress {
+    paced: Paced,
+    text: String,
+    sequence_number: u64,
+    started: bool,
+    finished: bool,
+}
+
+impl Progress {
+    /// `events` as one write, numbered on from the last.
+    fn events(&mut self, events: Vec<StreamEvent>) -> Result<Bytes, MockError> {
+        let mut out = Vec::new();
+        for event in events {
+            let name = event.name();
+            out.extend_from_slice(&sse::named_event(
+                name,
+                &Sequenced {
+                    event,
+                    sequence_number: self.sequence_number,
+                },
+            )?);
+            self.sequence_number += 1;
+        }
+        Ok(Bytes::from(out))
+    }
+}
+
+fn stream_reply(reply: Reply, paced: Paced) -> impl futures::Stream<Item = Result<Bytes, Error>> {
+    let reply = Rc::new(reply);
+    let progress = Progress {
+        paced,
+        text: String::new(),
+        sequence_number: 0,
+        started: false,
+        finished: false,
+    };
+    stream::unfold(progress, move |mut progress| {
+        let reply = reply.clone();
+        async move {
+            if progress.finished {
+                return None;
+            }
+            let (id, output_index, content_index) = (reply.item_id.as_str(), 0, 0);
+            if !progress.started {
+                progress.started = true;
+                let events = progress.events(vec![
+                    StreamEvent::Created { response: reply.in_progress() },
+                    StreamEvent::InProgress { response: reply.in_progress() },
+                    StreamEvent::OutputItemAdded {
+                        output_index,
+                        item: reply.message("in_progress", None),
+                    },
+                    StreamEvent::ContentPartAdded {
+                        item_id: id,
+                        output_index,
+                        content_index,
+                        part: OutputText::new(""),
+                    },
+                ]);
+                return Some((events.map_err(Error::from), progress));
+            }
+            let events = match progress.paced.next().await {
+                Step::Chunk(chunk) => {
+                    progress.text.push_str(&chunk);
+                    progress.events(vec![StreamEvent::OutputTextDelta {
+                        item_id: id,
+                        output_index,
+                        content_index,
+                        delta: &chunk,
+                    }])
+                }
+                Step::Failed => {
+                    progress.finished = true;
+                    let message = progress.paced.error_message().to_string();
+                    progress.events(vec![StreamEvent::Failed {
+                        response: reply.failed(&message),
+                    }])
+                }
+                Step::Done => {
+                    progress.finished = true;
+                    let text = std::mem::take(&mut progress.text);
+                    let status = if reply.incomplete.is_some() { "incomplete" } else { "completed" };
+                    let response = reply.ended(&text);
+                    let last = match reply.incomplete {
+                        Some(_) => StreamEvent::Incomplete { response },
+                        None => StreamEvent::Completed { response },
+                    };
+                    progress.events(vec![
+                        StreamEvent::OutputTextDone {
+                            item_id: id,
+                            output_index,
+                            content_index,
+                            text: &text,
+                        },
+                        StreamEvent::ContentPartDone {
+                            item_id: id,
+                            output_index,
+                            content_index,
+                            part: OutputText::new(&text),
+                        },
+                        StreamEvent::OutputItemDone {
+                            output_index,
+                            item: reply.message(status, Some(&text)),
+                        },
+                        last,
+                    ])
+                }
+            };
+            Some((events.map_err(Error::from), progress))
+        }
+    })
+}
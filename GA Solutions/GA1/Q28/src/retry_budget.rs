@@ -0,0 +1,121 @@
+//! Simulated retry budgets (`[retry_budget]`), after Finagle's: each key
+//! earns a share of a retry for every first attempt it sends, plus a slow
+//! trickle, and every failure the fault injector hands it is paid for from
+//! that balance. Once the balance runs out the injector holds back, so a
+//! client retrying within its budget always gets through, and
+//! `x-mock-retry-budget-remaining` tells it how many more injected failures,
+//! and so retries, to expect.
+//!
+//! Budgets belong to accounts the server can vouch for: the organization
+//! and project when the request has one, else a listed key. Unlisted keys
+//! and requests without one share a single budget, so making up a new token
+//! does not buy a fresh balance. Balances that have refilled, or gone
+//! untouched for `IDLE_AFTER`, are dropped once many accounts are tracked.
+//!
+//! Requests carrying a positive retry count (`x-stainless-retry-count` by
+//! default, as the OpenAI SDKs send) earn nothing. Only a stub's injected
+//! failures (`error_after`) are paid for; 429 storms are server-wide and
+//! ignore budgets.
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{HttpRequest, HttpResponseBuilder};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::RetryBudgetConfig;
+
+pub const REMAINING_HEADER: &str = "x-mock-retry-budget-remaining";
+/// Accounts tracked before idle ones are dropped to make room for a new one.
+const PRUNE_ABOVE: usize = 1024;
+/// How long an account's balance is kept without a request, even if it
+/// has not refilled; the account then starts over with a full budget.
+const IDLE_AFTER: Duration = Duration::from_secs(600);
+
+/// A key's budget after a request was admitted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryBudgetStatus {
+    /// Whole retries left in the budget.
+    pub remaining: u64,
+    /// Whether the injector may fail the request, having paid for the retry.
+    pub fault_allowed: bool,
+}
+
+impl RetryBudgetStatus {
+    pub fn insert_headers(&self, response: &mut HttpResponseBuilder) {
+        response.insert_header((REMAINING_HEADER, self.remaining.to_string()));
+    }
+
+    /// `insert_headers` for a response that is already built.
+    pub fn insert_into(&self, headers: &mut HeaderMap) {
+        headers.insert(HeaderName::from_static(REMAINING_HEADER), HeaderValue::from(self.remaining));
+    }
+}
+
+struct Balance {
+    retries: f64,
+    updated: Instant,
+}
+
+impl Balance {
+    /// Whether forgetting the balance at `now` would change nothing, or it
+    /// has been unused for long enough not to matter.
+    fn idle_at(&self, now: Instant, config: &RetryBudgetConfig) -> bool {
+        let idle = now.duration_since(self.updated);
+        idle >= IDLE_AFTER || self.retries + idle.as_secs_f64() * config.min_retries_per_sec >= config.max_retries
+    }
+}
+
+pub struct RetryBudgets {
+    config: RetryBudgetConfig,
+    accounts: Mutex<HashMap<String, Balance>>,
+}
+
+impl RetryBudgets {
+    pub fn new(config: &RetryBudgetConfig) -> RetryBudgets {
+        RetryBudgets {
+            config: config.clone(),
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `req` says it is a retry.
+    pub fn is_retry(&self, req: &HttpRequest) -> bool {
+        req.headers()
+            .get(self.config.retry_header.as_str())
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .is_some_and(|attempt| attempt > 0)
+    }
+
+    /// Admits a request from `account`, `""` for one the server cannot vouch
+    /// for: a first attempt earns its share of
+    /// a retry, and a request the injector `wants_fault` on pays for one if
+    /// the balance allows. `None` when budgets are off.
+    pub fn admit(&self, account: &str, retry: bool, wants_fault: bool) -> Option<RetryBudgetStatus> {
+        if !self.config.enabled {
+            return None;
+        }
+        let now = Instant::now();
+        let max = self.config.max_retries;
+        let mut accounts = self.accounts.lock().unwrap_or_else(|e| e.into_inner());
+        if accounts.len() >= PRUNE_ABOVE && !accounts.contains_key(account) {
+            accounts.retain(|_, balance| !balance.idle_at(now, &self.config));
+        }
+        let balance = accounts.entry(account.to_string()).or_insert(Balance { retries: max, updated: now });
+        let trickle = now.duration_since(balance.updated).as_secs_f64() * self.config.min_retries_per_sec;
+        balance.retries = (balance.retries + trickle).min(max);
+        balance.updated = now;
+        if !retry {
+            balance.retries = (balance.retries + self.config.percent_can_retry / 100.0).min(max);
+        }
+        let fault_allowed = wants_fault && balance.retries >= 1.0;
+        if fault_allowed {
+            balance.retries -= 1.0;
+        }
+        Some(RetryBudgetStatus {
+            remaining: balance.retries.floor() as u64,
+            fault_allowed,
+        })
+    }
+}
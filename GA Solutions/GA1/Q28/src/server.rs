@@ -0,0 +1,124 @@
+use actix_web::dev::Server;
+use actix_web::http::KeepAlive;
+use actix_web::{middleware, web, App, HttpServer};
+use socket2::{Domain, Socket, Type};
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::config::{Config, ServerConfig};
+use crate::error;
+use crate::state::AppState;
+
+/// Binds `server.bind` with the configured listen backlog.
+pub fn bind(config: &ServerConfig) -> io::Result<TcpListener> {
+    let addr: SocketAddr = config
+        .bind
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("cannot resolve {}", config.bind)))?;
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    socket.set_reuse_address(true)?;
+    if config.reuse_port {
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        #[cfg(not(unix))]
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "server.reuse_port needs SO_REUSEPORT, which is Unix only"));
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(config.backlog.min(i32::MAX as u32) as i32)?;
+    Ok(socket.into())
+}
+
+/// Sends every new connection on the port to `listener`, the newest socket in
+/// its `SO_REUSEPORT` group, so the server it is replacing gets no more before
+/// it closes its own listener; Linux resets connections still queued on a
+/// listener when it closes. Elsewhere the kernel keeps spreading connections
+/// over both sockets, and those queued on the old one when it stops are reset.
+#[cfg(target_os = "linux")]
+pub fn steer_new_connections(listener: &TcpListener) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    // From <asm-generic/socket.h>; libc does not export it for Linux.
+    const SO_ATTACH_REUSEPORT_CBPF: libc::c_int = 51;
+    // Picks socket 1, this one, while the old server's socket 0 is in the
+    // group. Once that closes this one becomes socket 0, and the out-of-range
+    // pick falls back to the kernel's usual hashing.
+    let mut program = [libc::sock_filter {
+        code: (libc::BPF_RET | libc::BPF_K) as u16,
+        jt: 0,
+        jf: 0,
+        k: 1,
+    }];
+    let fprog = libc::sock_fprog {
+        len: program.len() as u16,
+        filter: program.as_mut_ptr(),
+    };
+    // SAFETY: `fprog` and the program it points to outlive the call, which
+    // copies them into the kernel.
+    let attached = unsafe {
+        libc::setsockopt(
+            listener.as_raw_fd(),
+            libc::SOL_SOCKET,
+            SO_ATTACH_REUSEPORT_CBPF,
+            (&fprog as *const libc::sock_fprog).cast(),
+            std::mem::size_of::<libc::sock_fprog>() as libc::socklen_t,
+        )
+    };
+    if attached == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn steer_new_connections(_listener: &TcpListener) -> io::Result<()> {
+    Ok(())
+}
+
+/// Takes the listening socket systemd passed in with socket activation
+/// (`LISTEN_FDS`), if there is one. `server.bind` and `server.backlog` do not
+/// apply to it; the socket unit's `ListenStream=` and `Backlog=` do.
+pub fn activated() -> io::Result<Option<TcpListener>> {
+    listenfd::ListenFd::from_env().take_tcp_listener(0)
+}
+
+/// Builds the HTTP server on an already-bound listener. Binding is left to the
+/// caller so tests and alternative launchers can choose the socket.
+pub fn serve(config: Config, listener: TcpListener) -> io::Result<Server> {
+    let tuning = config.server.clone();
+    let state = AppState::new(&config);
+    #[cfg(feature = "generators")]
+    let state = AppState {
+        corpus: crate::corpus::open(&config.corpus)?,
+        ..state
+    };
+    #[cfg(not(feature = "generators"))]
+    if config.corpus.path.is_some() {
+        log::warn!("this build has no generators feature, so [corpus] is ignored");
+    }
+    let state = web::Data::new(state);
+    let config = web::Data::new(config);
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .app_data(config.clone())
+            .app_data(state.clone())
+            .wrap(middleware::from_fn(crate::timeouts::enforce))
+            .wrap(middleware::from_fn(crate::panics::catch))
+            .wrap(middleware::Logger::default())
+            .configure(crate::configure)
+            // Replaces the default-limit JSON config `configure` registers.
+            .app_data(error::json_config().limit(config.request.max_body_bytes))
+    })
+    .keep_alive(match tuning.keep_alive_secs {
+        0 => KeepAlive::Disabled,
+        secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+    })
+    .client_request_timeout(Duration::from_millis(tuning.client_request_timeout_ms))
+    .client_disconnect_timeout(Duration::from_millis(tuning.client_disconnect_timeout_ms))
+    .shutdown_timeout(tuning.shutdown_timeout_secs);
+    if let Some(workers) = tuning.workers {
+        server = server.workers(workers);
+    }
+    Ok(server.listen(listener)?.run())
+}
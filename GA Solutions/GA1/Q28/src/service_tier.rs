@@ -0,0 +1,53 @@
+//! OpenAI's `service_tier` request parameter, for clients that choose a
+//! tier per request. Each tier scales the reply's pacing by its
+//! `latency_factor` in `[service_tiers]`: by default `flex` streams three
+//! times slower than `default`, and `priority` twice as fast. Replies echo
+//! the tier served, on every chunk of a stream and on a JSON reply, when the
+//! request named one.
+
+use serde::Deserialize;
+
+use crate::config::{ServiceTierSettings, ServiceTiersConfig};
+use crate::stubs::ResponseProfile;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceTier {
+    /// Served as `default`, as OpenAI serves projects without Scale Tier.
+    Auto,
+    Default,
+    Flex,
+    Priority,
+}
+
+impl ServiceTier {
+    /// The tier served, as echoed in replies.
+    pub fn name(self) -> &'static str {
+        match self {
+            ServiceTier::Auto | ServiceTier::Default => "default",
+            ServiceTier::Flex => "flex",
+            ServiceTier::Priority => "priority",
+        }
+    }
+}
+
+impl ServiceTiersConfig {
+    pub fn get(&self, tier: ServiceTier) -> &ServiceTierSettings {
+        match tier {
+            ServiceTier::Auto | ServiceTier::Default => &self.default,
+            ServiceTier::Flex => &self.flex,
+            ServiceTier::Priority => &self.priority,
+        }
+    }
+}
+
+impl ServiceTierSettings {
+    /// Scales `profile`'s delays, and divides its token rate, by the factor.
+    pub fn apply(&self, profile: &mut ResponseProfile) {
+        let factor = self.latency_factor;
+        let scale = move |ms: u64| (ms as f64 * factor).round() as u64;
+        profile.first_chunk_delay_ms = profile.first_chunk_delay_ms.map(scale);
+        profile.chunk_delay_ms = scale(profile.chunk_delay_ms);
+        profile.tokens_per_sec = profile.tokens_per_sec.map(|rate| rate / factor);
+    }
+}
@@ -0,0 +1,500 @@
+//! Shadow traffic (`[shadow]`): each chat request is mirrored to a secondary
+//! target, either an OpenAI-compatible `url` or a second local `profile`, and
+//! the two replies are compared once both have finished: token counts,
+//! time to first chunk and total latency, and how similar the text is. The
+//! comparisons are kept, newest last, for `/v1/internal/shadow`, and logged.
+//!
+//! Neither reply is held whole. Each is digested as it streams: its counts,
+//! word frequencies, SimHash, hashes of each block of `BLOCK_CHARS`
+//! characters and its first `MAX_CONTENT_CHARS` characters. The token
+//! alignment covers those first characters only, and past them a
+//! divergence is placed at the start of the first block that differs.
+//!
+//! The mirror runs in the background and never holds up or changes the
+//! reply the client gets. At most `max_in_flight` mirrors run at once;
+//! requests beyond that are not mirrored. Mirroring to a `url` needs the
+//! `shadow` feature, and keeping comparisons the `recording` feature; other
+//! builds only log them.
+
+use actix_web::{delete, get, web, HttpResponse};
+use futures::channel::oneshot;
+use futures::FutureExt;
+#[cfg(feature = "shadow")]
+use futures::StreamExt;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+use crate::chat::{NormalizedRequest, ReplyText};
+use crate::config::{Config, ShadowConfig, StreamConfig};
+use crate::diffing::{self, SimHasher};
+use crate::generator;
+use crate::lifecycle;
+use crate::metrics;
+use crate::models::ModelInfo;
+use crate::pacer::{self, Pace};
+#[cfg(feature = "shadow")]
+use crate::sse;
+use crate::state::AppState;
+use crate::stubs::ResponseProfile;
+use crate::tokenizer::{self, TokenEncoding};
+
+/// Reply text kept in a comparison for each side, and aligned token by token.
+const MAX_CONTENT_CHARS: usize = 4096;
+/// Past `MAX_CONTENT_CHARS`, replies are compared a block of this many
+/// characters at a time.
+const BLOCK_CHARS: usize = 1024;
+/// Text without a place to split tokens is counted anyway once this long,
+/// so a reply without spaces is not held whole.
+const MAX_PENDING_BYTES: usize = 1024;
+
+/// One mirrored request and how the two replies differed.
+#[derive(Serialize, Clone, Debug)]
+pub struct ShadowDiff {
+    pub request_id: String,
+    pub model: Option<String>,
+    pub received_at_ms: u64,
+    /// `"url"` or `"profile"`.
+    pub target: &'static str,
+    pub primary: Side,
+    pub shadow: Side,
+    /// Sørensen–Dice coefficient of the two replies' words: 1 for the same
+    /// words in any order, 0 for none in common.
+    pub similarity: f64,
+    /// Shared tokens in order, as `diffing::TokenDiff::ratio`, over each
+    /// side's `content`.
+    pub token_ratio: f64,
+    /// Bits the replies' SimHashes differ in, out of 64; see `diffing`.
+    pub semantic_distance: u32,
+    pub identical: bool,
+    /// Character offset of the first difference, unless identical. Past
+    /// `content`, the start of the block of 1024 characters it is in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diverges_at: Option<usize>,
+}
+
+/// One reply as a comparison sees it.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Side {
+    pub tokens: usize,
+    pub chars: usize,
+    /// Time from the request to the first chunk of reply text.
+    pub first_chunk_ms: Option<u64>,
+    pub duration_ms: u64,
+    /// Whether the reply ran to its end.
+    pub finished: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The reply text, up to 4096 characters.
+    pub content: String,
+}
+
+impl Side {
+    fn new(digest: &Digest, first_chunk: Option<Duration>, duration: Duration, error: Option<String>) -> Side {
+        Side {
+            tokens: digest.tokens,
+            chars: digest.chars,
+            first_chunk_ms: first_chunk.map(|d| d.as_millis() as u64),
+            duration_ms: duration.as_millis() as u64,
+            finished: error.is_none(),
+            error,
+            content: digest.content.clone(),
+        }
+    }
+}
+
+/// What a comparison needs of one reply, taken in as it streams.
+#[derive(Default)]
+struct Digest {
+    encoding: TokenEncoding,
+    /// The first `MAX_CONTENT_CHARS` characters.
+    content: String,
+    chars: usize,
+    tokens: usize,
+    /// Text after the last place to split tokens, taken in with the next chunk.
+    pending: String,
+    words: HashMap<String, usize>,
+    simhash: SimHasher,
+    /// The hash of each full block of `BLOCK_CHARS` characters, then of the
+    /// last, partial one once finished.
+    blocks: Vec<u64>,
+    block: String,
+}
+
+impl Digest {
+    fn new(encoding: TokenEncoding) -> Digest {
+        Digest {
+            encoding,
+            ..Digest::default()
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.chars < MAX_CONTENT_CHARS {
+                self.content.push(c);
+            }
+            self.chars += 1;
+            self.block.push(c);
+            if self.chars.is_multiple_of(BLOCK_CHARS) {
+                self.blocks.push(generator::stable_hash([self.block.as_str()]));
+                self.block.clear();
+            }
+        }
+        self.pending.push_str(text);
+        let split = tokenizer::token_boundary(&self.pending).or((self.pending.len() > MAX_PENDING_BYTES).then_some(self.pending.len()));
+        if let Some(split) = split {
+            let words: String = self.pending.drain(..split).collect();
+            self.take(&words);
+        }
+    }
+
+    /// Takes in the text still pending.
+    fn finish(&mut self) {
+        let rest = std::mem::take(&mut self.pending);
+        self.take(&rest);
+        if !self.block.is_empty() {
+            self.blocks.push(generator::stable_hash([self.block.as_str()]));
+            self.block.clear();
+        }
+    }
+
+    /// Counts `text`, which ends where a word does.
+    fn take(&mut self, text: &str) {
+        self.tokens += self.encoding.count(text);
+        for word in text.split_whitespace() {
+            *self.words.entry(word.to_string()).or_default() += 1;
+        }
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            self.simhash.push(word);
+        }
+    }
+
+    /// Where `self` and `other` first differ, if they do.
+    fn diverges_from(&self, other: &Digest) -> Option<usize> {
+        if self.chars == other.chars && self.blocks == other.blocks {
+            return None;
+        }
+        let kept = self.content.chars().zip(other.content.chars()).position(|(x, y)| x != y);
+        kept.or_else(|| {
+            let shorter = self.chars.min(other.chars);
+            if shorter <= MAX_CONTENT_CHARS {
+                return Some(shorter);
+            }
+            let block = self.blocks.iter().zip(&other.blocks).position(|(x, y)| x != y);
+            Some(block.map_or(shorter, |i| (i * BLOCK_CHARS).max(MAX_CONTENT_CHARS)))
+        })
+    }
+}
+
+/// Where mirrored requests go.
+enum Target {
+    #[cfg(feature = "shadow")]
+    Url {
+        client: reqwest::Client,
+        url: String,
+        api_key: Option<String>,
+        timeout: Duration,
+    },
+    Profile(Box<ResponseProfile>),
+}
+
+pub struct Shadow {
+    target: Option<Target>,
+    limit: usize,
+    /// A permit for each mirror that may run at once.
+    in_flight: Arc<Semaphore>,
+    diffs: Mutex<VecDeque<ShadowDiff>>,
+}
+
+impl Shadow {
+    pub fn new(config: &ShadowConfig) -> Shadow {
+        if config.url.is_some() && config.profile.is_some() {
+            log::warn!("[shadow] sets both url and profile; mirroring to the url");
+        }
+        let target = match (&config.url, &config.profile) {
+            #[cfg(feature = "shadow")]
+            (Some(url), _) => Some(Target::Url {
+                client: reqwest::Client::new(),
+                url: url.clone(),
+                api_key: config.api_key.clone(),
+                timeout: Duration::from_millis(config.timeout_ms),
+            }),
+            #[cfg(not(feature = "shadow"))]
+            (Some(_), _) => {
+                log::warn!("built without the shadow feature; requests will not be mirrored to the [shadow] url");
+                None
+            }
+            (None, Some(profile)) => Some(Target::Profile(Box::new(profile.clone()))),
+            (None, None) => None,
+        };
+        Shadow {
+            target,
+            // Without the `recording` feature nothing is kept, as with `limit = 0`.
+            limit: if cfg!(feature = "recording") { config.limit } else { 0 },
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight)),
+            diffs: Mutex::default(),
+        }
+    }
+
+    /// Starts mirroring `req` and returns the probe that follows its primary
+    /// reply, or `None` when shadowing is off or `max_in_flight` mirrors are
+    /// already running.
+    pub fn mirror(
+        &self,
+        config: &Config,
+        state: &web::Data<AppState>,
+        req: &NormalizedRequest,
+        model: Option<&ModelInfo>,
+        request_id: &str,
+    ) -> Option<ShadowProbe> {
+        let target = self.target.as_ref()?;
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            log::debug!("shadow: too many mirrors running, not mirroring {}", request_id);
+            return None;
+        };
+        let encoding = model.map(|m| m.encoding).unwrap_or_default();
+        let started = Instant::now();
+        let (name, run) = match target {
+            #[cfg(feature = "shadow")]
+            Target::Url {
+                client,
+                url,
+                api_key,
+                timeout,
+            } => (
+                "url",
+                fetch(client.clone(), url.clone(), api_key.clone(), *timeout, upstream_body(req), encoding, started).boxed_local(),
+            ),
+            Target::Profile(profile) => {
+                let chunks = ReplyText::new(config, state, req, profile, None).chunks;
+                ("profile", replay(chunks, (**profile).clone(), encoding, config.stream.clone(), started).boxed_local())
+            }
+        };
+        let (send, primary) = oneshot::channel();
+        let state = state.clone();
+        let request_id = request_id.to_string();
+        let model = req.model.clone();
+        let received_at_ms = lifecycle::now_ms();
+        metrics::spawn(async move {
+            let _permit = permit;
+            let (mut digest, first_chunk, duration, error) = run.await;
+            digest.finish();
+            let shadow = (Side::new(&digest, first_chunk, duration, error), digest);
+            let Ok(primary) = primary.await else {
+                return;
+            };
+            state.shadow.record(ShadowDiff::new(request_id, model, received_at_ms, name, primary, shadow));
+        });
+        Some(ShadowProbe {
+            started,
+            progress: RefCell::new(Some((Digest::new(encoding), None))),
+            send: RefCell::new(Some(send)),
+        })
+    }
+
+    fn record(&self, diff: ShadowDiff) {
+        log::info!(
+            "shadow {}: {} vs {} tokens, {} vs {} ms, similarity {:.3}",
+            diff.request_id,
+            diff.primary.tokens,
+            diff.shadow.tokens,
+            diff.primary.duration_ms,
+            diff.shadow.duration_ms,
+            diff.similarity
+        );
+        if self.limit == 0 {
+            return;
+        }
+        let mut diffs = self.lock();
+        if diffs.len() == self.limit {
+            diffs.pop_front();
+        }
+        diffs.push_back(diff);
+    }
+
+    /// Every retained comparison, oldest first.
+    pub fn list(&self) -> Vec<ShadowDiff> {
+        self.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<ShadowDiff>> {
+        self.diffs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Follows the primary reply of a mirrored request. Its outcome is compared
+/// once `finish` is called, or when it is dropped, as an unfinished reply.
+pub struct ShadowProbe {
+    started: Instant,
+    /// The reply so far and when it started; `None` once reported.
+    progress: RefCell<Option<(Digest, Option<Duration>)>>,
+    send: RefCell<Option<oneshot::Sender<(Side, Digest)>>>,
+}
+
+impl ShadowProbe {
+    pub fn chunk(&self, text: &str) {
+        if let Some((digest, first_chunk)) = self.progress.borrow_mut().as_mut() {
+            first_chunk.get_or_insert_with(|| self.started.elapsed());
+            digest.push(text);
+        }
+    }
+
+    /// Reports the reply, `finished` if it ran to its end. Later calls and
+    /// chunks are ignored.
+    pub fn finish(&self, finished: bool) {
+        let (Some((mut digest, first_chunk)), Some(send)) = (self.progress.take(), self.send.take()) else {
+            return;
+        };
+        digest.finish();
+        let error = (!finished).then(|| "the reply did not finish".to_string());
+        let _ = send.send((Side::new(&digest, first_chunk, self.started.elapsed(), error), digest));
+    }
+}
+
+impl Drop for ShadowProbe {
+    fn drop(&mut self) {
+        self.finish(false);
+    }
+}
+
+impl ShadowDiff {
+    fn new(
+        request_id: String,
+        model: Option<String>,
+        received_at_ms: u64,
+        target: &'static str,
+        (primary, a): (Side, Digest),
+        (shadow, b): (Side, Digest),
+    ) -> ShadowDiff {
+        let diverges_at = a.diverges_from(&b);
+        ShadowDiff {
+            request_id,
+            model,
+            received_at_ms,
+            target,
+            similarity: diffing::dice(&a.words, &b.words),
+            token_ratio: diffing::token_diff(&primary.content, &shadow.content).ratio,
+            semantic_distance: (a.simhash.finish() ^ b.simhash.finish()).count_ones(),
+            identical: diverges_at.is_none(),
+            diverges_at,
+            primary,
+            shadow,
+        }
+    }
+}
+
+/// What a mirror produced: its reply, time to first chunk, total time and
+/// why it stopped early, if it did.
+type Outcome = (Digest, Option<Duration>, Duration, Option<String>);
+
+/// Plays a local profile's reply at its pace.
+async fn replay(
+    chunks: Box<dyn Iterator<Item = String>>,
+    profile: ResponseProfile,
+    encoding: TokenEncoding,
+    stream: StreamConfig,
+    started: Instant,
+) -> Outcome {
+    let pace = Pace::new(&profile, encoding);
+    let (mut text, mut first_chunk) = (Digest::new(encoding), None);
+    for (i, chunk) in chunks.enumerate() {
+        if profile.error_after == Some(i) {
+            return (text, first_chunk, started.elapsed(), Some(profile.error_message));
+        }
+        let delay = pace.before(&chunk, i == 0);
+        if !delay.is_zero() {
+            pacer::sleep(delay, &stream).await;
+        }
+        first_chunk.get_or_insert_with(|| started.elapsed());
+        text.push(&chunk);
+    }
+    (text, first_chunk, started.elapsed(), None)
+}
+
+/// The request as sent upstream: the fields that shape the reply, streamed.
+#[cfg(feature = "shadow")]
+fn upstream_body(req: &NormalizedRequest) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "messages": req.messages,
+        "stream": true,
+        "seed": req.seed,
+    });
+    if let Some(model) = &req.model {
+        body["model"] = model.as_str().into();
+    }
+    if let Some(max_tokens) = req.max_tokens {
+        body["max_tokens"] = max_tokens.into();
+    }
+    body
+}
+
+/// Streams the reply from an OpenAI-compatible upstream, collecting the
+/// content deltas of its SSE events.
+#[cfg(feature = "shadow")]
+async fn fetch(
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    timeout: Duration,
+    body: serde_json::Value,
+    encoding: TokenEncoding,
+    started: Instant,
+) -> Outcome {
+    let (mut text, mut first_chunk) = (Digest::new(encoding), None);
+    let mut request = client.post(&url).timeout(timeout).json(&body);
+    if let Some(key) = &api_key {
+        request = request.bearer_auth(key);
+    }
+    let response = match request.send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => return (text, None, started.elapsed(), Some(format!("upstream answered {}", response.status()))),
+        Err(e) => return (text, None, started.elapsed(), Some(e.to_string())),
+    };
+    let mut bytes = response.bytes_stream();
+    let mut parser = sse::Parser::new();
+    while let Some(read) = bytes.next().await {
+        let read = match read {
+            Ok(read) => read,
+            Err(e) => return (text, first_chunk, started.elapsed(), Some(e.to_string())),
+        };
+        for event in parser.push(&read) {
+            if event.is_done() {
+                return (text, first_chunk, started.elapsed(), None);
+            }
+            let delta = serde_json::from_str::<serde_json::Value>(&event.data)
+                .ok()
+                .and_then(|event| event["choices"][0]["delta"]["content"].as_str().map(str::to_string));
+            if let Some(delta) = delta.filter(|d| !d.is_empty()) {
+                first_chunk.get_or_insert_with(|| started.elapsed());
+                text.push(&delta);
+            }
+        }
+    }
+    (text, first_chunk, started.elapsed(), Some("upstream ended without [DONE]".to_string()))
+}
+
+#[derive(Serialize)]
+struct ShadowList {
+    data: Vec<ShadowDiff>,
+}
+
+#[get("/v1/internal/shadow")]
+pub async fn list_endpoint(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(ShadowList {
+        data: state.shadow.list(),
+    })
+}
+
+#[delete("/v1/internal/shadow")]
+pub async fn clear_endpoint(state: web::Data<AppState>) -> HttpResponse {
+    state.shadow.clear();
+    HttpResponse::NoContent().finish()
+}
@@ -0,0 +1,410 @@
+//! Server-sent events: framing what the mock sends, and `Parser` for
+//! reading any SSE stream back, the mock's own included.
+
+use actix_web::Error;
+use bytes::Bytes;
+use futures::stream::{self, LocalBoxStream, Stream, StreamExt};
+use serde::Serialize;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::error::MockError;
+use crate::pool;
+use crate::stubs::{FrameFault, WriteSplit};
+
+const DATA_PREFIX: &[u8] = b"data: ";
+const EVENT_END: &[u8] = b"\n\n";
+
+/// Appends `value` as JSON using the serializer selected at build time:
+/// sonic-rs with the `simd-json` feature, serde_json otherwise.
+pub fn write_json<T: Serialize>(out: &mut Vec<u8>, value: &T) -> Result<(), MockError> {
+    #[cfg(feature = "simd-json")]
+    let result = sonic_rs::to_writer(out, value);
+    #[cfg(not(feature = "simd-json"))]
+    let result = serde_json::to_writer(out, value);
+    result.map_err(|e| MockError::Serialize(e.to_string()))
+}
+
+/// Serializes `value` straight into a pooled buffer framed as one SSE `data:` event.
+pub fn data_event<T: Serialize>(value: &T) -> Result<Bytes, MockError> {
+    let mut buf = pool::take();
+    buf.extend_from_slice(DATA_PREFIX);
+    write_json(&mut buf, value)?;
+    buf.extend_from_slice(EVENT_END);
+    Ok(pool::freeze(buf))
+}
+
+/// Like `data_event`, with an `event: <name>` line first so standard clients
+/// dispatch it apart from unnamed `message` events.
+pub fn named_event<T: Serialize>(name: &str, value: &T) -> Result<Bytes, MockError> {
+    let mut buf = pool::take();
+    buf.extend_from_slice(b"event: ");
+    buf.extend_from_slice(name.as_bytes());
+    buf.push(b'\n');
+    buf.extend_from_slice(DATA_PREFIX);
+    write_json(&mut buf, value)?;
+    buf.extend_from_slice(EVENT_END);
+    Ok(pool::freeze(buf))
+}
+
+/// A `data:` event pre-serialized around a single JSON string field.
+///
+/// Chunk envelopes differ only in their `content` string, so the bytes on
+/// either side of it are rendered once per request and each chunk only costs
+/// an escape-and-copy of its text.
+pub struct FrameTemplate {
+    prefix: Vec<u8>,
+    suffix: Vec<u8>,
+}
+
+impl FrameTemplate {
+    /// Serializes `envelope`, whose spliced string field must hold exactly
+    /// `placeholder`, and splits the output around it. `placeholder` must not
+    /// need JSON escaping and must not occur anywhere else in the envelope.
+    pub fn new<T: Serialize>(envelope: &T, placeholder: &str) -> Option<FrameTemplate> {
+        let mut json = Vec::new();
+        write_json(&mut json, envelope).ok()?;
+        let at = json.windows(placeholder.len()).position(|w| w == placeholder.as_bytes())?;
+
+        let mut prefix = Vec::with_capacity(DATA_PREFIX.len() + at);
+        prefix.extend_from_slice(DATA_PREFIX);
+        prefix.extend_from_slice(&json[..at]);
+        let mut suffix = json[at + placeholder.len()..].to_vec();
+        suffix.extend_from_slice(EVENT_END);
+        Some(FrameTemplate { prefix, suffix })
+    }
+
+    pub fn render(&self, content: &str) -> Bytes {
+        let mut buf = pool::take();
+        buf.reserve(self.prefix.len() + content.len() + self.suffix.len());
+        buf.extend_from_slice(&self.prefix);
+        escape_json_into(content, &mut buf);
+        buf.extend_from_slice(&self.suffix);
+        pool::freeze(buf)
+    }
+}
+
+/// Appends `s` to `out` escaped for use inside a JSON string literal, exactly
+/// as `serde_json` escapes it: quotes, backslashes and control characters are
+/// escaped and everything else, including non-ASCII, is copied verbatim.
+pub fn escape_json_into(s: &str, out: &mut Vec<u8>) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let bytes = s.as_bytes();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b >= 0x20 && b != b'"' && b != b'\\' {
+            continue;
+        }
+        out.extend_from_slice(&bytes[start..i]);
+        match b {
+            b'"' => out.extend_from_slice(b"\\\""),
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            0x08 => out.extend_from_slice(b"\\b"),
+            0x0c => out.extend_from_slice(b"\\f"),
+            _ => out.extend_from_slice(&[b'\\', b'u', b'0', b'0', HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]]),
+        }
+        start = i + 1;
+    }
+    out.extend_from_slice(&bytes[start..]);
+}
+
+/// Prefixes every event with an `id:` line, counting up from `after + 1`, so
+/// clients can resume with `Last-Event-ID` and tests can check ordering.
+pub fn number_events<S>(events: S, after: usize) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>>,
+{
+    events.enumerate().map(move |(i, event)| {
+        let event = event?;
+        let mut buf = pool::take();
+        // Writing into a Vec cannot fail.
+        let _ = writeln!(buf, "id: {}", after + i + 1);
+        buf.extend_from_slice(&event);
+        Ok(pool::freeze(buf))
+    })
+}
+
+/// Offsets at which `event` is cut under `split`, ascending and excluding 0
+/// and `event.len()`.
+pub fn split_points(event: &[u8], split: WriteSplit) -> Vec<usize> {
+    let cut_after = |keep: fn(u8) -> bool| -> Vec<usize> {
+        (0..event.len().saturating_sub(1)).filter(|&i| keep(event[i])).map(|i| i + 1).collect()
+    };
+    match split {
+        WriteSplit::Off => Vec::new(),
+        WriteSplit::MidEscape => cut_after(|b| b == b'\\'),
+        // A leading byte of a multi-byte character always has a continuation byte after it.
+        WriteSplit::MidUtf8 => cut_after(|b| b >= 0xc0),
+        WriteSplit::EveryByte => (1..event.len()).collect(),
+    }
+}
+
+/// Re-emits every event as the pieces `split` cuts it into, pausing between
+/// pieces so each reaches the socket in a write of its own.
+pub fn split_writes<S>(events: S, split: WriteSplit, pause: Duration) -> LocalBoxStream<'static, Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + 'static,
+{
+    if split == WriteSplit::Off {
+        return events.boxed_local();
+    }
+    events
+        .flat_map(move |event| {
+            let pieces = match event {
+                Ok(event) => {
+                    let mut start = 0;
+                    let mut pieces = Vec::new();
+                    for at in split_points(&event, split).into_iter().chain([event.len()]) {
+                        pieces.push(Ok(event.slice(start..at)));
+                        start = at;
+                    }
+                    pieces
+                }
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(pieces.into_iter().enumerate()).then(move |(i, piece)| async move {
+                if i > 0 {
+                    if pause.is_zero() {
+                        tokio::task::yield_now().await;
+                    } else {
+                        tokio::time::sleep(pause).await;
+                    }
+                }
+                piece
+            })
+        })
+        .boxed_local()
+}
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Rewrites `event` with the line endings `faults` ask for.
+fn reframe(event: &Bytes, faults: &[FrameFault]) -> Bytes {
+    let ending: &[u8] = if faults.contains(&FrameFault::Crlf) {
+        b"\r\n"
+    } else if faults.contains(&FrameFault::Cr) {
+        b"\r"
+    } else {
+        return event.clone();
+    };
+    let mut buf = pool::take();
+    for &b in event.iter() {
+        if b == b'\n' {
+            buf.extend_from_slice(ending);
+        } else {
+            buf.push(b);
+        }
+    }
+    pool::freeze(buf)
+}
+
+/// Applies `faults` to the framing of `events`. Event payloads are JSON,
+/// whose newlines are always escaped, so only the framing is affected.
+pub fn corrupt_frames<S>(events: S, faults: Vec<FrameFault>) -> LocalBoxStream<'static, Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + 'static,
+{
+    if faults.is_empty() {
+        return events.boxed_local();
+    }
+    let truncate_last = faults.contains(&FrameFault::MissingFinalBlankLine);
+    let mut bom = faults.contains(&FrameFault::Bom);
+    let reframed = events.map(move |event| {
+        let event = reframe(&event?, &faults);
+        if !std::mem::take(&mut bom) {
+            return Ok(event);
+        }
+        let mut buf = pool::take();
+        buf.extend_from_slice(BOM);
+        buf.extend_from_slice(&event);
+        Ok(pool::freeze(buf))
+    });
+    if !truncate_last {
+        return reframed.boxed_local();
+    }
+    // Hold each event back until the next arrives, so the last one is known.
+    stream::unfold((reframed.boxed_local(), Lookahead::Start), |(mut events, ahead)| async move {
+        let current = match ahead {
+            Lookahead::Start => events.next().await?,
+            Lookahead::Held(event) => event,
+            Lookahead::Ended => return None,
+        };
+        match events.next().await {
+            Some(next) => Some((current, (events, Lookahead::Held(next)))),
+            None => Some((current.map(drop_final_line_ending), (events, Lookahead::Ended))),
+        }
+    })
+    .boxed_local()
+}
+
+enum Lookahead {
+    Start,
+    Held(Result<Bytes, Error>),
+    Ended,
+}
+
+/// `event` without the line ending that terminates its final blank line.
+fn drop_final_line_ending(event: Bytes) -> Bytes {
+    let end = if event.ends_with(b"\r\n") {
+        event.len() - 2
+    } else if event.ends_with(b"\n") || event.ends_with(b"\r") {
+        event.len() - 1
+    } else {
+        event.len()
+    };
+    event.slice(..end)
+}
+
+/// One event read back by `Parser`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    /// The `event:` type, or `None` for the default `message`.
+    pub event: Option<String>,
+    /// The event's `data:` lines, joined with `\n`.
+    pub data: String,
+    /// The last `id:` seen so far, whether on this event or an earlier one,
+    /// as a client would send it in `Last-Event-ID`.
+    pub id: Option<String>,
+}
+
+impl Event {
+    /// Whether this is OpenAI's `data: [DONE]` end-of-stream marker.
+    pub fn is_done(&self) -> bool {
+        self.data == "[DONE]"
+    }
+}
+
+/// An incremental SSE parser, following the WHATWG event stream rules.
+///
+/// Bytes go in as they arrive, split anywhere: inside a line ending, a
+/// multi-byte character or the leading BOM. Lines may end in `\n`, `\r\n`
+/// or a lone `\r`. Comment lines (`: keepalive`) and unknown fields are
+/// skipped, and a block without `data:` lines, such as a comment on its own,
+/// is not an event.
+#[derive(Debug, Default)]
+pub struct Parser {
+    /// The bytes since the last blank line.
+    buf: Vec<u8>,
+    /// Where the current line starts in `buf`.
+    line_start: usize,
+    /// How far `buf` has been searched for a line ending.
+    scanned: usize,
+    started: bool,
+    /// The last line ended in `\r` at the end of `buf`, so an `\n` arriving
+    /// next belongs to the same line ending.
+    after_cr: bool,
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser::default()
+    }
+
+    /// Feeds the next bytes of the stream and returns the events they
+    /// complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Event> {
+        self.buf.extend_from_slice(bytes);
+        if !self.started {
+            if self.buf.len() < BOM.len() && BOM.starts_with(&self.buf) {
+                return Vec::new();
+            }
+            if self.buf.starts_with(BOM) {
+                self.buf.drain(..BOM.len());
+            }
+            self.started = true;
+        }
+        let mut events = Vec::new();
+        while self.scanned < self.buf.len() {
+            if std::mem::take(&mut self.after_cr) && self.buf[self.scanned] == b'\n' {
+                self.scanned += 1;
+                self.line_start = self.scanned;
+                continue;
+            }
+            let Some(at) = self.buf[self.scanned..].iter().position(|&b| b == b'\n' || b == b'\r') else {
+                self.scanned = self.buf.len();
+                break;
+            };
+            let end = self.scanned + at;
+            let mut next = end + 1;
+            if self.buf[end] == b'\r' {
+                match self.buf.get(next) {
+                    Some(b'\n') => next += 1,
+                    Some(_) => {}
+                    None => self.after_cr = true,
+                }
+            }
+            if end == self.line_start {
+                events.extend(self.dispatch());
+                self.buf.drain(..next);
+                self.line_start = 0;
+                self.scanned = 0;
+            } else {
+                let line = String::from_utf8_lossy(&self.buf[self.line_start..end]).into_owned();
+                self.field(&line);
+                self.line_start = next;
+                self.scanned = next;
+            }
+        }
+        events
+    }
+
+    fn field(&mut self, line: &str) {
+        if line.starts_with(':') {
+            return;
+        }
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match name {
+            "event" => self.event = (!value.is_empty()).then(|| value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "id" if !value.contains('\0') => self.id = (!value.is_empty()).then(|| value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => self.retry = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = self.event.take();
+        Some(Event {
+            event,
+            data: self.data.take()?,
+            id: self.id.clone(),
+        })
+    }
+
+    /// The last `id:` seen, to resume from with `Last-Event-ID`.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The last `retry:` reconnection time, in milliseconds.
+    pub fn retry(&self) -> Option<u64> {
+        self.retry
+    }
+
+    /// The bytes after the last blank line, which a stream that ended
+    /// cleanly leaves empty.
+    pub fn remainder(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Every event in a whole SSE body.
+pub fn parse(body: &[u8]) -> Vec<Event> {
+    Parser::new().push(body)
+}
@@ -0,0 +1,68 @@
+use crate::audit::AuditLog;
+use crate::capture::CaptureStore;
+use crate::config::Config;
+#[cfg(feature = "generators")]
+use crate::corpus::Corpus;
+use crate::lifecycle::{EventBus, LifecycleEvent};
+use crate::models::ModelRegistry;
+use crate::queue::AdmissionQueue;
+use crate::quota::Quotas;
+use crate::ratelimit::RateLimiter;
+use crate::replay::ReplayStore;
+use crate::retry_budget::RetryBudgets;
+use crate::shadow::Shadow;
+use crate::storm::StormControl;
+use crate::stored::CompletionStore;
+use crate::throughput::Throughput;
+use crate::webhooks::Webhooks;
+
+/// Runtime state shared by every worker, registered next to the config as
+/// `web::Data<AppState>`.
+pub struct AppState {
+    pub throughput: Throughput,
+    pub queue: Option<AdmissionQueue>,
+    pub rate_limits: RateLimiter,
+    pub retry_budgets: RetryBudgets,
+    pub quotas: Quotas,
+    pub storm: StormControl,
+    pub models: ModelRegistry,
+    pub captures: CaptureStore,
+    pub stored: CompletionStore,
+    pub shadow: Shadow,
+    pub webhooks: Webhooks,
+    pub events: EventBus,
+    pub replay: ReplayStore,
+    pub audit: AuditLog,
+    /// Opened by `server::serve`, which can report a bad file.
+    #[cfg(feature = "generators")]
+    pub corpus: Option<Corpus>,
+}
+
+impl AppState {
+    pub fn new(config: &Config) -> AppState {
+        AppState {
+            throughput: Throughput::new(&config.throughput),
+            queue: AdmissionQueue::new(&config.queue),
+            rate_limits: RateLimiter::new(&config.rate_limits),
+            retry_budgets: RetryBudgets::new(&config.retry_budget),
+            quotas: Quotas::new(&config.quota),
+            storm: StormControl::default(),
+            models: ModelRegistry::new(&config.models),
+            captures: CaptureStore::new(&config.capture),
+            stored: CompletionStore::new(&config.store),
+            shadow: Shadow::new(&config.shadow),
+            webhooks: Webhooks::new(&config.webhooks),
+            events: EventBus::default(),
+            replay: ReplayStore::default(),
+            audit: AuditLog::new(&config.admin),
+            #[cfg(feature = "generators")]
+            corpus: None,
+        }
+    }
+
+    /// Hands a lifecycle event to the webhooks and the event firehose.
+    pub fn publish(&self, event: LifecycleEvent) {
+        self.webhooks.dispatch(&event);
+        self.events.publish(event);
+    }
+}
@@ -0,0 +1,367 @@
+//! Stored completions: replies to requests sent with `store: true` are kept
+//! with the request's `metadata`, and read back through OpenAI's
+//! stored-completions API (`GET /v1/chat/completions`, `/{id}` and
+//! `/{id}/messages`), for testing pipelines that build eval data from
+//! traffic. Only replies that run to the end are stored, under the
+//! `x-request-id` they were served with. The store is in memory and keeps
+//! the latest `[store] limit` completions, each up to `MAX_CONTENT_BYTES`
+//! of reply.
+//!
+//! A completion belongs to the organization and project it was made under,
+//! or else to the bearer token, and only that owner can list or read it;
+//! to anyone else it does not exist.
+
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chat::{Message, MessageContent, NormalizedRequest};
+use crate::config::{Config, StoreConfig};
+use crate::error::MockError;
+use crate::keys;
+use crate::lifecycle::Usage;
+use crate::orgs::{self, OrgScope};
+use crate::service_tier::ServiceTier;
+use crate::state::AppState;
+
+/// Request `metadata`: string tags to find stored completions by.
+pub type Metadata = BTreeMap<String, String>;
+
+const MAX_METADATA_PAIRS: usize = 16;
+const MAX_KEY_CHARS: usize = 64;
+const MAX_VALUE_CHARS: usize = 512;
+const DEFAULT_PAGE: usize = 20;
+const MAX_PAGE: usize = 100;
+/// Reply text kept per completion; the rest of a longer reply is dropped.
+pub const MAX_CONTENT_BYTES: usize = 1 << 20;
+
+/// Whom a completion made under `scope` by `http_req` belongs to.
+pub fn owner(scope: &OrgScope, http_req: &HttpRequest) -> String {
+    scope.account(keys::bearer_token(http_req).unwrap_or_default()).into_owned()
+}
+
+/// Appends as much of `text` to `content` as fits in `MAX_CONTENT_BYTES`,
+/// ending on a character boundary.
+fn push_capped(content: &mut String, text: &str) {
+    let mut end = MAX_CONTENT_BYTES.saturating_sub(content.len()).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    content.push_str(&text[..end]);
+}
+
+/// Holds `metadata` to OpenAI's limits.
+pub fn validate(metadata: &Metadata) -> Result<(), MockError> {
+    let invalid = |message: String| Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some("metadata")));
+    if metadata.len() > MAX_METADATA_PAIRS {
+        return invalid(format!("Invalid 'metadata': too many properties. Expected at most {}, got {}.", MAX_METADATA_PAIRS, metadata.len()));
+    }
+    for (key, value) in metadata {
+        if key.chars().count() > MAX_KEY_CHARS {
+            return invalid(format!("Invalid 'metadata': key '{}' is longer than {} characters.", key, MAX_KEY_CHARS));
+        }
+        if value.chars().count() > MAX_VALUE_CHARS {
+            return invalid(format!("Invalid 'metadata.{}': string too long. Expected at most {} characters.", key, MAX_VALUE_CHARS));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+pub struct StoredCompletion {
+    pub id: String,
+    pub object: &'static str,
+    pub created: u64,
+    pub model: Option<String>,
+    pub choices: [StoredChoice; 1],
+    pub usage: Usage,
+    pub metadata: Metadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<&'static str>,
+    /// The request's messages, listed by `/{id}/messages`.
+    #[serde(skip)]
+    pub messages: Vec<Message>,
+    /// See `owner`.
+    #[serde(skip)]
+    pub owner: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct StoredChoice {
+    pub index: u32,
+    pub message: StoredReply,
+    pub finish_reason: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct StoredReply {
+    pub role: &'static str,
+    /// The text of the reply, or the transcript of a spoken one.
+    pub content: String,
+}
+
+impl StoredCompletion {
+    /// The completion of `req`, served as `id` to `owner`.
+    pub fn new(req: &NormalizedRequest, owner: &str, id: &str, text: &str, finish_reason: String, usage: Usage) -> StoredCompletion {
+        let mut content = String::new();
+        push_capped(&mut content, text);
+        StoredCompletion {
+            id: id.to_string(),
+            object: "chat.completion",
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            model: req.model.clone(),
+            choices: [StoredChoice {
+                index: 0,
+                message: StoredReply {
+                    role: "assistant",
+                    content,
+                },
+                finish_reason,
+            }],
+            usage,
+            metadata: req.metadata.clone(),
+            service_tier: req.service_tier.map(ServiceTier::name),
+            messages: req.messages.clone(),
+            owner: owner.to_string(),
+        }
+    }
+}
+
+pub struct CompletionStore {
+    limit: usize,
+    entries: Mutex<VecDeque<StoredCompletion>>,
+}
+
+impl CompletionStore {
+    pub fn new(config: &StoreConfig) -> CompletionStore {
+        CompletionStore {
+            limit: config.limit,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn put(&self, completion: StoredCompletion) {
+        if self.limit == 0 {
+            return;
+        }
+        let mut entries = self.lock();
+        if entries.len() == self.limit {
+            entries.pop_front();
+        }
+        entries.push_back(completion);
+    }
+
+    /// Completion `id`, if `owner` stored it.
+    pub fn get(&self, id: &str, owner: &str) -> Option<StoredCompletion> {
+        self.lock().iter().find(|c| c.id == id && c.owner == owner).cloned()
+    }
+
+    /// The page `query` asks for of the completions `owner` stored, cloning
+    /// only those on it.
+    fn list(&self, owner: &str, query: &ListQuery) -> Page<StoredCompletion> {
+        let entries = self.lock();
+        let completions = entries
+            .iter()
+            .filter(|c| c.owner == owner)
+            .filter(|c| query.model.as_ref().is_none_or(|model| c.model.as_ref() == Some(model)))
+            .filter(|c| query.metadata.iter().all(|(key, value)| c.metadata.get(key) == Some(value)))
+            .collect();
+        query.page(completions, |c| &c.id, |c| c.clone())
+    }
+
+    /// The page `query` asks for of the messages of completion `id`, if
+    /// `owner` stored it.
+    fn messages(&self, id: &str, owner: &str, query: &ListQuery) -> Option<Page<StoredMessage>> {
+        let entries = self.lock();
+        let completion = entries.iter().find(|c| c.id == id && c.owner == owner)?;
+        let messages = completion.messages.iter().enumerate().map(|(i, m)| (format!("{}-{}", id, i), m)).collect();
+        Some(query.page(messages, |(id, _)| id, |(id, m)| StoredMessage {
+            id,
+            role: m.role.clone(),
+            content: m.content.clone(),
+            name: m.name.clone(),
+        }))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<StoredCompletion>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Collects a streamed reply for storing, and stores it once the stream
+/// reaches its end. Dropped unfinished, it stores nothing.
+pub struct StoredStream {
+    completion: RefCell<Option<StoredCompletion>>,
+}
+
+impl StoredStream {
+    /// A collector for `req`, or `None` when it did not ask to be stored.
+    pub fn new(req: &NormalizedRequest, owner: &str, id: &str, finish_reason: String) -> Option<StoredStream> {
+        req.store.then(|| StoredStream {
+            completion: RefCell::new(Some(StoredCompletion::new(req, owner, id, "", finish_reason, Usage::default()))),
+        })
+    }
+
+    /// Adds `text` to the reply, up to `MAX_CONTENT_BYTES` in all.
+    pub fn chunk(&self, text: &str) {
+        if let Some(completion) = self.completion.borrow_mut().as_mut() {
+            push_capped(&mut completion.choices[0].message.content, text);
+        }
+    }
+
+    /// Records that the reply was cut short for `reason`.
+    pub fn end_for(&self, reason: &str) {
+        if let Some(completion) = self.completion.borrow_mut().as_mut() {
+            completion.choices[0].finish_reason = reason.to_string();
+        }
+    }
+
+    /// Stores the reply. Later calls are ignored.
+    pub fn finish(&self, state: &AppState, usage: Usage) {
+        if let Some(mut completion) = self.completion.take() {
+            completion.usage = usage;
+            state.stored.put(completion);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Page<T> {
+    object: &'static str,
+    data: Vec<T>,
+    first_id: Option<String>,
+    last_id: Option<String>,
+    has_more: bool,
+}
+
+/// The paging parameters both list endpoints take, and the `model` and
+/// `metadata[key]` filters of the completions list.
+#[derive(Default)]
+struct ListQuery {
+    after: Option<String>,
+    limit: usize,
+    descending: bool,
+    model: Option<String>,
+    metadata: Metadata,
+}
+
+impl ListQuery {
+    /// Parsed by hand, since `metadata[key]=value` filters have open names.
+    fn parse(pairs: Vec<(String, String)>) -> Result<ListQuery, MockError> {
+        let invalid = |message: String, param: &str| Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some(param)));
+        let mut query = ListQuery {
+            limit: DEFAULT_PAGE,
+            ..ListQuery::default()
+        };
+        for (name, value) in pairs {
+            match name.as_str() {
+                "after" => query.after = Some(value),
+                "model" => query.model = Some(value),
+                "limit" => match value.parse() {
+                    Ok(limit) if (1..=MAX_PAGE).contains(&limit) => query.limit = limit,
+                    _ => return invalid(format!("Invalid 'limit': expected an integer from 1 to {}, got '{}'.", MAX_PAGE, value), "limit"),
+                },
+                "order" => match value.as_str() {
+                    "asc" => query.descending = false,
+                    "desc" => query.descending = true,
+                    _ => return invalid(format!("Invalid 'order': expected 'asc' or 'desc', got '{}'.", value), "order"),
+                },
+                _ => {
+                    if let Some(key) = name.strip_prefix("metadata[").and_then(|rest| rest.strip_suffix(']')) {
+                        query.metadata.insert(key.to_string(), value);
+                    }
+                }
+            }
+        }
+        Ok(query)
+    }
+
+    /// One page of `items`, given oldest first, each made into what is
+    /// listed with `make` once the page is chosen.
+    fn page<S, T>(&self, mut items: Vec<S>, id_of: impl Fn(&S) -> &str, make: impl FnMut(S) -> T) -> Page<T> {
+        if self.descending {
+            items.reverse();
+        }
+        if let Some(after) = &self.after {
+            let start = items.iter().position(|item| id_of(item) == after).map_or(items.len(), |i| i + 1);
+            items.drain(..start);
+        }
+        let has_more = items.len() > self.limit;
+        items.truncate(self.limit);
+        Page {
+            object: "list",
+            first_id: items.first().map(|item| id_of(item).to_string()),
+            last_id: items.last().map(|item| id_of(item).to_string()),
+            data: items.into_iter().map(make).collect(),
+            has_more,
+        }
+    }
+}
+
+fn not_found(id: &str) -> MockError {
+    MockError::rejected(StatusCode::NOT_FOUND, format!("No chat completion found with id '{}'.", id), None).with_code("not_found")
+}
+
+/// Checks the caller's key and organization headers as a chat request's
+/// are, and returns whom they read completions as.
+fn caller(config: &Config, http_req: &HttpRequest) -> Result<String, MockError> {
+    keys::authorize_use(&config.keys, http_req, None, [])?;
+    let scope = orgs::resolve(config, http_req)?;
+    Ok(owner(&scope, http_req))
+}
+
+#[get("/v1/chat/completions")]
+pub async fn list_endpoint(
+    http_req: HttpRequest,
+    query: web::Query<Vec<(String, String)>>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let owner = caller(&config, &http_req)?;
+    let query = ListQuery::parse(query.into_inner())?;
+    Ok(HttpResponse::Ok().json(state.stored.list(&owner, &query)))
+}
+
+#[get("/v1/chat/completions/{id}")]
+pub async fn retrieve_endpoint(
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let owner = caller(&config, &http_req)?;
+    let id = path.into_inner();
+    match state.stored.get(&id, &owner) {
+        Some(completion) => Ok(HttpResponse::Ok().json(completion)),
+        None => Err(not_found(&id)),
+    }
+}
+
+/// A request message as `/{id}/messages` lists it.
+#[derive(Serialize)]
+struct StoredMessage {
+    id: String,
+    role: String,
+    content: Option<MessageContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+#[get("/v1/chat/completions/{id}/messages")]
+pub async fn messages_endpoint(
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<Vec<(String, String)>>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let owner = caller(&config, &http_req)?;
+    let id = path.into_inner();
+    let query = ListQuery::parse(query.into_inner())?;
+    let page = state.stored.messages(&id, &owner, &query).ok_or_else(|| not_found(&id))?;
+    Ok(HttpResponse::Ok().json(page))
+}
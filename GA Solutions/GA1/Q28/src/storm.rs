@@ -0,0 +1,209 @@
+//! 429 storm simulation: for a configured duration a share of chat requests
+//! is rejected with `429 Too Many Requests`, following a recovery curve, so
+//! client backoff and circuit breakers can be exercised against realistic
+//! outage shapes. Started and stopped through the admin API.
+
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::admin;
+use crate::audit::Change;
+use crate::config::Config;
+use crate::error::MockError;
+use crate::generator::{self, SPLITMIX_GAMMA};
+use crate::state::AppState;
+
+/// How the rejection probability evolves over the storm.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    /// `probability` for the whole duration, then an abrupt recovery.
+    #[default]
+    Constant,
+    /// Falls linearly from `probability` to zero.
+    Linear,
+    /// Decays exponentially from `probability`, reaching ~1% of it at the end.
+    Exponential,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StormSpec {
+    /// Share of requests rejected at the start of the storm, 0 to 1.
+    pub probability: f64,
+    pub duration_secs: f64,
+    #[serde(default)]
+    pub curve: Curve,
+    /// Sent as `Retry-After` on rejections.
+    #[serde(default)]
+    pub retry_after_secs: Option<f64>,
+    /// Fixes the rejection sequence for reproducible runs.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl StormSpec {
+    fn validate(&self) -> Result<(), MockError> {
+        let bad = |message: &str, param: &str| Err(MockError::rejected(StatusCode::BAD_REQUEST, message, Some(param)));
+        if !(0.0..=1.0).contains(&self.probability) {
+            return bad("probability must be between 0 and 1", "probability");
+        }
+        if !(self.duration_secs.is_finite() && self.duration_secs > 0.0) {
+            return bad("duration_secs must be positive", "duration_secs");
+        }
+        if self.retry_after_secs.is_some_and(|s| !(s.is_finite() && s >= 0.0)) {
+            return bad("retry_after_secs must not be negative", "retry_after_secs");
+        }
+        Ok(())
+    }
+}
+
+struct Storm {
+    spec: StormSpec,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Storm {
+    /// Rejection probability `elapsed` into the storm, or `None` once it is over.
+    fn probability_at(&self, elapsed: Duration) -> Option<f64> {
+        if elapsed >= self.duration {
+            return None;
+        }
+        let progress = elapsed.as_secs_f64() / self.duration.as_secs_f64();
+        let p = self.spec.probability;
+        Some(match self.spec.curve {
+            Curve::Constant => p,
+            Curve::Linear => p * (1.0 - progress),
+            // e^-4.6 ~= 0.01
+            Curve::Exponential => p * (-4.6 * progress).exp(),
+        })
+    }
+}
+
+/// The active storm, if any, shared by all workers.
+#[derive(Default)]
+pub struct StormControl {
+    storm: Mutex<Option<Storm>>,
+    rng: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl StormControl {
+    pub fn start(&self, spec: StormSpec) {
+        let seed = spec.seed.unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+        });
+        self.rng.store(seed, Ordering::Relaxed);
+        self.rejected.store(0, Ordering::Relaxed);
+        let duration = Duration::from_secs_f64(spec.duration_secs);
+        *self.lock() = Some(Storm {
+            spec,
+            started: Instant::now(),
+            duration,
+        });
+    }
+
+    pub fn stop(&self) {
+        *self.lock() = None;
+    }
+
+    /// Rolls the dice for one request, returning the 429 to send if it loses.
+    pub fn check(&self) -> Result<(), MockError> {
+        let mut storm = self.lock();
+        let Some(active) = storm.as_ref() else {
+            return Ok(());
+        };
+        let Some(probability) = active.probability_at(active.started.elapsed()) else {
+            // Recovered; clear it so later requests skip the lock-held math.
+            *storm = None;
+            return Ok(());
+        };
+        if self.next_unit() >= probability {
+            return Ok(());
+        }
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        Err(MockError::RateLimited {
+            message: "Rate limit reached for requests. Please try again later.".to_string(),
+            retry_after: active.spec.retry_after_secs.map(Duration::from_secs_f64),
+        })
+    }
+
+    pub fn status(&self) -> StormStatus {
+        let storm = self.lock();
+        let active = storm.as_ref().and_then(|s| {
+            let elapsed = s.started.elapsed();
+            s.probability_at(elapsed).map(|p| (s, elapsed, p))
+        });
+        StormStatus {
+            active: active.is_some(),
+            probability: active.map(|(_, _, p)| p).unwrap_or(0.0),
+            remaining_secs: active.map(|(s, elapsed, _)| (s.duration - elapsed).as_secs_f64()).unwrap_or(0.0),
+            spec: active.map(|(s, _, _)| s.spec.clone()),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Storm>> {
+        self.storm.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A uniform value in `[0, 1)` from a shared SplitMix64 sequence.
+    fn next_unit(&self) -> f64 {
+        let state = self.rng.fetch_add(SPLITMIX_GAMMA, Ordering::Relaxed).wrapping_add(SPLITMIX_GAMMA);
+        generator::unit(generator::mix64(state))
+    }
+}
+
+#[derive(Serialize)]
+pub struct StormStatus {
+    pub active: bool,
+    /// Current rejection probability.
+    pub probability: f64,
+    pub remaining_secs: f64,
+    pub spec: Option<StormSpec>,
+    /// Requests rejected since the storm started.
+    pub rejected: u64,
+}
+
+#[post("/v1/admin/storm")]
+pub async fn start_endpoint(
+    req: HttpRequest,
+    spec: web::Json<StormSpec>,
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return Ok(resp);
+    }
+    spec.validate()?;
+    let before = state.storm.status().spec;
+    let after = json!(&*spec);
+    state.storm.start(spec.into_inner());
+    state.audit.record(&req, "storm.start", Change { before: json!(before), after })?;
+    Ok(HttpResponse::Ok().json(state.storm.status()))
+}
+
+#[get("/v1/admin/storm")]
+pub async fn status_endpoint(req: HttpRequest, config: web::Data<Config>, state: web::Data<AppState>) -> HttpResponse {
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return resp;
+    }
+    HttpResponse::Ok().json(state.storm.status())
+}
+
+#[delete("/v1/admin/storm")]
+pub async fn stop_endpoint(req: HttpRequest, config: web::Data<Config>, state: web::Data<AppState>) -> Result<HttpResponse, MockError> {
+    if let Some(resp) = admin::reject_unauthorized(&req, &config) {
+        return Ok(resp);
+    }
+    let before = state.storm.status().spec;
+    state.storm.stop();
+    state.audit.record(&req, "storm.stop", Change { before: json!(before), after: Value::Null })?;
+    Ok(HttpResponse::Ok().json(state.storm.status()))
+}
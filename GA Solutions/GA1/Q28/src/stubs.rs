@@ -0,0 +1,332 @@
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::HttpResponseBuilder;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::chat::CHARS_PER_TOKEN;
+use crate::chunking::ChunkStrategy;
+use crate::error::ErrorStyle;
+use crate::extensions::Extensions;
+use crate::guardrail::Guardrail;
+use crate::languages::Language;
+use crate::transforms::Transform;
+
+/// A prompt matcher paired with the response profile it selects.
+///
+/// Rules are evaluated in config order and the first match wins, so more
+/// specific rules should be listed before broad ones.
+#[derive(Deserialize, Clone)]
+pub struct StubRule {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(rename = "match")]
+    pub matcher: Matcher,
+    #[serde(default)]
+    pub profile: ResponseProfile,
+}
+
+/// Conditions on the prompt text. Every condition that is set must hold; a
+/// matcher with no conditions matches every prompt.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Matcher {
+    pub contains: Option<String>,
+    pub starts_with: Option<String>,
+    pub ends_with: Option<String>,
+    pub equals: Option<String>,
+    pub regex: Option<Pattern>,
+    /// vLLM and TGI sampling fields the request must carry with these values.
+    pub extensions: Extensions,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn regex(&self) -> &Regex {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Regex::new(&source).map(Pattern)
+    }
+}
+
+/// How the stream for a matched prompt should behave.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseProfile {
+    /// Where the reply text comes from.
+    pub generator: GeneratorKind,
+    /// Number of content chunks the reply is split into.
+    pub chunks: usize,
+    /// Fixed chunk size in characters. Overrides `chunks` when set.
+    pub chunk_chars: Option<usize>,
+    /// Where deltas are cut; see `chunking`.
+    pub chunk_strategy: ChunkStrategy,
+    /// Keeps fence markers, and escapes inside code blocks, within one
+    /// delta; see `chunking::fence_safe`.
+    pub fence_safe: bool,
+    /// Delay before each chunk is sent.
+    pub chunk_delay_ms: u64,
+    /// Delay before the first chunk (time to first token). Defaults to `chunk_delay_ms`.
+    pub first_chunk_delay_ms: Option<u64>,
+    /// Paces each chunk by the tokens in it instead of `chunk_delay_ms`;
+    /// see `pacer::Pace`.
+    pub tokens_per_sec: Option<f64>,
+    /// Characters per token, for pacing and for `tokens`, calibrated to the
+    /// language of the replies. Unset counts with the model's tokenizer and
+    /// sizes `tokens` at four characters each.
+    pub chars_per_token: Option<f64>,
+    /// Language of the canned reply; see `languages`. Unset is English.
+    pub language: Option<Language>,
+    /// Replies in the prompt's language when it can be told, else in
+    /// `language`.
+    pub detect_language: bool,
+    /// Cites a source after each sentence, with `url_citation` annotations;
+    /// see `citations`.
+    pub citations: bool,
+    /// Approximate reply length in tokens; the corpus is repeated to reach it.
+    pub tokens: Option<usize>,
+    /// Emit an error event after this many chunks and end the stream. `0` fails
+    /// the request with `error_code` as the HTTP status before streaming starts.
+    pub error_after: Option<usize>,
+    pub error_code: u16,
+    pub error_message: String,
+    /// Provider format injected errors are reported in.
+    pub error_style: ErrorStyle,
+    /// Sent in a final empty-delta chunk before `[DONE]` when set.
+    pub finish_reason: Option<String>,
+    /// Relative share of a throughput budget when streams compete for it.
+    pub weight: u32,
+    /// Rewrites applied to each outgoing delta, in order.
+    pub transforms: Vec<Transform>,
+    /// Non-conformant stream shapes seen from real providers; see `Quirk`.
+    pub quirks: Vec<Quirk>,
+    /// Splits each SSE event across several socket writes to stress client
+    /// parsers; see `WriteSplit`.
+    pub write_split: WriteSplit,
+    /// Pause between the pieces of a split event. `0` only yields to the
+    /// runtime, which is still enough for each piece to be written on its own.
+    pub write_split_pause_ms: u64,
+    /// Deviations from standard SSE framing; see `FrameFault`.
+    pub frame_faults: Vec<FrameFault>,
+    /// Sends an `event: metadata` frame with usage, duration and a content
+    /// hash just before `[DONE]`; see `metadata`.
+    pub metadata_frame: bool,
+    /// Longest the stream may run, from when streaming starts. Unset lets
+    /// it run to the end.
+    pub max_duration_ms: Option<u64>,
+    /// What happens when `max_duration_ms` runs out; see `TimeoutMode`.
+    pub on_timeout: TimeoutMode,
+    /// Whether chunks carry new text, the text so far, or both. Unset
+    /// leaves it to the model's `[[models]]` entry, else deltas.
+    pub content_mode: Option<ContentMode>,
+    /// A moderation pass over the end of the reply; see `guardrail`.
+    pub guardrail: Option<Guardrail>,
+    /// Headers added to the reply, after the mock's own, so they replace
+    /// any of the same name.
+    pub headers: ResponseHeaders,
+}
+
+impl Default for ResponseProfile {
+    fn default() -> Self {
+        ResponseProfile {
+            generator: GeneratorKind::Canned,
+            chunks: 15,
+            chunk_chars: None,
+            chunk_strategy: ChunkStrategy::Chars,
+            fence_safe: false,
+            chunk_delay_ms: 75,
+            first_chunk_delay_ms: None,
+            tokens_per_sec: None,
+            chars_per_token: None,
+            language: None,
+            detect_language: false,
+            citations: false,
+            tokens: None,
+            error_after: None,
+            error_code: 500,
+            error_message: "Injected failure".to_string(),
+            error_style: ErrorStyle::Openai,
+            finish_reason: None,
+            weight: 1,
+            transforms: Vec::new(),
+            quirks: Vec::new(),
+            write_split: WriteSplit::Off,
+            write_split_pause_ms: 0,
+            frame_faults: Vec::new(),
+            metadata_frame: false,
+            max_duration_ms: None,
+            on_timeout: TimeoutMode::Soft,
+            content_mode: None,
+            guardrail: None,
+            headers: ResponseHeaders::default(),
+        }
+    }
+}
+
+impl ResponseProfile {
+    /// Characters of reply that `tokens` tokens stand for.
+    pub fn chars_for_tokens(&self, tokens: usize) -> usize {
+        match self.chars_per_token.filter(|ratio| *ratio > 0.0) {
+            Some(ratio) => (tokens as f64 * ratio).round() as usize,
+            None => tokens * CHARS_PER_TOKEN,
+        }
+    }
+}
+
+/// Response headers set by a profile, checked when the config loads so a
+/// bad name or value fails there rather than on every reply.
+#[derive(Deserialize, Clone, Default)]
+#[serde(try_from = "BTreeMap<String, String>")]
+pub struct ResponseHeaders(Vec<(HeaderName, HeaderValue)>);
+
+impl ResponseHeaders {
+    pub fn insert_headers(&self, response: &mut HttpResponseBuilder) {
+        for (name, value) in &self.0 {
+            response.insert_header((name.clone(), value.clone()));
+        }
+    }
+
+    /// `insert_headers` for a response that is already built.
+    pub fn insert_into(&self, headers: &mut HeaderMap) {
+        for (name, value) in &self.0 {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for ResponseHeaders {
+    type Error = String;
+
+    fn try_from(source: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        source
+            .into_iter()
+            .map(|(name, value)| {
+                let header = HeaderName::try_from(name.as_str()).map_err(|_| format!("invalid header name `{}`", name))?;
+                let value = HeaderValue::try_from(value).map_err(|_| format!("invalid value for header `{}`", name))?;
+                Ok((header, value))
+            })
+            .collect::<Result<_, _>>()
+            .map(ResponseHeaders)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorKind {
+    /// The built-in canned reply, once through unless `tokens` asks for more.
+    #[default]
+    Canned,
+    /// A lazily generated reply of `tokens` length (one million by default),
+    /// for context-window and memory testing.
+    Long,
+    /// A canned reply heavy with fenced code and backslash escapes, once
+    /// through unless `tokens` asks for more.
+    Code,
+    /// A reply from the packed `[corpus]`, picked by the request seed and
+    /// sent once through unless `tokens` asks for more.
+    Corpus,
+}
+
+/// Deviations from a well-formed OpenAI stream that real providers have
+/// been seen to produce, for hardening client parsers. Extra events ride in
+/// the same write as a content chunk, so event ids and resume are unaffected.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Quirk {
+    /// The first delta omits `"role": "assistant"`.
+    MissingRole,
+    /// An empty `{"delta": {}}` event follows every content chunk.
+    EmptyDeltas,
+    /// The stream opens with a `"choices": []` event, like Azure's
+    /// content-filter preamble.
+    EmptyChoices,
+    /// A complete tool call is streamed before the reply, which then
+    /// continues with ordinary content deltas.
+    ContentAfterToolCalls,
+}
+
+/// Where an SSE event is cut into separate writes. Combine with
+/// `chunk_chars = 1` or `chunks = 1` for one-character or single-chunk replies.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteSplit {
+    #[default]
+    Off,
+    /// Right after every backslash, so JSON escapes such as `\n` and `\"`
+    /// straddle two reads.
+    MidEscape,
+    /// Inside every multi-byte UTF-8 character.
+    MidUtf8,
+    /// One byte per write.
+    EveryByte,
+}
+
+/// A framing deviation applied to every event of the stream. `crlf` and
+/// `cr` are legal per the SSE spec but trip naive `\n\n` splitters; `bom`
+/// and `missing_final_blank_line` are the kind of damage proxies introduce.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameFault {
+    /// Lines end with `\r\n`.
+    Crlf,
+    /// Lines end with a bare `\r`.
+    Cr,
+    /// The stream opens with a UTF-8 byte order mark.
+    Bom,
+    /// The last event lacks the blank line that dispatches it.
+    MissingFinalBlankLine,
+}
+
+/// What each chunk's text holds.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentMode {
+    /// `delta.content` is the text new in this chunk, as OpenAI sends.
+    #[default]
+    Delta,
+    /// `delta.content` is the whole reply so far, for clients written
+    /// against providers that stream snapshots.
+    Cumulative,
+    /// `delta.content` is the new text and `delta.text` the reply so far,
+    /// like the `text` of Cohere-style events.
+    Both,
+}
+
+/// How a stream that outlives `max_duration_ms` ends.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutMode {
+    /// Finish gracefully with `finish_reason: "length"` and `[DONE]`, as a
+    /// provider cutting generation short does.
+    #[default]
+    Soft,
+    /// Drop the connection mid-body, without `[DONE]` or the terminating
+    /// chunk, as a gateway timeout does.
+    Hard,
+}
+
+impl Matcher {
+    pub fn matches(&self, prompt: &str) -> bool {
+        self.contains.as_deref().is_none_or(|s| prompt.contains(s))
+            && self.starts_with.as_deref().is_none_or(|s| prompt.starts_with(s))
+            && self.ends_with.as_deref().is_none_or(|s| prompt.ends_with(s))
+            && self.equals.as_deref().is_none_or(|s| prompt == s)
+            && self.regex.as_ref().is_none_or(|p| p.0.is_match(prompt))
+    }
+}
+
+/// Returns the first rule whose matcher accepts `prompt` and `extensions`.
+pub fn resolve<'a>(rules: &'a [StubRule], prompt: &str, extensions: &Extensions) -> Option<&'a StubRule> {
+    rules
+        .iter()
+        .find(|rule| rule.matcher.matches(prompt) && rule.matcher.extensions.within(extensions))
+}
@@ -0,0 +1,106 @@
+//! Token-throughput caps shared by every stream on the server, emulating a
+//! saturated inference backend: the configured tokens-per-second budget is
+//! split between active streams in proportion to their weights, so each
+//! stream slows down as concurrency rises while the total stays capped.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::ThroughputConfig;
+
+/// One budget and the weight of the streams currently drawing on it.
+struct Budget {
+    tokens_per_sec: f64,
+    active_weight: AtomicU64,
+}
+
+impl Budget {
+    fn new(tokens_per_sec: f64) -> Arc<Budget> {
+        Arc::new(Budget {
+            tokens_per_sec,
+            active_weight: AtomicU64::new(0),
+        })
+    }
+}
+
+/// The server-wide budget plus any per-model budgets.
+#[derive(Default)]
+pub struct Throughput {
+    global: Option<Arc<Budget>>,
+    models: HashMap<String, Arc<Budget>>,
+}
+
+impl Throughput {
+    pub fn new(config: &ThroughputConfig) -> Throughput {
+        Throughput {
+            global: config.tokens_per_sec.filter(|&rate| rate > 0.0).map(Budget::new),
+            models: config
+                .models
+                .iter()
+                .filter(|(_, &rate)| rate > 0.0)
+                .map(|(model, &rate)| (model.clone(), Budget::new(rate)))
+                .collect(),
+        }
+    }
+
+    /// Registers a stream against the global budget and its model's budget.
+    /// The stream counts towards both until the lease is dropped. `rate`
+    /// additionally caps the stream on its own, as a key tier does.
+    pub fn lease(&self, model: Option<&str>, weight: u32, rate: Option<f64>) -> Lease {
+        let weight = u64::from(weight.max(1));
+        let budgets: Vec<Arc<Budget>> = self
+            .global
+            .iter()
+            .chain(model.and_then(|m| self.models.get(m)))
+            .cloned()
+            .collect();
+        for budget in &budgets {
+            budget.active_weight.fetch_add(weight, Ordering::Relaxed);
+        }
+        Lease {
+            budgets,
+            weight,
+            rate: rate.filter(|&rate| rate > 0.0),
+        }
+    }
+}
+
+/// A stream's claim on its budgets.
+pub struct Lease {
+    budgets: Vec<Arc<Budget>>,
+    weight: u64,
+    rate: Option<f64>,
+}
+
+impl Lease {
+    /// Whether any budget or rate applies, so `delay_for` is worth asking.
+    pub fn is_capped(&self) -> bool {
+        !self.budgets.is_empty() || self.rate.is_some()
+    }
+
+    /// How long emitting `tokens` takes at this stream's current fair share,
+    /// under the tightest of its budgets and its own rate. Zero when nothing
+    /// is capped.
+    pub fn delay_for(&self, tokens: usize) -> Duration {
+        self.budgets
+            .iter()
+            .map(|budget| {
+                let active = budget.active_weight.load(Ordering::Relaxed).max(self.weight);
+                budget.tokens_per_sec * self.weight as f64 / active as f64
+            })
+            .chain(self.rate)
+            .map(|rate| Duration::from_secs_f64(tokens as f64 / rate))
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        for budget in &self.budgets {
+            budget.active_weight.fetch_sub(self.weight, Ordering::Relaxed);
+        }
+    }
+}
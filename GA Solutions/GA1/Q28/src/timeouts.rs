@@ -0,0 +1,136 @@
+//! Budgets that cut off a handler that hangs (`[timeouts]`), for backends
+//! that can stall, such as a proxy or a local model, without taking the
+//! server's workers down with them. A route has two:
+//!
+//! - **Validation**: from the request arriving to the response headers. Past
+//!   it the client gets a `504` with an OpenAI-style error body.
+//! - **Stream idle**: the longest a streamed body may go without sending
+//!   anything once its headers are out. Past it an event stream gets a
+//!   `{"error": ..., "code": 504}` event, the mock's in-band error format,
+//!   and ends without `[DONE]`; a JSON-lines stream gets the same object as
+//!   its last line. Other streamed bodies, such as Gemini's JSON array, have
+//!   no way to say so and are cut off without their terminating chunk, so
+//!   the client sees a transport error. So are compressed streams, which a
+//!   plain-text error appended to would only corrupt.
+//!
+//! A profile's chunk delays count against the idle budget like any other
+//! wait, so it should be set above the longest delay a stub asks for.
+
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes};
+use actix_web::Error;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+use crate::config::Config;
+use crate::error::MockError;
+use crate::metrics;
+
+/// Middleware enforcing `[timeouts]`; see the module docs.
+pub async fn enforce(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let (validation, stream_idle) = match req.app_data::<web::Data<Config>>() {
+        Some(config) => config.timeouts.for_path(req.path()),
+        None => (None, None),
+    };
+    let path = req.path().to_string();
+    let response = match validation {
+        Some(limit) => match tokio::time::timeout(limit, next.call(req)).await {
+            Ok(response) => response?,
+            Err(_) => {
+                metrics::HANDLER_TIMEOUTS.inc();
+                log::warn!("handler for {} gave no response within {} ms", path, limit.as_millis());
+                let message = format!("The server did not respond within {} ms.", limit.as_millis());
+                return Err(MockError::rejected(StatusCode::GATEWAY_TIMEOUT, message, None).with_code("timeout").into());
+            }
+        },
+        None => next.call(req).await?,
+    };
+    let streamed = matches!(response.response().body().size(), BodySize::Stream);
+    let encoded = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .is_some_and(|v| v != "identity");
+    let framing = match response.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        _ if encoded => Framing::Cut,
+        Some(v) if v.starts_with("text/event-stream") => Framing::Sse,
+        Some(v) if v.starts_with("application/stream+json") || v.starts_with("application/x-ndjson") || v.starts_with("application/jsonl") => {
+            Framing::JsonLines
+        }
+        _ => Framing::Cut,
+    };
+    match stream_idle.filter(|_| streamed) {
+        Some(limit) => Ok(response.map_body(|_, body| BoxBody::new(IdleLimit::new(body, limit, framing)))),
+        None => Ok(response.map_into_boxed_body()),
+    }
+}
+
+/// How a stream that went silent is told so.
+#[derive(Clone, Copy)]
+enum Framing {
+    /// A `data:` event.
+    Sse,
+    /// A line of JSON.
+    JsonLines,
+    /// It is not: the body fails, cutting the connection.
+    Cut,
+}
+
+/// A body that ends with a timeout once `inner` has been silent for `limit`.
+struct IdleLimit<B> {
+    inner: Pin<Box<B>>,
+    limit: Duration,
+    framing: Framing,
+    timer: Pin<Box<Sleep>>,
+    timed_out: bool,
+}
+
+impl<B: MessageBody> IdleLimit<B> {
+    fn new(inner: B, limit: Duration, framing: Framing) -> IdleLimit<B> {
+        IdleLimit {
+            inner: Box::pin(inner),
+            limit,
+            framing,
+            timer: Box::pin(tokio::time::sleep(limit)),
+            timed_out: false,
+        }
+    }
+}
+
+impl<B: MessageBody> MessageBody for IdleLimit<B> {
+    type Error = Box<dyn StdError>;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        if self.timed_out {
+            return Poll::Ready(None);
+        }
+        if let Poll::Ready(item) = self.inner.as_mut().poll_next(cx) {
+            let deadline = Instant::now() + self.limit;
+            self.timer.as_mut().reset(deadline);
+            return Poll::Ready(item.map(|item| item.map_err(Into::into)));
+        }
+        if self.timer.as_mut().poll(cx).is_ready() {
+            self.timed_out = true;
+            metrics::HANDLER_TIMEOUTS.inc();
+            let ms = self.limit.as_millis();
+            log::warn!("stream sent nothing for {} ms and was ended", ms);
+            let error = format!("{{\"error\":\"The stream sent nothing for {} ms and was ended.\",\"code\":504}}", ms);
+            return Poll::Ready(Some(match self.framing {
+                Framing::Sse => Ok(Bytes::from(format!("data: {}\n\n", error))),
+                Framing::JsonLines => Ok(Bytes::from(format!("{}\n", error))),
+                Framing::Cut => Err(error.into()),
+            }));
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,111 @@
+//! `/v1/internal/tokenize` and `/v1/internal/detokenize`: the mock's token
+//! accounting exposed directly, so tests can predict `usage` numbers.
+
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::chat::Message;
+use crate::error::MockError;
+use crate::state::AppState;
+use crate::tokenizer::TokenEncoding;
+
+/// Picks the encoding the way usage accounting does: an explicit `encoding`,
+/// else the registered model's, else the default.
+fn resolve_encoding(state: &AppState, model: Option<&str>, encoding: Option<TokenEncoding>) -> TokenEncoding {
+    encoding
+        .or_else(|| model.and_then(|m| state.models.get(m)).map(|m| m.encoding))
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenizeRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub encoding: Option<TokenEncoding>,
+    /// Plain text to encode.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// A chat prompt, counted the way it is billed as `prompt_tokens`.
+    #[serde(default)]
+    pub messages: Option<Vec<Message>>,
+}
+
+#[derive(Serialize)]
+struct TokenizeResponse {
+    encoding: &'static str,
+    count: usize,
+    /// Omitted for `messages`, whose count includes framing tokens with no text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokens: Option<Vec<u32>>,
+}
+
+#[post("/v1/internal/tokenize")]
+pub async fn tokenize_endpoint(
+    body: web::Json<TokenizeRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let req = body.into_inner();
+    let encoding = resolve_encoding(&state, req.model.as_deref(), req.encoding);
+    let response = match (req.text, req.messages) {
+        (Some(text), None) => {
+            let tokens = encoding.encode(&text);
+            TokenizeResponse {
+                encoding: encoding.name(),
+                count: tokens.len(),
+                tokens: Some(tokens),
+            }
+        }
+        (None, Some(messages)) => TokenizeResponse {
+            encoding: encoding.name(),
+            count: encoding.count_messages(&messages),
+            tokens: None,
+        },
+        _ => {
+            return Err(MockError::rejected(
+                StatusCode::BAD_REQUEST,
+                "Exactly one of 'text' or 'messages' must be provided",
+                Some("text"),
+            ))
+        }
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetokenizeRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub encoding: Option<TokenEncoding>,
+    pub tokens: Vec<u32>,
+}
+
+#[derive(Serialize)]
+struct DetokenizeResponse {
+    encoding: &'static str,
+    text: String,
+}
+
+#[post("/v1/internal/detokenize")]
+pub async fn detokenize_endpoint(
+    body: web::Json<DetokenizeRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, MockError> {
+    let req = body.into_inner();
+    let encoding = resolve_encoding(&state, req.model.as_deref(), req.encoding);
+    let text = encoding.decode(&req.tokens).map_err(|token| {
+        MockError::rejected(
+            StatusCode::BAD_REQUEST,
+            format!("Token id {} is not in the {} vocabulary", token, encoding.name()),
+            Some("tokens"),
+        )
+    })?;
+    Ok(HttpResponse::Ok().json(DetokenizeResponse {
+        encoding: encoding.name(),
+        text,
+    }))
+}
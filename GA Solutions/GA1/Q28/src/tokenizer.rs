@@ -0,0 +1,139 @@
+//! Token counting with the BPE encodings OpenAI models use. Models from other
+//! providers are counted with `cl100k_base` as a close approximation. Builds
+//! without the `tokenizer` feature estimate by script instead.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tokenizer")]
+use tiktoken_rs::CoreBPE;
+
+use crate::chat::{Message, MessageContent};
+use crate::image::{self, Detail};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TokenEncoding {
+    #[default]
+    #[serde(rename = "cl100k_base")]
+    Cl100k,
+    #[serde(rename = "o200k_base")]
+    O200k,
+}
+
+impl TokenEncoding {
+    #[cfg(feature = "tokenizer")]
+    fn bpe(self) -> &'static CoreBPE {
+        match self {
+            TokenEncoding::Cl100k => tiktoken_rs::cl100k_base_singleton(),
+            TokenEncoding::O200k => tiktoken_rs::o200k_base_singleton(),
+        }
+    }
+
+    /// Tokens in `text`, treating special-token markup as ordinary text.
+    #[cfg(feature = "tokenizer")]
+    pub fn count(self, text: &str) -> usize {
+        self.encode(text).len()
+    }
+
+    /// Estimated tokens in `text`, roughly as `cl100k_base` splits each
+    /// script: four ASCII characters a token, two of the other alphabets
+    /// below U+0800 (Greek, Cyrillic, Arabic...), a token for each CJK,
+    /// kana, Hangul or other character up to U+FFFF, and two for each emoji
+    /// or other character beyond.
+    #[cfg(not(feature = "tokenizer"))]
+    pub fn count(self, text: &str) -> usize {
+        let quarters: usize = text
+            .chars()
+            .map(|c| match c as u32 {
+                0..=0x7f => 1,
+                0x80..=0x7ff => 2,
+                0x800..=0xffff => 4,
+                _ => 8,
+            })
+            .sum();
+        quarters.div_ceil(4)
+    }
+
+    /// The most bytes of text one token can stand for: the longest entry in
+    /// either vocabulary. Every text takes at least `len / max_token_bytes` tokens.
+    #[cfg(feature = "tokenizer")]
+    pub fn max_token_bytes(self) -> usize {
+        128
+    }
+
+    /// The most bytes one token of `count`'s estimate stands for: four ASCII
+    /// characters, two of two bytes, one of three or half of four.
+    #[cfg(not(feature = "tokenizer"))]
+    pub fn max_token_bytes(self) -> usize {
+        4
+    }
+
+    /// Token ids for `text`, treating special-token markup as ordinary text.
+    #[cfg(feature = "tokenizer")]
+    pub fn encode(self, text: &str) -> Vec<u32> {
+        self.bpe().encode_ordinary(text)
+    }
+
+    /// Text for `tokens`, or the first id the encoding does not know. A
+    /// sequence that ends inside a multi-byte character decodes with U+FFFD
+    /// in its place, as streaming detokenizers do.
+    #[cfg(feature = "tokenizer")]
+    pub fn decode(self, tokens: &[u32]) -> Result<String, u32> {
+        let bytes = self.bpe().decode_bytes(tokens).map_err(|e| e.token)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenEncoding::Cl100k => "cl100k_base",
+            TokenEncoding::O200k => "o200k_base",
+        }
+    }
+
+    /// Prompt tokens for a chat request, counted the way OpenAI bills them:
+    /// each message costs its role and content plus 3 framing tokens, a
+    /// `name` its text plus 1, and the reply is primed with 3 more. This is
+    /// what `usage.prompt_tokens` reports; client code can call it to budget
+    /// requests the same way.
+    pub fn count_messages(self, messages: &[Message]) -> usize {
+        const PER_MESSAGE: usize = 3;
+        const PER_NAME: usize = 1;
+        const REPLY_PRIMING: usize = 3;
+        messages
+            .iter()
+            .map(|m| {
+                let content = m.content.as_ref().map_or(0, |c| self.count_content(c));
+                let name = m.name.as_deref().map_or(0, |n| PER_NAME + self.count(n));
+                PER_MESSAGE + self.count(&m.role) + content + name
+            })
+            .sum::<usize>()
+            + REPLY_PRIMING
+    }
+
+    /// Tokens in a message's content: each text part on its own, and each
+    /// image by `image::tokens`. Other parts are free.
+    pub fn count_content(self, content: &MessageContent) -> usize {
+        match content {
+            MessageContent::Text(text) => self.count(text),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .map(|part| match (&part.text, &part.image_url) {
+                    (Some(text), _) => self.count(text),
+                    (None, Some(image)) => image.tokens(),
+                    // Gemini's inline images carry no URL to size.
+                    (None, None) if part.kind == "image_url" => image::tokens(image::UNKNOWN_SIZE, Detail::Auto),
+                    (None, None) => 0,
+                })
+                .sum(),
+        }
+    }
+}
+
+/// Where the last whitespace after a letter or digit starts in `text`. No
+/// token spans it: the tokenizers end a word or number there before
+/// splitting further, so text streamed in pieces can be counted up to it.
+pub fn token_boundary(text: &str) -> Option<usize> {
+    text.char_indices()
+        .rev()
+        .zip(text.chars().rev().skip(1))
+        .find(|((_, c), previous)| c.is_whitespace() && previous.is_alphanumeric())
+        .map(|((i, _), _)| i)
+}
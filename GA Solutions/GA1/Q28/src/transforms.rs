@@ -0,0 +1,119 @@
+//! Rewrites applied to outgoing deltas, configured per stub rule, for testing
+//! how clients sanitize, redact and diff text that is not quite what the
+//! model "meant" to send.
+//!
+//! Transforms see one chunk at a time, so a pattern split across a chunk
+//! boundary is not matched. Random choices come from the request seed, so a
+//! replay with the same seed is corrupted the same way.
+
+use serde::Deserialize;
+
+use crate::generator::{self, SPLITMIX_GAMMA};
+use crate::stubs::Pattern;
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Transform {
+    /// Swaps adjacent letters, each pair with probability `rate`.
+    Typos { rate: f64 },
+    /// Replaces every match of `pattern` with `replacement`.
+    Redact {
+        pattern: Pattern,
+        #[serde(default = "default_replacement")]
+        replacement: String,
+    },
+    /// Re-reads the UTF-8 bytes of non-ASCII characters as Latin-1, the
+    /// classic "cafÃ©" mis-decoding, each character with probability `rate`.
+    Mojibake {
+        #[serde(default = "always")]
+        rate: f64,
+    },
+    /// Replaces non-ASCII characters with U+FFFD, as a lossy decoder would,
+    /// each with probability `rate`.
+    ReplacementChar {
+        #[serde(default = "always")]
+        rate: f64,
+    },
+    Uppercase,
+}
+
+fn default_replacement() -> String {
+    "[REDACTED]".to_string()
+}
+
+fn always() -> f64 {
+    1.0
+}
+
+/// The transforms of one stream together with their random state.
+pub struct Pipeline {
+    transforms: Vec<Transform>,
+    rng: Rng,
+}
+
+impl Pipeline {
+    pub fn new(transforms: &[Transform], seed: u64) -> Pipeline {
+        Pipeline {
+            transforms: transforms.to_vec(),
+            rng: Rng(seed),
+        }
+    }
+
+    /// Runs `chunk` through every transform in order.
+    pub fn apply(&mut self, mut chunk: String) -> String {
+        let rng = &mut self.rng;
+        for transform in &self.transforms {
+            chunk = match transform {
+                Transform::Typos { rate } => typos(&chunk, *rate, rng),
+                Transform::Redact { pattern, replacement } => {
+                    pattern.regex().replace_all(&chunk, replacement.as_str()).into_owned()
+                }
+                Transform::Mojibake { rate } => map_non_ascii(&chunk, *rate, rng, |ch, out| {
+                    let mut buf = [0; 4];
+                    out.extend(ch.encode_utf8(&mut buf).bytes().map(char::from));
+                }),
+                Transform::ReplacementChar { rate } => {
+                    map_non_ascii(&chunk, *rate, rng, |_, out| out.push(char::REPLACEMENT_CHARACTER))
+                }
+                Transform::Uppercase => chunk.to_uppercase(),
+            };
+        }
+        chunk
+    }
+}
+
+/// A SplitMix64 sequence.
+struct Rng(u64);
+
+impl Rng {
+    fn chance(&mut self, rate: f64) -> bool {
+        self.0 = self.0.wrapping_add(SPLITMIX_GAMMA);
+        generator::unit(generator::mix64(self.0)) < rate
+    }
+}
+
+fn typos(chunk: &str, rate: f64, rng: &mut Rng) -> String {
+    let mut chars: Vec<char> = chunk.chars().collect();
+    let mut i = 0;
+    while i + 1 < chars.len() {
+        if chars[i].is_alphabetic() && chars[i + 1].is_alphabetic() && chars[i] != chars[i + 1] && rng.chance(rate) {
+            chars.swap(i, i + 1);
+            // Never swap a letter twice, or the typo could undo itself.
+            i += 1;
+        }
+        i += 1;
+    }
+    chars.into_iter().collect()
+}
+
+fn map_non_ascii(chunk: &str, rate: f64, rng: &mut Rng, replace: impl Fn(char, &mut String)) -> String {
+    let mut out = String::with_capacity(chunk.len());
+    for ch in chunk.chars() {
+        if !ch.is_ascii() && rng.chance(rate) {
+            replace(ch, &mut out);
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
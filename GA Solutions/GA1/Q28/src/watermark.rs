@@ -0,0 +1,89 @@
+//! Optional markers tying each reply to the server and stub rule that
+//! produced it, so mock output that leaks into downstream datasets can be
+//! recognised: zero-width characters hidden after the first chunk's text,
+//! and/or an `x_mock.watermark` object on the first chunk. `detect` finds the
+//! hidden form in any text, which is what `stream-api detect` runs.
+
+use serde::Serialize;
+
+use crate::config::WatermarkConfig;
+
+/// `instance` when none is configured.
+pub const DEFAULT_INSTANCE: &str = "stream-api";
+
+/// Opens and closes a hidden marker (WORD JOINER).
+const FENCE: char = '\u{2060}';
+/// Zero and one bits (ZERO WIDTH SPACE, ZERO WIDTH NON-JOINER).
+const ZERO: char = '\u{200B}';
+const ONE: char = '\u{200C}';
+/// Separates the instance from the rule inside a marker.
+const SEPARATOR: u8 = 0;
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Watermark {
+    pub instance: String,
+    /// The `[[stubs]]` rule that matched, if it has a name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+impl Watermark {
+    pub fn new(config: &WatermarkConfig, rule: Option<&str>) -> Watermark {
+        Watermark {
+            instance: config.instance.clone().unwrap_or_else(|| DEFAULT_INSTANCE.to_string()),
+            rule: rule.map(str::to_string),
+        }
+    }
+
+    /// The marker as zero-width characters, eight per byte of
+    /// `instance`, NUL, `rule`.
+    pub fn hidden(&self) -> String {
+        let mut payload = self.instance.as_bytes().to_vec();
+        if let Some(rule) = &self.rule {
+            payload.push(SEPARATOR);
+            payload.extend_from_slice(rule.as_bytes());
+        }
+        let mut out = String::with_capacity((payload.len() * 8 + 2) * 3);
+        out.push(FENCE);
+        for byte in payload {
+            for bit in (0..8).rev() {
+                out.push(if byte >> bit & 1 == 1 { ONE } else { ZERO });
+            }
+        }
+        out.push(FENCE);
+        out
+    }
+}
+
+/// Every hidden marker in `text`, in order.
+pub fn detect(text: &str) -> Vec<Watermark> {
+    let mut found = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != FENCE {
+            continue;
+        }
+        let mut bits = Vec::new();
+        while let Some(&bit @ (ZERO | ONE)) = chars.peek() {
+            bits.push(bit == ONE);
+            chars.next();
+        }
+        if chars.peek() != Some(&FENCE) || bits.is_empty() || bits.len() % 8 != 0 {
+            continue;
+        }
+        chars.next();
+        let payload: Vec<u8> = bits.chunks(8).map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8)).collect();
+        let (instance, rule) = match payload.iter().position(|&b| b == SEPARATOR) {
+            Some(at) => (&payload[..at], Some(&payload[at + 1..])),
+            None => (&payload[..], None),
+        };
+        let (Ok(instance), Ok(rule)) = (std::str::from_utf8(instance), rule.map(std::str::from_utf8).transpose()) else {
+            continue;
+        };
+        found.push(Watermark {
+            instance: instance.to_string(),
+            rule: rule.map(str::to_string),
+        });
+    }
+    found
+}
@@ -0,0 +1,150 @@
+//! Delivery of lifecycle events to configured webhooks (`[[webhooks]]`), so
+//! external test orchestrators can react to mock traffic without polling.
+//!
+//! Each delivery is a JSON `POST` of one `LifecycleEvent`. With a `secret`,
+//! it carries `X-Mock-Timestamp` and `X-Mock-Signature: sha256=<hex>`, the
+//! HMAC-SHA256 of `"{timestamp}.{body}"`. Network errors, 429s and 5xx
+//! responses are retried with exponential backoff; deliveries run in the
+//! background and never hold up the stream. Builds without the `webhooks`
+//! feature accept the config but deliver nothing.
+
+#[cfg(feature = "webhooks")]
+use bytes::Bytes;
+#[cfg(feature = "webhooks")]
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+#[cfg(feature = "webhooks")]
+use sha2::Sha256;
+#[cfg(feature = "webhooks")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::lifecycle::{EventKind, LifecycleEvent};
+#[cfg(feature = "webhooks")]
+use crate::metrics;
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Event types to send. Defaults to all of them.
+    #[serde(default = "all_events")]
+    pub events: Vec<EventKind>,
+    /// Signs deliveries when set.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Further attempts after the first fails.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Wait before the first retry; doubled for each one after.
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn all_events() -> Vec<EventKind> {
+    EventKind::ALL.to_vec()
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    250
+}
+
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+pub struct Webhooks {
+    hooks: Vec<WebhookConfig>,
+    #[cfg(feature = "webhooks")]
+    client: reqwest::Client,
+}
+
+impl Webhooks {
+    pub fn new(hooks: &[WebhookConfig]) -> Webhooks {
+        #[cfg(not(feature = "webhooks"))]
+        if !hooks.is_empty() {
+            log::warn!("built without the webhooks feature; {} webhook(s) will not be called", hooks.len());
+        }
+        Webhooks {
+            hooks: if cfg!(feature = "webhooks") { hooks.to_vec() } else { Vec::new() },
+            #[cfg(feature = "webhooks")]
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Starts delivering `event` to every hook subscribed to its type.
+    #[cfg(feature = "webhooks")]
+    pub fn dispatch(&self, event: &LifecycleEvent) {
+        let mut body = None;
+        for hook in self.hooks.iter().filter(|h| h.events.contains(&event.kind)) {
+            let body = body
+                .get_or_insert_with(|| Bytes::from(serde_json::to_vec(event).expect("lifecycle events always serialize")))
+                .clone();
+            metrics::spawn(deliver(self.client.clone(), hook.clone(), event.kind, body));
+        }
+    }
+
+    #[cfg(not(feature = "webhooks"))]
+    pub fn dispatch(&self, _event: &LifecycleEvent) {}
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `"{timestamp}.{body}"` under `secret`.
+#[cfg(feature = "webhooks")]
+pub fn signature(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    let mut out = String::with_capacity(7 + digest.len() * 2);
+    out.push_str("sha256=");
+    for b in digest {
+        out.push_str(&format!("{:02x}", b));
+    }
+    out
+}
+
+#[cfg(feature = "webhooks")]
+async fn deliver(client: reqwest::Client, hook: WebhookConfig, kind: EventKind, body: Bytes) {
+    for attempt in 0..=hook.max_retries {
+        if attempt > 0 {
+            let backoff = hook.retry_backoff_ms.saturating_mul(1 << (attempt - 1).min(16));
+            tokio::time::sleep(Duration::from_millis(backoff)).await;
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut request = client
+            .post(&hook.url)
+            .timeout(Duration::from_millis(hook.timeout_ms))
+            .header("Content-Type", "application/json")
+            .header("X-Mock-Event", kind.name())
+            .body(body.clone());
+        if let Some(secret) = &hook.secret {
+            request = request
+                .header("X-Mock-Timestamp", timestamp)
+                .header("X-Mock-Signature", signature(secret, timestamp, &body));
+        }
+        match request.send().await {
+            Ok(resp) if resp.status().is_success() => {
+                metrics::WEBHOOK_DELIVERIES.inc();
+                return;
+            }
+            Ok(resp) if !(resp.status().is_server_error() || resp.status().as_u16() == 429) => {
+                log::debug!("webhook {} answered {}; not retrying", hook.url, resp.status());
+                break;
+            }
+            Ok(resp) => log::debug!("webhook {} answered {}; attempt {}", hook.url, resp.status(), attempt + 1),
+            Err(e) => log::debug!("webhook {} failed: {}; attempt {}", hook.url, e, attempt + 1),
+        }
+    }
+    log::warn!("giving up delivering {} to webhook {}", kind.name(), hook.url);
+    metrics::WEBHOOK_FAILURES.inc();
+}
@@ -0,0 +1,153 @@
+//! Anthropic's Messages API: the request, the whole `message`, the streamed
+//! events and the error body, which rules with `error_style = "anthropic"`
+//! also send.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// `POST /v1/messages`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MessagesRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub max_tokens: usize,
+    /// A string or a list of `text` blocks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<Content>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Map<String, Value>>,
+    /// Every other field, such as `top_k` or `thinking`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A turn, with the `user` and `assistant` roles.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub role: String,
+    pub content: Content,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Content {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+/// A `text`, `image`, `tool_use` or `tool_result` block, or any other kind,
+/// its fields in `extra`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ContentBlock {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// `tool_use`'s id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `tool_use`'s arguments, as a JSON object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The whole reply, and the start of a streamed one with no content yet.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MessagesResponse {
+    pub id: String,
+    /// Always `message`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub role: String,
+    pub content: Vec<ContentBlock>,
+    pub model: String,
+    /// `end_turn`, `max_tokens`, `stop_sequence` or `tool_use`; `null`
+    /// until the reply ends.
+    pub stop_reason: Option<String>,
+    pub stop_sequence: Option<String>,
+    pub usage: Usage,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Usage {
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<usize>,
+}
+
+/// One `data:` event of the stream, named by its `event:` line as well.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    MessageStart { message: MessagesResponse },
+    ContentBlockStart { index: usize, content_block: ContentBlock },
+    Ping,
+    ContentBlockDelta { index: usize, delta: Delta },
+    ContentBlockStop { index: usize },
+    MessageDelta { delta: MessageDelta, usage: DeltaUsage },
+    MessageStop,
+    Error { error: ErrorBody },
+}
+
+/// A `text_delta` or `input_json_delta`, or any other kind, its fields in
+/// `extra`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Delta {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// A piece of a `tool_use` block's `input`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial_json: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// How the reply ended, sent once before `message_stop`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MessageDelta {
+    pub stop_reason: Option<String>,
+    pub stop_sequence: Option<String>,
+}
+
+/// The output tokens of the whole reply.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DeltaUsage {
+    pub output_tokens: usize,
+}
+
+/// `{"type": "error", "error": {"type", "message"}}`, as a response body or
+/// an in-stream `data:` event.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ErrorEnvelope {
+    /// Always `error`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub error: ErrorBody,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ErrorBody {
+    /// Such as `rate_limit_error` or `overloaded_error`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub message: String,
+}
@@ -0,0 +1,73 @@
+//! Cohere's `/v1/chat`: the request, the newline-delimited stream events
+//! and the error body.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChatRequest {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chat_history: Vec<ChatMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preamble: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+}
+
+/// A turn, with Cohere's `USER`, `CHATBOT` and `SYSTEM` roles.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChatMessage {
+    pub role: String,
+    pub message: String,
+}
+
+/// One line of the stream.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "event_type")]
+pub enum StreamEvent {
+    #[serde(rename = "stream-start")]
+    StreamStart { is_finished: bool, generation_id: String },
+    #[serde(rename = "text-generation")]
+    TextGeneration { is_finished: bool, text: String },
+    #[serde(rename = "stream-end")]
+    StreamEnd {
+        is_finished: bool,
+        /// `COMPLETE`, `MAX_TOKENS` or `ERROR`.
+        finish_reason: String,
+        response: ChatResponse,
+    },
+}
+
+/// The whole reply, as the `stream-end` event carries it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChatResponse {
+    pub response_id: String,
+    pub generation_id: String,
+    pub text: String,
+    pub finish_reason: String,
+    pub chat_history: Vec<ChatMessage>,
+    pub meta: Meta,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Meta {
+    pub billed_units: BilledUnits,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BilledUnits {
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+}
+
+/// `{"message": "..."}`, the body of every error response.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Error {
+    pub message: String,
+}
@@ -0,0 +1,23 @@
+//! Owned serde types for every provider format the mock speaks, for Rust
+//! services that read or write the same JSON.
+//!
+//! The handlers build their replies from borrowed, serialize-only structs
+//! tuned for the hot path; these mirror them field for field and also
+//! deserialize, so the mock's output and the providers' own parse into them
+//! and serialize back unchanged. Optional fields are left out when absent,
+//! as the mock leaves them out, except where the mock sends an explicit
+//! `null`. Unknown fields are kept in an `extra` map where a provider adds
+//! fields freely, and dropped elsewhere.
+//!
+//! The mock serves neither Anthropic's Messages API nor Ollama's chat, so
+//! apart from the Anthropic error body its `error_style` produces, those
+//! types are checked against the providers' documented JSON rather than
+//! the mock's own output.
+
+pub mod anthropic;
+pub mod cohere;
+pub mod gemini;
+pub mod ollama;
+pub mod openai;
+pub mod realtime;
+pub mod responses;
@@ -0,0 +1,87 @@
+//! Ollama's `/api/chat`: the request, each newline-delimited reply object
+//! and the error body.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// `POST /api/chat`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    /// Ollama streams unless this is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    /// `json` or a JSON schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<Value>,
+    /// Sampling options such as `temperature`, `seed` and `num_predict`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Value>>,
+    /// A duration such as `5m`, or seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A turn, with the `system`, `user`, `assistant` and `tool` roles.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+    /// Base64 images.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ToolCall {
+    pub function: FunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    /// A JSON object, not a string as OpenAI sends.
+    pub arguments: Value,
+}
+
+/// One line of a streamed reply, or the whole reply when `stream` is
+/// `false`. The last has `done: true` and the timings and counts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChatResponse {
+    pub model: String,
+    /// RFC 3339.
+    pub created_at: String,
+    pub message: Message,
+    pub done: bool,
+    /// `stop`, `length` or `load`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done_reason: Option<String>,
+    /// Nanoseconds, as are the other durations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_duration: Option<u64>,
+}
+
+/// `{"error": "..."}`, as a response body or a line of the stream.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Error {
+    pub error: String,
+}
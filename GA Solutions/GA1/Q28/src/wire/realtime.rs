@@ -0,0 +1,93 @@
+//! Realtime transcription sessions: the events each side sends over the
+//! `/v1/realtime` WebSocket.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A client event, with the optional id errors about it echo.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClientEvent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    #[serde(flatten)]
+    pub kind: ClientEventKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum ClientEventKind {
+    #[serde(rename = "transcription_session.update")]
+    SessionUpdate { session: SessionUpdate },
+    #[serde(rename = "input_audio_buffer.append")]
+    Append {
+        /// Base64, in the session's `input_audio_format`.
+        audio: String,
+    },
+    #[serde(rename = "input_audio_buffer.commit")]
+    Commit {},
+    #[serde(rename = "input_audio_buffer.clear")]
+    Clear {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SessionUpdate {
+    /// `pcm16`, `g711_ulaw` or `g711_alaw`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_audio_format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_audio_transcription: Option<Value>,
+}
+
+/// A server event.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerEvent {
+    pub event_id: String,
+    #[serde(flatten)]
+    pub kind: ServerEventKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum ServerEventKind {
+    #[serde(rename = "transcription_session.created")]
+    SessionCreated { session: TranscriptionSession },
+    #[serde(rename = "transcription_session.updated")]
+    SessionUpdated { session: TranscriptionSession },
+    #[serde(rename = "input_audio_buffer.committed")]
+    Committed { previous_item_id: Option<String>, item_id: String },
+    #[serde(rename = "input_audio_buffer.cleared")]
+    Cleared {},
+    #[serde(rename = "conversation.item.input_audio_transcription.delta")]
+    TranscriptionDelta {
+        item_id: String,
+        content_index: usize,
+        delta: String,
+    },
+    #[serde(rename = "conversation.item.input_audio_transcription.completed")]
+    TranscriptionCompleted {
+        item_id: String,
+        content_index: usize,
+        transcript: String,
+    },
+    #[serde(rename = "error")]
+    Error { error: RealtimeError },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TranscriptionSession {
+    pub id: String,
+    pub object: String,
+    pub input_audio_format: String,
+    pub input_audio_transcription: Value,
+    pub turn_detection: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RealtimeError {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub code: String,
+    pub message: String,
+    /// The `event_id` of the client event at fault, if it had one.
+    pub event_id: Option<String>,
+}
@@ -0,0 +1,78 @@
+use actix_web::Error;
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, LocalBoxStream, Stream, StreamExt};
+use std::time::Duration;
+use tokio::time::{timeout_at, Instant};
+
+use crate::config::WriteMode;
+
+/// Applies the configured write policy to a stream of complete SSE events.
+pub fn apply<S>(events: S, mode: WriteMode, window: Duration) -> LocalBoxStream<'static, Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + 'static,
+{
+    match mode {
+        WriteMode::Flush => flush_each(events).boxed_local(),
+        WriteMode::Coalesce => coalesce(events, window).boxed_local(),
+    }
+}
+
+/// Yields back to the runtime after every event. actix only flushes its write
+/// buffer once the body stream returns `Pending`, so this guarantees one flush
+/// per event even when events are produced back-to-back.
+pub fn flush_each<S>(events: S) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + 'static,
+{
+    stream::unfold((Box::pin(events), false), |(mut events, yielded)| async move {
+        if yielded {
+            tokio::task::yield_now().await;
+        }
+        let event = events.next().await?;
+        Some((event, (events, true)))
+    })
+}
+
+/// Joins every event that arrives within `window` of the first one into a single chunk.
+pub fn coalesce<S>(events: S, window: Duration) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + 'static,
+{
+    stream::unfold((Some(Box::pin(events)), None), move |(events, pending)| async move {
+        if let Some(err) = pending {
+            return Some((Err(err), (None, None)));
+        }
+        let mut events = events?;
+        let mut buf = match events.next().await? {
+            Ok(first) => BytesMut::from(&first[..]),
+            Err(err) => return Some((Err(err), (None, None))),
+        };
+
+        let deadline = Instant::now() + window;
+        loop {
+            match timeout_at(deadline, events.next()).await {
+                Ok(Some(Ok(event))) => buf.extend_from_slice(&event),
+                // Hand out what was gathered so far, then the error on the next poll.
+                Ok(Some(Err(err))) => return Some((Ok(buf.freeze()), (None, Some(err)))),
+                Ok(None) => return Some((Ok(buf.freeze()), (None, None))),
+                Err(_) => return Some((Ok(buf.freeze()), (Some(events), None))),
+            }
+        }
+    })
+}
+
+/// Collects the whole body and releases it as a single chunk once the inner
+/// stream ends, reproducing the "everything arrives at once" proxy failure.
+pub fn buffer_all<S>(body: S) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + 'static,
+{
+    stream::once(async move {
+        let mut body = Box::pin(body);
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = body.next().await {
+            buf.extend_from_slice(&chunk?);
+        }
+        Ok(buf.freeze())
+    })
+}
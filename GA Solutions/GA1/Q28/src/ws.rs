@@ -0,0 +1,250 @@
+//! Just enough of RFC 6455 to serve WebSocket routes from an ordinary
+//! actix-web handler: the opening handshake and message framing. The
+//! `ws` feature of actix-http would pull in crates this build does without,
+//! and the mock only ever exchanges short text messages, so neither
+//! extensions nor subprotocols are negotiated.
+//!
+//! A route answers an upgrade request with `handshake`'s `101` and streams
+//! frames from `encode` as its body, reading the client's frames back out
+//! of the request payload with `decode`.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+
+use crate::error::MockError;
+
+/// Appended to the client's key before hashing, per the RFC.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn bits(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xa,
+        }
+    }
+
+    /// Close, ping and pong, which may not be fragmented or carry more than
+    /// 125 bytes.
+    pub fn is_control(self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+
+    fn from_bits(bits: u8) -> Option<Opcode> {
+        Some(match bits {
+            0x0 => Opcode::Continuation,
+            0x1 => Opcode::Text,
+            0x2 => Opcode::Binary,
+            0x8 => Opcode::Close,
+            0x9 => Opcode::Ping,
+            0xa => Opcode::Pong,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+/// Why a client's bytes could not be read as frames.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// Reserved bits, an unknown opcode, a frame masked the wrong way for
+    /// its sender, or an oversized or fragmented control frame.
+    Protocol,
+    /// A frame longer than the caller allows.
+    TooLarge,
+}
+
+impl FrameError {
+    /// The close code reporting the error.
+    pub fn close_code(&self) -> u16 {
+        match self {
+            FrameError::Protocol => 1002,
+            FrameError::TooLarge => 1009,
+        }
+    }
+}
+
+/// `Sec-WebSocket-Accept` for a client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    STANDARD.encode(sha1(format!("{}{}", key.trim(), ACCEPT_GUID).as_bytes()))
+}
+
+/// The `101 Switching Protocols` response to `req`, to be given the stream
+/// of frames as its body, or a `426` when `req` is not a version 13
+/// upgrade.
+pub fn handshake(req: &HttpRequest) -> Result<HttpResponseBuilder, MockError> {
+    let headers = req.headers();
+    let has = |name: header::HeaderName, token: &str| {
+        headers
+            .get_all(name)
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim().eq_ignore_ascii_case(token))
+    };
+    let key = headers.get(header::SEC_WEBSOCKET_KEY).and_then(|v| v.to_str().ok());
+    let key = match key {
+        Some(key) if has(header::UPGRADE, "websocket") && has(header::CONNECTION, "upgrade") && has(header::SEC_WEBSOCKET_VERSION, "13") => key,
+        _ => {
+            return Err(MockError::rejected(
+                StatusCode::UPGRADE_REQUIRED,
+                "This endpoint only speaks WebSocket (version 13).",
+                None,
+            ))
+        }
+    };
+    let mut response = HttpResponse::SwitchingProtocols();
+    response
+        .upgrade("websocket")
+        .insert_header((header::SEC_WEBSOCKET_ACCEPT, accept_key(key)));
+    Ok(response)
+}
+
+/// One unfragmented frame; `mask` is for clients, which must mask theirs.
+pub fn encode(opcode: Opcode, payload: &[u8], mask: Option<[u8; 4]>) -> Bytes {
+    let mut out = Vec::with_capacity(payload.len() + 14);
+    out.push(0x80 | opcode.bits());
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => out.push(mask_bit | len as u8),
+        len @ 126..=0xffff => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            out.extend_from_slice(&mask);
+            out.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+        None => out.extend_from_slice(payload),
+    }
+    Bytes::from(out)
+}
+
+/// A close frame with `code` and `reason`.
+pub fn close(code: u16, reason: &str) -> Bytes {
+    let mut payload = code.to_be_bytes().to_vec();
+    payload.extend_from_slice(reason.as_bytes());
+    encode(Opcode::Close, &payload, None)
+}
+
+/// The first frame in `buf`, unmasked, and the bytes it took, or `None`
+/// until the whole frame has arrived. A server reads its client's frames,
+/// which must be masked, and a client its server's, which must not be.
+pub fn decode(buf: &[u8], max_len: usize, from_client: bool) -> Result<Option<(Frame, usize)>, FrameError> {
+    let [first, second, ..] = *buf else {
+        return Ok(None);
+    };
+    if first & 0x70 != 0 {
+        return Err(FrameError::Protocol);
+    }
+    let opcode = Opcode::from_bits(first & 0x0f).ok_or(FrameError::Protocol)?;
+    let masked = second & 0x80 != 0;
+    if masked != from_client || (opcode.is_control() && (first & 0x80 == 0 || second & 0x7f > 125)) {
+        return Err(FrameError::Protocol);
+    }
+    let (len, mut at) = match second & 0x7f {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() >= 10 => (u64::from_be_bytes(buf[2..10].try_into().expect("eight bytes")), 10),
+        126 | 127 => return Ok(None),
+        len => (len as u64, 2),
+    };
+    if len > max_len as u64 {
+        return Err(FrameError::TooLarge);
+    }
+    let len = len as usize;
+    let mask = if masked {
+        let Some(mask) = buf.get(at..at + 4) else {
+            return Ok(None);
+        };
+        at += 4;
+        Some([mask[0], mask[1], mask[2], mask[3]])
+    } else {
+        None
+    };
+    let Some(payload) = buf.get(at..at + len) else {
+        return Ok(None);
+    };
+    let payload = match mask {
+        Some(mask) => payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect(),
+        None => payload.to_vec(),
+    };
+    Ok(Some((
+        Frame {
+            fin: first & 0x80 != 0,
+            opcode,
+            payload,
+        },
+        at + len,
+    )))
+}
+
+/// SHA-1, which the handshake needs and nothing else does.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476, 0xc3d2_e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (out, word) in digest.chunks_mut(4).zip(h) {
+        out.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
@@ -0,0 +1,138 @@
+//! Spoken replies: `modalities: ["text", "audio"]`.
+
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use common::{client, parse_events, post, start, unpaced_config};
+use streaming_llm_api::audio::{self, Tone, MAX_AUDIO_SECS, MS_PER_CHAR, SAMPLE_RATE};
+
+fn request(format: &str, stream: bool) -> serde_json::Value {
+    serde_json::json!({
+        "messages": [{"role": "user", "content": "Say hi"}],
+        "modalities": ["text", "audio"],
+        "audio": {"voice": "alloy", "format": format},
+        "stream": stream,
+    })
+}
+
+fn deltas(body: &str) -> Vec<serde_json::Value> {
+    parse_events(body)
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str::<serde_json::Value>(&e.data).unwrap()["choices"][0]["delta"].clone())
+        .collect()
+}
+
+#[test]
+fn tones_last_per_character_and_are_silent_on_whitespace() {
+    let samples = (SAMPLE_RATE * MS_PER_CHAR / 1000) as usize;
+    let pcm = Tone::new("alloy").speak("a b");
+    assert_eq!(pcm.len(), 3 * samples * 2);
+    let silence = &pcm[samples * 2..samples * 4];
+    assert!(silence.iter().all(|&b| b == 0));
+    assert!(pcm[..samples * 2].iter().any(|&b| b != 0));
+    assert_ne!(Tone::new("alloy").speak("a"), Tone::new("echo").speak("a"), "voices differ in pitch");
+
+    let wav = audio::wav(&pcm).unwrap();
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize, pcm.len());
+    assert_eq!(&wav[44..], &pcm[..]);
+}
+
+#[test]
+fn audio_stops_at_the_cap_across_chunks() {
+    let samples = (SAMPLE_RATE * MS_PER_CHAR / 1000) as usize;
+    let chars = (MAX_AUDIO_SECS * 1000 / MS_PER_CHAR) as usize;
+    let mut tone = Tone::new("alloy");
+    assert_eq!(tone.speak(&"a".repeat(chars - 2)).len(), (chars - 2) * samples * 2);
+    assert_eq!(tone.speak("abcd").len(), 2 * samples * 2);
+    assert!(tone.speak("more").is_empty());
+}
+
+#[actix_rt::test]
+async fn streams_carry_transcripts_with_their_audio() {
+    let base = start(unpaced_config());
+    let body = post(&base, request("pcm16", true)).await.text().await.unwrap();
+    let deltas = deltas(&body);
+    let spoken: Vec<&serde_json::Value> = deltas.iter().filter(|d| d["audio"]["transcript"].is_string()).collect();
+    assert!(spoken.len() > 1);
+    let id = spoken[0]["audio"]["id"].as_str().unwrap();
+    assert!(id.starts_with("audio_"), "{}", id);
+    let samples = (SAMPLE_RATE * MS_PER_CHAR / 1000) as usize;
+    let mut transcript = String::new();
+    let mut tone = Tone::new("alloy");
+    for delta in &spoken {
+        assert!(delta.get("content").is_none(), "{}", delta);
+        let text = delta["audio"]["transcript"].as_str().unwrap();
+        let data = STANDARD.decode(delta["audio"]["data"].as_str().unwrap()).unwrap();
+        assert_eq!(data.len(), text.chars().count() * samples * 2);
+        assert_eq!(data, tone.speak(text), "the wave continues across chunks");
+        transcript.push_str(text);
+    }
+    assert!(transcript.starts_with("Regarding your prompt 'Say hi':"));
+    assert!(spoken[1]["audio"].get("id").is_none());
+
+    let end = &deltas.last().unwrap()["audio"];
+    assert_eq!(end["id"], id);
+    assert!(end["expires_at"].as_u64().unwrap() > 1_700_000_000);
+    assert!(end.get("data").is_none());
+}
+
+#[actix_rt::test]
+async fn json_replies_carry_a_wav_file() {
+    let base = start(unpaced_config());
+    // `stream: false` is refused, so JSON is asked for through `Accept`.
+    let mut body = request("wav", false);
+    body.as_object_mut().unwrap().remove("stream");
+    let reply: serde_json::Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let message = &reply["choices"][0]["message"];
+    assert!(message["content"].is_null(), "{}", message);
+    let transcript = message["audio"]["transcript"].as_str().unwrap();
+    let wav = STANDARD.decode(message["audio"]["data"].as_str().unwrap()).unwrap();
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(&wav[44..], &Tone::new("alloy").speak(transcript)[..]);
+    assert!(message["audio"]["id"].as_str().unwrap().starts_with("audio_"));
+}
+
+#[actix_rt::test]
+async fn unsupported_audio_requests_are_rejected() {
+    let base = start(unpaced_config());
+    let cases = [
+        (request("wav", true), "audio.format"),
+        (request("mp3", true), "audio.format"),
+        (
+            serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "modalities": ["text", "audio"], "stream": true}),
+            "audio",
+        ),
+    ];
+    for (body, param) in cases {
+        let resp = post(&base, body).await;
+        assert_eq!(resp.status(), 400);
+        let error: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(error["error"]["param"], param, "{}", error);
+    }
+}
+
+#[actix_rt::test]
+async fn text_only_requests_ignore_the_audio_parameter() {
+    let base = start(unpaced_config());
+    let body = serde_json::json!({
+        "messages": [{"role": "user", "content": "hi"}],
+        "audio": {"voice": "alloy", "format": "mp3"},
+        "stream": true,
+    });
+    let text = post(&base, body).await.text().await.unwrap();
+    assert!(!text.contains("\"audio\""));
+    assert!(deltas(&text)[0]["content"].is_string());
+}
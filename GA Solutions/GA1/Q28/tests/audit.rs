@@ -0,0 +1,151 @@
+//! The hash-chained audit log of admin mutations and `/v1/admin/audit`.
+#![cfg(feature = "admin")]
+
+mod common;
+
+use std::path::Path;
+
+use common::{client, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::audit::{verify, AuditRecord, GENESIS};
+use streaming_llm_api::config::Config;
+
+const TOKEN: &str = "audit-token";
+
+fn admin_config(log: Option<&Path>, secret: Option<&str>) -> Config {
+    let mut config = unpaced_config();
+    config.admin.token = Some(TOKEN.to_string());
+    config.admin.audit_log = log.map(Path::to_path_buf);
+    config.admin.audit_secret = secret.map(str::to_string);
+    config
+}
+
+async fn admin(method: reqwest::Method, base: &str, path: &str, body: Option<Value>) -> reqwest::Response {
+    let mut req = client().request(method, format!("{}{}", base, path)).bearer_auth(TOKEN);
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    req.send().await.unwrap()
+}
+
+async fn audit(base: &str, query: &str) -> Value {
+    admin(reqwest::Method::GET, base, &format!("/v1/admin/audit{}", query), None).await.json().await.unwrap()
+}
+
+async fn start_and_stop_a_storm(base: &str) {
+    let spec = json!({"probability": 0.5, "duration_secs": 60, "seed": 7});
+    assert_eq!(admin(reqwest::Method::POST, base, "/v1/admin/storm", Some(spec)).await.status(), 200);
+    assert_eq!(admin(reqwest::Method::DELETE, base, "/v1/admin/storm", None).await.status(), 200);
+}
+
+fn records(list: &Value) -> Vec<AuditRecord> {
+    serde_json::from_value(list["data"].clone()).unwrap()
+}
+
+fn records_of(list: &Value) -> Vec<u64> {
+    records(list).iter().map(|r| r.seq).collect()
+}
+
+#[actix_rt::test]
+async fn mutations_are_chained_with_actor_and_change() {
+    let base = start(admin_config(None, None));
+    start_and_stop_a_storm(&base).await;
+    assert_eq!(admin(reqwest::Method::DELETE, &base, "/v1/admin/replay", None).await.status(), 200);
+    // Reads are not audited.
+    admin(reqwest::Method::GET, &base, "/v1/admin/storm", None).await;
+
+    let list = audit(&base, "").await;
+    assert_eq!(list["valid"], true);
+    let records = records(&list);
+    let actions: Vec<&str> = records.iter().map(|r| r.action.as_str()).collect();
+    assert_eq!(actions, ["storm.start", "storm.stop", "replay.clear"]);
+
+    let start = &records[0];
+    assert_eq!(start.prev_hash, GENESIS);
+    assert!(start.actor.starts_with("sha256:") && start.actor.len() == 23 && !start.actor.contains(TOKEN));
+    assert_eq!(start.change.before, Value::Null);
+    assert_eq!(start.change.after["probability"], 0.5);
+    assert_eq!(records[1].change.before["seed"], 7);
+    assert_eq!(records[1].prev_hash, start.hash);
+    assert!(records.iter().all(|r| r.signature.is_none()));
+
+    let stops = audit(&base, "?action=storm.stop").await;
+    assert_eq!(records_of(&stops), [1]);
+    assert_eq!(records_of(&audit(&base, "?after=0").await), [1, 2]);
+}
+
+#[actix_rt::test]
+async fn the_chain_continues_in_the_log_file_and_shows_tampering() {
+    let dir = std::env::temp_dir().join(format!("stream-api-audit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.jsonl");
+    let _ = std::fs::remove_file(&path);
+
+    start_and_stop_a_storm(&start(admin_config(Some(&path), Some("s3cret")))).await;
+    let base = start(admin_config(Some(&path), Some("s3cret")));
+    start_and_stop_a_storm(&base).await;
+    let list = audit(&base, "").await;
+    assert_eq!(list["valid"], true);
+    assert_eq!(records_of(&list), [0, 1, 2, 3]);
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 4);
+
+    // Another secret cannot vouch for the records.
+    assert_eq!(audit(&start(admin_config(Some(&path), Some("other"))), "").await["broken_at"], 0);
+
+    // Nor can a log with one record edited.
+    let edited = std::fs::read_to_string(&path).unwrap().replacen("\"probability\":0.5", "\"probability\":0.1", 2);
+    std::fs::write(&path, edited).unwrap();
+    let list = audit(&start(admin_config(Some(&path), Some("s3cret"))), "").await;
+    assert_eq!(list["valid"], false);
+    assert_eq!(list["broken_at"], 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[actix_rt::test]
+async fn unreadable_lines_are_skipped_and_break_the_chain() {
+    let dir = std::env::temp_dir().join(format!("stream-api-audit-unreadable-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.jsonl");
+    let _ = std::fs::remove_file(&path);
+
+    start_and_stop_a_storm(&start(admin_config(Some(&path), None))).await;
+    // A record cut short, as by a crash mid-write.
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    std::io::Write::write_all(&mut file, b"{\"seq\":2,\"tim").unwrap();
+    drop(file);
+
+    let base = start(admin_config(Some(&path), None));
+    start_and_stop_a_storm(&base).await;
+    let list = audit(&base, "").await;
+    assert_eq!(records_of(&list), [0, 1, 3, 4]);
+    assert_eq!(list["valid"], false);
+    assert_eq!(list["broken_at"], 2);
+    // Still persisted, each on its own line.
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(text.lines().count(), 5);
+    assert!(text.lines().last().unwrap().contains("\"seq\":4"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[actix_rt::test]
+async fn the_audit_log_needs_the_admin_token() {
+    let base = start(admin_config(None, None));
+    let resp = client().get(format!("{}/v1/admin/audit", base)).bearer_auth("sk-user").send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_rt::test]
+async fn dropped_or_reordered_records_break_the_chain() {
+    let base = start(admin_config(None, None));
+    start_and_stop_a_storm(&base).await;
+    start_and_stop_a_storm(&base).await;
+    let records = records(&audit(&base, "").await);
+    assert_eq!(verify(&records, None), Ok(()));
+
+    let mut reordered = records.clone();
+    reordered.swap(1, 2);
+    assert_eq!(verify(&reordered, None), Err(2));
+    let mut dropped = records;
+    dropped.remove(1);
+    assert_eq!(verify(&dropped, None), Err(2));
+}
@@ -0,0 +1,75 @@
+//! Per-chunk emission times recorded in the capture store.
+#![cfg(feature = "recording")]
+
+mod common;
+
+use futures::StreamExt;
+use std::time::Duration;
+
+use common::{client, post, start, with_profile};
+use streaming_llm_api::stubs::ResponseProfile;
+
+fn paced() -> String {
+    start(with_profile(ResponseProfile {
+        chunk_delay_ms: 100,
+        chunks: 4,
+        ..ResponseProfile::default()
+    }))
+}
+
+async fn captures(base: &str) -> Vec<serde_json::Value> {
+    let body: serde_json::Value = client()
+        .get(format!("{}/v1/internal/captures", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["data"].as_array().unwrap().clone()
+}
+
+fn millis(values: &serde_json::Value) -> Vec<u64> {
+    values.as_array().unwrap().iter().map(|v| v.as_u64().unwrap()).collect()
+}
+
+#[actix_rt::test]
+async fn every_chunk_is_timestamped() {
+    let base = paced();
+    post(&base, serde_json::json!({"prompt": "pacing", "stream": true})).await.text().await.unwrap();
+
+    let capture = &captures(&base).await[0];
+    let chunks = millis(&capture["timing"]["chunks_at_ms"]);
+    assert_eq!(chunks.len(), 4);
+    assert!(chunks[0] >= capture["received_at_ms"].as_u64().unwrap());
+    for gap in chunks.windows(2).map(|w| w[1] - w[0]) {
+        assert!((90..200).contains(&gap), "no inter-chunk gap should stray far from 100 ms: {:?}", chunks);
+    }
+    assert!(capture["timing"]["ended_at_ms"].as_u64().unwrap() >= chunks[3]);
+}
+
+#[actix_rt::test]
+async fn abandoned_streams_keep_what_was_sent() {
+    let base = paced();
+    let mut body = post(&base, serde_json::json!({"prompt": "leaving", "stream": true})).await.bytes_stream();
+    body.next().await.unwrap().unwrap();
+    drop(body);
+
+    for _ in 0..50 {
+        if let Some(timing) = captures(&base).await[0].get("timing").filter(|t| !t.is_null()) {
+            let sent = millis(&timing["chunks_at_ms"]).len();
+            assert!((1..4).contains(&sent), "{} chunks recorded", sent);
+            return;
+        }
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the abandoned stream was never timed");
+}
+
+#[actix_rt::test]
+async fn requests_that_never_stream_have_no_timing() {
+    let base = paced();
+    let resp = post(&base, serde_json::json!({"prompt": "not streaming", "stream": false})).await;
+    assert_eq!(resp.status(), 400);
+    assert!(captures(&base).await[0]["timing"].is_null());
+}
@@ -0,0 +1,130 @@
+//! `stream-api check`: config validation before deployment.
+
+use std::process::Command;
+
+use streaming_llm_api::check::{check_text, Finding, Severity};
+#[cfg(feature = "generators")]
+use streaming_llm_api::corpus;
+
+fn messages(findings: &[Finding]) -> Vec<String> {
+    findings.iter().map(Finding::to_string).collect()
+}
+
+#[test]
+fn a_clean_config_has_no_findings() {
+    let text = r#"
+[stream]
+event_ids = true
+
+[[stubs]]
+name = "slow"
+match = { contains = "slow" }
+profile.chunk_delay_ms = 500
+
+[[stubs]]
+match = {}
+"#;
+    assert_eq!(check_text(text), []);
+}
+
+#[test]
+fn schema_errors_point_at_their_line() {
+    let text = "[stream]\nevent_ids = true\n\n[[stubs]]\nmatch = { contains = \"x\" }\nprofile.chunk_delay = 5\n";
+    let findings = check_text(text);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].severity, Severity::Error);
+    assert_eq!(findings[0].line, Some(6));
+    assert!(findings[0].message.contains("chunk_delay"), "{}", findings[0]);
+}
+
+#[test]
+fn shadowed_and_contradictory_rules_are_reported() {
+    let text = r#"
+[[stubs]]
+name = "errors"
+match = { contains = "error" }
+
+[[stubs]]
+name = "timeout errors"
+match = { starts_with = "timeout error" }
+
+[[stubs]]
+name = "exact"
+match = { equals = "hello", contains = "bye" }
+
+[[stubs]]
+name = "catch-all"
+match = {}
+
+[[stubs]]
+name = "too late"
+match = { contains = "anything" }
+"#;
+    let findings = messages(&check_text(text));
+    assert_eq!(
+        findings,
+        [
+            "line 6: warning: stub rule 'timeout errors' is unreachable: every prompt it matches is taken by 'errors' first",
+            "line 10: warning: stub rule 'exact' can never match: its conditions contradict each other",
+            "line 18: warning: stub rule 'too late' is unreachable: every prompt it matches is taken by 'catch-all' first",
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "webhooks")]
+fn unknown_presets_and_bad_urls_are_errors() {
+    let text = r#"
+[model_presets]
+gpt-4o = "no-such-preset"
+
+[[webhooks]]
+url = "not a url"
+"#;
+    let findings = check_text(text);
+    assert_eq!(findings.len(), 2);
+    assert!(findings.iter().all(|f| f.severity == Severity::Error));
+    assert_eq!(findings[0].line, Some(3));
+    assert_eq!(findings[1].line, Some(5));
+}
+
+#[cfg(feature = "generators")]
+#[test]
+fn corpus_files_must_exist_and_be_packed() {
+    let dir = std::env::temp_dir().join(format!("stream-api-check-corpus-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let packed = dir.join("replies.corpus");
+    corpus::pack(std::io::Cursor::new("\"one\"\n"), std::fs::File::create(&packed).unwrap()).unwrap();
+    let unpacked = dir.join("replies.jsonl");
+    std::fs::write(&unpacked, "\"one\"\n").unwrap();
+    let config = |path: &std::path::Path| format!("[stream]\nevent_ids = true\n\n[corpus]\npath = {:?}\n", path);
+
+    assert_eq!(check_text(&config(&packed)), []);
+    for path in [dir.join("missing.corpus"), unpacked] {
+        let findings = check_text(&config(&path));
+        assert_eq!(findings.len(), 1, "{:?}", findings);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[0].line, Some(5));
+        assert!(findings[0].message.starts_with("corpus.path"), "{}", findings[0]);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_cli_exits_non_zero_on_errors() {
+    let dir = std::env::temp_dir().join(format!("stream-api-check-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let good = dir.join("good.toml");
+    let bad = dir.join("bad.toml");
+    std::fs::write(&good, "[stream]\nevent_ids = true\n").unwrap();
+    std::fs::write(&bad, "[stream]\nevent_ids = \"yes\"\n").unwrap();
+
+    let run = |path: &std::path::Path| Command::new(env!("CARGO_BIN_EXE_stream-api")).arg("check").arg(path).output().unwrap();
+    let ok = run(&good);
+    assert!(ok.status.success());
+    assert!(String::from_utf8_lossy(&ok.stdout).contains("0 error(s), 0 warning(s)"));
+    let failed = run(&bad);
+    assert_eq!(failed.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&failed.stdout).contains("line 2: error:"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
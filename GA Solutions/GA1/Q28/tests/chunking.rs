@@ -0,0 +1,267 @@
+//! Property tests for `CyclingText`, the chunk size arithmetic and the
+//! chunking strategies.
+
+mod common;
+
+use proptest::prelude::*;
+use unicode_segmentation::UnicodeSegmentation;
+
+use common::{parse_events, post, start, with_profile};
+use streaming_llm_api::chat::CODE_CONTENT;
+use streaming_llm_api::chunking::{fence_safe, rechunk, ChunkStrategy};
+use streaming_llm_api::generator::{chunk_size_for, CyclingText};
+use streaming_llm_api::stubs::ResponseProfile;
+
+/// What the generator should produce: the header once, then the corpus
+/// repeated with a blank line between passes, cut at `total_chars`.
+fn expected(header: &str, corpus: &str, total_chars: usize) -> String {
+    header
+        .chars()
+        .chain(std::iter::repeat(corpus).flat_map(|pass| pass.chars().chain("\n\n".chars())))
+        .take(total_chars)
+        .collect()
+}
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+/// `text` cut every `size` characters, as the generator would.
+fn pieces(text: &str, size: usize) -> Box<dyn Iterator<Item = String>> {
+    let chars: Vec<char> = text.chars().collect();
+    let pieces: Vec<String> = chars.chunks(size.max(1)).map(|c| c.iter().collect()).collect();
+    Box::new(pieces.into_iter())
+}
+
+proptest! {
+    #[test]
+    fn chunks_reconstruct_the_content(
+        header in "\\PC{0,20}",
+        corpus in "\\PC{1,40}",
+        total_chars in 0usize..300,
+        chunk_chars in 0usize..50,
+    ) {
+        let corpus = leak(corpus);
+        let chunks: Vec<String> = CyclingText::new(header.clone(), corpus, total_chars, chunk_chars).collect();
+        prop_assert_eq!(chunks.concat(), expected(&header, corpus, total_chars));
+    }
+
+    #[test]
+    fn chunks_are_non_empty_and_within_the_limit(
+        header in "\\PC{0,20}",
+        corpus in "\\PC{1,40}",
+        total_chars in 0usize..300,
+        chunk_chars in 0usize..50,
+    ) {
+        let corpus = leak(corpus);
+        let limit = chunk_chars.max(1);
+        let chunks: Vec<String> = CyclingText::new(header, corpus, total_chars, chunk_chars).collect();
+        for chunk in &chunks {
+            let len = chunk.chars().count();
+            prop_assert!(len > 0, "empty chunk");
+            prop_assert!(len <= limit, "chunk of {} chars exceeds {}", len, limit);
+        }
+        // Every chunk but the last is full.
+        if let Some((_, full)) = chunks.split_last() {
+            prop_assert!(full.iter().all(|c| c.chars().count() == limit));
+        }
+    }
+
+    #[test]
+    fn sentences_reconstruct_the_content(text in "[a-zA-Z .!?\n\"5]{0,120}", size in 1usize..12) {
+        let deltas: Vec<String> = rechunk(ChunkStrategy::Sentence, 1, pieces(&text, size)).collect();
+        prop_assert_eq!(deltas.concat(), text.clone());
+        prop_assert!(deltas.iter().all(|d| !d.is_empty()));
+        // However the text arrives, the sentences come out the same.
+        let whole: Vec<String> = rechunk(ChunkStrategy::Sentence, 1, pieces(&text, text.chars().count())).collect();
+        prop_assert_eq!(deltas, whole);
+    }
+
+    #[test]
+    fn words_reconstruct_the_content_and_stay_whole(
+        text in "(\\PC|[ \n\t']){0,160}",
+        size in 1usize..12,
+        target in 1usize..20,
+    ) {
+        let deltas: Vec<String> = rechunk(ChunkStrategy::Word, target, pieces(&text, size)).collect();
+        prop_assert_eq!(deltas.concat(), text.clone());
+        let bounds: Vec<usize> = text.split_word_bound_indices().map(|(i, _)| i).collect();
+        let mut at = 0;
+        for delta in &deltas[..deltas.len().saturating_sub(1)] {
+            prop_assert!(!delta.is_empty());
+            prop_assert!(delta.chars().count() >= target, "short delta {:?}", delta);
+            at += delta.len();
+            prop_assert!(bounds.contains(&at), "cut inside a word at {} of {:?}", at, text);
+        }
+        // Resuming the scan where it left off cuts where a scan of the whole text would.
+        let whole: Vec<String> = rechunk(ChunkStrategy::Word, target, pieces(&text, text.chars().count())).collect();
+        prop_assert_eq!(deltas, whole);
+    }
+
+    #[test]
+    fn words_keep_their_trailing_whitespace(
+        words in prop::collection::vec(("[a-zA-Z'.,]{1,9}", "[ \n]{1,3}"), 1..40),
+        size in 1usize..12,
+        target in 1usize..20,
+    ) {
+        let text: String = words.iter().map(|(word, space)| format!("{}{}", word, space)).collect();
+        let deltas: Vec<String> = rechunk(ChunkStrategy::Word, target, pieces(&text, size)).collect();
+        prop_assert_eq!(deltas.concat(), text.clone());
+        for pair in deltas.windows(2) {
+            prop_assert!(pair[0].ends_with(char::is_whitespace), "{:?} was cut from its space", pair[0]);
+            prop_assert!(!pair[1].starts_with(char::is_whitespace), "{:?} starts with a space", pair[1]);
+        }
+        // However the text arrives, the deltas come out the same.
+        let whole: Vec<String> = rechunk(ChunkStrategy::Word, target, pieces(&text, text.chars().count())).collect();
+        prop_assert_eq!(deltas, whole);
+    }
+
+    #[test]
+    fn fence_safe_cuts_keep_markers_together(
+        text in "([a-z \n]|`|~|\\\\[nu{}0-9a-f\"]){0,160}",
+        size in 1usize..12,
+    ) {
+        let deltas: Vec<String> = fence_safe(pieces(&text, size)).collect();
+        prop_assert_eq!(deltas.concat(), text.clone());
+        prop_assert!(deltas.iter().all(|d| !d.is_empty()));
+        for pair in deltas.windows(2) {
+            let (before, after) = (pair[0].chars().last().unwrap(), pair[1].chars().next().unwrap());
+            prop_assert!(!(before == after && matches!(before, '`' | '~')), "{:?} | {:?} splits a marker", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn fence_safe_code_keeps_its_escapes_whole(size in 1usize..40) {
+        let deltas: Vec<String> = fence_safe(pieces(CODE_CONTENT, size)).collect();
+        prop_assert_eq!(deltas.concat(), CODE_CONTENT);
+        let escape = regex::Regex::new(r"\\(u\{[0-9a-fA-F]*\}|u[0-9a-fA-F]{4}|x[0-9a-fA-F]{2}|.)").unwrap();
+        let escapes: Vec<(usize, usize)> = escape.find_iter(CODE_CONTENT).map(|m| (m.start(), m.end())).collect();
+        let mut at = 0;
+        for delta in &deltas {
+            at += delta.len();
+            prop_assert!(!escapes.iter().any(|&(start, end)| start < at && at < end), "cut inside an escape at {}", at);
+            prop_assert!(delta.chars().count() <= size + 12, "delta of {} chars from {}-char pieces", delta.chars().count(), size);
+        }
+    }
+
+    #[test]
+    fn chunk_count_never_exceeds_the_request(total_chars in 0usize..5_000, chunks in 0usize..64) {
+        let size = chunk_size_for(total_chars, chunks);
+        prop_assert!(size >= 1);
+        prop_assert!(total_chars.div_ceil(size) <= chunks.max(1));
+    }
+
+    #[test]
+    fn chunk_count_is_exact_when_content_allows(total_chars in 1usize..5_000, chunks in 1usize..64) {
+        let size = chunk_size_for(total_chars, chunks);
+        let produced = CyclingText::new(String::new(), "abc", total_chars, size).count();
+        prop_assert_eq!(produced, total_chars.div_ceil(size));
+        // Short replies fall back to one character per chunk instead of fewer, larger ones.
+        if total_chars <= chunks {
+            prop_assert_eq!(produced, total_chars);
+        }
+    }
+}
+
+#[test]
+fn empty_corpus_yields_nothing() {
+    assert_eq!(CyclingText::new("header".to_string(), "", 10, 3).count(), 0);
+}
+
+#[test]
+fn sentence_chunks_hold_one_sentence_each() {
+    let text = "Rust is fast. Is it safe? Yes! Mr. Smith agrees, etc. 5 apples later.\n\nNew paragraph";
+    let deltas: Vec<String> = rechunk(ChunkStrategy::Sentence, 1, pieces(text, 3)).collect();
+    assert_eq!(
+        deltas,
+        ["Rust is fast. ", "Is it safe? ", "Yes! ", "Mr. ", "Smith agrees, etc. 5 apples later.\n\n", "New paragraph"]
+    );
+}
+
+#[test]
+fn long_sentences_arriving_a_character_at_a_time_are_scanned_once() {
+    let sentence = format!("It starts, e.g. like this, {}and ends. ", "and goes on ".repeat(5_000));
+    let text = format!("{}Then another.", sentence);
+    let started = std::time::Instant::now();
+    let deltas: Vec<String> = rechunk(ChunkStrategy::Sentence, 1, pieces(&text, 1)).collect();
+    assert_eq!(deltas, [sentence.as_str(), "Then another."]);
+    // Rescanning the held text for every character takes minutes.
+    assert!(started.elapsed() < std::time::Duration::from_secs(10), "took {:?}", started.elapsed());
+}
+
+#[test]
+fn long_words_arriving_a_character_at_a_time_are_scanned_once() {
+    let text = "流式响应".repeat(12_000);
+    let started = std::time::Instant::now();
+    let deltas: Vec<String> = rechunk(ChunkStrategy::Word, 20_000, pieces(&text, 1)).collect();
+    assert_eq!(deltas.concat(), text);
+    assert!(started.elapsed() < std::time::Duration::from_secs(10), "took {:?}", started.elapsed());
+}
+
+#[test]
+fn unspaced_text_is_cut_between_words() {
+    let text = "流式响应应该在词语之间分开而不是在字符中间";
+    let deltas: Vec<String> = rechunk(ChunkStrategy::Word, 2, pieces(text, 1)).collect();
+    assert_eq!(deltas.concat(), text);
+    assert!(deltas.len() > 1);
+    assert!(deltas.iter().all(|d| d.chars().count() <= 9));
+}
+
+#[actix_rt::test]
+async fn streams_can_send_a_sentence_per_delta() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        chunk_strategy: ChunkStrategy::Sentence,
+        ..ResponseProfile::default()
+    }));
+    let body = post(&base, serde_json::json!({"messages": [{"role": "user", "content": "sentences"}], "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    let deltas: Vec<String> = parse_events(&body)
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .filter_map(|e| serde_json::from_str::<serde_json::Value>(&e.data).ok())
+        .filter_map(|c| c["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+        .collect();
+    assert!(deltas.len() > 3);
+    for delta in &deltas[..deltas.len() - 1] {
+        let sentence = delta.trim_end();
+        assert!(sentence.ends_with(['.', '!', '?', ':']) || delta.ends_with('\n'), "not a whole sentence: {:?}", delta);
+    }
+}
+
+#[test]
+fn plain_chunks_split_markers_that_fence_safe_keeps_whole() {
+    let split = |deltas: &[String]| deltas.windows(2).any(|p| p[0].ends_with('`') && p[1].starts_with('`'));
+    let plain: Vec<String> = pieces(CODE_CONTENT, 2).collect();
+    assert!(split(&plain));
+    let safe: Vec<String> = fence_safe(pieces(CODE_CONTENT, 2)).collect();
+    assert!(!split(&safe));
+}
+
+#[cfg(feature = "generators")]
+#[actix_rt::test]
+async fn code_replies_stream_with_fences_whole() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        generator: streaming_llm_api::stubs::GeneratorKind::Code,
+        chunk_chars: Some(3),
+        fence_safe: true,
+        ..ResponseProfile::default()
+    }));
+    let body = post(&base, serde_json::json!({"messages": [{"role": "user", "content": "code"}], "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    let deltas: Vec<String> = parse_events(&body)
+        .iter()
+        .filter_map(|e| serde_json::from_str::<serde_json::Value>(&e.data).ok())
+        .filter_map(|c| c["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+        .collect();
+    assert!(deltas.concat().ends_with(CODE_CONTENT));
+    assert!(deltas.iter().any(|d| d.contains("```")));
+    assert!(!deltas.windows(2).any(|p| p[0].ends_with('`') && p[1].starts_with('`')));
+}
@@ -0,0 +1,143 @@
+//! `url_citation` annotations on cited replies.
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, with_profile};
+use streaming_llm_api::citations::Citations;
+use streaming_llm_api::config::WatermarkMode;
+use streaming_llm_api::presets;
+use streaming_llm_api::stubs::ResponseProfile;
+
+fn citing() -> ResponseProfile {
+    ResponseProfile {
+        chunk_delay_ms: 0,
+        citations: true,
+        chunk_chars: Some(7),
+        ..ResponseProfile::default()
+    }
+}
+
+fn request(stream: bool) -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": "Cite this. Please."}], "stream": stream})
+}
+
+/// The text an annotation points at, counting characters.
+fn cited(content: &str, annotation: &serde_json::Value) -> String {
+    let start = annotation["url_citation"]["start_index"].as_u64().unwrap() as usize;
+    let end = annotation["url_citation"]["end_index"].as_u64().unwrap() as usize;
+    content.chars().skip(start).take(end - start).collect()
+}
+
+#[test]
+fn links_follow_sentences_and_are_reported_once_complete() {
+    let chunks = ["Intro: a. B", "ig. Then 1. item.", " End"].map(String::from);
+    let (citations, linked) = Citations::wrap(10, Box::new(chunks.into_iter()));
+    let mut text = String::new();
+    let mut found = Vec::new();
+    for chunk in linked {
+        text.push_str(&chunk);
+        found.push(citations.completed_by(&chunk).len());
+    }
+    // The header's stop and the list number's are not cited.
+    assert_eq!(text.matches("](https://").count(), 2, "{}", text);
+    assert!(text.starts_with("Intro: a. B"), "{}", text);
+    assert!(text.contains("Big. ([developer.mozilla.org]("), "{}", text);
+    assert!(text.ends_with(")) End"), "{}", text);
+    assert_eq!(found, [0, 1, 1]);
+}
+
+#[actix_rt::test]
+async fn streamed_annotations_point_at_their_links() {
+    let base = start(with_profile(citing()));
+    let events = parse_events(&post(&base, request(true)).await.text().await.unwrap());
+    let content = content_of(&events);
+    let deltas: Vec<serde_json::Value> = events
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str::<serde_json::Value>(&e.data).unwrap()["choices"][0]["delta"].clone())
+        .collect();
+    let mut sent = 0;
+    let mut last_end = 0;
+    let mut count = 0;
+    for delta in &deltas {
+        let text = delta["content"].as_str().unwrap_or_default();
+        sent += text.chars().count();
+        for annotation in delta["annotations"].as_array().into_iter().flatten() {
+            count += 1;
+            assert_eq!(annotation["type"], "url_citation");
+            let end = annotation["url_citation"]["end_index"].as_u64().unwrap() as usize;
+            assert!(end <= sent && end > sent - text.chars().count(), "sent with the delta that completes it");
+            assert!(end > last_end);
+            last_end = end;
+            let url = annotation["url_citation"]["url"].as_str().unwrap();
+            let link = cited(&content, annotation);
+            assert!(link.starts_with("([") && link.ends_with(&format!("]({}))", url)), "{}", link);
+            assert!(!annotation["url_citation"]["title"].as_str().unwrap().is_empty());
+        }
+    }
+    assert_eq!(count, content.matches("](https://").count());
+    assert!(count > 5, "{}", count);
+    assert!(!content.starts_with("Regarding your prompt 'Cite this. (["), "the prompt header is not cited");
+}
+
+#[actix_rt::test]
+async fn json_replies_list_every_annotation_on_the_message() {
+    let base = start(with_profile(citing()));
+    let streamed = parse_events(&post(&base, request(true)).await.text().await.unwrap());
+    let reply: serde_json::Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "Cite this. Please."}]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let message = &reply["choices"][0]["message"];
+    let content = message["content"].as_str().unwrap();
+    assert_eq!(content, content_of(&streamed));
+    let annotations = message["annotations"].as_array().unwrap();
+    assert_eq!(annotations.len(), content.matches("](https://").count());
+    for annotation in annotations {
+        assert!(cited(content, annotation).starts_with("(["));
+    }
+}
+
+#[actix_rt::test]
+async fn offsets_count_the_hidden_watermark() {
+    let mut config = with_profile(citing());
+    config.watermark.mode = WatermarkMode::ZeroWidth;
+    let base = start(config);
+    let events = parse_events(&post(&base, request(true)).await.text().await.unwrap());
+    let content = content_of(&events);
+    assert!(content.contains('\u{2060}'), "{}", content);
+    let annotations: Vec<serde_json::Value> = events
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .filter_map(|e| serde_json::from_str::<serde_json::Value>(&e.data).unwrap()["choices"][0]["delta"]["annotations"].as_array().cloned())
+        .flatten()
+        .collect();
+    assert!(annotations.len() > 5);
+    for annotation in &annotations {
+        let link = cited(&content, annotation);
+        assert!(link.starts_with("([") && link.ends_with("))"), "{}", link);
+    }
+}
+
+#[actix_rt::test]
+async fn uncited_replies_have_no_links_or_annotations() {
+    let base = start(with_profile(ResponseProfile {
+        citations: false,
+        ..citing()
+    }));
+    let body = post(&base, request(true)).await.text().await.unwrap();
+    assert!(!body.contains("annotations"));
+    assert!(!content_of(&parse_events(&body)).contains("](https://"));
+}
+
+#[test]
+fn the_search_preset_cites() {
+    assert!(presets::find("mock-search").unwrap().profile().citations);
+    assert!(!presets::find("openai-gpt4o").unwrap().profile().citations);
+}
@@ -0,0 +1,94 @@
+//! The Cohere-style `/v1/chat` NDJSON stream.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config, with_profile};
+use serde_json::{json, Value};
+use streaming_llm_api::stubs::ResponseProfile;
+
+async fn cohere(base: &str, body: Value) -> reqwest::Response {
+    client().post(format!("{}/v1/chat", base)).json(&body).send().await.unwrap()
+}
+
+async fn events(base: &str, body: Value) -> Vec<Value> {
+    let response = cohere(base, body).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/stream+json");
+    let body = response.text().await.unwrap();
+    assert!(body.ends_with('\n'));
+    body.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+#[actix_rt::test]
+async fn streams_start_text_and_end_events() {
+    let base = start(unpaced_config());
+    let events = events(&base, json!({"message": "hello", "stream": true})).await;
+    let (first, last) = (&events[0], events.last().unwrap());
+    assert_eq!(first["event_type"], "stream-start");
+    assert_eq!(first["is_finished"], false);
+    assert!(first["generation_id"].is_string());
+    assert_eq!(last["event_type"], "stream-end");
+    assert_eq!(last["is_finished"], true);
+    assert_eq!(last["finish_reason"], "COMPLETE");
+
+    let middle = &events[1..events.len() - 1];
+    assert!(middle.len() > 1);
+    assert!(middle.iter().all(|e| e["event_type"] == "text-generation" && e["is_finished"] == false));
+    let text: String = middle.iter().map(|e| e["text"].as_str().unwrap()).collect();
+    let response = &last["response"];
+    assert_eq!(response["text"], text.as_str());
+    assert_eq!(response["generation_id"], first["generation_id"]);
+    assert_eq!(response["chat_history"], json!([{"role": "USER", "message": "hello"}, {"role": "CHATBOT", "message": text}]));
+    assert!(response["meta"]["billed_units"]["output_tokens"].as_u64().unwrap() > 0);
+
+    // The same rules and text as chat completions.
+    let openai = post(&base, json!({"messages": [{"role": "user", "content": "hello"}], "stream": true})).await;
+    assert_eq!(content_of(&parse_events(&openai.text().await.unwrap())), text);
+}
+
+#[actix_rt::test]
+async fn history_and_preamble_count_as_input() {
+    let base = start(unpaced_config());
+    let input = |events: Vec<Value>| events.last().unwrap()["response"]["meta"]["billed_units"]["input_tokens"].as_u64().unwrap();
+    let bare = input(events(&base, json!({"message": "hi", "stream": true})).await);
+    let history = json!([{"role": "USER", "message": "earlier question"}, {"role": "CHATBOT", "message": "earlier answer"}]);
+    let events = events(&base, json!({"message": "hi", "preamble": "Be brief.", "chat_history": history, "stream": true})).await;
+    assert!(input(events.clone()) > bare);
+    assert_eq!(events.last().unwrap()["response"]["chat_history"].as_array().unwrap().len(), 4);
+}
+
+#[actix_rt::test]
+async fn failures_end_the_stream_with_error() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(2),
+        ..ResponseProfile::default()
+    }));
+    let events = events(&base, json!({"message": "hello", "stream": true})).await;
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[3]["finish_reason"], "ERROR");
+    assert_eq!(events[3]["response"]["finish_reason"], "ERROR");
+}
+
+#[actix_rt::test]
+async fn errors_use_cohere_bodies() {
+    let base = start(unpaced_config());
+    let response = cohere(&base, json!({"message": "hello"})).await;
+    assert_eq!(response.status(), 400);
+    assert_eq!(response.json::<Value>().await.unwrap(), json!({"message": "stream parameter must be true"}));
+
+    let response = cohere(&base, json!({"message": " ", "stream": true})).await;
+    assert_eq!(response.status(), 400);
+    assert!(response.json::<Value>().await.unwrap()["message"].is_string());
+
+    let base = start(with_profile(ResponseProfile {
+        error_after: Some(0),
+        error_code: 503,
+        error_message: "overloaded".to_string(),
+        ..ResponseProfile::default()
+    }));
+    let response = cohere(&base, json!({"message": "hello", "stream": true})).await;
+    assert_eq!(response.status(), 503);
+    assert_eq!(response.json::<Value>().await.unwrap(), json!({"message": "overloaded"}));
+}
@@ -0,0 +1,107 @@
+//! Helpers shared by the integration test binaries.
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+
+use streaming_llm_api::config::Config;
+use streaming_llm_api::server;
+use streaming_llm_api::stubs::{Matcher, ResponseProfile, StubRule};
+
+/// Default behavior with pacing switched off, so streams finish immediately.
+pub fn unpaced_config() -> Config {
+    with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        ..ResponseProfile::default()
+    })
+}
+
+/// A config whose single catch-all stub rule uses `profile`.
+pub fn with_profile(profile: ResponseProfile) -> Config {
+    Config {
+        stubs: vec![StubRule {
+            name: Some("test".to_string()),
+            matcher: Matcher::default(),
+            profile,
+        }],
+        ..Config::default()
+    }
+}
+
+/// Starts a server on an ephemeral port and returns its base URL.
+pub fn start(config: Config) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    actix_rt::spawn(server::serve(config, listener).unwrap());
+    format!("http://{}", addr)
+}
+
+thread_local! {
+    // Building a client loads the TLS roots, which dominates short tests.
+    // libtest gives every test its own thread, so pooled connections never
+    // outlive the runtime that opened them.
+    static CLIENT: reqwest::Client = reqwest::Client::new();
+}
+
+/// A client shared by the current test.
+pub fn client() -> reqwest::Client {
+    CLIENT.with(reqwest::Client::clone)
+}
+
+pub async fn post(base: &str, body: serde_json::Value) -> reqwest::Response {
+    client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+/// One parsed SSE event.
+#[derive(Debug)]
+pub struct Event {
+    pub id: Option<u64>,
+    /// The `event:` name, for events other than plain `message` ones.
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Splits an SSE body into events, asserting the basic framing on the way.
+pub fn parse_events(body: &str) -> Vec<Event> {
+    assert!(body.ends_with("\n\n"), "body must end with a blank line: {:?}", &body[body.len().saturating_sub(40)..]);
+    body.split_terminator("\n\n")
+        .map(|raw| {
+            let mut id = None;
+            let mut event = None;
+            let mut data = None;
+            for line in raw.lines() {
+                if let Some(v) = line.strip_prefix("id: ") {
+                    id = Some(v.parse().expect("numeric event id"));
+                } else if let Some(v) = line.strip_prefix("event: ") {
+                    event = Some(v.to_string());
+                } else if let Some(v) = line.strip_prefix("data: ") {
+                    assert!(data.is_none(), "one data line per event: {:?}", raw);
+                    data = Some(v.to_string());
+                } else {
+                    panic!("unexpected SSE line {:?}", line);
+                }
+            }
+            Event {
+                id,
+                event,
+                data: data.expect("every event carries data"),
+            }
+        })
+        .collect()
+}
+
+/// Joins the `delta.content` of every chunk event.
+pub fn content_of(events: &[Event]) -> String {
+    events
+        .iter()
+        .filter(|e| e.event.is_none() && e.data != "[DONE]")
+        .map(|e| {
+            let v: serde_json::Value = serde_json::from_str(&e.data).unwrap();
+            v["choices"][0]["delta"]["content"].as_str().unwrap_or("").to_string()
+        })
+        .collect()
+}
@@ -0,0 +1,84 @@
+//! `stream-api conformance`: the mock passes its own guarantees, and
+//! endpoints that break them are reported.
+#![cfg(feature = "client-tools")]
+
+mod common;
+
+use std::path::PathBuf;
+
+use common::{start, unpaced_config, with_profile};
+use streaming_llm_api::client::conformance::{self, ConformanceArgs};
+use streaming_llm_api::stubs::{Quirk, ResponseProfile};
+
+fn args(url: String) -> ConformanceArgs {
+    ConformanceArgs {
+        url,
+        api_key: None,
+        model: "gpt-4o-mini".to_string(),
+        junit: None::<PathBuf>,
+        timeout_secs: 10,
+        compare_url: None,
+        min_similarity: 0.5,
+    }
+}
+
+#[actix_rt::test]
+async fn the_mock_conforms_to_itself() {
+    let report = conformance::run(&args(start(unpaced_config()))).await;
+    assert!(report.passed(), "{:#?}", report.steps);
+    assert_eq!(report.steps.len(), 5);
+}
+
+#[actix_rt::test]
+async fn divergences_are_reported_per_guarantee() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        quirks: vec![Quirk::MissingRole],
+        error_after: Some(2),
+        ..ResponseProfile::default()
+    }));
+    let report = conformance::run(&args(base)).await;
+    let failed: Vec<(&str, &[String])> = report
+        .steps
+        .iter()
+        .filter(|s| !s.passed())
+        .map(|s| (s.name.as_str(), s.failures.as_slice()))
+        .collect();
+    assert_eq!(
+        failed,
+        [
+            (
+                "chunks carry choices with deltas",
+                &["the first delta's role is null, expected \"assistant\"".to_string(), "chunk 2 has no choices array".to_string()][..]
+            ),
+            ("stream ends with a single [DONE]", &["no data: [DONE] event".to_string()][..]),
+        ]
+    );
+}
+
+#[actix_rt::test]
+async fn replies_are_diffed_against_a_comparison_endpoint() {
+    let mut checked = args(start(unpaced_config()));
+    checked.compare_url = Some(start(unpaced_config()));
+    let report = conformance::run(&checked).await;
+    assert!(report.passed(), "{:#?}", report.steps);
+    assert_eq!(report.steps.len(), 6);
+
+    checked.compare_url = Some(start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        tokens: Some(2),
+        ..ResponseProfile::default()
+    })));
+    let report = conformance::run(&checked).await;
+    let compared = &report.steps[3];
+    assert!(compared.name.starts_with("reply resembles "));
+    assert_eq!(compared.failures.len(), 1, "{:#?}", compared);
+    assert!(compared.failures[0].starts_with("token ratio 0."), "{}", compared.failures[0]);
+}
+
+#[actix_rt::test]
+async fn unreachable_endpoints_are_errors_not_divergences() {
+    let report = conformance::run(&args("http://127.0.0.1:9".to_string())).await;
+    assert!(report.steps.iter().all(|s| s.failures.is_empty() && s.error.is_some()), "{:#?}", report.steps);
+    assert!(report.junit().contains(r#"tests="5" failures="0" errors="5""#));
+}
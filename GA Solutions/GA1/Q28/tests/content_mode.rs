@@ -0,0 +1,95 @@
+//! `content_mode`: chunks carrying deltas, cumulative snapshots, or both.
+
+mod common;
+
+use common::{client, content_of, parse_events, start, unpaced_config, with_profile};
+use serde_json::Value;
+use streaming_llm_api::config::Config;
+use streaming_llm_api::models::ModelInfo;
+use streaming_llm_api::stubs::{ContentMode, ResponseProfile};
+use streaming_llm_api::tokenizer::TokenEncoding;
+
+fn with_mode(mode: Option<ContentMode>) -> Config {
+    with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        content_mode: mode,
+        ..ResponseProfile::default()
+    })
+}
+
+async fn deltas(config: Config, model: &str) -> Vec<Value> {
+    let base = start(config);
+    let body = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"model": model, "messages": [{"role": "user", "content": "snap"}], "stream": true}))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    parse_events(&body)
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str::<Value>(&e.data).unwrap()["choices"][0]["delta"].clone())
+        .collect()
+}
+
+async fn full_reply() -> String {
+    let base = start(unpaced_config());
+    let body = common::post(&base, serde_json::json!({"messages": [{"role": "user", "content": "snap"}], "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    content_of(&parse_events(&body))
+}
+
+fn content(delta: &Value) -> &str {
+    delta["content"].as_str().unwrap()
+}
+
+#[actix_rt::test]
+async fn cumulative_chunks_grow_to_the_whole_reply() {
+    let reply = full_reply().await;
+    let chunks = deltas(with_mode(Some(ContentMode::Cumulative)), "gpt-4o").await;
+    assert!(chunks.len() > 2);
+    for pair in chunks.windows(2) {
+        assert!(content(&pair[1]).starts_with(content(&pair[0])));
+    }
+    assert_eq!(content(chunks.last().unwrap()), reply);
+    assert!(chunks.iter().all(|d| d.get("text").is_none()));
+}
+
+#[actix_rt::test]
+async fn both_sends_the_delta_and_the_snapshot() {
+    let chunks = deltas(with_mode(Some(ContentMode::Both)), "gpt-4o").await;
+    let mut so_far = String::new();
+    for delta in &chunks {
+        so_far.push_str(content(delta));
+        assert_eq!(delta["text"], so_far.as_str());
+    }
+    assert_eq!(so_far, full_reply().await);
+}
+
+#[actix_rt::test]
+async fn models_set_the_mode_unless_the_rule_does() {
+    let model = |config: &mut Config| {
+        config.models.push(ModelInfo {
+            id: "command-r".to_string(),
+            context_window: 128_000,
+            encoding: TokenEncoding::Cl100k,
+            owned_by: "cohere".to_string(),
+            content_mode: Some(ContentMode::Both),
+            pricing: None,
+        });
+    };
+    let mut config = with_mode(None);
+    model(&mut config);
+    assert!(deltas(config.clone(), "command-r").await[1].get("text").is_some());
+    assert!(deltas(config, "gpt-4o").await[1].get("text").is_none());
+
+    let mut config = with_mode(Some(ContentMode::Delta));
+    model(&mut config);
+    assert!(deltas(config, "command-r").await[1].get("text").is_none());
+}
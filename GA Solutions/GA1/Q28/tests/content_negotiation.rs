@@ -0,0 +1,151 @@
+//! Request media types and charsets, and `Accept` choosing between SSE and
+//! JSON when `stream` is left out.
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config, with_profile};
+use serde_json::{json, Value};
+use streaming_llm_api::config::CompressionMode;
+use streaming_llm_api::keys::{ApiKey, Tier};
+use streaming_llm_api::orgs::Organization;
+use streaming_llm_api::stubs::ResponseProfile;
+
+const BODY: &str = r#"{"messages": [{"role": "user", "content": "negotiate"}], "stream": true}"#;
+const UNSET: &str = r#"{"messages": [{"role": "user", "content": "negotiate"}]}"#;
+
+async fn send(base: &str, body: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+    let mut request = client().post(format!("{}/v1/chat/completions", base)).body(body.to_string());
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    request.send().await.unwrap()
+}
+
+#[actix_rt::test]
+async fn json_with_a_utf8_charset_is_accepted() {
+    let base = start(unpaced_config());
+    for content_type in ["application/json", "application/json; charset=utf-8", "application/json;charset=UTF-8", "application/vnd.api+json"] {
+        let response = send(&base, BODY, &[("content-type", content_type)]).await;
+        assert_eq!(response.status(), 200, "{}", content_type);
+    }
+}
+
+#[actix_rt::test]
+async fn other_media_types_get_an_openai_415() {
+    let base = start(unpaced_config());
+    for headers in [&[("content-type", "text/plain")][..], &[("content-type", "application/json; charset=iso-8859-1")], &[]] {
+        let response = send(&base, BODY, headers).await;
+        assert_eq!(response.status(), 415, "{:?}", headers);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["code"], "unsupported_media_type");
+    }
+    let body: Value = send(&base, BODY, &[("content-type", "text/plain")]).await.json().await.unwrap();
+    assert_eq!(body["error"]["message"], "Unsupported Content-Type 'text/plain'; expected application/json with UTF-8 encoding");
+}
+
+#[actix_rt::test]
+async fn accept_picks_the_format_when_stream_is_left_out() {
+    let base = start(unpaced_config());
+    let json_type = ("content-type", "application/json");
+    let streamed = send(&base, UNSET, &[json_type, ("accept", "text/event-stream")]).await;
+    assert_eq!(streamed.headers()["content-type"], "text/event-stream");
+    assert_eq!(streamed.headers()["vary"], "Accept");
+    let text = content_of(&parse_events(&streamed.text().await.unwrap()));
+
+    let response = send(&base, UNSET, &[json_type, ("accept", "text/event-stream;q=0.5, application/json")]).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(response.headers()["vary"], "Accept");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["choices"][0]["message"], json!({"role": "assistant", "content": text}));
+    assert_eq!(body["choices"][0]["finish_reason"], "stop");
+    let usage = &body["usage"];
+    assert_eq!(usage["total_tokens"], usage["prompt_tokens"].as_u64().unwrap() + usage["completion_tokens"].as_u64().unwrap());
+}
+
+#[actix_rt::test]
+async fn an_explicit_stream_flag_wins_over_accept() {
+    let base = start(unpaced_config());
+    let json_type = ("content-type", "application/json");
+    let response = send(&base, BODY, &[json_type, ("accept", "application/json")]).await;
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    assert!(response.headers().get("vary").is_none());
+
+    let explicit_false = r#"{"messages": [{"role": "user", "content": "negotiate"}], "stream": false}"#;
+    assert_eq!(send(&base, explicit_false, &[json_type, ("accept", "application/json")]).await.status(), 400);
+    // Without a preference there is nothing to negotiate.
+    assert_eq!(send(&base, UNSET, &[json_type, ("accept", "*/*")]).await.status(), 400);
+    assert_eq!(post(&base, json!({"messages": [{"role": "user", "content": "negotiate"}]})).await.status(), 400);
+}
+
+#[actix_rt::test]
+async fn json_replies_fail_whole_on_injected_errors() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(3),
+        error_code: 503,
+        ..ResponseProfile::default()
+    }));
+    let response = send(&base, UNSET, &[("content-type", "application/json"), ("accept", "application/json")]).await;
+    assert_eq!(response.status(), 503);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "server_error");
+}
+
+#[actix_rt::test]
+async fn json_replies_carry_the_stream_headers() {
+    let mut config = unpaced_config();
+    config.organizations = vec![Organization {
+        id: "org-acme".to_string(),
+        projects: vec!["proj_web".to_string()],
+    }];
+    config.keys = vec![ApiKey {
+        key: "sk-acme".to_string(),
+        tier: Tier::Pro,
+        organization: Some("org-acme".to_string()),
+        project: Some("proj_web".to_string()),
+        models: None,
+        capabilities: None,
+        token_quota: None,
+    }];
+    config.tiers.pro.tokens_per_sec = None;
+    let base = start(config);
+    let headers = [("content-type", "application/json"), ("authorization", "Bearer sk-acme")];
+    let streamed = send(&base, UNSET, &[headers[0], headers[1], ("accept", "text/event-stream")]).await;
+    let whole = send(&base, UNSET, &[headers[0], headers[1], ("accept", "application/json")]).await;
+    assert_eq!(whole.headers()["content-type"], "application/json");
+    for name in [
+        "access-control-allow-origin",
+        "access-control-allow-methods",
+        "access-control-allow-headers",
+        "x-mock-tier",
+        "openai-organization",
+        "openai-project",
+    ] {
+        assert!(streamed.headers().contains_key(name), "{}", name);
+        assert_eq!(whole.headers().get(name), streamed.headers().get(name), "{}", name);
+    }
+}
+
+#[actix_rt::test]
+async fn compressed_replies_vary_on_accept_too() {
+    let mut config = unpaced_config();
+    config.stream.compression = CompressionMode::Auto;
+    let base = start(config);
+    let vary = |response: &reqwest::Response| -> Vec<String> {
+        response.headers().get_all("vary").iter().map(|v| v.to_str().unwrap().to_string()).collect()
+    };
+    let json_type = ("content-type", "application/json");
+    let gzip = ("accept-encoding", "gzip");
+
+    let streamed = send(&base, UNSET, &[json_type, gzip, ("accept", "text/event-stream")]).await;
+    assert_eq!(streamed.headers()["content-encoding"], "gzip");
+    assert_eq!(vary(&streamed), ["Accept", "Accept-Encoding"]);
+    let explicit = send(&base, BODY, &[json_type, gzip]).await;
+    assert_eq!(vary(&explicit), ["Accept-Encoding"]);
+    let whole = send(&base, UNSET, &[json_type, gzip, ("accept", "application/json")]).await;
+    assert_eq!(whole.headers()["content-type"], "application/json");
+    assert_eq!(vary(&whole), ["Accept"]);
+}
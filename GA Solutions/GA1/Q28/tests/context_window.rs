@@ -0,0 +1,85 @@
+//! Context window enforcement against the model registry.
+#![cfg(feature = "tokenizer")]
+
+mod common;
+
+use common::{post, start, unpaced_config};
+use streaming_llm_api::chat::Message;
+use streaming_llm_api::models::{ModelInfo, ModelRegistry};
+use streaming_llm_api::tokenizer::TokenEncoding;
+
+fn request(model: &str, content: &str, max_tokens: Option<usize>) -> serde_json::Value {
+    let mut body = serde_json::json!({"model": model, "messages": [{"role": "user", "content": content}], "stream": true});
+    if let Some(max) = max_tokens {
+        body["max_tokens"] = max.into();
+    }
+    body
+}
+
+#[test]
+fn message_tokens_include_framing_overhead() {
+    assert_eq!(TokenEncoding::Cl100k.count("hello world"), 2);
+    let messages: Vec<Message> = serde_json::from_value(serde_json::json!([{"role": "user", "content": "hello world"}])).unwrap();
+    // 3 framing + 1 role + 2 content + 3 reply priming.
+    assert_eq!(TokenEncoding::Cl100k.count_messages(&messages), 9);
+}
+
+#[test]
+fn configured_models_override_builtins() {
+    let registry = ModelRegistry::new(&[ModelInfo {
+        id: "gpt-4".to_string(),
+        context_window: 100,
+        encoding: TokenEncoding::Cl100k,
+        owned_by: "me".to_string(),
+        content_mode: None,
+        pricing: None,
+    }]);
+    assert_eq!(registry.get("gpt-4").unwrap().context_window, 100);
+    assert_eq!(registry.get("gpt-4o").unwrap().context_window, 128_000);
+    assert_eq!(registry.all().iter().filter(|m| m.id == "gpt-4").count(), 1);
+}
+
+#[actix_rt::test]
+async fn over_limit_messages_get_context_length_exceeded() {
+    let base = start(unpaced_config());
+    // " hello" is one cl100k token, so this is 9000 content tokens plus 7 of framing.
+    let content = " hello".repeat(9_000);
+    let resp = post(&base, request("gpt-4", &content, None)).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "context_length_exceeded");
+    assert_eq!(body["error"]["param"], "messages");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(
+        body["error"]["message"],
+        "This model's maximum context length is 8192 tokens. However, your messages resulted in 9007 tokens. \
+         Please reduce the length of the messages."
+    );
+}
+
+#[actix_rt::test]
+async fn reserved_completion_counts_towards_the_limit() {
+    let base = start(unpaced_config());
+    let content = " hello".repeat(8_000);
+    let resp = post(&base, request("gpt-4", &content, Some(1_000))).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["error"]["message"],
+        "This model's maximum context length is 8192 tokens. However, you requested 9007 tokens \
+         (8007 in the messages, 1000 in the completion). Please reduce the length of the messages or completion."
+    );
+
+    assert_eq!(post(&base, request("gpt-4", &content, Some(100))).await.status(), 200);
+    assert_eq!(post(&base, request("unregistered-model", &content, Some(100_000))).await.status(), 200);
+}
+
+#[actix_rt::test]
+async fn overflowing_completion_reservations_are_rejected() {
+    let base = start(unpaced_config());
+    let resp = post(&base, request("gpt-4", "hello", Some(usize::MAX))).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "context_length_exceeded");
+    assert_eq!(body["error"]["param"], "max_tokens");
+}
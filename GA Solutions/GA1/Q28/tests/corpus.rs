@@ -0,0 +1,179 @@
+//! Packed reply corpora and the corpus generator.
+#![cfg(feature = "generators")]
+
+mod common;
+
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use common::{content_of, parse_events, post, start, with_profile};
+use serde_json::json;
+use streaming_llm_api::config::{Config, CorpusSelection};
+use streaming_llm_api::corpus::{self, Corpus};
+use streaming_llm_api::stubs::{GeneratorKind, ResponseProfile};
+
+const REPLIES: [&str; 3] = [
+    "The first reply, about tides.",
+    "The second reply, about ferns. It has two sentences.",
+    "Die dritte Antwort, über Schnee ❄️.",
+];
+
+fn packed(name: &str) -> PathBuf {
+    let jsonl = format!("{}\n\n{}\n{}\n", json!(REPLIES[0]), json!({"text": REPLIES[1]}), json!({"text": REPLIES[2], "lang": "de"}));
+    let path = std::env::temp_dir().join(format!("stream-api-corpus-{}-{}.br", name, std::process::id()));
+    let mut bytes = Vec::new();
+    assert_eq!(corpus::pack(Cursor::new(jsonl), &mut bytes).unwrap(), 3);
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn corpus_config(path: Option<&PathBuf>) -> Config {
+    let mut config = with_profile(ResponseProfile {
+        generator: GeneratorKind::Corpus,
+        chunk_delay_ms: 0,
+        ..ResponseProfile::default()
+    });
+    config.corpus.path = path.map(|p| p.to_str().unwrap().to_string());
+    config
+}
+
+#[test]
+fn packed_replies_read_back_one_at_a_time() {
+    let path = packed("roundtrip");
+    let corpus = Corpus::open(&path).unwrap();
+    assert_eq!(corpus.len(), 3);
+    for (i, reply) in REPLIES.iter().enumerate() {
+        assert_eq!(corpus.reply(i).unwrap(), *reply);
+    }
+    assert_eq!(corpus.pick(42), corpus.pick(42));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn bad_files_are_refused() {
+    let path = std::env::temp_dir().join(format!("stream-api-corpus-bad-{}.br", std::process::id()));
+    std::fs::write(&path, "not a corpus at all").unwrap();
+    assert!(Corpus::open(&path).err().unwrap().to_string().contains("not a packed corpus"));
+
+    let truncated = packed("truncated");
+    let mut bytes = std::fs::read(&truncated).unwrap();
+    std::fs::remove_file(truncated).unwrap();
+    bytes.drain(8..20);
+    std::fs::write(&path, bytes).unwrap();
+    assert!(Corpus::open(&path).is_err());
+    std::fs::remove_file(path).unwrap();
+
+    assert!(corpus::pack(Cursor::new("{\"body\": 1}\n"), Vec::new()).is_err());
+    for empty in ["\"\"", "{\"text\": \"  \"}"] {
+        let error = corpus::pack(Cursor::new(format!("\"fine\"\n{}\n", empty)), Vec::new()).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: the reply is empty");
+    }
+}
+
+#[test]
+fn a_failed_pack_leaves_the_old_corpus_in_place() {
+    let dir = std::env::temp_dir().join(format!("stream-api-corpus-run-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (input, output) = (dir.join("replies.jsonl"), dir.join("corpus.br"));
+    std::fs::write(&input, format!("{}\n{}\n", json!(REPLIES[0]), json!(REPLIES[1]))).unwrap();
+    let pack = || corpus::run(&corpus::CorpusCommand::Pack { input: input.clone(), output: output.clone() });
+    pack().unwrap();
+    let packed = std::fs::read(&output).unwrap();
+
+    std::fs::write(&input, format!("{}\nnot json\n", json!(REPLIES[2]))).unwrap();
+    assert!(pack().is_err());
+    assert_eq!(std::fs::read(&output).unwrap(), packed);
+    let left: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(left.len(), 2, "{:?}", left);
+    assert_eq!(Corpus::open(&output).unwrap().len(), 2);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[actix_rt::test]
+async fn replies_come_from_the_corpus_by_seed() {
+    let path = packed("serve");
+    let base = start(corpus_config(Some(&path)));
+    let mut seen = HashSet::new();
+    for seed in 0..24 {
+        let body = post(&base, json!({"messages": [{"role": "user", "content": "tell me"}], "stream": true, "seed": seed}))
+            .await
+            .text()
+            .await
+            .unwrap();
+        let content = content_of(&parse_events(&body));
+        let reply = REPLIES.iter().find(|r| content.ends_with(*r)).unwrap_or_else(|| panic!("unexpected reply {:?}", content));
+        seen.insert(*reply);
+    }
+    assert_eq!(seen.len(), REPLIES.len());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[actix_rt::test]
+async fn without_a_corpus_the_canned_reply_is_sent() {
+    let base = start(corpus_config(None));
+    let body = post(&base, json!({"messages": [{"role": "user", "content": "tell me"}], "stream": true})).await.text().await.unwrap();
+    assert!(!content_of(&parse_events(&body)).is_empty());
+}
+
+#[actix_rt::test]
+async fn a_missing_corpus_stops_the_server_starting() {
+    let config = corpus_config(Some(&PathBuf::from("/nonexistent/corpus.br")));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    assert!(streaming_llm_api::server::serve(config, listener).is_err());
+}
+
+#[test]
+fn similar_selection_picks_the_closest_reply() {
+    let path = packed("similar");
+    let mut corpus = Corpus::open(&path).unwrap();
+    assert_eq!(corpus.choose("what about ferns?", 5), corpus.pick(5));
+    corpus.select_similar(1);
+    for seed in 0..8 {
+        assert_eq!(corpus.choose("Tell me about FERNS", seed), 1);
+        assert_eq!(corpus.choose("tides and more tides", seed), 0);
+        assert_eq!(corpus.choose("Schnee", seed), 2);
+    }
+    // Nothing in common, so the seed decides.
+    assert_eq!(corpus.choose("quantum chromodynamics", 9), corpus.pick(9));
+
+    // "reply" is in two of them, and the seed spreads requests over both.
+    corpus.select_similar(2);
+    let picked: HashSet<usize> = (0..32).map(|seed| corpus.choose("a reply", seed)).collect();
+    assert_eq!(picked, HashSet::from([0, 1]));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn terms_in_most_replies_are_left_out_of_the_index() {
+    let jsonl: String = (0..2100).map(|i| format!("{}\n", json!(format!("shared word{}", i)))).collect();
+    let path = std::env::temp_dir().join(format!("stream-api-corpus-common-{}.br", std::process::id()));
+    let mut bytes = Vec::new();
+    corpus::pack(Cursor::new(jsonl), &mut bytes).unwrap();
+    std::fs::write(&path, bytes).unwrap();
+    let mut corpus = Corpus::open(&path).unwrap();
+    corpus.select_similar(1);
+    for seed in 0..8 {
+        // Every reply has "shared", so it cannot tell them apart and the seed decides.
+        assert_eq!(corpus.choose("shared", seed), corpus.pick(seed));
+        assert_eq!(corpus.choose("shared word7", seed), 7);
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[actix_rt::test]
+async fn the_prompt_picks_the_reply_when_selection_is_similar() {
+    let path = packed("similar-serve");
+    let mut config = corpus_config(Some(&path));
+    config.corpus.selection = CorpusSelection::Similar;
+    let base = start(config);
+    for seed in 0..4 {
+        let body = post(&base, json!({"messages": [{"role": "user", "content": "ferns, please"}], "stream": true, "seed": seed}))
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert!(content_of(&parse_events(&body)).ends_with(REPLIES[1]));
+    }
+    std::fs::remove_file(path).unwrap();
+}
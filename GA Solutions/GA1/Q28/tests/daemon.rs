@@ -0,0 +1,103 @@
+//! `stream-api serve --daemonize` and its pidfile.
+
+mod common;
+
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+use streaming_llm_api::daemon::{check_pidfile, PidFile};
+
+fn scratch(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("stream-api-daemon-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn pidfiles_are_exclusive_and_removed_on_drop() {
+    let path = scratch("lock").join("stream-api.pid");
+    check_pidfile(&path).unwrap();
+
+    let held = PidFile::create(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("{}\n", std::process::id()));
+    let err = check_pidfile(&path).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    assert!(err.to_string().contains(&format!("already running as pid {}", std::process::id())), "{}", err);
+    assert_eq!(PidFile::create(&path).unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+    drop(held);
+    assert!(!path.exists());
+    check_pidfile(&path).unwrap();
+}
+
+/// Waits for `path` to hold a pid.
+#[cfg(unix)]
+fn wait_for_pid(path: &Path) -> u32 {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(pid) = std::fs::read_to_string(path).ok().and_then(|s| s.trim().parse().ok()) {
+            return pid;
+        }
+        assert!(Instant::now() < deadline, "no pid in {}", path.display());
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[cfg(unix)]
+#[actix_rt::test]
+async fn daemons_detach_serve_and_clean_up_on_sigterm() {
+    let dir = scratch("serve");
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let write_config = |name: &str, port: u16| {
+        let path = dir.join(name);
+        let text = format!("[server]\nbind = \"127.0.0.1:{}\"\n\n[[stubs]]\nmatch = {{}}\nprofile.chunk_delay_ms = 0\n", port);
+        std::fs::write(&path, text).unwrap();
+        path
+    };
+    let (config, elsewhere) = (write_config("config.toml", port), write_config("elsewhere.toml", 0));
+    let (pidfile, log) = (dir.join("stream-api.pid"), dir.join("stream-api.log"));
+    let daemonize = |config: &Path| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_stream-api"))
+            .arg("--config")
+            .arg(config)
+            .args(["serve", "--daemonize", "--pidfile"])
+            .arg(&pidfile)
+            .arg("--log-file")
+            .arg(&log)
+            .output()
+            .unwrap()
+    };
+
+    // The launching process returns once the server has detached.
+    assert!(daemonize(&config).status.success());
+    let pid = wait_for_pid(&pidfile);
+    assert_ne!(pid, std::process::id());
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let body = common::post(&base, serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(body.ends_with("data: [DONE]\n\n"), "{}", body);
+
+    // A second server on another port is turned away by the pidfile before
+    // it detaches, so the error reaches the terminal.
+    let second = daemonize(&elsewhere);
+    assert!(!second.status.success());
+    let stderr = String::from_utf8_lossy(&second.stderr);
+    assert!(stderr.contains(&format!("already running as pid {}", pid)), "{}", stderr);
+
+    assert!(std::process::Command::new("kill").arg(pid.to_string()).status().unwrap().success());
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while pidfile.exists() {
+        assert!(Instant::now() < deadline, "pidfile outlived the server");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    // Output after detaching, here the server log, goes to --log-file.
+    assert!(std::fs::read_to_string(&log).unwrap().contains("actix_server"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,114 @@
+//! Completion diffs: token alignment, SimHash distance and the diff endpoint.
+
+mod common;
+
+use serde_json::json;
+use streaming_llm_api::diffing::{self, Op};
+
+#[test]
+fn similarity_compares_words() {
+    assert_eq!(diffing::similarity("a b c", "c b a"), 1.0);
+    assert_eq!(diffing::similarity("a b", "c d"), 0.0);
+    assert_eq!(diffing::similarity("a a b", "a b b"), 2.0 * 2.0 / 6.0);
+    assert_eq!(diffing::similarity("", ""), 1.0);
+}
+
+#[test]
+fn tokens_are_words_and_marks() {
+    let text = "Hello, wörld 42!";
+    let tokens: Vec<&str> = diffing::tokens(text).into_iter().map(|(s, e)| &text[s..e]).collect();
+    assert_eq!(tokens, ["Hello", ",", "wörld", "42", "!"]);
+}
+
+#[test]
+fn token_diffs_align_the_common_words() {
+    let diff = diffing::token_diff("The quick brown fox jumps.", "The slow brown fox  leaps high.");
+    assert_eq!(
+        diff.ops,
+        [
+            Op::Equal("The".to_string()),
+            Op::Delete("quick".to_string()),
+            Op::Insert("slow".to_string()),
+            Op::Equal("brown fox".to_string()),
+            Op::Delete("jumps".to_string()),
+            Op::Insert("leaps high".to_string()),
+            Op::Equal(".".to_string()),
+        ]
+    );
+    assert_eq!((diff.a_tokens, diff.b_tokens, diff.equal, diff.deleted, diff.inserted), (6, 7, 4, 2, 3));
+    assert_eq!(diff.ratio, 8.0 / 13.0);
+
+    let same = diffing::token_diff("same text", "same  text");
+    assert_eq!((same.ops, same.ratio), (vec![Op::Equal("same text".to_string())], 1.0));
+    assert_eq!(diffing::token_diff("", "").ratio, 1.0);
+}
+
+#[test]
+fn long_middles_are_replaced_whole() {
+    let a = (0..1500).map(|i| format!("a{}", i)).collect::<Vec<_>>().join(" ");
+    let b = (0..1500).map(|i| format!("b{}", i)).collect::<Vec<_>>().join(" ");
+    let diff = diffing::token_diff(&format!("start {} end", a), &format!("start {} end", b));
+    assert_eq!(diff.ops.len(), 4);
+    assert_eq!((diff.equal, diff.deleted, diff.inserted), (2, 1500, 1500));
+}
+
+#[test]
+fn near_duplicates_have_close_simhashes() {
+    let reply = "Streaming responses let clients render tokens as soon as the model produces them, \
+                 which makes long answers feel fast and keeps users engaged while the rest arrives.";
+    let reworded = reply.replace("feel fast", "feel quick");
+    let unrelated = "Sourdough needs a lively starter, a long cold proof in the fridge overnight, \
+                     and a very hot oven with steam for the first twenty minutes of baking.";
+    let near = diffing::diff(reply, &reworded).semantic.distance;
+    let far = diffing::diff(reply, unrelated).semantic.distance;
+    assert!(near < far, "{} vs {}", near, far);
+    assert_eq!(diffing::simhash("Same WORDS here"), diffing::simhash("same words, here"));
+
+    let diff = diffing::diff(reply, reply);
+    assert!(diff.identical && diff.diverges_at.is_none() && diff.semantic.similarity == 1.0);
+    assert_eq!(diffing::diff("abcd", "abxd").diverges_at, Some(2));
+    assert_eq!(diffing::diff("abc", "abcdef").diverges_at, Some(3));
+}
+
+#[test]
+fn reply_text_reads_every_body_shape() {
+    let completion = json!({"object": "chat.completion", "choices": [{"message": {"role": "assistant", "content": "hi there"}}]});
+    assert_eq!(diffing::reply_text(&completion).as_deref(), Some("hi there"));
+    let stream = "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"hi\"}}]}\n\n\
+                  data: {\"choices\":[{\"delta\":{\"content\":\" there\"}}]}\n\ndata: [DONE]\n\n";
+    assert_eq!(diffing::reply_text(&json!(stream)).as_deref(), Some("hi there"));
+    assert_eq!(diffing::reply_text(&json!("just text")).as_deref(), Some("just text"));
+    assert_eq!(diffing::reply_text(&json!({"choices": []})), None);
+    assert_eq!(diffing::reply_text(&json!(7)), None);
+}
+
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn the_endpoint_diffs_two_bodies() {
+    use common::{client, start, unpaced_config};
+    use serde_json::Value;
+
+    let base = start(unpaced_config());
+    let send = |body: Value| client().post(format!("{}/v1/internal/diff", base)).json(&body).send();
+    let completion = json!({"choices": [{"message": {"role": "assistant", "content": "one two three"}}]});
+    let resp = send(json!({"a": completion, "b": "data: {\"choices\":[{\"delta\":{\"content\":\"one 2 three\"}}]}\n\n"})).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let diff: Value = resp.json().await.unwrap();
+    assert_eq!(diff["identical"], false);
+    assert_eq!(diff["diverges_at"], 4);
+    assert_eq!(
+        diff["tokens"]["ops"],
+        json!([
+            {"op": "equal", "text": "one"},
+            {"op": "delete", "text": "two"},
+            {"op": "insert", "text": "2"},
+            {"op": "equal", "text": "three"},
+        ])
+    );
+    assert_eq!(diff["semantic"]["a_hash"].as_str().unwrap().len(), 16);
+
+    let resp = send(json!({"a": {"choices": []}, "b": "text"})).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let error: Value = resp.json().await.unwrap();
+    assert_eq!(error["error"]["param"], "a");
+}
@@ -0,0 +1,55 @@
+//! `[echo]`: how much of the prompt replies quote.
+
+mod common;
+
+use common::{content_of, parse_events, post, start, unpaced_config};
+use streaming_llm_api::chat::prompt_header;
+use streaming_llm_api::config::{EchoConfig, EchoMode};
+
+const SECRET: &str = "my card is 4111 1111 1111 1111, please help";
+
+async fn reply_to(echo: EchoConfig, prompt: &str) -> String {
+    let mut config = unpaced_config();
+    config.echo = echo;
+    let base = start(config);
+    let body = post(&base, serde_json::json!({"messages": [{"role": "user", "content": prompt}], "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    content_of(&parse_events(&body))
+}
+
+fn echo(mode: EchoMode, max_chars: usize) -> EchoConfig {
+    EchoConfig { mode, max_chars }
+}
+
+#[actix_rt::test]
+async fn policies_keep_the_prompt_out_of_replies() {
+    let truncated = reply_to(echo(EchoMode::Truncate, 10), SECRET).await;
+    assert!(truncated.starts_with("Regarding your prompt 'my card is…':\n\n"), "{}", truncated);
+
+    let hashed = reply_to(echo(EchoMode::Hash, 0), SECRET).await;
+    assert!(hashed.starts_with("Regarding your prompt 'sha256:"), "{}", hashed);
+    assert!(!hashed.contains("4111"));
+    assert_eq!(hashed, reply_to(echo(EchoMode::Hash, 0), SECRET).await);
+
+    let omitted = reply_to(echo(EchoMode::Omit, 0), SECRET).await;
+    assert!(omitted.starts_with("Regarding your prompt:\n\n"), "{}", omitted);
+    assert!(!omitted.contains("4111"));
+}
+
+#[actix_rt::test]
+async fn the_whole_prompt_is_echoed_by_default() {
+    let full = reply_to(EchoConfig::default(), SECRET).await;
+    assert!(full.starts_with(&format!("Regarding your prompt '{}':", SECRET)));
+}
+
+#[test]
+fn headers_cut_on_characters_and_hash_stably() {
+    let truncate = echo(EchoMode::Truncate, 3);
+    assert_eq!(prompt_header("héllo", &truncate), "Regarding your prompt 'hél…':\n\n");
+    assert_eq!(prompt_header("hé", &truncate), "Regarding your prompt 'hé':\n\n");
+    // The first six bytes of SHA-256("abc").
+    assert_eq!(prompt_header("abc", &echo(EchoMode::Hash, 0)), "Regarding your prompt 'sha256:ba7816bf8f01':\n\n");
+}
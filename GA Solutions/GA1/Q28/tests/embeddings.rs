@@ -0,0 +1,123 @@
+//! `/v1/embeddings`: stable unit vectors, `dimensions` and `base64`, and the
+//! `embeddings` capability on restricted keys.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use common::{client, start, unpaced_config};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::keys::{ApiKey, Capability, Tier};
+use streaming_llm_api::wire::openai::{EmbeddingList, EmbeddingVector};
+
+async fn embed(base: &str, key: Option<&str>, body: Value) -> (u16, Value) {
+    let mut request = client().post(format!("{}/v1/embeddings", base)).json(&body);
+    if let Some(key) = key {
+        request = request.bearer_auth(key);
+    }
+    let response = request.send().await.unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+fn floats(list: &EmbeddingList, index: usize) -> Vec<f32> {
+    match &list.data[index].embedding {
+        EmbeddingVector::Float(vector) => vector.iter().map(|&x| x as f32).collect(),
+        EmbeddingVector::Base64(encoded) => panic!("expected floats, got {}", encoded),
+    }
+}
+
+fn key(capabilities: Vec<Capability>) -> Config {
+    let mut config = unpaced_config();
+    config.keys = vec![ApiKey {
+        key: "sk-embed".to_string(),
+        tier: Tier::Pro,
+        organization: None,
+        project: None,
+        models: Some(vec!["text-embedding-3-small".to_string()]),
+        capabilities: Some(capabilities),
+        token_quota: None,
+    }];
+    config.tiers.pro.tokens_per_sec = None;
+    config
+}
+
+#[actix_rt::test]
+async fn vectors_are_stable_unit_vectors() {
+    let base = start(unpaced_config());
+    let (status, body) = embed(&base, None, json!({"model": "text-embedding-3-small", "input": ["hello", "world", "hello"]})).await;
+    assert_eq!(status, 200);
+    let list: EmbeddingList = serde_json::from_value(body).unwrap();
+    assert_eq!(list.object, "list");
+    assert_eq!(list.model, "text-embedding-3-small");
+    assert_eq!(list.data.len(), 3);
+    assert!(list.usage.prompt_tokens > 0);
+    assert_eq!(list.usage.total_tokens, list.usage.prompt_tokens);
+
+    let first = floats(&list, 0);
+    assert_eq!(first.len(), 1536);
+    let norm = first.iter().map(|x| x * x).sum::<f32>().sqrt();
+    assert!((norm - 1.0).abs() < 1e-4, "{}", norm);
+    assert_eq!(first, floats(&list, 2));
+    assert_ne!(first, floats(&list, 1));
+    assert_eq!(list.data.iter().map(|e| e.index).collect::<Vec<_>>(), [0, 1, 2]);
+
+    let (_, again) = embed(&base, None, json!({"model": "text-embedding-3-small", "input": "hello"})).await;
+    assert_eq!(floats(&serde_json::from_value(again).unwrap(), 0), first);
+}
+
+#[actix_rt::test]
+async fn dimensions_and_base64() {
+    let base = start(unpaced_config());
+    let (_, body) = embed(&base, None, json!({"model": "text-embedding-3-large", "input": "hi"})).await;
+    assert_eq!(floats(&serde_json::from_value(body).unwrap(), 0).len(), 3072);
+
+    let (_, body) = embed(&base, None, json!({"model": "text-embedding-3-small", "input": "hi", "dimensions": 8})).await;
+    let short = floats(&serde_json::from_value(body).unwrap(), 0);
+    assert_eq!(short.len(), 8);
+
+    let body = json!({"model": "text-embedding-3-small", "input": "hi", "dimensions": 8, "encoding_format": "base64"});
+    let (_, body) = embed(&base, None, body).await;
+    let list: EmbeddingList = serde_json::from_value(body).unwrap();
+    let EmbeddingVector::Base64(encoded) = &list.data[0].embedding else { panic!("expected base64") };
+    let decoded: Vec<f32> = STANDARD
+        .decode(encoded)
+        .unwrap()
+        .chunks(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+    assert_eq!(decoded, short);
+}
+
+#[actix_rt::test]
+async fn bad_requests_are_refused() {
+    let base = start(unpaced_config());
+    for (body, param) in [
+        (json!({"input": []}), "input"),
+        (json!({"input": ["ok", ""]}), "input"),
+        (json!({"model": "text-embedding-3-small", "input": "hi", "dimensions": 0}), "dimensions"),
+        (json!({"model": "text-embedding-3-small", "input": "hi", "dimensions": 5000}), "dimensions"),
+        (json!({"model": "text-embedding-ada-002", "input": "hi", "dimensions": 8}), "dimensions"),
+    ] {
+        let (status, reply) = embed(&base, None, body.clone()).await;
+        assert_eq!(status, 400, "{}", body);
+        assert_eq!(reply["error"]["param"], param, "{}", body);
+    }
+}
+
+#[actix_rt::test]
+async fn keys_need_the_embeddings_capability() {
+    let base = start(key(vec![Capability::Tools]));
+    let (status, body) = embed(&base, Some("sk-embed"), json!({"model": "text-embedding-3-small", "input": "hi"})).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["error"]["code"], "permission_denied");
+
+    let base = start(key(vec![Capability::Embeddings]));
+    let (status, _) = embed(&base, Some("sk-embed"), json!({"model": "text-embedding-3-small", "input": "hi"})).await;
+    assert_eq!(status, 200);
+    let (status, body) = embed(&base, Some("sk-embed"), json!({"model": "text-embedding-3-large", "input": "hi"})).await;
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["code"], "model_not_found");
+}
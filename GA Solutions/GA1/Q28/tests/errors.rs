@@ -0,0 +1,54 @@
+//! `MockError` responses and serialization failure propagation.
+
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use serde::{Serialize, Serializer};
+
+use streaming_llm_api::error::MockError;
+use streaming_llm_api::sse;
+
+/// A value whose serialization always fails, standing in for e.g. a map with
+/// non-string keys.
+struct Unserializable;
+
+impl Serialize for Unserializable {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("refusing to serialize"))
+    }
+}
+
+async fn body_json(err: &MockError) -> serde_json::Value {
+    let body = to_bytes(err.error_response().into_body()).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[test]
+fn data_event_reports_serialization_failures() {
+    match sse::data_event(&Unserializable) {
+        Err(MockError::Serialize(detail)) => assert!(detail.contains("refusing to serialize"), "{}", detail),
+        other => panic!("expected a serialization error, got {:?}", other.map(|b| b.len())),
+    }
+}
+
+#[actix_rt::test]
+async fn serialization_failures_are_json_500s() {
+    let err = sse::data_event(&Unserializable).unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let body = body_json(&err).await;
+    assert_eq!(body["error"]["type"], "server_error");
+    assert_eq!(body["error"]["code"], "serialization_failed");
+    assert!(body["error"]["message"].as_str().unwrap().contains("refusing to serialize"));
+}
+
+#[actix_rt::test]
+async fn rejections_keep_their_status_and_param() {
+    let err = MockError::rejected(StatusCode::TOO_MANY_REQUESTS, "slow down", Some("model"));
+    assert_eq!(err.status_code(), StatusCode::TOO_MANY_REQUESTS);
+
+    let body = body_json(&err).await;
+    assert_eq!(body["error"]["message"], "slow down");
+    assert_eq!(body["error"]["type"], "rate_limit_error");
+    assert_eq!(body["error"]["param"], "model");
+}
@@ -0,0 +1,86 @@
+//! Entity tags and conditional requests on JSON completions.
+
+mod common;
+
+use common::{client, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::etag;
+
+async fn complete(base: &str, body: Value, if_none_match: Option<&str>) -> reqwest::Response {
+    let mut request = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&body);
+    if let Some(tag) = if_none_match {
+        request = request.header("if-none-match", tag);
+    }
+    request.send().await.unwrap()
+}
+
+fn chat(seed: u64) -> Value {
+    json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "cache me"}], "seed": seed})
+}
+
+fn etag(resp: &reqwest::Response) -> String {
+    resp.headers().get("etag").unwrap().to_str().unwrap().to_string()
+}
+
+#[actix_rt::test]
+async fn seeded_replies_keep_their_tag() {
+    let base = start(unpaced_config());
+    let first = complete(&base, chat(7), None).await;
+    let tag = etag(&first);
+    assert!(tag.starts_with('"') && tag.ends_with('"'));
+    let second = complete(&base, chat(7), None).await;
+    assert_eq!(etag(&second), tag);
+    let (a, b): (Value, Value) = (first.json().await.unwrap(), second.json().await.unwrap());
+    assert_ne!(a["id"], b["id"]);
+
+    let mut other = chat(7);
+    other["messages"][0]["content"] = json!("something else");
+    assert_ne!(etag(&complete(&base, other, None).await), tag);
+}
+
+#[actix_rt::test]
+async fn unseeded_replies_are_not_tagged() {
+    let base = start(unpaced_config());
+    let resp = complete(&base, json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "cache me"}]}), None).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get("etag").is_none());
+}
+
+#[test]
+fn per_request_extension_fields_are_left_out_of_the_tag() {
+    let reply = |x_mock: Value| json!({"id": "chatcmpl-1", "object": "chat.completion", "created": 1, "choices": [], "x_mock": x_mock});
+    let tag = etag::of(&reply(json!({"estimated_cost_usd": 0.5})));
+    let queued = reply(json!({"estimated_cost_usd": 0.5, "queue_ms": 40, "watermark": {"instance": "a"}}));
+    assert_eq!(etag::of(&queued), tag);
+    assert_eq!(etag::of(&reply(json!({"queue_ms": 40}))), etag::of(&json!({"id": "chatcmpl-2", "object": "chat.completion", "created": 2, "choices": []})));
+    assert_ne!(etag::of(&reply(json!({"estimated_cost_usd": 0.6}))), tag);
+}
+
+#[actix_rt::test]
+async fn a_matching_if_none_match_gets_a_304() {
+    let base = start(unpaced_config());
+    let tag = etag(&complete(&base, chat(3), None).await);
+
+    let resp = complete(&base, chat(3), Some(&tag)).await;
+    assert_eq!(resp.status(), 304);
+    assert_eq!(etag(&resp), tag);
+    assert!(resp.headers().get("x-request-id").is_some());
+    assert!(resp.bytes().await.unwrap().is_empty());
+
+    let listed = format!("\"stale\", W/{}", tag);
+    assert_eq!(complete(&base, chat(3), Some(&listed)).await.status(), 304);
+    assert_eq!(complete(&base, chat(3), Some("*")).await.status(), 304);
+    let resp = complete(&base, chat(3), Some("\"stale\"")).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.json::<Value>().await.unwrap()["object"], "chat.completion");
+}
+
+#[actix_rt::test]
+async fn streams_are_not_tagged() {
+    let base = start(unpaced_config());
+    let resp = common::post(&base, json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "cache me"}], "stream": true})).await;
+    assert!(resp.headers().get("etag").is_none());
+}
@@ -0,0 +1,96 @@
+//! The `/v1/internal/events` lifecycle firehose.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{client, post, start, unpaced_config};
+
+/// Reads SSE events off `resp` until `n` named events have arrived,
+/// returning `(name, data)` pairs.
+async fn next_events(resp: &mut reqwest::Response, buffer: &mut String, n: usize) -> Vec<(String, serde_json::Value)> {
+    let mut events = Vec::new();
+    loop {
+        while let Some(end) = buffer.find("\n\n") {
+            let raw: String = buffer.drain(..end + 2).collect();
+            if raw.starts_with(':') {
+                continue;
+            }
+            let name = raw.lines().find_map(|l| l.strip_prefix("event: ")).unwrap().to_string();
+            let data = raw.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+            events.push((name, serde_json::from_str(data).unwrap()));
+            if events.len() == n {
+                return events;
+            }
+        }
+        let chunk = tokio::time::timeout(Duration::from_secs(5), resp.chunk())
+            .await
+            .expect("firehose went quiet")
+            .unwrap()
+            .expect("firehose ended");
+        buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+}
+
+async fn subscribe(base: &str) -> (reqwest::Response, String) {
+    let mut resp = client().get(format!("{}/v1/internal/events", base)).send().await.unwrap();
+    assert_eq!(resp.headers()["content-type"], "text/event-stream");
+    let first = resp.chunk().await.unwrap().unwrap();
+    assert_eq!(&first[..], b": subscribed\n\n");
+    (resp, String::new())
+}
+
+fn request(content: &str) -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": content}], "stream": true})
+}
+
+#[actix_rt::test]
+async fn subscribers_see_every_request_lifecycle() {
+    let base = start(unpaced_config());
+    let (mut events, mut buffer) = subscribe(&base).await;
+
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let resp = post(&base, request(&format!("request {}", i))).await;
+        ids.push(resp.headers()["x-request-id"].to_str().unwrap().to_string());
+        resp.text().await.unwrap();
+    }
+
+    let seen = next_events(&mut events, &mut buffer, 6).await;
+    let completed: Vec<_> = seen
+        .iter()
+        .filter(|(name, _)| name == "stream.completed")
+        .map(|(_, data)| data["request_id"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(completed, ids);
+    for (name, data) in &seen {
+        assert_eq!(data["type"], name.as_str());
+    }
+}
+
+#[actix_rt::test]
+async fn every_subscriber_gets_its_own_copy() {
+    let base = start(unpaced_config());
+    let (mut a, mut buffer_a) = subscribe(&base).await;
+    let (mut b, mut buffer_b) = subscribe(&base).await;
+
+    post(&base, request("fan out")).await.text().await.unwrap();
+    let from_a = next_events(&mut a, &mut buffer_a, 2).await;
+    let from_b = next_events(&mut b, &mut buffer_b, 2).await;
+    assert_eq!(from_a[1].0, "stream.completed");
+    assert_eq!(from_a[1].1["request_id"], from_b[1].1["request_id"]);
+}
+
+#[actix_rt::test]
+async fn untracked_streams_send_no_events() {
+    let base = start(unpaced_config());
+    // Nobody is subscribed, so this stream is not tracked at all.
+    post(&base, request("before")).await.text().await.unwrap();
+
+    let (mut events, mut buffer) = subscribe(&base).await;
+    post(&base, request("after")).await.text().await.unwrap();
+    let seen = next_events(&mut events, &mut buffer, 1).await;
+    assert_eq!(seen[0].0, "stream.started");
+    assert_eq!(seen[0].1["request_id"], "req_2");
+}
@@ -0,0 +1,171 @@
+//! A/B routing of a model to weighted profile variants.
+
+mod common;
+
+use common::{client, parse_events, post, start};
+use serde_json::{json, Value};
+use streaming_llm_api::config::Config;
+
+const EXPERIMENT: &str = r#"
+[[experiments]]
+model = "gpt-4o"
+
+[[experiments.variants]]
+name = "control"
+weight = 3
+profile = { chunk_delay_ms = 0, finish_reason = "stop" }
+
+[[experiments.variants]]
+name = "terse"
+profile = { chunk_delay_ms = 0, tokens = 4, finish_reason = "length" }
+
+[[experiments.variants]]
+name = "retired"
+weight = 0
+"#;
+
+fn experiment_config() -> Config {
+    toml::from_str(EXPERIMENT).unwrap()
+}
+
+fn chat(seed: u64) -> Value {
+    json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "compare"}], "stream": true, "seed": seed})
+}
+
+/// The variant header, and the events of the reply.
+async fn variant_of(base: &str, body: Value) -> (Option<String>, Vec<Value>) {
+    let resp = post(base, body).await;
+    let variant = resp.headers().get("x-mock-variant").map(|v| v.to_str().unwrap().to_string());
+    let text = resp.text().await.unwrap();
+    let chunks = parse_events(&text)
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect();
+    (variant, chunks)
+}
+
+#[actix_rt::test]
+async fn seeds_pick_variants_by_weight() {
+    let base = start(experiment_config());
+    let mut counts = std::collections::HashMap::new();
+    for seed in 0..40 {
+        let (variant, chunks) = variant_of(&base, chat(seed)).await;
+        let variant = variant.unwrap();
+        assert_eq!(chunks[0]["x_mock"]["variant"], variant.as_str());
+        let finish = if variant == "terse" { "length" } else { "stop" };
+        assert_eq!(chunks.last().unwrap()["choices"][0]["finish_reason"], finish);
+        *counts.entry(variant).or_insert(0) += 1;
+    }
+    assert!(!counts.contains_key("retired"));
+    assert!(counts["control"] > counts["terse"]);
+
+    // The same seed always lands on the same variant.
+    let (first, _) = variant_of(&base, chat(7)).await;
+    for _ in 0..5 {
+        assert_eq!(variant_of(&base, chat(7)).await.0, first);
+    }
+}
+
+#[actix_rt::test]
+async fn json_replies_and_other_models_are_labeled_accordingly() {
+    let base = start(experiment_config());
+    let mut body = chat(3);
+    body.as_object_mut().unwrap().remove("stream");
+    let resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let variant = resp.headers().get("x-mock-variant").unwrap().to_str().unwrap().to_string();
+    let reply: Value = resp.json().await.unwrap();
+    assert_eq!(reply["x_mock"]["variant"], variant.as_str());
+
+    let mut other = chat(3);
+    other["model"] = json!("gpt-4o-mini");
+    let (variant, chunks) = variant_of(&base, other).await;
+    assert_eq!(variant, None);
+    assert!(chunks.iter().all(|c| c["x_mock"].get("variant").is_none()));
+}
+
+#[actix_rt::test]
+async fn stub_rules_win_over_experiments() {
+    let mut config = experiment_config();
+    config.stubs = toml::from_str::<Config>("[[stubs]]\nname = \"pinned\"\nmatch = { contains = \"pin\" }\nprofile = { chunk_delay_ms = 0 }\n")
+        .unwrap()
+        .stubs;
+    let base = start(config);
+    let mut body = chat(1);
+    body["messages"][0]["content"] = json!("pin this");
+    let resp = post(&base, body).await;
+    assert_eq!(resp.headers().get("x-mock-rule").unwrap(), "pinned");
+    assert!(resp.headers().get("x-mock-variant").is_none());
+}
+
+#[cfg(feature = "recording")]
+#[actix_rt::test]
+async fn captures_record_the_variant() {
+    let base = start(experiment_config());
+    let (variant, _) = variant_of(&base, chat(11)).await;
+    let captures: Value = client()
+        .get(format!("{}/v1/internal/captures", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(captures["data"][0]["variant"], variant.unwrap().as_str());
+}
+
+#[cfg(all(feature = "recording", feature = "endpoints"))]
+#[actix_rt::test]
+async fn cohere_and_gemini_captures_record_the_variant() {
+    let config: Config = toml::from_str(
+        r#"
+[[experiments]]
+model = "command-r"
+[[experiments.variants]]
+name = "cohere-only"
+profile = { chunk_delay_ms = 0 }
+
+[[experiments]]
+model = "gemini-2.0-flash"
+[[experiments.variants]]
+name = "gemini-only"
+profile = { chunk_delay_ms = 0 }
+"#,
+    )
+    .unwrap();
+    let base = start(config);
+    let cohere = client()
+        .post(format!("{}/v1/chat", base))
+        .json(&json!({"model": "command-r", "message": "compare", "stream": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(cohere.status(), 200);
+    cohere.text().await.unwrap();
+    let gemini = client()
+        .post(format!("{}/v1beta/models/gemini-2.0-flash:streamGenerateContent", base))
+        .json(&json!({"contents": [{"role": "user", "parts": [{"text": "compare"}]}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(gemini.status(), 200);
+    gemini.text().await.unwrap();
+
+    let captures: Value = client()
+        .get(format!("{}/v1/internal/captures", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut variants: Vec<&str> = captures["data"].as_array().unwrap().iter().map(|c| c["variant"].as_str().unwrap()).collect();
+    variants.sort_unstable();
+    assert_eq!(variants, ["cohere-only", "gemini-only"]);
+}
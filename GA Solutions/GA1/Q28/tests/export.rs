@@ -0,0 +1,128 @@
+//! Exporting captured traffic as fine-tuning JSONL, over the admin API and the CLI.
+#![cfg(all(feature = "admin", feature = "recording", feature = "client-tools"))]
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, with_profile};
+use streaming_llm_api::client::export::{self, ExportArgs};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::export::ExportFormat;
+use streaming_llm_api::stubs::{Matcher, ResponseProfile, StubRule};
+
+const TOKEN: &str = "export-token";
+
+fn config() -> Config {
+    let mut config = with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        chunks: 4,
+        ..ResponseProfile::default()
+    });
+    config.stubs.insert(
+        0,
+        StubRule {
+            name: Some("failing".to_string()),
+            matcher: Matcher {
+                contains: Some("fail".to_string()),
+                ..Matcher::default()
+            },
+            profile: ResponseProfile {
+                chunk_delay_ms: 0,
+                error_after: Some(2),
+                ..ResponseProfile::default()
+            },
+        },
+    );
+    config.admin.token = Some(TOKEN.to_string());
+    config
+}
+
+fn chat(content: &str) -> serde_json::Value {
+    serde_json::json!({
+        "messages": [
+            {"role": "system", "content": "You are terse."},
+            {"role": "user", "content": content}
+        ],
+        "stream": true,
+        "seed": 0
+    })
+}
+
+async fn export(base: &str) -> Vec<serde_json::Value> {
+    let resp = client()
+        .get(format!("{}/v1/admin/captures/export", base))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "application/jsonl");
+    let body = resp.text().await.unwrap();
+    body.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+#[actix_rt::test]
+async fn finished_streams_become_chat_examples() {
+    let base = start(config());
+    let events = parse_events(&post(&base, chat("hello")).await.text().await.unwrap());
+    post(&base, chat("please fail")).await.text().await.unwrap();
+    post(&base, serde_json::json!({"prompt": "unstreamed", "stream": false})).await;
+
+    let examples = export(&base).await;
+    assert_eq!(examples.len(), 1, "failed and unstreamed requests are left out");
+    assert_eq!(
+        examples[0]["messages"],
+        serde_json::json!([
+            {"role": "system", "content": "You are terse."},
+            {"role": "user", "content": "hello"},
+            {"role": "assistant", "content": content_of(&events)}
+        ])
+    );
+}
+
+#[actix_rt::test]
+async fn truncated_replies_are_marked_and_left_out() {
+    let mut config = config();
+    config.capture.max_reply_chars = 10;
+    let base = start(config);
+    post(&base, chat("hello")).await.text().await.unwrap();
+
+    let captures: serde_json::Value = client()
+        .get(format!("{}/v1/internal/captures", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let reply = &captures["data"][0]["reply"];
+    assert_eq!(reply["content"].as_str().unwrap().chars().count(), 10);
+    assert_eq!(reply["truncated"], true);
+    assert_eq!(reply["finished"], true);
+    assert!(export(&base).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn export_requires_the_admin_token() {
+    let base = start(config());
+    let resp = client().get(format!("{}/v1/admin/captures/export", base)).send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_rt::test]
+async fn the_cli_writes_the_export_to_a_file() {
+    let base = start(config());
+    post(&base, chat("hello")).await.text().await.unwrap();
+
+    let output = std::env::temp_dir().join(format!("stream-api-export-{}.jsonl", std::process::id()));
+    let args = ExportArgs {
+        url: base,
+        token: Some(TOKEN.to_string()),
+        format: ExportFormat::Finetune,
+        output: Some(output.clone()),
+    };
+    export::run(&args).await.unwrap();
+    let written = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(written.lines().count(), 1);
+    assert!(written.contains("\"role\":\"assistant\""));
+}
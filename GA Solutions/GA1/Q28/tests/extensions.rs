@@ -0,0 +1,107 @@
+//! vLLM and TGI sampling fields: accepted, validated, captured and matched on.
+
+mod common;
+
+use common::{post, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::extensions::Extensions;
+use streaming_llm_api::stubs::{Matcher, ResponseProfile, StubRule};
+
+fn chat(extra: Value) -> Value {
+    let mut body = json!({"messages": [{"role": "user", "content": "sample"}], "stream": true});
+    body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+    body
+}
+
+#[actix_rt::test]
+async fn vllm_requests_are_accepted() {
+    let base = start(unpaced_config());
+    let body = chat(json!({"best_of": 4, "use_beam_search": true, "min_tokens": 8, "max_tokens": 64, "repetition_penalty": 1.1}));
+    assert_eq!(post(&base, body).await.status(), 200);
+}
+
+#[actix_rt::test]
+async fn out_of_range_values_are_rejected_like_vllm() {
+    let base = start(unpaced_config());
+    for (extra, param) in [
+        (json!({"best_of": 0}), "best_of"),
+        (json!({"use_beam_search": true}), "best_of"),
+        (json!({"min_tokens": 100, "max_tokens": 10}), "min_tokens"),
+        (json!({"repetition_penalty": 0.0}), "repetition_penalty"),
+        (json!({"repetition_penalty": -1.5}), "repetition_penalty"),
+    ] {
+        let response = post(&base, chat(extra.clone())).await;
+        assert_eq!(response.status(), 400, "{}", extra);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["param"], param, "{}", extra);
+    }
+}
+
+#[actix_rt::test]
+async fn rules_match_on_extension_values() {
+    let mut config: Config = unpaced_config();
+    config.stubs.insert(
+        0,
+        StubRule {
+            name: Some("beam".to_string()),
+            matcher: Matcher {
+                extensions: Extensions {
+                    use_beam_search: Some(true),
+                    ..Extensions::default()
+                },
+                ..Matcher::default()
+            },
+            profile: ResponseProfile {
+                chunk_delay_ms: 0,
+                ..ResponseProfile::default()
+            },
+        },
+    );
+    let base = start(config);
+    let rule = |response: reqwest::Response| response.headers()["x-mock-rule"].to_str().unwrap().to_string();
+    assert_eq!(rule(post(&base, chat(json!({"use_beam_search": true, "best_of": 3}))).await), "beam");
+    assert_eq!(rule(post(&base, chat(json!({"use_beam_search": false}))).await), "test");
+    assert_eq!(rule(post(&base, chat(json!({}))).await), "test");
+}
+
+#[test]
+fn rules_take_extensions_from_toml() {
+    let config: Config = toml::from_str(
+        r#"
+        [[stubs]]
+        match = { contains = "x", extensions = { best_of = 2, repetition_penalty = 1.2 } }
+        "#,
+    )
+    .unwrap();
+    let wanted = &config.stubs[0].matcher.extensions;
+    assert_eq!(wanted.best_of, Some(2));
+    let request = Extensions {
+        best_of: Some(2),
+        repetition_penalty: Some(1.2),
+        min_tokens: Some(5),
+        ..Extensions::default()
+    };
+    assert!(wanted.within(&request));
+    assert!(!wanted.within(&Extensions::default()));
+    assert!(toml::from_str::<Config>("[[stubs]]\nmatch = { extensions = { best_off = 2 } }").is_err());
+}
+
+#[cfg(feature = "recording")]
+#[actix_rt::test]
+async fn captures_keep_the_fields_that_were_sent() {
+    let base = start(unpaced_config());
+    post(&base, chat(json!({"min_tokens": 4, "repetition_penalty": 1.05}))).await.text().await.unwrap();
+    post(&base, chat(json!({}))).await.text().await.unwrap();
+    let captures: Value = common::client()
+        .get(format!("{}/v1/internal/captures", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let data = captures["data"].as_array().unwrap();
+    assert_eq!(data[0]["extensions"], json!({"min_tokens": 4, "repetition_penalty": 1.05}));
+    assert!(data[1].get("extensions").is_none());
+}
@@ -0,0 +1,45 @@
+//! Routes follow the enabled cargo features, and the chat endpoint works in
+//! every build. Run with `--no-default-features` to check the minimal build.
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config};
+
+fn found(resp: &reqwest::Response) -> bool {
+    resp.status() != 404
+}
+
+#[actix_rt::test]
+async fn optional_routes_exist_only_with_their_feature() {
+    let base = start(unpaced_config());
+    let get = |path: &str| client().get(format!("{}{}", base, path)).send();
+
+    assert_eq!(found(&get("/v1/models").await.unwrap()), cfg!(feature = "endpoints"));
+    assert_eq!(found(&get("/v1/internal/stats").await.unwrap()), cfg!(feature = "endpoints"));
+    assert_eq!(found(&get("/v1/internal/metrics").await.unwrap()), cfg!(feature = "endpoints"));
+    assert_eq!(found(&get("/v1/internal/captures").await.unwrap()), cfg!(feature = "recording"));
+    assert_eq!(found(&get("/v1/internal/shadow").await.unwrap()), cfg!(feature = "recording"));
+    assert_eq!(found(&get("/v1/admin/storm").await.unwrap()), cfg!(feature = "admin"));
+    assert_eq!(found(&get("/openapi.json").await.unwrap()), cfg!(feature = "openapi"));
+    assert_eq!(found(&get("/docs").await.unwrap()), cfg!(feature = "openapi"));
+    let tokenize = client()
+        .post(format!("{}/v1/internal/tokenize", base))
+        .json(&serde_json::json!({"text": "hi"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(found(&tokenize), cfg!(feature = "tokenizer"));
+}
+
+#[actix_rt::test]
+async fn chat_completions_stream_in_every_build() {
+    let base = start(unpaced_config());
+    let body = post(&base, serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    let events = parse_events(&body);
+    assert!(!content_of(&events).is_empty());
+    assert_eq!(events.last().unwrap().data, "[DONE]");
+}
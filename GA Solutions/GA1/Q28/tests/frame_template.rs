@@ -0,0 +1,62 @@
+use streaming_llm_api::chat::StreamChunk;
+use streaming_llm_api::sse::{self, escape_json_into, FrameTemplate};
+
+fn escaped(s: &str) -> String {
+    let mut out = Vec::new();
+    escape_json_into(s, &mut out);
+    String::from_utf8(out).unwrap()
+}
+
+fn serde_escaped(s: &str) -> String {
+    let quoted = serde_json::to_string(s).unwrap();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[test]
+fn escaping_matches_serde_json_for_every_ascii_byte() {
+    for b in 0u8..0x80 {
+        let s = (b as char).to_string();
+        assert_eq!(escaped(&s), serde_escaped(&s), "byte {:#04x}", b);
+    }
+}
+
+#[test]
+fn escaping_matches_serde_json_for_mixed_text() {
+    let samples = [
+        "",
+        "plain text",
+        "quote \" and backslash \\ and slash /",
+        "line\nbreak\r\n\ttab",
+        "\u{0}\u{1}\u{1f}\u{7f}",
+        "naïve café, 日本語, emoji 🚀👩‍💻",
+        "already \\\"escaped\\\"",
+        "\u{2028}\u{2029} separators",
+        "```rust\nfn main() { println!(\"hi\\n\"); }\n```",
+    ];
+    for s in samples {
+        assert_eq!(escaped(s), serde_escaped(s), "{:?}", s);
+    }
+}
+
+#[test]
+fn escaped_output_round_trips_through_a_json_parser() {
+    let s = "mixed \"\\\u{8}\u{c}\n\r\t\u{1b} ünïcödé 🦀";
+    let parsed: String = serde_json::from_str(&format!("\"{}\"", escaped(s))).unwrap();
+    assert_eq!(parsed, s);
+}
+
+#[test]
+fn rendered_frames_match_full_serialization() {
+    let template = FrameTemplate::new(&StreamChunk::with_content("__CONTENT__".to_string()), "__CONTENT__")
+        .expect("placeholder should appear in the envelope");
+    for content in ["hello", "", "with \"quotes\"\nand lines", "🚀 __CONTENT__ literal"] {
+        let rendered = template.render(content);
+        let expected = sse::data_event(&StreamChunk::with_content(content.to_string())).unwrap();
+        assert_eq!(rendered, expected, "{:?}", content);
+    }
+}
+
+#[test]
+fn missing_placeholder_yields_no_template() {
+    assert!(FrameTemplate::new(&StreamChunk::with_content("other".to_string()), "__CONTENT__").is_none());
+}
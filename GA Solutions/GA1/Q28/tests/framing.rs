@@ -0,0 +1,59 @@
+//! SSE frame corruption: alternative line endings, a BOM, and a missing final blank line.
+
+mod common;
+
+use common::{client, start, with_profile};
+use streaming_llm_api::stubs::{FrameFault, ResponseProfile};
+
+async fn body(frame_faults: Vec<FrameFault>, event_ids: bool) -> Vec<u8> {
+    let mut config = with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        chunks: 3,
+        frame_faults,
+        ..ResponseProfile::default()
+    });
+    config.stream.event_ids = event_ids;
+    let base = start(config);
+    client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"prompt": "frames", "stream": true, "seed": 0}))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+#[actix_rt::test]
+async fn crlf_and_cr_replace_every_line_ending() {
+    let plain = body(Vec::new(), true).await;
+    let crlf = body(vec![FrameFault::Crlf], true).await;
+    assert!(crlf.windows(4).any(|w| w == b"\r\n\r\n"));
+    assert!(!crlf.windows(2).enumerate().any(|(i, w)| w[1] == b'\n' && (i == 0 || w[0] != b'\r')));
+    assert_eq!(String::from_utf8(crlf).unwrap().replace("\r\n", "\n").into_bytes(), plain);
+
+    let cr = body(vec![FrameFault::Cr], true).await;
+    assert!(!cr.contains(&b'\n'));
+    assert_eq!(String::from_utf8(cr).unwrap().replace('\r', "\n").into_bytes(), plain);
+}
+
+#[actix_rt::test]
+async fn bom_prefixes_only_the_stream() {
+    let plain = body(Vec::new(), false).await;
+    let bom = body(vec![FrameFault::Bom], false).await;
+    assert_eq!(&bom[..3], b"\xef\xbb\xbf");
+    assert_eq!(&bom[3..], &plain[..]);
+}
+
+#[actix_rt::test]
+async fn the_final_event_is_left_undispatched() {
+    let plain = body(Vec::new(), false).await;
+    let truncated = body(vec![FrameFault::MissingFinalBlankLine], false).await;
+    assert!(truncated.ends_with(b"data: [DONE]\n"));
+    assert_eq!(truncated.len(), plain.len() - 1);
+
+    let both = body(vec![FrameFault::MissingFinalBlankLine, FrameFault::Crlf], false).await;
+    assert!(both.ends_with(b"data: [DONE]\r\n"));
+}
@@ -0,0 +1,167 @@
+//! Gemini's `streamGenerateContent`, as SSE and as a streamed JSON array.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config, with_profile};
+use serde_json::{json, Value};
+use streaming_llm_api::keys::{ApiKey, Tier};
+use streaming_llm_api::stubs::ResponseProfile;
+
+const MODEL: &str = "gemini-1.5-flash";
+
+async fn generate(base: &str, query: &str, body: Value) -> reqwest::Response {
+    client()
+        .post(format!("{}/v1beta/models/{}:streamGenerateContent{}", base, MODEL, query))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+fn hello() -> Value {
+    json!({"contents": [{"role": "user", "parts": [{"text": "hello"}]}]})
+}
+
+async fn sse_responses(base: &str, body: Value) -> Vec<Value> {
+    let response = generate(base, "?alt=sse", body).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    parse_events(&response.text().await.unwrap())
+        .iter()
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect()
+}
+
+fn text_of(response: &Value) -> &str {
+    response["candidates"][0]["content"]["parts"][0]["text"].as_str().unwrap()
+}
+
+#[actix_rt::test]
+async fn sse_streams_candidates_with_usage() {
+    let base = start(unpaced_config());
+    let responses = sse_responses(&base, hello()).await;
+    assert!(responses.len() > 1);
+    let (last, rest) = responses.split_last().unwrap();
+    assert_eq!(last["candidates"][0]["finishReason"], "STOP");
+    assert!(rest.iter().all(|r| r["candidates"][0].get("finishReason").is_none()));
+    assert!(responses.iter().all(|r| r["candidates"][0]["content"]["role"] == "model" && r["modelVersion"] == MODEL));
+
+    let usage = &last["usageMetadata"];
+    let prompt = usage["promptTokenCount"].as_u64().unwrap();
+    let candidates = usage["candidatesTokenCount"].as_u64().unwrap();
+    assert!(prompt > 0 && candidates > 0);
+    assert_eq!(usage["totalTokenCount"].as_u64().unwrap(), prompt + candidates);
+
+    // The same rules and text as chat completions.
+    let text: String = responses.iter().map(text_of).collect();
+    let openai = post(&base, json!({"messages": [{"role": "user", "content": "hello"}], "stream": true})).await;
+    assert_eq!(content_of(&parse_events(&openai.text().await.unwrap())), text);
+}
+
+#[actix_rt::test]
+async fn without_alt_the_responses_are_one_json_array() {
+    let base = start(unpaced_config());
+    let response = generate(&base, "", hello()).await;
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let array: Vec<Value> = response.json().await.unwrap();
+    let sse = sse_responses(&base, hello()).await;
+    assert_eq!(array.len(), sse.len());
+    assert_eq!(array.iter().map(text_of).collect::<String>(), sse.iter().map(text_of).collect::<String>());
+}
+
+#[actix_rt::test]
+async fn system_instruction_and_history_count_as_input() {
+    let base = start(unpaced_config());
+    let prompt_tokens = |responses: Vec<Value>| responses[0]["usageMetadata"]["promptTokenCount"].as_u64().unwrap();
+    let bare = prompt_tokens(sse_responses(&base, hello()).await);
+    let body = json!({
+        "systemInstruction": {"parts": [{"text": "Be brief."}]},
+        "contents": [
+            {"role": "user", "parts": [{"text": "earlier question"}]},
+            {"role": "model", "parts": [{"text": "earlier answer"}]},
+            {"role": "user", "parts": [{"text": "hello"}]}
+        ],
+        "generationConfig": {"maxOutputTokens": 64}
+    });
+    assert!(prompt_tokens(sse_responses(&base, body).await) > bare);
+}
+
+#[actix_rt::test]
+async fn errors_use_google_bodies() {
+    let base = start(unpaced_config());
+    let response = generate(&base, "?alt=sse", json!({"contents": []})).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], 400);
+    assert_eq!(body["error"]["status"], "INVALID_ARGUMENT");
+
+    let base = start(with_profile(ResponseProfile {
+        error_after: Some(0),
+        error_code: 429,
+        error_message: "Resource has been exhausted".to_string(),
+        ..ResponseProfile::default()
+    }));
+    let response = generate(&base, "?alt=sse", hello()).await;
+    assert_eq!(response.status(), 429);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body, json!({"error": {"code": 429, "message": "Resource has been exhausted", "status": "RESOURCE_EXHAUSTED"}}));
+
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(2),
+        ..ResponseProfile::default()
+    }));
+    let responses = sse_responses(&base, hello()).await;
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[2]["error"]["status"], "INTERNAL");
+    let array: Vec<Value> = generate(&base, "", hello()).await.json().await.unwrap();
+    assert_eq!(array[2]["error"]["code"], 500);
+}
+
+#[actix_rt::test]
+async fn empty_replies_are_still_one_finished_response() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        tokens: Some(0),
+        ..ResponseProfile::default()
+    }));
+    let text = generate(&base, "", hello()).await.text().await.unwrap();
+    assert!(text.starts_with('[') && text.ends_with(']'), "{}", text);
+    let array: Vec<Value> = serde_json::from_str(&text).unwrap();
+    assert_eq!(array.len(), 1);
+    assert_eq!(text_of(&array[0]), "");
+    assert_eq!(array[0]["candidates"][0]["finishReason"], "STOP");
+    let events = sse_responses(&base, hello()).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["candidates"], array[0]["candidates"]);
+}
+
+#[actix_rt::test]
+async fn keys_are_read_the_way_google_sends_them() {
+    let mut config = unpaced_config();
+    config.keys = vec![ApiKey {
+        key: "AIza-test".to_string(),
+        tier: Tier::Pro,
+        organization: None,
+        project: None,
+        models: Some(vec!["gemini-2.0-flash".to_string()]),
+        capabilities: None,
+        token_quota: None,
+    }];
+    let base = start(config);
+    let with_header = client()
+        .post(format!("{}/v1beta/models/{}:streamGenerateContent", base, MODEL))
+        .header("x-goog-api-key", "AIza-test")
+        .json(&hello())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(with_header.status(), 404);
+    assert_eq!(with_header.json::<Value>().await.unwrap()["error"]["status"], "NOT_FOUND");
+    let in_query = generate(&base, "?alt=sse&key=AIza-test", hello()).await;
+    assert_eq!(in_query.status(), 404);
+    assert_eq!(in_query.headers()["content-type"], "application/json");
+    // Without a key the allowlist does not apply.
+    assert_eq!(generate(&base, "?alt=sse", hello()).await.status(), 200);
+}
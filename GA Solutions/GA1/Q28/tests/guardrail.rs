@@ -0,0 +1,78 @@
+//! Guardrails: a simulated moderation pass that holds back the end of a reply.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{client, content_of, parse_events, post, start, with_profile};
+use serde_json::{json, Value};
+use streaming_llm_api::stubs::ResponseProfile;
+
+fn guarded(guardrail: Value) -> String {
+    start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        guardrail: Some(serde_json::from_value(guardrail).unwrap()),
+        ..ResponseProfile::default()
+    }))
+}
+
+fn request() -> Value {
+    json!({"messages": [{"role": "user", "content": "moderate"}], "stream": true, "seed": 1})
+}
+
+/// The reply text and its finish reasons.
+async fn reply(base: &str) -> (String, Vec<Value>) {
+    let events = parse_events(&post(base, request()).await.text().await.unwrap());
+    let finishes = events
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str::<Value>(&e.data).unwrap()["choices"][0]["finish_reason"].clone())
+        .filter(|reason| !reason.is_null())
+        .collect();
+    (content_of(&events), finishes)
+}
+
+#[actix_rt::test]
+async fn a_passing_guardrail_only_delays_the_end() {
+    let (plain, _) = reply(&start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        ..ResponseProfile::default()
+    })))
+    .await;
+    let base = guarded(json!({"delay_ms": 200, "window_chars": 40, "trigger": "never said"}));
+    let started = Instant::now();
+    let (content, finishes) = reply(&base).await;
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(content, plain);
+    assert!(finishes.is_empty());
+}
+
+#[actix_rt::test]
+async fn a_tripped_guardrail_blocks_the_tail() {
+    let (plain, _) = reply(&guarded(json!({}))).await;
+    let (content, finishes) = reply(&guarded(json!({"window_chars": 40, "rate": 1.0}))).await;
+    assert!(content.len() + 40 <= plain.len());
+    assert!(plain.starts_with(&content));
+    assert_eq!(finishes, [json!("content_filter")]);
+}
+
+#[actix_rt::test]
+async fn rewrites_replace_the_tail_in_json_replies_too() {
+    let base = guarded(json!({"action": "rewrite", "replacement": " [filtered]", "rate": 1.0}));
+    let (content, finishes) = reply(&base).await;
+    assert!(content.ends_with(" [filtered]"));
+    assert_eq!(finishes, [json!("content_filter")]);
+
+    let body: Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&json!({"messages": [{"role": "user", "content": "moderate"}], "seed": 1}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["choices"][0]["message"]["content"], content.as_str());
+    assert_eq!(body["choices"][0]["finish_reason"], "content_filter");
+}
@@ -0,0 +1,114 @@
+//! Handler timeouts: a 504 before the headers, an in-band error event after.
+
+mod common;
+
+use common::{client, parse_events, post, start, with_profile};
+use serde_json::{json, Value};
+use streaming_llm_api::config::{CompressionMode, Config, RouteTimeouts};
+use streaming_llm_api::stubs::ResponseProfile;
+
+/// Replies that take 300 ms to finish, with `timeouts` applied.
+fn slow(timeouts: &str) -> Config {
+    let mut config = with_profile(ResponseProfile {
+        chunks: 3,
+        chunk_delay_ms: 100,
+        ..ResponseProfile::default()
+    });
+    config.timeouts = toml::from_str::<Config>(timeouts).unwrap().timeouts;
+    config
+}
+
+async fn json_reply(base: &str) -> reqwest::Response {
+    client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&json!({"messages": [{"role": "user", "content": "take your time"}]}))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn slow_handlers_get_a_gateway_timeout() {
+    let base = start(slow("[timeouts]\nvalidation_ms = 150"));
+    let resp = json_reply(&base).await;
+    assert_eq!(resp.status(), 504);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "server_error");
+    assert_eq!(body["error"]["code"], "timeout");
+    assert_eq!(body["error"]["message"], "The server did not respond within 150 ms.");
+
+    // A stream answers at once, so only the idle budget applies to it.
+    let events = parse_events(&post(&base, json!({"prompt": "quick headers", "stream": true})).await.text().await.unwrap());
+    assert_eq!(events.last().unwrap().data, "[DONE]");
+}
+
+#[actix_rt::test]
+async fn silent_streams_end_with_an_error_event() {
+    let base = start(slow("[timeouts]\nstream_idle_ms = 60"));
+    let body = post(&base, json!({"prompt": "too slow", "stream": true})).await.text().await.unwrap();
+    let events = parse_events(&body);
+    assert_eq!(events.len(), 1, "{}", body);
+    let error: Value = serde_json::from_str(&events[0].data).unwrap();
+    assert_eq!(error, json!({"error": "The stream sent nothing for 60 ms and was ended.", "code": 504}));
+}
+
+#[actix_rt::test]
+async fn silent_compressed_streams_are_cut_off() {
+    let mut config = slow("[timeouts]\nstream_idle_ms = 60");
+    config.stream.compression = CompressionMode::Auto;
+    let base = start(config);
+    let resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept-encoding", "gzip")
+        .json(&json!({"prompt": "too slow", "stream": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-encoding"], "gzip");
+    // A plain-text error event would corrupt the gzip stream, so there is none.
+    assert!(resp.bytes().await.is_err());
+}
+
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn every_streamed_body_is_held_to_the_idle_budget() {
+    let base = start(slow("[timeouts]\nstream_idle_ms = 60"));
+    let lines = client()
+        .post(format!("{}/v1/chat", base))
+        .json(&json!({"message": "too slow", "stream": true}))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let last: Value = serde_json::from_str(lines.lines().last().unwrap()).unwrap();
+    assert_eq!(last, json!({"error": "The stream sent nothing for 60 ms and was ended.", "code": 504}));
+
+    // A JSON array cannot carry the error, so the connection is cut.
+    let gemini = client()
+        .post(format!("{}/v1beta/models/gemini-2.0-flash:streamGenerateContent", base))
+        .json(&json!({"contents": [{"role": "user", "parts": [{"text": "too slow"}]}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(gemini.status(), 200);
+    assert!(gemini.bytes().await.is_err());
+}
+
+#[actix_rt::test]
+async fn routes_override_the_default_budgets() {
+    let base = start(slow(
+        "[timeouts]\nvalidation_ms = 150\nstream_idle_ms = 60\n[timeouts.routes.\"/v1/chat/completions\"]\nvalidation_ms = 0\nstream_idle_ms = 1000",
+    ));
+    assert_eq!(json_reply(&base).await.status(), 200);
+    let events = parse_events(&post(&base, json!({"prompt": "patient", "stream": true})).await.text().await.unwrap());
+    assert_eq!(events.last().unwrap().data, "[DONE]");
+
+    let config = slow("[timeouts]\nstream_idle_ms = 60\n[timeouts.routes.\"/other\"]\nvalidation_ms = 5");
+    assert_eq!(config.timeouts.routes["/other"].stream_idle_ms, None);
+    let (validation, idle) = config.timeouts.for_path("/other");
+    assert_eq!((validation.unwrap().as_millis(), idle.unwrap().as_millis()), (5, 60));
+    assert!(toml::from_str::<RouteTimeouts>("idle_ms = 5").is_err());
+}
@@ -0,0 +1,99 @@
+//! `serve --reuse-port --pidfile`: a new server takes over the port from a
+//! running one, which drains its streams and exits.
+#![cfg(unix)]
+
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn start(config: &Path, pidfile: &Path) -> Server {
+    let child = Command::new(env!("CARGO_BIN_EXE_stream-api"))
+        .arg("--config")
+        .arg(config)
+        .args(["serve", "--reuse-port", "--pidfile"])
+        .arg(pidfile)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    Server(child)
+}
+
+fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting until {}", what);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn pid_in(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// A new connection for every request, so each one is a fresh accept that
+/// either server could get.
+async fn chat(client: &reqwest::Client, base: &str, latency_ms: u64) -> Result<String, reqwest::Error> {
+    client
+        .post(format!("{}/v1/chat/completions", base))
+        .header("X-Mock-Latency", latency_ms.to_string())
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true}))
+        .send()
+        .await?
+        .text()
+        .await
+}
+
+#[actix_rt::test]
+async fn the_new_server_takes_over_without_dropping_requests() {
+    let dir = std::env::temp_dir().join(format!("stream-api-handover-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let config = dir.join("config.toml");
+    let text = format!("[server]\nbind = \"127.0.0.1:{}\"\nshutdown_timeout_secs = 10\n\n[[stubs]]\nmatch = {{}}\nprofile.chunks = 10\n", port);
+    std::fs::write(&config, text).unwrap();
+    let pidfile = dir.join("stream-api.pid");
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::Client::builder().pool_max_idle_per_host(0).build().unwrap();
+
+    let mut old = start(&config, &pidfile);
+    wait_until("the first server writes its pidfile", || pid_in(&pidfile) == Some(old.0.id()));
+    wait_until("the first server accepts", || std::net::TcpStream::connect(("127.0.0.1", port)).is_ok());
+
+    // A stream of about a second is in flight on the old server...
+    let in_flight = actix_rt::spawn({
+        let (client, base) = (client.clone(), base.clone());
+        async move { chat(&client, &base, 100).await }
+    });
+    actix_rt::time::sleep(Duration::from_millis(200)).await;
+
+    // ...while short requests keep arriving throughout the handover.
+    let new = start(&config, &pidfile);
+    // Several at a time, so the old listener has a queue when it closes.
+    let mut served = 0;
+    while old.0.try_wait().unwrap().is_none() || served < 20 {
+        let batch = (0..8).map(|_| chat(&client, &base, 0));
+        for body in futures::future::join_all(batch).await {
+            let body = body.expect("a request was dropped during the handover");
+            assert!(body.ends_with("data: [DONE]\n\n"), "{}", body);
+            served += 1;
+        }
+        assert!(served < 40000, "the old server never exited");
+    }
+
+    let body = in_flight.await.unwrap().unwrap();
+    assert!(body.ends_with("data: [DONE]\n\n"), "the draining stream was cut off: {}", body);
+    assert!(old.0.wait().unwrap().success());
+    wait_until("the new server claims the pidfile", || pid_in(&pidfile) == Some(new.0.id()));
+    drop(new);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,163 @@
+//! HAR export of captured traffic and HAR import into replay mode.
+#![cfg(all(feature = "admin", feature = "recording"))]
+
+mod common;
+
+use futures::StreamExt;
+use std::time::{Duration, Instant};
+
+use common::{client, content_of, parse_events, post, start, unpaced_config, with_profile};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::stubs::ResponseProfile;
+
+const TOKEN: &str = "har-token";
+
+fn admin(mut config: Config) -> Config {
+    config.admin.token = Some(TOKEN.to_string());
+    config
+}
+
+fn chat(content: &str) -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": content}], "stream": true, "seed": 0})
+}
+
+async fn export_har(base: &str) -> serde_json::Value {
+    client()
+        .get(format!("{}/v1/admin/captures/export?format=har", base))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn import_har(base: &str, har: &serde_json::Value) -> reqwest::Response {
+    client()
+        .post(format!("{}/v1/admin/replay", base))
+        .bearer_auth(TOKEN)
+        .body(har.to_string())
+        .send()
+        .await
+        .unwrap()
+}
+
+fn paced() -> Config {
+    admin(with_profile(ResponseProfile {
+        chunk_delay_ms: 100,
+        chunks: 4,
+        ..ResponseProfile::default()
+    }))
+}
+
+#[actix_rt::test]
+async fn exports_streams_with_chunk_timings() {
+    let base = start(paced());
+    let events = parse_events(&post(&base, chat("archive me")).await.text().await.unwrap());
+
+    let har = export_har(&base).await;
+    assert_eq!(har["log"]["version"], "1.2");
+    let entries = har["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+
+    let started = entry["startedDateTime"].as_str().unwrap();
+    assert_eq!(started.len(), "2024-01-01T00:00:00.000Z".len());
+    assert!(started.ends_with('Z') && started.as_bytes()[10] == b'T', "{}", started);
+
+    let request: serde_json::Value = serde_json::from_str(entry["request"]["postData"]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(request["messages"][0]["content"], "archive me");
+
+    let response = &entry["response"];
+    assert_eq!(response["status"], 200);
+    assert_eq!(response["content"]["mimeType"], "text/event-stream");
+    let recorded = parse_events(response["content"]["text"].as_str().unwrap());
+    assert_eq!(content_of(&recorded), content_of(&events));
+    assert_eq!(recorded.last().unwrap().data, "[DONE]");
+
+    let offsets: Vec<u64> = entry["_sseChunkOffsetsMs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_u64().unwrap())
+        .collect();
+    assert_eq!(offsets.len(), 4);
+    assert!(offsets.windows(2).all(|w| w[1] - w[0] >= 90), "{:?}", offsets);
+}
+
+#[actix_rt::test]
+async fn imported_hars_replay_content_and_pacing() {
+    let recorder = start(paced());
+    let original = parse_events(&post(&recorder, chat("replay me")).await.text().await.unwrap());
+    let har = export_har(&recorder).await;
+
+    // The replaying server would otherwise answer instantly with its own text.
+    let replayer = start(admin(unpaced_config()));
+    let status: serde_json::Value = import_har(&replayer, &har).await.json().await.unwrap();
+    assert_eq!(status, serde_json::json!({"loaded": 1, "recordings": 1}));
+
+    let started = Instant::now();
+    let resp = post(&replayer, chat("replay me")).await;
+    assert_eq!(resp.headers()["x-mock-replay"], "true");
+    let replayed = parse_events(&resp.text().await.unwrap());
+    assert!(started.elapsed() >= Duration::from_millis(350), "{:?}", started.elapsed());
+    assert_eq!(content_of(&replayed), content_of(&original));
+
+    // Other prompts are generated as usual.
+    let other = post(&replayer, chat("something else")).await;
+    assert!(other.headers().get("x-mock-replay").is_none());
+}
+
+#[actix_rt::test]
+async fn devtools_hars_without_chunk_timings_are_spread_over_receive() {
+    let body = [
+        r#"data: {"choices":[{"delta":{"role":"assistant","content":"Hel"}}]}"#,
+        r#"data: {"choices":[{"delta":{"content":"lo"}}]}"#,
+        r#"data: {"choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+        "data: [DONE]",
+    ]
+    .join("\r\n\r\n");
+    let har = serde_json::json!({"log": {"version": "1.2", "entries": [{
+        "request": {"method": "POST", "url": "https://api.openai.com/v1/chat/completions",
+                    "postData": {"mimeType": "application/json", "text": chat("hi").to_string()}},
+        "response": {"status": 200, "content": {"mimeType": "text/event-stream", "text": body}},
+        "timings": {"send": 1, "wait": 50, "receive": 200}
+    }]}});
+    let base = start(admin(unpaced_config()));
+    assert_eq!(import_har(&base, &har).await.status(), 200);
+
+    let started = Instant::now();
+    let events = parse_events(&post(&base, chat("hi")).await.text().await.unwrap());
+    assert!(started.elapsed() >= Duration::from_millis(240), "{:?}", started.elapsed());
+    assert_eq!(content_of(&events), "Hello");
+}
+
+#[actix_rt::test]
+async fn replay_admin_is_guarded_and_validates() {
+    let base = start(admin(unpaced_config()));
+    let unauthorized = client().post(format!("{}/v1/admin/replay", base)).body("{}").send().await.unwrap();
+    assert_eq!(unauthorized.status(), 401);
+    // A body that never ends: answered without waiting for it.
+    let endless = futures::stream::once(async { Ok::<_, std::io::Error>("{\"log\":") }).chain(futures::stream::pending());
+    let unread = client()
+        .post(format!("{}/v1/admin/replay", base))
+        .body(reqwest::Body::wrap_stream(endless))
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unread.status(), 401);
+
+    let invalid = client()
+        .post(format!("{}/v1/admin/replay", base))
+        .bearer_auth(TOKEN)
+        .body("not json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(invalid.status(), 400);
+
+    let cleared = client().delete(format!("{}/v1/admin/replay", base)).bearer_auth(TOKEN).send().await.unwrap();
+    assert_eq!(cleared.status(), 200);
+}
@@ -0,0 +1,167 @@
+//! Chat bodies read incrementally, with prompts that cannot fit the context
+//! window turned away before the body has been read whole.
+#![cfg(feature = "tokenizer")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{client, post, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::ingest::PromptScanner;
+use streaming_llm_api::models::ModelInfo;
+use streaming_llm_api::tokenizer::TokenEncoding;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn tiny_model() -> Config {
+    let mut config = unpaced_config();
+    config.models.push(ModelInfo {
+        id: "tiny".to_string(),
+        context_window: 100,
+        encoding: TokenEncoding::Cl100k,
+        owned_by: "me".to_string(),
+        content_mode: None,
+        pricing: None,
+    });
+    config
+}
+
+/// Sends the head of a body that claims to be much longer, and never the rest.
+async fn send_unfinished(base: &str, head: &str) -> TcpStream {
+    let mut socket = TcpStream::connect(base.trim_start_matches("http://")).await.unwrap();
+    let request = format!(
+        "POST /v1/chat/completions HTTP/1.1\r\nHost: mock\r\nContent-Type: application/json\r\nContent-Length: 1000000\r\n\r\n{}",
+        head
+    );
+    socket.write_all(request.as_bytes()).await.unwrap();
+    socket
+}
+
+/// The status line and JSON body of the reply.
+async fn read_reply(socket: &mut TcpStream) -> (String, Value) {
+    let mut reply = Vec::new();
+    let mut buf = [0; 4096];
+    let (headers, length) = loop {
+        let n = tokio::time::timeout(Duration::from_secs(5), socket.read(&mut buf))
+            .await
+            .expect("rejected without waiting for the body")
+            .unwrap();
+        assert!(n > 0, "connection closed before a reply");
+        reply.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&reply).to_string();
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length = text[..end]
+                .lines()
+                .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length: ").map(str::to_string))
+                .unwrap();
+            break (end + 4, length.parse::<usize>().unwrap());
+        }
+    };
+    while reply.len() < headers + length {
+        let n = socket.read(&mut buf).await.unwrap();
+        reply.extend_from_slice(&buf[..n]);
+    }
+    let status = String::from_utf8_lossy(&reply[..reply.iter().position(|&b| b == b'\r').unwrap()]).to_string();
+    (status, serde_json::from_slice(&reply[headers..headers + length]).unwrap())
+}
+
+#[actix_rt::test]
+async fn oversized_prompts_are_rejected_before_the_body_ends() {
+    let base = start(tiny_model());
+    // The first 20 000 bytes of content already need at least 156 tokens.
+    let head = format!(r#"{{"model": "tiny", "stream": true, "messages": [{{"role": "user", "content": "{}"#, "a".repeat(20_000));
+    let (status, error) = read_reply(&mut send_unfinished(&base, &head).await).await;
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    assert_eq!(error["error"]["code"], "context_length_exceeded");
+    assert_eq!(error["error"]["param"], "messages");
+    assert_eq!(
+        error["error"]["message"],
+        "This model's maximum context length is 100 tokens. However, your messages resulted in at least 162 tokens. \
+         Please reduce the length of the messages."
+    );
+
+    // Without a registered model there is no window to check early.
+    let head = format!(r#"{{"model": "unknown", "messages": [{{"role": "user", "content": "{}"#, "a".repeat(20_000));
+    let mut socket = send_unfinished(&base, &head).await;
+    assert!(tokio::time::timeout(Duration::from_millis(300), socket.read(&mut [0; 64])).await.is_err());
+}
+
+#[actix_rt::test]
+async fn bodies_of_unknown_length_are_cut_off_at_the_cap() {
+    let mut config = unpaced_config();
+    config.request.max_body_bytes = 4096;
+    let base = start(config);
+    let mut socket = TcpStream::connect(base.trim_start_matches("http://")).await.unwrap();
+    let head = format!(r#"{{"stream": true, "messages": [{{"role": "user", "content": "{}"#, "a".repeat(8192));
+    let request = format!(
+        "POST /v1/chat/completions HTTP/1.1\r\nHost: mock\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+        head.len(),
+        head
+    );
+    socket.write_all(request.as_bytes()).await.unwrap();
+    // The body never ends, so only the cap can answer.
+    let (status, error) = read_reply(&mut socket).await;
+    assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+    assert!(error["error"]["message"].is_string());
+}
+
+#[actix_rt::test]
+async fn the_model_may_come_after_the_messages() {
+    let base = start(tiny_model());
+    let raw = format!(r#"{{"messages": [{{"role": "user", "content": "{}"}}], "stream": true, "model": "tiny"}}"#, "a".repeat(20_000));
+    let response = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("content-type", "application/json")
+        .body(raw)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error: Value = response.json().await.unwrap();
+    assert!(error["error"]["message"].as_str().unwrap().contains("at least 162 tokens"));
+}
+
+#[actix_rt::test]
+async fn prompts_that_may_fit_are_read_and_counted_exactly() {
+    let base = start(tiny_model());
+    let ok = post(&base, json!({"model": "tiny", "messages": [{"role": "user", "content": "short"}], "stream": true})).await;
+    assert_eq!(ok.status(), 200);
+    // 2 400 bytes could be as few as 18 tokens, so this is only caught by the full count.
+    let response = post(&base, json!({"model": "tiny", "messages": [{"role": "user", "content": " hello".repeat(400)}], "stream": true})).await;
+    assert_eq!(response.status(), 400);
+    let error: Value = response.json().await.unwrap();
+    assert!(error["error"]["message"].as_str().unwrap().contains("resulted in 407 tokens"));
+
+    let malformed = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("content-type", "application/json")
+        .body(r#"{"messages": [}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(malformed.status(), 400);
+}
+
+#[test]
+fn the_scanner_counts_only_message_text() {
+    let body = r#"{"model": "tiny", "tools": [{"content": "not a message"}], "messages": [
+        {"role": "system", "content": "ab\"cé"},
+        {"role": "user", "content": [{"type": "text", "text": "hello"}, {"type": "image_url", "image_url": {"url": "data:..."}}]},
+        {"role": "assistant", "content": null, "name": "content"}
+    ]}"#;
+    let mut whole = PromptScanner::default();
+    whole.feed(body.as_bytes());
+    assert_eq!(whole.model(), Some("tiny"));
+    // 3 messages of framing, reply priming, and the bytes of "ab\"cé" and "hello".
+    assert_eq!(whole.min_tokens(1), 9 + 3 + 6 + 5);
+
+    // Chunk boundaries make no difference.
+    let mut bytewise = PromptScanner::default();
+    for b in body.as_bytes() {
+        bytewise.feed(std::slice::from_ref(b));
+    }
+    assert_eq!(bytewise.model(), Some("tiny"));
+    assert_eq!(bytewise.min_tokens(1), whole.min_tokens(1));
+}
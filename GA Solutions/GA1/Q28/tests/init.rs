@@ -0,0 +1,75 @@
+//! `stream-api init`: starter configs that load and pass `check`.
+
+use streaming_llm_api::check::check_file;
+use streaming_llm_api::config::Config;
+#[cfg(feature = "generators")]
+use streaming_llm_api::corpus::Corpus;
+use streaming_llm_api::init::{self, starter_config, InitArgs};
+
+fn variants() -> Vec<InitArgs> {
+    [(false, false), (true, false), (false, true), (true, true)]
+        .into_iter()
+        .map(|(with_auth, with_anthropic)| InitArgs {
+            with_auth,
+            with_anthropic,
+            ..InitArgs::default()
+        })
+        .collect()
+}
+
+#[test]
+fn every_variant_is_a_clean_config() {
+    let dir = std::env::temp_dir().join(format!("stream-api-init-variants-{}", std::process::id()));
+    for mut args in variants() {
+        args.dir = dir.clone();
+        args.force = true;
+        let path = init::run(&args).unwrap();
+        assert_eq!(check_file(path.to_str().unwrap()).unwrap(), [], "{:?}", args);
+        let config = Config::from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), starter_config(&args));
+        assert_eq!(config.admin.token.is_some(), args.with_auth);
+        assert_eq!(!config.keys.is_empty(), args.with_auth);
+        assert_eq!(config.model_presets.contains_key("claude-3-5-sonnet"), args.with_anthropic);
+        let corpus_rules = usize::from(cfg!(feature = "generators"));
+        assert_eq!(config.stubs.len(), 3 + corpus_rules + usize::from(args.with_anthropic));
+        assert_eq!(config.corpus.path.is_some(), cfg!(feature = "generators"));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "generators")]
+#[test]
+fn the_sample_corpus_is_packed_from_its_source() {
+    let dir = std::env::temp_dir().join(format!("stream-api-init-corpus-{}", std::process::id()));
+    init::run(&InitArgs {
+        dir: dir.clone(),
+        ..InitArgs::default()
+    })
+    .unwrap();
+    let source = std::fs::read_to_string(dir.join("corpus/replies.jsonl")).unwrap();
+    let corpus = Corpus::open(&dir.join("corpus/replies.corpus")).unwrap();
+    assert_eq!(corpus.len(), source.lines().count());
+    let first: serde_json::Value = serde_json::from_str(source.lines().next().unwrap()).unwrap();
+    assert_eq!(corpus.reply(0).unwrap(), first["text"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn existing_configs_are_kept_unless_forced() {
+    let dir = std::env::temp_dir().join(format!("stream-api-init-{}", std::process::id()));
+    let mut args = InitArgs {
+        dir: dir.clone(),
+        ..InitArgs::default()
+    };
+    let path = init::run(&args).unwrap();
+    assert_eq!(path, dir.join("config.toml"));
+
+    std::fs::write(&path, "# edited\n").unwrap();
+    assert_eq!(init::run(&args).unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "# edited\n");
+
+    args.force = true;
+    init::run(&args).unwrap();
+    assert!(std::fs::read_to_string(&path).unwrap().contains("[[stubs]]"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,121 @@
+//! Per-key model allowlists and capabilities, and the provider's errors for
+//! requests outside them.
+
+mod common;
+
+use common::{client, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::check::check_text;
+use streaming_llm_api::config::Config;
+use streaming_llm_api::keys::{ApiKey, Capability, Tier};
+
+fn restricted_config() -> Config {
+    let mut config = unpaced_config();
+    config.keys = vec![ApiKey {
+        key: "sk-mini".to_string(),
+        tier: Tier::Pro,
+        organization: None,
+        project: None,
+        models: Some(vec!["gpt-4o-mini".to_string()]),
+        capabilities: Some(vec![Capability::Tools]),
+        token_quota: None,
+    }];
+    config.tiers.pro.tokens_per_sec = None;
+    config
+}
+
+async fn send(base: &str, key: &str, body: Value) -> (u16, Value) {
+    let resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .bearer_auth(key)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    let body = resp.text().await.unwrap();
+    (status, serde_json::from_str(&body).unwrap_or_default())
+}
+
+fn chat(model: &str) -> Value {
+    json!({"model": model, "messages": [{"role": "user", "content": "hi"}], "stream": true})
+}
+
+fn with_image(model: &str) -> Value {
+    json!({"model": model, "stream": true, "messages": [{"role": "user", "content": [
+        {"type": "text", "text": "what is this?"},
+        {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}},
+    ]}]})
+}
+
+#[actix_rt::test]
+async fn models_outside_the_allowlist_are_not_found() {
+    let base = start(restricted_config());
+    assert_eq!(send(&base, "sk-mini", chat("gpt-4o-mini")).await.0, 200);
+
+    let (status, body) = send(&base, "sk-mini", chat("gpt-4o")).await;
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["code"], "model_not_found");
+    assert_eq!(body["error"]["message"], "The model `gpt-4o` does not exist or you do not have access to it.");
+
+    // Unlisted keys are not restricted.
+    assert_eq!(send(&base, "sk-other", chat("gpt-4o")).await.0, 200);
+}
+
+#[actix_rt::test]
+async fn capabilities_not_granted_are_denied() {
+    let base = start(restricted_config());
+
+    let (status, body) = send(&base, "sk-mini", with_image("gpt-4o-mini")).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["error"]["code"], "permission_denied");
+    assert_eq!(body["error"]["type"], "permission_error");
+    assert_eq!(body["error"]["param"], "messages");
+    assert_eq!(body["error"]["message"], "This API key does not have permission to use vision.");
+    assert_eq!(send(&base, "sk-other", with_image("gpt-4o-mini")).await.0, 200);
+
+    let mut tools = chat("gpt-4o-mini");
+    tools["tools"] = json!([{"type": "function", "function": {"name": "lookup"}}]);
+    assert_eq!(send(&base, "sk-mini", tools).await.0, 200);
+
+    let mut config = restricted_config();
+    config.keys[0].capabilities = Some(vec![]);
+    let base = start(config);
+    let mut functions = chat("gpt-4o-mini");
+    functions["functions"] = json!([{"name": "lookup"}]);
+    let (status, body) = send(&base, "sk-mini", functions).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["error"]["param"], "tools");
+}
+
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn keys_only_see_their_models() {
+    let base = start(restricted_config());
+    let get = |path: &str, key: &str| client().get(format!("{}{}", base, path)).bearer_auth(key).send();
+
+    let list: Value = get("/v1/models", "sk-mini").await.unwrap().json().await.unwrap();
+    let ids: Vec<&str> = list["data"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["gpt-4o-mini"]);
+    let all: Value = get("/v1/models", "sk-other").await.unwrap().json().await.unwrap();
+    assert!(all["data"].as_array().unwrap().len() > 1);
+
+    assert_eq!(get("/v1/models/gpt-4o-mini", "sk-mini").await.unwrap().status(), 200);
+    let hidden = get("/v1/models/gpt-4o", "sk-mini").await.unwrap();
+    assert_eq!(hidden.status(), 404);
+    let body: Value = hidden.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "model_not_found");
+}
+
+#[test]
+fn check_warns_about_unknown_allowlisted_models() {
+    let text = r#"
+[[keys]]
+key = "sk-a"
+tier = "free"
+models = ["gpt-4o", "gpt-17"]
+capabilities = ["vision"]
+"#;
+    let messages: Vec<String> = check_text(text).iter().map(ToString::to_string).collect();
+    assert_eq!(messages, ["line 2: warning: keys[0].models lists 'gpt-17', which is not a known model"]);
+}
@@ -0,0 +1,155 @@
+//! Canned replies in the prompt's language.
+
+mod common;
+
+use common::{content_of, parse_events, post, start, with_profile};
+use streaming_llm_api::languages::{self, Language, LANGUAGES};
+use streaming_llm_api::stubs::ResponseProfile;
+
+async fn reply_to(profile: ResponseProfile, prompt: &str) -> (Option<String>, String) {
+    let base = start(with_profile(profile));
+    let resp = post(&base, serde_json::json!({"messages": [{"role": "user", "content": prompt}], "stream": true})).await;
+    let language = resp.headers().get("X-Mock-Language").map(|v| v.to_str().unwrap().to_string());
+    (language, content_of(&parse_events(&resp.text().await.unwrap())))
+}
+
+fn detecting() -> ResponseProfile {
+    ResponseProfile {
+        detect_language: true,
+        ..ResponseProfile::default()
+    }
+}
+
+#[test]
+fn prompts_are_detected_by_script_and_common_words() {
+    let cases = [
+        ("What is the capital of France?", Language::English),
+        ("¿Qué es la fotosíntesis y cómo funciona?", Language::Spanish),
+        ("Comment est-ce que les abeilles font du miel ?", Language::French),
+        ("Warum ist der Himmel blau und nicht grün?", Language::German),
+        ("Você pode explicar como funciona a internet?", Language::Portuguese),
+        ("Почему небо голубое?", Language::Russian),
+        ("为什么天空是蓝色的？", Language::Chinese),
+        ("空はなぜ青いのですか？", Language::Japanese),
+        ("하늘은 왜 파란가요?", Language::Korean),
+        ("لماذا السماء زرقاء؟", Language::Arabic),
+        ("आसमान नीला क्यों है?", Language::Hindi),
+    ];
+    for (prompt, language) in cases {
+        assert_eq!(languages::detect(prompt), Some(language), "{}", prompt);
+    }
+}
+
+#[test]
+fn each_language_is_detected_across_everyday_prompts() {
+    let cases: &[(Language, &[&str])] = &[
+        (
+            Language::English,
+            &["Can you summarize this article for me?", "How do I reset my password", "Write a poem about the sea and the wind"],
+        ),
+        (
+            Language::Spanish,
+            &["¿Cómo puedo aprender a programar?", "Dame una receta para la cena", "Explica por qué el cielo es azul"],
+        ),
+        (
+            Language::French,
+            &["Pouvez-vous résumer cet article pour moi ?", "Comment faire une tarte aux pommes", "Quelle est la capitale du Canada et pourquoi ?"],
+        ),
+        (
+            Language::German,
+            &["Kannst du mir den Artikel zusammenfassen?", "Wie backe ich einen Apfelkuchen", "Was ist die Hauptstadt von Kanada?"],
+        ),
+        (
+            Language::Portuguese,
+            &["Você pode resumir este artigo para mim?", "Como faço um bolo de chocolate", "Qual é a capital do Canadá e por quê?"],
+        ),
+        (Language::Russian, &["Можешь кратко пересказать статью?", "Как испечь яблочный пирог", "Какая столица Канады?"]),
+        (Language::Chinese, &["你能帮我总结这篇文章吗？", "怎么做苹果派", "加拿大的首都是哪里？"]),
+        (Language::Japanese, &["この記事を要約してもらえますか？", "アップルパイの作り方", "カナダの首都はどこですか？"]),
+        (Language::Korean, &["이 기사를 요약해 줄 수 있나요?", "사과 파이 만드는 법", "캐나다의 수도는 어디인가요?"]),
+        (Language::Arabic, &["هل يمكنك تلخيص هذه المقالة؟", "كيف أصنع فطيرة التفاح", "ما هي عاصمة كندا؟"]),
+        (Language::Hindi, &["क्या आप इस लेख का सारांश दे सकते हैं?", "सेब की पाई कैसे बनाएं", "कनाडा की राजधानी क्या है?"]),
+    ];
+    for &(language, prompts) in cases {
+        for prompt in prompts {
+            assert_eq!(languages::detect(prompt), Some(language), "{}", prompt);
+        }
+    }
+}
+
+/// What telling languages apart by script and a few common words gets
+/// wrong, as the README describes.
+#[test]
+fn the_built_in_detector_has_known_limits() {
+    // Kanji alone reads as Chinese.
+    assert_eq!(languages::detect("東京大学"), Some(Language::Chinese));
+    // Other languages in a known script take that script's language.
+    assert_eq!(languages::detect("Чому небо блакитне?"), Some(Language::Russian));
+    assert_eq!(languages::detect("آسمان چرا آبی است؟"), Some(Language::Arabic));
+    // Other Latin-script languages go to whichever shares a common word, or none.
+    assert_eq!(languages::detect("Come si fa la pizza?"), Some(Language::Spanish));
+    assert_eq!(languages::detect("Waarom is de lucht blauw?"), Some(Language::English));
+    assert_eq!(languages::detect("Miksi taivas on sininen?"), None);
+}
+
+#[test]
+fn short_or_wordless_prompts_are_not_detected() {
+    assert_eq!(languages::detect("42?"), None);
+    assert_eq!(languages::detect("ok"), None);
+    assert_eq!(languages::detect("xyzzy plugh"), None);
+}
+
+#[test]
+fn every_language_has_its_own_reply() {
+    for &language in LANGUAGES {
+        assert!(!language.reply().is_empty());
+        if language != Language::English {
+            assert_eq!(languages::detect(language.reply()), Some(language), "{}", language.code());
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn replies_follow_the_prompt_language() {
+    let (language, text) = reply_to(detecting(), "为什么天空是蓝色的？").await;
+    assert_eq!(language.as_deref(), Some("zh"));
+    assert!(text.starts_with("关于您的问题 '为什么天空是蓝色的？':\n\n"), "{}", text);
+    assert_eq!(languages::detect(&text), Some(Language::Chinese));
+
+    let (language, text) = reply_to(detecting(), "Почему небо голубое?").await;
+    assert_eq!(language.as_deref(), Some("ru"));
+    assert!(text.starts_with("По вашему запросу"), "{}", text);
+}
+
+#[actix_rt::test]
+async fn undetected_prompts_fall_back_to_the_profile_language() {
+    let profile = ResponseProfile {
+        language: Some(Language::Arabic),
+        ..detecting()
+    };
+    let (language, text) = reply_to(profile, "42?").await;
+    assert_eq!(language.as_deref(), Some("ar"));
+    assert!(text.starts_with("بخصوص سؤالك"), "{}", text);
+
+    let (language, text) = reply_to(detecting(), "42?").await;
+    assert_eq!(language.as_deref(), Some("en"));
+    assert!(text.starts_with("Regarding your prompt"), "{}", text);
+}
+
+#[actix_rt::test]
+async fn a_fixed_language_ignores_the_prompt() {
+    let profile = ResponseProfile {
+        language: Some(Language::Japanese),
+        ..ResponseProfile::default()
+    };
+    let (language, text) = reply_to(profile, "What is the capital of France?").await;
+    assert_eq!(language.as_deref(), Some("ja"));
+    assert!(text.starts_with("ご質問について"), "{}", text);
+}
+
+#[actix_rt::test]
+async fn replies_are_english_without_a_header_by_default() {
+    let (language, text) = reply_to(ResponseProfile::default(), "为什么天空是蓝色的？").await;
+    assert_eq!(language, None);
+    assert!(text.starts_with("Regarding your prompt"), "{}", text);
+}
@@ -0,0 +1,91 @@
+//! The optional `event: metadata` frame closing a stream.
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config, with_profile};
+use sha2::{Digest, Sha256};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::stubs::ResponseProfile;
+use streaming_llm_api::tokenizer::TokenEncoding;
+
+fn config() -> Config {
+    let mut config = with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        chunks: 5,
+        metadata_frame: true,
+        ..ResponseProfile::default()
+    });
+    config.stream.event_ids = true;
+    config
+}
+
+fn sha256_hex(text: &str) -> String {
+    Sha256::digest(text.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[actix_rt::test]
+async fn metadata_precedes_done_and_describes_the_reply() {
+    let base = start(config());
+    let body = post(&base, serde_json::json!({"prompt": "metadata", "stream": true, "seed": 0}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    let events = parse_events(&body);
+    let [.., metadata, done] = &events[..] else { panic!("too few events") };
+    assert_eq!(done.data, "[DONE]");
+    assert_eq!(metadata.event.as_deref(), Some("metadata"));
+
+    let metadata: serde_json::Value = serde_json::from_str(&metadata.data).unwrap();
+    let content = content_of(&events);
+    assert_eq!(metadata["content_sha256"], sha256_hex(&content));
+    let usage = &metadata["usage"];
+    // Counted delta by delta, as they were generated, not over the joined text.
+    let completion: usize = events
+        .iter()
+        .filter(|e| e.event.is_none() && e.data != "[DONE]")
+        .map(|e| {
+            let chunk: serde_json::Value = serde_json::from_str(&e.data).unwrap();
+            TokenEncoding::default().count(chunk["choices"][0]["delta"]["content"].as_str().unwrap_or(""))
+        })
+        .sum();
+    assert_eq!(usage["completion_tokens"], completion);
+    assert_eq!(
+        usage["total_tokens"].as_u64().unwrap(),
+        usage["prompt_tokens"].as_u64().unwrap() + completion as u64
+    );
+    assert!(metadata["duration_ms"].is_u64());
+}
+
+#[actix_rt::test]
+async fn a_resumed_stream_hashes_the_whole_reply() {
+    let base = start(config());
+    let request = serde_json::json!({"prompt": "metadata", "stream": true, "seed": 0});
+    let full = parse_events(&post(&base, request.clone()).await.text().await.unwrap());
+    let resumed = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Last-Event-ID", "3")
+        .json(&request)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let resumed = parse_events(&resumed);
+    assert!(content_of(&resumed).len() < content_of(&full).len());
+
+    let metadata = |events: &[common::Event]| -> serde_json::Value {
+        let event = events.iter().find(|e| e.event.as_deref() == Some("metadata")).unwrap();
+        serde_json::from_str(&event.data).unwrap()
+    };
+    assert_eq!(metadata(&resumed)["content_sha256"], metadata(&full)["content_sha256"]);
+    assert_eq!(metadata(&resumed)["usage"], metadata(&full)["usage"]);
+}
+
+#[actix_rt::test]
+async fn streams_omit_it_by_default() {
+    let base = start(unpaced_config());
+    let body = post(&base, serde_json::json!({"prompt": "metadata", "stream": true})).await.text().await.unwrap();
+    assert!(parse_events(&body).iter().all(|e| e.event.is_none()));
+}
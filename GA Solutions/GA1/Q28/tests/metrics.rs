@@ -0,0 +1,110 @@
+//! Prometheus metrics: per-model time-to-first-token and inter-chunk gap
+//! histograms, and process gauges.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use futures::StreamExt;
+use std::time::Duration;
+
+use common::{client, post, start, with_profile};
+use serde_json::json;
+use streaming_llm_api::config::PacingMode;
+use streaming_llm_api::stubs::ResponseProfile;
+
+async fn scrape(base: &str) -> String {
+    let resp = client().get(format!("{}/v1/internal/metrics", base)).send().await.unwrap();
+    assert!(resp.headers()["content-type"].to_str().unwrap().starts_with("text/plain; version=0.0.4"));
+    resp.text().await.unwrap()
+}
+
+/// The value of the sample named exactly `series`.
+fn sample(metrics: &str, series: &str) -> f64 {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("no {} in\n{}", series, metrics))
+        .parse()
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn streams_are_timed_per_model() {
+    let base = start(with_profile(ResponseProfile {
+        chunks: 5,
+        chunk_delay_ms: 20,
+        first_chunk_delay_ms: Some(60),
+        ..ResponseProfile::default()
+    }));
+    for _ in 0..2 {
+        let body = json!({"model": "pacing-probe", "messages": [{"role": "user", "content": "time me"}], "stream": true});
+        post(&base, body).await.text().await.unwrap();
+    }
+    let metrics = scrape(&base).await;
+    assert!(metrics.contains("# TYPE mock_time_to_first_token_seconds histogram"));
+
+    let ttft = |s: &str| sample(&metrics, &format!("mock_time_to_first_token_seconds{}", s));
+    assert_eq!(ttft(r#"_count{model="pacing-probe"}"#), 2.0);
+    assert_eq!(ttft(r#"_bucket{model="pacing-probe",le="0.05"}"#), 0.0);
+    assert_eq!(ttft(r#"_bucket{model="pacing-probe",le="+Inf"}"#), 2.0);
+    assert!(ttft(r#"_sum{model="pacing-probe"}"#) >= 0.12);
+
+    let gap = |s: &str| sample(&metrics, &format!("mock_inter_chunk_gap_seconds{}", s));
+    let chunks = gap(r#"_count{model="pacing-probe"}"#);
+    assert!(chunks >= 8.0, "at least four gaps a stream, got {}", chunks);
+    assert_eq!(gap(r#"_bucket{model="pacing-probe",le="0.01"}"#), 0.0);
+    assert_eq!(gap(r#"_bucket{model="pacing-probe",le="1"}"#), chunks);
+}
+
+#[actix_rt::test]
+async fn request_counters_are_exported_too() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        ..ResponseProfile::default()
+    }));
+    post(&base, json!({"messages": [{"role": "user", "content": "count me"}], "stream": true})).await.text().await.unwrap();
+    let metrics = scrape(&base).await;
+    assert!(metrics.contains("# TYPE mock_chat_requests_total counter"));
+    assert!(sample(&metrics, "mock_chat_requests_total") >= 1.0);
+    assert!(sample(&metrics, r#"mock_time_to_first_token_seconds_count{model="unknown"}"#) >= 1.0);
+}
+
+#[actix_rt::test]
+async fn process_gauges_are_reported() {
+    let mut config = with_profile(ResponseProfile {
+        chunks: 4,
+        chunk_delay_ms: 150,
+        ..ResponseProfile::default()
+    });
+    config.stream.pacing = PacingMode::Scheduler;
+    let base = start(config);
+    let mut open = post(&base, json!({"messages": [{"role": "user", "content": "hold on"}], "stream": true}))
+        .await
+        .bytes_stream();
+    open.next().await.unwrap().unwrap();
+
+    // Other tests in this binary move the same gauges concurrently, and the
+    // server parks the next chunk only after the first one is written.
+    let mut metrics = scrape(&base).await;
+    for _ in 0..20 {
+        if sample(&metrics, "mock_parked_timers") >= 1.0 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(5)).await;
+        metrics = scrape(&base).await;
+    }
+    assert!(sample(&metrics, "mock_active_streams") >= 1.0);
+    // The open stream waits out its next chunk in the wheel, which a task drives.
+    assert!(sample(&metrics, "mock_parked_timers") >= 1.0);
+    assert!(sample(&metrics, "mock_background_tasks") >= 1.0);
+    if cfg!(target_os = "linux") {
+        assert!(sample(&metrics, "process_resident_memory_bytes") > 0.0);
+        assert!(sample(&metrics, "process_open_fds") > 0.0);
+    }
+
+    let stats: serde_json::Value = client().get(format!("{}/v1/internal/stats", base)).send().await.unwrap().json().await.unwrap();
+    let process = &stats["process"];
+    assert!(process["active_streams"].as_u64().unwrap() >= 1);
+    assert!(process["parked_timers"].as_u64().unwrap() >= 1);
+    assert!(process["background_tasks"].as_u64().unwrap() >= 1);
+}
@@ -0,0 +1,57 @@
+//! `GET /v1/models` and `GET /v1/models/{id}`.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use common::{client, start, unpaced_config};
+use streaming_llm_api::models::ModelInfo;
+
+async fn get(base: &str, path: &str) -> (u16, serde_json::Value) {
+    let resp = client().get(format!("{}{}", base, path)).send().await.unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}
+
+#[actix_rt::test]
+async fn builtin_models_are_listed_and_retrievable() {
+    let base = start(unpaced_config());
+    let (status, list) = get(&base, "/v1/models").await;
+    assert_eq!(status, 200);
+    assert_eq!(list["object"], "list");
+    let ids: Vec<_> = list["data"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap()).collect();
+    assert!(ids.contains(&"gpt-4o"));
+
+    let (status, model) = get(&base, "/v1/models/gpt-4").await;
+    assert_eq!(status, 200);
+    assert_eq!(model["object"], "model");
+    assert_eq!(model["owned_by"], "openai");
+    assert_eq!(model["context_window"], 8192);
+    assert_eq!(model["encoding"], "cl100k_base");
+    assert!(model["created"].is_u64());
+}
+
+#[actix_rt::test]
+async fn configured_ids_may_contain_slashes() {
+    let mut config = unpaced_config();
+    config.models.push(
+        toml::from_str::<ModelInfo>("id = \"meta-llama/Llama-3.1-70B\"\ncontext_window = 131072\nowned_by = \"meta\"").unwrap(),
+    );
+    let base = start(config);
+    let (status, model) = get(&base, "/v1/models/meta-llama/Llama-3.1-70B").await;
+    assert_eq!(status, 200);
+    assert_eq!(model["id"], "meta-llama/Llama-3.1-70B");
+    assert_eq!(model["owned_by"], "meta");
+}
+
+#[actix_rt::test]
+async fn unknown_ids_get_an_openai_404() {
+    let base = start(unpaced_config());
+    let (status, body) = get(&base, "/v1/models/gpt-9").await;
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["code"], "model_not_found");
+    assert_eq!(body["error"]["param"], serde_json::Value::Null);
+    assert_eq!(
+        body["error"]["message"],
+        "The model `gpt-9` does not exist or you do not have access to it."
+    );
+}
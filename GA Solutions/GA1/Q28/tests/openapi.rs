@@ -0,0 +1,396 @@
+//! The OpenAPI document: it is served, it resolves, the mock's real replies
+//! validate against its schemas, every operation it lists is routed, and
+//! every route served is listed.
+#![cfg(feature = "openapi")]
+
+mod common;
+
+use serde_json::{json, Value};
+
+use common::{client, parse_events, post, start, unpaced_config, with_profile};
+use streaming_llm_api::openapi;
+use streaming_llm_api::stubs::{ContentMode, Quirk, ResponseProfile};
+
+/// The errors found validating `value` against `schema`, the subset of JSON
+/// Schema the document uses.
+fn validate(doc: &Value, schema: &Value, value: &Value, at: &str, errors: &mut Vec<String>) {
+    if let Some(target) = schema["$ref"].as_str() {
+        let name = target.strip_prefix("#/components/schemas/").unwrap();
+        return validate(doc, &doc["components"]["schemas"][name], value, at, errors);
+    }
+    if let Some(options) = schema["anyOf"].as_array().or(schema["oneOf"].as_array()) {
+        let matching = options
+            .iter()
+            .filter(|option| {
+                let mut inner = Vec::new();
+                validate(doc, option, value, at, &mut inner);
+                inner.is_empty()
+            })
+            .count();
+        let expected = if schema["oneOf"].is_array() { matching == 1 } else { matching > 0 };
+        if !expected {
+            errors.push(format!("{}: {} of {} options match {}", at, matching, options.len(), value));
+        }
+        return;
+    }
+    if let Some(kind) = schema["type"].as_str() {
+        let ok = match kind {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            other => panic!("unknown type {}", other),
+        };
+        if !ok {
+            errors.push(format!("{}: expected {}, got {}", at, kind, value));
+            return;
+        }
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        if !values.contains(value) {
+            errors.push(format!("{}: {} is not one of {:?}", at, value, values));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        for name in schema["required"].as_array().into_iter().flatten() {
+            if !object.contains_key(name.as_str().unwrap()) {
+                errors.push(format!("{}: missing {}", at, name));
+            }
+        }
+        for (name, field) in object {
+            let at = format!("{}.{}", at, name);
+            match &schema["properties"][name] {
+                Value::Null if schema["additionalProperties"] == json!(false) => errors.push(format!("{}: not in the schema", at)),
+                Value::Null => {}
+                property => validate(doc, property, field, &at, errors),
+            }
+        }
+    }
+    if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in values.iter().enumerate() {
+            validate(doc, items, item, &format!("{}[{}]", at, i), errors);
+        }
+    }
+}
+
+#[track_caller]
+fn assert_valid(doc: &Value, schema: &str, value: &Value) {
+    let mut errors = Vec::new();
+    validate(doc, &json!({"$ref": format!("#/components/schemas/{}", schema)}), value, schema, &mut errors);
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+/// Every `$ref` under `value`.
+fn refs<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(target)) = object.get("$ref") {
+                out.push(target);
+            }
+            object.values().for_each(|v| refs(v, out));
+        }
+        Value::Array(values) => values.iter().for_each(|v| refs(v, out)),
+        _ => {}
+    }
+}
+
+async fn document(base: &str) -> Value {
+    client().get(format!("{}/openapi.json", base)).send().await.unwrap().json().await.unwrap()
+}
+
+fn chat(prompt: &str) -> Value {
+    json!({"model": "gpt-4o", "messages": [{"role": "user", "content": prompt}], "stream": true})
+}
+
+#[actix_rt::test]
+async fn document_is_served_and_resolves() {
+    let base = start(unpaced_config());
+    let doc = document(&base).await;
+    assert_eq!(doc, openapi::document());
+    assert_eq!(doc["openapi"], "3.1.0");
+    assert_eq!(doc["info"]["version"], env!("CARGO_PKG_VERSION"));
+
+    let mut targets = Vec::new();
+    refs(&doc, &mut targets);
+    assert!(!targets.is_empty());
+    for target in targets {
+        let path: Vec<&str> = target.strip_prefix("#/").unwrap().split('/').collect();
+        let resolved = path.iter().fold(&doc, |node, key| &node[*key]);
+        assert!(resolved.is_object(), "dangling {}", target);
+    }
+
+    let mut ids: Vec<&str> = doc["paths"]
+        .as_object()
+        .unwrap()
+        .values()
+        .flat_map(|item| item.as_object().unwrap().values())
+        .map(|operation| operation["operationId"].as_str().unwrap())
+        .collect();
+    let count = ids.len();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), count, "operation ids must be unique");
+}
+
+#[test]
+fn reply_schemas_are_closed() {
+    let doc = openapi::document();
+    let usage = json!({"prompt_tokens": 1, "completion_tokens": 2, "total_tokens": 3});
+    assert_valid(&doc, "Usage", &usage);
+    let mut errors = Vec::new();
+    let mut extra = usage.clone();
+    extra["cached_tokens"] = json!(0);
+    validate(&doc, &json!({"$ref": "#/components/schemas/Usage"}), &extra, "Usage", &mut errors);
+    assert_eq!(errors, ["Usage.cached_tokens: not in the schema"]);
+}
+
+#[actix_rt::test]
+async fn docs_page_loads_the_document() {
+    let base = start(unpaced_config());
+    let response = client().get(format!("{}/docs", base)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/html"));
+    assert!(response.text().await.unwrap().contains("url: \"/openapi.json\""));
+}
+
+#[actix_rt::test]
+async fn streamed_chunks_match_the_schema() {
+    let doc = openapi::document();
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        citations: true,
+        quirks: vec![Quirk::ContentAfterToolCalls],
+        content_mode: Some(ContentMode::Both),
+        ..ResponseProfile::default()
+    }));
+    let mut body = chat("hello");
+    body["stream_options"] = json!({"include_usage": true});
+    body["service_tier"] = json!("flex");
+    let events = parse_events(&post(&base, body).await.text().await.unwrap());
+    let chunks: Vec<Value> = events
+        .iter()
+        .filter(|e| e.event.is_none() && e.data != "[DONE]")
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect();
+    assert!(chunks.len() > 2);
+    for chunk in &chunks {
+        assert_valid(&doc, "ChatCompletionChunk", chunk);
+    }
+
+    let mut body = chat("hello");
+    body["modalities"] = json!(["text", "audio"]);
+    body["audio"] = json!({"voice": "alloy", "format": "pcm16"});
+    for event in parse_events(&post(&base, body).await.text().await.unwrap()) {
+        if event.data != "[DONE]" {
+            assert_valid(&doc, "ChatCompletionChunk", &serde_json::from_str(&event.data).unwrap());
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn whole_replies_and_errors_match_the_schema() {
+    let doc = openapi::document();
+    let base = start(unpaced_config());
+    let mut body = chat("hello");
+    body.as_object_mut().unwrap().remove("stream");
+    body["store"] = json!(true);
+    body["metadata"] = json!({"run": "a"});
+    let reply: Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_valid(&doc, "ChatCompletion", &reply);
+
+    let rejected = post(&base, json!({"messages": "nope"})).await;
+    assert_eq!(rejected.status(), 400);
+    assert_valid(&doc, "ErrorEnvelope", &rejected.json().await.unwrap());
+
+    if cfg!(feature = "endpoints") {
+        let get = |path: String| async move { client().get(path).send().await.unwrap().json::<Value>().await.unwrap() };
+        assert_valid(&doc, "ModelList", &get(format!("{}/v1/models", base)).await);
+        assert_valid(&doc, "ChatCompletionList", &get(format!("{}/v1/chat/completions", base)).await);
+        let id = reply["id"].as_str().unwrap();
+        assert_valid(&doc, "ChatCompletion", &get(format!("{}/v1/chat/completions/{}", base, id)).await);
+        assert_valid(&doc, "StoredMessageList", &get(format!("{}/v1/chat/completions/{}/messages", base, id)).await);
+        assert_valid(&doc, "InternalStats", &get(format!("{}/v1/internal/stats", base)).await);
+    }
+}
+
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn provider_replies_match_the_schema() {
+    let doc = openapi::document();
+    let base = start(unpaced_config());
+
+    let whole: Value = client()
+        .post(format!("{}/v1/responses", base))
+        .json(&json!({"model": "gpt-4o", "input": "hello"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_valid(&doc, "Response", &whole);
+    let streamed = client()
+        .post(format!("{}/v1/responses", base))
+        .json(&json!({"model": "gpt-4o", "input": "hello", "stream": true}))
+        .send()
+        .await
+        .unwrap();
+    for event in parse_events(&streamed.text().await.unwrap()) {
+        assert_valid(&doc, "ResponseStreamEvent", &serde_json::from_str(&event.data).unwrap());
+    }
+
+    for format in ["float", "base64"] {
+        let embeddings: Value = client()
+            .post(format!("{}/v1/embeddings", base))
+            .json(&json!({"input": ["hello", "world"], "dimensions": 4, "encoding_format": format}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_valid(&doc, "EmbeddingList", &embeddings);
+    }
+
+    let cohere = client()
+        .post(format!("{}/v1/chat", base))
+        .json(&json!({"message": "hello", "stream": true}))
+        .send()
+        .await
+        .unwrap();
+    for line in cohere.text().await.unwrap().lines() {
+        assert_valid(&doc, "CohereStreamEvent", &serde_json::from_str(line).unwrap());
+    }
+    let refused = client().post(format!("{}/v1/chat", base)).json(&json!({"message": "hello"})).send().await.unwrap();
+    assert_eq!(refused.status(), 400);
+    assert_valid(&doc, "CohereError", &refused.json().await.unwrap());
+
+    let gemini: Value = client()
+        .post(format!("{}/v1beta/models/gemini-2.0-flash:streamGenerateContent", base))
+        .json(&json!({"contents": [{"role": "user", "parts": [{"text": "hello"}]}]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    for piece in gemini.as_array().unwrap() {
+        assert_valid(&doc, "GeminiResponse", piece);
+    }
+    let refused = client()
+        .post(format!("{}/v1beta/models/gemini-2.0-flash:streamGenerateContent", base))
+        .json(&json!({"contents": []}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), 400);
+    assert_valid(&doc, "GeminiErrorEnvelope", &refused.json().await.unwrap());
+}
+
+#[actix_rt::test]
+async fn every_operation_is_routed() {
+    let base = start(unpaced_config());
+    let doc = document(&base).await;
+    for (path, item) in doc["paths"].as_object().unwrap() {
+        for method in item.as_object().unwrap().keys() {
+            // The profiler samples for the whole request.
+            if path == "/debug/pprof/profile" {
+                continue;
+            }
+            let url = format!(
+                "{}{}",
+                base,
+                path.replace("{model}", "gpt-4o").replace("{completion_id}", "chatcmpl-missing")
+            );
+            let request = match method.as_str() {
+                "get" => client().get(&url),
+                "post" => client().post(&url).json(&json!({})),
+                "delete" => client().delete(&url),
+                other => panic!("unexpected method {}", other),
+            };
+            let response = request.send().await.unwrap();
+            let status = response.status();
+            // An unrouted request gets actix's bare 404; a handler's 404 says why.
+            if status == 404 {
+                let body: Value = response.json().await.unwrap_or(Value::Null);
+                assert!(body["error"].is_object(), "{} {} is not routed", method, path);
+            }
+            assert_ne!(status, 405, "{} {}", method, path);
+        }
+    }
+}
+
+/// Every `#[get("...")]`-style route attribute under `dir`, as its method
+/// and path.
+fn route_attributes(dir: &std::path::Path, out: &mut Vec<(String, String)>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            route_attributes(&path, out);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            for line in std::fs::read_to_string(&path).unwrap().lines() {
+                for method in ["get", "post", "put", "patch", "delete"] {
+                    if let Some(rest) = line.trim().strip_prefix(&format!("#[{}(\"", method)) {
+                        out.push((method.to_string(), rest[..rest.find('"').unwrap()].to_string()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `path` with each `{parameter}` replaced by `with`.
+fn fill(path: &str, with: &str) -> String {
+    let mut out = String::new();
+    let mut rest = path;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        out.push_str(with);
+        rest = &rest[open + rest[open..].find('}').unwrap() + 1..];
+    }
+    out + rest
+}
+
+#[actix_rt::test]
+async fn every_route_is_documented() {
+    let base = start(unpaced_config());
+    let doc = document(&base).await;
+    let documented: Vec<(String, String)> = doc["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, item)| item.as_object().unwrap().keys().map(move |method| (method.clone(), fill(path, "{}"))))
+        .collect();
+    let mut routes = Vec::new();
+    route_attributes(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut routes);
+    assert!(routes.len() > 10, "{:?}", routes);
+    for (method, path) in routes {
+        if documented.contains(&(method.clone(), fill(&path, "{}"))) {
+            continue;
+        }
+        // Undocumented is fine only for a route this build leaves out.
+        let url = format!("{}{}", base, fill(&path, "missing"));
+        let request = match method.as_str() {
+            "get" => client().get(&url),
+            "delete" => client().delete(&url),
+            other => client().request(other.to_uppercase().parse().unwrap(), &url).json(&json!({})),
+        };
+        let response = request.send().await.unwrap();
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        let routed = status != 405 && (status != 404 || body["error"].is_object());
+        assert!(!routed, "{} {} is served but not documented", method, path);
+    }
+}
@@ -0,0 +1,200 @@
+//! `OpenAI-Organization` / `OpenAI-Project`: validation against the
+//! configured organizations, the provider's 401s, and usage scoped by them.
+
+mod common;
+
+use common::{client, start, unpaced_config};
+use streaming_llm_api::check::check_text;
+use streaming_llm_api::config::Config;
+use streaming_llm_api::keys::{ApiKey, Tier};
+use streaming_llm_api::orgs::Organization;
+
+fn org_config() -> Config {
+    let mut config = unpaced_config();
+    config.organizations = vec![
+        Organization {
+            id: "org-acme".to_string(),
+            projects: vec!["proj_web".to_string(), "proj_batch".to_string()],
+        },
+        Organization {
+            id: "org-other".to_string(),
+            projects: vec!["proj_other".to_string()],
+        },
+    ];
+    config.keys = vec![
+        ApiKey {
+            key: "sk-acme".to_string(),
+            tier: Tier::Pro,
+            organization: Some("org-acme".to_string()),
+            project: None,
+            models: None,
+            capabilities: None,
+            token_quota: None,
+        },
+        ApiKey {
+            key: "sk-proj-web".to_string(),
+            tier: Tier::Pro,
+            organization: Some("org-acme".to_string()),
+            project: Some("proj_web".to_string()),
+            models: None,
+            capabilities: None,
+            token_quota: None,
+        },
+    ];
+    config.tiers.pro.tokens_per_sec = None;
+    config
+}
+
+async fn send(base: &str, key: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+    let mut request = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .bearer_auth(key)
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true}));
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    request.send().await.unwrap()
+}
+
+fn header<'a>(resp: &'a reqwest::Response, name: &str) -> Option<&'a str> {
+    resp.headers().get(name).map(|v| v.to_str().unwrap())
+}
+
+async fn error_of(resp: reqwest::Response) -> (u16, serde_json::Value) {
+    let status = resp.status().as_u16();
+    (status, resp.json::<serde_json::Value>().await.unwrap()["error"].clone())
+}
+
+#[actix_rt::test]
+async fn matching_headers_are_served_and_echoed() {
+    let base = start(org_config());
+
+    let resp = send(&base, "sk-acme", &[("OpenAI-Organization", "org-acme"), ("OpenAI-Project", "proj_batch")]).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(header(&resp, "openai-organization"), Some("org-acme"));
+    assert_eq!(header(&resp, "openai-project"), Some("proj_batch"));
+
+    // Without headers, the key's own organization and project apply.
+    let resp = send(&base, "sk-proj-web", &[]).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(header(&resp, "openai-organization"), Some("org-acme"));
+    assert_eq!(header(&resp, "openai-project"), Some("proj_web"));
+}
+
+#[actix_rt::test]
+async fn mismatches_get_the_providers_401s() {
+    let base = start(org_config());
+
+    for org in ["org-other", "org-missing"] {
+        let (status, error) = error_of(send(&base, "sk-acme", &[("OpenAI-Organization", org)]).await).await;
+        assert_eq!(status, 401);
+        assert_eq!(error["code"], "mismatched_organization");
+        assert_eq!(error["message"], "OpenAI-Organization header should match organization for API key");
+        assert_eq!(error["type"], "authentication_error");
+    }
+
+    // A project of another organization, and another project than the key's.
+    for (key, project) in [("sk-acme", "proj_other"), ("sk-proj-web", "proj_batch")] {
+        let (status, error) = error_of(send(&base, key, &[("OpenAI-Project", project)]).await).await;
+        assert_eq!(status, 401, "{} {}", key, project);
+        assert_eq!(error["code"], "mismatched_project");
+        assert_eq!(error["message"], "OpenAI-Project header should match project for API key");
+    }
+}
+
+#[actix_rt::test]
+async fn rate_limits_are_charged_per_organization_and_project() {
+    let mut config = org_config();
+    config.rate_limits.requests_per_min = Some(10);
+    let base = start(config);
+    let remaining = |resp: &reqwest::Response| header(resp, "x-ratelimit-remaining-requests").unwrap().to_string();
+
+    // Both keys of org-acme spend the same project budget.
+    let web = [("OpenAI-Project", "proj_web")];
+    assert_eq!(remaining(&send(&base, "sk-acme", &web).await), "9");
+    assert_eq!(remaining(&send(&base, "sk-proj-web", &[]).await), "8");
+    assert_eq!(remaining(&send(&base, "sk-acme", &[("OpenAI-Project", "proj_batch")]).await), "9");
+}
+
+#[actix_rt::test]
+async fn only_the_keys_own_scopes_are_accepted() {
+    let mut config = org_config();
+    config.rate_limits.requests_per_min = Some(10);
+    let base = start(config.clone());
+    // An unlisted key belongs to no organization, so naming one would charge
+    // usage to an account of the client's choosing.
+    for headers in [[("OpenAI-Organization", "org-acme")], [("OpenAI-Project", "proj_web")]] {
+        let (status, _) = error_of(send(&base, "sk-any", &headers).await).await;
+        assert_eq!(status, 401, "{:?}", headers);
+    }
+    let resp = send(&base, "sk-any", &[]).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(header(&resp, "openai-organization"), None);
+
+    // Without [[organizations]], a key's own organization and project are
+    // still all it may name.
+    config.organizations.clear();
+    let base = start(config);
+    let resp = send(&base, "sk-proj-web", &[("OpenAI-Organization", "org-acme"), ("OpenAI-Project", "proj_web")]).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(header(&resp, "openai-project"), Some("proj_web"));
+    for (key, name, value) in [("sk-acme", "OpenAI-Organization", "org-anything"), ("sk-acme", "OpenAI-Project", "proj_web")] {
+        let (status, error) = error_of(send(&base, key, &[(name, value)]).await).await;
+        assert_eq!(status, 401, "{} {}", name, value);
+        assert!(error["code"].as_str().unwrap().starts_with("mismatched_"));
+    }
+}
+
+#[test]
+fn check_reports_keys_outside_the_organizations() {
+    let text = r#"
+[[organizations]]
+id = "org-acme"
+projects = ["proj_web"]
+
+[[keys]]
+key = "sk-a"
+tier = "free"
+organization = "org-nope"
+
+[[keys]]
+key = "sk-b"
+tier = "free"
+organization = "org-acme"
+project = "proj_nope"
+"#;
+    let messages: Vec<String> = check_text(text).iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        [
+            "line 6: error: keys[0].organization 'org-nope' is not listed in [[organizations]]",
+            "line 11: error: keys[1].project 'proj_nope' is not a project of its organization",
+        ]
+    );
+}
+
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn provider_endpoints_check_and_report_the_scope() {
+    let base = start(org_config());
+    let endpoints = [
+        ("/v1/responses", serde_json::json!({"input": "hi"})),
+        ("/v1/chat", serde_json::json!({"message": "hi", "stream": true})),
+        ("/v1beta/models/gemini-2.0-flash:streamGenerateContent", serde_json::json!({"contents": [{"parts": [{"text": "hi"}]}]})),
+    ];
+    for (path, body) in endpoints {
+        let request = |org: &str| {
+            client()
+                .post(format!("{}{}", base, path))
+                .bearer_auth("sk-proj-web")
+                .header("OpenAI-Organization", org)
+                .json(&body)
+                .send()
+        };
+        assert_eq!(request("org-other").await.unwrap().status(), 401, "{}", path);
+        let accepted = request("org-acme").await.unwrap();
+        assert_eq!(accepted.status(), 200, "{}", path);
+        assert_eq!(header(&accepted, "openai-organization"), Some("org-acme"), "{}", path);
+        assert_eq!(header(&accepted, "openai-project"), Some("proj_web"), "{}", path);
+    }
+}
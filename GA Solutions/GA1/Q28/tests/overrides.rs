@@ -0,0 +1,79 @@
+//! `X-Mock-*` request headers shaping individual streams.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{content_of, parse_events, start, unpaced_config};
+
+async fn post_with(base: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+    let mut req = common::client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "overrides"}], "stream": true}));
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    req.send().await.unwrap()
+}
+
+#[actix_rt::test]
+async fn chunks_header_sets_the_chunk_count() {
+    let base = start(unpaced_config());
+    let body = post_with(&base, &[("X-Mock-Chunks", "3")]).await.text().await.unwrap();
+    let events = parse_events(&body);
+    assert_eq!(events.len(), 4, "three chunks and [DONE]");
+    assert!(content_of(&events).starts_with("Regarding your prompt 'overrides'"));
+}
+
+#[actix_rt::test]
+async fn latency_header_paces_chunks() {
+    let base = start(unpaced_config());
+    let started = Instant::now();
+    let body = post_with(&base, &[("X-Mock-Chunks", "4"), ("X-Mock-Latency", "40")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(parse_events(&body).len(), 5);
+    assert!(started.elapsed() >= Duration::from_millis(160), "took {:?}", started.elapsed());
+}
+
+#[actix_rt::test]
+async fn error_after_header_injects_a_failure() {
+    let base = start(unpaced_config());
+    let body = post_with(&base, &[("X-Mock-Error-After", "2")]).await.text().await.unwrap();
+    let events = parse_events(&body);
+    assert_eq!(events.len(), 3);
+    assert!(events[2].data.contains("Injected failure"));
+
+    let resp = post_with(&base, &[("X-Mock-Error-After", "0")]).await;
+    assert_eq!(resp.status(), 500);
+}
+
+#[actix_rt::test]
+async fn finish_reason_header_adds_a_closing_chunk() {
+    let base = start(unpaced_config());
+    let body = post_with(&base, &[("X-Mock-Chunks", "2"), ("X-Mock-Finish-Reason", "length")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    let events = parse_events(&body);
+    assert_eq!(events.len(), 4);
+    let closing: serde_json::Value = serde_json::from_str(&events[2].data).unwrap();
+    assert_eq!(closing["choices"][0]["finish_reason"], "length");
+    assert_eq!(closing["choices"][0]["delta"], serde_json::json!({}));
+    assert_eq!(events[3].data, "[DONE]");
+}
+
+#[actix_rt::test]
+async fn malformed_override_headers_are_rejected() {
+    let base = start(unpaced_config());
+    for (name, value) in [("X-Mock-Chunks", "0"), ("X-Mock-Latency", "fast"), ("X-Mock-Error-After", "-1")] {
+        let resp = post_with(&base, &[(name, value)]).await;
+        assert_eq!(resp.status(), 400, "{}: {}", name, value);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["error"]["param"], name);
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+}
@@ -0,0 +1,131 @@
+//! The shared pacing wheel.
+
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use common::{parse_events, post, start, with_profile};
+use streaming_llm_api::config::PacingMode;
+use streaming_llm_api::pacer::{self, Pace};
+use streaming_llm_api::stubs::ResponseProfile;
+use streaming_llm_api::tokenizer::TokenEncoding;
+use tokio::time::Instant;
+
+#[actix_rt::test]
+async fn parked_streams_wake_after_their_delay() {
+    let started = Instant::now();
+    pacer::park(Duration::from_millis(20), None).await;
+    assert!(started.elapsed() >= Duration::from_millis(20));
+    assert_eq!(pacer::parked(), 0);
+}
+
+#[actix_rt::test]
+async fn budget_releases_due_streams_in_arrival_order() {
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let started = Instant::now();
+    let tasks: Vec<_> = (0..8)
+        .map(|i| {
+            let parked = pacer::park(Duration::from_millis(5), Some(1));
+            let order = order.clone();
+            actix_rt::spawn(async move {
+                parked.await;
+                order.borrow_mut().push(i);
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(*order.borrow(), (0..8).collect::<Vec<_>>());
+    // One release per tick: the last stream waited out seven extra ticks.
+    assert!(started.elapsed() >= Duration::from_millis(5) + pacer::TICK * 7);
+}
+
+#[actix_rt::test]
+async fn scheduler_pacing_streams_complete() {
+    let mut config = with_profile(ResponseProfile {
+        chunk_delay_ms: 2,
+        chunks: 5,
+        ..ResponseProfile::default()
+    });
+    config.stream.pacing = PacingMode::Scheduler;
+    let base = start(config);
+
+    let body = serde_json::json!({"messages": [{"role": "user", "content": "paced"}], "stream": true});
+    let replies = futures::future::join_all((0..20).map(|_| post(&base, body.clone()))).await;
+    for resp in replies {
+        let events = parse_events(&resp.text().await.unwrap());
+        assert_eq!(events.len(), 6);
+        assert_eq!(events.last().unwrap().data, "[DONE]");
+    }
+}
+
+fn token_paced(chars_per_token: Option<f64>) -> ResponseProfile {
+    ResponseProfile {
+        tokens_per_sec: Some(100.0),
+        chars_per_token,
+        ..ResponseProfile::default()
+    }
+}
+
+#[test]
+fn token_pacing_waits_longer_for_denser_scripts() {
+    let pace = Pace::new(&token_paced(None), TokenEncoding::Cl100k);
+    let per_char = |text: &str| pace.before(text, false) / text.chars().count() as u32;
+    let english = per_char("The weather in Tokyo is sunny today");
+    assert_eq!(pace.before("The weather", false), Duration::from_millis(10) * pace.tokens("The weather") as u32);
+    assert!(per_char("東京の今日の天気は晴れです。気温は") >= english * 2, "{:?}", english);
+    assert!(per_char("🎉🚀🌈🔥🍣🎸🦀🌋") >= english * 4, "{:?}", english);
+}
+
+#[test]
+fn vanishing_token_rates_wait_an_hour_at_most() {
+    let profile = ResponseProfile {
+        tokens_per_sec: Some(1e-300),
+        ..ResponseProfile::default()
+    };
+    let pace = Pace::new(&profile, TokenEncoding::Cl100k);
+    assert_eq!(pace.before("The weather", false), Duration::from_secs(3600));
+}
+
+#[test]
+fn chars_per_token_calibrates_pacing_and_reply_length() {
+    let profile = token_paced(Some(1.5));
+    let pace = Pace::new(&profile, TokenEncoding::Cl100k);
+    assert_eq!(pace.tokens("東京の今日の天気"), 6);
+    assert_eq!(pace.before("東京の今日の天気", false), Duration::from_millis(60));
+    assert_eq!(profile.chars_for_tokens(100), 150);
+    assert_eq!(ResponseProfile::default().chars_for_tokens(100), 400);
+}
+
+#[test]
+fn first_chunk_delay_overrides_token_pacing() {
+    let profile = ResponseProfile {
+        first_chunk_delay_ms: Some(5),
+        ..token_paced(Some(1.0))
+    };
+    let pace = Pace::new(&profile, TokenEncoding::Cl100k);
+    assert_eq!(pace.before("東京の今日の天気", true), Duration::from_millis(5));
+    assert_eq!(pace.before("東京の今日の天気", false), Duration::from_millis(80));
+    let fixed = Pace::new(&ResponseProfile::default(), TokenEncoding::Cl100k);
+    assert_eq!(fixed.before("東京の今日の天気", false), Duration::from_millis(75));
+}
+
+#[actix_rt::test]
+async fn token_paced_streams_take_their_tokens_time() {
+    let base = start(with_profile(ResponseProfile {
+        tokens_per_sec: Some(1000.0),
+        chars_per_token: Some(1.0),
+        tokens: Some(200),
+        ..ResponseProfile::default()
+    }));
+
+    let started = Instant::now();
+    let resp = post(&base, serde_json::json!({"messages": [{"role": "user", "content": "paced"}], "stream": true})).await;
+    let events = parse_events(&resp.text().await.unwrap());
+    assert_eq!(events.last().unwrap().data, "[DONE]");
+    // 200 tokens at 1000 a second, one character each.
+    assert!(started.elapsed() >= Duration::from_millis(190), "{:?}", started.elapsed());
+}
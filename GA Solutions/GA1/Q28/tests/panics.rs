@@ -0,0 +1,82 @@
+//! Handler panics answered with a 500 error envelope instead of a dropped connection.
+
+mod common;
+
+use actix_web::middleware::from_fn;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use serde_json::Value;
+use std::net::TcpListener;
+
+use common::client;
+use streaming_llm_api::{metrics, panics};
+
+async fn labelled(req: HttpRequest) -> HttpResponse {
+    panics::label(&req, "req_42");
+    panic!("lost track of request {}", 42);
+}
+
+async fn unlabelled() -> HttpResponse {
+    panic!("too early for an id");
+}
+
+async fn poisoning() -> HttpResponse {
+    let _held = metrics::CHUNK_TIMINGS.lock();
+    panic!("while holding the timings");
+}
+
+/// Serves the routes above behind the middleware, returning the base URL.
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = HttpServer::new(|| {
+        App::new()
+            .wrap(from_fn(panics::catch))
+            .route("/labelled", web::get().to(labelled))
+            .route("/unlabelled", web::get().to(unlabelled))
+            .route("/poisoning", web::get().to(poisoning))
+            .route("/metrics", web::get().to(|| async { HttpResponse::Ok().body(metrics::prometheus()) }))
+            .route("/fine", web::get().to(|| async { HttpResponse::Ok().body("fine") }))
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_rt::spawn(server);
+    format!("http://{}", addr)
+}
+
+#[actix_rt::test]
+async fn panics_become_server_errors() {
+    let base = serve();
+    let get = |path: &str| client().get(format!("{}{}", base, path)).send();
+    let before = metrics::HANDLER_PANICS.get();
+
+    let resp = get("/labelled").await.unwrap();
+    assert_eq!(resp.status(), 500);
+    assert_eq!(resp.headers()["x-request-id"], "req_42");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "server_error");
+    assert!(body["error"]["message"].as_str().unwrap().contains("error while processing"));
+
+    let resp = get("/unlabelled").await.unwrap();
+    assert_eq!(resp.status(), 500);
+    assert!(resp.headers().get("x-request-id").is_none());
+    assert_eq!(metrics::HANDLER_PANICS.get() - before, 2);
+
+    // The worker survives, and handlers that return are untouched.
+    let resp = get("/fine").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text().await.unwrap(), "fine");
+}
+
+#[actix_rt::test]
+async fn state_locked_during_a_panic_stays_usable() {
+    let base = serve();
+    let get = |path: &str| client().get(format!("{}{}", base, path)).send();
+    assert_eq!(get("/poisoning").await.unwrap().status(), 500);
+    assert!(metrics::CHUNK_TIMINGS.is_poisoned());
+    let resp = get("/metrics").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp.text().await.unwrap().contains("mock_inter_chunk_gap_seconds"));
+    metrics::chunk_timings(Some("gpt-4o"));
+}
@@ -0,0 +1,102 @@
+//! Chunk-boundary pathologies: splitting events across writes and extreme chunk sizes.
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, with_profile};
+use streaming_llm_api::sse::split_points;
+use streaming_llm_api::stubs::{ResponseProfile, WriteSplit};
+
+fn profile(write_split: WriteSplit) -> ResponseProfile {
+    ResponseProfile {
+        chunk_delay_ms: 0,
+        write_split,
+        ..ResponseProfile::default()
+    }
+}
+
+/// The body as the client read it, one entry per read.
+async fn reads(profile: ResponseProfile, prompt: &str) -> Vec<Vec<u8>> {
+    let base = start(with_profile(profile));
+    let mut resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"prompt": prompt, "stream": true, "seed": 0}))
+        .send()
+        .await
+        .unwrap();
+    let mut reads = Vec::new();
+    while let Some(chunk) = resp.chunk().await.unwrap() {
+        reads.push(chunk.to_vec());
+    }
+    reads
+}
+
+#[test]
+fn split_points_land_inside_escapes_and_characters() {
+    let event = "data: {\"c\":\"a\\nb\\\"\"}\n\n".as_bytes();
+    let cuts = split_points(event, WriteSplit::MidEscape);
+    assert_eq!(cuts.len(), 2);
+    assert!(cuts.iter().all(|&at| event[at - 1] == b'\\'));
+
+    let event = "data: {\"c\":\"caf\u{e9} \u{1f680}\"}\n\n".as_bytes();
+    let cuts = split_points(event, WriteSplit::MidUtf8);
+    assert_eq!(cuts.len(), 2);
+    assert!(cuts.iter().all(|&at| std::str::from_utf8(&event[..at]).is_err()));
+
+    assert_eq!(split_points(b"abc", WriteSplit::EveryByte), [1, 2]);
+    assert!(split_points(b"abc", WriteSplit::Off).is_empty());
+}
+
+#[actix_rt::test]
+async fn escapes_straddle_reads_without_changing_the_body() {
+    let plain = reads(profile(WriteSplit::Off), "split").await.concat();
+    let split = reads(
+        ResponseProfile {
+            write_split_pause_ms: 20,
+            ..profile(WriteSplit::MidEscape)
+        },
+        "split",
+    )
+    .await;
+    assert!(split.iter().any(|read| read.ends_with(b"\\")), "no read ended mid-escape");
+    assert_eq!(split.concat(), plain);
+}
+
+#[actix_rt::test]
+async fn multibyte_characters_straddle_reads() {
+    let split = reads(
+        ResponseProfile {
+            write_split_pause_ms: 20,
+            ..profile(WriteSplit::MidUtf8)
+        },
+        "naïve café 🚀",
+    )
+    .await;
+    assert!(split.iter().any(|read| std::str::from_utf8(read).is_err()));
+    let body = String::from_utf8(split.concat()).unwrap();
+    assert!(content_of(&parse_events(&body)).contains("naïve café 🚀"));
+}
+
+#[actix_rt::test]
+async fn byte_at_a_time_and_one_char_chunks_still_parse() {
+    let one_char = ResponseProfile {
+        chunk_chars: Some(1),
+        tokens: Some(10),
+        ..profile(WriteSplit::EveryByte)
+    };
+    let body = String::from_utf8(reads(one_char, "tiny").await.concat()).unwrap();
+    let events = parse_events(&body);
+    assert_eq!(events.len(), 41);
+    assert_eq!(content_of(&events).chars().count(), 40);
+}
+
+#[actix_rt::test]
+async fn single_chunk_replies_carry_everything_at_once() {
+    let base = start(with_profile(ResponseProfile {
+        chunks: 1,
+        ..profile(WriteSplit::Off)
+    }));
+    let body = post(&base, serde_json::json!({"prompt": "whole", "stream": true})).await.text().await.unwrap();
+    let events = parse_events(&body);
+    assert_eq!(events.len(), 2);
+    assert!(content_of(&events).len() > 1000);
+}
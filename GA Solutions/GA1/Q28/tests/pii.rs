@@ -0,0 +1,123 @@
+//! The inbound PII filter and the capture store it reports to.
+#![cfg(feature = "recording")]
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config};
+use streaming_llm_api::config::{Config, PiiPolicy};
+use streaming_llm_api::pii::{self, PiiKind};
+
+const ALL: &[PiiKind] = &[PiiKind::Email, PiiKind::Phone, PiiKind::CreditCard];
+
+fn kinds(text: &str) -> Vec<PiiKind> {
+    pii::scan(text, ALL).into_iter().map(|(kind, _)| kind).collect()
+}
+
+fn with_policy(policy: PiiPolicy) -> Config {
+    let mut config = unpaced_config();
+    config.pii.policy = policy;
+    config
+}
+
+async fn captures(base: &str) -> Vec<serde_json::Value> {
+    let body: serde_json::Value = client()
+        .get(format!("{}/v1/internal/captures", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["data"].as_array().unwrap().clone()
+}
+
+fn chat(content: &str) -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": content}], "stream": true})
+}
+
+#[test]
+fn detects_each_kind() {
+    assert_eq!(kinds("mail jane.doe+test@example.co.uk today"), [PiiKind::Email]);
+    assert_eq!(kinds("call +1 415-555-0100 or (415) 555-0199"), [PiiKind::Phone, PiiKind::Phone]);
+    assert_eq!(kinds("card 4111 1111 1111 1111 exp 12/29"), [PiiKind::CreditCard]);
+    // Fails the Luhn check, and is too long to be a phone number.
+    assert!(kinds("order 4111111111111112").is_empty());
+    assert!(kinds("nothing to see in version 1.2.3").is_empty());
+}
+
+#[test]
+fn redaction_replaces_matches_with_placeholders() {
+    let text = "reach me at a@b.io or 415.555.0100";
+    assert_eq!(pii::redact(text, &pii::scan(text, ALL)), "reach me at [EMAIL] or [PHONE]");
+    let only_email = pii::scan(text, &[PiiKind::Email]);
+    assert_eq!(pii::redact(text, &only_email), "reach me at [EMAIL] or 415.555.0100");
+}
+
+#[actix_rt::test]
+async fn redact_policy_rewrites_the_prompt_and_records_findings() {
+    let base = start(with_policy(PiiPolicy::Redact));
+    let resp = post(&base, chat("my email is jane@example.com")).await;
+    assert_eq!(resp.status(), 200);
+    let content = content_of(&parse_events(&resp.text().await.unwrap()));
+    assert!(content.contains("my email is [EMAIL]"), "{}", content);
+    assert!(!content.contains("jane@example.com"));
+
+    let captures = captures(&base).await;
+    assert_eq!(captures.len(), 1);
+    assert_eq!(captures[0]["prompt"], "my email is [EMAIL]");
+    assert_eq!(captures[0]["rejected"], false);
+    assert_eq!(
+        captures[0]["pii"],
+        serde_json::json!([{"kind": "email", "message": 0, "start": 12, "end": 28}])
+    );
+}
+
+#[actix_rt::test]
+async fn reject_policy_refuses_without_keeping_the_value() {
+    let base = start(with_policy(PiiPolicy::Reject));
+    let resp = post(&base, chat("card 4111-1111-1111-1111")).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "pii_detected");
+    assert_eq!(body["error"]["param"], "messages");
+
+    assert_eq!(post(&base, chat("just a question")).await.status(), 200);
+
+    let recorded = captures(&base).await;
+    assert_eq!(recorded.len(), 2);
+    assert_eq!(recorded[0]["rejected"], true);
+    assert_eq!(recorded[0]["prompt"], "card [CREDIT_CARD]");
+    assert_eq!(recorded[1]["rejected"], false);
+    assert_eq!(recorded[1]["pii"], serde_json::json!([]));
+
+    let cleared = client().delete(format!("{}/v1/internal/captures", base)).send().await.unwrap();
+    assert_eq!(cleared.status(), 204);
+    assert!(captures(&base).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn audit_policy_passes_prompts_through() {
+    let base = start(with_policy(PiiPolicy::Audit));
+    let resp = post(&base, serde_json::json!({"prompt": "ring 415-555-0100", "stream": true})).await;
+    let content = content_of(&parse_events(&resp.text().await.unwrap()));
+    assert!(content.contains("415-555-0100"));
+
+    let captures = captures(&base).await;
+    assert_eq!(captures[0]["pii"][0]["kind"], "phone");
+    assert_eq!(captures[0]["prompt"], "ring [PHONE]");
+    assert_eq!(captures[0]["messages"][0]["content"], "ring [PHONE]");
+}
+
+#[actix_rt::test]
+async fn capture_limit_keeps_the_newest() {
+    let mut config = unpaced_config();
+    config.capture.limit = 2;
+    let base = start(config);
+    for prompt in ["one", "two", "three"] {
+        post(&base, chat(prompt)).await.text().await.unwrap();
+    }
+    let captures = captures(&base).await;
+    let prompts: Vec<_> = captures.iter().map(|c| c["prompt"].as_str().unwrap()).collect();
+    assert_eq!(prompts, ["two", "three"]);
+    assert_eq!(captures[1]["id"], 3);
+}
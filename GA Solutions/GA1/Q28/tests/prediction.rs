@@ -0,0 +1,119 @@
+//! Predicted outputs: `prediction` and its token counts in `usage`.
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config};
+use streaming_llm_api::diffing;
+use streaming_llm_api::pricing::UsageMeter;
+use streaming_llm_api::tokenizer::TokenEncoding;
+
+async fn completion(base: &str, prediction: Option<serde_json::Value>) -> serde_json::Value {
+    let mut body = serde_json::json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Rewrite it"}], "seed": 0});
+    if let Some(prediction) = prediction {
+        body["prediction"] = prediction;
+    }
+    client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[test]
+fn shared_runs_are_accepted_and_the_rest_rejected() {
+    let encoding = TokenEncoding::default();
+    let exact = diffing::prediction_tokens("fn main() {}", "fn main() {}", encoding);
+    assert_eq!(exact.rejected_prediction_tokens, 0);
+    assert_eq!(exact.accepted_prediction_tokens, encoding.count("fn main() {}"));
+
+    let edited = diffing::prediction_tokens("let x = 1; let y = 2;", "let x = 1; let z = 3;", encoding);
+    assert!(edited.accepted_prediction_tokens > 0);
+    assert!(edited.rejected_prediction_tokens > 0);
+
+    let unused = diffing::prediction_tokens("alpha beta", "gamma delta", encoding);
+    assert_eq!(unused.accepted_prediction_tokens, 0);
+    assert_eq!(unused.rejected_prediction_tokens, encoding.count("alpha beta"));
+}
+
+#[test]
+fn replies_are_compared_as_they_arrive() {
+    let prediction = "fn total(xs: &[u32]) -> u32 { xs.iter().sum() }";
+    let reply = "fn total(values: &[u32]) -> u64 { values.iter().map(|&v| u64::from(v)).sum() }\n";
+    let whole = diffing::prediction_tokens(prediction, reply, TokenEncoding::default());
+    assert!(whole.accepted_prediction_tokens > 0 && whole.rejected_prediction_tokens > 0, "{:?}", whole);
+    for size in [1, 3, 7] {
+        let meter = UsageMeter::start("req_1", None, &[], Some(prediction), true).unwrap();
+        let chars: Vec<char> = reply.chars().collect();
+        for piece in chars.chunks(size) {
+            meter.chunk(&piece.iter().collect::<String>());
+        }
+        assert_eq!(meter.usage().completion_tokens_details, Some(whole), "{}-char chunks", size);
+    }
+
+    // A reply that stops early leaves the rest of the prediction rejected.
+    let partial = diffing::prediction_tokens("one two three four", "one two", TokenEncoding::default());
+    assert_eq!(partial.accepted_prediction_tokens, TokenEncoding::default().count("one two"));
+    assert_eq!(partial.rejected_prediction_tokens, TokenEncoding::default().count("three four"));
+}
+
+#[actix_rt::test]
+async fn usage_reports_how_much_of_the_prediction_was_used() {
+    let base = start(unpaced_config());
+    let plain = completion(&base, None).await;
+    assert!(plain["usage"].get("completion_tokens_details").is_none());
+    let reply = plain["choices"][0]["message"]["content"].as_str().unwrap().to_string();
+    let generated = plain["usage"]["completion_tokens"].as_u64().unwrap();
+
+    let exact = completion(&base, Some(serde_json::json!({"type": "content", "content": reply}))).await;
+    let details = &exact["usage"]["completion_tokens_details"];
+    assert!(details["accepted_prediction_tokens"].as_u64().unwrap() > 0);
+    assert_eq!(details["rejected_prediction_tokens"], 0);
+    assert_eq!(exact["usage"]["completion_tokens"], generated);
+
+    // Content parts are joined like message content.
+    let parts = serde_json::json!({"type": "content", "content": [{"type": "text", "text": "zzyzx qwxq"}]});
+    let wrong = completion(&base, Some(parts)).await;
+    let details = &wrong["usage"]["completion_tokens_details"];
+    let rejected = details["rejected_prediction_tokens"].as_u64().unwrap();
+    assert_eq!(details["accepted_prediction_tokens"], 0);
+    assert!(rejected > 0);
+    // Rejected tokens are billed as completion tokens.
+    assert_eq!(wrong["usage"]["completion_tokens"].as_u64().unwrap(), generated + rejected);
+    assert_eq!(
+        wrong["usage"]["total_tokens"].as_u64().unwrap(),
+        wrong["usage"]["prompt_tokens"].as_u64().unwrap() + generated + rejected
+    );
+}
+
+#[actix_rt::test]
+async fn streams_report_prediction_tokens_in_the_usage_chunk() {
+    let base = start(unpaced_config());
+    let body = serde_json::json!({
+        "messages": [{"role": "user", "content": "Rewrite it"}],
+        "prediction": {"type": "content", "content": "Regarding your prompt"},
+        "stream": true,
+        "stream_options": {"include_usage": true},
+    });
+    let events = parse_events(&post(&base, body).await.text().await.unwrap());
+    assert!(content_of(&events).starts_with("Regarding your prompt"));
+    let usage: serde_json::Value = serde_json::from_str(&events[events.len() - 2].data).unwrap();
+    let details = &usage["usage"]["completion_tokens_details"];
+    assert!(details["accepted_prediction_tokens"].as_u64().unwrap() >= 3, "{}", usage);
+    assert_eq!(details["rejected_prediction_tokens"], 0);
+}
+
+#[actix_rt::test]
+async fn unknown_prediction_types_are_rejected() {
+    let base = start(unpaced_config());
+    let body = serde_json::json!({
+        "messages": [{"role": "user", "content": "hi"}],
+        "prediction": {"type": "diff", "content": "hi"},
+        "stream": true,
+    });
+    assert_eq!(post(&base, body).await.status(), 400);
+}
@@ -0,0 +1,87 @@
+//! Provider presets selected by model name.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{client, start};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::presets;
+
+fn request(model: &str) -> serde_json::Value {
+    serde_json::json!({"model": model, "messages": [{"role": "user", "content": "preset"}], "stream": true})
+}
+
+#[test]
+fn every_preset_has_a_usable_profile() {
+    for preset in presets::PRESETS {
+        let profile = preset.profile();
+        assert!(profile.chunk_chars.unwrap() > 0, "{}", preset.name);
+        assert_eq!(profile.first_chunk_delay_ms, Some(preset.ttft_ms), "{}", preset.name);
+        assert!(presets::find(preset.name).is_some());
+    }
+}
+
+#[actix_rt::test]
+async fn mapped_model_gets_the_preset_pacing() {
+    let mut config = Config::default();
+    config.model_presets.insert("gpt-4o".to_string(), "groq-fast".to_string());
+    let base = start(config);
+
+    let started = Instant::now();
+    let mut resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&request("gpt-4o"))
+        .send()
+        .await
+        .unwrap();
+    let first = resp.chunk().await.unwrap().unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(150), "TTFT {:?}", started.elapsed());
+
+    let event: serde_json::Value = serde_json::from_slice(first.strip_prefix(b"data: ").unwrap().trim_ascii()).unwrap();
+    let content = event["choices"][0]["delta"]["content"].as_str().unwrap();
+    assert_eq!(content.chars().count(), 16, "four-token chunks");
+}
+
+#[actix_rt::test]
+async fn anthropic_preset_uses_anthropic_error_envelopes() {
+    let base = start(Config::default());
+
+    let resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("X-Mock-Error-After", "0")
+        .json(&request("anthropic-sonnet"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 500);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["type"], "error");
+    assert_eq!(body["error"]["type"], "api_error");
+    assert_eq!(body["error"]["message"], "Injected failure");
+
+    let body = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("X-Mock-Error-After", "1")
+        .header("X-Mock-Latency", "0")
+        .json(&request("anthropic-sonnet"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let last = body.split_terminator("\n\n").last().unwrap();
+    let data = last.strip_prefix("event: error\ndata: ").expect("named error event");
+    let error: serde_json::Value = serde_json::from_str(data).unwrap();
+    assert_eq!(error["error"]["type"], "api_error");
+}
+
+#[test]
+fn unknown_preset_names_fail_config_loading() {
+    let path = std::env::temp_dir().join(format!("presets-{}.toml", std::process::id()));
+    std::fs::write(&path, "[model_presets]\n\"gpt-4o\" = \"no-such-preset\"\n").unwrap();
+    let err = Config::from_file(path.to_str().unwrap()).err().expect("rejected");
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.to_string().contains("no-such-preset"), "{}", err);
+}
@@ -0,0 +1,143 @@
+//! Per-model pricing: `stream_options.include_usage`, estimated costs and
+//! their running total.
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::lifecycle::Usage;
+use streaming_llm_api::models::ModelRegistry;
+use streaming_llm_api::pricing::Pricing;
+
+fn chat(model: &str, include_usage: bool) -> Value {
+    json!({
+        "model": model,
+        "messages": [{"role": "user", "content": "what will this cost?"}],
+        "stream": true,
+        "stream_options": {"include_usage": include_usage},
+    })
+}
+
+async fn chunks(base: &str, body: Value) -> Vec<Value> {
+    let text = post(base, body).await.text().await.unwrap();
+    parse_events(&text)
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect()
+}
+
+#[test]
+fn costs_are_per_million_tokens() {
+    let pricing = Pricing {
+        input_per_million: 2.5,
+        output_per_million: 10.0,
+    };
+    let usage = Usage {
+        prompt_tokens: 1_000,
+        completion_tokens: 500,
+        total_tokens: 1_500,
+        completion_tokens_details: None,
+    };
+    assert!((pricing.cost(&usage) - 0.0075).abs() < 1e-12);
+    let gpt4o = ModelRegistry::new(&[]).get("gpt-4o").unwrap().pricing;
+    assert_eq!(gpt4o, Some(pricing));
+}
+
+// Without the tokenizer, counts are estimates rounded up piece by piece.
+#[cfg(feature = "tokenizer")]
+#[test]
+fn words_split_across_chunks_are_counted_once() {
+    let models = ModelRegistry::new(&[]);
+    let model = models.get("gpt-4o").unwrap();
+    let reply = "Internationalization  matters,\n\nespecially for 12345 tokens. ";
+    let whole = model.encoding.count(reply);
+    for size in [1, 2, 3, 7] {
+        let meter = streaming_llm_api::pricing::UsageMeter::start("req_1", Some(model), &[], None, true).unwrap();
+        let chars: Vec<char> = reply.chars().collect();
+        for piece in chars.chunks(size) {
+            meter.chunk(&piece.iter().collect::<String>());
+        }
+        assert_eq!(meter.usage().completion_tokens, whole, "chunks of {}", size);
+    }
+}
+
+#[actix_rt::test]
+async fn the_usage_chunk_carries_the_estimate() {
+    let base = start(unpaced_config());
+    let sent = chunks(&base, chat("gpt-4o", true)).await;
+    let last = sent.last().unwrap();
+    assert_eq!(last["choices"], json!([]));
+    let usage = &last["usage"];
+    let (prompt, completion) = (usage["prompt_tokens"].as_f64().unwrap(), usage["completion_tokens"].as_f64().unwrap());
+    assert!(completion > 0.0);
+    let expected = (prompt * 2.5 + completion * 10.0) / 1e6;
+    assert!((last["x_mock"]["estimated_cost_usd"].as_f64().unwrap() - expected).abs() < 1e-12);
+    // Only the usage chunk has usage.
+    assert!(sent[..sent.len() - 1].iter().all(|c| c.get("usage").is_none()));
+
+    let without = chunks(&base, chat("gpt-4o", false)).await;
+    assert!(without.iter().all(|c| c.get("usage").is_none() && c.get("x_mock").is_none()));
+}
+
+#[actix_rt::test]
+async fn unpriced_models_report_usage_without_a_cost() {
+    let mut config: Config = unpaced_config();
+    config.models = toml::from_str::<Config>("[[models]]\nid = \"in-house\"\ncontext_window = 4096\n").unwrap().models;
+    let base = start(config);
+    let sent = chunks(&base, chat("in-house", true)).await;
+    let last = sent.last().unwrap();
+    assert!(last["usage"]["total_tokens"].as_u64().unwrap() > 0);
+    assert!(last.get("x_mock").is_none());
+    // The reply itself is unchanged.
+    let plain = post(&base, chat("in-house", false)).await.text().await.unwrap();
+    let with_usage = post(&base, chat("in-house", true)).await.text().await.unwrap();
+    assert_eq!(content_of(&parse_events(&plain)), content_of(&parse_events(&with_usage)));
+}
+
+#[actix_rt::test]
+async fn json_replies_and_the_model_list_show_prices() {
+    let mut config: Config = unpaced_config();
+    config.models = toml::from_str::<Config>(
+        "[[models]]\nid = \"priced\"\ncontext_window = 4096\npricing = { input_per_million = 1.0, output_per_million = 2.0 }\n",
+    )
+    .unwrap()
+    .models;
+    let base = start(config);
+    let body = json!({"model": "priced", "messages": [{"role": "user", "content": "quote"}]});
+    let reply: Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let usage = &reply["usage"];
+    let expected = (usage["prompt_tokens"].as_f64().unwrap() + 2.0 * usage["completion_tokens"].as_f64().unwrap()) / 1e6;
+    assert!((reply["x_mock"]["estimated_cost_usd"].as_f64().unwrap() - expected).abs() < 1e-12);
+
+    #[cfg(feature = "endpoints")]
+    {
+        let model: Value = client().get(format!("{}/v1/models/priced", base)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(model["pricing"], json!({"input_per_million": 1.0, "output_per_million": 2.0}));
+    }
+}
+
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn stats_total_the_estimates() {
+    let base = start(unpaced_config());
+    let stats = || async {
+        let stats: Value = client().get(format!("{}/v1/internal/stats", base)).send().await.unwrap().json().await.unwrap();
+        stats["requests"]["estimated_cost_usd"].as_f64().unwrap()
+    };
+    let before = stats().await;
+    let sent = chunks(&base, chat("gpt-4", true)).await;
+    let cost = sent.last().unwrap()["x_mock"]["estimated_cost_usd"].as_f64().unwrap();
+    // Other tests in this binary add to the same total concurrently.
+    assert!(stats().await - before >= cost - 1e-9);
+}
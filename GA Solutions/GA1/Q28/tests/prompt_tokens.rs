@@ -0,0 +1,98 @@
+//! Prompt token accounting: message framing, `name` fields and images.
+#![cfg(feature = "tokenizer")]
+
+mod common;
+
+use base64::Engine;
+use common::{client, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::chat::Message;
+use streaming_llm_api::image::{self, Detail};
+use streaming_llm_api::tokenizer::TokenEncoding;
+
+fn messages(value: Value) -> Vec<Message> {
+    serde_json::from_value(value).unwrap()
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    bytes.extend_from_slice(&width.to_be_bytes());
+    bytes.extend_from_slice(&height.to_be_bytes());
+    bytes.extend_from_slice(&[8, 6, 0, 0, 0]);
+    bytes
+}
+
+fn data_url(bytes: &[u8]) -> String {
+    format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+#[test]
+fn names_cost_their_text_plus_one() {
+    let plain = messages(json!([{"role": "user", "content": "hello world"}]));
+    let named = messages(json!([{"role": "user", "content": "hello world", "name": "alice"}]));
+    let encoding = TokenEncoding::Cl100k;
+    assert_eq!(encoding.count_messages(&named), encoding.count_messages(&plain) + 1 + encoding.count("alice"));
+}
+
+#[test]
+fn images_are_billed_by_tile() {
+    // The worked examples from OpenAI's vision guide.
+    assert_eq!(image::tokens((1024, 1024), Detail::High), 765);
+    assert_eq!(image::tokens((2048, 4096), Detail::High), 1105);
+    assert_eq!(image::tokens((4096, 8192), Detail::Low), 85);
+    assert_eq!(image::tokens((512, 512), Detail::Auto), 255);
+    assert_eq!(image::tokens((100, 3000), Detail::High), 85 + 170 * 4);
+}
+
+#[test]
+fn image_sizes_are_read_from_data_urls() {
+    assert_eq!(image::data_url_size(&data_url(&png(640, 480))), Some((640, 480)));
+    assert_eq!(image::data_url_size("https://example.com/cat.png"), None);
+    assert_eq!(image::data_url_size("data:image/png;base64,bm90IGFuIGltYWdl"), None);
+
+    let gif = b"GIF89a\x40\x01\xf0\x00";
+    assert_eq!(image::dimensions(gif), Some((320, 240)));
+    let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00];
+    jpeg.extend_from_slice(&[0xff, 0xc0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20, 0x03]);
+    assert_eq!(image::dimensions(&jpeg), Some((800, 600)));
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+    webp.extend_from_slice(&[0xff, 0x03, 0x00, 0x2b, 0x01, 0x00]);
+    assert_eq!(image::dimensions(&webp), Some((1024, 300)));
+}
+
+#[test]
+fn image_parts_count_alongside_text() {
+    let encoding = TokenEncoding::O200k;
+    let text_only = messages(json!([{"role": "user", "content": [{"type": "text", "text": "what is this?"}]}]));
+    let with_images = messages(json!([{"role": "user", "content": [
+        {"type": "text", "text": "what is this?"},
+        {"type": "image_url", "image_url": {"url": data_url(&png(1024, 1024))}},
+        {"type": "image_url", "image_url": {"url": "https://example.com/cat.png", "detail": "low"}},
+    ]}]));
+    assert_eq!(encoding.count_messages(&with_images), encoding.count_messages(&text_only) + 765 + 85);
+    // Remote images are not fetched, and count as 1024×1024.
+    let remote = messages(json!([{"role": "user", "content": [{"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}]}]));
+    assert_eq!(encoding.count_messages(&remote), 3 + 1 + 765 + 3);
+}
+
+#[actix_rt::test]
+async fn usage_reports_the_same_count() {
+    let base = start(unpaced_config());
+    let body = json!({"model": "gpt-4o", "messages": [
+        {"role": "system", "content": "Describe images.", "name": "narrator"},
+        {"role": "user", "content": [{"type": "text", "text": "and this?"}, {"type": "image_url", "image_url": {"url": data_url(&png(2048, 4096))}}]},
+    ]});
+    let expected = TokenEncoding::O200k.count_messages(&messages(body["messages"].clone()));
+    let response: Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["usage"]["prompt_tokens"], expected);
+    assert!(expected > 1105);
+}
@@ -0,0 +1,48 @@
+//! Admission queue: service rate, depth and `x_mock.queue_ms` reporting.
+
+mod common;
+
+use common::{parse_events, post, start, unpaced_config};
+
+fn request() -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": "queued"}], "stream": true})
+}
+
+#[actix_rt::test]
+async fn spikes_queue_then_overflow_with_503() {
+    let mut config = unpaced_config();
+    config.queue.admissions_per_sec = Some(10.0);
+    config.queue.depth = 2;
+    let base = start(config);
+
+    let replies = futures::future::join_all((0..4).map(|_| post(&base, request()))).await;
+    let mut queue_ms = Vec::new();
+    let mut rejected = 0;
+    for resp in replies {
+        if resp.status() == 503 {
+            let body: serde_json::Value = resp.json().await.unwrap();
+            assert_eq!(body["error"]["type"], "server_error");
+            rejected += 1;
+            continue;
+        }
+        let events = parse_events(&resp.text().await.unwrap());
+        let first: serde_json::Value = serde_json::from_str(&events[0].data).unwrap();
+        let second: serde_json::Value = serde_json::from_str(&events[1].data).unwrap();
+        assert!(second.get("x_mock").is_none(), "only the first chunk reports queueing");
+        queue_ms.push(first["x_mock"]["queue_ms"].as_u64().unwrap());
+    }
+    queue_ms.sort();
+
+    assert_eq!(rejected, 1, "depth 2 turns the fourth simultaneous request away");
+    assert!(queue_ms[0] < 50, "first request starts immediately: {:?}", queue_ms);
+    assert!(queue_ms[1] >= 80, "second waits one service interval: {:?}", queue_ms);
+    assert!(queue_ms[2] >= 180, "third waits two: {:?}", queue_ms);
+}
+
+#[actix_rt::test]
+async fn queue_is_off_by_default() {
+    let base = start(unpaced_config());
+    let events = parse_events(&post(&base, request()).await.text().await.unwrap());
+    let first: serde_json::Value = serde_json::from_str(&events[0].data).unwrap();
+    assert!(first.get("x_mock").is_none());
+}
@@ -0,0 +1,72 @@
+//! Provider quirk modes that bend the stream shape clients must parse.
+
+mod common;
+
+use common::{content_of, parse_events, post, start, unpaced_config, with_profile};
+use streaming_llm_api::stubs::{Quirk, ResponseProfile};
+
+fn chunks(events: &[common::Event]) -> Vec<serde_json::Value> {
+    events
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect()
+}
+
+async fn stream(quirks: Vec<Quirk>) -> Vec<common::Event> {
+    let config = with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        chunks: 3,
+        quirks,
+        ..ResponseProfile::default()
+    });
+    let base = start(config);
+    let body = post(&base, serde_json::json!({"prompt": "quirks", "stream": true, "seed": 0}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    parse_events(&body)
+}
+
+#[actix_rt::test]
+async fn conformant_streams_send_the_role_once() {
+    let base = start(unpaced_config());
+    let body = post(&base, serde_json::json!({"prompt": "roles", "stream": true})).await.text().await.unwrap();
+    let chunks = chunks(&parse_events(&body));
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    assert!(chunks[1..].iter().all(|c| c["choices"][0]["delta"].get("role").is_none()));
+}
+
+#[actix_rt::test]
+async fn missing_role_drops_it_from_the_first_delta() {
+    let events = stream(vec![Quirk::MissingRole]).await;
+    assert!(chunks(&events).iter().all(|c| c["choices"][0]["delta"].get("role").is_none()));
+}
+
+#[actix_rt::test]
+async fn empty_deltas_follow_every_chunk() {
+    let plain = stream(Vec::new()).await;
+    let events = stream(vec![Quirk::EmptyDeltas]).await;
+    let chunks = chunks(&events);
+    assert_eq!(chunks.len(), 6);
+    for pair in chunks.chunks(2) {
+        assert!(pair[0]["choices"][0]["delta"]["content"].is_string());
+        assert_eq!(pair[1]["choices"][0]["delta"], serde_json::json!({}));
+    }
+    assert_eq!(content_of(&events), content_of(&plain));
+}
+
+#[actix_rt::test]
+async fn empty_choices_and_tool_calls_precede_the_content() {
+    let events = stream(vec![Quirk::EmptyChoices, Quirk::ContentAfterToolCalls]).await;
+    let chunks = chunks(&events);
+    assert_eq!(chunks[0]["choices"], serde_json::json!([]));
+    let call = &chunks[1]["choices"][0]["delta"]["tool_calls"][0];
+    assert_eq!(call["type"], "function");
+    assert_eq!(call["function"]["name"], "lookup");
+    let args: serde_json::Value = serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+    assert_eq!(args["query"], "mock");
+    assert!(chunks[2]["choices"][0]["delta"]["content"].as_str().unwrap().starts_with("Regarding"));
+    assert_eq!(chunks.len(), 5);
+}
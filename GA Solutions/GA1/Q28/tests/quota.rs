@@ -0,0 +1,143 @@
+//! Token quotas: usage headers, threshold warnings and their alerts.
+
+mod common;
+
+use common::{client, start, unpaced_config};
+use serde_json::json;
+use streaming_llm_api::config::Config;
+use streaming_llm_api::keys::ApiKey;
+use streaming_llm_api::quota::Quotas;
+
+const KEYS: &str = "[[keys]]\nkey = \"sk-budget\"\ntier = \"pro\"\ntoken_quota = 1000\n\n[[keys]]\nkey = \"sk-open\"\ntier = \"pro\"\n";
+
+fn budget_config() -> Config {
+    let mut config = unpaced_config();
+    config.keys = toml::from_str::<Config>(KEYS).unwrap().keys;
+    config.tiers.pro.tokens_per_sec = None;
+    config
+}
+
+/// Sends a JSON chat that charges `max_tokens` on top of its short prompt.
+async fn send(base: &str, key: &str, max_tokens: u64) -> reqwest::Response {
+    client()
+        .post(format!("{}/v1/chat/completions", base))
+        .bearer_auth(key)
+        .header("accept", "application/json")
+        .json(&json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hi"}], "max_tokens": max_tokens}))
+        .send()
+        .await
+        .unwrap()
+}
+
+fn header(resp: &reqwest::Response, name: &str) -> Option<String> {
+    resp.headers().get(name).map(|v| v.to_str().unwrap().to_string())
+}
+
+#[test]
+fn crossings_are_reported_once() {
+    let mut config: Config = toml::from_str(&format!("[quota]\nthresholds = [90, 25]\n\n{}", KEYS)).unwrap();
+    let quotas = Quotas::new(&config.quota);
+    let key: ApiKey = config.keys.remove(0);
+    let crossed = |tokens| quotas.charge(&key, || tokens).unwrap().crossed;
+    assert_eq!(crossed(200), None);
+    assert_eq!(crossed(100), Some(25));
+    assert_eq!(crossed(100), None);
+    // Jumping several thresholds reports the highest.
+    assert_eq!(crossed(900), Some(90));
+    let status = quotas.charge(&key, || 0).unwrap();
+    assert_eq!((status.used, status.reached, status.crossed), (1300, Some(90), None));
+
+    let open = config.keys.remove(0);
+    assert_eq!(quotas.charge(&open, || 500), None);
+    assert_eq!(Config::default().quota.thresholds, [50, 80, 100]);
+}
+
+#[test]
+fn huge_charges_saturate() {
+    let mut config: Config = toml::from_str(&format!("[quota]\nthresholds = [50, 100]\n\n{}", KEYS)).unwrap();
+    let quotas = Quotas::new(&config.quota);
+    let mut key: ApiKey = config.keys.remove(0);
+    key.token_quota = Some(u64::MAX);
+    assert_eq!(quotas.charge(&key, || 1000).unwrap().crossed, None);
+    let status = quotas.charge(&key, || u64::MAX).unwrap();
+    assert_eq!((status.used, status.crossed), (u64::MAX, Some(100)));
+    assert_eq!(quotas.charge(&key, || 1).unwrap().used, u64::MAX);
+}
+
+#[actix_rt::test]
+async fn warnings_follow_the_key_once_a_threshold_is_crossed() {
+    let base = start(budget_config());
+    let first = send(&base, "sk-budget", 300).await;
+    assert_eq!(first.status(), 200);
+    assert_eq!(header(&first, "x-mock-quota-limit-tokens").as_deref(), Some("1000"));
+    let used: u64 = header(&first, "x-mock-quota-used-tokens").unwrap().parse().unwrap();
+    assert!(used > 300 && used < 500);
+    assert_eq!(header(&first, "x-mock-quota-warning"), None);
+
+    let second = send(&base, "sk-budget", 300).await;
+    assert_eq!(header(&second, "x-mock-quota-warning").as_deref(), Some("50% of this key's token quota used"));
+    let third = send(&base, "sk-budget", 1).await;
+    assert_eq!(header(&third, "x-mock-quota-warning").as_deref(), Some("50% of this key's token quota used"));
+    // Over quota, the key is warned but still served.
+    let fourth = send(&base, "sk-budget", 1000).await;
+    assert_eq!(fourth.status(), 200);
+    assert_eq!(header(&fourth, "x-mock-quota-warning").as_deref(), Some("100% of this key's token quota used"));
+
+    let open = send(&base, "sk-open", 1000).await;
+    assert!(header(&open, "x-mock-quota-limit-tokens").is_none() && header(&open, "x-mock-quota-warning").is_none());
+}
+
+#[cfg(feature = "webhooks")]
+#[actix_rt::test]
+async fn crossings_are_delivered_as_events() {
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use serde_json::Value;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use streaming_llm_api::lifecycle::EventKind;
+    use streaming_llm_api::webhooks::WebhookConfig;
+
+    let received: Arc<Mutex<Vec<Value>>> = Arc::default();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let sink = web::Data::new(received.clone());
+    let server = HttpServer::new(move || {
+        App::new().app_data(sink.clone()).route(
+            "/hook",
+            web::post().to(|body: web::Json<Value>, sink: web::Data<Arc<Mutex<Vec<Value>>>>| async move {
+                sink.lock().unwrap().push(body.into_inner());
+                HttpResponse::NoContent().finish()
+            }),
+        )
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_rt::spawn(server);
+
+    let mut config = budget_config();
+    let mut hook: WebhookConfig = toml::from_str(&format!("url = {:?}", url)).unwrap();
+    hook.events = vec![EventKind::QuotaThreshold];
+    config.webhooks = vec![hook];
+    let base = start(config);
+    let resp = send(&base, "sk-budget", 850).await;
+    let request_id = header(&resp, "x-request-id").unwrap();
+
+    for _ in 0..200 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+    }
+    let events = received.lock().unwrap().clone();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event["type"], "quota.threshold");
+    assert_eq!(event["request_id"], request_id.as_str());
+    assert_eq!(event["quota"]["key"], "...dget");
+    assert_eq!(event["quota"]["threshold_percent"], 80);
+    assert_eq!(event["quota"]["quota_tokens"], 1000);
+    assert!(event["quota"]["used_tokens"].as_u64().unwrap() >= 850);
+}
@@ -0,0 +1,153 @@
+//! `x-ratelimit-*` headers on successful streams, and the opt-in 429s once a
+//! limit is used up.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{client, start, unpaced_config};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::ratelimit::format_reset;
+
+fn limited(requests_per_min: u64, tokens_per_min: Option<u64>, enforce: bool) -> Config {
+    let mut config = unpaced_config();
+    config.rate_limits.requests_per_min = Some(requests_per_min);
+    config.rate_limits.tokens_per_min = tokens_per_min;
+    config.rate_limits.enforce = enforce;
+    config
+}
+
+async fn send(base: &str, key: &str) -> reqwest::Response {
+    client()
+        .post(format!("{}/v1/chat/completions", base))
+        .bearer_auth(key)
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true, "max_tokens": 100}))
+        .send()
+        .await
+        .unwrap()
+}
+
+fn header<'a>(resp: &'a reqwest::Response, name: &str) -> Option<&'a str> {
+    resp.headers().get(name).map(|v| v.to_str().unwrap())
+}
+
+fn number(resp: &reqwest::Response, name: &str) -> u64 {
+    header(resp, name).unwrap().parse().unwrap()
+}
+
+#[actix_rt::test]
+async fn successful_streams_report_what_is_left() {
+    let base = start(limited(3, Some(10_000), false));
+
+    let first = send(&base, "sk-a").await;
+    assert_eq!(first.status(), 200);
+    assert_eq!(number(&first, "x-ratelimit-limit-requests"), 3);
+    assert_eq!(number(&first, "x-ratelimit-remaining-requests"), 2);
+    // One request of three refills in a third of a minute.
+    let reset = header(&first, "x-ratelimit-reset-requests").unwrap();
+    assert!(reset.ends_with('s') && (19.0..=20.0).contains(&reset.trim_end_matches('s').parse::<f64>().unwrap()), "{}", reset);
+    // The prompt plus the 100 reserved completion tokens.
+    assert_eq!(number(&first, "x-ratelimit-limit-tokens"), 10_000);
+    let tokens = 10_000 - number(&first, "x-ratelimit-remaining-tokens");
+    assert!((101..120).contains(&tokens), "{}", tokens);
+    first.text().await.unwrap();
+
+    let second = send(&base, "sk-a").await;
+    assert_eq!(number(&second, "x-ratelimit-remaining-requests"), 1);
+    // Keys have buckets of their own.
+    let other = send(&base, "sk-b").await;
+    assert_eq!(number(&other, "x-ratelimit-remaining-requests"), 2);
+}
+
+#[actix_rt::test]
+async fn limits_are_only_reported_unless_enforced() {
+    let base = start(limited(2, None, false));
+    for remaining in [1, 0, 0, 0] {
+        let resp = send(&base, "sk-a").await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(number(&resp, "x-ratelimit-remaining-requests"), remaining);
+        assert!(header(&resp, "x-ratelimit-limit-tokens").is_none());
+        resp.text().await.unwrap();
+    }
+}
+
+#[actix_rt::test]
+async fn enforced_limits_turn_requests_away() {
+    let base = start(limited(2, None, true));
+    for _ in 0..2 {
+        assert_eq!(send(&base, "sk-a").await.status(), 200);
+    }
+    let rejected = send(&base, "sk-a").await;
+    assert_eq!(rejected.status(), 429);
+    let retry_after: u64 = header(&rejected, "Retry-After").unwrap().parse().unwrap();
+    assert!((29..=30).contains(&retry_after), "{}", retry_after);
+    let body: serde_json::Value = rejected.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(message.starts_with("Rate limit reached on requests per min (RPM): Limit 2, Used 2, Requested 1."), "{}", message);
+
+    assert_eq!(send(&base, "sk-b").await.status(), 200);
+}
+
+#[actix_rt::test]
+async fn unlimited_servers_send_no_headers() {
+    let base = start(unpaced_config());
+    let resp = send(&base, "sk-a").await;
+    assert!(resp.headers().keys().all(|name| !name.as_str().starts_with("x-ratelimit")));
+}
+
+#[test]
+fn resets_are_formatted_like_openai() {
+    assert_eq!(format_reset(Duration::from_millis(120)), "120ms");
+    assert_eq!(format_reset(Duration::from_millis(8640)), "8.64s");
+    assert_eq!(format_reset(Duration::from_secs(1)), "1s");
+    assert_eq!(format_reset(Duration::from_secs(360)), "6m0s");
+    assert_eq!(format_reset(Duration::from_millis(90_500)), "1m30.5s");
+}
+
+#[actix_rt::test]
+async fn huge_reservations_overdraw_without_overflowing() {
+    let send = |base: String| async move {
+        client()
+            .post(format!("{}/v1/chat/completions", base))
+            .bearer_auth("sk-a")
+            .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true, "max_tokens": u64::MAX}))
+            .send()
+            .await
+            .unwrap()
+    };
+    let reported = send(start(limited(10, Some(10_000), false))).await;
+    assert_eq!(reported.status(), 200);
+    assert_eq!(number(&reported, "x-ratelimit-remaining-tokens"), 0);
+
+    let rejected = send(start(limited(10, Some(10_000), true))).await;
+    assert_eq!(rejected.status(), 429);
+}
+
+/// The Responses API, Cohere and Gemini draw on the same limits as chat completions.
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn every_provider_endpoint_is_charged() {
+    use serde_json::json;
+
+    let base = start(limited(3, None, true));
+    let provider = |path: &str, body: serde_json::Value| {
+        client().post(format!("{}{}", base, path)).bearer_auth("sk-shared").json(&body).send()
+    };
+    let responses = provider("/v1/responses", json!({"input": "hi"})).await.unwrap();
+    assert_eq!(responses.status(), 200);
+    assert_eq!(number(&responses, "x-ratelimit-remaining-requests"), 2);
+    let cohere = provider("/v1/chat", json!({"message": "hi", "stream": true})).await.unwrap();
+    assert_eq!(number(&cohere, "x-ratelimit-remaining-requests"), 1);
+    let gemini = provider("/v1beta/models/gemini-2.0-flash:streamGenerateContent", json!({"contents": [{"parts": [{"text": "hi"}]}]}))
+        .await
+        .unwrap();
+    assert_eq!(number(&gemini, "x-ratelimit-remaining-requests"), 0);
+
+    assert_eq!(send(&base, "sk-shared").await.status(), 429);
+    let refused = provider("/v1/chat", json!({"message": "hi", "stream": true})).await.unwrap();
+    assert_eq!(refused.status(), 429);
+    assert!(refused.json::<serde_json::Value>().await.unwrap()["message"].is_string());
+    // Another account still has its own allowance.
+    assert_eq!(send(&base, "sk-other").await.status(), 200);
+}
@@ -0,0 +1,257 @@
+//! Realtime transcription sessions at `/v1/realtime`, driven by a minimal
+//! WebSocket client.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use common::{client, start, unpaced_config};
+use serde_json::{json, Value};
+use std::time::Duration;
+use streaming_llm_api::config::{Config, RealtimeConfig};
+use streaming_llm_api::keys::{ApiKey, Tier};
+use streaming_llm_api::ws::{self, Frame, Opcode};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// pcm16 is 48 bytes a millisecond.
+const MS: usize = 48;
+
+fn config() -> Config {
+    Config {
+        realtime: RealtimeConfig {
+            ms_per_word: 100,
+            delta_interval_ms: 10,
+        },
+        ..unpaced_config()
+    }
+}
+
+struct Socket {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl Socket {
+    /// Opens `/v1/realtime` with the RFC's sample key and checks the reply.
+    async fn connect(base: &str) -> Socket {
+        let mut stream = TcpStream::connect(base.trim_start_matches("http://")).await.unwrap();
+        let request = "GET /v1/realtime?intent=transcription HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut buf = Vec::new();
+        let end = loop {
+            if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end + 4;
+            }
+            let mut read = [0; 1024];
+            let n = stream.read(&mut read).await.unwrap();
+            assert!(n > 0, "closed during the handshake");
+            buf.extend_from_slice(&read[..n]);
+        };
+        let head = String::from_utf8(buf[..end].to_vec()).unwrap().to_ascii_lowercase();
+        assert!(head.starts_with("http/1.1 101"), "{}", head);
+        assert!(head.contains("upgrade: websocket"), "{}", head);
+        assert!(head.contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="), "{}", head);
+        buf.drain(..end);
+        Socket { stream, buf }
+    }
+
+    async fn write(&mut self, opcode: Opcode, payload: &[u8]) {
+        self.write_frame(&ws::encode(opcode, payload, Some([1, 2, 3, 4]))).await;
+    }
+
+    async fn write_frame(&mut self, frame: &[u8]) {
+        self.stream.write_all(frame).await.unwrap();
+    }
+
+    async fn send(&mut self, event: Value) {
+        self.write(Opcode::Text, event.to_string().as_bytes()).await;
+    }
+
+    async fn append(&mut self, ms: usize) {
+        let audio = STANDARD.encode(vec![0u8; ms * MS]);
+        self.send(json!({"type": "input_audio_buffer.append", "audio": audio})).await;
+    }
+
+    async fn frame(&mut self) -> Frame {
+        loop {
+            if let Some((frame, used)) = ws::decode(&self.buf, usize::MAX, false).unwrap() {
+                self.buf.drain(..used);
+                return frame;
+            }
+            let mut read = [0; 4096];
+            let n = tokio::time::timeout(Duration::from_secs(5), self.stream.read(&mut read)).await.unwrap().unwrap();
+            assert!(n > 0, "closed mid-frame");
+            self.buf.extend_from_slice(&read[..n]);
+        }
+    }
+
+    async fn event(&mut self) -> Value {
+        let frame = self.frame().await;
+        assert_eq!(frame.opcode, Opcode::Text);
+        serde_json::from_slice(&frame.payload).unwrap()
+    }
+}
+
+#[test]
+fn accept_keys_follow_the_rfc() {
+    assert_eq!(ws::accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[actix_rt::test]
+async fn transcripts_arrive_as_deltas_while_audio_streams_in() {
+    let base = start(config());
+    let mut socket = Socket::connect(&base).await;
+    let created = socket.event().await;
+    assert_eq!(created["type"], "transcription_session.created");
+    assert_eq!(created["session"]["input_audio_format"], "pcm16");
+    assert_eq!(created["session"]["input_audio_transcription"]["model"], "gpt-4o-transcribe");
+
+    socket.append(300).await;
+    let first = socket.event().await;
+    assert_eq!(first["type"], "conversation.item.input_audio_transcription.delta");
+    assert_eq!(first["delta"], "Streaming LLM APIs");
+    let item = first["item_id"].clone();
+
+    // 150ms more is one whole word now and a part word at the commit.
+    socket.append(150).await;
+    let second = socket.event().await;
+    assert_eq!(second["delta"], " represent");
+    socket.send(json!({"type": "input_audio_buffer.commit"})).await;
+    let flushed = socket.event().await;
+    assert_eq!(flushed["delta"], " a");
+    let committed = socket.event().await;
+    assert_eq!(committed["type"], "input_audio_buffer.committed");
+    assert_eq!(committed["item_id"], item);
+    assert!(committed["previous_item_id"].is_null());
+    let completed = socket.event().await;
+    assert_eq!(completed["type"], "conversation.item.input_audio_transcription.completed");
+    assert_eq!(completed["item_id"], item);
+    assert_eq!(completed["transcript"], "Streaming LLM APIs represent a");
+
+    // The next item carries on through the corpus.
+    socket.append(100).await;
+    socket.send(json!({"type": "input_audio_buffer.commit"})).await;
+    let mut events = Vec::new();
+    while events.last().is_none_or(|e: &Value| e["type"] != "conversation.item.input_audio_transcription.completed") {
+        events.push(socket.event().await);
+    }
+    let committed = events.iter().find(|e| e["type"] == "input_audio_buffer.committed").unwrap();
+    assert_eq!(committed["previous_item_id"], item);
+    assert_ne!(committed["item_id"], item);
+    assert_eq!(events.last().unwrap()["transcript"], "paradigm");
+}
+
+#[actix_rt::test]
+async fn session_updates_change_the_audio_format() {
+    let base = start(config());
+    let mut socket = Socket::connect(&base).await;
+    socket.event().await;
+    socket
+        .send(json!({"type": "transcription_session.update", "session": {"input_audio_format": "g711_ulaw"}}))
+        .await;
+    let updated = socket.event().await;
+    assert_eq!(updated["type"], "transcription_session.updated");
+    assert_eq!(updated["session"]["input_audio_format"], "g711_ulaw");
+
+    // 8 bytes a millisecond, so pcm16's 300ms is 1.8s of words.
+    socket.append(300).await;
+    let delta = socket.event().await;
+    assert_eq!(delta["delta"].as_str().unwrap().split(' ').count(), 18);
+}
+
+#[actix_rt::test]
+async fn bad_events_get_error_events() {
+    let base = start(config());
+    let mut socket = Socket::connect(&base).await;
+    socket.event().await;
+    socket.send(json!({"type": "input_audio_buffer.commit", "event_id": "mine"})).await;
+    let error = socket.event().await;
+    assert_eq!(error["type"], "error");
+    assert_eq!(error["error"]["code"], "input_audio_buffer_commit_empty");
+    assert_eq!(error["error"]["event_id"], "mine");
+
+    socket.send(json!({"type": "input_audio_buffer.append", "audio": "not base64!"})).await;
+    assert_eq!(socket.event().await["error"]["code"], "invalid_value");
+    socket.send(json!({"type": "response.create"})).await;
+    assert_eq!(socket.event().await["error"]["code"], "invalid_event");
+    socket.write(Opcode::Text, b"{").await;
+    assert_eq!(socket.event().await["error"]["code"], "invalid_json");
+
+    socket.append(50).await;
+    socket.send(json!({"type": "input_audio_buffer.clear"})).await;
+    assert_eq!(socket.event().await["type"], "input_audio_buffer.cleared");
+    socket.send(json!({"type": "input_audio_buffer.commit"})).await;
+    assert_eq!(socket.event().await["error"]["code"], "input_audio_buffer_commit_empty");
+}
+
+#[actix_rt::test]
+async fn control_frames_are_answered() {
+    let base = start(config());
+    let mut socket = Socket::connect(&base).await;
+    socket.event().await;
+    socket.write(Opcode::Ping, b"hi").await;
+    let pong = socket.frame().await;
+    assert_eq!((pong.opcode, pong.payload.as_slice()), (Opcode::Pong, &b"hi"[..]));
+
+    socket.write(Opcode::Close, &1000u16.to_be_bytes()).await;
+    let close = socket.frame().await;
+    assert_eq!(close.opcode, Opcode::Close);
+    assert_eq!(close.payload[..2], 1000u16.to_be_bytes());
+}
+
+#[actix_rt::test]
+async fn plain_requests_are_told_to_upgrade() {
+    let base = start(config());
+    let response = client().get(format!("{}/v1/realtime", base)).send().await.unwrap();
+    assert_eq!(response.status(), 426);
+}
+
+#[actix_rt::test]
+async fn frames_breaking_the_rfc_are_closed_with_1002() {
+    let base = start(config());
+    let unmasked = ws::encode(Opcode::Text, br#"{"type": "input_audio_buffer.clear"}"#, None);
+    let long_ping = ws::encode(Opcode::Ping, &[0; 126], Some([1, 2, 3, 4]));
+    let mut fragmented_ping = ws::encode(Opcode::Ping, b"hi", Some([1, 2, 3, 4])).to_vec();
+    fragmented_ping[0] &= 0x7f;
+    for frame in [unmasked.to_vec(), long_ping.to_vec(), fragmented_ping] {
+        let mut socket = Socket::connect(&base).await;
+        socket.event().await;
+        socket.write_frame(&frame).await;
+        let close = socket.frame().await;
+        assert_eq!(close.opcode, Opcode::Close);
+        assert_eq!(close.payload[..2], 1002u16.to_be_bytes());
+    }
+    assert_eq!(ws::decode(&ws::encode(Opcode::Text, b"hi", Some([1, 2, 3, 4])), 100, false), Err(ws::FrameError::Protocol));
+}
+
+#[actix_rt::test]
+async fn listed_keys_are_required_to_connect() {
+    let mut config = config();
+    config.keys = vec![ApiKey {
+        key: "sk-listed".to_string(),
+        tier: Tier::Free,
+        organization: None,
+        project: None,
+        models: None,
+        capabilities: None,
+        token_quota: None,
+    }];
+    let base = start(config);
+    let connect = |key: Option<&str>| {
+        let mut request = client().get(format!("{}/v1/realtime", base));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        request.send()
+    };
+    let missing = connect(None).await.unwrap();
+    assert_eq!(missing.status(), 401);
+    let revoked = connect(Some("sk-revoked")).await.unwrap();
+    assert_eq!(revoked.status(), 401);
+    let error: Value = revoked.json().await.unwrap();
+    assert_eq!(error["error"]["code"], "invalid_api_key");
+    // Past the key check, a plain request is told to upgrade.
+    assert_eq!(connect(Some("sk-listed")).await.unwrap().status(), 426);
+}
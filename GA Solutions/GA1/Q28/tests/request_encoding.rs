@@ -0,0 +1,85 @@
+//! Compressed request bodies, and the size limit on what they decode to.
+
+mod common;
+
+use std::io::Write;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use serde_json::{json, Value};
+
+fn body(prompt: &str) -> Vec<u8> {
+    json!({"messages": [{"role": "user", "content": prompt}], "stream": true}).to_string().into_bytes()
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn deflate(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn brotli(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    brotli::BrotliCompress(&mut &bytes[..], &mut out, &Default::default()).unwrap();
+    out
+}
+
+async fn send(base: &str, encoding: &str, body: Vec<u8>) -> reqwest::Response {
+    client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("content-type", "application/json")
+        .header("content-encoding", encoding)
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn compressed_bodies_are_decoded() {
+    let base = start(unpaced_config());
+    let plain = post(&base, json!({"messages": [{"role": "user", "content": "squeeze"}], "stream": true})).await;
+    let expected = content_of(&parse_events(&plain.text().await.unwrap()));
+    for (encoding, compressed) in [("gzip", gzip(&body("squeeze"))), ("deflate", deflate(&body("squeeze"))), ("br", brotli(&body("squeeze")))] {
+        let response = send(&base, encoding, compressed).await;
+        assert_eq!(response.status(), 200, "{}", encoding);
+        assert_eq!(content_of(&parse_events(&response.text().await.unwrap())), expected, "{}", encoding);
+    }
+}
+
+#[actix_rt::test]
+async fn the_limit_applies_to_decoded_bytes() {
+    let mut config = unpaced_config();
+    config.request.max_body_bytes = 64 * 1024;
+    let base = start(config);
+    // 8 MiB of one letter compresses to a few kilobytes.
+    let bomb = gzip(&body(&"a".repeat(8 * 1024 * 1024)));
+    assert!(bomb.len() < 64 * 1024);
+    let response = send(&base, "gzip", bomb).await;
+    assert_eq!(response.status(), 413);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["type"], "invalid_request_error");
+
+    assert_eq!(send(&base, "gzip", gzip(&body(&"a".repeat(1024)))).await.status(), 200);
+}
+
+#[actix_rt::test]
+async fn unknown_or_corrupt_encodings_are_refused() {
+    let base = start(unpaced_config());
+    let response = send(&base, "compress", body("squeeze")).await;
+    assert_eq!(response.status(), 415);
+    let error: Value = response.json().await.unwrap();
+    assert!(error["error"]["message"].as_str().unwrap().starts_with("Unsupported Content-Encoding 'compress'"));
+
+    let mut corrupt = gzip(&body("squeeze"));
+    corrupt.truncate(corrupt.len() / 2);
+    corrupt.extend_from_slice(b"not gzip at all");
+    assert_eq!(send(&base, "gzip", corrupt).await.status(), 400);
+}
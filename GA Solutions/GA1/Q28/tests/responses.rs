@@ -0,0 +1,175 @@
+//! The Responses API's `/v1/responses`, streamed and whole.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config, with_profile};
+use serde_json::{json, Value};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::stubs::ResponseProfile;
+
+async fn respond(base: &str, body: Value) -> reqwest::Response {
+    client().post(format!("{}/v1/responses", base)).json(&body).send().await.unwrap()
+}
+
+/// Each event's name and data.
+async fn events(base: &str, body: Value) -> Vec<(String, Value)> {
+    let response = respond(base, body).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    parse_events(&response.text().await.unwrap())
+        .into_iter()
+        .map(|e| (e.event.unwrap(), serde_json::from_str(&e.data).unwrap()))
+        .collect()
+}
+
+#[actix_rt::test]
+async fn streams_output_text_deltas_between_the_lifecycle_events() {
+    let base = start(unpaced_config());
+    let events = events(&base, json!({"model": "gpt-4o", "input": "hello", "stream": true})).await;
+    let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names[..4],
+        ["response.created", "response.in_progress", "response.output_item.added", "response.content_part.added"]
+    );
+    assert_eq!(
+        names[names.len() - 4..],
+        ["response.output_text.done", "response.content_part.done", "response.output_item.done", "response.completed"]
+    );
+    let deltas = &events[4..events.len() - 4];
+    assert!(deltas.len() > 1);
+    for (i, (name, data)) in events.iter().enumerate() {
+        assert_eq!(data["type"], *name);
+        assert_eq!(data["sequence_number"], i);
+    }
+
+    let text: String = deltas
+        .iter()
+        .map(|(name, data)| {
+            assert_eq!(name, "response.output_text.delta");
+            assert_eq!(data["item_id"], events[2].1["item"]["id"]);
+            data["delta"].as_str().unwrap()
+        })
+        .collect();
+    let created = &events[0].1["response"];
+    assert_eq!(created["status"], "in_progress");
+    assert!(created["usage"].is_null());
+    let completed = &events.last().unwrap().1["response"];
+    assert_eq!(completed["id"], created["id"]);
+    assert_eq!(completed["status"], "completed");
+    assert_eq!(completed["model"], "gpt-4o");
+    assert_eq!(completed["output"][0]["content"][0]["type"], "output_text");
+    assert_eq!(completed["output"][0]["content"][0]["text"], text);
+    let usage = &completed["usage"];
+    assert_eq!(
+        usage["total_tokens"].as_u64().unwrap(),
+        usage["input_tokens"].as_u64().unwrap() + usage["output_tokens"].as_u64().unwrap()
+    );
+
+    // The same rules and text as chat completions.
+    let chat = post(&base, json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hello"}], "stream": true})).await;
+    assert_eq!(content_of(&parse_events(&chat.text().await.unwrap())), text);
+}
+
+#[actix_rt::test]
+async fn input_items_and_instructions_become_the_conversation() {
+    let base = start(unpaced_config());
+    let body = json!({
+        "instructions": "Be brief.",
+        "input": [
+            {"role": "user", "content": "first"},
+            {"type": "message", "role": "assistant", "content": [{"type": "output_text", "text": "ok"}]},
+            {"type": "function_call_output", "call_id": "call_1", "output": "{}"},
+            {"role": "user", "content": [{"type": "input_text", "text": "second"}]},
+        ],
+    });
+    let reply: Value = respond(&base, body).await.json().await.unwrap();
+    let text = reply["output"][0]["content"][0]["text"].as_str().unwrap();
+    assert!(text.starts_with("Regarding your prompt 'second':"), "{}", text);
+}
+
+#[actix_rt::test]
+async fn non_streaming_requests_get_the_whole_response() {
+    let base = start(unpaced_config());
+    let response = respond(&base, json!({"input": "hello"})).await;
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["x-request-id"].to_str().unwrap().starts_with("req_"));
+    let reply: Value = response.json().await.unwrap();
+    assert_eq!(reply["object"], "response");
+    assert_eq!(reply["status"], "completed");
+    assert_eq!(reply["output"][0]["role"], "assistant");
+    assert_eq!(reply["output"][0]["status"], "completed");
+    assert!(reply["output"][0]["content"][0]["text"].as_str().unwrap().starts_with("Regarding your prompt 'hello':"));
+    assert!(reply["usage"]["output_tokens"].as_u64().unwrap() > 0);
+}
+
+#[actix_rt::test]
+async fn length_finishes_are_incomplete() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        finish_reason: Some("length".to_string()),
+        ..ResponseProfile::default()
+    }));
+    let events = events(&base, json!({"input": "hello", "stream": true})).await;
+    let (name, last) = events.last().unwrap();
+    assert_eq!(name, "response.incomplete");
+    assert_eq!(last["response"]["status"], "incomplete");
+    assert_eq!(last["response"]["incomplete_details"]["reason"], "max_output_tokens");
+}
+
+#[actix_rt::test]
+async fn injected_failures_end_the_stream_as_failed() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(2),
+        error_message: "boom".to_string(),
+        ..ResponseProfile::default()
+    }));
+    let events = events(&base, json!({"input": "hello", "stream": true})).await;
+    let deltas = events.iter().filter(|(name, _)| name == "response.output_text.delta").count();
+    assert_eq!(deltas, 2);
+    let (name, last) = events.last().unwrap();
+    assert_eq!(name, "response.failed");
+    assert_eq!(last["response"]["status"], "failed");
+    assert_eq!(last["response"]["error"]["message"], "boom");
+
+    let response = respond(&base, json!({"input": "hello"})).await;
+    assert_eq!(response.status(), 500);
+}
+
+#[actix_rt::test]
+async fn injected_failures_keep_the_error_style_and_headers() {
+    let mut config: Config = toml::from_str(
+        r#"
+[[stubs]]
+name = "at-once"
+match = { contains = "now" }
+profile = { error_after = 0, error_code = 529, error_style = "anthropic", error_message = "Overloaded", headers = { x-provider-region = "eu-west-1" } }
+
+[[stubs]]
+name = "midway"
+match = { contains = "later" }
+profile = { chunk_delay_ms = 0, error_after = 2, error_code = 529, error_style = "anthropic", error_message = "Overloaded", headers = { x-provider-region = "eu-west-1" } }
+"#,
+    )
+    .unwrap();
+    config.rate_limits.requests_per_min = Some(100);
+    let base = start(config);
+    for (prompt, charged) in [("fail now", false), ("fail later", true)] {
+        let response = respond(&base, json!({"input": prompt})).await;
+        assert_eq!(response.status(), 529, "{}", prompt);
+        assert_eq!(response.headers()["x-provider-region"], "eu-west-1", "{}", prompt);
+        assert_eq!(response.headers().contains_key("x-ratelimit-limit-requests"), charged, "{}", prompt);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body, json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}));
+    }
+}
+
+#[actix_rt::test]
+async fn empty_input_is_rejected() {
+    let base = start(unpaced_config());
+    let response = respond(&base, json!({"input": "  "})).await;
+    assert_eq!(response.status(), 400);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["param"], "input");
+}
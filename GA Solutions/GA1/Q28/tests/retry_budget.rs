@@ -0,0 +1,79 @@
+//! Retry budgets: injected failures paid for from a per-account balance.
+
+mod common;
+
+use common::{client, start, unpaced_config, with_profile};
+use serde_json::json;
+use streaming_llm_api::stubs::ResponseProfile;
+
+fn failing() -> streaming_llm_api::config::Config {
+    let mut config = with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(0),
+        error_code: 503,
+        ..ResponseProfile::default()
+    });
+    config.retry_budget.enabled = true;
+    config.retry_budget.percent_can_retry = 50.0;
+    config.retry_budget.min_retries_per_sec = 0.0;
+    config.retry_budget.max_retries = 2.0;
+    config.keys = toml::from_str::<streaming_llm_api::config::Config>("[[keys]]\nkey = \"sk-a\"\ntier = \"pro\"\n\n[[keys]]\nkey = \"sk-b\"\ntier = \"pro\"\n").unwrap().keys;
+    config.tiers.pro.tokens_per_sec = None;
+    config
+}
+
+/// The status and remaining budget of a request from `key` on its `attempt`.
+async fn send(base: &str, key: &str, attempt: u32) -> (u16, Option<String>) {
+    let resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .bearer_auth(key)
+        .header("x-stainless-retry-count", attempt.to_string())
+        .json(&json!({"prompt": "flaky", "stream": true}))
+        .send()
+        .await
+        .unwrap();
+    let remaining = resp
+        .headers()
+        .get("x-mock-retry-budget-remaining")
+        .map(|v| v.to_str().unwrap().to_string());
+    (resp.status().as_u16(), remaining)
+}
+
+#[actix_rt::test]
+async fn failures_stop_once_the_budget_is_spent() {
+    let base = start(failing());
+    let remaining = |n: &str| Some(n.to_string());
+    assert_eq!(send(&base, "sk-a", 0).await, (503, remaining("1")));
+    assert_eq!(send(&base, "sk-a", 1).await, (503, remaining("0")));
+    // The third try finds the budget empty and gets through.
+    assert_eq!(send(&base, "sk-a", 2).await, (200, remaining("0")));
+
+    // First attempts earn half a retry each, so every second one may fail.
+    assert_eq!(send(&base, "sk-a", 0).await, (200, remaining("0")));
+    assert_eq!(send(&base, "sk-a", 0).await, (503, remaining("0")));
+
+    // Each listed key has its own budget.
+    assert_eq!(send(&base, "sk-b", 0).await, (503, remaining("1")));
+}
+
+#[actix_rt::test]
+async fn unlisted_keys_share_one_budget() {
+    let base = start(failing());
+    let remaining = |n: &str| Some(n.to_string());
+    assert_eq!(send(&base, "sk-made-up-1", 1).await, (503, remaining("1")));
+    assert_eq!(send(&base, "sk-made-up-2", 1).await, (503, remaining("0")));
+    assert_eq!(send(&base, "sk-made-up-3", 1).await, (200, remaining("0")));
+    assert_eq!(send(&base, "sk-a", 1).await, (503, remaining("1")));
+}
+
+#[actix_rt::test]
+async fn budgets_are_off_by_default() {
+    let base = start(unpaced_config());
+    assert_eq!(send(&base, "sk-a", 0).await, (200, None));
+    let mut config = failing();
+    config.retry_budget.enabled = false;
+    let base = start(config);
+    for attempt in 0..4 {
+        assert_eq!(send(&base, "sk-a", attempt).await, (503, None));
+    }
+}
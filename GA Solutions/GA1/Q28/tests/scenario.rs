@@ -0,0 +1,158 @@
+//! `stream-api scenario run`: scripted requests, assertions and JUnit output.
+#![cfg(feature = "client-tools")]
+
+mod common;
+
+use common::start;
+use std::time::Duration;
+
+use streaming_llm_api::client::scenario::{self, Report, Scenario, StepResult};
+use streaming_llm_api::config::Config;
+
+const CONFIG: &str = r#"
+[[stubs]]
+match = { contains = "slow" }
+profile.first_chunk_delay_ms = 200
+profile.chunk_delay_ms = 0
+
+[[stubs]]
+match = { contains = "fail" }
+profile.chunk_delay_ms = 0
+profile.error_after = 2
+profile.error_code = 503
+
+[[stubs]]
+match = { contains = "overloaded" }
+profile.chunk_delay_ms = 0
+profile.error_after = 0
+profile.error_code = 529
+profile.error_style = "anthropic"
+
+[[stubs]]
+match = {}
+profile.chunk_delay_ms = 0
+profile.chunks = 4
+profile.finish_reason = "stop"
+"#;
+
+const SCENARIO: &str = r#"
+name: conformance
+steps:
+  - name: streams to completion
+    request:
+      body: {messages: [{role: user, content: hi}], stream: true}
+    expect: {min_chunks: 4, max_chunks: 4, finish_reason: stop, done: true, max_total_ms: 5000}
+  - name: slow first token
+    request:
+      body: {messages: [{role: user, content: be slow}], stream: true}
+    expect: {min_first_chunk_ms: 150, done: true}
+  - name: fails mid-stream
+    request:
+      body: {messages: [{role: user, content: please fail}], stream: true}
+    expect: {error: true, done: false}
+  - name: rejects a bad request
+    request:
+      body: {stream: true}
+    expect: {status: 400, error_type: invalid_request_error}
+  - name: rejects in anthropic format
+    request:
+      body: {messages: [{role: user, content: overloaded}], stream: true}
+    expect: {status: 529, error_type: overloaded_error}
+  - name: wrong expectations
+    request:
+      body: {messages: [{role: user, content: hi <&>}], stream: true}
+    expect: {status: 200, content_contains: "no such text", max_chunks: 1}
+"#;
+
+fn scenario() -> Scenario {
+    serde_yaml::from_str(SCENARIO).unwrap()
+}
+
+#[actix_rt::test]
+async fn steps_are_checked_in_order() {
+    let base = start(toml::from_str::<Config>(CONFIG).unwrap());
+    let report = scenario::run(&scenario(), &base).await;
+
+    let outcomes: Vec<(&str, bool)> = report.steps.iter().map(|s| (s.name.as_str(), s.passed())).collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("streams to completion", true),
+            ("slow first token", true),
+            ("fails mid-stream", true),
+            ("rejects a bad request", true),
+            ("rejects in anthropic format", true),
+            ("wrong expectations", false),
+        ],
+        "{:#?}",
+        report.steps
+    );
+    let failed = &report.steps[5];
+    assert_eq!(failed.failures.len(), 2, "{:?}", failed.failures);
+    assert!(failed.failures[0].starts_with("4 chunk(s), expected at most 1"), "{:?}", failed.failures);
+    assert!(!report.passed());
+}
+
+#[test]
+fn junit_separates_failures_from_errors() {
+    let step = |name: &str, failures: &[&str], error: Option<&str>| StepResult {
+        name: name.to_string(),
+        elapsed: Duration::from_millis(12),
+        failures: failures.iter().map(|f| f.to_string()).collect(),
+        error: error.map(str::to_string),
+    };
+    let report = Report {
+        scenario: "a&b".to_string(),
+        steps: vec![
+            step("ok", &[], None),
+            step("bad", &["status 500, expected 2xx", "stream ended without [DONE]"], None),
+            step("down", &[], Some("request failed: connection refused")),
+        ],
+        elapsed: Duration::from_millis(1500),
+    };
+    assert_eq!(
+        report.junit(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="a&amp;b" tests="3" failures="1" errors="1" time="1.500">
+  <testsuite name="a&amp;b" tests="3" failures="1" errors="1" time="1.500">
+    <testcase name="ok" classname="a&amp;b" time="0.012"/>
+    <testcase name="bad" classname="a&amp;b" time="0.012">
+      <failure message="status 500, expected 2xx">status 500, expected 2xx
+stream ended without [DONE]</failure>
+    </testcase>
+    <testcase name="down" classname="a&amp;b" time="0.012">
+      <error message="request failed: connection refused"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+    );
+}
+
+#[actix_rt::test]
+async fn the_cli_writes_junit_and_exits_non_zero_on_failure() {
+    let base = start(toml::from_str::<Config>(CONFIG).unwrap());
+    let dir = std::env::temp_dir().join(format!("stream-api-scenario-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("scenario.yaml");
+    let junit = dir.join("junit.xml");
+    std::fs::write(&file, SCENARIO).unwrap();
+
+    // Async, so the server on this test's runtime keeps answering.
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_stream-api"))
+        .args(["scenario", "run"])
+        .arg(&file)
+        .args(["--url", &base, "--junit"])
+        .arg(&junit)
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("5/6 step(s) passed"));
+
+    let xml = std::fs::read_to_string(&junit).unwrap();
+    assert!(xml.contains(r#"tests="6" failures="1" errors="0""#), "{}", xml);
+    assert!(xml.contains(r#"<failure message="4 chunk(s), expected at most 1">"#), "{}", xml);
+    assert!(xml.contains("content does not contain &quot;no such text&quot;"), "{}", xml);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
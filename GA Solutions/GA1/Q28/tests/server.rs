@@ -0,0 +1,103 @@
+//! Behavior checks against a real listening server over TCP. These exercise
+//! the full socket path, so they are the suite any alternative runtime or
+//! listener backend has to pass unchanged.
+
+mod common;
+
+use common::{post, start, unpaced_config};
+use streaming_llm_api::config::WriteMode;
+
+#[actix_rt::test]
+async fn streams_sse_events_ending_in_done() {
+    let base = start(unpaced_config());
+    let resp = post(&base, serde_json::json!({"prompt": "parity", "stream": true})).await;
+
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "text/event-stream");
+    assert_eq!(resp.headers()["x-accel-buffering"], "no");
+    let body = resp.text().await.unwrap();
+    let events: Vec<&str> = body.split_terminator("\n\n").collect();
+    assert!(events.len() > 10);
+    assert!(events.iter().all(|e| e.starts_with("data: ")));
+    assert_eq!(events.last(), Some(&"data: [DONE]"));
+}
+
+#[actix_rt::test]
+async fn coalesced_writes_keep_event_framing() {
+    let mut config = unpaced_config();
+    config.stream.write_mode = WriteMode::Coalesce;
+    let base = start(config);
+    let body = post(&base, serde_json::json!({"prompt": "parity", "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert!(body.ends_with("data: [DONE]\n\n"));
+    assert_eq!(body.matches("data: [DONE]").count(), 1);
+}
+
+#[actix_rt::test]
+async fn rejects_non_streaming_requests_with_json_error() {
+    let base = start(unpaced_config());
+    let resp = post(&base, serde_json::json!({"prompt": "parity", "stream": false})).await;
+    assert_eq!(resp.status(), 400);
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["param"], "stream");
+}
+
+#[actix_rt::test]
+async fn legacy_flag_keeps_sse_validation_errors() {
+    let mut config = unpaced_config();
+    config.compat.legacy_sse_errors = true;
+    let base = start(config);
+    let resp = post(&base, serde_json::json!({"prompt": " ", "stream": true})).await;
+    assert_eq!(resp.status(), 400);
+    assert_eq!(resp.headers()["content-type"], "text/event-stream");
+    assert!(resp.text().await.unwrap().starts_with("data: {\"error\":"));
+}
+
+#[actix_rt::test]
+async fn malformed_bodies_get_openai_style_errors() {
+    let base = start(unpaced_config());
+
+    let missing = post(&base, serde_json::json!({"stream": true})).await;
+    assert_eq!(missing.status(), 400);
+    let body: serde_json::Value = missing.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["param"], "messages");
+
+    let garbage = common::client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("content-type", "application/json")
+        .body("{not json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(garbage.status(), 400);
+    let body: serde_json::Value = garbage.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+}
+
+#[actix_rt::test]
+async fn accepts_standard_chat_messages() {
+    let base = start(unpaced_config());
+    let resp = post(
+        &base,
+        serde_json::json!({
+            "model": "gpt-4o-mini",
+            "messages": [
+                {"role": "system", "content": "be brief"},
+                {"role": "user", "content": "chat shape"}
+            ],
+            "stream": true
+        }),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let body = resp.text().await.unwrap();
+    assert!(body.contains("Regarding your prompt 'chat shape'"));
+    assert!(body.ends_with("data: [DONE]\n\n"));
+}
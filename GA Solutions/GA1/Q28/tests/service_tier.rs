@@ -0,0 +1,117 @@
+//! `service_tier`: echoed on replies, and scaling their pacing.
+
+mod common;
+
+use common::{client, parse_events, post, start, unpaced_config, with_profile};
+use std::time::{Duration, Instant};
+use streaming_llm_api::config::{Config, ServiceTiersConfig};
+use streaming_llm_api::service_tier::ServiceTier;
+use streaming_llm_api::stubs::ResponseProfile;
+
+fn request(tier: Option<&str>) -> serde_json::Value {
+    let mut body = serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true});
+    if let Some(tier) = tier {
+        body["service_tier"] = tier.into();
+    }
+    body
+}
+
+fn paced() -> ResponseProfile {
+    ResponseProfile {
+        chunk_delay_ms: 20,
+        first_chunk_delay_ms: Some(0),
+        chunks: 6,
+        ..ResponseProfile::default()
+    }
+}
+
+async fn timed(base: &str, tier: &str) -> Duration {
+    let started = Instant::now();
+    post(base, request(Some(tier))).await.text().await.unwrap();
+    started.elapsed()
+}
+
+#[test]
+fn tiers_scale_delays_and_divide_the_token_rate() {
+    let tiers = ServiceTiersConfig::default();
+    let mut profile = ResponseProfile {
+        chunk_delay_ms: 30,
+        first_chunk_delay_ms: Some(101),
+        tokens_per_sec: Some(60.0),
+        ..ResponseProfile::default()
+    };
+    tiers.get(ServiceTier::Flex).apply(&mut profile);
+    assert_eq!(profile.chunk_delay_ms, 90);
+    assert_eq!(profile.first_chunk_delay_ms, Some(303));
+    assert_eq!(profile.tokens_per_sec, Some(20.0));
+    tiers.get(ServiceTier::Priority).apply(&mut profile);
+    assert_eq!(profile.chunk_delay_ms, 45);
+    assert_eq!(profile.first_chunk_delay_ms, Some(152));
+    assert_eq!(profile.tokens_per_sec, Some(40.0));
+    assert_eq!(ServiceTier::Auto.name(), "default");
+}
+
+#[test]
+fn a_tier_table_without_a_factor_is_served_at_one() {
+    let config: Config = toml::from_str("[service_tiers.flex]\n[service_tiers.priority]\nlatency_factor = 0.25\n").unwrap();
+    assert_eq!(config.service_tiers.get(ServiceTier::Flex).latency_factor, 1.0);
+    assert_eq!(config.service_tiers.get(ServiceTier::Priority).latency_factor, 0.25);
+    assert_eq!(config.service_tiers.get(ServiceTier::Default).latency_factor, 1.0);
+}
+
+#[actix_rt::test]
+async fn every_chunk_echoes_the_tier() {
+    let base = start(unpaced_config());
+    let body = post(&base, request(Some("flex"))).await.text().await.unwrap();
+    let chunks: Vec<serde_json::Value> = parse_events(&body)
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect();
+    assert!(chunks.len() > 2);
+    for chunk in &chunks {
+        assert_eq!(chunk["service_tier"], "flex", "{}", chunk);
+    }
+
+    let body = post(&base, request(Some("auto"))).await.text().await.unwrap();
+    assert!(body.contains("\"service_tier\":\"default\""));
+    let body = post(&base, request(None)).await.text().await.unwrap();
+    assert!(!body.contains("service_tier"));
+}
+
+#[actix_rt::test]
+async fn json_replies_echo_the_tier() {
+    let base = start(unpaced_config());
+    let mut body = request(Some("priority"));
+    body.as_object_mut().unwrap().remove("stream");
+    let reply: serde_json::Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(reply["service_tier"], "priority");
+}
+
+#[actix_rt::test]
+async fn flex_is_slower_and_priority_faster() {
+    let base = start(with_profile(paced()));
+    // Warms the connection, so the timings below are of the pacing alone.
+    timed(&base, "default").await;
+    let default = timed(&base, "default").await;
+    let flex = timed(&base, "flex").await;
+    let priority = timed(&base, "priority").await;
+    assert!(flex > default * 2, "{:?} vs {:?}", flex, default);
+    assert!(priority < default, "{:?} vs {:?}", priority, default);
+}
+
+#[actix_rt::test]
+async fn unknown_tiers_are_rejected() {
+    let base = start(unpaced_config());
+    let resp = post(&base, request(Some("scale"))).await;
+    assert_eq!(resp.status(), 400);
+}
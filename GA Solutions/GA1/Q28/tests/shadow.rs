@@ -0,0 +1,160 @@
+//! Shadow traffic: mirroring requests and comparing the two replies.
+#![cfg(feature = "recording")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config, with_profile};
+use serde_json::{json, Value};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::stubs::ResponseProfile;
+
+fn chat() -> Value {
+    json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "mirror me"}], "stream": true})
+}
+
+fn shadowed(profile: ResponseProfile) -> Config {
+    let mut config = unpaced_config();
+    config.shadow.profile = Some(profile);
+    config
+}
+
+/// Waits for `n` comparisons and returns them.
+async fn diffs(base: &str, n: usize) -> Vec<Value> {
+    for _ in 0..200 {
+        let body: Value = client().get(format!("{}/v1/internal/shadow", base)).send().await.unwrap().json().await.unwrap();
+        let data = body["data"].as_array().unwrap();
+        if data.len() >= n {
+            return data.clone();
+        }
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {} shadow comparisons", n);
+}
+
+#[actix_rt::test]
+async fn an_identical_profile_matches_the_reply() {
+    let base = start(shadowed(ResponseProfile {
+        chunk_delay_ms: 0,
+        ..ResponseProfile::default()
+    }));
+    let resp = post(&base, chat()).await;
+    let request_id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    let content = content_of(&parse_events(&resp.text().await.unwrap()));
+
+    let diff = &diffs(&base, 1).await[0];
+    assert_eq!(diff["request_id"], request_id.as_str());
+    assert_eq!(diff["target"], "profile");
+    assert_eq!(diff["identical"], true);
+    assert_eq!(diff["similarity"], 1.0);
+    assert_eq!(diff["semantic_distance"], 0);
+    assert!(diff.get("diverges_at").is_none());
+    assert_eq!(diff["primary"]["content"], content.as_str());
+    assert_eq!(diff["primary"]["tokens"], diff["shadow"]["tokens"]);
+    assert_eq!((diff["primary"]["finished"].clone(), diff["shadow"]["finished"].clone()), (json!(true), json!(true)));
+}
+
+#[actix_rt::test]
+async fn a_different_profile_is_reported_as_a_diff() {
+    let base = start(shadowed(ResponseProfile {
+        chunk_delay_ms: 0,
+        first_chunk_delay_ms: Some(50),
+        tokens: Some(4),
+        ..ResponseProfile::default()
+    }));
+    let body: Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "mirror me"}]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(body["choices"][0]["message"]["content"].as_str().unwrap().len() > 16);
+
+    let diff = &diffs(&base, 1).await[0];
+    assert_eq!(diff["identical"], false);
+    assert_eq!(diff["shadow"]["chars"], 16);
+    assert_eq!(diff["diverges_at"], 16);
+    assert!(diff["similarity"].as_f64().unwrap() < 1.0);
+    assert!(diff["token_ratio"].as_f64().unwrap() < 1.0);
+    assert!(diff["semantic_distance"].as_u64().unwrap() > 0);
+    assert!(diff["shadow"]["tokens"].as_u64().unwrap() < diff["primary"]["tokens"].as_u64().unwrap());
+    assert!(diff["shadow"]["first_chunk_ms"].as_u64().unwrap() >= 50);
+}
+
+#[actix_rt::test]
+async fn long_replies_are_compared_past_the_kept_content() {
+    let long = |tokens| ResponseProfile {
+        chunk_delay_ms: 0,
+        tokens: Some(tokens),
+        ..ResponseProfile::default()
+    };
+    let mut config = with_profile(long(3000));
+    config.shadow.profile = Some(long(3000));
+    let base = start(config);
+    post(&base, chat()).await.text().await.unwrap();
+    let diff = &diffs(&base, 1).await[0];
+    assert!(diff["primary"]["chars"].as_u64().unwrap() > 4096);
+    assert_eq!(diff["primary"]["content"].as_str().unwrap().chars().count(), 4096);
+    assert_eq!(diff["identical"], true);
+
+    let mut config = with_profile(long(3000));
+    config.shadow.profile = Some(long(2000));
+    let base = start(config);
+    post(&base, chat()).await.text().await.unwrap();
+    let diff = &diffs(&base, 1).await[0];
+    let shorter = diff["shadow"]["chars"].as_u64().unwrap();
+    assert!(shorter > 4096 && shorter < diff["primary"]["chars"].as_u64().unwrap());
+    assert_eq!(diff["identical"], false);
+    // Past the kept content, to the block of 1024 characters.
+    let diverges_at = diff["diverges_at"].as_u64().unwrap();
+    assert!(diverges_at <= shorter && diverges_at + 1024 > shorter, "{} vs {}", diverges_at, shorter);
+    assert_eq!(diff["token_ratio"], 1.0);
+    assert!(diff["similarity"].as_f64().unwrap() < 1.0);
+}
+
+#[actix_rt::test]
+async fn requests_past_the_cap_are_not_mirrored() {
+    let mut config = shadowed(ResponseProfile {
+        chunk_delay_ms: 100,
+        tokens: Some(8),
+        ..ResponseProfile::default()
+    });
+    config.shadow.max_in_flight = 1;
+    let base = start(config);
+    for _ in 0..3 {
+        post(&base, chat()).await.text().await.unwrap();
+    }
+    assert_eq!(diffs(&base, 1).await.len(), 1);
+    actix_rt::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(diffs(&base, 1).await.len(), 1);
+    // Once the mirror is done, the next request is mirrored again.
+    post(&base, chat()).await.text().await.unwrap();
+    diffs(&base, 2).await;
+}
+
+#[cfg(feature = "shadow")]
+#[actix_rt::test]
+async fn requests_can_be_mirrored_to_a_url() {
+    let upstream = start(unpaced_config());
+    let mut config = unpaced_config();
+    config.shadow.url = Some(format!("{}/v1/chat/completions", upstream));
+    let base = start(config);
+    post(&base, chat()).await.text().await.unwrap();
+    let diff = &diffs(&base, 1).await[0];
+    assert_eq!(diff["target"], "url");
+    // The upstream is the same mock, and the seed is passed along.
+    assert_eq!(diff["identical"], true);
+
+    config = unpaced_config();
+    config.shadow.url = Some(format!("{}/nowhere", upstream));
+    let base = start(config);
+    post(&base, chat()).await.text().await.unwrap();
+    let diff = &diffs(&base, 1).await[0];
+    assert_eq!(diff["shadow"]["finished"], false);
+    assert_eq!(diff["shadow"]["error"], "upstream answered 404 Not Found");
+}
@@ -0,0 +1,80 @@
+//! `stream-api bench --duration`: soak runs with a concurrency ramp.
+#![cfg(all(feature = "client-tools", feature = "endpoints"))]
+
+mod common;
+
+use std::time::Duration;
+
+use clap::Parser;
+use common::{start, with_profile};
+use streaming_llm_api::client::bench::{BenchArgs, Outcome, Ramp};
+use streaming_llm_api::client::soak;
+use streaming_llm_api::stubs::ResponseProfile;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    bench: BenchArgs,
+}
+
+fn bench_args(url: &str, extra: &[&str]) -> BenchArgs {
+    let args = ["bench", "--url", url].into_iter().chain(extra.iter().copied());
+    Cli::parse_from(args).bench
+}
+
+fn short_streams() -> ResponseProfile {
+    ResponseProfile {
+        chunk_delay_ms: 20,
+        chunks: 5,
+        ..ResponseProfile::default()
+    }
+}
+
+#[test]
+fn ramps_are_linear_and_parsed_from_the_cli() {
+    let args = bench_args("http://x", &["--duration", "30m", "--ramp", "0..500"]);
+    assert_eq!(args.duration, Some(Duration::from_secs(1800)));
+    let ramp = args.ramp.unwrap();
+    assert_eq!(ramp, Ramp { from: 0, to: 500 });
+    let total = Duration::from_secs(100);
+    assert_eq!(ramp.at(Duration::ZERO, total), 0);
+    assert_eq!(ramp.at(Duration::from_secs(25), total), 125);
+    assert_eq!(ramp.at(Duration::from_secs(200), total), 500);
+    assert!(Cli::try_parse_from(["bench", "--ramp", "lots"]).is_err());
+}
+
+#[actix_rt::test]
+async fn soaks_sample_the_server_while_ramping() {
+    let base = start(with_profile(short_streams()));
+    let args = bench_args(&base, &["--duration", "2s", "--ramp", "1..4", "--sample-secs", "1"]);
+    let report = soak::run(&args, args.duration.unwrap()).await;
+
+    assert!(!report.results.is_empty());
+    assert_eq!(report.count(Outcome::Completed), report.results.len());
+    assert_eq!(report.first_failure, None);
+    assert!((3..=4).contains(&report.peak_active), "{}", report.peak_active);
+
+    // Samples at 0s, 1s and the end.
+    assert_eq!(report.samples.len(), 3, "{:#?}", report.samples);
+    let targets: Vec<usize> = report.samples.iter().map(|s| s.target).collect();
+    assert!(targets.windows(2).all(|w| w[0] <= w[1]), "{:?}", targets);
+    assert_eq!(report.samples.last().unwrap().completed, report.results.len());
+    if cfg!(target_os = "linux") {
+        assert!(report.samples.iter().all(|s| s.rss_bytes.is_some() && s.open_fds.is_some()));
+    }
+}
+
+#[actix_rt::test]
+async fn the_first_failure_is_reported_with_its_concurrency() {
+    let base = start(with_profile(ResponseProfile {
+        error_after: Some(2),
+        ..short_streams()
+    }));
+    let args = bench_args(&base, &["--duration", "1s", "--concurrency", "2"]);
+    let report = soak::run(&args, args.duration.unwrap()).await;
+
+    assert_eq!(report.count(Outcome::Completed), 0);
+    let (at, in_flight) = report.first_failure.unwrap();
+    assert!(at < Duration::from_millis(500), "{:?}", at);
+    assert!((1..=2).contains(&in_flight), "{}", in_flight);
+}
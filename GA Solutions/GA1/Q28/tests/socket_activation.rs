@@ -0,0 +1,74 @@
+//! Systemd socket activation: `serve` takes the listener passed in through
+//! `LISTEN_FDS` instead of binding, so the socket outlives restarts.
+#![cfg(unix)]
+
+use std::net::TcpListener;
+use std::os::fd::AsRawFd;
+use std::process::{Child, Command, Stdio};
+
+use socket2::Socket;
+
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Starts the binary the way systemd does, with `listener` inherited and
+/// announced in the environment. The config's own `bind` is unusable, so
+/// every request served must have come through the passed socket.
+fn activate(listener: &Socket, config: &std::path::Path) -> Server {
+    let child = Command::new(env!("CARGO_BIN_EXE_stream-api"))
+        .arg("--config")
+        .arg(config)
+        .env("LISTEN_FDS", "1")
+        .env("LISTEN_FDS_FIRST_FD", listener.as_raw_fd().to_string())
+        .env_remove("LISTEN_PID")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    Server(child)
+}
+
+/// Uses a fresh connection, since pooled ones die with the server.
+async fn chat(base: &str) -> String {
+    reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "stream": true}))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn passed_sockets_are_served_and_survive_restarts() {
+    let dir = std::env::temp_dir().join(format!("stream-api-activation-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("config.toml");
+    std::fs::write(&config, "[server]\nbind = \"256.0.0.1:1\"\n\n[[stubs]]\nmatch = {}\nprofile.chunk_delay_ms = 0\n").unwrap();
+
+    let listener = Socket::from(TcpListener::bind("127.0.0.1:0").unwrap());
+    listener.set_cloexec(false).unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap().as_socket().unwrap());
+
+    let server = activate(&listener, &config);
+    assert!(chat(&base).await.ends_with("data: [DONE]\n\n"));
+
+    // Connections made while no server runs wait in the backlog for the next.
+    drop(server);
+    let pending = actix_rt::spawn({
+        let base = base.clone();
+        async move { chat(&base).await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let _server = activate(&listener, &config);
+    assert!(pending.await.unwrap().ends_with("data: [DONE]\n\n"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
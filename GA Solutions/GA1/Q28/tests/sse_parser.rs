@@ -0,0 +1,136 @@
+//! `sse::Parser`: events split across arbitrary reads, line endings,
+//! comments and `[DONE]`.
+
+mod common;
+
+use proptest::prelude::*;
+
+use common::{client, parse_events, start, with_profile};
+use streaming_llm_api::sse::{self, Event, Parser};
+use streaming_llm_api::stubs::{FrameFault, ResponseProfile};
+
+fn event(data: &str) -> Event {
+    Event {
+        data: data.to_string(),
+        ..Event::default()
+    }
+}
+
+/// Parses `body` fed in the pieces `cuts` splits it into.
+fn parse_in_pieces(body: &[u8], cuts: &[usize]) -> (Vec<Event>, Vec<u8>) {
+    let mut parser = Parser::new();
+    let mut events = Vec::new();
+    let mut start = 0;
+    for &at in cuts.iter().chain([&body.len()]) {
+        let at = at.clamp(start, body.len());
+        events.extend(parser.push(&body[start..at]));
+        start = at;
+    }
+    (events, parser.remainder().to_vec())
+}
+
+const BODY: &str = "\u{feff}: opening comment\r\nid: 1\r\nevent: note\r\ndata: first\r\ndata:  second line\r\n\r\ndata:{\"text\":\"héllo ✓\"}\r\rretry: 250\nid: 2\ndata: [DONE]\n\n";
+
+fn expected() -> Vec<Event> {
+    vec![
+        Event {
+            event: Some("note".to_string()),
+            data: "first\n second line".to_string(),
+            id: Some("1".to_string()),
+        },
+        Event {
+            id: Some("1".to_string()),
+            ..event("{\"text\":\"héllo ✓\"}")
+        },
+        Event {
+            id: Some("2".to_string()),
+            ..event("[DONE]")
+        },
+    ]
+}
+
+#[test]
+fn reads_fields_line_endings_and_the_bom() {
+    let mut parser = Parser::new();
+    assert_eq!(parser.push(BODY.as_bytes()), expected());
+    assert!(parser.remainder().is_empty());
+    assert_eq!(parser.last_event_id(), Some("2"));
+    assert_eq!(parser.retry(), Some(250));
+    assert!(sse::parse(BODY.as_bytes()).last().unwrap().is_done());
+}
+
+#[test]
+fn comments_and_blocks_without_data_are_not_events() {
+    let body = b": keepalive\n\nevent: ping\n\nid: 7\n\ndata\n\nunknown: x\ndata: kept\n\n";
+    let events = sse::parse(body);
+    // A bare `data` line is an empty data field; the type set with no data is dropped.
+    assert_eq!(
+        events,
+        [
+            Event {
+                id: Some("7".to_string()),
+                ..event("")
+            },
+            Event {
+                id: Some("7".to_string()),
+                ..event("kept")
+            },
+        ]
+    );
+}
+
+#[test]
+fn a_lone_cr_at_the_end_of_a_read_is_not_a_blank_line() {
+    let mut parser = Parser::new();
+    assert!(parser.push(b"data: a\r").is_empty());
+    // The LF finishes the same line ending, so the event is still open.
+    assert!(parser.push(b"\n").is_empty());
+    assert_eq!(parser.push(b"\r\n"), [event("a")]);
+}
+
+#[test]
+fn unfinished_events_stay_in_the_remainder() {
+    let mut parser = Parser::new();
+    assert_eq!(parser.push(b"data: a\n\ndata: [DONE]\n"), [event("a")]);
+    assert_eq!(parser.remainder(), b"data: [DONE]\n");
+}
+
+proptest! {
+    #[test]
+    fn any_split_reads_the_same_events(mut cuts in prop::collection::vec(0..BODY.len(), 0..12)) {
+        cuts.sort_unstable();
+        let (events, remainder) = parse_in_pieces(BODY.as_bytes(), &cuts);
+        prop_assert_eq!(events, expected());
+        prop_assert!(remainder.is_empty());
+    }
+}
+
+async fn stream(frame_faults: Vec<FrameFault>) -> Vec<u8> {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        frame_faults,
+        ..ResponseProfile::default()
+    }));
+    let response = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"prompt": "parse me", "stream": true, "seed": 1}))
+        .send()
+        .await
+        .unwrap();
+    response.bytes().await.unwrap().to_vec()
+}
+
+#[actix_rt::test]
+async fn reads_the_mocks_own_streams_even_when_corrupted() {
+    let plain = stream(Vec::new()).await;
+    let expected: Vec<String> = parse_events(std::str::from_utf8(&plain).unwrap()).into_iter().map(|e| e.data).collect();
+    let events = sse::parse(&plain);
+    assert_eq!(events.iter().map(|e| e.data.clone()).collect::<Vec<_>>(), expected);
+    assert!(events.last().unwrap().is_done());
+
+    let corrupted = stream(vec![FrameFault::Crlf, FrameFault::Bom]).await;
+    let cuts: Vec<usize> = (1..corrupted.len()).step_by(7).collect();
+    let (events, remainder) = parse_in_pieces(&corrupted, &cuts);
+    assert_eq!(events.into_iter().map(|e| e.data).collect::<Vec<_>>(), expected);
+    assert!(remainder.is_empty());
+}
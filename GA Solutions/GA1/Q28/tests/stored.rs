@@ -0,0 +1,205 @@
+//! Stored completions: `store: true`, `metadata` and the endpoints that
+//! read them back.
+#![cfg(feature = "endpoints")]
+
+mod common;
+
+use common::{client, content_of, parse_events, post, start, unpaced_config};
+use streaming_llm_api::chat::{IncomingRequest, NormalizedRequest};
+use streaming_llm_api::config::{Config, StoreConfig};
+use streaming_llm_api::lifecycle::Usage;
+use streaming_llm_api::state::AppState;
+use streaming_llm_api::stored::{StoredStream, MAX_CONTENT_BYTES};
+
+fn request(prompt: &str, metadata: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "model": "gpt-4o-mini",
+        "messages": [{"role": "system", "content": "Be brief."}, {"role": "user", "content": prompt}],
+        "stream": true,
+        "store": true,
+        "metadata": metadata,
+    })
+}
+
+/// Streams `body` to the end and returns its request id and content.
+async fn stream(base: &str, body: serde_json::Value) -> (String, String) {
+    let resp = post(base, body).await;
+    let id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    let content = content_of(&parse_events(&resp.text().await.unwrap()));
+    (id, content)
+}
+
+async fn get(base: &str, path: &str) -> reqwest::Response {
+    client().get(format!("{}{}", base, path)).send().await.unwrap()
+}
+
+async fn ids(base: &str, query: &str) -> Vec<String> {
+    let list: serde_json::Value = get(base, &format!("/v1/chat/completions?{}", query)).await.json().await.unwrap();
+    list["data"].as_array().unwrap().iter().map(|c| c["id"].as_str().unwrap().to_string()).collect()
+}
+
+#[actix_rt::test]
+async fn stored_streams_can_be_retrieved() {
+    let base = start(unpaced_config());
+    let (id, content) = stream(&base, request("Hello", serde_json::json!({"run": "a"}))).await;
+    let stored: serde_json::Value = get(&base, &format!("/v1/chat/completions/{}", id)).await.json().await.unwrap();
+    assert_eq!(stored["id"], id);
+    assert_eq!(stored["object"], "chat.completion");
+    assert_eq!(stored["model"], "gpt-4o-mini");
+    assert_eq!(stored["metadata"], serde_json::json!({"run": "a"}));
+    assert_eq!(stored["choices"][0]["message"]["content"], content);
+    assert_eq!(stored["choices"][0]["finish_reason"], "stop");
+    assert!(stored["usage"]["completion_tokens"].as_u64().unwrap() > 0);
+
+    let messages: serde_json::Value = get(&base, &format!("/v1/chat/completions/{}/messages", id)).await.json().await.unwrap();
+    assert_eq!(messages["object"], "list");
+    assert_eq!(messages["data"][0]["id"], format!("{}-0", id));
+    assert_eq!(messages["data"][0]["role"], "system");
+    assert_eq!(messages["data"][1]["content"], "Hello");
+    assert_eq!(messages["has_more"], false);
+}
+
+#[actix_rt::test]
+async fn json_replies_are_stored_too() {
+    let base = start(unpaced_config());
+    let mut body = request("Hello", serde_json::json!({}));
+    body.as_object_mut().unwrap().remove("stream");
+    let reply: serde_json::Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = reply["id"].as_str().unwrap();
+    let stored: serde_json::Value = get(&base, &format!("/v1/chat/completions/{}", id)).await.json().await.unwrap();
+    assert_eq!(stored["choices"][0]["message"]["content"], reply["choices"][0]["message"]["content"]);
+    assert_eq!(stored["usage"], reply["usage"]);
+}
+
+#[actix_rt::test]
+async fn only_requests_that_ask_are_stored() {
+    let base = start(unpaced_config());
+    let mut body = request("Hello", serde_json::json!({"run": "a"}));
+    body["store"] = false.into();
+    let (id, _) = stream(&base, body).await;
+    let resp = get(&base, &format!("/v1/chat/completions/{}", id)).await;
+    assert_eq!(resp.status(), 404);
+    let error: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(error["error"]["code"], "not_found");
+    assert!(ids(&base, "").await.is_empty());
+}
+
+#[actix_rt::test]
+async fn lists_filter_by_metadata_and_model() {
+    let base = start(unpaced_config());
+    let (a, _) = stream(&base, request("one", serde_json::json!({"run": "a", "split": "train"}))).await;
+    let (b, _) = stream(&base, request("two", serde_json::json!({"run": "b", "split": "train"}))).await;
+    let (c, _) = stream(&base, request("three", serde_json::json!({"run": "a", "split": "test"}))).await;
+
+    assert_eq!(ids(&base, "").await, [a.as_str(), &b, &c]);
+    assert_eq!(ids(&base, "metadata[run]=a").await, [a.as_str(), &c]);
+    assert_eq!(ids(&base, "metadata[run]=a&metadata[split]=test").await, [c.as_str()]);
+    assert_eq!(ids(&base, "model=gpt-4o-mini&order=desc").await, [c, b, a]);
+    assert!(ids(&base, "model=gpt-4o").await.is_empty());
+}
+
+#[actix_rt::test]
+async fn lists_page_after_an_id() {
+    let base = start(unpaced_config());
+    let mut stored = Vec::new();
+    for prompt in ["one", "two", "three"] {
+        stored.push(stream(&base, request(prompt, serde_json::json!({}))).await.0);
+    }
+    let page: serde_json::Value = get(&base, "/v1/chat/completions?limit=2").await.json().await.unwrap();
+    assert_eq!(page["has_more"], true);
+    assert_eq!(page["first_id"], stored[0]);
+    assert_eq!(page["last_id"], stored[1]);
+    assert_eq!(ids(&base, &format!("limit=2&after={}", stored[1])).await, [stored[2].as_str()]);
+
+    let resp = get(&base, "/v1/chat/completions?limit=0").await;
+    assert_eq!(resp.status(), 400);
+    let resp = get(&base, "/v1/chat/completions?order=newest").await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_rt::test]
+async fn oversized_metadata_is_rejected() {
+    let base = start(unpaced_config());
+    let many: serde_json::Map<String, serde_json::Value> = (0..17).map(|i| (format!("k{}", i), "v".into())).collect();
+    let long = serde_json::json!({"k": "v".repeat(513)});
+    for metadata in [serde_json::Value::Object(many), long] {
+        let resp = post(&base, request("Hello", metadata)).await;
+        assert_eq!(resp.status(), 400);
+        let error: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(error["error"]["param"], "metadata");
+    }
+}
+
+#[actix_rt::test]
+async fn the_oldest_completions_are_dropped_past_the_limit() {
+    let base = start(Config {
+        store: StoreConfig { limit: 2 },
+        ..unpaced_config()
+    });
+    let mut stored = Vec::new();
+    for prompt in ["one", "two", "three"] {
+        stored.push(stream(&base, request(prompt, serde_json::json!({}))).await.0);
+    }
+    assert_eq!(ids(&base, "").await, &stored[1..]);
+}
+
+#[actix_rt::test]
+async fn completions_are_only_seen_by_their_owner() {
+    let base = start(unpaced_config());
+    let as_key = |key: &str, path: String| client().get(format!("{}{}", base, path)).bearer_auth(key).send();
+    let resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .bearer_auth("sk-alice")
+        .json(&request("Hello", serde_json::json!({})))
+        .send()
+        .await
+        .unwrap();
+    let id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    resp.text().await.unwrap();
+
+    assert_eq!(as_key("sk-alice", format!("/v1/chat/completions/{}", id)).await.unwrap().status(), 200);
+    assert_eq!(as_key("sk-bob", format!("/v1/chat/completions/{}", id)).await.unwrap().status(), 404);
+    assert_eq!(as_key("sk-bob", format!("/v1/chat/completions/{}/messages", id)).await.unwrap().status(), 404);
+    let list: serde_json::Value = as_key("sk-bob", "/v1/chat/completions".to_string()).await.unwrap().json().await.unwrap();
+    assert_eq!(list["data"], serde_json::json!([]));
+    assert!(ids(&base, "").await.is_empty());
+    let list: serde_json::Value = as_key("sk-alice", "/v1/chat/completions".to_string()).await.unwrap().json().await.unwrap();
+    assert_eq!(list["data"][0]["id"], id);
+}
+
+#[actix_rt::test]
+async fn reading_checks_the_organization_headers() {
+    let base = start(unpaced_config());
+    let resp = client()
+        .get(format!("{}/v1/chat/completions", base))
+        .header("OpenAI-Organization", "org-unknown")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
+
+#[test]
+fn stored_replies_are_capped() {
+    let body = r#"{"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "hi"}], "store": true}"#;
+    let req = NormalizedRequest::from(serde_json::from_str::<IncomingRequest>(body).unwrap());
+    let state = AppState::new(&Config::default());
+    let stream = StoredStream::new(&req, "", "req_1", "stop".to_string()).unwrap();
+    let chunk = "é".repeat(1000);
+    for _ in 0..MAX_CONTENT_BYTES / chunk.len() + 2 {
+        stream.chunk(&chunk);
+    }
+    stream.finish(&state, Usage::default());
+    let content = state.stored.get("req_1", "").unwrap().choices[0].message.content.clone();
+    assert!(content.len() <= MAX_CONTENT_BYTES && content.len() > MAX_CONTENT_BYTES - 2);
+    assert!(content.chars().all(|c| c == 'é'));
+}
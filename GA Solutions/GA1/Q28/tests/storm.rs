@@ -0,0 +1,96 @@
+//! 429 storm mode driven through the admin API.
+#![cfg(feature = "admin")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{post, start, unpaced_config};
+
+const TOKEN: &str = "storm-token";
+
+fn base_with_admin() -> String {
+    let mut config = unpaced_config();
+    config.admin.token = Some(TOKEN.to_string());
+    start(config)
+}
+
+fn request() -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": "storm"}], "stream": true})
+}
+
+async fn admin(method: reqwest::Method, base: &str, body: Option<serde_json::Value>) -> reqwest::Response {
+    let mut req = common::client()
+        .request(method, format!("{}/v1/admin/storm", base))
+        .bearer_auth(TOKEN);
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    req.send().await.unwrap()
+}
+
+#[actix_rt::test]
+async fn full_storm_rejects_then_recovers() {
+    let base = base_with_admin();
+    let resp = admin(
+        reqwest::Method::POST,
+        &base,
+        Some(serde_json::json!({"probability": 1.0, "duration_secs": 0.3, "retry_after_secs": 2})),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+
+    let resp = post(&base, request()).await;
+    assert_eq!(resp.status(), 429);
+    assert_eq!(resp.headers()["Retry-After"], "2");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "rate_limit_error");
+    assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert_eq!(post(&base, request()).await.status(), 200);
+    let status: serde_json::Value = admin(reqwest::Method::GET, &base, None).await.json().await.unwrap();
+    assert_eq!(status["active"], false);
+    assert_eq!(status["rejected"], 1);
+}
+
+#[actix_rt::test]
+async fn partial_storm_rejects_about_its_probability() {
+    let base = base_with_admin();
+    admin(
+        reqwest::Method::POST,
+        &base,
+        Some(serde_json::json!({"probability": 0.5, "duration_secs": 60, "seed": 42})),
+    )
+    .await;
+    let mut rejected = 0;
+    for _ in 0..100 {
+        let resp = post(&base, request()).await;
+        if resp.status() == 429 {
+            rejected += 1;
+        }
+        resp.bytes().await.unwrap();
+    }
+    assert!((25..=75).contains(&rejected), "rejected {} of 100", rejected);
+
+    assert_eq!(admin(reqwest::Method::DELETE, &base, None).await.status(), 200);
+    assert_eq!(post(&base, request()).await.status(), 200);
+}
+
+#[actix_rt::test]
+async fn storm_control_needs_the_admin_token() {
+    let base = base_with_admin();
+    let resp = common::client()
+        .post(format!("{}/v1/admin/storm", base))
+        .json(&serde_json::json!({"probability": 1.0, "duration_secs": 10}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(post(&base, request()).await.status(), 200);
+
+    let resp = admin(reqwest::Method::POST, &base, Some(serde_json::json!({"probability": 2.0, "duration_secs": 10}))).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["param"], "probability");
+}
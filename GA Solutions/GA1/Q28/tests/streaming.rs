@@ -0,0 +1,138 @@
+//! Wire-format guarantees of the chat completions stream.
+
+mod common;
+
+use common::{content_of, parse_events, post, start, unpaced_config, with_profile};
+use streaming_llm_api::chat::EXTENDED_CONTENT;
+use streaming_llm_api::stubs::ResponseProfile;
+
+fn request(prompt: &str) -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": prompt}], "stream": true})
+}
+
+/// Seed 0 keeps the corpus in its original order.
+fn unrotated(prompt: &str) -> serde_json::Value {
+    serde_json::json!({"messages": [{"role": "user", "content": prompt}], "stream": true, "seed": 0})
+}
+
+async fn reply(base: &str, body: serde_json::Value) -> (String, String) {
+    let resp = post(base, body).await;
+    let seed = resp.headers()["X-Mock-Seed"].to_str().unwrap().to_string();
+    (seed, content_of(&parse_events(&resp.text().await.unwrap())))
+}
+
+#[actix_rt::test]
+async fn default_stream_is_progressive_and_done_terminated() {
+    let base = start(unpaced_config());
+    let body = post(&base, request("wire format")).await.text().await.unwrap();
+    let events = parse_events(&body);
+
+    let chunks = events.iter().filter(|e| e.data != "[DONE]").count();
+    assert!(chunks >= 10, "expected progressive delivery, got {} chunks", chunks);
+    assert_eq!(events.iter().filter(|e| e.data == "[DONE]").count(), 1);
+    assert_eq!(events.last().unwrap().data, "[DONE]");
+    assert!(events.iter().all(|e| e.id.is_none()), "ids are opt-in");
+}
+
+#[actix_rt::test]
+async fn fixed_chunk_size_yields_exact_chunk_count() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        chunk_chars: Some(100),
+        ..ResponseProfile::default()
+    }));
+    let body = post(&base, unrotated("sized")).await.text().await.unwrap();
+    let events = parse_events(&body);
+
+    let expected = format!("Regarding your prompt 'sized':\n\n{}", EXTENDED_CONTENT);
+    let total = expected.chars().count();
+    assert_eq!(events.len() - 1, total.div_ceil(100));
+    assert_eq!(content_of(&events), expected);
+}
+
+#[actix_rt::test]
+async fn chunks_arrive_in_order_and_reassemble_the_reply() {
+    let base = start(unpaced_config());
+    let body = post(&base, unrotated("ordering")).await.text().await.unwrap();
+    let content = content_of(&parse_events(&body));
+    assert_eq!(content, format!("Regarding your prompt 'ordering':\n\n{}", EXTENDED_CONTENT));
+}
+
+#[actix_rt::test]
+async fn event_ids_increase_monotonically_when_enabled() {
+    let mut config = unpaced_config();
+    config.stream.event_ids = true;
+    let base = start(config);
+    let body = post(&base, request("ids")).await.text().await.unwrap();
+    let events = parse_events(&body);
+
+    let ids: Vec<u64> = events.iter().map(|e| e.id.expect("every event has an id")).collect();
+    assert_eq!(ids.first(), Some(&1));
+    assert!(ids.windows(2).all(|w| w[1] == w[0] + 1), "ids not consecutive: {:?}", ids);
+    assert_eq!(events.last().unwrap().data, "[DONE]");
+}
+
+#[actix_rt::test]
+async fn injected_error_ends_stream_without_done() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(3),
+        ..ResponseProfile::default()
+    }));
+    let body = post(&base, request("failing")).await.text().await.unwrap();
+    let events = parse_events(&body);
+
+    assert_eq!(events.len(), 4);
+    assert!(events.iter().all(|e| e.data != "[DONE]"));
+    let error: serde_json::Value = serde_json::from_str(&events[3].data).unwrap();
+    assert_eq!(error["code"], 500);
+}
+
+#[actix_rt::test]
+async fn last_event_id_resumes_after_the_given_event() {
+    let mut config = unpaced_config();
+    config.stream.event_ids = true;
+    let base = start(config);
+
+    let full = parse_events(&post(&base, request("resume")).await.text().await.unwrap());
+    let resumed = common::client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Last-Event-ID", "5")
+        .json(&request("resume"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let resumed = parse_events(&resumed);
+
+    assert_eq!(resumed.first().unwrap().id, Some(6));
+    assert_eq!(content_of(&resumed), content_of(&full[5..]));
+    assert_eq!(resumed.len(), full.len() - 5);
+}
+
+#[actix_rt::test]
+async fn identical_messages_get_identical_replies() {
+    let base = start(unpaced_config());
+    let (seed_a, first) = reply(&base, request("snapshot me")).await;
+    let (seed_b, second) = reply(&base, request("snapshot me")).await;
+    assert_eq!(seed_a, seed_b);
+    assert_eq!(first, second);
+
+    let (seed_c, other) = reply(&base, request("something else entirely")).await;
+    assert_ne!(seed_a, seed_c);
+    let strip = |s: &str| s.split_once("\n\n").unwrap().1.to_string();
+    assert_ne!(strip(&first), strip(&other), "different prompts should open differently");
+    assert_eq!(first.chars().count() - "snapshot me".len(), other.chars().count() - "something else entirely".len());
+}
+
+#[actix_rt::test]
+async fn explicit_seed_overrides_the_derived_one() {
+    let base = start(unpaced_config());
+    let seeded = |prompt: &str| serde_json::json!({"messages": [{"role": "user", "content": prompt}], "stream": true, "seed": 7});
+    let (seed, first) = reply(&base, seeded("one prompt")).await;
+    let (_, second) = reply(&base, seeded("another prompt")).await;
+    assert_eq!(seed, "7");
+    assert_eq!(first.split_once("\n\n").unwrap().1, second.split_once("\n\n").unwrap().1);
+}
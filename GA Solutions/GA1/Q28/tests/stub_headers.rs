@@ -0,0 +1,60 @@
+//! Response headers attached by stub rules.
+
+mod common;
+
+use common::{client, post, start};
+use serde_json::json;
+use streaming_llm_api::config::Config;
+
+const STUBS: &str = r#"
+[[stubs]]
+match = { contains = "eu" }
+profile = { chunk_delay_ms = 0, headers = { x-provider-region = "eu-west-1", x-trace-id = "abc123" } }
+
+[[stubs]]
+match = { contains = "down" }
+profile = { error_after = 0, error_code = 503, headers = { x-provider-region = "us-east-1" } }
+
+[[stubs]]
+match = {}
+profile = { chunk_delay_ms = 0 }
+"#;
+
+fn config() -> Config {
+    toml::from_str(STUBS).unwrap()
+}
+
+#[actix_rt::test]
+async fn matched_replies_carry_the_rule_headers() {
+    let base = start(config());
+    let resp = post(&base, json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "eu please"}], "stream": true})).await;
+    assert_eq!(resp.headers().get("x-provider-region").unwrap(), "eu-west-1");
+    assert_eq!(resp.headers().get("x-trace-id").unwrap(), "abc123");
+    resp.text().await.unwrap();
+
+    let resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("accept", "application/json")
+        .json(&json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "eu please"}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers().get("x-provider-region").unwrap(), "eu-west-1");
+
+    let resp = post(&base, json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "anywhere"}], "stream": true})).await;
+    assert!(resp.headers().get("x-provider-region").is_none());
+}
+
+#[actix_rt::test]
+async fn injected_failures_carry_them_too() {
+    let base = start(config());
+    let resp = post(&base, json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "down again"}], "stream": true})).await;
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.headers().get("x-provider-region").unwrap(), "us-east-1");
+}
+
+#[test]
+fn invalid_headers_fail_to_load() {
+    assert!(toml::from_str::<Config>("[[stubs]]\nmatch = {}\nprofile = { headers = { \"bad name\" = \"x\" } }").is_err());
+    assert!(toml::from_str::<Config>("[[stubs]]\nmatch = {}\nprofile = { headers = { x-ok = \"line\\nbreak\" } }").is_err());
+}
@@ -0,0 +1,57 @@
+//! Server-wide and per-model token throughput caps.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{parse_events, post, start, with_profile};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::stubs::ResponseProfile;
+
+/// 200 tokens in 10-token chunks, unpaced unless a cap applies.
+fn capped(tokens_per_sec: Option<f64>, models: &[(&str, f64)]) -> Config {
+    let mut config = with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        tokens: Some(200),
+        chunk_chars: Some(40),
+        chars_per_token: Some(4.0),
+        ..ResponseProfile::default()
+    });
+    config.throughput.tokens_per_sec = tokens_per_sec;
+    config.throughput.models = models.iter().map(|(m, r)| (m.to_string(), *r)).collect();
+    config
+}
+
+fn request(model: &str) -> serde_json::Value {
+    serde_json::json!({"model": model, "messages": [{"role": "user", "content": "throughput"}], "stream": true})
+}
+
+async fn timed(base: &str, model: &str, concurrency: usize) -> Duration {
+    let started = Instant::now();
+    let replies = futures::future::join_all((0..concurrency).map(|_| post(base, request(model)))).await;
+    for resp in replies {
+        let events = parse_events(&resp.text().await.unwrap());
+        assert_eq!(events.len(), 21, "all chunks still arrive");
+    }
+    started.elapsed()
+}
+
+#[actix_rt::test]
+async fn global_budget_is_shared_between_streams() {
+    let base = start(capped(Some(1000.0), &[]));
+    let single = timed(&base, "any", 1).await;
+    assert!(single >= Duration::from_millis(180), "one stream at 1000 tok/s took {:?}", single);
+
+    // Four streams split the same budget, so together they take about four times as long.
+    let shared = timed(&base, "any", 4).await;
+    assert!(shared >= Duration::from_millis(700), "four streams took {:?}", shared);
+}
+
+#[actix_rt::test]
+async fn model_budgets_only_apply_to_their_model() {
+    let base = start(capped(None, &[("slow-model", 500.0)]));
+    let slow = timed(&base, "slow-model", 1).await;
+    assert!(slow >= Duration::from_millis(380), "capped model took {:?}", slow);
+    let fast = timed(&base, "fast-model", 1).await;
+    assert!(fast < Duration::from_millis(300), "uncapped model took {:?}", fast);
+}
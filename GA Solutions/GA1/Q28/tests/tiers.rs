@@ -0,0 +1,117 @@
+//! API key tiers: queue priority, per-stream token rates and the `X-Mock-Tier` header.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{client, parse_events, start, unpaced_config};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::keys::{ApiKey, Tier};
+
+fn tiered_config() -> Config {
+    let mut config = unpaced_config();
+    config.keys = [("sk-free", Tier::Free), ("sk-pro", Tier::Pro), ("sk-enterprise", Tier::Enterprise)]
+        .into_iter()
+        .map(|(key, tier)| ApiKey {
+            key: key.to_string(),
+            tier,
+            organization: None,
+            project: None,
+            models: None,
+            capabilities: None,
+            token_quota: None,
+        })
+        .collect();
+    // Rate caps are tested on their own; elsewhere they would only slow streams down.
+    for tier in [&mut config.tiers.free, &mut config.tiers.pro, &mut config.tiers.enterprise] {
+        tier.tokens_per_sec = None;
+    }
+    config
+}
+
+async fn send(base: &str, key: Option<&str>) -> reqwest::Response {
+    let mut request = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "tiers"}], "stream": true}));
+    if let Some(key) = key {
+        request = request.bearer_auth(key);
+    }
+    request.send().await.unwrap()
+}
+
+async fn queue_ms(resp: reqwest::Response) -> u64 {
+    let events = parse_events(&resp.text().await.unwrap());
+    let first: serde_json::Value = serde_json::from_str(&events[0].data).unwrap();
+    first["x_mock"]["queue_ms"].as_u64().unwrap()
+}
+
+#[actix_rt::test]
+async fn higher_tiers_jump_the_queue() {
+    let mut config = tiered_config();
+    config.queue.admissions_per_sec = Some(10.0);
+    let base = start(config);
+
+    // The first request takes the only free slot; the rest queue behind it.
+    let first = send(&base, Some("sk-free")).await;
+    let free = futures::future::join_all((0..2).map(|_| send(&base, Some("sk-free"))));
+    let enterprise = async {
+        actix_rt::time::sleep(Duration::from_millis(30)).await;
+        send(&base, Some("sk-enterprise")).await
+    };
+    let (free, enterprise) = futures::join!(free, enterprise);
+
+    assert!(queue_ms(first).await < 50);
+    let enterprise = queue_ms(enterprise).await;
+    for resp in free {
+        let free = queue_ms(resp).await;
+        assert!(enterprise < free, "enterprise arrived last but waited {}ms, free {}ms", enterprise, free);
+    }
+}
+
+#[actix_rt::test]
+async fn lower_tiers_stream_at_their_token_rate() {
+    let mut config = tiered_config();
+    config.stubs[0].profile.tokens = Some(100);
+    config.tiers.free.tokens_per_sec = Some(250.0);
+    let base = start(config);
+
+    let timed = |key| {
+        let base = base.clone();
+        async move {
+            let started = Instant::now();
+            send(&base, Some(key)).await.text().await.unwrap();
+            started.elapsed()
+        }
+    };
+    let free = timed("sk-free").await;
+    let enterprise = timed("sk-enterprise").await;
+    assert!(free >= Duration::from_millis(350), "100 tokens at 250/s took {:?}", free);
+    assert!(enterprise < Duration::from_millis(200), "enterprise is uncapped but took {:?}", enterprise);
+}
+
+#[actix_rt::test]
+async fn listed_keys_report_their_tier() {
+    let base = start(tiered_config());
+    let pro = send(&base, Some("sk-pro")).await;
+    assert_eq!(pro.headers()["x-mock-tier"], "pro");
+    for key in [None, Some("sk-unknown")] {
+        let resp = send(&base, key).await;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().get("x-mock-tier").is_none());
+    }
+}
+
+#[test]
+fn a_partial_tier_table_keeps_the_tier_defaults() {
+    let config: Config = toml::from_str("[tiers.pro]\ntokens_per_sec = 300\n[tiers.free]\npriority = 3\n[tiers.enterprise]\nweight = 8\n").unwrap();
+    let pro = config.tiers.get(Tier::Pro);
+    assert_eq!((pro.priority, pro.weight, pro.tokens_per_sec), (1, 4, Some(300.0)));
+    let free = config.tiers.get(Tier::Free);
+    assert_eq!((free.priority, free.weight, free.tokens_per_sec), (3, 1, Some(50.0)));
+    let enterprise = config.tiers.get(Tier::Enterprise);
+    assert_eq!((enterprise.priority, enterprise.weight, enterprise.tokens_per_sec), (2, 8, None));
+
+    let uncapped: Config = toml::from_str("[tiers.free]\ntokens_per_sec = 0\n").unwrap();
+    assert_eq!(uncapped.tiers.get(Tier::Free).tokens_per_sec, None);
+    assert!(toml::from_str::<Config>("[tiers.pro]\nrate = 1\n").is_err());
+}
@@ -0,0 +1,74 @@
+//! Server-side stream deadlines: soft finishes with `length`, hard drops the connection.
+
+mod common;
+
+use futures::StreamExt;
+use std::time::{Duration, Instant};
+
+use common::{parse_events, post, start, with_profile};
+use streaming_llm_api::stubs::{ResponseProfile, TimeoutMode};
+
+fn timed_out(on_timeout: TimeoutMode) -> String {
+    start(with_profile(ResponseProfile {
+        chunk_delay_ms: 100,
+        chunks: 10,
+        max_duration_ms: Some(350),
+        on_timeout,
+        ..ResponseProfile::default()
+    }))
+}
+
+fn request() -> serde_json::Value {
+    serde_json::json!({"prompt": "deadline", "stream": true})
+}
+
+#[actix_rt::test]
+async fn soft_timeouts_finish_with_length() {
+    let base = timed_out(TimeoutMode::Soft);
+    let started = Instant::now();
+    let body = post(&base, request()).await.text().await.unwrap();
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(350) && elapsed < Duration::from_millis(700), "{:?}", elapsed);
+
+    let events = parse_events(&body);
+    let [content @ .., finish, done] = &events[..] else { panic!("too few events") };
+    assert_eq!(content.len(), 3, "chunks due at 100, 200 and 300 ms make it");
+    let finish: serde_json::Value = serde_json::from_str(&finish.data).unwrap();
+    assert_eq!(finish["choices"][0]["finish_reason"], "length");
+    assert_eq!(done.data, "[DONE]");
+}
+
+#[actix_rt::test]
+async fn hard_timeouts_drop_the_connection() {
+    let base = timed_out(TimeoutMode::Hard);
+    let mut body = post(&base, request()).await.bytes_stream();
+    let mut received = Vec::new();
+    let mut error = None;
+    while let Some(chunk) = body.next().await {
+        match chunk {
+            Ok(chunk) => received.extend_from_slice(&chunk),
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
+    assert!(error.is_some(), "the body must end in a transport error");
+    let received = String::from_utf8(received).unwrap();
+    assert_eq!(parse_events(&received).len(), 3);
+    assert!(!received.contains("[DONE]"));
+}
+
+#[actix_rt::test]
+async fn streams_inside_the_deadline_are_untouched() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        chunks: 4,
+        max_duration_ms: Some(5_000),
+        on_timeout: TimeoutMode::Hard,
+        ..ResponseProfile::default()
+    }));
+    let events = parse_events(&post(&base, request()).await.text().await.unwrap());
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[4].data, "[DONE]");
+}
@@ -0,0 +1,71 @@
+//! `/v1/internal/tokenize` and `/v1/internal/detokenize`.
+#![cfg(feature = "tokenizer")]
+
+mod common;
+
+use common::{client, start, unpaced_config};
+use streaming_llm_api::tokenizer::TokenEncoding;
+
+async fn call(base: &str, path: &str, body: serde_json::Value) -> (u16, serde_json::Value) {
+    let resp = client()
+        .post(format!("{}/v1/internal/{}", base, path))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}
+
+#[actix_rt::test]
+async fn text_round_trips_through_the_model_encoding() {
+    let base = start(unpaced_config());
+    let text = "Streaming tokenizers déjà vu 🚀";
+    for (model, encoding) in [("gpt-4o", TokenEncoding::O200k), ("gpt-4", TokenEncoding::Cl100k)] {
+        let (status, body) = call(&base, "tokenize", serde_json::json!({"model": model, "text": text})).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["encoding"], encoding.name());
+        let tokens: Vec<u32> = serde_json::from_value(body["tokens"].clone()).unwrap();
+        assert_eq!(tokens, encoding.encode(text));
+        assert_eq!(body["count"], tokens.len());
+
+        let (status, body) = call(&base, "detokenize", serde_json::json!({"model": model, "tokens": tokens})).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["text"], text);
+    }
+}
+
+#[actix_rt::test]
+async fn unknown_models_fall_back_and_encoding_can_be_forced() {
+    let base = start(unpaced_config());
+    let (_, body) = call(&base, "tokenize", serde_json::json!({"model": "my-finetune", "text": "hi"})).await;
+    assert_eq!(body["encoding"], "cl100k_base");
+    let (_, body) = call(&base, "tokenize", serde_json::json!({"model": "gpt-4", "encoding": "o200k_base", "text": "hi"})).await;
+    assert_eq!(body["encoding"], "o200k_base");
+}
+
+#[actix_rt::test]
+async fn messages_are_counted_as_billed() {
+    let base = start(unpaced_config());
+    let messages = serde_json::json!([
+        {"role": "system", "content": "You are terse."},
+        {"role": "user", "content": "Count me."},
+    ]);
+    let (status, body) = call(&base, "tokenize", serde_json::json!({"model": "gpt-4o", "messages": messages})).await;
+    assert_eq!(status, 200);
+    let expected = TokenEncoding::O200k.count_messages(&serde_json::from_value::<Vec<_>>(messages).unwrap());
+    assert_eq!(body["count"], expected);
+    assert!(body.get("tokens").is_none());
+}
+
+#[actix_rt::test]
+async fn bad_input_gets_openai_errors() {
+    let base = start(unpaced_config());
+    let (status, body) = call(&base, "tokenize", serde_json::json!({"model": "gpt-4o"})).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["param"], "text");
+
+    let (status, body) = call(&base, "detokenize", serde_json::json!({"tokens": [1, 4_000_000_000u32]})).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["param"], "tokens");
+    assert!(body["error"]["message"].as_str().unwrap().contains("4000000000"));
+}
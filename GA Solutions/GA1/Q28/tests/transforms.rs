@@ -0,0 +1,94 @@
+//! Per-rule transforms applied to outgoing deltas.
+
+mod common;
+
+use common::{content_of, parse_events, post, start, with_profile};
+use streaming_llm_api::stubs::ResponseProfile;
+use streaming_llm_api::transforms::{Pipeline, Transform};
+
+fn transforms(toml: &str) -> Vec<Transform> {
+    #[derive(serde::Deserialize)]
+    struct Wrapper {
+        transforms: Vec<Transform>,
+    }
+    toml::from_str::<Wrapper>(toml).unwrap().transforms
+}
+
+fn profile(transforms: Vec<Transform>) -> ResponseProfile {
+    ResponseProfile {
+        chunk_delay_ms: 0,
+        transforms,
+        ..ResponseProfile::default()
+    }
+}
+
+async fn reply(transforms: Vec<Transform>, prompt: &str, seed: u64) -> String {
+    let base = start(with_profile(profile(transforms)));
+    let body = post(&base, serde_json::json!({"prompt": prompt, "stream": true, "seed": seed}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    content_of(&parse_events(&body))
+}
+
+#[test]
+fn encoding_transforms_rewrite_non_ascii() {
+    let mut mojibake = Pipeline::new(&transforms(r#"transforms = [{ kind = "mojibake" }]"#), 0);
+    assert_eq!(mojibake.apply("café".to_string()), "cafÃ©");
+
+    let mut lossy = Pipeline::new(&transforms(r#"transforms = [{ kind = "replacement_char" }]"#), 0);
+    assert_eq!(lossy.apply("naïve ☕".to_string()), "na\u{fffd}ve \u{fffd}");
+}
+
+#[test]
+fn typos_only_permute_letters() {
+    let mut pipeline = Pipeline::new(&transforms(r#"transforms = [{ kind = "typos", rate = 0.5 }]"#), 7);
+    let input = "The quick brown fox jumps over the lazy dog.";
+    let output = pipeline.apply(input.to_string());
+    assert_ne!(output, input);
+    let sorted = |s: &str| {
+        let mut chars: Vec<char> = s.chars().collect();
+        chars.sort_unstable();
+        chars
+    };
+    assert_eq!(sorted(&output), sorted(input));
+    assert_eq!(output.split(' ').count(), input.split(' ').count());
+}
+
+#[test]
+fn unknown_kinds_and_fields_are_rejected() {
+    #[derive(serde::Deserialize)]
+    struct Wrapper {
+        #[allow(dead_code)]
+        transforms: Vec<Transform>,
+    }
+    assert!(toml::from_str::<Wrapper>(r#"transforms = [{ kind = "shout" }]"#).is_err());
+    assert!(toml::from_str::<Wrapper>(r#"transforms = [{ kind = "typos", rate = 0.1, extra = 1 }]"#).is_err());
+    assert!(toml::from_str::<Wrapper>(r#"transforms = [{ kind = "redact", pattern = "(" }]"#).is_err());
+}
+
+#[actix_rt::test]
+async fn redaction_and_uppercase_apply_in_order() {
+    let transforms = transforms(
+        r#"transforms = [
+            { kind = "redact", pattern = "secret-[0-9]+" },
+            { kind = "uppercase" },
+        ]"#,
+    );
+    let content = reply(transforms, "my key is secret-1234", 0).await;
+    assert!(content.contains("[REDACTED]"), "{}", content);
+    assert!(!content.contains("1234"));
+    assert_eq!(content, content.to_uppercase());
+}
+
+#[actix_rt::test]
+async fn corruption_is_reproducible_per_seed() {
+    let typos = || transforms(r#"transforms = [{ kind = "typos", rate = 0.2 }]"#);
+    let clean = reply(Vec::new(), "typos", 3).await;
+    let first = reply(typos(), "typos", 3).await;
+    let again = reply(typos(), "typos", 3).await;
+    assert_ne!(first, clean);
+    assert_eq!(first, again);
+    assert_eq!(first.chars().count(), clean.chars().count());
+}
@@ -0,0 +1,78 @@
+//! `[request] unknown_fields`: rejecting, logging or ignoring fields the
+//! OpenAI API does not define.
+
+mod common;
+
+use common::{post, start, unpaced_config};
+use serde_json::{json, Value};
+use streaming_llm_api::chat::{IncomingRequest, NormalizedRequest};
+use streaming_llm_api::config::{Config, UnknownFieldPolicy};
+
+fn with_policy(policy: UnknownFieldPolicy) -> Config {
+    let mut config = unpaced_config();
+    config.request.unknown_fields = policy;
+    config
+}
+
+fn chat(extra: Value) -> Value {
+    let mut body = json!({"messages": [{"role": "user", "content": "fields"}], "stream": true});
+    body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+    body
+}
+
+#[actix_rt::test]
+async fn strict_rejects_unknown_fields_as_openai_does() {
+    let base = start(with_policy(UnknownFieldPolicy::Strict));
+    let response = post(&base, chat(json!({"temprature": 0.2}))).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["message"], "Unrecognized request argument supplied: temprature");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert!(body["error"]["param"].is_null());
+
+    // Sent as text, since `Value` sorts its keys.
+    let raw = r#"{"messages": [{"role": "user", "content": "fields"}], "stream": true, "foo": 1, "bar": {"nested": true}}"#;
+    let response = common::client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("content-type", "application/json")
+        .body(raw)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["message"], "Unrecognized request arguments supplied: foo, bar");
+}
+
+#[actix_rt::test]
+async fn documented_and_modelled_fields_pass_strict_mode() {
+    let base = start(with_policy(UnknownFieldPolicy::Strict));
+    let extra = json!({
+        "temperature": 0.2,
+        "top_p": 0.9,
+        "stop": ["\n"],
+        "stream_options": {"include_usage": true},
+        "user": "u-1",
+        "max_tokens": 64,
+        "min_tokens": 1,
+    });
+    assert_eq!(post(&base, chat(extra)).await.status(), 200);
+}
+
+#[actix_rt::test]
+async fn warn_and_ignore_serve_the_request() {
+    for policy in [UnknownFieldPolicy::Warn, UnknownFieldPolicy::Ignore] {
+        let base = start(with_policy(policy));
+        assert_eq!(post(&base, chat(json!({"temprature": 0.2}))).await.status(), 200, "{:?}", policy);
+    }
+    let config: Config = toml::from_str("[request]\nunknown_fields = \"warn\"").unwrap();
+    assert_eq!(config.request.unknown_fields, UnknownFieldPolicy::Warn);
+    assert_eq!(Config::default().request.unknown_fields, UnknownFieldPolicy::Ignore);
+}
+
+#[test]
+fn unknown_keys_are_collected_in_order() {
+    let body = r#"{"zeta": null, "messages": [{"role": "user", "content": "fields"}], "temperature": 1, "alpha": [1, 2]}"#;
+    let req = NormalizedRequest::from(serde_json::from_str::<IncomingRequest>(body).unwrap());
+    assert_eq!(req.unknown_fields, ["zeta", "alpha"]);
+    assert_eq!(req.prompt, "fields");
+}
@@ -0,0 +1,96 @@
+//! Watermarks naming the server and stub rule in replies, and `detect`.
+
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::{content_of, parse_events, post, start, unpaced_config};
+use streaming_llm_api::config::{Config, WatermarkMode};
+use streaming_llm_api::watermark::{detect, Watermark};
+
+fn marked(mode: WatermarkMode) -> Config {
+    let mut config = unpaced_config();
+    config.watermark.mode = mode;
+    config.watermark.instance = Some("staging-7".to_string());
+    config
+}
+
+async fn reply(config: Config) -> (Vec<serde_json::Value>, String) {
+    let base = start(config);
+    let body = post(&base, serde_json::json!({"messages": [{"role": "user", "content": "mark me"}], "stream": true}))
+        .await
+        .text()
+        .await
+        .unwrap();
+    let events = parse_events(&body);
+    let chunks = events
+        .iter()
+        .filter(|e| e.data != "[DONE]")
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect();
+    (chunks, content_of(&events))
+}
+
+fn staging(rule: Option<&str>) -> Watermark {
+    Watermark {
+        instance: "staging-7".to_string(),
+        rule: rule.map(str::to_string),
+    }
+}
+
+#[actix_rt::test]
+async fn zero_width_marks_are_hidden_in_the_text() {
+    let (chunks, content) = reply(marked(WatermarkMode::ZeroWidth)).await;
+    assert_eq!(detect(&content), [staging(Some("test"))]);
+    let visible: String = content.chars().filter(|c| !matches!(c, '\u{200B}' | '\u{200C}' | '\u{2060}')).collect();
+    let (_, plain) = reply(unpaced_config()).await;
+    assert_eq!(visible, plain);
+    assert!(chunks.iter().all(|c| c.get("x_mock").is_none()));
+}
+
+#[actix_rt::test]
+async fn extension_marks_ride_on_the_first_chunk() {
+    let (chunks, content) = reply(marked(WatermarkMode::Extension)).await;
+    assert!(detect(&content).is_empty());
+    assert_eq!(chunks[0]["x_mock"]["watermark"], serde_json::json!({"instance": "staging-7", "rule": "test"}));
+    assert!(chunks[1..].iter().all(|c| c.get("x_mock").is_none()));
+
+    let (chunks, content) = reply(marked(WatermarkMode::Both)).await;
+    assert_eq!(detect(&content).len(), 1);
+    assert_eq!(chunks[0]["x_mock"]["watermark"]["instance"], "staging-7");
+}
+
+#[actix_rt::test]
+async fn replies_are_unmarked_by_default() {
+    let (chunks, content) = reply(unpaced_config()).await;
+    assert!(detect(&content).is_empty());
+    assert!(chunks[0].get("x_mock").is_none());
+}
+
+#[test]
+fn markers_survive_being_embedded_in_other_text() {
+    let marks = [staging(None), staging(Some("refunds/ü")), Watermark { instance: "b".to_string(), rule: None }];
+    let text: String = marks.iter().map(|m| format!("some text {} more\u{2060}", m.hidden())).collect();
+    assert_eq!(detect(&text), marks);
+    assert!(detect("\u{2060}\u{200B}\u{2060} plain \u{2060}").is_empty());
+}
+
+#[test]
+fn the_cli_names_the_producer() {
+    let leaked = format!("a leaked row {}\n", staging(Some("refunds")).hidden());
+    let run = |input: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_stream-api"))
+            .arg("detect")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+    let out = run(&leaked);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "-: staging-7 rule refunds\n");
+    assert!(!run("nothing here").status.success());
+}
@@ -0,0 +1,165 @@
+//! Lifecycle webhooks: delivery, signing, retries and event filtering.
+#![cfg(feature = "webhooks")]
+
+mod common;
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{client, post, start, unpaced_config};
+use streaming_llm_api::config::Config;
+use streaming_llm_api::lifecycle::EventKind;
+use streaming_llm_api::webhooks::{self, WebhookConfig};
+
+#[derive(Clone)]
+struct Delivery {
+    event: serde_json::Value,
+    timestamp: Option<u64>,
+    signature: Option<String>,
+    raw: Vec<u8>,
+}
+
+#[derive(Clone, Default)]
+struct Receiver {
+    deliveries: Arc<Mutex<Vec<Delivery>>>,
+    /// Answer this many requests with a 503 before accepting.
+    failures: Arc<Mutex<usize>>,
+}
+
+impl Receiver {
+    fn start(failures: usize) -> (Receiver, String) {
+        let receiver = Receiver::default();
+        *receiver.failures.lock().unwrap() = failures;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let data = web::Data::new(receiver.clone());
+        let server = HttpServer::new(move || App::new().app_data(data.clone()).route("/hook", web::post().to(accept)))
+            .workers(1)
+            .listen(listener)
+            .unwrap()
+            .run();
+        actix_rt::spawn(server);
+        (receiver, url)
+    }
+
+    /// Waits until `n` events have arrived and returns them.
+    async fn wait_for(&self, n: usize) -> Vec<Delivery> {
+        for _ in 0..200 {
+            let got = self.deliveries.lock().unwrap().clone();
+            if got.len() >= n {
+                return got;
+            }
+            actix_rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected {} deliveries, got {}", n, self.deliveries.lock().unwrap().len());
+    }
+}
+
+async fn accept(req: HttpRequest, body: web::Bytes, receiver: web::Data<Receiver>) -> HttpResponse {
+    {
+        let mut failures = receiver.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            return HttpResponse::ServiceUnavailable().finish();
+        }
+    }
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    receiver.deliveries.lock().unwrap().push(Delivery {
+        event: serde_json::from_slice(&body).unwrap(),
+        timestamp: header("X-Mock-Timestamp").map(|t| t.parse().unwrap()),
+        signature: header("X-Mock-Signature"),
+        raw: body.to_vec(),
+    });
+    HttpResponse::NoContent().finish()
+}
+
+fn hook(url: &str) -> WebhookConfig {
+    toml::from_str(&format!("url = {:?}\nretry_backoff_ms = 10", url)).unwrap()
+}
+
+fn with_hook(hook: WebhookConfig) -> Config {
+    let mut config = unpaced_config();
+    config.webhooks = vec![hook];
+    config
+}
+
+fn request() -> serde_json::Value {
+    serde_json::json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "webhooks"}], "stream": true})
+}
+
+#[actix_rt::test]
+async fn started_and_completed_carry_usage_and_a_valid_signature() {
+    let (receiver, url) = Receiver::start(0);
+    let mut hook = hook(&url);
+    hook.secret = Some("s3cret".to_string());
+    let base = start(with_hook(hook));
+
+    let resp = post(&base, request()).await;
+    let request_id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    resp.text().await.unwrap();
+
+    let deliveries = receiver.wait_for(2).await;
+    let types: Vec<_> = deliveries.iter().map(|d| d.event["type"].as_str().unwrap()).collect();
+    assert_eq!(types, ["stream.started", "stream.completed"]);
+    for d in &deliveries {
+        assert_eq!(d.event["request_id"], request_id.as_str());
+        assert_eq!(d.event["model"], "gpt-4o");
+        let expected = webhooks::signature("s3cret", d.timestamp.unwrap(), &d.raw);
+        assert_eq!(d.signature.as_deref(), Some(expected.as_str()));
+    }
+    let usage = &deliveries[1].event["usage"];
+    assert!(usage["prompt_tokens"].as_u64().unwrap() > 0);
+    assert!(usage["completion_tokens"].as_u64().unwrap() > 100);
+    assert_eq!(
+        usage["total_tokens"].as_u64(),
+        Some(usage["prompt_tokens"].as_u64().unwrap() + usage["completion_tokens"].as_u64().unwrap())
+    );
+    assert_eq!(deliveries[0].event["usage"]["completion_tokens"], 0);
+}
+
+#[actix_rt::test]
+async fn failed_deliveries_are_retried() {
+    let (receiver, url) = Receiver::start(2);
+    let mut hook = hook(&url);
+    hook.events = vec![EventKind::Completed];
+    let base = start(with_hook(hook));
+
+    post(&base, request()).await.text().await.unwrap();
+    let deliveries = receiver.wait_for(1).await;
+    assert_eq!(deliveries[0].event["type"], "stream.completed");
+    assert!(deliveries[0].signature.is_none());
+}
+
+#[actix_rt::test]
+async fn injected_errors_and_disconnects_are_reported() {
+    let (receiver, url) = Receiver::start(0);
+    let mut hook = hook(&url);
+    hook.events = vec![EventKind::Error];
+    let mut config = with_hook(hook);
+    config.stubs[0].profile.error_after = Some(2);
+    config.stubs[0].profile.error_message = "boom".to_string();
+    let mut slow = config.stubs[0].clone();
+    slow.matcher.contains = Some("slow".to_string());
+    slow.profile.error_after = None;
+    slow.profile.chunk_delay_ms = 200;
+    config.stubs.insert(0, slow);
+    let base = start(config);
+
+    post(&base, request()).await.text().await.unwrap();
+    let first = receiver.wait_for(1).await;
+    assert_eq!(first[0].event["error"], "boom");
+
+    let mut resp = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "slow"}], "stream": true}))
+        .send()
+        .await
+        .unwrap();
+    resp.chunk().await.unwrap();
+    drop(resp);
+    let both = receiver.wait_for(2).await;
+    assert_eq!(both[1].event["type"], "stream.error");
+    assert_eq!(both[1].event["error"], "client disconnected");
+}
@@ -0,0 +1,447 @@
+//! The `wire` types: provider JSON, and everything the mock sends, parses
+//! into them and serializes back field for field.
+
+mod common;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use common::{client, parse_events, post, start, unpaced_config, with_profile};
+use streaming_llm_api::error::ErrorStyle;
+use streaming_llm_api::stubs::{ContentMode, Quirk, ResponseProfile};
+use streaming_llm_api::wire::{anthropic, ollama, openai};
+
+/// `value` parsed as `T`, after checking `T` writes it back unchanged.
+fn round_trip<T: Serialize + DeserializeOwned>(value: &Value) -> T {
+    let typed: T = serde_json::from_value(value.clone()).unwrap_or_else(|e| panic!("{}: {}", e, value));
+    assert_eq!(serde_json::to_value(&typed).unwrap(), *value);
+    typed
+}
+
+/// The JSON of every unnamed event before `[DONE]`.
+async fn chunks(base: &str, body: Value) -> Vec<Value> {
+    let response = post(base, body).await;
+    parse_events(&response.text().await.unwrap())
+        .into_iter()
+        .filter(|e| e.event.is_none() && e.data != "[DONE]")
+        .map(|e| serde_json::from_str(&e.data).unwrap())
+        .collect()
+}
+
+#[cfg(feature = "endpoints")]
+async fn get(base: &str, path: &str) -> Value {
+    client().get(format!("{}{}", base, path)).send().await.unwrap().json().await.unwrap()
+}
+
+fn chat(prompt: &str) -> Value {
+    json!({"model": "gpt-4o", "messages": [{"role": "user", "content": prompt}], "stream": true})
+}
+
+#[test]
+fn openai_requests_round_trip() {
+    let request = json!({
+        "model": "gpt-4o",
+        "messages": [
+            {"role": "system", "content": "Be brief."},
+            {"role": "user", "name": "ada", "content": [
+                {"type": "text", "text": "What is this?"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/a.png", "detail": "low"}},
+                {"type": "input_audio", "input_audio": {"data": "AAAA", "format": "wav"}},
+            ]},
+            {"role": "assistant", "tool_calls": [
+                {"id": "call_1", "type": "function", "function": {"name": "look", "arguments": "{\"q\":1}"}},
+            ]},
+            {"role": "tool", "tool_call_id": "call_1", "content": "a cat"},
+        ],
+        "stream": true,
+        "stream_options": {"include_usage": true},
+        "seed": 7,
+        "max_completion_tokens": 64,
+        "temperature": 0.5,
+        "stop": ["\n\n"],
+        "tools": [{"type": "function", "function": {"name": "look", "parameters": {"type": "object"}}}],
+        "tool_choice": "auto",
+        "modalities": ["text", "audio"],
+        "audio": {"voice": "alloy", "format": "wav"},
+        "prediction": {"type": "content", "content": "A cat."},
+        "service_tier": "flex",
+        "store": true,
+        "metadata": {"run": "a"},
+        "response_format": {"type": "json_object"},
+        "best_of": 2,
+    });
+    let typed: openai::ChatCompletionRequest = round_trip(&request);
+    assert_eq!(typed.messages[1].name.as_deref(), Some("ada"));
+    assert!(typed.extra.contains_key("best_of"));
+    assert_eq!(typed.messages[2].tool_calls.as_ref().unwrap()[0].function.name, "look");
+
+    round_trip::<openai::LegacyRequest>(&json!({"prompt": "hello", "stream": true}));
+}
+
+#[test]
+fn openai_replies_round_trip() {
+    let chunk = json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion.chunk",
+        "created": 1_700_000_000,
+        "model": "gpt-4o-2024-08-06",
+        "system_fingerprint": "fp_1",
+        "choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{\"q\""}}]}}],
+    });
+    round_trip::<openai::ChatCompletionChunk>(&chunk);
+    let completion = json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1_700_000_000,
+        "model": "gpt-4o",
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi."}, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 9, "completion_tokens": 2, "total_tokens": 11},
+        "service_tier": "default",
+    });
+    round_trip::<openai::ChatCompletion>(&completion);
+}
+
+#[test]
+fn anthropic_messages_round_trip() {
+    let request = json!({
+        "model": "claude-sonnet-4-5",
+        "max_tokens": 1024,
+        "system": [{"type": "text", "text": "Be brief.", "cache_control": {"type": "ephemeral"}}],
+        "messages": [
+            {"role": "user", "content": "What is this?"},
+            {"role": "assistant", "content": [
+                {"type": "tool_use", "id": "toolu_1", "name": "look", "input": {"q": 1}},
+            ]},
+            {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": "a cat"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}},
+            ]},
+        ],
+        "stream": true,
+        "stop_sequences": ["\n\nHuman:"],
+        "tools": [{"name": "look", "input_schema": {"type": "object"}}],
+        "metadata": {"user_id": "ada"},
+        "top_k": 5,
+    });
+    let typed: anthropic::MessagesRequest = round_trip(&request);
+    assert!(typed.extra.contains_key("top_k"));
+    let anthropic::Content::Blocks(blocks) = &typed.messages[1].content else { panic!("{:?}", typed.messages[1]) };
+    assert_eq!(blocks[0].name.as_deref(), Some("look"));
+
+    let message = json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{"type": "text", "text": "A cat."}],
+        "model": "claude-sonnet-4-5",
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": {"input_tokens": 12, "output_tokens": 4, "cache_read_input_tokens": 0},
+    });
+    round_trip::<anthropic::MessagesResponse>(&message);
+
+    let mut start = message.clone();
+    start["content"] = json!([]);
+    start["stop_reason"] = Value::Null;
+    let events = [
+        json!({"type": "message_start", "message": start}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "ping"}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "A cat."}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"q\""}}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "end_turn", "stop_sequence": null}, "usage": {"output_tokens": 4}}),
+        json!({"type": "message_stop"}),
+        json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}),
+    ];
+    let events: Vec<anthropic::StreamEvent> = events.iter().map(round_trip).collect();
+    assert!(matches!(events[6], anthropic::StreamEvent::MessageDelta { .. }));
+}
+
+#[test]
+fn ollama_chats_round_trip() {
+    let request = json!({
+        "model": "llama3.2",
+        "messages": [
+            {"role": "system", "content": "Be brief."},
+            {"role": "user", "content": "What is this?", "images": ["AAAA"]},
+            {"role": "assistant", "content": "", "tool_calls": [{"function": {"name": "look", "arguments": {"q": 1}}}]},
+            {"role": "tool", "content": "a cat", "tool_name": "look"},
+        ],
+        "stream": false,
+        "format": "json",
+        "options": {"temperature": 0.5, "seed": 7, "num_predict": 64},
+        "keep_alive": "5m",
+        "think": false,
+    });
+    let typed: ollama::ChatRequest = round_trip(&request);
+    assert!(typed.extra.contains_key("think"));
+    assert_eq!(typed.messages[2].tool_calls.as_ref().unwrap()[0].function.arguments, json!({"q": 1}));
+
+    let chunk = json!({
+        "model": "llama3.2",
+        "created_at": "2025-01-01T00:00:00.000000Z",
+        "message": {"role": "assistant", "content": "A"},
+        "done": false,
+    });
+    round_trip::<ollama::ChatResponse>(&chunk);
+    let last = json!({
+        "model": "llama3.2",
+        "created_at": "2025-01-01T00:00:01.000000Z",
+        "message": {"role": "assistant", "content": ""},
+        "done": true,
+        "done_reason": "stop",
+        "total_duration": 4_883_583_458u64,
+        "load_duration": 1_334_875,
+        "prompt_eval_count": 26,
+        "prompt_eval_duration": 342_546_000,
+        "eval_count": 282,
+        "eval_duration": 4_535_599_000u64,
+    });
+    let last: ollama::ChatResponse = round_trip(&last);
+    assert_eq!(last.eval_count, Some(282));
+    round_trip::<ollama::Error>(&json!({"error": "model \"nope\" not found, try pulling it first"}));
+}
+
+#[actix_rt::test]
+async fn streamed_chunks_round_trip() {
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        citations: true,
+        quirks: vec![Quirk::ContentAfterToolCalls],
+        content_mode: Some(ContentMode::Both),
+        ..ResponseProfile::default()
+    }));
+    let mut body = chat("hello");
+    body["stream_options"] = json!({"include_usage": true});
+    body["service_tier"] = json!("priority");
+    let chunks = chunks(&base, body).await;
+    let typed: Vec<openai::ChatCompletionChunk> = chunks.iter().map(round_trip).collect();
+    assert!(typed.iter().any(|c| c.choices.iter().any(|ch| ch.delta.tool_calls.is_some())));
+    assert!(typed.iter().any(|c| c.choices.iter().any(|ch| ch.delta.annotations.is_some())));
+    assert!(typed.iter().any(|c| c.choices.iter().any(|ch| ch.delta.text.is_some())));
+    let last = typed.last().unwrap();
+    assert!(last.usage.is_some());
+    assert_eq!(last.service_tier.as_deref(), Some("priority"));
+}
+
+#[actix_rt::test]
+async fn spoken_replies_round_trip() {
+    let base = start(unpaced_config());
+    let mut body = chat("hello");
+    body["modalities"] = json!(["text", "audio"]);
+    body["audio"] = json!({"voice": "alloy", "format": "pcm16"});
+    let typed: Vec<openai::ChatCompletionChunk> = chunks(&base, body.clone()).await.iter().map(round_trip).collect();
+    assert!(typed.iter().any(|c| c.choices.iter().any(|ch| ch.delta.audio.is_some())));
+
+    body.as_object_mut().unwrap().remove("stream");
+    body["audio"]["format"] = json!("wav");
+    let reply: Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let completion: openai::ChatCompletion = round_trip(&reply);
+    assert!(completion.choices[0].message.audio.is_some());
+    assert!(completion.choices[0].message.content.is_none());
+}
+
+#[cfg(feature = "endpoints")]
+#[actix_rt::test]
+async fn json_and_stored_replies_round_trip() {
+    let base = start(unpaced_config());
+    let mut body = chat("hello");
+    body.as_object_mut().unwrap().remove("stream");
+    body["store"] = json!(true);
+    body["metadata"] = json!({"run": "a"});
+    body["messages"][0]["name"] = json!("ada");
+    let reply: Value = client()
+        .post(format!("{}/v1/chat/completions", base))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let completion: openai::ChatCompletion = round_trip(&reply);
+
+    let list: openai::List<openai::ChatCompletion> = round_trip(&get(&base, "/v1/chat/completions").await);
+    assert_eq!(list.data[0].metadata.as_ref().unwrap()["run"], "a");
+    round_trip::<openai::ChatCompletion>(&get(&base, &format!("/v1/chat/completions/{}", completion.id)).await);
+    let messages = get(&base, &format!("/v1/chat/completions/{}/messages", completion.id)).await;
+    round_trip::<openai::List<openai::StoredMessage>>(&messages);
+}
+
+#[actix_rt::test]
+async fn error_bodies_round_trip() {
+    let base = start(unpaced_config());
+    let response = post(&base, json!({"messages": []})).await;
+    assert_eq!(response.status(), 400);
+    round_trip::<openai::ErrorEnvelope>(&response.json().await.unwrap());
+
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(1),
+        error_message: "boom".to_string(),
+        ..ResponseProfile::default()
+    }));
+    let error: openai::StreamError = round_trip(chunks(&base, chat("hello")).await.last().unwrap());
+    assert_eq!(error.error, "boom");
+
+    let base = start(with_profile(ResponseProfile {
+        chunk_delay_ms: 0,
+        error_after: Some(1),
+        error_code: 529,
+        error_style: ErrorStyle::Anthropic,
+        ..ResponseProfile::default()
+    }));
+    let body = post(&base, chat("hello")).await.text().await.unwrap();
+    let event = parse_events(&body).pop().unwrap();
+    assert_eq!(event.event.as_deref(), Some("error"));
+    let error: anthropic::ErrorEnvelope = round_trip(&serde_json::from_str(&event.data).unwrap());
+    assert_eq!(error.error.kind, "overloaded_error");
+}
+
+#[cfg(feature = "endpoints")]
+mod endpoints {
+    use super::*;
+    use streaming_llm_api::config::RealtimeConfig;
+    use streaming_llm_api::realtime::Session;
+    use streaming_llm_api::wire::{cohere, gemini, realtime, responses};
+
+    #[actix_rt::test]
+    async fn responses_round_trip() {
+        let request = json!({
+            "model": "gpt-4o",
+            "instructions": "Be brief.",
+            "input": [
+                {"role": "user", "content": "first"},
+                {"type": "message", "role": "user", "content": [{"type": "input_text", "text": "second"}]},
+                {"type": "function_call_output", "call_id": "call_1", "output": "{}"},
+            ],
+            "stream": true,
+            "max_output_tokens": 64,
+        });
+        round_trip::<responses::ResponsesRequest>(&request);
+
+        let base = start(unpaced_config());
+        let response = client().post(format!("{}/v1/responses", base)).json(&request).send().await.unwrap();
+        let events: Vec<responses::SequencedEvent> = parse_events(&response.text().await.unwrap())
+            .iter()
+            .map(|e| round_trip(&serde_json::from_str(&e.data).unwrap()))
+            .collect();
+        assert!(matches!(events.last().unwrap().event, responses::StreamEvent::Completed { .. }));
+
+        let whole = json!({"input": "hello"});
+        let response: Value = client().post(format!("{}/v1/responses", base)).json(&whole).send().await.unwrap().json().await.unwrap();
+        round_trip::<responses::Response>(&response);
+    }
+
+    #[actix_rt::test]
+    async fn embeddings_round_trip() {
+        let request = json!({"model": "text-embedding-3-small", "input": ["hi", "there"], "dimensions": 4, "user": "u1"});
+        round_trip::<openai::EmbeddingRequest>(&request);
+
+        let base = start(unpaced_config());
+        for format in ["float", "base64"] {
+            let mut request = request.clone();
+            request["encoding_format"] = json!(format);
+            let response = client().post(format!("{}/v1/embeddings", base)).json(&request).send().await.unwrap();
+            let list: openai::EmbeddingList = round_trip(&response.json().await.unwrap());
+            assert_eq!(list.data.len(), 2);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn gemini_round_trips() {
+        let request = json!({
+            "contents": [
+                {"role": "user", "parts": [{"text": "hi"}, {"inlineData": {"mimeType": "image/png", "data": "AAAA"}}]},
+                {"role": "model", "parts": [{"text": "hello"}]},
+                {"role": "user", "parts": [{"text": "again"}]},
+            ],
+            "systemInstruction": {"parts": [{"text": "Be brief."}]},
+            "generationConfig": {"maxOutputTokens": 64, "seed": 3, "topK": 40},
+            "safetySettings": [],
+        });
+        round_trip::<gemini::GenerateContentRequest>(&request);
+
+        let base = start(unpaced_config());
+        let url = format!("{}/v1beta/models/gemini-1.5-flash:streamGenerateContent?alt=sse", base);
+        let body = client().post(&url).json(&request).send().await.unwrap().text().await.unwrap();
+        let pieces: Vec<gemini::GenerateContentResponse> =
+            parse_events(&body).iter().map(|e| round_trip(&serde_json::from_str(&e.data).unwrap())).collect();
+        assert_eq!(pieces.last().unwrap().candidates[0].finish_reason.as_deref(), Some("STOP"));
+
+        let response = client().post(&url).json(&json!({"contents": []})).send().await.unwrap();
+        assert_eq!(response.status(), 400);
+        round_trip::<gemini::ErrorEnvelope>(&response.json().await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn cohere_round_trips() {
+        let request = json!({
+            "message": "again",
+            "chat_history": [{"role": "USER", "message": "hi"}, {"role": "CHATBOT", "message": "hello"}],
+            "preamble": "Be brief.",
+            "stream": true,
+        });
+        round_trip::<cohere::ChatRequest>(&request);
+
+        let base = start(unpaced_config());
+        let body = client().post(format!("{}/v1/chat", base)).json(&request).send().await.unwrap().text().await.unwrap();
+        let events: Vec<cohere::StreamEvent> = body.lines().map(|line| round_trip(&serde_json::from_str(line).unwrap())).collect();
+        assert!(matches!(events.last().unwrap(), cohere::StreamEvent::StreamEnd { .. }));
+
+        let response = client().post(format!("{}/v1/chat", base)).json(&json!({"message": " "})).send().await.unwrap();
+        assert!(response.status().is_client_error());
+        round_trip::<cohere::Error>(&response.json().await.unwrap());
+    }
+
+    #[test]
+    fn realtime_events_round_trip() {
+        let mut session = Session::new(&RealtimeConfig {
+            ms_per_word: 100,
+            delta_interval_ms: 10,
+        });
+        let client_events = [
+            json!({"type": "transcription_session.update", "session": {"input_audio_format": "pcm16"}}),
+            json!({"event_id": "mine", "type": "input_audio_buffer.commit"}),
+            json!({"type": "input_audio_buffer.append", "audio": "A".repeat(24_000)}),
+            json!({"type": "input_audio_buffer.commit"}),
+            json!({"type": "input_audio_buffer.clear"}),
+        ];
+        for event in &client_events {
+            round_trip::<realtime::ClientEvent>(event);
+            session.receive(&event.to_string());
+        }
+        let server_events: Vec<realtime::ServerEvent> =
+            session.outgoing.iter().map(|e| round_trip(&serde_json::from_str(e).unwrap())).collect();
+        let kinds: Vec<String> = session
+            .outgoing
+            .iter()
+            .map(|e| serde_json::from_str::<Value>(e).unwrap()["type"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "transcription_session.created",
+                "transcription_session.updated",
+                "error",
+                "conversation.item.input_audio_transcription.delta",
+                "input_audio_buffer.committed",
+                "conversation.item.input_audio_transcription.completed",
+                "input_audio_buffer.cleared",
+            ]
+        );
+        assert!(matches!(&server_events[2].kind, realtime::ServerEventKind::Error { error } if error.event_id.as_deref() == Some("mine")));
+    }
+}